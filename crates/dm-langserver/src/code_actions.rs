@@ -0,0 +1,789 @@
+//! Quick fixes for diagnostics.
+
+use std::ops::Range;
+
+use crate::document;
+
+/// The proc and type named by an "undefined proc" diagnostic.
+pub fn undefined_proc(message: &str) -> Option<(&str, &str)> {
+    let rest = message.strip_prefix("undefined proc: \"")?;
+    rest.split_once("\" on ")
+}
+
+/// The var named by an "unused var" diagnostic.
+pub fn unused_var(message: &str) -> Option<&str> {
+    message.strip_prefix("unused var: \"")?.strip_suffix('"')
+}
+
+/// The name, operator, and suggested type of a `:` access diagnostic which
+/// suggests a typed rewrite.
+pub fn colon_access(message: &str) -> Option<(&str, &str, Option<&str>)> {
+    let (name, rest) = message.strip_prefix('"')?.split_once("\" accessed with \"")?;
+    let (op, rest) = rest.split_once('"')?;
+    let (_, rest) = rest.strip_prefix("; use \"")?.split_once('"')?;
+    if rest == " instead" {
+        return Some((name, op, None));
+    }
+    let ty = rest.strip_prefix(" on a ")?.strip_suffix(" instead")?;
+    Some((name, op, Some(ty)))
+}
+
+/// Rewrite a `:` access at the given byte offset to use `.`, and if a type
+/// is given, change the declared type of the var it is used on. Accesses
+/// which come from a macro, and vars not declared in the same proc, can't be
+/// rewritten.
+pub fn colon_rewrite(text: &str, offset: usize, name: &str, op: &str, ty: Option<&str>) -> Option<Vec<(Range<usize>, String)>> {
+    let after = text.get(offset..)?.strip_prefix(op)?.strip_prefix(name)?;
+    if after.starts_with(|ch: char| ch == '_' || ch.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let mut edits = vec![(offset..offset + op.len(), op.replace(':', "."))];
+    let Some(ty) = ty else {
+        return Some(edits);
+    };
+
+    let lines = Lines::new(text);
+    let line = lines.starts.iter().rposition(|&start| start <= offset)?;
+    let before = &text[lines.starts[line]..offset];
+    let var = &before[before.rfind(|ch: char| ch != '_' && !ch.is_ascii_alphanumeric()).map_or(0, |idx| idx + 1)..];
+    if !document::is_valid_ident(var) || before[..before.len() - var.len()].ends_with(['.', ':', '/']) {
+        return None;
+    }
+    let ty = ty.trim_start_matches('/');
+    for idx in (0..line).rev() {
+        let text = lines.text[idx];
+        let trimmed = text.trim_start();
+        let start = lines.starts[idx] + text.len() - trimmed.len();
+        if indent_width(text) == 0 {
+            // the var may be a parameter of the proc
+            let open = trimmed.find('(')?;
+            let mut param_start = open + 1;
+            let mut depth = 0;
+            for (pos, ch) in trimmed.char_indices().skip(open + 1) {
+                match ch {
+                    '(' => depth += 1,
+                    ')' if depth > 0 => depth -= 1,
+                    ',' | ')' if depth == 0 => {
+                        let param = &trimmed[param_start..pos];
+                        let param_start = param_start + param.len() - param.trim_start().len();
+                        let path = split_path(param.trim_start()).0;
+                        if path.rsplit('/').next() == Some(var) {
+                            let range = start + param_start..start + param_start + path.len();
+                            edits.push((range, format!("{}/{}", ty, var)));
+                            return Some(edits);
+                        }
+                        if ch == ')' {
+                            return None;
+                        }
+                    },
+                    _ => {},
+                }
+                if ch == ',' && depth == 0 {
+                    param_start = pos + 1;
+                }
+            }
+            return None;
+        }
+        let path = split_path(trimmed).0;
+        let Some(rest) = path.strip_prefix("var/") else {
+            continue;
+        };
+        let mut segments: Vec<&str> = rest.split('/').collect();
+        if segments.pop() != Some(var) {
+            continue;
+        }
+        let modifiers: Vec<&str> = segments.into_iter()
+            .take_while(|segment| matches!(*segment, "global" | "static" | "const" | "tmp" | "final"))
+            .collect();
+        let mut declaration = String::from("var/");
+        for modifier in modifiers {
+            declaration.push_str(modifier);
+            declaration.push('/');
+        }
+        edits.push((start..start + path.len(), format!("{}{}/{}", declaration, ty, var)));
+        return Some(edits);
+    }
+    None
+}
+
+/// The operator and side named by an ambiguous `in` diagnostic.
+pub fn ambiguous_in(message: &str) -> Option<(&str, &str)> {
+    let (op, rest) = message.strip_prefix("ambiguous `")?.split_once("` on ")?;
+    Some((op, rest.strip_suffix(" side of an `in`")?))
+}
+
+/// Parenthesize an ambiguous `in` expression which starts at the given byte
+/// offset, so that `!a in b` becomes `!(a in b)` and `a in b && c` becomes
+/// `(a in b) && c`. Only expressions on a single line are handled.
+pub fn in_grouping(text: &str, offset: usize, op: &str, side: &str) -> Option<Vec<(Range<usize>, String)>> {
+    let rest = text.get(offset..)?;
+    let rest = &rest[..rest.find('\n').unwrap_or(rest.len())];
+
+    // the operators and the end of the expression, outside of any brackets
+    let mut in_pos = None;
+    let mut op_pos = None;
+    let mut end = rest.len();
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = rest.char_indices().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        if let Some(close) = quote {
+            if ch == '\\' {
+                chars.next();
+            } else if ch == close {
+                quote = None;
+            }
+            continue;
+        }
+        match ch {
+            '"' | '\'' => quote = Some(ch),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth == 0 => {
+                end = pos;
+                break;
+            },
+            ')' | ']' | '}' => depth -= 1,
+            ',' | ';' if depth == 0 => {
+                end = pos;
+                break;
+            },
+            _ if depth > 0 => {},
+            'i' if in_pos.is_none()
+                && rest[pos..].starts_with("in")
+                && !rest[..pos].ends_with(|ch: char| ch == '_' || ch.is_ascii_alphanumeric())
+                && !rest[pos + 2..].starts_with(|ch: char| ch == '_' || ch.is_ascii_alphanumeric()) =>
+            {
+                in_pos = Some(pos);
+            },
+            '&' | '|' if in_pos.is_some() && op_pos.is_none() && rest[pos..].starts_with(op) => op_pos = Some(pos),
+            _ => {},
+        }
+    }
+    in_pos?;
+    if quote.is_some() || depth != 0 {
+        return None;
+    }
+
+    if side == "left" {
+        if op != "!" || !rest.starts_with('!') {
+            return None;
+        }
+        let end = offset + rest[..end].trim_end().len();
+        Some(vec![(offset + 1..offset + 1, "(".to_owned()), (end..end, ")".to_owned())])
+    } else {
+        let op_pos = op_pos?;
+        let end = offset + rest[..op_pos].trim_end().len();
+        Some(vec![(offset..offset, "(".to_owned()), (end..end, ")".to_owned())])
+    }
+}
+
+/// Whether a diagnostic reports an assignment used as a condition.
+pub fn assignment_condition(message: &str) -> bool {
+    message == "assignment used as a condition; did you mean `==`?"
+}
+
+/// Find the byte offset of the `=` of an assignment used as a condition,
+/// which is the first lone `=` on the line after the given offset.
+pub fn condition_assignment(text: &str, offset: usize) -> Option<usize> {
+    let rest = text.get(offset..)?;
+    let rest = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    let mut previous = ' ';
+    let mut quote = None;
+    let mut chars = rest.char_indices().peekable();
+    while let Some((pos, ch)) = chars.next() {
+        if let Some(close) = quote {
+            if ch == '\\' {
+                chars.next();
+            } else if ch == close {
+                quote = None;
+            }
+        } else if ch == '"' || ch == '\'' {
+            quote = Some(ch);
+        } else if ch == '=' && !"=!<>+-*/%&|^:~".contains(previous) && chars.peek().is_none_or(|&(_, next)| next != '=') {
+            return Some(offset + pos);
+        }
+        previous = ch;
+    }
+    None
+}
+
+/// The string and the typepath it names from a diagnostic about a string
+/// used where a typepath is expected.
+pub fn string_typepath(message: &str) -> Option<(&str, &str)> {
+    let (_, rest) = message.split_once(" the string \"")?;
+    let (string, rest) = rest.split_once("\"; use the path ")?;
+    Some((string, rest.strip_suffix(" instead")?))
+}
+
+/// Find the string literal a diagnostic is about on the rest of its line.
+pub fn string_literal(text: &str, offset: usize, string: &str) -> Option<Range<usize>> {
+    let rest = text.get(offset..)?;
+    let rest = &rest[..rest.find('\n').unwrap_or(rest.len())];
+    let literal = format!("\"{}\"", string);
+    let start = offset + rest.find(&literal)?;
+    Some(start..start + literal.len())
+}
+
+/// Find the line to delete to remove an unused var, if its declaration is
+/// alone on that line and its initializer has no side effects.
+pub fn removable_declaration(text: &str, line: usize, name: &str) -> Option<Range<usize>> {
+    let lines = Lines::new(text);
+    let content = lines.text.get(line)?.trim();
+    let (declaration, initializer) = content.split_once('=').unwrap_or((content, ""));
+    let path = declaration.trim_end().strip_prefix("var/")?;
+    if path.rsplit('/').next() != Some(name) || !path.split('/').all(document::is_valid_ident) {
+        return None;
+    }
+    if !is_side_effect_free(initializer) {
+        return None;
+    }
+    let end = lines.starts.get(line + 1).copied().unwrap_or(text.len());
+    Some(lines.starts[line]..end)
+}
+
+/// Whether an expression which is alone on its line makes no calls or
+/// assignments. `list()` doesn't count as a call.
+fn is_side_effect_free(expression: &str) -> bool {
+    use dm::lexer::{Lexer, Punctuation, Token};
+
+    let context = dm::Context::default();
+    let tokens = Lexer::new(&context, Default::default(), expression.as_bytes())
+        .map(|located| located.token)
+        .filter(|token| !matches!(token, Token::Punct(Punctuation::Space | Punctuation::Tab | Punctuation::Newline)));
+    let mut depth = 0;
+    let mut previous = Token::Eof;
+    for token in tokens {
+        match token {
+            Token::Punct(Punctuation::LParen) if match previous {
+                Token::Ident(ref ident, _) => ident != "list",
+                Token::Punct(Punctuation::RParen | Punctuation::RBracket) => true,
+                _ => false,
+            } => return false,
+            Token::Punct(Punctuation::LParen | Punctuation::LBracket | Punctuation::LBrace) => depth += 1,
+            Token::Punct(Punctuation::RParen | Punctuation::RBracket | Punctuation::RBrace) => depth -= 1,
+            // in a list, `=` makes an association instead
+            Token::Punct(Punctuation::Semicolon | Punctuation::Assign) if depth == 0 => return false,
+            Token::Punct(
+                Punctuation::PlusPlus
+                | Punctuation::MinusMinus
+                | Punctuation::AddAssign
+                | Punctuation::SubAssign
+                | Punctuation::MulAssign
+                | Punctuation::DivAssign
+            ) => return false,
+            Token::Ident(ref ident, _) if ident == "new" => return false,
+            _ => {}
+        }
+        previous = token;
+    }
+    depth == 0 && !expression.trim_end().ends_with('\\')
+}
+
+/// Name the parameters of a new proc after the arguments of a call to it:
+/// keyword arguments by their keyword, variables and fields by their name,
+/// and anything else by its position.
+pub fn parameter_names(args: &[&str]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(args.len());
+    for (i, arg) in args.iter().enumerate() {
+        let name = document::keyword_argument_name(arg)
+            .or_else(|| {
+                let last = arg.rsplit(['.', ':']).next()?.trim();
+                Some(last).filter(|last| document::is_valid_ident(last))
+            })
+            .filter(|name| !RESERVED.contains(name) && !names.iter().any(|existing| existing == name))
+            .map_or_else(|| format!("arg{}", i + 1), ToOwned::to_owned);
+        names.push(name);
+    }
+    names
+}
+
+/// Names which make poor parameters even when the argument is just them.
+const RESERVED: &[&str] = &["src", "usr", "world", "global", "args", "null", "new", "list"];
+
+/// Write a proc stub and find the byte offset to insert it at.
+///
+/// If `after` is the zero-based line of an existing proc's header, the stub
+/// follows that proc's body in the same style; otherwise it is appended to
+/// the end of the text using its full path.
+pub fn proc_stub(text: &str, type_path: &str, after: Option<usize>, name: &str, params: &[String]) -> (usize, String) {
+    let lines: Vec<&str> = text.lines().collect();
+    let unit = document::detect_indent(text);
+    let params = params.join(", ");
+
+    if let Some(header) = after.filter(|&line| line < lines.len()) {
+        let end = block_end(&lines, header);
+        let header_text = lines[header];
+        let indent = &header_text[..header_text.len() - header_text.trim_start().len()];
+        let head = if header_text.trim_start().starts_with('/') {
+            format!("{}/proc/{}", type_path, name)
+        } else if parent_line(&lines, header).is_some_and(|parent| matches!(parent.trim(), "proc" | "verb")) {
+            // already inside a `proc` block
+            name.to_owned()
+        } else {
+            format!("proc/{}", name)
+        };
+        let offset = line_end_offset(text, end);
+        return (offset, format!("\n\n{indent}{head}({params})\n{indent}{unit}return"));
+    }
+
+    let separator = if text.is_empty() {
+        ""
+    } else if text.ends_with('\n') {
+        "\n"
+    } else {
+        "\n\n"
+    };
+    (text.len(), format!("{separator}{type_path}/proc/{name}({params})\n{unit}return\n"))
+}
+
+/// Rewrite the top-level block around the given line so that each entry in
+/// it is declared with an absolute path, such as `/obj/item/gun/proc/fire()`.
+/// Returns the byte range to replace and what to replace it with.
+pub fn absolute_rewrite(text: &str, line: usize) -> Option<(Range<usize>, String)> {
+    let lines = Lines::new(text);
+    let header = lines.top_level_header(line)?;
+    let (path, rest) = split_path(lines.text[header]);
+    let rest = rest.trim();
+    if !rest.is_empty() && !rest.starts_with("//") {
+        return None;
+    }
+    let end = block_end(&lines.text, header);
+    let child_indent = lines.text[header + 1..=end].iter()
+        .find(|text| is_declaration(text.trim_start()))
+        .map(|text| indent_width(text))?;
+
+    let base = path.trim_matches('/');
+    let mut out = Vec::new();
+    if !rest.is_empty() {
+        out.push(rest.to_owned());
+    }
+    for text in &lines.text[header + 1..=end] {
+        let trimmed = text.trim_start();
+        let width = indent_width(text);
+        if trimmed.is_empty() {
+            out.push(String::new());
+        } else if width < child_indent {
+            // comments can be anywhere, but entries cannot
+            if is_declaration(trimmed) {
+                return None;
+            }
+            out.push(trimmed.to_owned());
+        } else if width == child_indent && is_declaration(trimmed) {
+            // a leading slash in a block is still relative to it
+            let (head, rest) = split_path(trimmed);
+            out.push(format!("/{}/{}{}", base, head.trim_start_matches('/'), rest));
+        } else {
+            out.push(text[child_indent..].to_owned());
+        }
+    }
+    Some((lines.span(header, end), out.join(lines.newline)))
+}
+
+/// Rewrite the run of top-level declarations around the given line which are
+/// on the same type into one indented block under that type. Returns the
+/// type's path, the byte range to replace, and what to replace it with.
+pub fn block_rewrite(text: &str, line: usize) -> Option<(String, Range<usize>, String)> {
+    let lines = Lines::new(text);
+    let header = lines.top_level_header(line)?;
+    let type_path = format!("/{}", declared_type(split_path(lines.text[header]).0)?);
+    let prefix = format!("{}/", type_path);
+
+    let entries = lines.top_level_entries();
+    let belongs = |(header, _): (usize, usize)| {
+        let (path, rest) = split_path(lines.text[header]);
+        format!("/{}", path.trim_matches('/')).starts_with(&prefix) && !rest.trim_start().starts_with('{')
+    };
+    // only blank lines and comments may come between entries in the run
+    let adjacent = |before: (usize, usize), after: (usize, usize)| {
+        lines.text[before.1 + 1..after.0].iter().all(|text| is_gap(text.trim()))
+    };
+    let idx = entries.iter().position(|&(start, _)| start == header)?;
+    let mut first = idx;
+    while first > 0 && belongs(entries[first - 1]) && adjacent(entries[first - 1], entries[first]) {
+        first -= 1;
+    }
+    let mut last = idx;
+    while last + 1 < entries.len() && belongs(entries[last + 1]) && adjacent(entries[last], entries[last + 1]) {
+        last += 1;
+    }
+
+    // take along the comments directly above the first entry
+    let mut start = entries[first].0;
+    while start > 0 && lines.text[start - 1].starts_with("//") {
+        start -= 1;
+    }
+    let end = entries[last].1;
+
+    let unit = document::detect_indent(text);
+    let mut out = vec![type_path.clone()];
+    for (idx, text) in lines.text.iter().enumerate().take(end + 1).skip(start) {
+        if text.trim().is_empty() {
+            out.push(String::new());
+        } else if entries[first..=last].iter().any(|&(header, _)| header == idx) {
+            let (path, rest) = split_path(text);
+            let relative = &format!("/{}", path.trim_matches('/'))[prefix.len()..];
+            out.push(format!("{}{}{}", unit, relative, rest));
+        } else {
+            out.push(format!("{}{}", unit, text));
+        }
+    }
+    Some((type_path, lines.span(start, end), out.join(lines.newline)))
+}
+
+/// The lines of a text, without their line endings, and where they start.
+struct Lines<'a> {
+    text: Vec<&'a str>,
+    starts: Vec<usize>,
+    newline: &'static str,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str) -> Lines<'a> {
+        let mut lines = Lines {
+            text: Vec::new(),
+            starts: Vec::new(),
+            newline: if text.contains("\r\n") { "\r\n" } else { "\n" },
+        };
+        let mut start = 0;
+        for line in text.split('\n') {
+            lines.text.push(line.trim_end_matches('\r'));
+            lines.starts.push(start);
+            start += line.len() + 1;
+        }
+        lines
+    }
+
+    /// The byte range from the start of one line to the end of another.
+    fn span(&self, first: usize, last: usize) -> Range<usize> {
+        self.starts[first]..self.starts[last] + self.text[last].len()
+    }
+
+    /// Find the header of the top-level declaration the line is part of.
+    fn top_level_header(&self, line: usize) -> Option<usize> {
+        let header = (0..=line.min(self.text.len().checked_sub(1)?)).rev()
+            .find(|&idx| indent_width(self.text[idx]) == 0 && is_declaration(self.text[idx]))?;
+        Some(header).filter(|&header| block_end(&self.text, header) >= line)
+    }
+
+    /// The header and last line of each top-level declaration.
+    fn top_level_entries(&self) -> Vec<(usize, usize)> {
+        let mut entries = Vec::new();
+        let mut idx = 0;
+        while idx < self.text.len() {
+            if indent_width(self.text[idx]) == 0 && is_declaration(self.text[idx]) {
+                let end = block_end(&self.text, idx);
+                entries.push((idx, end));
+                idx = end;
+            }
+            idx += 1;
+        }
+        entries
+    }
+}
+
+/// Split a declaration into its path and whatever follows it.
+fn split_path(line: &str) -> (&str, &str) {
+    let end = line.find(|ch: char| ch != '/' && ch != '_' && !ch.is_ascii_alphanumeric()).unwrap_or(line.len());
+    line.split_at(end)
+}
+
+fn is_declaration(line: &str) -> bool {
+    !is_gap(line) && !split_path(line).0.trim_matches('/').is_empty()
+}
+
+fn is_gap(line: &str) -> bool {
+    line.is_empty() || line.starts_with("//") || line.starts_with("/*")
+}
+
+/// Find the type a top-level declaration is on, which is everything before
+/// `proc`, `verb`, or `var`, or else its parent. The root doesn't count.
+fn declared_type(path: &str) -> Option<String> {
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let len = parts.iter()
+        .position(|part| matches!(*part, "proc" | "verb" | "var"))
+        .unwrap_or(parts.len() - 1);
+    if len == 0 {
+        return None;
+    }
+    Some(parts[..len].join("/"))
+}
+
+/// Find the last line of the block which starts at the given line, which is
+/// every following line that is indented further. Blank and comment lines
+/// are skipped, so that they stay with whatever follows them.
+fn block_end(lines: &[&str], start: usize) -> usize {
+    let indent = indent_width(lines[start]);
+    let mut end = start;
+    for (idx, line) in lines.iter().enumerate().skip(start + 1) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            continue;
+        }
+        if indent_width(line) <= indent {
+            break;
+        }
+        end = idx;
+    }
+    end
+}
+
+/// Find the nearest line before the given one which is indented less.
+fn parent_line<'a>(lines: &[&'a str], line: usize) -> Option<&'a str> {
+    let indent = indent_width(lines[line]);
+    lines[..line].iter().rev()
+        .find(|text| !text.trim().is_empty() && indent_width(text) < indent)
+        .copied()
+}
+
+fn indent_width(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Find the byte offset of the end of a zero-based line, before any `\r`.
+fn line_end_offset(text: &str, line: usize) -> usize {
+    let mut start = 0;
+    for (idx, each) in text.split('\n').enumerate() {
+        if idx == line {
+            return start + each.trim_end_matches('\r').len();
+        }
+        start += each.len() + 1;
+    }
+    text.len()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn insert(text: &str, after: Option<usize>) -> String {
+        let params = ["target".to_owned(), "arg2".to_owned()];
+        let (offset, stub) = proc_stub(text, "/obj/machinery/thing", after, "do_the_thing", &params);
+        format!("{}{}{}", &text[..offset], stub, &text[offset..])
+    }
+
+    #[test]
+    fn removes_declarations() {
+        let text = "/proc/test()\n\tvar/obj/O = null\n\tvar/list/L = list(\"a\" = 1)\n\tvar/x = f(1)\n\tvar/y = new /obj\n\tvar/z = 1; f()\n\treturn\n";
+        assert_eq!(unused_var("unused var: \"O\""), Some("O"));
+        assert_eq!(removable_declaration(text, 1, "O").map(|range| &text[range]), Some("\tvar/obj/O = null\n"));
+        assert_eq!(removable_declaration(text, 2, "L").map(|range| &text[range]), Some("\tvar/list/L = list(\"a\" = 1)\n"));
+        assert_eq!(removable_declaration(text, 3, "x"), None);
+        assert_eq!(removable_declaration(text, 4, "y"), None);
+        assert_eq!(removable_declaration(text, 5, "z"), None);
+        assert_eq!(removable_declaration(text, 1, "L"), None);
+    }
+
+    #[test]
+    fn parses_diagnostic() {
+        assert_eq!(undefined_proc("undefined proc: \"do_the_thing\" on /obj/machinery/thing"), Some(("do_the_thing", "/obj/machinery/thing")));
+        assert_eq!(undefined_proc("undefined var: \"x\" on /obj"), None);
+    }
+
+    #[test]
+    fn rewrites_colon_access() {
+        let text = "/proc/test(obj/item/I, thing)\n\tvar/static/T = thing\n\tT:use()\n\tthing?:charge = I:charge\n\tUSE(thing)\n";
+        let apply = |offset: usize, message: &str| {
+            let (name, op, ty) = colon_access(message)?;
+            let mut edits = colon_rewrite(text, offset, name, op, ty)?;
+            edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+            let mut out = text.to_owned();
+            for (range, new_text) in edits {
+                out.replace_range(range, &new_text);
+            }
+            Some(out)
+        };
+        let at = |needle: &str| text.find(needle).unwrap();
+
+        assert_eq!(colon_access("\"use\" accessed with \":\""), None);
+        assert_eq!(
+            apply(at(":use"), "\"use\" accessed with \":\"; use \".\" on a /obj/item instead").as_deref(),
+            Some("/proc/test(obj/item/I, thing)\n\tvar/static/obj/item/T = thing\n\tT.use()\n\tthing?:charge = I:charge\n\tUSE(thing)\n"),
+        );
+        assert_eq!(
+            apply(at("?:charge"), "\"charge\" accessed with \"?:\"; use \"?.\" on a /obj/item instead").as_deref(),
+            Some("/proc/test(obj/item/I, obj/item/thing)\n\tvar/static/T = thing\n\tT:use()\n\tthing?.charge = I:charge\n\tUSE(thing)\n"),
+        );
+        assert_eq!(
+            apply(at("I:charge") + 1, "\"charge\" accessed with \":\"; use \".\" instead").as_deref(),
+            Some("/proc/test(obj/item/I, thing)\n\tvar/static/T = thing\n\tT:use()\n\tthing?:charge = I.charge\n\tUSE(thing)\n"),
+        );
+        assert_eq!(apply(at("USE"), "\"use\" accessed with \":\"; use \".\" on a /obj/item instead"), None);
+    }
+
+    #[test]
+    fn groups_ambiguous_in() {
+        let apply = |text: &str, message: &str| {
+            let (op, side) = ambiguous_in(message)?;
+            let mut edits = in_grouping(text, text.find("if(")? + 3, op, side)?;
+            edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+            let mut out = text.to_owned();
+            for (range, new_text) in edits {
+                out.replace_range(range, &new_text);
+            }
+            Some(out)
+        };
+
+        assert_eq!(
+            apply("\tif(!x in list(\"a)\", y))\n", "ambiguous `!` on left side of an `in`").as_deref(),
+            Some("\tif(!(x in list(\"a)\", y)))\n"),
+        );
+        assert_eq!(
+            apply("\tif(x in L && y)\n", "ambiguous `&&` on right side of an `in`").as_deref(),
+            Some("\tif((x in L) && y)\n"),
+        );
+        assert_eq!(
+            apply("\tif(x in get(a || b) || y && z)\n", "ambiguous `||` on right side of an `in`").as_deref(),
+            Some("\tif((x in get(a || b)) || y && z)\n"),
+        );
+        assert_eq!(apply("\tif(-x in L)\n", "ambiguous `-` on left side of an `in`"), None);
+        assert_eq!(apply("\tif(index && y)\n", "ambiguous `&&` on right side of an `in`"), None);
+    }
+
+    #[test]
+    fn finds_condition_assignment() {
+        let text = "\tif(x != \"a=b\" && y <= 2 || z = next())\n\tw = 1\n";
+        assert_eq!(condition_assignment(text, 3), text.find("= next"));
+        assert_eq!(condition_assignment("\tif(x == 1)\n\tw = 1\n", 3), None);
+    }
+
+    #[test]
+    fn finds_string_typepath() {
+        let message = "istype() expects a typepath, but is given the string \"/obj/item\"; use the path /obj/item instead";
+        assert_eq!(string_typepath(message), Some(("/obj/item", "/obj/item")));
+        assert_eq!(string_typepath("ispath() expects a typepath, but is given a string"), None);
+        let text = "\tif(istype(O, \"/obj/item\"))\n\t\"/obj/item\"\n";
+        assert_eq!(string_literal(text, 3, "/obj/item"), Some(14..25));
+        assert_eq!(string_literal(text, 26, "/obj/item"), None);
+    }
+
+    #[test]
+    fn names_parameters() {
+        assert_eq!(parameter_names(&["user", "src.loc", "5", "amount = 2", "user", "src"]), [
+            "user", "loc", "arg3", "amount", "arg5", "arg6",
+        ]);
+    }
+
+    #[test]
+    fn stub_after_block_proc() {
+        let text = "/obj/machinery/thing\n\tname = \"thing\"\n\n\tproc/existing()\n\t\treturn 1\n\n\t// trailing note\n/obj/other\n";
+        assert_eq!(insert(text, Some(3)), "/obj/machinery/thing\n\tname = \"thing\"\n\n\tproc/existing()\n\t\treturn 1\n\n\tproc/do_the_thing(target, arg2)\n\t\treturn\n\n\t// trailing note\n/obj/other\n");
+    }
+
+    #[test]
+    fn stub_inside_proc_block() {
+        let text = "/obj/machinery/thing\n    proc\n        existing()\n            return 1\n";
+        assert_eq!(insert(text, Some(2)), "/obj/machinery/thing\n    proc\n        existing()\n            return 1\n\n        do_the_thing(target, arg2)\n            return\n");
+    }
+
+    #[test]
+    fn stub_after_full_path_proc() {
+        let text = "/obj/machinery/thing/existing()\n\treturn 1";
+        assert_eq!(insert(text, Some(0)), "/obj/machinery/thing/existing()\n\treturn 1\n\n/obj/machinery/thing/proc/do_the_thing(target, arg2)\n\treturn");
+    }
+
+    #[test]
+    fn stub_at_end() {
+        assert_eq!(insert("/obj/machinery/thing\n", None), "/obj/machinery/thing\n\n/obj/machinery/thing/proc/do_the_thing(target, arg2)\n\treturn\n");
+    }
+
+    const GUNS: &str = "\
+/obj/item/gun
+\tname = \"gun\" // shown in examine
+\tvar/ammo = 5
+
+\t/// Fire once.
+\tproc/fire(mob/user)
+\t\tif(ammo > 0)
+\t\t\tammo--
+\t\treturn ammo
+
+\tlaser
+\t\tname = \"laser gun\"
+\t\tfire(mob/user)
+\t\t\treturn ..()
+\tverb
+\t\tunload()
+\t\t\tammo = 0
+
+// more guns below
+";
+
+    /// What a file declares, to check that a rewrite doesn't change it.
+    pub fn declarations(code: &str) -> Vec<String> {
+        let context = dm::Context::default();
+        let lexer = dm::lexer::Lexer::new(&context, Default::default(), code.as_bytes());
+        let indent = dm::indents::IndentProcessor::new(&context, lexer);
+        let tree = dm::parser::Parser::new(&context, indent).parse_object_tree_without_builtins();
+        assert!(context.errors().is_empty(), "{:?}\n{}", context.errors(), code);
+
+        let mut found = Vec::new();
+        for ty in tree.iter_types() {
+            found.push(ty.path.to_string());
+            for (name, var) in ty.vars.iter() {
+                found.push(format!("{} var {} {} {:?}", ty.path, name, var.declaration.is_some(), var.value.constant));
+            }
+            for (name, proc) in ty.procs.iter() {
+                let kind = proc.declaration.as_ref().map(|decl| decl.kind.name());
+                for value in proc.value.iter() {
+                    let params: Vec<_> = value.parameters.iter().map(|param| param.name.as_str()).collect();
+                    let code = value.code.as_ref().map(|code| code.len());
+                    found.push(format!("{} proc {} {:?} {:?} {:?} {:?}", ty.path, name, kind, params, code, value.docs.text()));
+                }
+            }
+        }
+        found.sort();
+        found
+    }
+
+    fn apply(text: &str, range: Range<usize>, replacement: &str) -> String {
+        format!("{}{}{}", &text[..range.start], replacement, &text[range.end..])
+    }
+
+    #[test]
+    fn absolute_round_trip() {
+        let (range, replacement) = absolute_rewrite(GUNS, 5).unwrap();
+        let absolute = apply(GUNS, range, &replacement);
+        assert_eq!(absolute, "\
+/obj/item/gun/name = \"gun\" // shown in examine
+/obj/item/gun/var/ammo = 5
+
+/// Fire once.
+/obj/item/gun/proc/fire(mob/user)
+\tif(ammo > 0)
+\t\tammo--
+\treturn ammo
+
+/obj/item/gun/laser
+\tname = \"laser gun\"
+\tfire(mob/user)
+\t\treturn ..()
+/obj/item/gun/verb
+\tunload()
+\t\tammo = 0
+
+// more guns below
+");
+        assert_eq!(declarations(&absolute), declarations(GUNS));
+
+        // and back again, from any of the entries
+        let (type_path, range, replacement) = block_rewrite(&absolute, 12).unwrap();
+        assert_eq!(type_path, "/obj/item/gun");
+        let block = apply(&absolute, range, &replacement);
+        assert_eq!(block, GUNS);
+        assert_eq!(declarations(&block), declarations(GUNS));
+    }
+
+    #[test]
+    fn block_rewrite_stops_at_other_types() {
+        let text = "/obj/item/proc/a()\n\treturn\n/obj/item/gun/proc/fire()\n\treturn\n// reload\n/obj/item/gun/var/ammo\n/obj/structure/var/x\n";
+        let (type_path, range, replacement) = block_rewrite(text, 3).unwrap();
+        assert_eq!(type_path, "/obj/item/gun");
+        let block = apply(text, range, &replacement);
+        assert_eq!(block, "/obj/item/proc/a()\n\treturn\n/obj/item/gun\n\tproc/fire()\n\t\treturn\n\t// reload\n\tvar/ammo\n/obj/structure/var/x\n");
+        assert_eq!(declarations(&block), declarations(text));
+
+        // nothing to do outside of declarations or for global procs
+        assert!(block_rewrite("/proc/global_thing()\n\treturn\n", 0).is_none());
+        assert!(absolute_rewrite(text, 0).is_none());
+    }
+}
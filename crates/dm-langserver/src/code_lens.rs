@@ -0,0 +1,99 @@
+//! Code lenses showing reference, override, and subtype counts.
+
+use lsp_types::Url;
+
+use dm::objtree::ObjectTree;
+use dm::{FileId, Location};
+
+/// What a lens counts once it is resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LensKind {
+    References,
+    Overrides,
+    Subtypes,
+}
+
+impl LensKind {
+    pub fn title(self, count: usize) -> String {
+        let noun = match self {
+            LensKind::References => "reference",
+            LensKind::Overrides => "override",
+            LensKind::Subtypes => "subtype",
+        };
+        format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" })
+    }
+}
+
+/// The data carried by an unresolved lens, so that `codeLens/resolve` can
+/// find its symbol again.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LensData {
+    pub uri: Url,
+    /// A path like `/obj/item/proc/attack`, for `resolve_symbol_path`.
+    pub path: String,
+    pub kind: LensKind,
+}
+
+/// List the lenses for the type, var, and proc declarations in a file.
+pub fn declarations(objtree: &ObjectTree, file: FileId) -> Vec<(Location, String, LensKind)> {
+    let mut output = Vec::new();
+    for ty in objtree.iter_types() {
+        if !ty.is_root() && ty.location.file == file {
+            output.push((ty.location, ty.path.to_string(), LensKind::Subtypes));
+        }
+        for (name, type_var) in ty.vars.iter() {
+            if let Some(decl) = type_var.declaration.as_ref().filter(|decl| decl.location.file == file) {
+                output.push((decl.location, format!("{}/var/{}", ty.path, name), LensKind::References));
+            }
+        }
+        for (name, type_proc) in ty.procs.iter() {
+            if let Some(decl) = type_proc.declaration.as_ref().filter(|decl| decl.location.file == file) {
+                let path = format!("{}/{}/{}", ty.path, decl.kind, name);
+                output.push((decl.location, path.clone(), LensKind::References));
+                output.push((decl.location, path, LensKind::Overrides));
+            }
+        }
+    }
+    output.sort_by_key(|&(location, _, _)| location);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declarations_in_file() {
+        let context = dm::Context::default();
+        let code = r#"
+/obj/item
+    var/force = 1
+    proc/attack(target)
+/obj/item/sword
+    force = 5
+/obj/item/sword/attack(target)
+"#;
+        let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dm".into(), code.trim());
+        let indents = dm::indents::IndentProcessor::new(&context, pp);
+        let mut parser = dm::parser::Parser::new(&context, indents);
+        parser.enable_procs();
+        let objtree = parser.parse_object_tree();
+
+        let file = objtree.expect("/obj/item").location.file;
+        let lenses: Vec<_> = declarations(&objtree, file).into_iter()
+            .map(|(location, path, kind)| (location.line, path, kind))
+            .collect();
+        // Without the builtins loaded, `/obj` is first defined here too.
+        assert_eq!(lenses, vec![
+            (1, "/obj".to_owned(), LensKind::Subtypes),
+            (1, "/obj/item".to_owned(), LensKind::Subtypes),
+            (2, "/obj/item/var/force".to_owned(), LensKind::References),
+            (3, "/obj/item/proc/attack".to_owned(), LensKind::References),
+            (3, "/obj/item/proc/attack".to_owned(), LensKind::Overrides),
+            (4, "/obj/item/sword".to_owned(), LensKind::Subtypes),
+        ]);
+        assert_eq!(LensKind::Overrides.title(1), "1 override");
+        assert_eq!(LensKind::References.title(0), "0 references");
+    }
+}
@@ -2,10 +2,14 @@
 //!
 //! Uses a regular expression on the source text to handle `#define`s and
 //! colors inside HTML blocks, which an annotation for strings or `rgb()` calls
-//! would not catch.
+//! would not catch. Calls whose arguments are not plain numbers are found by
+//! `find_calls` and folded by the caller.
 
 use regex::Regex;
 
+use dm::constants::Constant;
+use dm::lexer::{Lexer, Punctuation, Token};
+
 /// Extract ranges and colors from an input string.
 pub fn extract_colors(input: &str) -> impl Iterator<Item=(usize, usize, [u8; 4])> + '_ {
     COLOR_REGEX.captures_iter(input).flat_map(|capture| {
@@ -16,16 +20,62 @@ pub fn extract_colors(input: &str) -> impl Iterator<Item=(usize, usize, [u8; 4])
     })
 }
 
-#[derive(Copy, Clone)]
+/// Find the `rgb()` and `rgba()` calls in an input string, as byte ranges.
+pub fn find_calls(input: &str) -> Vec<(usize, usize)> {
+    let mut line_starts = vec![0];
+    line_starts.extend(input.match_indices('\n').map(|(i, _)| i + 1));
+    let offset = |location: dm::Location| {
+        line_starts.get(location.line as usize - 1).map_or(input.len(), |start| start + location.column as usize - 1)
+    };
+
+    let context = dm::Context::default();
+    let tokens: Vec<_> = Lexer::new(&context, Default::default(), input.as_bytes())
+        .filter(|token| !token.token.is_whitespace())
+        .collect();
+    let mut output = Vec::new();
+    for (i, pair) in tokens.windows(2).enumerate() {
+        if !(pair[0].token.is_ident("rgb") || pair[0].token.is_ident("rgba")) || pair[1].token != Token::Punct(Punctuation::LParen) {
+            continue;
+        }
+        let mut depth = 0;
+        for token in &tokens[i + 1..] {
+            match token.token {
+                Token::Punct(Punctuation::LParen) => depth += 1,
+                Token::Punct(Punctuation::RParen) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        output.push((offset(pair[0].location), offset(token.location) + 1));
+                        break;
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+    output
+}
+
+/// The color a folded constant names, if it is a color string.
+pub fn constant_color(constant: &Constant) -> Option<[u8; 4]> {
+    let hex = constant.as_str()?.strip_prefix('#')?;
+    if !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return None;
+    }
+    parse_hex(hex)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ColorFormat {
     Hex {
-        // TODO: uppercase
         single_quoted: bool,
         short: bool,
         alpha: bool,
+        uppercase: bool,
     },
     Rgb {
         alpha: bool,
+        // spelled `rgba()` rather than `rgb()`
+        rgba: bool,
     }
 }
 
@@ -34,6 +84,7 @@ impl ColorFormat {
         if input.starts_with("rgb") {
             return Some(ColorFormat::Rgb {
                 alpha: input.chars().filter(|&c| c == ',').count() > 2,
+                rgba: input.starts_with("rgba"),
             });
         };
         let single_quoted = if input.starts_with("'#") && input.ends_with('\'') {
@@ -48,32 +99,62 @@ impl ColorFormat {
             short: input.len() <= 7,
             // "#rgba" or "#rrggbbaa"
             alpha: input.len() == 7 || input.len() == 11,
+            uppercase: input.chars().any(|c| c.is_ascii_uppercase()) && !input.chars().any(|c| c.is_ascii_lowercase()),
         })
     }
 
+    /// The other way of writing a color in code, for offering both.
+    pub fn alternate(self) -> ColorFormat {
+        match self {
+            ColorFormat::Hex { alpha, .. } => ColorFormat::Rgb { alpha, rgba: false },
+            ColorFormat::Rgb { alpha, .. } => ColorFormat::Hex { single_quoted: false, short: false, alpha, uppercase: false },
+        }
+    }
+
     pub fn format(self, [r, g, b, a]: [u8; 4]) -> String {
         match self {
-            ColorFormat::Hex { single_quoted, short, alpha } => {
+            ColorFormat::Hex { single_quoted, short, alpha, uppercase } => {
                 let q = if single_quoted { '\'' } else { '"' };
                 let short = short && r % 0x11 == 0 && g % 0x11 == 0 && b % 0x11 == 0 && a % 0x11 == 0;
                 let alpha = alpha || a != 255;
-                match (short, alpha) {
-                    (false, false) => format!("{}#{:02x}{:02x}{:02x}{}", q, r, g, b, q),
-                    (false, true) => format!("{}#{:02x}{:02x}{:02x}{:02x}{}", q, r, g, b, a, q),
-                    (true, false) => format!("{}#{:x}{:x}{:x}{}", q, r / 0x11, g / 0x11, b / 0x11, q),
-                    (true, true) => format!("{}#{:x}{:x}{:x}{:x}{}", q, r / 0x11, g / 0x11, b / 0x11, a / 0x11, q),
+                let hex = match (short, alpha) {
+                    (false, false) => format!("{:02x}{:02x}{:02x}", r, g, b),
+                    (false, true) => format!("{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+                    (true, false) => format!("{:x}{:x}{:x}", r / 0x11, g / 0x11, b / 0x11),
+                    (true, true) => format!("{:x}{:x}{:x}{:x}", r / 0x11, g / 0x11, b / 0x11, a / 0x11),
+                };
+                let hex = if uppercase { hex.to_ascii_uppercase() } else { hex };
+                format!("{}#{}{}", q, hex, q)
+            },
+            ColorFormat::Rgb { alpha, rgba } => {
+                let name = if rgba { "rgba" } else { "rgb" };
+                if alpha || a != 255 {
+                    format!("{}({}, {}, {}, {})", name, r, g, b, a)
+                } else {
+                    format!("{}({}, {}, {})", name, r, g, b)
                 }
             },
-            ColorFormat::Rgb { alpha } if alpha || a != 255 => format!("rgb({}, {}, {}, {})", r, g, b, a),
-            ColorFormat::Rgb { alpha: _ } => format!("rgb({}, {}, {})", r, g, b),
         }
     }
 }
 
 impl Default for ColorFormat {
     fn default() -> ColorFormat {
-        ColorFormat::Hex { single_quoted: false, short: false, alpha: false }
+        ColorFormat::Hex { single_quoted: false, short: false, alpha: false, uppercase: false }
+    }
+}
+
+/// Normalize a color string, such as the folded result of an `rgb()` call,
+/// to the `#rrggbb` form, or `#rrggbbaa` if it is not opaque.
+pub fn normalize(input: &str) -> Option<String> {
+    let hex = input.strip_prefix('#')?;
+    if !hex.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return None;
     }
+    Some(match parse_hex(hex)? {
+        [r, g, b, 255] => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        [r, g, b, a] => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+    })
 }
 
 lazy_static! {
@@ -138,3 +219,28 @@ fn parse_rgba(r: &str, g: &str, b: &str, a: Option<&str>) -> Option<[u8; 4]> {
         a.and_then(|a| a.parse::<u8>().ok()).unwrap_or(255),
     ])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_preserve_style() {
+        let upper = ColorFormat::parse("\"#FF00AA\"").unwrap();
+        assert_eq!(upper.format([0xab, 0xcd, 0xef, 255]), "\"#ABCDEF\"");
+        let short = ColorFormat::parse("'#f0a'").unwrap();
+        assert_eq!(short.format([0x11, 0x22, 0x33, 255]), "'#123'");
+        assert_eq!(short.format([0x12, 0x22, 0x33, 255]), "'#122233'");
+        let rgba = ColorFormat::parse("rgba(1, 2, 3, 4)").unwrap();
+        assert_eq!(rgba.format([10, 20, 30, 255]), "rgba(10, 20, 30, 255)");
+        assert_eq!(rgba.alternate().format([10, 20, 30, 255]), "\"#0a141eff\"");
+        assert_eq!(upper.alternate().format([10, 20, 30, 128]), "rgb(10, 20, 30, 128)");
+    }
+
+    #[test]
+    fn calls_with_expressions() {
+        let input = "var/c = rgb(100 + 55, (2), 3)\nvar/d = rgba(R, G, B, A) + rgb";
+        let calls: Vec<&str> = find_calls(input).into_iter().map(|(start, end)| &input[start..end]).collect();
+        assert_eq!(calls, vec!["rgb(100 + 55, (2), 3)", "rgba(R, G, B, A)"]);
+    }
+}
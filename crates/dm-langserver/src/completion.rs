@@ -4,13 +4,22 @@ use foldhash::{HashSet, HashSetExt};
 
 use lsp_types::*;
 
-use dm::annotation::Annotation;
-use dm::ast::PathOp;
+use dm::annotation::{Annotation, AnnotationTree};
+use dm::intern::Interned;
+use dm::ast::{Parameter, PathOp};
 use dm::objtree::{ProcValue, TypeProc, TypeRef, TypeVar};
+use dm::lexer::{Lexer, Punctuation, Token};
+use dm::Location;
 
+use crate::icons::Icon;
 use crate::symbol_search::contains;
 use crate::{is_constructor_name, Engine, Span};
 
+lazy_static! {
+    static ref ICON_STATE_VALUE: regex::Regex = regex::Regex::new(r#"\bicon_state\s*=\s*"([^"\[\]\\]*)$"#).unwrap();
+    static ref ICON_ASSIGNMENT: regex::Regex = regex::Regex::new(r#"\bicon\s*=\s*'([^']+)'"#).unwrap();
+}
+
 #[rustfmt::skip]
 static PROC_KEYWORDS: &[&str] = &[
     // Implicit variables
@@ -68,6 +77,7 @@ fn item_var(ty: TypeRef, name: &str, var: &TypeVar) -> CompletionItem {
         kind: Some(CompletionItemKind::FIELD),
         detail: Some(detail),
         documentation: item_documentation(&var.value.docs),
+        tags: item_tags(&var.value.docs),
         .. Default::default()
     }
 }
@@ -84,10 +94,47 @@ fn item_proc(ty: TypeRef, name: &str, proc: &TypeProc) -> CompletionItem {
         }),
         detail: Some(format!("on {}", ty.pretty_path())),
         documentation: item_documentation(&proc.main_value().docs),
+        // overrides are deprecated along with their declaration
+        tags: ty.get_proc(name)
+            .map(|mut declaration| {
+                while let Some(parent) = declaration.parent_proc() {
+                    declaration = parent;
+                }
+                declaration
+            })
+            .and_then(|declaration| item_tags(&declaration.get().docs)),
         ..Default::default()
     }
 }
 
+/// Describe a parameter for signature help and completion, including its
+/// declared type, input source, and folded default value.
+pub fn parameter_label(param: &Parameter) -> String {
+    use std::fmt::Write;
+
+    let mut label = String::new();
+    for each in param.var_type.type_path.iter() {
+        let _ = write!(label, "{}/", each);
+    }
+    label.push_str(&param.name);
+    if let Some(input_type) = param.input_type {
+        let _ = write!(label, " as {}", input_type);
+    }
+    if let Some(ref in_list) = param.in_list {
+        match in_list.clone().simple_evaluate(param.location) {
+            Ok(constant) => { let _ = write!(label, " in {}", constant); }
+            Err(_) => label.push_str(" in ..."),
+        }
+    }
+    if let Some(ref default) = param.default {
+        match default.clone().simple_evaluate(param.location) {
+            Ok(constant) => { let _ = write!(label, " = {}", constant); }
+            Err(_) => label.push_str(" = ..."),
+        }
+    }
+    label
+}
+
 fn item_documentation(docs: &dm::docs::DocCollection) -> Option<Documentation> {
     if docs.is_empty() {
         return None;
@@ -99,9 +146,13 @@ fn item_documentation(docs: &dm::docs::DocCollection) -> Option<Documentation> {
     }))
 }
 
+fn item_tags(docs: &dm::docs::DocCollection) -> Option<Vec<CompletionItemTag>> {
+    docs.deprecated().map(|_| vec![CompletionItemTag::DEPRECATED])
+}
+
 fn items_ty<'a>(
     results: &mut Vec<CompletionItem>,
-    skip: &mut HashSet<(&str, &'a String)>,
+    skip: &mut HashSet<(&str, &'a Interned)>,
     ty: TypeRef<'a>,
     query: &str,
 ) {
@@ -122,7 +173,7 @@ fn items_ty<'a>(
         }
         if contains(name, query) {
             results.push(CompletionItem {
-                insert_text: Some(name.to_owned()),
+                insert_text: Some(name.to_string()),
                 .. item_proc(ty, name, proc)
             });
         }
@@ -227,7 +278,7 @@ impl Engine {
         Some(TypePathResult { ty, decl, proc })
     }
 
-    pub fn tree_completions(&self, results: &mut Vec<CompletionItem>, exact: bool, ty: TypeRef, query: &str) {
+    pub fn tree_completions(&self, results: &mut Vec<CompletionItem>, exact: bool, ty: TypeRef, query: &str, indent: &str) {
         // path keywords
         for &name in ["proc", "var", "verb"].iter() {
             if contains(name, query) {
@@ -247,12 +298,15 @@ impl Engine {
                         label: child.name().to_owned(),
                         kind: Some(CompletionItemKind::CLASS),
                         documentation: item_documentation(&child.docs),
+                        tags: item_tags(&child.docs),
                         .. Default::default()
                     });
                 }
             }
         }
 
+        let snippets = self.client_caps.snippet_support;
+        let insert_text_format = Some(if snippets { InsertTextFormat::SNIPPET } else { InsertTextFormat::PLAIN_TEXT });
         let mut next = Some(ty).filter(|ty| !ty.is_root());
         let mut skip = HashSet::new();
         while let Some(ty) = next {
@@ -261,9 +315,23 @@ impl Engine {
                 if !skip.insert(("var", name)) {
                     continue;
                 }
+                let Some((decl_ty, decl)) = find_var_declaration(ty, name) else {
+                    continue;
+                };
+                if decl.var_type.flags.is_const() {
+                    continue;
+                }
                 if contains(name, query) {
+                    let default = var.value.constant.as_ref().map(ToString::to_string).unwrap_or_default();
+                    let insert_text = if snippets {
+                        format!("{} = ${{1:{}}}", name, escape_snippet(&default))
+                    } else {
+                        format!("{} = {}", name, default)
+                    };
                     results.push(CompletionItem {
-                        insert_text: Some(format!("{} = ", name)),
+                        detail: Some(format!("override from {}", decl_ty.pretty_path())),
+                        insert_text: Some(insert_text),
+                        insert_text_format,
                         .. item_var(ty, name, var)
                     });
                 }
@@ -277,9 +345,28 @@ impl Engine {
                 if contains(name, query) {
                     use std::fmt::Write;
 
+                    // overrides often leave the parameters out, so look for
+                    // the nearest definition which has them
+                    let mut parameters = &proc.main_value().parameters;
+                    let mut decl_ty = ty;
+                    let mut current = Some(ty);
+                    while let Some(each) = current {
+                        if let Some(each_proc) = each.get().procs.get(name) {
+                            if parameters.is_empty() {
+                                parameters = &each_proc.main_value().parameters;
+                            }
+                            if each_proc.declaration.is_some() {
+                                decl_ty = each;
+                                break;
+                            }
+                        }
+                        current = each.parent_type();
+                    }
+                    let docs = &decl_ty.get().procs[name].main_value().docs;
+
                     let mut completion = format!("{}(", name);
                     let mut sep = "";
-                    for param in proc.main_value().parameters.iter() {
+                    for param in parameters.iter() {
                         for each in param.var_type.type_path.iter() {
                             let _ = write!(completion, "{}{}", sep, each);
                             sep = "/";
@@ -287,10 +374,20 @@ impl Engine {
                         let _ = write!(completion, "{}{}", sep, param.name);
                         sep = ", ";
                     }
-                    let _ = write!(completion, ")\n\t. = ..()\n\t");
+                    if snippets {
+                        completion = escape_snippet(&completion);
+                        let _ = write!(completion, ")\n{indent}. = ..()\n{indent}$0");
+                    } else {
+                        let _ = write!(completion, ")\n{indent}. = ..()\n{indent}");
+                    }
 
                     results.push(CompletionItem {
+                        detail: Some(format!("override from {}", decl_ty.pretty_path())),
+                        documentation: item_documentation(docs),
+                        tags: item_tags(docs),
                         insert_text: Some(completion),
+                        insert_text_format,
+                        insert_text_mode: Some(InsertTextMode::ADJUST_INDENTATION),
                         .. item_proc(ty, name, proc)
                     });
                 }
@@ -306,6 +403,7 @@ impl Engine {
         parts: &'b [(PathOp, String)],
         _last_op: PathOp,
         query: &str,
+        context: &PathContext,
     ) where
         I: Iterator<Item = (Span, &'b Annotation)> + Clone,
     {
@@ -318,23 +416,40 @@ impl Engine {
                 proc: None,
             }) => {
                 // path keywords
-                for &name in ["proc", "verb"].iter() {
-                    if contains(name, query) {
-                        results.push(CompletionItem {
-                            label: name.to_owned(),
-                            kind: Some(CompletionItemKind::KEYWORD),
-                            .. Default::default()
-                        })
+                if let PathContext::Other | PathContext::Ispath = context {
+                    for &name in ["proc", "verb"].iter() {
+                        if contains(name, query) {
+                            results.push(CompletionItem {
+                                label: name.to_owned(),
+                                kind: Some(CompletionItemKind::KEYWORD),
+                                .. Default::default()
+                            })
+                        }
                     }
                 }
 
+                // istype() can only succeed for types related to the operand's
+                let related = match context {
+                    PathContext::Istype(Some(name)) => self.find_scoped_type(iter, std::slice::from_ref(name)),
+                    _ => None,
+                };
+
                 // child types
                 for child in ty.children() {
+                    match context {
+                        // abstract types are still a way to reach their children
+                        PathContext::New if is_abstract(child) && child.children().next().is_none() => continue,
+                        PathContext::Istype(_) if related.is_some_and(|related| {
+                            !child.is_subtype_of(related.get()) && !related.is_subtype_of(child.get())
+                        }) => continue,
+                        _ => {}
+                    }
                     if contains(child.name(), query) {
                         results.push(CompletionItem {
                             label: child.name().to_owned(),
                             kind: Some(CompletionItemKind::CLASS),
                             documentation: item_documentation(&child.docs),
+                            tags: item_tags(&child.docs),
                             .. Default::default()
                         });
                     }
@@ -374,6 +489,72 @@ impl Engine {
         }
     }
 
+    pub fn prefab_var_completions<'b, I>(
+        &'b self,
+        results: &mut Vec<CompletionItem>,
+        iter: &I,
+        path: &'b [(PathOp, String)],
+        assigned: &[String],
+        query: &str,
+    ) where
+        I: Iterator<Item = (Span, &'b Annotation)> + Clone,
+    {
+        let Some(TypePathResult { ty: prefab, decl: None, proc: None }) = self.follow_type_path(iter, path) else {
+            return;
+        };
+        let mut next = Some(prefab);
+        let mut skip: HashSet<&str> = assigned.iter().map(String::as_str).collect();
+        while let Some(ty) = next {
+            for (name, var) in ty.get().vars.iter() {
+                if !skip.insert(name) {
+                    continue;
+                }
+                let Some(decl) = prefab.get_var_declaration(name) else {
+                    continue;
+                };
+                if decl.var_type.flags.is_const() || decl.var_type.flags.is_static() {
+                    continue;
+                }
+                if contains(name, query) {
+                    let value = match prefab.get_value(name) {
+                        Some(dm::objtree::VarValue { constant: Some(constant), .. }) => constant.to_string(),
+                        Some(dm::objtree::VarValue { expression: Some(_), .. }) => "...".to_owned(),
+                        _ => "null".to_owned(),
+                    };
+                    results.push(CompletionItem {
+                        detail: Some(format!("{}{} = {}", decl.var_type, name, value)),
+                        insert_text: Some(format!("{} = ", name)),
+                        .. item_var(ty, name, var)
+                    });
+                }
+            }
+            next = ty.parent_type_without_root();
+        }
+    }
+
+    /// Find the icon of the type whose `icon_state` is being assigned, as
+    /// the resource's name and the file it is relative to.
+    pub fn icon_of_context<'b, I>(&'b self, iter: &I, path_context: &'b PathContext) -> Option<(String, std::path::PathBuf)>
+    where
+        I: Iterator<Item = (Span, &'b Annotation)> + Clone,
+    {
+        let ty = match path_context {
+            PathContext::PrefabVars { path, .. } => match self.follow_type_path(iter, path) {
+                Some(TypePathResult { ty, decl: None, proc: None }) => ty,
+                _ => return None,
+            },
+            _ => self.find_type_context(iter).0?,
+        };
+        let value = ty.get_value("icon")?;
+        match value.constant {
+            Some(dm::constants::Constant::Resource(ref name)) if !value.location.is_builtins() => {
+                let file = crate::url_to_path(&self.file_url(value.location.file).ok()?).ok()?;
+                Some((name.to_string(), file))
+            }
+            _ => None,
+        }
+    }
+
     pub fn unscoped_completions<'b, I>(&'b self, results: &mut Vec<CompletionItem>, iter: &I, query: &str)
     where
         I: Iterator<Item = (Span, &'b Annotation)> + Clone,
@@ -436,6 +617,7 @@ impl Engine {
                         kind: Some(CompletionItemKind::CONSTANT),
                         detail: Some(define.display_with_name(name).to_string()),
                         documentation: item_documentation(define.docs()),
+                        tags: item_tags(define.docs()),
                         .. Default::default()
                     });
                 }
@@ -451,6 +633,124 @@ impl Engine {
         }
     }
 
+    /// Find the proc call whose argument list surrounds the cursor.
+    pub fn find_call_context<'b, I>(&'b self, iter: &I) -> Option<CallContext<'b>>
+    where
+        I: Iterator<Item = (Span, &'b Annotation)> + Clone,
+    {
+        // find the innermost call
+        let mut call: Option<(Span, &[String], &String, usize)> = None;
+        for (span, annotation) in iter.clone() {
+            if let Annotation::ProcArguments(priors, proc_name, count) = annotation {
+                if call.is_none_or(|(call_span, ..)| span.start >= call_span.start) {
+                    call = Some((span, priors, proc_name, *count));
+                }
+            }
+        }
+        let (span, priors, proc_name, mut argument) = call?;
+
+        // take the specific argument we're working on
+        let mut argument_start = None;
+        let mut new_type = None;
+        for (arg_span, annotation) in iter.clone() {
+            match annotation {
+                Annotation::ProcArgument(i) if arg_span.start >= span.start && argument_start.is_none_or(|start| arg_span.start >= start) => {
+                    argument_start = Some(arg_span.start);
+                    argument = *i;
+                }
+                Annotation::NewArguments(path) if arg_span.start == span.start => new_type = Some(path),
+                _ => {}
+            }
+        }
+
+        // figure out which type to start looking for the proc on
+        let (mut next, proc_name) = if let Some(path) = new_type {
+            match self.follow_type_path(iter, path) {
+                Some(TypePathResult { ty, decl: None, proc: None }) => (Some(ty), "New"),
+                _ => return None,
+            }
+        } else if proc_name == ".." {
+            match self.find_type_context(iter) {
+                (Some(ty), Some((name, _))) => (ty.parent_type(), name),
+                _ => return None,
+            }
+        } else {
+            (self.find_scoped_type(iter, priors), proc_name.as_str())
+        };
+
+        // overrides often leave out the parameters, so prefer the nearest
+        // definition which actually declares some
+        let mut found = None;
+        while let Some(ty) = next {
+            if let Some(proc) = ty.get().procs.get(proc_name) {
+                let value = proc.main_value();
+                if found.is_none() || !value.parameters.is_empty() {
+                    found = Some((ty, value));
+                }
+                if !value.parameters.is_empty() {
+                    break;
+                }
+            }
+            next = ty.parent_type();
+            if let Some(ref n) = next {
+                if n.is_root() && !priors.is_empty() {
+                    break;
+                }
+            }
+        }
+        let (ty, proc) = found?;
+        Some(CallContext {
+            ty,
+            proc_name,
+            proc,
+            span,
+            argument,
+            argument_start,
+        })
+    }
+
+    /// Offer the parameters of the surrounding call which have not yet been
+    /// supplied, in `name = ` form.
+    pub fn keyword_argument_completions(
+        &self,
+        results: &mut Vec<CompletionItem>,
+        annotations: &AnnotationTree,
+        call: &CallContext,
+        text: &str,
+        query: &str,
+    ) {
+        let mut supplied = HashSet::new();
+        for (idx, start) in call_arguments(annotations, call.span) {
+            if Some(start) == call.argument_start {
+                continue;
+            }
+            let keyword = crate::document::location_offset(text, start.line, start.column)
+                .and_then(|offset| crate::document::keyword_argument_name(&text[offset..]));
+            match keyword {
+                Some(name) => { supplied.insert(name); }
+                None => if let Some(param) = call.proc.parameters.get(idx) {
+                    supplied.insert(&param.name);
+                },
+            }
+        }
+
+        for param in call.proc.parameters.iter() {
+            if supplied.contains(param.name.as_str()) || !contains(&param.name, query) {
+                continue;
+            }
+            results.push(CompletionItem {
+                label: format!("{} = ", param.name),
+                kind: Some(CompletionItemKind::VARIABLE),
+                detail: Some(parameter_label(param)),
+                documentation: Some(Documentation::String(format!("parameter of {}/{}()", call.ty.pretty_path(), call.proc_name))),
+                sort_text: Some(format!("0{}", param.name)),
+                filter_text: Some(param.name.clone()),
+                insert_text: Some(format!("{} = ", param.name)),
+                .. Default::default()
+            });
+        }
+    }
+
     pub fn scoped_completions<'b, I>(
         &'b self,
         results: &mut Vec<CompletionItem>,
@@ -469,8 +769,342 @@ impl Engine {
     }
 }
 
+/// Find the start of each argument at the top level of a call's argument
+/// list, skipping those of calls nested within it.
+fn call_arguments(annotations: &AnnotationTree, span: Span) -> Vec<(usize, Location)> {
+    let nested: Vec<Span> = annotations.get_range_raw(span)
+        .filter_map(|(inner, annotation)| match annotation {
+            Annotation::ProcArguments(..) if inner.start > span.start => Some(inner),
+            _ => None,
+        })
+        .collect();
+    annotations.get_range_raw(span)
+        .filter_map(|(arg_span, annotation)| match annotation {
+            Annotation::ProcArgument(idx) if arg_span.start >= span.start
+                && !nested.iter().any(|inner| inner.start <= arg_span.start && arg_span.start <= inner.end) => Some((*idx, arg_span.start)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// If the cursor is in the string value of an `icon_state` assignment, split
+/// the line into what comes before the assignment and the state typed so far.
+pub fn icon_state_value(line_before_cursor: &str) -> Option<(&str, &str)> {
+    let captures = ICON_STATE_VALUE.captures(line_before_cursor)?;
+    let start = captures.get(0).unwrap().start();
+    Some((&line_before_cursor[..start], captures.get(1).unwrap().as_str()))
+}
+
+/// Find the nearest `icon = '...'` above an `icon_state` assignment in the
+/// same block: earlier within the same prefab braces, or earlier on lines of
+/// the same indentation.
+pub fn preceding_icon_override<'a>(text: &'a str, line: u32, before_assignment: &'a str) -> Option<&'a str> {
+    let mut depth = 0;
+    let mut prefab_start = None;
+    for (i, ch) in before_assignment.char_indices().rev() {
+        match ch {
+            '}' => depth += 1,
+            '{' if depth == 0 => {
+                prefab_start = Some(i + 1);
+                break;
+            }
+            '{' => depth -= 1,
+            _ => {}
+        }
+    }
+    if let Some(start) = prefab_start {
+        return ICON_ASSIGNMENT.captures_iter(&before_assignment[start..])
+            .last()
+            .map(|captures| captures.get(1).unwrap().as_str());
+    }
+
+    let indent = before_assignment.len() - before_assignment.trim_start().len();
+    for above in text.lines().take(line as usize).collect::<Vec<_>>().into_iter().rev() {
+        let trimmed = above.trim_start();
+        if trimmed.is_empty() {
+            continue;
+        }
+        let above_indent = above.len() - trimmed.len();
+        if above_indent < indent {
+            break;
+        } else if above_indent == indent {
+            if let Some(captures) = ICON_ASSIGNMENT.captures(trimmed).filter(|captures| captures.get(0).unwrap().start() == 0) {
+                return Some(captures.get(1).unwrap().as_str());
+            }
+        }
+    }
+    None
+}
+
+/// Offer the states of an icon which start with what has been typed.
+pub fn icon_state_items(results: &mut Vec<CompletionItem>, icon: &Icon, typed: &str, range: Range) {
+    for (name, dirs, frames) in icon.states() {
+        if !name.starts_with(typed) {
+            continue;
+        }
+        results.push(CompletionItem {
+            label: name.to_owned(),
+            kind: Some(CompletionItemKind::VALUE),
+            detail: Some(format!(
+                "{} dir{}, {} frame{}",
+                dirs,
+                if dirs == 1 { "" } else { "s" },
+                frames,
+                if frames == 1 { "" } else { "s" },
+            )),
+            filter_text: Some(name.to_owned()),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(range, name.to_owned()))),
+            .. Default::default()
+        });
+    }
+}
+
+/// The kind of expression a type path being completed appears in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathContext {
+    Other,
+    /// `new /path`, which should be instantiable.
+    New,
+    /// `istype(x, /path)`, which should be related to the type of `x`.
+    Istype(Option<String>),
+    /// `ispath(x, /path)`.
+    Ispath,
+    /// `locate(/path)`.
+    Locate,
+    /// The var names of a `/path{...}` prefab, whether or not it is being
+    /// created with `new`, and those already assigned.
+    PrefabVars {
+        path: Vec<(PathOp, String)>,
+        assigned: Vec<String>,
+    },
+}
+
+/// Classify the position of a type path by inspecting the tokens of the
+/// current line backwards from the cursor.
+pub fn classify_path_context(line_before_cursor: &str) -> PathContext {
+    let context = dm::Context::default();
+    let mut tokens: Vec<Token> = Lexer::new(&context, Default::default(), line_before_cursor.as_bytes())
+        .map(|token| token.token)
+        .filter(|token| !token.is_whitespace())
+        .collect();
+
+    // a partially typed var name in a prefab block
+    if let Some(Token::Ident(..)) = tokens.last() {
+        if let Some(Token::Punct(Punctuation::LBrace | Punctuation::Semicolon | Punctuation::Comma)) = tokens.iter().rev().nth(1) {
+            tokens.pop();
+        }
+    }
+    if let Some(Token::Punct(Punctuation::LBrace | Punctuation::Semicolon | Punctuation::Comma)) = tokens.last() {
+        if let Some(open) = find_unmatched_open(&tokens, Punctuation::LBrace) {
+            let mut before = tokens[..open].to_vec();
+            let path = pop_type_path(&mut before);
+            // a path starting a line is a type definition, not a prefab
+            let expression = before.last().is_some_and(|token| match token {
+                Token::Ident(word, _) => word == "new" || word == "return",
+                Token::Punct(_) => true,
+                _ => false,
+            });
+            if !path.is_empty() && expression {
+                let assigned = assigned_vars(&tokens[open..]);
+                return PathContext::PrefabVars { path, assigned };
+            }
+        }
+    }
+
+    // strip the path itself and look at what comes before it
+    pop_type_path(&mut tokens);
+    match tokens.last() {
+        Some(token) if token.is_ident("new") => PathContext::New,
+        Some(Token::Punct(Punctuation::LParen | Punctuation::Comma)) => {
+            let Some(open) = find_unmatched_open(&tokens, Punctuation::LParen) else {
+                return PathContext::Other;
+            };
+            let arguments = &tokens[open + 1..];
+            let mut depth = 0;
+            let index = arguments.iter().filter(|token| match token {
+                Token::Punct(Punctuation::LParen | Punctuation::LBracket | Punctuation::LBrace) => { depth += 1; false }
+                Token::Punct(Punctuation::RParen | Punctuation::RBracket | Punctuation::RBrace) => { depth -= 1; false }
+                Token::Punct(Punctuation::Comma) => depth == 0,
+                _ => false,
+            }).count();
+            match open.checked_sub(1).map(|i| &tokens[i]) {
+                Some(Token::Ident(name, _)) if name == "istype" && index == 1 => PathContext::Istype(match arguments {
+                    [Token::Ident(first, _), Token::Punct(Punctuation::Comma)] => Some(first.clone()),
+                    _ => None,
+                }),
+                Some(Token::Ident(name, _)) if name == "ispath" => PathContext::Ispath,
+                Some(Token::Ident(name, _)) if name == "locate" => PathContext::Locate,
+                _ => PathContext::Other,
+            }
+        }
+        _ => PathContext::Other,
+    }
+}
+
+/// The names assigned at the top level of a `{...}` block, starting at the
+/// brace.
+fn assigned_vars(block: &[Token]) -> Vec<String> {
+    let mut depth = 0;
+    let mut assigned = Vec::new();
+    for (i, token) in block.iter().enumerate() {
+        match token {
+            Token::Punct(Punctuation::LParen | Punctuation::LBracket | Punctuation::LBrace) => depth += 1,
+            Token::Punct(Punctuation::RParen | Punctuation::RBracket | Punctuation::RBrace) => depth -= 1,
+            Token::Ident(name, _) if depth == 1 => {
+                let separated = matches!(block[i - 1], Token::Punct(Punctuation::LBrace | Punctuation::Semicolon | Punctuation::Comma));
+                if separated && block.get(i + 1) == Some(&Token::Punct(Punctuation::Assign)) {
+                    assigned.push(name.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+    assigned
+}
+
+/// Find the type which declares a var, along with the declaration.
+fn find_var_declaration<'a>(ty: TypeRef<'a>, name: &str) -> Option<(TypeRef<'a>, &'a dm::objtree::VarDeclaration)> {
+    let mut current = Some(ty);
+    while let Some(each) = current {
+        if let Some(decl) = each.get().vars.get(name).and_then(|var| var.declaration.as_ref()) {
+            return Some((each, decl));
+        }
+        current = each.parent_type();
+    }
+    None
+}
+
+fn escape_snippet(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('$', "\\$").replace('}', "\\}")
+}
+
+/// Whether a type is marked abstract by the `abstract_type = /its/own/path`
+/// convention.
+fn is_abstract(ty: TypeRef) -> bool {
+    ty.get_value("abstract_type")
+        .and_then(|value| value.constant.as_ref())
+        .and_then(|constant| ty.tree().type_by_constant(constant))
+        .is_some_and(|abstract_type| abstract_type.index() == ty.index())
+}
+
+/// Remove a trailing type path from the tokens, returning it.
+fn pop_type_path(tokens: &mut Vec<Token>) -> Vec<(PathOp, String)> {
+    let mut path = Vec::new();
+    loop {
+        let op = match tokens.iter().rev().nth(1) {
+            Some(Token::Punct(Punctuation::Slash)) => Some(PathOp::Slash),
+            Some(Token::Punct(Punctuation::Dot)) => Some(PathOp::Dot),
+            Some(Token::Punct(Punctuation::Colon | Punctuation::CloseColon)) => Some(PathOp::Colon),
+            _ => None,
+        };
+        match (tokens.last(), op) {
+            (Some(Token::Ident(name, _)), Some(op)) => {
+                path.push((op, name.clone()));
+                tokens.truncate(tokens.len() - 2);
+            }
+            (Some(Token::Punct(Punctuation::Slash | Punctuation::Dot | Punctuation::Colon | Punctuation::CloseColon)), _) => {
+                tokens.pop();
+            }
+            _ => break,
+        }
+    }
+    path.reverse();
+    path
+}
+
+/// Find the index of the bracket of the given kind which is still open at the
+/// end of the tokens.
+fn find_unmatched_open(tokens: &[Token], kind: Punctuation) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().rev() {
+        match token {
+            Token::Punct(Punctuation::RParen | Punctuation::RBracket | Punctuation::RBrace) => depth += 1,
+            Token::Punct(punct @ (Punctuation::LParen | Punctuation::LBracket | Punctuation::LBrace)) => {
+                if depth == 0 {
+                    return Some(i).filter(|_| *punct == kind);
+                }
+                depth -= 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+pub struct CallContext<'a> {
+    pub ty: TypeRef<'a>,
+    pub proc_name: &'a str,
+    pub proc: &'a ProcValue,
+    /// The span of the argument list.
+    pub span: Span,
+    /// The positional index of the argument under the cursor.
+    pub argument: usize,
+    pub argument_start: Option<Location>,
+}
+
 pub struct TypePathResult<'a> {
     pub ty: TypeRef<'a>,
     pub decl: Option<&'static str>,
     pub proc: Option<(&'a str, &'a ProcValue)>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_contexts() {
+        assert_eq!(classify_path_context("\tif(istype(I, /obj/"), PathContext::Istype(Some("I".to_owned())));
+        assert_eq!(classify_path_context("\tif(istype(get_thing(), /obj/it"), PathContext::Istype(None));
+        assert_eq!(classify_path_context("\tvar/x = new /obj/"), PathContext::New);
+        assert_eq!(classify_path_context("\tlocate(/mob/"), PathContext::Locate);
+        assert_eq!(classify_path_context("\tif(ispath(T, /datum"), PathContext::Ispath);
+        assert_eq!(classify_path_context("\tfoo(/obj/"), PathContext::Other);
+        let obj_item = vec![(PathOp::Slash, "obj".to_owned()), (PathOp::Slash, "item".to_owned())];
+        assert_eq!(
+            classify_path_context("\tnew /obj/item{name = \"x\"; de"),
+            PathContext::PrefabVars { path: obj_item.clone(), assigned: vec!["name".to_owned()] },
+        );
+        assert_eq!(
+            classify_path_context("\tvar/list/L = list(/obj/item{desc = f(a, b = 1), name = \"x\", "),
+            PathContext::PrefabVars { path: obj_item.clone(), assigned: vec!["desc".to_owned(), "name".to_owned()] },
+        );
+        assert_eq!(
+            classify_path_context("\treturn /obj/item{"),
+            PathContext::PrefabVars { path: obj_item, assigned: Vec::new() },
+        );
+        assert_eq!(classify_path_context("/obj/item{"), PathContext::Other);
+        assert_eq!(classify_path_context("\tvar/obj/item{"), PathContext::Other);
+    }
+
+    #[test]
+    fn deprecated_items() {
+        let context = dm::Context::default();
+        let code = "/// @deprecated use fire()\n/obj/proc/shoot()\n/obj/item/shoot()\n/obj/proc/fire()\n";
+        let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dm".into(), code);
+        let indents = dm::indents::IndentProcessor::new(&context, pp);
+        let objtree = dm::parser::Parser::new(&context, indents).parse_object_tree();
+
+        let item = objtree.expect("/obj/item");
+        let tags = |name: &str| item_proc(item, name, &item.get().procs[name]).tags;
+        assert_eq!(tags("shoot"), Some(vec![CompletionItemTag::DEPRECATED]));
+        let obj = objtree.expect("/obj");
+        assert_eq!(item_proc(obj, "fire", &obj.get().procs["fire"]).tags, None);
+    }
+
+    #[test]
+    fn icon_overrides() {
+        assert_eq!(icon_state_value("\ticon_state = \"pis"), Some(("\t", "pis")));
+        assert_eq!(icon_state_value("\ticon_state = \"pistol\""), None);
+        assert_eq!(icon_state_value("\tname = \"pis"), None);
+
+        let text = "/obj/gun\n\ticon = 'guns.dmi'\n\tproc/fire()\n\t\ticon = 'fired.dmi'\n\tname = \"gun\"\n\ticon_state = \"\n";
+        assert_eq!(preceding_icon_override(text, 5, "\t"), Some("guns.dmi"));
+        assert_eq!(preceding_icon_override(text, 4, "\t\t"), Some("fired.dmi"));
+        assert_eq!(preceding_icon_override(text, 1, "\t"), None);
+
+        let prefab = "\tnew /obj{icon = 'a.dmi'; name = \"x\"; ";
+        assert_eq!(preceding_icon_override(text, 5, prefab), Some("a.dmi"));
+        assert_eq!(preceding_icon_override(text, 5, "\tnew /obj{"), None);
+    }
+}
@@ -14,6 +14,7 @@ use lsp_types::{
 };
 
 use super::{invalid_request, url_to_path};
+use crate::positions::Encoding;
 
 /// A store for the contents of currently-open documents, with appropriate
 /// fallback for documents which are not currently open.
@@ -65,6 +66,22 @@ impl DocumentStore {
         result
     }
 
+    /// The URLs of all currently-open documents.
+    pub fn open_urls(&self) -> impl Iterator<Item = &Url> {
+        self.map.keys()
+    }
+
+    /// Whether a document is open with contents other than those on disk.
+    pub fn is_modified(&self, url: &Url) -> bool {
+        let Some(document) = self.map.get(url) else {
+            return false;
+        };
+        match url_to_path(url).and_then(|path| std::fs::read_to_string(path).map_err(invalid_request)) {
+            Ok(text) => text != *document.text,
+            Err(_) => true,
+        }
+    }
+
     pub fn get_contents<'a>(&'a self, url: &Url) -> io::Result<Cow<'a, str>> {
         if let Some(document) = self.map.get(url) {
             return Ok(Cow::Borrowed(&document.text));
@@ -143,14 +160,16 @@ fn line_offset(text: &str, line_number: u32) -> Result<usize, jsonrpc::Error> {
     Ok(start_pos)
 }
 
-fn total_offset(text: &str, line: u32, mut character: u32) -> Result<usize, jsonrpc::Error> {
+pub fn total_offset(text: &str, line: u32, mut character: u32) -> Result<usize, jsonrpc::Error> {
     let start = line_offset(text, line)?;
 
-    // column is measured in UTF-16 code units, which is really inconvenient.
+    // column is measured in UTF-16 code units unless the client agreed to
+    // UTF-8, which is really inconvenient.
+    let encoding = Encoding::current();
     let mut chars = text[start..].chars();
     while character > 0 {
         if let Some(ch) = chars.next() {
-            character = character.saturating_sub(ch.len_utf16() as u32);
+            character = character.saturating_sub(encoding.char_len(ch));
         } else {
             break
         }
@@ -170,14 +189,36 @@ pub fn offset_to_position(text: &str, offset: usize) -> lsp_types::Position {
         line += 1;
     }
 
-    let mut character = 0;
-    for ch in text[line_start..offset].chars() {
-        character += ch.len_utf16() as u32;
-    }
+    let character = Encoding::current().len(&text[line_start..offset]);
 
     lsp_types::Position { line, character }
 }
 
+/// The text of a line up to the given position.
+pub fn line_before(text: &str, position: lsp_types::Position) -> Option<&str> {
+    let start = line_offset(text, position.line).ok()?;
+    let end = total_offset(text, position.line, position.character).ok()?;
+    text.get(start..end)
+}
+
+/// Find the one-indexed line and byte column of a byte offset.
+pub fn offset_line_column(text: &str, offset: usize) -> (u32, u16) {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line = before.matches('\n').count() as u32 + 1;
+    (line, (offset - line_start + 1).min(u16::MAX as usize) as u16)
+}
+
+/// Find the byte offset of a one-indexed line and byte column.
+pub fn location_offset(text: &str, line: u32, column: u16) -> Option<usize> {
+    let offset = line_offset(text, line.checked_sub(1)?).ok()? + column.checked_sub(1)? as usize;
+    if offset <= text.len() && text.is_char_boundary(offset) {
+        Some(offset)
+    } else {
+        None
+    }
+}
+
 pub fn get_range(text: &str, range: lsp_types::Range) -> Result<&str, jsonrpc::Error> {
     Ok(&text[
         total_offset(text, range.start.line, range.start.character)?
@@ -219,6 +260,165 @@ pub fn find_word(text: &str, offset: usize) -> &str {
     }
 }
 
+/// Find the identifier surrounding the given position, returning its range
+/// and text.
+pub fn find_word_range(text: &str, position: lsp_types::Position) -> Option<(lsp_types::Range, &str)> {
+    let offset = total_offset(text, position.line, position.character).ok()?;
+    let start = text[..offset].rfind(|ch| !is_ident(ch)).map_or(0, |idx| idx + 1);
+    let end = text[offset..].find(|ch| !is_ident(ch)).map_or(text.len(), |idx| offset + idx);
+    if start == end {
+        return None;
+    }
+    let range = lsp_types::Range::new(offset_to_position(text, start), offset_to_position(text, end));
+    Some((range, &text[start..end]))
+}
+
+/// Find a whole-word occurrence of `name` on the given zero-indexed line,
+/// preferring the first one at or after the given byte column and otherwise
+/// taking the last one before it.
+pub fn find_identifier(text: &str, line: u32, column: usize, name: &str) -> Option<lsp_types::Range> {
+    let start = line_offset(text, line).ok()?;
+    let end = text[start..].find('\n').map_or(text.len(), |idx| start + idx);
+    let idx = find_identifier_in_line(&text[start..end], column, name)?;
+    Some(lsp_types::Range::new(
+        offset_to_position(text, start + idx),
+        offset_to_position(text, start + idx + name.len()),
+    ))
+}
+
+/// Like `find_identifier`, but within a single line and returning the byte
+/// offset into it.
+pub fn find_identifier_in_line(line_text: &str, column: usize, name: &str) -> Option<usize> {
+    let mut found = None;
+    for idx in whole_word_matches(line_text, name) {
+        found = Some(idx);
+        if idx >= column {
+            break;
+        }
+    }
+    found
+}
+
+/// Find the byte offset of the last whole-word occurrence of `name` in the
+/// line which ends at or before the given byte column.
+pub fn find_identifier_before_in_line(line_text: &str, column: usize, name: &str) -> Option<usize> {
+    whole_word_matches(line_text, name)
+        .filter(|&idx| idx + name.len() <= column)
+        .last()
+}
+
+/// Find the byte range of the identifier surrounding the given byte column.
+pub fn word_bounds_in_line(line_text: &str, column: usize) -> Option<(usize, usize)> {
+    if column > line_text.len() || !line_text.is_char_boundary(column) {
+        return None;
+    }
+    let start = line_text[..column].rfind(|ch| !is_ident(ch)).map_or(0, |idx| idx + 1);
+    let end = line_text[column..].find(|ch| !is_ident(ch)).map_or(line_text.len(), |idx| column + idx);
+    if start == end {
+        None
+    } else {
+        Some((start, end))
+    }
+}
+
+fn whole_word_matches<'a>(line_text: &'a str, name: &'a str) -> impl Iterator<Item=usize> + 'a {
+    line_text.match_indices(name).map(|(idx, _)| idx).filter(move |&idx| {
+        let before = line_text[..idx].chars().next_back();
+        let after = line_text[idx + name.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+/// Guess the indentation style of a file: a tab, or the smallest indent of
+/// the space-indented lines if those are in the majority.
+pub fn detect_indent(text: &str) -> String {
+    let mut tabs = 0;
+    let mut spaced = 0;
+    let mut smallest = usize::MAX;
+    for line in text.lines() {
+        if line.starts_with('\t') {
+            tabs += 1;
+        } else {
+            let count = line.len() - line.trim_start_matches(' ').len();
+            if count > 0 && count < line.len() {
+                spaced += 1;
+                smallest = smallest.min(count);
+            }
+        }
+    }
+    if spaced > tabs {
+        " ".repeat(smallest)
+    } else {
+        "\t".to_owned()
+    }
+}
+
+/// If the text of a proc call argument starts with `name =`, return that
+/// name. Leading commas and parentheses are ignored.
+pub fn keyword_argument_name(text: &str) -> Option<&str> {
+    let text = text.trim_start_matches(['(', ',']).trim_start();
+    let end = text.find(|ch| !is_ident(ch)).unwrap_or(text.len());
+    let (name, rest) = text.split_at(end);
+    let rest = rest.trim_start();
+    if is_valid_ident(name) && rest.starts_with('=') && !rest.starts_with("==") {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Split the arguments of a macro call whose name ends at the given byte
+/// offset, trimmed as written. Returns `None` unless a complete argument list
+/// follows.
+pub fn call_arguments(text: &str, offset: usize) -> Option<Vec<&str>> {
+    let rest = text.get(offset..)?;
+    let open = text.len() - rest.trim_start_matches([' ', '\t']).len();
+    if !text[open..].starts_with('(') {
+        return None;
+    }
+
+    let mut args = Vec::new();
+    let mut start = open + 1;
+    let mut depth = 0;
+    let mut quote = None;
+    let mut chars = text[open..].char_indices();
+    while let Some((idx, ch)) = chars.next() {
+        let idx = open + idx;
+        if let Some(end) = quote {
+            if ch == '\\' {
+                chars.next();
+            } else if ch == end {
+                quote = None;
+            }
+            continue;
+        }
+        match ch {
+            '"' | '\'' => quote = Some(ch),
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    let last = text[start..idx].trim();
+                    if !last.is_empty() || !args.is_empty() {
+                        args.push(last);
+                    }
+                    return Some(args);
+                }
+            }
+            ',' if depth == 1 => {
+                args.push(text[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+pub fn is_valid_ident(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with(|ch: char| ch.is_ascii_digit()) && name.chars().all(is_ident)
+}
+
 fn is_ident(ch: char) -> bool {
     ch.is_ascii_digit() || ch.is_ascii_lowercase() || ch.is_ascii_uppercase() || ch == '_'
 }
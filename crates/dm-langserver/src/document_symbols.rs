@@ -0,0 +1,338 @@
+//! Builds the outline of a single document from its annotations.
+//!
+//! Types, procs, and vars are arranged by the type they belong to rather
+//! than by how they were written, so `/obj/item/gun/proc/fire()` at column
+//! zero is listed under an `/obj/item/gun` node even when the file has no
+//! block for that type.
+
+use lsp_types::{DocumentSymbol, Range, SymbolKind};
+
+use dm::annotation::{Annotation, AnnotationTree};
+use dm::ast::ProcDeclKind;
+use dm::{FileId, Location};
+
+use crate::positions::LineIndex;
+use crate::{document, is_constructor_name};
+
+struct Node {
+    symbol: DocumentSymbol,
+    children: Vec<usize>,
+    /// The type path this node stands for, if it is a type.
+    type_path: Option<Vec<String>>,
+    /// Whether this type node was made up to hold path-prefixed members.
+    synthetic: bool,
+}
+
+#[derive(Default)]
+struct Outline {
+    nodes: Vec<Node>,
+    roots: Vec<usize>,
+    /// The blocks and procs enclosing the current position.
+    stack: Vec<(usize, Location)>,
+}
+
+impl Outline {
+    fn push(&mut self, parent: Option<usize>, node: Node) -> usize {
+        let idx = self.nodes.len();
+        self.nodes.push(node);
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(idx),
+            None => self.roots.push(idx),
+        }
+        idx
+    }
+
+    fn close_until(&mut self, start: Location) {
+        while self.stack.last().is_some_and(|&(_, end)| end < start) {
+            self.stack.pop();
+        }
+    }
+
+    /// The innermost enclosing type block.
+    fn enclosing_type(&self) -> Option<(usize, &[String])> {
+        self.stack.iter().rev()
+            .find_map(|&(idx, _)| self.nodes[idx].type_path.as_deref().map(|path| (idx, path)))
+    }
+
+    /// The innermost enclosing proc, if nothing else is open inside it.
+    fn enclosing_proc(&self) -> Option<usize> {
+        let &(idx, _) = self.stack.last()?;
+        (self.nodes[idx].symbol.kind != SymbolKind::CLASS).then_some(idx)
+    }
+
+    /// Find or create the node that members of `type_path` belong under.
+    fn container_for(&mut self, type_path: &[String], range: Range, selection_range: Range) -> Option<usize> {
+        if type_path.is_empty() {
+            return None;
+        }
+        let (parent, prefix_len) = match self.enclosing_type() {
+            Some((idx, path)) if path == type_path => return Some(idx),
+            Some((idx, path)) if type_path.starts_with(path) => (Some(idx), path.len()),
+            _ => (None, 0),
+        };
+
+        // Consecutive members of the same type share one node.
+        let siblings = match parent {
+            Some(parent) => &self.nodes[parent].children,
+            None => &self.roots,
+        };
+        if let Some(&last) = siblings.last() {
+            let node = &mut self.nodes[last];
+            if node.synthetic && node.type_path.as_deref() == Some(type_path) {
+                node.symbol.range.end = node.symbol.range.end.max(range.end);
+                return Some(last);
+            }
+        }
+
+        let name = relative_name(type_path, prefix_len);
+        Some(self.push(parent, Node {
+            symbol: symbol(name, None, SymbolKind::CLASS, range, selection_range),
+            children: Vec::new(),
+            type_path: Some(type_path.to_owned()),
+            synthetic: true,
+        }))
+    }
+
+    fn finish(mut self) -> Vec<DocumentSymbol> {
+        let roots = std::mem::take(&mut self.roots);
+        roots.into_iter().map(|idx| self.take(idx)).collect()
+    }
+
+    fn take(&mut self, idx: usize) -> DocumentSymbol {
+        let children = std::mem::take(&mut self.nodes[idx].children);
+        let children: Vec<DocumentSymbol> = children.into_iter().map(|child| self.take(child)).collect();
+        let mut symbol = self.nodes[idx].symbol.clone();
+        if !children.is_empty() || symbol.kind != SymbolKind::FIELD {
+            symbol.children = Some(children);
+        }
+        symbol
+    }
+}
+
+/// Build the outline of a file from its annotations and contents.
+pub fn document_symbols(annotations: &AnnotationTree, file_id: FileId, text: &str) -> Vec<DocumentSymbol> {
+    let lines = LineIndex::new(text);
+    let location_to_range = |location| lines.range(location);
+    let span_to_range = |span| lines.span(span);
+
+    let start = Location { file: file_id, line: 0, column: 0 };
+    let end = Location { file: file_id, line: !0, column: !0 };
+    let mut all: Vec<_> = annotations.get_range(start..end)
+        .map(|(range, annotation)| (range.start, range.end, annotation))
+        .collect();
+    all.sort_by_key(|&(start, end, _)| (start, std::cmp::Reverse(end)));
+
+    // The full extent of a block starts at its header, which is the last
+    // tree path before its first entry.
+    let tree_paths: Vec<(Location, Location)> = all.iter()
+        .filter(|(_, _, annotation)| matches!(annotation, Annotation::TreePath(..)))
+        .map(|&(start, end, _)| (start, end))
+        .collect();
+    // The name of each entry is the last identifier in its path.
+    let name_at = |start: Location| -> Range {
+        let path_end = tree_paths.iter()
+            .find(|&&(path_start, _)| path_start == start)
+            .map_or(start, |&(_, end)| end);
+        all.iter()
+            .filter(|&&(seq_start, seq_end, annotation)| {
+                matches!(annotation, Annotation::InSequence(_)) && seq_start >= start && seq_end == path_end
+            })
+            .map(|&(seq_start, seq_end, _)| span_to_range(seq_start..Location { column: seq_end.column + 1, ..seq_end }))
+            .next_back()
+            .unwrap_or_else(|| location_to_range(start))
+    };
+
+    // Blocks and the entries inside them start at the same place, so visit
+    // blocks first.
+    // Each entry is its full extent, plus the end of its header for procs.
+    let mut entries: Vec<(Location, Location, Location, &Annotation)> = Vec::new();
+    for &(start, end, annotation) in all.iter() {
+        match annotation {
+            Annotation::TreeBlock(_) => {
+                let header = tree_paths.partition_point(|&(path_start, _)| path_start < start);
+                let header_start = header.checked_sub(1).map_or(start, |i| tree_paths[i].0);
+                entries.push((header_start, end, end, annotation));
+            },
+            Annotation::ProcHeader(..) => {
+                // Pair the header with its body to cover the whole proc.
+                let body_end = all[all.partition_point(|&(body_start, _, _)| body_start <= end)..].iter()
+                    .find(|(_, _, body)| matches!(body, Annotation::ProcBody(..)))
+                    .map_or(end, |&(_, body_end, _)| body_end);
+                entries.push((start, body_end, end, annotation));
+            },
+            Annotation::Variable(_) |
+            Annotation::LocalVarScope(..) |
+            Annotation::MacroDefinition(_) => entries.push((start, end, end, annotation)),
+            _ => {}
+        }
+    }
+    entries.sort_by_key(|&(start, end, _, annotation)| (start, std::cmp::Reverse(end), !matches!(annotation, Annotation::TreeBlock(_))));
+
+    let mut outline = Outline::default();
+    for (start, end, header_end, annotation) in entries {
+        outline.close_until(start);
+        let range = span_to_range(start..end);
+        match annotation {
+            Annotation::TreeBlock(path) => {
+                // `var/` and `proc/` blocks only group their entries, which
+                // find their own place by path.
+                if path.is_empty() || path.iter().any(|part| part == "var" || ProcDeclKind::from_name(part).is_some()) {
+                    continue;
+                }
+                let (parent, prefix_len) = match outline.enclosing_type() {
+                    Some((idx, parent_path)) if path.starts_with(parent_path) => (Some(idx), parent_path.len()),
+                    _ => (None, 0),
+                };
+                let idx = outline.push(parent, Node {
+                    symbol: symbol(relative_name(path, prefix_len), None, SymbolKind::CLASS, range, name_at(start)),
+                    children: Vec::new(),
+                    type_path: Some(path.clone()),
+                    synthetic: false,
+                });
+                outline.stack.push((idx, end));
+            },
+            Annotation::Variable(path) => {
+                let Some((name, type_path)) = path.split_last() else { continue };
+                let selection_range = name_at(start);
+                let parent = outline.container_for(type_path, range, selection_range);
+                outline.push(parent, Node {
+                    symbol: symbol(name.clone(), None, SymbolKind::FIELD, range, selection_range),
+                    children: Vec::new(),
+                    type_path: None,
+                    synthetic: false,
+                });
+            },
+            Annotation::ProcHeader(path, _) => {
+                let Some((name, mut type_path)) = path.split_last() else { continue };
+                if let Some((last, rest)) = type_path.split_last() {
+                    if ProcDeclKind::from_name(last).is_some() {
+                        type_path = rest;
+                    }
+                }
+                let kind = if type_path.is_empty() {
+                    SymbolKind::FUNCTION
+                } else if is_constructor_name(name) {
+                    SymbolKind::CONSTRUCTOR
+                } else {
+                    SymbolKind::METHOD
+                };
+                let selection_range = name_at(start);
+                let parent = outline.container_for(type_path, range, selection_range);
+                let idx = outline.push(parent, Node {
+                    symbol: symbol(name.clone(), parameter_list(text, start, header_end), kind, range, selection_range),
+                    children: Vec::new(),
+                    type_path: None,
+                    synthetic: false,
+                });
+                outline.stack.push((idx, end));
+            },
+            Annotation::LocalVarScope(_, name) => {
+                if let Some(parent) = outline.enclosing_proc() {
+                    let selection_range = location_to_range(start);
+                    outline.push(Some(parent), Node {
+                        symbol: symbol(name.clone(), None, SymbolKind::VARIABLE, range, selection_range),
+                        children: Vec::new(),
+                        type_path: None,
+                        synthetic: false,
+                    });
+                }
+            },
+            Annotation::MacroDefinition(name) => {
+                outline.push(None, Node {
+                    symbol: symbol(name.clone(), None, SymbolKind::CONSTANT, range, location_to_range(start)),
+                    children: Vec::new(),
+                    type_path: None,
+                    synthetic: false,
+                });
+            },
+            _ => {}
+        }
+    }
+    outline.finish()
+}
+
+#[allow(deprecated)]  // DocumentSymbol::deprecated is... deprecated. But we need to provide a `None` anyways.
+fn symbol(name: String, detail: Option<String>, kind: SymbolKind, range: Range, selection_range: Range) -> DocumentSymbol {
+    DocumentSymbol {
+        name,
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range,
+        children: None,
+    }
+}
+
+/// Name a type relative to the type it is nested in, or by its full path.
+fn relative_name(path: &[String], prefix_len: usize) -> String {
+    if prefix_len == 0 {
+        format!("/{}", path.join("/"))
+    } else {
+        path[prefix_len..].join("/")
+    }
+}
+
+/// The parameter list of a proc header as written, on one line.
+fn parameter_list(text: &str, start: Location, end: Location) -> Option<String> {
+    let start = document::location_offset(text, start.line, start.column)?;
+    let end = document::location_offset(text, end.line, end.column)?;
+    let header = text.get(start..=end.min(text.len().saturating_sub(1)))?;
+    let open = header.find('(')?;
+    let close = header.rfind(')')?;
+    let params = header.get(open + 1..close)?;
+    Some(format!("({})", params.split_whitespace().collect::<Vec<_>>().join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline(code: &str) -> Vec<DocumentSymbol> {
+        let context = Default::default();
+        let lexer = dm::lexer::Lexer::new(&context, Default::default(), code.as_bytes());
+        let indent = dm::indents::IndentProcessor::new(&context, lexer);
+        let mut annotations = AnnotationTree::default();
+        dm::parser::Parser::new(&context, indent).parse_annotations_only(&mut annotations);
+        document_symbols(&annotations, Default::default(), code)
+    }
+
+    fn names(symbols: &[DocumentSymbol]) -> Vec<String> {
+        symbols.iter().map(|symbol| match symbol.children.as_deref() {
+            Some(children) if !children.is_empty() => format!("{} [{}]", symbol.name, names(children).join(", ")),
+            _ => symbol.name.clone(),
+        }).collect()
+    }
+
+    #[test]
+    fn nesting_by_type() {
+        let symbols = outline(r#"
+/obj/item
+    var/force = 5
+    proc/attack(mob/target,
+        amount = 1)
+        var/x = 1
+        return x
+
+/obj/item/gun/proc/fire(atom/target)
+    return
+/obj/item/gun/var/ammo = 3
+
+/obj/item/gun
+    name = "gun"
+"#.trim());
+        assert_eq!(names(&symbols), vec![
+            "/obj/item [force, attack [x]]",
+            "/obj/item/gun [fire, ammo]",
+            "/obj/item/gun [name]",
+        ]);
+        let attack = &symbols[0].children.as_ref().unwrap()[1];
+        assert_eq!(attack.detail.as_deref(), Some("(mob/target, amount = 1)"));
+        assert_eq!(attack.selection_range.start.line, 2);
+        assert_eq!(attack.selection_range.start.character, 9);
+        assert_eq!(attack.selection_range.end.character, 15);
+        assert_eq!(attack.range.start.character, 4);
+        assert_eq!(symbols[0].range.start.line, 0);
+    }
+}
@@ -50,12 +50,192 @@ pub struct ObjectTreeProc {
     pub is_verb: Option<bool>,
 }
 
+pub enum ObjectTreeRoot {}
+impl Request for ObjectTreeRoot {
+    const METHOD: &'static str = "experimental/objectTree";
+    type Params = ObjectTreeRootParams;
+    type Result = Option<ObjectTreeNode>;
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectTreeRootParams {
+    /// A document in the workspace whose tree is wanted.
+    pub text_document: Option<lsp_types::TextDocumentIdentifier>,
+    /// The most children to list.
+    pub limit: Option<usize>,
+}
+
+pub enum ObjectTreeExpand {}
+impl Request for ObjectTreeExpand {
+    const METHOD: &'static str = "experimental/objectTreeExpand";
+    type Params = ObjectTreeExpandParams;
+    type Result = Option<ObjectTreeNode>;
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectTreeExpandParams {
+    pub text_document: Option<lsp_types::TextDocumentIdentifier>,
+    pub path: String,
+    /// How many children to skip, from a previous response's `nextOffset`.
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+/// One type, with its vars and procs and a page of its children.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectTreeNode {
+    pub path: String,
+    pub name: String,
+    pub parent: Option<String>,
+    pub location: Option<lsp_types::Location>,
+    pub vars: Vec<ObjectTreeNodeVar>,
+    pub procs: Vec<ObjectTreeNodeProc>,
+    pub child_count: usize,
+    pub children: Vec<ObjectTreeChild>,
+    /// The offset of the next page of children, if there are more.
+    pub next_offset: Option<usize>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectTreeChild {
+    pub path: String,
+    pub name: String,
+    pub location: Option<lsp_types::Location>,
+    pub child_count: usize,
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectTreeNodeVar {
+    pub name: String,
+    pub location: Option<lsp_types::Location>,
+    pub is_declaration: bool,
+    /// The var's value, if it folds to a constant.
+    pub value: Option<String>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectTreeNodeProc {
+    pub name: String,
+    pub location: Option<lsp_types::Location>,
+    pub is_declaration: bool,
+    pub is_verb: bool,
+}
+
+pub enum ObjectTreeChanged {}
+impl Notification for ObjectTreeChanged {
+    const METHOD: &'static str = "experimental/objectTreeChanged";
+    type Params = ObjectTreeChangedParams;
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectTreeChangedParams {
+    /// The workspace folder whose tree was rebuilt.
+    pub root: Option<url::Url>,
+    pub type_count: usize,
+}
+
+/// `$/progress` carrying a batch of references, for requests which sent a
+/// `partialResultToken`.
+pub enum ReferencesPartialResult {}
+impl Notification for ReferencesPartialResult {
+    const METHOD: &'static str = "$/progress";
+    type Params = PartialResultProgress<Vec<lsp_types::Location>>;
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PartialResultProgress<T> {
+    pub token: lsp_types::ProgressToken,
+    pub value: T,
+}
+
 pub enum Reparse {}
 impl Notification for Reparse {
     const METHOD: &'static str = "experimental/dreammaker/reparse";
     type Params = lsp_types::InitializedParams;
 }
 
+pub enum Status {}
+impl Request for Status {
+    const METHOD: &'static str = "experimental/status";
+    type Params = StatusParams;
+    type Result = StatusResult;
+}
+
+/// Rebuild every environment from scratch, answering once it is done.
+pub enum ReparseEnvironment {}
+impl Request for ReparseEnvironment {
+    const METHOD: &'static str = "experimental/reparse";
+    type Params = StatusParams;
+    type Result = StatusResult;
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusParams {
+    /// A document in the workspace whose status is wanted.
+    pub text_document: Option<lsp_types::TextDocumentIdentifier>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatusResult {
+    pub root: Option<url::Url>,
+    /// The loaded `.dme` file, if any.
+    pub environment: Option<url::Url>,
+    pub file_count: usize,
+    pub type_count: usize,
+    pub proc_count: usize,
+    /// The diagnostics last published for the whole environment.
+    pub diagnostics: DiagnosticCounts,
+    /// When the last rebuild started and finished, in milliseconds since the
+    /// Unix epoch.
+    pub rebuild_started: Option<u64>,
+    pub rebuild_finished: Option<u64>,
+    pub references: IndexStatus,
+}
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct DiagnosticCounts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub info: usize,
+    pub hints: usize,
+}
+/// Whether find-references can answer, and how well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IndexStatus {
+    /// No environment is loaded.
+    Unavailable,
+    /// Being built for the first time, or rebuilt after a reparse.
+    Building,
+    /// Built, but files have changed since.
+    Stale,
+    Ready,
+}
+
+/// Size and complexity metrics of the procs in the environment or a file.
+pub enum ProcMetrics {}
+impl Request for ProcMetrics {
+    const METHOD: &'static str = "experimental/dreammaker/procMetrics";
+    type Params = ProcMetricsParams;
+    type Result = Vec<ProcMetricsEntry>;
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcMetricsParams {
+    /// Only measure the procs defined in this document.
+    pub text_document: Option<lsp_types::TextDocumentIdentifier>,
+    /// The metric to sort by, largest first. Defaults to `complexity`.
+    pub sort: Option<String>,
+    pub limit: Option<usize>,
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProcMetricsEntry {
+    pub path: String,
+    pub location: lsp_types::Location,
+    pub complexity: usize,
+    pub statements: usize,
+    pub nesting: usize,
+    pub parameters: usize,
+}
+
 pub enum StartDebugger {}
 impl Request for StartDebugger {
     const METHOD: &'static str = "experimental/dreammaker/startDebugger";
@@ -71,3 +251,55 @@ pub struct StartDebuggerParams {
 pub struct StartDebuggerResult {
     pub port: u16,
 }
+
+pub enum TypeHierarchyPrepare {}
+impl Request for TypeHierarchyPrepare {
+    const METHOD: &'static str = "textDocument/prepareTypeHierarchy";
+    type Params = TypeHierarchyPrepareParams;
+    type Result = Option<Vec<TypeHierarchyItem>>;
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeHierarchyPrepareParams {
+    #[serde(flatten)]
+    pub text_document_position_params: lsp_types::TextDocumentPositionParams,
+}
+
+pub enum TypeHierarchySupertypes {}
+impl Request for TypeHierarchySupertypes {
+    const METHOD: &'static str = "typeHierarchy/supertypes";
+    type Params = TypeHierarchyParams;
+    type Result = Option<Vec<TypeHierarchyItem>>;
+}
+
+pub enum TypeHierarchySubtypes {}
+impl Request for TypeHierarchySubtypes {
+    const METHOD: &'static str = "typeHierarchy/subtypes";
+    type Params = TypeHierarchyParams;
+    type Result = Option<Vec<TypeHierarchyItem>>;
+}
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TypeHierarchyParams {
+    pub item: TypeHierarchyItem,
+}
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeHierarchyItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub uri: url::Url,
+    pub range: lsp_types::Range,
+    pub selection_range: lsp_types::Range,
+    /// The type's path, used to resolve supertypes and subtypes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+/// Advertise capabilities which `lsp_types` has no fields for.
+pub fn extend_capabilities(initialize_result: &mut serde_json::Value) {
+    if let Some(capabilities) = initialize_result.get_mut("capabilities").and_then(|caps| caps.as_object_mut()) {
+        capabilities.insert("typeHierarchyProvider".to_owned(), serde_json::Value::Bool(true));
+    }
+}
@@ -2,34 +2,201 @@
 
 use foldhash::{HashMap, HashMapExt};
 
+
 use dm::ast::*;
+use dm::intern::{intern, Interned};
 use dm::objtree::*;
-use dm::Location;
+use dm::{FileId, FileList, Location};
+
+/// How often the cancellation check is consulted by long queries.
+const CANCEL_CHECK_INTERVAL: usize = 1024;
 
 pub struct ReferencesTable {
-    uses: HashMap<SymbolId, References>,
+    references: SymbolLocations,
+    implementations: SymbolLocations,
     symbols: SymbolIdSource,
+    /// References which assign to their symbol, sorted.
+    writes: Vec<(SymbolId, Location)>,
+    /// Declarations and uses of named local variables and parameters,
+    /// sorted by location.
+    locals: Vec<(Location, SymbolId)>,
+    local_names: HashMap<SymbolId, String>,
+    scopes: Vec<LocalScope>,
+    /// Every proc call made from within a proc body, sorted by caller.
+    calls: Vec<CallSite>,
+    /// Indices into `calls`, sorted by callee.
+    incoming: Vec<u32>,
+    procs: Vec<ProcKey>,
+    proc_ids: HashMap<ProcKey, u32>,
+    /// Every definition of each declared proc, sorted by type path.
+    overrides: HashMap<SymbolId, Vec<(ProcKey, Location)>>,
 }
 
+/// Identifies a single proc definition, which may be an override.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ProcKey {
+    pub path: Interned,
+    pub name: Interned,
+    pub index: usize,
+}
+
+impl ProcKey {
+    pub fn new(proc: ProcRef) -> ProcKey {
+        ProcKey {
+            path: intern(&proc.ty().path),
+            name: intern(proc.name()),
+            index: proc.index(),
+        }
+    }
+
+    pub fn resolve<'o>(&self, objtree: &'o ObjectTree) -> Option<ProcRef<'o>> {
+        objtree.find(&self.path)?
+            .iter_self_procs()
+            .find(|proc| proc.name() == self.name && proc.index() == self.index)
+    }
+}
+
+/// An inlay hint found while walking a proc body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    pub location: Location,
+    pub kind: HintKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HintKind {
+    /// The inferred type of an untyped local, shown after its name.
+    Type { var_name: String, type_path: String },
+    /// The name of the parameter a positional argument is passed to.
+    Parameter(String),
+    /// The arguments implicitly forwarded by a bare `..()`.
+    ParentArguments(Vec<String>),
+}
+
+/// Walk a single proc body and collect its inlay hints.
+pub fn collect_hints(objtree: &ObjectTree, proc: ProcRef) -> Vec<Hint> {
+    let Some(code) = proc.get().code.as_ref() else {
+        return Vec::new();
+    };
+    let mut tab = ReferencesTable::empty();
+    let mut walk = WalkProc::from_proc(&mut tab, objtree, proc);
+    walk.hints = Some(Vec::new());
+    walk.run(proc, code);
+    walk.hints.unwrap_or_default()
+}
+
+/// Walk a single proc body and collect the static type of each term and
+/// follow within it, keyed by the location where it begins. List types
+/// produce both `/list` and their element type.
+pub fn collect_types(objtree: &ObjectTree, proc: ProcRef) -> Vec<(Location, Vec<NodeIndex>)> {
+    let Some(code) = proc.get().code.as_ref() else {
+        return Vec::new();
+    };
+    let mut tab = ReferencesTable::empty();
+    let mut walk = WalkProc::from_proc(&mut tab, objtree, proc);
+    walk.types = Some(Vec::new());
+    walk.run(proc, code);
+    walk.types.unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct CallSite {
+    caller: u32,
+    callee: u32,
+    location: Location,
+}
+
+/// The lines spanned by a proc body and the names of the locals within it.
+struct LocalScope {
+    start: Location,
+    end: Location,
+    names: Vec<String>,
+}
+
+/// The locations of every symbol, stored in a single allocation.
+///
+/// While the table is being built, `(symbol, location)` pairs are appended in
+/// any order. `finish` then sorts and deduplicates them and indexes the run
+/// belonging to each symbol, and the sub-run of that within each file.
 #[derive(Default)]
-struct References {
-    references: Vec<Location>,
-    implementations: Vec<Location>,
+struct SymbolLocations {
+    pending: Vec<(SymbolId, Location)>,
+    locations: Vec<Location>,
+    index: HashMap<SymbolId, (u32, u32)>,
+    by_file: HashMap<(FileId, SymbolId), (u32, u32)>,
+}
+
+impl SymbolLocations {
+    fn push(&mut self, symbol: SymbolId, location: Location) {
+        self.pending.push((symbol, location));
+    }
+
+    fn finish(&mut self) {
+        let mut pending = std::mem::take(&mut self.pending);
+        pending.sort_unstable();
+        pending.dedup();
+
+        self.locations = Vec::with_capacity(pending.len());
+        for (symbol, location) in pending {
+            let idx = self.locations.len() as u32;
+            self.index.entry(symbol)
+                .and_modify(|run| run.1 = idx + 1)
+                .or_insert((idx, idx + 1));
+            self.by_file.entry((location.file, symbol))
+                .and_modify(|run| run.1 = idx + 1)
+                .or_insert((idx, idx + 1));
+            self.locations.push(location);
+        }
+        self.index.shrink_to_fit();
+        self.by_file.shrink_to_fit();
+    }
+
+    fn get(&self, symbol: SymbolId) -> &[Location] {
+        match self.index.get(&symbol) {
+            None => &[],
+            Some(&(start, end)) => &self.locations[start as usize..end as usize],
+        }
+    }
+
+    fn get_in_file(&self, symbol: SymbolId, file: FileId) -> &[Location] {
+        match self.by_file.get(&(file, symbol)) {
+            None => &[],
+            Some(&(start, end)) => &self.locations[start as usize..end as usize],
+        }
+    }
 }
 
 impl ReferencesTable {
-    pub fn new(objtree: &ObjectTree) -> Self {
-        let mut tab = ReferencesTable {
-            uses: HashMap::new(),
+    fn empty() -> Self {
+        ReferencesTable {
+            references: Default::default(),
+            implementations: Default::default(),
             symbols: SymbolIdSource::new(SymbolIdCategory::LocalVars),
-        };
+            writes: Vec::new(),
+            calls: Vec::new(),
+            incoming: Vec::new(),
+            procs: Vec::new(),
+            proc_ids: HashMap::new(),
+            overrides: HashMap::new(),
+            locals: Vec::new(),
+            local_names: HashMap::new(),
+            scopes: Vec::new(),
+        }
+    }
+
+    pub fn new(objtree: &ObjectTree) -> Self {
+        ReferencesTable::with_progress(objtree, &mut |_, _| {})
+    }
+
+    /// Build the table, reporting how many of the types have been walked.
+    pub fn with_progress(objtree: &ObjectTree, progress: &mut dyn FnMut(usize, usize)) -> Self {
+        let mut tab = ReferencesTable::empty();
+        let total = objtree.iter_types().count();
+        let mut done = 0;
 
         // Insert the "definition" locations for the types and such
-        objtree.root().recurse(&mut |ty| {
-            tab.uses.insert(ty.id, References {
-                references: vec![],
-                implementations: vec![ty.location],
-            });
+        for ty in objtree.root().self_and_descendants() {
+            tab.impl_symbol(ty.id, ty.location);
             for (name, var) in ty.vars.iter() {
                 if let Some(decl) = ty.get_var_declaration(name) {
                     tab.impl_symbol(decl.id, var.value.location);
@@ -40,9 +207,19 @@ impl ReferencesTable {
                     tab.impl_symbol(decl.id, proc.value.first().unwrap().location);
                 }
             }
-        });
+            for proc in ty.iter_self_procs() {
+                if let Some(decl) = ty.get_proc_declaration(proc.name()) {
+                    tab.overrides.entry(decl.id).or_default().push((ProcKey::new(proc), proc.location));
+                }
+            }
+        }
+        for each in tab.overrides.values_mut() {
+            each.sort_unstable();
+        }
 
-        objtree.root().recurse(&mut |ty| {
+        for ty in objtree.root().self_and_descendants() {
+            progress(done, total);
+            done += 1;
             for (name, var) in ty.vars.iter() {
                 if let Some(ref expr) = var.value.expression {
                     let mut walk = WalkProc::from_ty(&mut tab, objtree, ty);
@@ -59,46 +236,235 @@ impl ReferencesTable {
                     WalkProc::from_proc(&mut tab, objtree, proc).run(proc, code);
                 }
             }
-        });
+        }
 
         // Sublime Text client does not sort these itself, so sort them here.
-        for value in tab.uses.values_mut() {
-            value.references.sort();
-            value.implementations.sort();
-        }
+        tab.references.finish();
+        tab.implementations.finish();
+        tab.writes.sort_unstable();
+        tab.writes.dedup();
+        tab.locals.sort_unstable();
+        tab.locals.dedup();
+        tab.calls.sort_unstable();
+        tab.calls.dedup();
+        tab.incoming = (0..tab.calls.len() as u32).collect();
+        tab.incoming.sort_unstable_by_key(|&idx| {
+            let call = tab.calls[idx as usize];
+            (call.callee, call.caller, call.location)
+        });
 
         tab
     }
 
     pub fn find_references(&self, symbol: SymbolId, _declaration: bool) -> &[Location] {
-        match self.uses.get(&symbol) {
-            None => &[],
-            Some(list) => &list.references,
-        }
+        self.references.get(symbol)
+    }
+
+    /// Find the references to a symbol, grouped by file.
+    ///
+    /// Files are sorted by path, and the locations within each file are
+    /// sorted and unique. Builtin locations are omitted.
+    pub fn find_references_grouped<'a>(&'a self, symbol: SymbolId, files: &FileList) -> impl Iterator<Item=(FileId, &'a [Location])> + 'a {
+        // The references are sorted by location, which sorts by file first.
+        let mut runs: Vec<(FileId, &'a [Location])> = self.find_references(symbol, false)
+            .chunk_by(|a, b| a.file == b.file)
+            .filter(|run| !run[0].is_builtins())
+            .map(|run| (run[0].file, run))
+            .collect();
+        runs.sort_by_cached_key(|&(file, _)| files.get_path(file).to_owned());
+        runs.into_iter()
     }
 
     pub fn find_implementations(&self, symbol: SymbolId) -> &[Location] {
-        match self.uses.get(&symbol) {
-            None => &[],
-            Some(list) => &list.implementations,
+        self.implementations.get(symbol)
+    }
+
+    /// Find every definition of the proc with the given declaration, across
+    /// the declaring type and all of its subtypes.
+    pub fn find_overrides(&self, symbol: SymbolId) -> &[(ProcKey, Location)] {
+        self.overrides.get(&symbol).map_or(&[], Vec::as_slice)
+    }
+
+    /// Find the uses and implementations of a symbol within a single file,
+    /// sorted, along with whether each one is assigned to.
+    pub fn find_highlights(&self, symbol: SymbolId, file: FileId) -> Vec<(Location, bool)> {
+        let mut result: Vec<(Location, bool)> = self.references.get_in_file(symbol, file).iter()
+            .map(|&loc| (loc, self.writes.binary_search(&(symbol, loc)).is_ok()))
+            .chain(self.implementations.get_in_file(symbol, file).iter().map(|&loc| (loc, false)))
+            .collect();
+        result.sort_unstable_by_key(|&(loc, _)| loc);
+        result.dedup_by_key(|&mut (loc, _)| loc);
+        result
+    }
+
+    /// Find the procs which call the given proc, along with the locations of
+    /// the calls within each, ordered by first call.
+    pub fn incoming_calls(&self, proc: &ProcKey) -> Vec<(&ProcKey, Vec<Location>)> {
+        let Some(&id) = self.proc_ids.get(proc) else {
+            return Vec::new();
+        };
+        let start = self.incoming.partition_point(|&idx| self.calls[idx as usize].callee < id);
+        let calls = self.incoming[start..].iter()
+            .map(|&idx| self.calls[idx as usize])
+            .take_while(|call| call.callee == id);
+        self.group_calls(calls, |call| call.caller)
+    }
+
+    /// Find the procs called by the given proc, along with the locations of
+    /// the calls to each, ordered by first call.
+    pub fn outgoing_calls(&self, proc: &ProcKey) -> Vec<(&ProcKey, Vec<Location>)> {
+        let Some(&id) = self.proc_ids.get(proc) else {
+            return Vec::new();
+        };
+        let start = self.calls.partition_point(|call| call.caller < id);
+        let mut calls: Vec<CallSite> = self.calls[start..].iter()
+            .take_while(|call| call.caller == id)
+            .copied()
+            .collect();
+        calls.sort_unstable_by_key(|call| (call.callee, call.location));
+        self.group_calls(calls.into_iter(), |call| call.callee)
+    }
+
+    fn group_calls(&self, calls: impl Iterator<Item=CallSite>, other: impl Fn(&CallSite) -> u32) -> Vec<(&ProcKey, Vec<Location>)> {
+        let mut result: Vec<(&ProcKey, Vec<Location>)> = Vec::new();
+        let mut last = None;
+        for call in calls {
+            let id = other(&call);
+            match result.last_mut() {
+                Some((_, locations)) if last == Some(id) => locations.push(call.location),
+                _ => result.push((&self.procs[id as usize], vec![call.location])),
+            }
+            last = Some(id);
+        }
+        result.sort_by_key(|(_, locations)| locations[0]);
+        result
+    }
+
+    /// Find the named local variable or parameter called `name` which is
+    /// declared or used on the same line as `location`, at or before it.
+    pub fn find_local(&self, location: Location, name: &str) -> Option<SymbolId> {
+        let end = self.locals.partition_point(|&(loc, _)| loc <= location);
+        self.locals[..end].iter().rev()
+            .take_while(|(loc, _)| loc.file == location.file && loc.line == location.line)
+            .find(|(_, symbol)| self.local_names.get(symbol).map(String::as_str) == Some(name))
+            .map(|&(_, symbol)| symbol)
+    }
+
+    /// Check whether `name` is already a local variable in a proc containing
+    /// any of the given locations. Returns `None` if `is_cancelled` reports
+    /// that the check is no longer wanted.
+    pub fn local_name_conflicts(&self, locations: &[Location], name: &str, is_cancelled: &dyn Fn() -> bool) -> Option<bool> {
+        for (i, scope) in self.scopes.iter().enumerate() {
+            if i % CANCEL_CHECK_INTERVAL == 0 && is_cancelled() {
+                return None;
+            }
+            let in_scope = scope.names.iter().any(|each| each == name) && locations.iter().any(|loc| {
+                loc.file == scope.start.file && loc.line >= scope.start.line && loc.line <= scope.end.line
+            });
+            if in_scope {
+                return Some(true);
+            }
         }
+        Some(false)
     }
 
     fn new_symbol(&mut self, location: Location) -> SymbolId {
         let id = self.symbols.allocate();
-        self.uses.insert(id, References {
-            references: vec![location],
-            implementations: vec![],
-        });
+        self.references.push(id, location);
+        id
+    }
+
+    fn new_local(&mut self, location: Location, name: &str) -> SymbolId {
+        let id = self.new_symbol(location);
+        self.locals.push((location, id));
+        self.local_names.insert(id, name.to_owned());
         id
     }
 
     fn use_symbol(&mut self, symbol: SymbolId, location: Location) {
-        self.uses.entry(symbol).or_default().references.push(location);
+        self.references.push(symbol, location);
+    }
+
+    fn use_local(&mut self, symbol: SymbolId, location: Location) {
+        self.references.push(symbol, location);
+        if self.local_names.contains_key(&symbol) {
+            self.locals.push((location, symbol));
+        }
     }
 
     fn impl_symbol(&mut self, symbol: SymbolId, location: Location) {
-        self.uses.entry(symbol).or_default().implementations.push(location);
+        self.implementations.push(symbol, location);
+    }
+
+    fn proc_id(&mut self, proc: ProcRef) -> u32 {
+        let key = ProcKey::new(proc);
+        if let Some(&id) = self.proc_ids.get(&key) {
+            return id;
+        }
+        let id = self.procs.len() as u32;
+        self.procs.push(key.clone());
+        self.proc_ids.insert(key, id);
+        id
+    }
+
+    fn call(&mut self, caller: ProcRef, callee: ProcRef, location: Location) {
+        let caller = self.proc_id(caller);
+        let callee = self.proc_id(callee);
+        self.calls.push(CallSite { caller, callee, location });
+    }
+}
+
+/// Resolve a path like `/obj/item`, `/obj/item/proc/attack`, or
+/// `/obj/item/var/name` to the symbol it declares.
+pub fn resolve_symbol_path(objtree: &ObjectTree, path: &str) -> Option<SymbolId> {
+    let parts: Vec<&str> = path.split('/').filter(|part| !part.is_empty()).collect();
+    match parts.iter().position(|&part| part == "proc" || part == "verb" || part == "var") {
+        Some(idx) => {
+            let ty = objtree.type_by_path(&parts[..idx])?;
+            let name = parts[idx + 1..].last()?;
+            if parts[idx] == "var" {
+                ty.get_var_declaration(name).map(|decl| decl.id)
+            } else {
+                ty.get_proc_declaration(name).map(|decl| decl.id)
+            }
+        }
+        None => objtree.type_by_path(&parts).map(|ty| ty.id),
+    }
+}
+
+/// Entry point for `dm-langserver --find-references /type/path`.
+pub fn find_references_main<I: Iterator<Item = String>>(mut args: I) {
+    let path = args.next().expect("must specify a path for --find-references");
+    if let Some(arg) = args.next() {
+        panic!("unknown argument {:?}", arg);
+    }
+
+    let environment = dm::detect_environment_default()
+        .expect("detect .dme error")
+        .expect("did not detect a .dme");
+    let mut ctx = dm::Context::default();
+    ctx.autodetect_config(&environment);
+    let pp = dm::preprocessor::Preprocessor::new(&ctx, environment).expect("i/o error opening .dme");
+    let objtree = {
+        let mut parser = dm::parser::Parser::new(&ctx, dm::indents::IndentProcessor::new(&ctx, pp));
+        parser.enable_procs();
+        parser.parse_object_tree()
+    };
+
+    let symbol = match resolve_symbol_path(&objtree, &path) {
+        Some(symbol) => symbol,
+        None => {
+            eprintln!("no such type, proc, or var: {}", path);
+            std::process::exit(1);
+        }
+    };
+
+    let table = ReferencesTable::new(&objtree);
+    for (file, locations) in table.find_references_grouped(symbol, ctx.file_list()) {
+        let file_path = ctx.file_path(file);
+        for location in locations {
+            println!("{}:{}:{}", file_path.display(), location.line, location.column);
+        }
     }
 }
 
@@ -133,6 +499,9 @@ struct WalkProc<'o> {
     ty: TypeRef<'o>,
     proc: Option<ProcRef<'o>>,
     local_vars: HashMap<String, Local<'o>>,
+    scope_end: Location,
+    hints: Option<Vec<Hint>>,
+    types: Option<Vec<(Location, Vec<NodeIndex>)>>,
 }
 
 impl<'o> WalkProc<'o> {
@@ -168,7 +537,10 @@ impl<'o> WalkProc<'o> {
             objtree,
             ty: proc.ty(),
             proc: Some(proc),
-            local_vars
+            local_vars,
+            scope_end: proc.location,
+            hints: None,
+            types: None,
         }
     }
 
@@ -184,7 +556,10 @@ impl<'o> WalkProc<'o> {
             objtree,
             ty,
             proc: None,
-            local_vars
+            local_vars,
+            scope_end: ty.location,
+            hints: None,
+            types: None,
         }
     }
 
@@ -195,12 +570,23 @@ impl<'o> WalkProc<'o> {
             if let Some(expr) = &param.default {
                 self.visit_expression(param.location, expr, None);
             }
+            if let Some(expr) = &param.in_list {
+                self.visit_expression(param.location, expr, None);
+            }
             self.local_vars.insert(param.name.to_owned(), Local {
                 ty,
-                symbol: self.tab.new_symbol(param.location)
+                symbol: self.tab.new_local(param.location, &param.name)
             });
         }
         self.visit_block(block);
+
+        let mut names: Vec<String> = self.local_vars.keys().cloned().collect();
+        names.sort_unstable();
+        self.tab.scopes.push(LocalScope {
+            start: proc.location,
+            end: self.scope_end,
+            names,
+        });
     }
 
     fn visit_block(&mut self, block: &'o [Spanned<Statement>]) {
@@ -210,6 +596,7 @@ impl<'o> WalkProc<'o> {
     }
 
     fn visit_statement(&mut self, location: Location, statement: &'o Statement) {
+        self.scope_end = self.scope_end.max(location);
         match statement {
             Statement::Expr(expr) => { self.visit_expression(location, expr, None); },
             Statement::Return(expr) => {
@@ -337,12 +724,21 @@ impl<'o> WalkProc<'o> {
         let ty = self.static_type(location, &var_type.type_path);
         self.use_type(location, &ty);
         if let Some(expr) = value {
-            self.visit_expression(location, expr, ty.basic_type());
+            let value_ty = self.visit_expression(location, expr, ty.basic_type());
+            if var_type.type_path.is_empty() {
+                if let Some(value_ty) = value_ty.basic_type().filter(|ty| !ty.is_root()) {
+                    self.hint(location, HintKind::Type {
+                        var_name: name.to_owned(),
+                        type_path: value_ty.path.to_string(),
+                    });
+                }
+            }
         }
-        self.local_vars.insert(name.to_owned(), Local {
-            ty,
-            symbol: self.tab.new_symbol(location),
-        });
+        let symbol = self.tab.new_local(location, name);
+        if value.is_some() {
+            self.tab.writes.push((symbol, location));
+        }
+        self.local_vars.insert(name.to_owned(), Local { ty, symbol });
     }
 
     fn use_type(&mut self, location: Location, ty: &StaticType<'o>) {
@@ -359,18 +755,7 @@ impl<'o> WalkProc<'o> {
     #[allow(clippy::only_used_in_recursion)]
     fn visit_expression(&mut self, location: Location, expression: &'o Expression, type_hint: Option<TypeRef<'o>>) -> StaticType<'o> {
         match expression {
-            Expression::Base { term, follow } => {
-                let base_type_hint = if follow.is_empty() {
-                    type_hint
-                } else {
-                    None
-                };
-                let mut ty = self.visit_term(term.location, &term.elem, base_type_hint);
-                for each in follow.iter() {
-                    ty = self.visit_follow(each.location, ty, &each.elem);
-                }
-                ty
-            },
+            Expression::Base { term, follow } => self.visit_base(term, follow, type_hint).0,
             Expression::BinaryOp { op: BinaryOp::Or, lhs, rhs } => {
                 // It appears that DM does this in more cases than this, but
                 // this is the only case I've seen it used in the wild.
@@ -384,9 +769,17 @@ impl<'o> WalkProc<'o> {
                 let rty = self.visit_expression(location, rhs, None);
                 self.visit_binary(lty, rty, *op)
             },
-            Expression::AssignOp { lhs, rhs, .. } => {
-                let lhs = self.visit_expression(location, lhs, None);
-                self.visit_expression(location, rhs, lhs.basic_type())
+            Expression::AssignOp { op, lhs, rhs, .. } => {
+                let lhs_ty = match &**lhs {
+                    Expression::Base { term, follow } => {
+                        let (ty, target) = self.visit_base(term, follow, None);
+                        self.mark_write(target);
+                        ty
+                    }
+                    _ => self.visit_expression(location, lhs, None),
+                };
+                self.visit_assign(location, &lhs_ty, *op);
+                self.visit_expression(location, rhs, lhs_ty.basic_type())
             },
             Expression::TernaryOp { cond, if_, else_ } => {
                 // TODO: be sensible
@@ -398,22 +791,83 @@ impl<'o> WalkProc<'o> {
         }
     }
 
+    /// Visit a term and its follows, returning their type and the variable
+    /// they name, if any.
+    fn visit_base(&mut self, term: &'o Spanned<Term>, follow: &'o [Spanned<Follow>], type_hint: Option<TypeRef<'o>>) -> (StaticType<'o>, Option<(SymbolId, Location)>) {
+        let base_type_hint = if follow.is_empty() {
+            type_hint
+        } else {
+            None
+        };
+        let (mut ty, mut target) = match (follow.first().map(|each| &each.elem), &term.elem) {
+            (Some(Follow::StaticField(_)) | Some(Follow::ProcReference(_)), _) => (self.visit_scope_term(term.location, &term.elem), None),
+            (_, Term::Ident(name)) => self.visit_ident_symbol(term.location, name),
+            (_, Term::GlobalIdent(name)) => self.visit_global_ident(term.location, name),
+            _ => (self.visit_term(term.location, &term.elem, base_type_hint), None),
+        };
+        self.record_type(term.location, &ty);
+        for each in follow.iter() {
+            if let Follow::Unary(UnaryOp::PreIncr | UnaryOp::PostIncr | UnaryOp::PreDecr | UnaryOp::PostDecr) = each.elem {
+                self.mark_write(target);
+            }
+            (ty, target) = match &each.elem {
+                Follow::Field(_, name) | Follow::StaticField(name) => self.visit_field_symbol(each.location, ty, name),
+                _ => (self.visit_follow(each.location, ty, &each.elem), None),
+            };
+            self.record_type(each.location, &ty);
+        }
+        (ty, target)
+    }
+
+    /// Mark a variable which was just visited as assigned to.
+    fn mark_write(&mut self, target: Option<(SymbolId, Location)>) {
+        if let Some(target) = target {
+            self.tab.writes.push(target);
+        }
+    }
+
     fn visit_ident(&mut self, location: Location, unscoped_name: &'o str) -> StaticType<'o> {
+        self.visit_ident_symbol(location, unscoped_name).0
+    }
+
+    fn visit_ident_symbol(&mut self, location: Location, unscoped_name: &'o str) -> (StaticType<'o>, Option<(SymbolId, Location)>) {
         if let Some(var) = self.local_vars.get(unscoped_name) {
-            self.tab.use_symbol(var.symbol, location);
-            return var.ty.clone();
+            self.tab.use_local(var.symbol, location);
+            return (var.ty.clone(), Some((var.symbol, location)));
         }
         if let Some(decl) = self.ty.get_var_declaration(unscoped_name) {
             self.tab.use_symbol(decl.id, location);
-            self.static_type(location, &decl.var_type.type_path)
+            (self.static_type(location, &decl.var_type.type_path), Some((decl.id, location)))
         } else {
-            StaticType::None
+            (StaticType::None, None)
+        }
+    }
+
+    fn visit_global_ident(&mut self, location: Location, name: &'o str) -> (StaticType<'o>, Option<(SymbolId, Location)>) {
+        if let Some(decl) = self.objtree.root().get_var_declaration(name) {
+            self.tab.use_symbol(decl.id, location);
+            (self.static_type(location, &decl.var_type.type_path), Some((decl.id, location)))
+        } else {
+            (StaticType::None, None)
         }
     }
 
+    /// Visit the left-hand side of `::`, which names a type rather than an
+    /// instance of one.
+    fn visit_scope_term(&mut self, location: Location, term: &'o Term) -> StaticType<'o> {
+        let ty = match term {
+            Term::Prefab(prefab) if prefab.vars.is_empty() => self.visit_prefab(location, prefab),
+            Term::Ident(name) if name == "type" && !self.local_vars.contains_key(name) => Some(self.ty),
+            Term::Ident(name) if name == "parent_type" && !self.local_vars.contains_key(name) => self.ty.parent_type(),
+            _ => return self.visit_term(location, term, None),
+        };
+        ty.map_or(StaticType::None, StaticType::Type)
+    }
+
     fn visit_term(&mut self, location: Location, term: &'o Term, type_hint: Option<TypeRef<'o>>) -> StaticType<'o> {
         match term {
             Term::Null => StaticType::None,
+            Term::Error => StaticType::None,
             Term::Int(_) => StaticType::None,
             Term::Float(_) => StaticType::None,
             Term::String(_) => StaticType::None,
@@ -426,9 +880,9 @@ impl<'o> WalkProc<'o> {
                 StaticType::None
             },
             Term::InterpString(_, parts) => {
-                for (ref expr, _) in parts.iter() {
-                    if let Some(expr) = expr {
-                        self.visit_expression(location, expr, None);
+                for (expr, _) in parts.iter() {
+                    if let Some(ref elem) = expr.elem {
+                        self.visit_expression(expr.location, elem, None);
                     }
                 }
                 StaticType::None
@@ -453,6 +907,12 @@ impl<'o> WalkProc<'o> {
                 }
             },
             Term::ParentCall(args) => {
+                if let (true, Some(proc)) = (args.is_empty(), self.proc) {
+                    let names: Vec<String> = proc.get().parameters.iter().map(|param| param.name.clone()).collect();
+                    if !names.is_empty() {
+                        self.hint(location, HintKind::ParentArguments(names));
+                    }
+                }
                 if let Some(proc) = self.proc.and_then(ProcRef::parent_proc) {
                     // TODO: if args are empty, call w/ same args
                     let src = self.ty;
@@ -534,14 +994,7 @@ impl<'o> WalkProc<'o> {
                     StaticType::None
                 }
             },
-            Term::GlobalIdent(name) => {
-                if let Some(decl) = self.objtree.root().get_var_declaration(name) {
-                    self.tab.use_symbol(decl.id, location);
-                    self.static_type(location, &decl.var_type.type_path)
-                } else {
-                    StaticType::None
-                }
-            },
+            Term::GlobalIdent(name) => self.visit_global_ident(location, name).0,
             Term::__TYPE__ => {
                 self.tab.use_symbol(self.ty.id, location);
                 StaticType::None
@@ -612,16 +1065,15 @@ impl<'o> WalkProc<'o> {
     }
 
     fn visit_field(&mut self, location: Location, lhs: StaticType<'o>, name: &'o str) -> StaticType<'o> {
-        if let Some(ty) = lhs.basic_type() {
-            if let Some(decl) = ty.get_var_declaration(name) {
-                self.tab.use_symbol(decl.id, location);
-                self.static_type(location, &decl.var_type.type_path)
-            } else {
-                StaticType::None
-            }
-        } else {
-            StaticType::None
-        }
+        self.visit_field_symbol(location, lhs, name).0
+    }
+
+    fn visit_field_symbol(&mut self, location: Location, lhs: StaticType<'o>, name: &'o str) -> (StaticType<'o>, Option<(SymbolId, Location)>) {
+        let Some(decl) = lhs.basic_type().and_then(|ty| ty.get_var_declaration(name)) else {
+            return (StaticType::None, None);
+        };
+        self.tab.use_symbol(decl.id, location);
+        (self.static_type(location, &decl.var_type.type_path), Some((decl.id, location)))
     }
 
     fn visit_follow(&mut self, location: Location, lhs: StaticType<'o>, rhs: &'o Follow) -> StaticType<'o> {
@@ -672,7 +1124,27 @@ impl<'o> WalkProc<'o> {
         StaticType::None
     }
 
-    fn visit_call(&mut self, location: Location, src: TypeRef<'o>, proc: ProcRef, args: &'o [Expression], is_exact: bool) -> StaticType<'o> {
+    fn visit_assign(&mut self, location: Location, lhs: &StaticType<'o>, op: AssignOp) {
+        if op == AssignOp::Assign {
+            return;
+        }
+        let Some(ty) = lhs.basic_type() else {
+            return;
+        };
+        // `x += y` calls `operator+=` if it exists anywhere on the type, and
+        // otherwise behaves like `x = x + y`, calling `operator+`.
+        let proc = ty.get_proc(&format!("operator{}", op))
+            .or_else(|| op.binary_op().and_then(|binary| ty.get_proc(&format!("operator{}", binary))));
+        if let Some(decl) = proc.and_then(ProcRef::get_declaration) {
+            self.tab.use_symbol(decl.id, location);
+        }
+    }
+
+    fn visit_call(&mut self, location: Location, src: TypeRef<'o>, proc: ProcRef<'o>, args: &'o [Expression], is_exact: bool) -> StaticType<'o> {
+        if let Some(caller) = self.proc {
+            self.tab.call(caller, proc, location);
+        }
+
         // register use of symbol
         if !is_exact {
             // Only include uses of the symbol by name, not `.()` or `..()`
@@ -683,14 +1155,16 @@ impl<'o> WalkProc<'o> {
         }
 
         // identify and register kwargs used
-        for arg in args {
+        let mut positional = true;
+        for (idx, arg) in args.iter().enumerate() {
             let mut argument_value = arg;
-            if let Expression::AssignOp { op: AssignOp::Assign, lhs, rhs } = arg {
+            if let Expression::AssignOp { op: AssignOp::Assign, lhs, rhs, .. } = arg {
                 match lhs.as_term() {
                     Some(Term::Ident(_name)) |
                     Some(Term::String(_name)) => {
                         // Don't visit_expression the kwarg key.
                         argument_value = rhs;
+                        positional = false;
 
                         // TODO: register a usage of the kwarg symbol here.
                         // Recurse to children too?
@@ -699,16 +1173,67 @@ impl<'o> WalkProc<'o> {
                 }
             }
 
+            if positional && self.hints.is_some() {
+                self.parameter_hint(proc, idx, arg);
+            }
             self.visit_expression(location, argument_value, None);
         }
 
-        StaticType::None
+        match proc.get_declaration().map(|decl| &decl.return_type) {
+            Some(ProcReturnType::TypePath(path)) => self.static_type(location, path),
+            _ => StaticType::None,
+        }
+    }
+
+    fn hint(&mut self, location: Location, kind: HintKind) {
+        if let Some(hints) = self.hints.as_mut() {
+            hints.push(Hint { location, kind });
+        }
+    }
+
+    fn record_type(&mut self, location: Location, ty: &StaticType<'o>) {
+        let Some(types) = self.types.as_mut() else {
+            return;
+        };
+        let mut found = Vec::new();
+        let mut current = ty;
+        loop {
+            match current {
+                StaticType::None => break,
+                StaticType::Type(ty) => {
+                    found.push(ty.index());
+                    break;
+                },
+                StaticType::List { list, keys } => {
+                    found.push(list.index());
+                    current = keys;
+                },
+            }
+        }
+        if !found.is_empty() {
+            types.push((location, found));
+        }
+    }
+
+    fn parameter_hint(&mut self, proc: ProcRef<'o>, idx: usize, arg: &Expression) {
+        let Some(param) = proc.get().parameters.get(idx) else {
+            return;
+        };
+        // Naming an argument after its parameter is already clear enough.
+        if let Some(Term::Ident(name)) = arg.as_term() {
+            if *name == param.name {
+                return;
+            }
+        }
+        if let Some(location) = expression_location(arg) {
+            self.hint(location, HintKind::Parameter(param.name.clone()));
+        }
     }
 
     fn visit_arguments(&mut self, location: Location, args: &'o [Expression]) {
         for arg in args {
             let mut argument_value = arg;
-            if let Expression::AssignOp { op: AssignOp::Assign, lhs, rhs } = arg {
+            if let Expression::AssignOp { op: AssignOp::Assign, lhs, rhs, .. } = arg {
                 match lhs.as_term() {
                     Some(Term::Ident(_name)) |
                     Some(Term::String(_name)) => {
@@ -744,3 +1269,402 @@ impl<'o> WalkProc<'o> {
         }
     }
 }
+
+/// Find the location of the leftmost term in an expression.
+fn expression_location(expr: &Expression) -> Option<Location> {
+    match expr {
+        Expression::Base { term, .. } => Some(term.location),
+        Expression::BinaryOp { lhs, .. } => expression_location(lhs),
+        Expression::AssignOp { lhs, .. } => expression_location(lhs),
+        Expression::TernaryOp { cond, .. } => expression_location(cond),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(code: &'static str) -> (dm::Context, ObjectTree) {
+        let context = dm::Context::default();
+        let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dm".into(), code.trim());
+        let indents = dm::indents::IndentProcessor::new(&context, pp);
+        let mut parser = dm::parser::Parser::new(&context, indents);
+        parser.enable_procs();
+        let objtree = parser.parse_object_tree();
+        (context, objtree)
+    }
+
+    fn proc_uses(objtree: &ObjectTree, type_path: &str, proc_name: &str) -> Vec<u32> {
+        let table = ReferencesTable::new(objtree);
+        let decl = objtree.expect(type_path).get_proc_declaration(proc_name).unwrap();
+        table.find_references(decl.id, false).iter().map(|loc| loc.line).collect()
+    }
+
+    #[test]
+    fn call_results_have_their_declared_return_type() {
+        let (_, objtree) = parse(r#"
+/datum/thing
+    var/count
+/datum/thing/proc/bump()
+/datum/thing/proc/copy() as /datum/thing
+/datum/thing/proc/untyped()
+
+/proc/make() as /datum/thing
+/proc/test()
+    var/datum/thing/T = new
+    make().bump()
+    T.copy().count = 1
+    T.copy().copy().bump()
+    T.untyped().bump()
+    return make().count
+"#);
+        let table = ReferencesTable::new(&objtree);
+        let thing = objtree.expect("/datum/thing");
+        let count = thing.get_var_declaration("count").unwrap().id;
+        let lines = |symbol| table.find_references(symbol, false).iter().map(|loc| loc.line).collect::<Vec<_>>();
+        assert_eq!(lines(count), vec![11, 14]);
+        // the result of a call without a return type is not known to be anything
+        assert_eq!(proc_uses(&objtree, "/datum/thing", "bump"), vec![10, 12]);
+        assert_eq!(proc_uses(&objtree, "/datum/thing", "copy"), vec![11, 12, 12]);
+    }
+
+    #[test]
+    fn augmented_assign_prefers_compound_operator() {
+        let (_, objtree) = parse(r#"
+/datum/vec/proc/operator+(other)
+/datum/vec/proc/operator+=(other)
+
+/proc/test()
+    var/datum/vec/V = new
+    V += 1
+"#);
+        assert_eq!(proc_uses(&objtree, "/datum/vec", "operator+="), vec![6]);
+        // `+=` is not also a use of `+` when both are overloaded
+        assert!(proc_uses(&objtree, "/datum/vec", "operator+").is_empty());
+    }
+
+    #[test]
+    fn augmented_assign_falls_back_to_binary_operator() {
+        let (_, objtree) = parse(r#"
+/datum/vec/proc/operator-(other)
+
+/datum/vec/sub
+
+/proc/test()
+    var/datum/vec/sub/V = new
+    V -= 1
+    V = 1
+"#);
+        assert_eq!(proc_uses(&objtree, "/datum/vec", "operator-"), vec![7]);
+    }
+
+    #[test]
+    fn grouped_references_are_deduplicated() {
+        let (context, objtree) = parse(r#"
+/datum/thing
+    var/count
+
+/proc/test()
+    var/datum/thing/T = new
+    T.count = T.count + 1
+    return T.count
+"#);
+        let table = ReferencesTable::new(&objtree);
+        let symbol = resolve_symbol_path(&objtree, "/datum/thing/var/count").unwrap();
+        let grouped: Vec<_> = table.find_references_grouped(symbol, context.file_list()).collect();
+        assert_eq!(grouped.len(), 1);
+        let positions: Vec<(u32, u16)> = grouped[0].1.iter().map(|location| (location.line, location.column)).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(positions.first().map(|pos| pos.0), Some(6));
+        assert_eq!(positions.last().map(|pos| pos.0), Some(7));
+    }
+
+    #[test]
+    fn references_in_multiline_interpolation() {
+        let (_, objtree) = parse(r#"
+/datum/thing
+    var/count
+/datum/thing/proc/describe()
+    world << {"first [src]
+second [count]
+third"}
+"#);
+        let table = ReferencesTable::new(&objtree);
+        let count = objtree.expect("/datum/thing").get_var_declaration("count").unwrap().id;
+        let positions: Vec<_> = table.find_references(count, false).iter().map(|loc| (loc.line, loc.column)).collect();
+        assert_eq!(positions, vec![(5, 9)]);
+    }
+
+    #[test]
+    fn resolve_symbol_paths() {
+        let (_, objtree) = parse(r#"
+/obj/item/var/force
+/obj/item/proc/attack()
+"#);
+        assert_eq!(resolve_symbol_path(&objtree, "/obj/item"), Some(objtree.expect("/obj/item").id));
+        assert!(resolve_symbol_path(&objtree, "/obj/item/proc/attack").is_some());
+        assert!(resolve_symbol_path(&objtree, "/obj/item/var/force").is_some());
+        assert!(resolve_symbol_path(&objtree, "/obj/item/proc/missing").is_none());
+        assert!(resolve_symbol_path(&objtree, "/obj/missing").is_none());
+    }
+
+    /// Every type, var, and proc declared in the code, with where it is
+    /// referenced and implemented.
+    fn dump_references(objtree: &ObjectTree, table: &ReferencesTable) -> Vec<String> {
+        let positions = |locations: &[Location]| locations.iter().map(|loc| format!("{}:{}", loc.line, loc.column)).collect::<Vec<_>>().join(" ");
+        let mut lines = Vec::new();
+        let mut line = |name: String, id: SymbolId| {
+            lines.push(format!("{} refs[{}] impls[{}]", name, positions(table.find_references(id, false)), positions(table.find_implementations(id))));
+        };
+        objtree.root().recurse(&mut |ty| {
+            if ty.location.is_builtins() {
+                return;
+            }
+            if !ty.is_root() {
+                line(ty.path.to_string(), ty.id);
+            }
+            for (name, var) in ty.vars.iter() {
+                if let Some(decl) = ty.get_var_declaration(name) {
+                    if !decl.location.is_builtins() && decl.location == var.value.location {
+                        line(format!("{}/var/{}", ty.path, name), decl.id);
+                    }
+                }
+            }
+            for (name, proc) in ty.procs.iter() {
+                if let Some(decl) = ty.get_proc_declaration(name) {
+                    if !decl.location.is_builtins() && proc.declaration.is_some() {
+                        line(format!("{}/proc/{}", ty.path, name), decl.id);
+                    }
+                }
+            }
+        });
+        lines
+    }
+
+    /// Recorded from the table as it was before references were kept in one
+    /// sorted arena, which must not change what queries return. Declarations
+    /// have since been located at their names, which moved the `impls`.
+    const REFERENCES_SNAPSHOT: &[&str] = &[
+        "/var/test_global refs[31:5 34:5 34:22] impls[32:17]",
+        "/proc/test refs[] impls[22:7]",
+        "/proc/use_global refs[] impls[33:7]",
+        "/datum/thing refs[23:5 35:12] impls[1:8]",
+        "/datum/thing/var/count refs[6:5 8:5 9:12 11:28 24:13 25:6 28:23 34:33] impls[2:9 13:5]",
+        "/datum/thing/var/next refs[11:12 11:16 28:10] impls[3:21]",
+        "/datum/thing/proc/bump refs[11:22 16:5 17:8 24:6 34:16] impls[7:19 14:18]",
+        "/datum/thing/proc/chain refs[20:5 30:17] impls[10:19]",
+        "/datum/thing/sub refs[23:25 26:5] impls[12:14]",
+        "/datum/thing/sub/proc/poke refs[27:10] impls[18:23]",
+        "/datum/thing/sub/other refs[29:18] impls[21:18]",
+    ];
+
+    #[test]
+    fn query_results_unchanged() {
+        let (_, objtree) = parse(r#"
+/datum/thing
+    var/count = 1
+    var/datum/thing/next
+/datum/thing/New(start)
+    ..()
+    count = start
+/datum/thing/proc/bump(amount)
+    count += amount
+    return count
+/datum/thing/proc/chain()
+    return next?.next.bump(count)
+/datum/thing/sub
+    count = 2
+/datum/thing/sub/bump(amount)
+    . = ..()
+    bump(1)
+    src.bump(2)
+/datum/thing/sub/verb/poke()
+    set name = "Poke"
+    chain()
+/datum/thing/sub/other
+/proc/test()
+    var/datum/thing/T = new /datum/thing/sub(4)
+    T.bump(T.count)
+    T.count = 3
+    for(var/datum/thing/sub/S in list(T))
+        S.poke()
+        S.next = new(T.count)
+    if(istype(T, /datum/thing/sub/other))
+        return T.chain()
+    test_global = T
+var/datum/thing/test_global
+/proc/use_global()
+    test_global.bump(test_global.count)
+    return new /datum/thing
+"#);
+        let table = ReferencesTable::new(&objtree);
+        assert_eq!(dump_references(&objtree, &table), REFERENCES_SNAPSHOT);
+
+        let thing = objtree.expect("/datum/thing");
+        let count = thing.get_var_declaration("count").unwrap().id;
+        let bump = thing.get_proc_declaration("bump").unwrap().id;
+        let overrides = table.find_overrides(bump).iter().map(|(key, _)| key.path.as_str()).collect::<Vec<_>>();
+        assert_eq!(overrides, vec!["/datum/thing", "/datum/thing/sub"]);
+        assert!(table.find_overrides(count).is_empty());
+    }
+
+    #[test]
+    fn locals_resolve_by_name_and_line() {
+        let (_, objtree) = parse(r#"
+/proc/test(amount)
+    var/total = amount
+    total += 1
+    return total
+"#);
+        let table = ReferencesTable::new(&objtree);
+        let file = objtree.root().get_proc("test").unwrap().location.file;
+        let at = |line, column| Location { file, line, column };
+        let total = table.find_local(at(2, 20), "total").unwrap();
+        assert_eq!(table.find_local(at(3, 5), "total"), Some(total));
+        assert_eq!(table.find_local(at(4, 12), "total"), Some(total));
+        let amount = table.find_local(at(2, 17), "amount").unwrap();
+        assert_ne!(amount, total);
+        assert_eq!(table.find_local(at(3, 5), "amount"), None);
+
+        let uses = table.find_references(total, true);
+        assert_eq!(table.local_name_conflicts(uses, "amount", &|| false), Some(true));
+        assert_eq!(table.local_name_conflicts(uses, "other", &|| false), Some(false));
+        assert_eq!(table.local_name_conflicts(uses, "amount", &|| true), None);
+    }
+
+    #[test]
+    fn highlights_mark_writes() {
+        let (_, objtree) = parse(r#"
+/datum/thing
+    var/count = 1
+/datum/thing/proc/bump()
+    var/total = count
+    count = total
+    total++
+    src.count += 1
+    return total
+"#);
+        let table = ReferencesTable::new(&objtree);
+        let thing = objtree.expect("/datum/thing");
+        let file = thing.location.file;
+        let highlights = |symbol| table.find_highlights(symbol, file).iter()
+            .map(|&(loc, write)| (loc.line, write))
+            .collect::<Vec<_>>();
+
+        let count = thing.get_var_declaration("count").unwrap().id;
+        assert_eq!(highlights(count), vec![(2, false), (4, false), (5, true), (7, true)]);
+        let total = table.find_local(Location { file, line: 4, column: 5 }, "total").unwrap();
+        assert_eq!(highlights(total), vec![(4, true), (5, false), (6, true), (8, false)]);
+    }
+
+    #[test]
+    fn writes_mark_the_assigned_symbol() {
+        let (_, objtree) = parse(r#"
+/datum/thing
+    var/count = 1
+    var/list/items
+#define MISSING_FIELD T.missing
+/proc/test()
+    var/datum/thing/T = new
+    MISSING_FIELD = 1
+    T.items[T.count] = 2
+    T.items.len = T.count
+    return T
+"#);
+        let table = ReferencesTable::new(&objtree);
+        let thing = objtree.expect("/datum/thing");
+        let file = thing.location.file;
+        let highlights = |symbol| table.find_highlights(symbol, file).iter()
+            .map(|&(loc, write)| (loc.line, write))
+            .collect::<Vec<_>>();
+
+        // neither the object of an unknown field nor an index is written to
+        let t = table.find_local(Location { file, line: 6, column: 21 }, "T").unwrap();
+        assert_eq!(highlights(t), vec![(6, true), (7, false), (8, false), (8, false), (9, false), (9, false), (10, false)]);
+        let count = thing.get_var_declaration("count").unwrap().id;
+        assert_eq!(highlights(count), vec![(2, false), (8, false), (9, false)]);
+        let items = thing.get_var_declaration("items").unwrap().id;
+        assert_eq!(highlights(items), vec![(3, false), (8, false), (9, false)]);
+    }
+
+    #[test]
+    fn call_hierarchy_edges() {
+        let (_, objtree) = parse(r#"
+/obj/item/proc/attack()
+    return
+/obj/item/sword/attack()
+    ..()
+    helper()
+    helper()
+/mob/proc/attack()
+    var/obj/item/I = new
+    I.attack()
+/proc/helper()
+"#);
+        let table = ReferencesTable::new(&objtree);
+        let key = |path: &str, name: &str| ProcKey { path: intern(path), name: intern(name), index: 0 };
+        let summarize = |calls: Vec<(&ProcKey, Vec<Location>)>| calls.into_iter()
+            .map(|(key, locations)| (key.path.to_string(), key.name.to_string(), locations.iter().map(|loc| loc.line).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(summarize(table.outgoing_calls(&key("/obj/item/sword", "attack"))), vec![
+            ("/obj/item".to_owned(), "attack".to_owned(), vec![4]),
+            ("".to_owned(), "helper".to_owned(), vec![5, 6]),
+        ]);
+        assert_eq!(summarize(table.incoming_calls(&key("/obj/item", "attack"))), vec![
+            ("/obj/item/sword".to_owned(), "attack".to_owned(), vec![4]),
+            ("/mob".to_owned(), "attack".to_owned(), vec![9]),
+        ]);
+        assert!(key("/mob", "attack").resolve(&objtree).is_some());
+    }
+
+    #[test]
+    fn inlay_hints() {
+        let (_, objtree) = parse(r#"
+/obj/item/proc/attack(target, amount)
+    return
+/obj/item/proc/spawn_copy() as /obj/item
+    return
+/obj/item/sword/attack(target, amount)
+    ..()
+    var/copy = spawn_copy()
+    var/obj/item/sword/S = new
+    S.attack(target, 5)
+"#);
+        let ty = objtree.find("/obj/item/sword").unwrap();
+        let proc = ty.get_proc("attack").unwrap();
+        let hints = collect_hints(&objtree, proc).into_iter()
+            .map(|hint| (hint.location.line, match hint.kind {
+                HintKind::Type { var_name, type_path } => format!("{}: {}", var_name, type_path),
+                HintKind::Parameter(name) => format!("{}:", name),
+                HintKind::ParentArguments(names) => names.join(", "),
+            }))
+            .collect::<Vec<_>>();
+        assert_eq!(hints, vec![
+            (6, "target, amount".to_owned()),
+            (7, "copy: /obj/item".to_owned()),
+            (9, "amount:".to_owned()),
+        ]);
+    }
+
+    #[test]
+    fn expression_types() {
+        let (_, objtree) = parse(r#"
+/obj/item
+    var/list/obj/item/contents_list
+/obj/item/proc/spawn_copy() as /obj/item
+    return
+/obj/item/proc/test()
+    spawn_copy()
+    contents_list.Cut()
+"#);
+        let proc = objtree.expect("/obj/item").get_proc("test").unwrap();
+        let types = collect_types(&objtree, proc).into_iter()
+            .map(|(location, types)| (location.line, types.into_iter().map(|idx| objtree[idx].path.to_string()).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(types, vec![
+            (6, vec!["/obj/item".to_owned()]),
+            (7, vec!["/list".to_owned(), "/obj/item".to_owned()]),
+        ]);
+    }
+}
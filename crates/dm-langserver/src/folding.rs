@@ -0,0 +1,237 @@
+//! Folding ranges for procs, types, statements, comments, and preprocessor
+//! regions.
+
+use lsp_types::{FoldingRange, FoldingRangeKind};
+
+use dm::annotation::{Annotation, AnnotationTree};
+use dm::docs::CommentKind;
+use dm::lexer::{LocatedToken, Lexer, Punctuation, Token};
+use dm::{FileId, Location};
+
+/// Compute the folding ranges of a file from its annotations and contents.
+pub fn folding_ranges(annotations: &AnnotationTree, file_id: FileId, text: &str) -> Vec<FoldingRange> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut output = Vec::new();
+
+    code_folds(annotations, file_id, &lines, &mut output);
+
+    let context = dm::Context::default();
+    let tokens: Vec<LocatedToken> = Lexer::new(&context, file_id, text.as_bytes()).collect();
+    comment_folds(&tokens, &mut output);
+    directive_folds(&tokens, &mut output);
+    region_folds(&lines, &mut output);
+
+    // Editors can only fold one range per starting line, so keep the
+    // innermost of each.
+    output.sort_by_key(|range| (range.start_line, range.end_line));
+    output.dedup_by_key(|range| range.start_line);
+    output
+}
+
+fn fold(start_line: u32, end_line: u32, kind: Option<FoldingRangeKind>) -> Option<FoldingRange> {
+    (end_line > start_line).then_some(FoldingRange {
+        start_line,
+        start_character: None,
+        end_line,
+        end_character: None,
+        kind,
+    })
+}
+
+/// Folds for type blocks, proc bodies, statement blocks, and multi-line
+/// statements, taken from the spans the parser recorded.
+fn code_folds(annotations: &AnnotationTree, file_id: FileId, lines: &[&str], output: &mut Vec<FoldingRange>) {
+    let start = Location { file: file_id, line: 0, column: 0 };
+    let end = Location { file: file_id, line: !0, column: !0 };
+    let mut all: Vec<_> = annotations.get_range(start..end)
+        .map(|(range, annotation)| (range.start, range.end, annotation))
+        .collect();
+    all.sort_by_key(|&(start, _, _)| start);
+    let tree_paths: Vec<Location> = all.iter()
+        .filter(|(_, _, annotation)| matches!(annotation, Annotation::TreePath(..)))
+        .map(|&(start, _, _)| start)
+        .collect();
+
+    for &(start, end, annotation) in all.iter() {
+        let header_line = match annotation {
+            // A type block's span starts at its first entry, so fold from
+            // its header instead.
+            Annotation::TreeBlock(_) => {
+                let header = tree_paths.partition_point(|&path_start| path_start < start);
+                header.checked_sub(1).map_or(start.line, |i| tree_paths[i].line)
+            },
+            Annotation::StatementBlock | Annotation::Statement => start.line,
+            _ => continue,
+        };
+        if let Some(range) = fold(header_line - 1, last_line_before(lines, end), None) {
+            output.push(range);
+        }
+    }
+}
+
+/// Find the zero-based line of the last non-blank text before `end`.
+///
+/// Spans end where the next token begins, so a span which ends at the start
+/// of a line does not include that line.
+fn last_line_before(lines: &[&str], end: Location) -> u32 {
+    let mut line = end.line.saturating_sub(1);
+    let at_line_start = lines.get(line as usize).is_none_or(|text| {
+        let indent = text.len() - text.trim_start().len();
+        end.column as usize <= indent + 1
+    });
+    if at_line_start {
+        line = line.saturating_sub(1);
+    }
+    while line > 0 && lines.get(line as usize).is_some_and(|text| text.trim().is_empty()) {
+        line -= 1;
+    }
+    line
+}
+
+/// Folds for `/** ... */` doc comments and runs of `///` lines.
+fn comment_folds(tokens: &[LocatedToken], output: &mut Vec<FoldingRange>) {
+    let mut run: Option<(u32, u32)> = None;
+    for token in tokens {
+        let Token::DocComment(ref comment) = token.token else {
+            continue;
+        };
+        let line = token.location.line - 1;
+        match comment.kind {
+            CommentKind::Block => {
+                let end_line = line + comment.text.matches('\n').count() as u32;
+                output.extend(fold(line, end_line, Some(FoldingRangeKind::Comment)));
+            },
+            CommentKind::Line => match run {
+                Some((_, ref mut last)) if *last + 1 == line => *last = line,
+                _ => {
+                    if let Some((first, last)) = run {
+                        output.extend(fold(first, last, Some(FoldingRangeKind::Comment)));
+                    }
+                    run = Some((line, line));
+                },
+            },
+        }
+    }
+    if let Some((first, last)) = run {
+        output.extend(fold(first, last, Some(FoldingRangeKind::Comment)));
+    }
+}
+
+/// Folds for `#if`/`#ifdef`/`#ifndef` regions, split at `#elif` and `#else`.
+fn directive_folds(tokens: &[LocatedToken], output: &mut Vec<FoldingRange>) {
+    let mut stack: Vec<u32> = Vec::new();
+    for pair in tokens.windows(2) {
+        let (Token::Punct(Punctuation::Hash), Token::Ident(ref directive, _)) = (&pair[0].token, &pair[1].token) else {
+            continue;
+        };
+        let line = pair[0].location.line - 1;
+        match directive.as_str() {
+            "if" | "ifdef" | "ifndef" => stack.push(line),
+            "elif" | "else" => if let Some(start) = stack.last_mut() {
+                output.extend(fold(*start, line.saturating_sub(1), Some(FoldingRangeKind::Region)));
+                *start = line;
+            },
+            "endif" => if let Some(start) = stack.pop() {
+                output.extend(fold(start, line.saturating_sub(1), Some(FoldingRangeKind::Region)));
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Folds between `// #region Name` and `// #endregion` marker comments.
+fn region_folds(lines: &[&str], output: &mut Vec<FoldingRange>) {
+    let mut stack: Vec<u32> = Vec::new();
+    for (i, text) in lines.iter().enumerate() {
+        let Some(comment) = text.trim_start().strip_prefix("//") else {
+            continue;
+        };
+        let comment = comment.trim_start();
+        if comment.starts_with("#region") {
+            stack.push(i as u32);
+        } else if comment.starts_with("#endregion") {
+            if let Some(start) = stack.pop() {
+                output.extend(fold(start, i as u32, Some(FoldingRangeKind::Region)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folds(code: &str) -> Vec<(u32, u32, Option<FoldingRangeKind>)> {
+        let context = Default::default();
+        let lexer = Lexer::new(&context, Default::default(), code.as_bytes());
+        let indent = dm::indents::IndentProcessor::new(&context, lexer);
+        let mut annotations = AnnotationTree::default();
+        dm::parser::Parser::new(&context, indent).parse_annotations_only(&mut annotations);
+        folding_ranges(&annotations, Default::default(), code).into_iter()
+            .map(|range| (range.start_line, range.end_line, range.kind))
+            .collect()
+    }
+
+    #[test]
+    fn blocks_and_statements() {
+        let code = r#"
+/obj/item
+    proc/attack(mob/target)
+        if(target)
+            world.log << "a"
+            world.log << "b"
+        else
+            return
+
+        switch(target)
+            if(1)
+                world.log << "x"
+                world.log << "x"
+            if(2, 3)
+                world.log << "y"
+                world.log << "y"
+        for(var/i in 1 to 10) {
+            world.log << i
+        }
+        return
+"#.trim();
+        assert_eq!(folds(code), vec![
+            (0, 18, None),
+            (1, 18, None),
+            (2, 4, None),
+            (5, 6, None),
+            (8, 14, None),
+            (9, 11, None),
+            (12, 14, None),
+            (15, 16, None),
+        ]);
+    }
+
+    #[test]
+    fn comments_and_regions() {
+        let code = r#"
+/// Some docs
+/// which go on
+/obj/item
+/**
+ * More docs
+ */
+/obj/thing
+// #region Things
+#ifdef FOO
+/obj/foo
+#else
+/obj/bar
+/obj/baz
+#endif
+// #endregion
+"#.trim();
+        assert_eq!(folds(code), vec![
+            (0, 1, Some(FoldingRangeKind::Comment)),
+            (3, 5, Some(FoldingRangeKind::Comment)),
+            (7, 14, Some(FoldingRangeKind::Region)),
+            (8, 9, Some(FoldingRangeKind::Region)),
+            (10, 12, Some(FoldingRangeKind::Region)),
+        ]);
+    }
+}
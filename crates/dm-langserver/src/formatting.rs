@@ -0,0 +1,715 @@
+//! Indentation assistance as the user types, and whitespace formatting.
+//!
+//! DM is indentation-sensitive, so rather than guessing from the text, the
+//! caller says whether a line is inside a proc body according to the parser,
+//! and lines are classified by their tokens.
+
+use std::ops::Range;
+
+use dm::lexer::{LocatedToken, Lexer, Punctuation, Token};
+
+/// A new indentation for a line.
+pub type Reindent = (usize, String);
+
+/// Indent the line the cursor moved to after a newline, realigning the line
+/// before it first if it was an `else`.
+pub fn after_newline(text: &str, line: usize, unit: &str, in_proc: impl Fn(usize) -> bool) -> Vec<Reindent> {
+    let lines = lines(text);
+    let mut edits = Vec::new();
+    let Some(prev) = previous_line(&lines, line) else {
+        return edits;
+    };
+
+    let mut base = leading_whitespace(lines[prev]).to_owned();
+    if matches!(tokens(lines[prev]).first(), Some(Token::Ident(word, _)) if word == "else") {
+        if let Some(indent) = matching_if(&lines, prev) {
+            if indent != base {
+                edits.push((prev, indent.to_owned()));
+                base = indent.to_owned();
+            }
+        }
+    }
+    if opens_block(lines[prev], in_proc(prev)) {
+        base.push_str(unit);
+    }
+    if line < lines.len() && leading_whitespace(lines[line]) != base {
+        edits.push((line, base));
+    }
+    edits
+}
+
+/// Indent a label once its colon is typed, as the statement it is.
+pub fn after_colon(text: &str, line: usize, unit: &str, in_proc: impl Fn(usize) -> bool) -> Vec<Reindent> {
+    let lines = lines(text);
+    if line >= lines.len() || !is_label(&tokens(lines[line])) {
+        return Vec::new();
+    }
+    let Some(prev) = previous_line(&lines, line) else {
+        return Vec::new();
+    };
+    let mut indent = leading_whitespace(lines[prev]).to_owned();
+    if opens_block(lines[prev], in_proc(prev)) {
+        indent.push_str(unit);
+    }
+    if leading_whitespace(lines[line]) == indent {
+        Vec::new()
+    } else {
+        vec![(line, indent)]
+    }
+}
+
+/// Whether the line after this one belongs in a block it starts.
+pub fn opens_block(line: &str, in_proc: bool) -> bool {
+    let tokens = tokens(line);
+    if let Some(Token::Punct(Punctuation::LBrace)) = tokens.last() {
+        return true;
+    }
+    if in_proc {
+        match tokens.as_slice() {
+            [Token::Ident(word, _), rest @ ..] => match word.as_str() {
+                "if" | "for" | "while" | "switch" => after_parens(rest).is_some_and(<[_]>::is_empty),
+                "spawn" | "catch" => rest.is_empty() || after_parens(rest).is_some_and(<[_]>::is_empty),
+                "else" => match rest {
+                    [] => true,
+                    [Token::Ident(word, _), rest @ ..] if word == "if" => after_parens(rest).is_some_and(<[_]>::is_empty),
+                    _ => false,
+                },
+                "do" | "try" => rest.is_empty(),
+                _ => is_label(&tokens),
+            },
+            _ => false,
+        }
+    } else {
+        if tokens.is_empty() || tokens.contains(&Token::Punct(Punctuation::Assign)) {
+            return false;
+        }
+        if tokens.contains(&Token::Punct(Punctuation::LParen)) {
+            // a proc header, unless something follows it
+            return matches!(tokens.last(), Some(Token::Punct(Punctuation::RParen)));
+        }
+        // a type or a `var` or `proc` block, but not a var declaration
+        let mut parts = Vec::new();
+        for token in tokens.iter() {
+            match token {
+                Token::Ident(part, _) => parts.push(part.as_str()),
+                Token::Punct(Punctuation::Slash) => {}
+                _ => return false,
+            }
+        }
+        match parts.iter().position(|part| *part == "var") {
+            Some(idx) => idx + 1 == parts.len(),
+            None => true,
+        }
+    }
+}
+
+/// Find the indentation of the `if` an `else` on the given line belongs to.
+fn matching_if<'a>(lines: &[&'a str], line: usize) -> Option<&'a str> {
+    let width = leading_whitespace(lines[line]).len();
+    for text in lines[..line].iter().rev() {
+        let trimmed = text.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with("//") {
+            continue;
+        }
+        let indent = leading_whitespace(text);
+        if indent.len() > width {
+            continue;
+        }
+        match tokens(trimmed).as_slice() {
+            [Token::Ident(word, _), ..] if word == "if" => return Some(indent),
+            [Token::Ident(first, _), Token::Ident(second, _), ..] if first == "else" && second == "if" => return Some(indent),
+            _ if indent.len() < width => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Skip a parenthesized group at the start of the tokens.
+fn after_parens(tokens: &[Token]) -> Option<&[Token]> {
+    if tokens.first() != Some(&Token::Punct(Punctuation::LParen)) {
+        return None;
+    }
+    let mut depth = 0;
+    for (idx, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct(Punctuation::LParen) => depth += 1,
+            Token::Punct(Punctuation::RParen) => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&tokens[idx + 1..]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_label(tokens: &[Token]) -> bool {
+    matches!(tokens, [Token::Ident(..), Token::Punct(Punctuation::Colon | Punctuation::CloseColon)])
+}
+
+fn tokens(line: &str) -> Vec<Token> {
+    let context = dm::Context::default();
+    Lexer::new(&context, Default::default(), line.trim().as_bytes())
+        .map(|token| token.token)
+        .filter(|token| !matches!(token, Token::Punct(Punctuation::Newline) | Token::DocComment(_)))
+        .collect()
+}
+
+fn lines(text: &str) -> Vec<&str> {
+    text.split('\n').map(|line| line.trim_end_matches('\r')).collect()
+}
+
+fn previous_line(lines: &[&str], line: usize) -> Option<usize> {
+    (0..line.min(lines.len())).rev().find(|&idx| {
+        let trimmed = lines[idx].trim();
+        !trimmed.is_empty() && !trimmed.starts_with("//")
+    })
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// A replacement for a byte range of the document.
+pub type Edit = (Range<usize>, String);
+
+/// How whitespace should be normalized.
+pub struct Style {
+    pub insert_spaces: bool,
+    pub tab_size: usize,
+    pub operator_spacing: bool,
+}
+
+/// Normalize the whitespace of a whole document.
+///
+/// Only whitespace between tokens is ever changed, and indentation only ever
+/// changes characters, never depth. Proc headers are identified by the caller
+/// so that a blank line can separate them from the proc before.
+pub fn format_document(text: &str, style: &Style, proc_header: impl Fn(usize) -> bool) -> Vec<Edit> {
+    let spans = spans(text);
+    let classes = classify(text, &spans);
+    let lines = analyze(text, &spans, &classes);
+    let mut edits = Vec::new();
+
+    // indentation characters and blank lines, line by line
+    let indents = convert_indents(text, &lines, style);
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut blank_run = false;
+    for (idx, line) in lines.iter().enumerate() {
+        let blank = line.kind == LineKind::Blank;
+        if blank && blank_run {
+            // drop the whole line, keeping only the first of a run
+            edits.push((line.start..lines.get(idx + 1).map_or(text.len(), |next| next.start), String::new()));
+            continue;
+        }
+        blank_run = blank;
+
+        let separate = line.kind == LineKind::Code && proc_header(idx) && follows_proc(text, &lines, idx);
+        let indent = &text[line.start..line.start + line.indent];
+        if separate || indents[idx].is_some() {
+            let mut replacement = if separate { newline.to_owned() } else { String::new() };
+            replacement.push_str(indents[idx].as_deref().unwrap_or(indent));
+            edits.push((line.start..line.start + line.indent, replacement));
+        }
+        if let Some(trailing) = line.trailing.clone() {
+            edits.push((trailing, String::new()));
+        }
+    }
+
+    // spacing between tokens within a line
+    for idx in 0..spans.len().saturating_sub(1) {
+        let (before, after) = (&spans[idx], &spans[idx + 1]);
+        let gap = &text[before.range.end..after.range.start];
+        if before.verbatim || gap == " " || !gap.bytes().all(|ch| ch == b' ' || ch == b'\t') {
+            continue;
+        }
+        if matches!(after.token, Token::Punct(Punctuation::Colon | Punctuation::CloseColon)) {
+            // whitespace decides whether a colon closes an expression
+            continue;
+        }
+        let spaced = match before.token {
+            Token::Punct(Punctuation::Comma) => !matches!(after.token, Token::Punct(Punctuation::RParen | Punctuation::RBracket)),
+            _ => style.operator_spacing && (is_binary(text, &spans, idx) || is_binary(text, &spans, idx + 1)),
+        };
+        if spaced {
+            edits.push((before.range.end..after.range.start, " ".to_owned()));
+        }
+    }
+
+    edits.sort_by_key(|(range, _)| range.start);
+    edits
+}
+
+/// A token and the bytes it was read from.
+struct Span {
+    range: Range<usize>,
+    token: Token,
+    /// Whether the whitespace following this token must be left alone, as in
+    /// preprocessor directives and string interpolations.
+    verbatim: bool,
+}
+
+fn spans(text: &str) -> Vec<Span> {
+    let context = dm::Context::default();
+    let mut line_starts = vec![if text.starts_with('\u{feff}') { 3 } else { 0 }];
+    line_starts.extend(text.match_indices('\n').map(|(idx, _)| idx + 1));
+
+    let mut lexer = Lexer::new(&context, Default::default(), text.as_bytes());
+    let mut spans = Vec::new();
+    let mut directive = false;
+    let mut interpolation = 0usize;
+    while let Some(LocatedToken { location, token, .. }) = Iterator::next(&mut lexer) {
+        match token {
+            Token::Punct(Punctuation::Space | Punctuation::Tab) => continue,
+            Token::Punct(Punctuation::Newline) => {
+                directive = false;
+                continue;
+            }
+            Token::Punct(Punctuation::Hash) => directive = true,
+            Token::InterpStringBegin(_) => interpolation += 1,
+            Token::InterpStringEnd(_) => interpolation = interpolation.saturating_sub(1),
+            _ => {}
+        }
+        let start = line_starts[location.line as usize - 1] + location.column as usize - 1;
+        spans.push(Span {
+            range: start..lexer.offset(),
+            token,
+            verbatim: directive || interpolation > 0,
+        });
+    }
+    spans
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Class {
+    Whitespace,
+    Token,
+    LineComment,
+    BlockComment,
+}
+
+/// Classify every byte of the text as part of a token, a comment, or neither.
+fn classify(text: &str, spans: &[Span]) -> Vec<Class> {
+    let bytes = text.as_bytes();
+    let mut classes = vec![Class::Whitespace; bytes.len()];
+    for span in spans {
+        classes[span.range.clone()].fill(Class::Token);
+    }
+
+    // comments are everything non-blank the lexer skipped over
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if classes[idx] != Class::Whitespace {
+            idx += 1;
+        } else if bytes[idx..].starts_with(b"//") {
+            // a backslash continues a line comment onto the next line
+            let mut end = idx;
+            loop {
+                match bytes[end..].iter().position(|&ch| ch == b'\n') {
+                    Some(len) if text[idx..end + len].trim_end_matches('\r').ends_with('\\') => end += len + 1,
+                    Some(len) => {
+                        end += len;
+                        break;
+                    }
+                    None => {
+                        end = bytes.len();
+                        break;
+                    }
+                }
+            }
+            classes[idx..end].fill(Class::LineComment);
+            idx = end;
+        } else if bytes[idx..].starts_with(b"/*") {
+            let start = idx;
+            let mut depth = 0;
+            while idx < bytes.len() {
+                if bytes[idx..].starts_with(b"/*") {
+                    depth += 1;
+                    idx += 2;
+                } else if bytes[idx..].starts_with(b"*/") {
+                    depth -= 1;
+                    idx += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    idx += 1;
+                }
+            }
+            let end = idx.min(bytes.len());
+            classes[start..end].fill(Class::BlockComment);
+        } else {
+            idx += 1;
+        }
+    }
+    classes
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum LineKind {
+    /// Nothing but whitespace.
+    Blank,
+    /// Starts a statement or declaration, so its indentation is significant.
+    Code,
+    /// Comments and continuations, whose indentation is not significant.
+    Other,
+    /// Starts inside a string or comment.
+    Verbatim,
+}
+
+struct Line {
+    start: usize,
+    indent: usize,
+    kind: LineKind,
+    braces: bool,
+    /// Trailing whitespace which can be removed.
+    trailing: Option<Range<usize>>,
+}
+
+fn analyze(text: &str, spans: &[Span], classes: &[Class]) -> Vec<Line> {
+    let bytes = text.as_bytes();
+    let mut lines = Vec::new();
+    let mut next_span = 0;
+    let mut depth = 0usize;
+    let mut continued = false;
+    let mut start = 0;
+    while start < bytes.len() {
+        let newline = bytes[start..].iter().position(|&ch| ch == b'\n').map(|len| start + len);
+        let mut end = newline.unwrap_or(bytes.len());
+        if end > start && bytes[end - 1] == b'\r' {
+            end -= 1;
+        }
+        let indent = leading_whitespace(&text[start..end]).len();
+
+        // parentheses open at the start of the line, and brace use on it
+        let first_span = next_span;
+        let depth_before = depth;
+        let line_end = newline.map_or(bytes.len(), |idx| idx + 1);
+        let mut braces = false;
+        while next_span < spans.len() && spans[next_span].range.start < line_end {
+            match spans[next_span].token {
+                Token::Punct(Punctuation::LParen) => depth += 1,
+                Token::Punct(Punctuation::RParen) => depth = depth.saturating_sub(1),
+                Token::Punct(Punctuation::LBrace | Punctuation::RBrace) => braces = true,
+                _ => {}
+            }
+            next_span += 1;
+        }
+
+        let inside = start > 0 && classes[start - 1] != Class::Whitespace && classes[start] == classes[start - 1];
+        let kind = if inside {
+            LineKind::Verbatim
+        } else if start + indent == end {
+            LineKind::Blank
+        } else if spans.get(first_span).is_some_and(|span| span.range.start == start + indent) && depth_before == 0 && !continued {
+            LineKind::Code
+        } else {
+            LineKind::Other
+        };
+
+        let content_end = start + text[start..end].trim_end_matches([' ', '\t']).len();
+        let trailing = if content_end < end && matches!(classes[content_end], Class::Whitespace | Class::LineComment) {
+            Some(content_end..end)
+        } else {
+            None
+        };
+        continued = content_end > start && bytes[content_end - 1] == b'\\' && classes[content_end - 1] == Class::Whitespace;
+
+        lines.push(Line { start, indent, kind, braces, trailing });
+        match newline {
+            Some(idx) => start = idx + 1,
+            None => break,
+        }
+    }
+    lines
+}
+
+/// Change indentation characters to the style's, wherever each line's
+/// indentation depth is sure to stay the same.
+///
+/// Depth is measured per run of indented lines, relative to the first one, so
+/// a run is only converted if its significant lines all scale evenly.
+fn convert_indents(text: &str, lines: &[Line], style: &Style) -> Vec<Option<String>> {
+    let mut result = vec![None; lines.len()];
+    let (want, other) = if style.insert_spaces { (b' ', b'\t') } else { (b'\t', b' ') };
+    let indent_of = |line: &Line| &text.as_bytes()[line.start..line.start + line.indent];
+    let pure = |indent: &[u8], ch: u8| !indent.is_empty() && indent.iter().all(|&b| b == ch);
+
+    let mut idx = 0;
+    while idx < lines.len() {
+        // a run continues until the next unindented line of code
+        let first = idx;
+        let mut braces = lines[idx].braces;
+        idx += 1;
+        while idx < lines.len() && !(lines[idx].kind == LineKind::Code && lines[idx].indent == 0) {
+            braces |= lines[idx].braces;
+            idx += 1;
+        }
+        if braces {
+            continue;
+        }
+        let run = &lines[first..idx];
+        let significant: Vec<&[u8]> = run.iter()
+            .filter(|line| line.kind == LineKind::Code && line.indent > 0)
+            .map(indent_of)
+            .collect();
+        let unit = match significant.first() {
+            _ if significant.iter().all(|indent| pure(indent, want)) => style.tab_size,
+            // spaces become tabs only if every line is a whole number of indents
+            Some(first) if !style.insert_spaces && significant.iter().all(|indent| pure(indent, b' ') && indent.len() % first.len() == 0) => first.len(),
+            Some(_) if style.insert_spaces && significant.iter().all(|indent| pure(indent, b'\t')) => style.tab_size,
+            _ => continue,
+        };
+        let unit = unit.max(1);
+        for (offset, line) in run.iter().enumerate() {
+            let indent = indent_of(line);
+            if !matches!(line.kind, LineKind::Code | LineKind::Other) || !pure(indent, other) {
+                continue;
+            }
+            result[first + offset] = if style.insert_spaces {
+                Some(" ".repeat(indent.len() * unit))
+            } else if indent.len() % unit == 0 {
+                Some("\t".repeat(indent.len() / unit))
+            } else {
+                None
+            };
+        }
+    }
+    result
+}
+
+/// Whether a line of code comes straight after the body of another proc,
+/// skipping over comments which belong to it.
+fn follows_proc(text: &str, lines: &[Line], idx: usize) -> bool {
+    let indent = &text[lines[idx].start..lines[idx].start + lines[idx].indent];
+    let mut first = idx;
+    while first > 0 {
+        let line = &lines[first - 1];
+        let content = &text[line.start + line.indent..];
+        let comment = content.starts_with("//") || content.starts_with("/*");
+        if !comment || &text[line.start..line.start + line.indent] != indent {
+            break;
+        }
+        first -= 1;
+    }
+    // and from strings or continuations back to where their statement began
+    let Some(last) = first.checked_sub(1).filter(|&last| lines[last].kind != LineKind::Blank) else {
+        return false;
+    };
+    lines[..=last].iter().rev()
+        .find(|line| line.kind == LineKind::Code)
+        .is_some_and(|line| line.indent > indent.len())
+}
+
+/// Whether the token at this index is a binary operator, rather than a unary
+/// one or part of a path.
+fn is_binary(text: &str, spans: &[Span], idx: usize) -> bool {
+    use dm::lexer::Punctuation::*;
+
+    let Token::Punct(punct) = spans[idx].token else {
+        return false;
+    };
+    let binary = matches!(punct,
+        Add | Sub | Mul | Pow | Mod | FloatMod |
+        Less | Greater | LessEq | GreaterEq | Eq | NotEq | LessGreater | Equiv | NotEquiv |
+        And | Or | BitAnd | BitOr | BitXor | LShift | RShift |
+        Assign | AssignInto | AddAssign | SubAssign | MulAssign | DivAssign | ModAssign | FloatModAssign |
+        BitAndAssign | BitOrAssign | BitXorAssign | LShiftAssign | RShiftAssign | AndAssign | OrAssign
+    );
+    if !binary || idx == 0 {
+        return false;
+    }
+    // operators at the start of a line are unary
+    let previous = &spans[idx - 1];
+    if text[previous.range.end..spans[idx].range.start].contains('\n') {
+        return false;
+    }
+    match previous.token {
+        Token::Ident(ref word, _) => !matches!(word.as_str(), "return" | "to" | "step" | "as" | "else" | "del" | "throw"),
+        Token::Int(_) | Token::Float(_) | Token::String(_) | Token::InterpStringEnd(_) | Token::Resource(_) => true,
+        Token::Punct(RParen | RBracket | Dot) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn newline(text: &str, line: usize) -> Vec<Reindent> {
+        // everything indented past the first line is in a proc
+        after_newline(text, line, "\t", |line| line > 0)
+    }
+
+    #[test]
+    fn indents_blocks() {
+        assert_eq!(newline("/proc/f()\n\tif(x)\n\t", 2), [(2, "\t\t".to_owned())]);
+        assert_eq!(newline("/proc/f()\n\tif(x) return\n\t", 2), []);
+        assert_eq!(newline("/proc/f()\n\tfor(var/i in list(1, 2))\n", 2), [(2, "\t\t".to_owned())]);
+        assert_eq!(newline("/proc/f()\n\touter:\n", 2), [(2, "\t\t".to_owned())]);
+        assert_eq!(newline("/proc/f()\n\tspawn\n", 2), [(2, "\t\t".to_owned())]);
+        assert_eq!(newline("/proc/f()\n\tfoo(x)\n\t", 2), []);
+
+        // and in the object tree, types and proc headers but not vars
+        assert_eq!(newline("/obj/item\n", 1), [(1, "\t".to_owned())]);
+        assert_eq!(newline("/obj/item/proc/fire(mob/user)\n", 1), [(1, "\t".to_owned())]);
+        assert_eq!(newline("/obj/item/var/ammo\n", 1), []);
+        assert_eq!(newline("/obj/item/name = \"x\"\n", 1), []);
+    }
+
+    #[test]
+    fn aligns_else() {
+        let text = "/proc/f()\n\tif(a)\n\t\tif(b)\n\t\t\ty()\n\t\t\telse\n";
+        assert_eq!(newline(text, 5), [(4, "\t\t".to_owned()), (5, "\t\t\t".to_owned())]);
+
+        let text = "/proc/f()\n\tif(a)\n\t\ty()\n\t\telse if(b)\n";
+        assert_eq!(newline(text, 4), [(3, "\t".to_owned()), (4, "\t\t".to_owned())]);
+    }
+
+    #[test]
+    fn switch_cases() {
+        let text = "/proc/f()\n    switch(x)\n        if(1)\n";
+        assert_eq!(after_newline(text, 3, "    ", |line| line > 0), [(3, "            ".to_owned())]);
+        let text = "/proc/f()\n    switch(x)\n";
+        assert_eq!(after_newline(text, 2, "    ", |line| line > 0), [(2, "        ".to_owned())]);
+    }
+
+    #[test]
+    fn labels() {
+        let text = "/proc/f()\n\tx()\n\t\touter:";
+        assert_eq!(after_colon(text, 2, "\t", |line| line > 0), [(2, "\t".to_owned())]);
+        assert_eq!(after_colon("/proc/f()\n\tx = a ? b :", 1, "\t", |line| line > 0), []);
+    }
+
+    const TABS: Style = Style { insert_spaces: false, tab_size: 4, operator_spacing: true };
+    const SPACES: Style = Style { insert_spaces: true, tab_size: 4, operator_spacing: true };
+
+    fn format(text: &str, style: &Style) -> String {
+        use dm::annotation::{Annotation, AnnotationTree};
+
+        let context = dm::Context::default();
+        let mut annotations = AnnotationTree::default();
+        let indents = dm::indents::IndentProcessor::new(&context, Lexer::new(&context, Default::default(), text.as_bytes()));
+        dm::parser::Parser::new(&context, indents).parse_annotations_only(&mut annotations);
+        let headers: Vec<usize> = annotations.iter()
+            .filter(|(_, annotation)| matches!(annotation, Annotation::ProcHeader(..)))
+            .map(|(range, _)| range.start.line as usize - 1)
+            .collect();
+
+        let proc_header = |line| headers.contains(&line);
+        let mut text = text.to_owned();
+        for (range, replacement) in format_document(&text.clone(), style, proc_header).into_iter().rev() {
+            text.replace_range(range, &replacement);
+        }
+        text
+    }
+
+    #[test]
+    fn spacing() {
+        assert_eq!(format("/proc/f()\n\tx=a+b*  c\n", &TABS), "/proc/f()\n\tx = a + b * c\n");
+        assert_eq!(format("/proc/f()\n\tf(a,b,  c)\n", &TABS), "/proc/f()\n\tf(a, b, c)\n");
+        assert_eq!(format("/proc/f()\n\tx = a<b  &&!c\n", &TABS), "/proc/f()\n\tx = a < b && !c\n");
+        assert_eq!(format("/proc/f()\n\tx=a+b\n", &Style { operator_spacing: false, ..TABS }), "/proc/f()\n\tx=a+b\n");
+
+        // unary operators and paths are left alone
+        let text = "/proc/f(a as num|text)\n\tx = -1\n\treturn -f(-a, /obj/item)\n";
+        assert_eq!(format(text, &TABS), "/proc/f(a as num | text)\n\tx = -1\n\treturn -f(-a, /obj/item)\n");
+
+        // as are strings, interpolations, comments, and directives
+        let text = "#define X(a,b) a+b\n/proc/f()\n\tx = \"a+b,[a+b]\" +1 // a+b,c\n";
+        assert_eq!(format(text, &TABS), "#define X(a,b) a+b\n/proc/f()\n\tx = \"a+b,[a+b]\" + 1 // a+b,c\n");
+    }
+
+    #[test]
+    fn trailing_whitespace_and_blank_lines() {
+        let text = "/obj \n\tvar/x = 1\t\n\n\n  \n\tproc/a()\n\t\treturn {\"\ntext  \n\"}  \n\tproc/b()\n\t\treturn\n";
+        let expected = "/obj\n\tvar/x = 1\n\n\tproc/a()\n\t\treturn {\"\ntext  \n\"}\n\n\tproc/b()\n\t\treturn\n";
+        assert_eq!(format(text, &TABS), expected);
+    }
+
+    #[test]
+    fn indentation() {
+        let spaces = "/obj\n    proc/f()\n        if(x)\n            y()\n    // odd comment\n";
+        let tabs = "/obj\n\tproc/f()\n\t\tif(x)\n\t\t\ty()\n\t// odd comment\n";
+        assert_eq!(format(spaces, &TABS), tabs);
+        assert_eq!(format(tabs, &SPACES), spaces);
+
+        // a run with uneven depths, or braces, keeps its indentation
+        let uneven = "/obj\n    proc/f()\n      y()\n/mob\n    proc/f()\n";
+        assert_eq!(format(uneven, &TABS), "/obj\n    proc/f()\n      y()\n/mob\n\tproc/f()\n");
+        let braces = "/obj{\n    name = \"x\"\n}\n";
+        assert_eq!(format(braces, &TABS), braces);
+    }
+
+    const CORPUS: &str = r#"
+/// A thing which can be held.
+/obj/item
+    name="item"   // shown in examine
+    var/list/contents_list=list(1,2,  3)
+    var/weight = 2*3+1
+    var/description = {"Multiple
+lines,   left  alone  "}
+
+
+
+    proc/use(mob/user,atom/target as mob|obj)
+        if(!user||target==null)
+            return -1
+        else if (weight>=10 &&  weight<20)
+            user.visible_message("[user]  uses [src+1] on [target]." , "you,   do")
+        for(var/i=1,i<=5,i++)
+            weight+=i
+            /* a  block
+               comment */
+        var/total = f(
+            1 ,
+            2)
+        return total%2
+    proc/drop()
+        . = ..()
+        loc = null // gone
+
+
+
+/obj/item/weapon
+    weight = 5
+    use(mob/user)
+        switch(weight)
+            if(1 to 5)
+                return ..() ** 2
+            else
+                return weight - -1
+"#;
+
+    fn tokens_of(text: &str) -> Vec<Token> {
+        let context = dm::Context::default();
+        Lexer::new(&context, Default::default(), text.as_bytes())
+            .map(|token| match token.token {
+                Token::Ident(name, _) => Token::Ident(name, false),
+                other => other,
+            })
+            .filter(|token| !token.is_whitespace())
+            .collect()
+    }
+
+    #[test]
+    fn corpus() {
+        use crate::code_actions::tests::declarations;
+
+        for (style, other) in [(&SPACES, &TABS), (&TABS, &SPACES)] {
+            let formatted = format(CORPUS, style);
+            assert_ne!(formatted, CORPUS);
+            assert_eq!(format(&formatted, style), formatted, "not idempotent:\n{}", formatted);
+            assert_eq!(tokens_of(&formatted), tokens_of(CORPUS));
+            assert_eq!(declarations(&formatted), declarations(CORPUS));
+
+            // and switching back again changes nothing else
+            let back = format(&formatted, other);
+            assert_eq!(format(&back, style), formatted);
+            assert_eq!(declarations(&back), declarations(CORPUS));
+        }
+    }
+}
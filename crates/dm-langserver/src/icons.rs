@@ -0,0 +1,146 @@
+//! Icon state previews, read from .dmi files.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use base64::Engine as _;
+use foldhash::HashMap;
+
+use dm::dmi::{Dir, Metadata, StateIndex};
+
+lazy_static! {
+    static ref ICON_STATE_ASSIGNMENT: regex::Regex = regex::Regex::new(r#"\bicon_state\s*=\s*"([^"\[\]\\]*)""#).unwrap();
+}
+
+/// Find the icon state assigned by an `icon_state = "..."` under the cursor.
+pub fn state_assigned_at(line: &str, column: usize) -> Option<&str> {
+    ICON_STATE_ASSIGNMENT.captures_iter(line)
+        .find(|captures| {
+            let whole = captures.get(0).unwrap();
+            whole.start() <= column && column <= whole.end()
+        })
+        .map(|captures| captures.get(1).unwrap().as_str())
+}
+
+/// How many icons to keep decoded before starting over.
+const CACHE_LIMIT: usize = 64;
+
+/// A decoded .dmi file.
+pub struct Icon {
+    bitmap: lodepng::Bitmap<lodepng::RGBA>,
+    metadata: Metadata,
+}
+
+impl Icon {
+    pub fn from_bytes(data: &[u8]) -> io::Result<Icon> {
+        let (bitmap, metadata) = Metadata::from_bytes(data)?;
+        Ok(Icon { bitmap, metadata })
+    }
+
+    pub fn has_state(&self, state: &str) -> bool {
+        self.metadata.get_icon_state(&StateIndex::from(state)).is_some()
+    }
+
+    /// The name, directions, and frame count of each state, by name.
+    pub fn states(&self) -> Vec<(&str, usize, usize)> {
+        let mut states: Vec<_> = self.metadata.states.iter()
+            .map(|state| (state.name.as_str(), state.dirs.count(), state.frames.count()))
+            .collect();
+        states.sort_by(|a, b| a.0.cmp(b.0));
+        states.dedup_by(|a, b| a.0 == b.0);
+        states
+    }
+
+    /// The first south-facing frame of a state, as a PNG data URI.
+    pub fn preview(&self, state: &str) -> Option<String> {
+        let (x, y, width, height) = self.metadata.rect_of(self.bitmap.width as u32, &StateIndex::from(state), Dir::South, 0)?;
+        let (x, y, width, height) = (x as usize, y as usize, width as usize, height as usize);
+        if x + width > self.bitmap.width || y + height > self.bitmap.height {
+            return None;
+        }
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in y..y + height {
+            let start = row * self.bitmap.width + x;
+            pixels.extend_from_slice(&self.bitmap.buffer[start..start + width]);
+        }
+        let png = lodepng::encode32(&pixels, width, height).ok()?;
+        Some(format!("data:image/png;base64,{}", base64::engine::general_purpose::STANDARD.encode(png)))
+    }
+}
+
+/// An icon as of its file's modification time, or why it couldn't be read.
+type CacheEntry = (Option<SystemTime>, Result<Rc<Icon>, String>);
+
+/// Decoded icons, reloaded when their files change.
+#[derive(Default)]
+pub struct IconCache {
+    icons: HashMap<PathBuf, CacheEntry>,
+}
+
+impl IconCache {
+    pub fn get(&mut self, path: &Path) -> Result<Rc<Icon>, String> {
+        let modified = std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        if let Some((when, icon)) = self.icons.get(path) {
+            if *when == modified {
+                return icon.clone();
+            }
+        }
+        if self.icons.len() >= CACHE_LIMIT {
+            self.icons.clear();
+        }
+        let icon = std::fs::read(path)
+            .and_then(|data| Icon::from_bytes(&data))
+            .map(Rc::new)
+            .map_err(|e| e.to_string());
+        self.icons.insert(path.to_owned(), (modified, icon.clone()));
+        icon
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 64x32 .dmi with a red "pistol" state and a blue "rifle" state.
+    fn test_dmi() -> Vec<u8> {
+        let red = lodepng::RGBA { r: 255, g: 0, b: 0, a: 255 };
+        let blue = lodepng::RGBA { r: 0, g: 0, b: 255, a: 255 };
+        let mut pixels = Vec::new();
+        for _ in 0..32 {
+            pixels.extend(std::iter::repeat_n(red, 32));
+            pixels.extend(std::iter::repeat_n(blue, 32));
+        }
+        let mut encoder = lodepng::Encoder::new();
+        encoder.info_png_mut().add_text(
+            "Description",
+            "# BEGIN DMI\nversion = 4.0\n\twidth = 32\n\theight = 32\nstate = \"pistol\"\n\tdirs = 1\n\tframes = 1\nstate = \"rifle\"\n\tdirs = 1\n\tframes = 1\n# END DMI\n",
+        ).unwrap();
+        encoder.encode(&pixels, 64, 32).unwrap()
+    }
+
+    #[test]
+    fn assignment_under_cursor() {
+        let line = "\ticon_state = \"pistol\" // not icon_state = \"rifle\"";
+        assert_eq!(state_assigned_at(line, 3), Some("pistol"));
+        assert_eq!(state_assigned_at(line, 17), Some("pistol"));
+        assert_eq!(state_assigned_at(line, 26), None);
+        assert_eq!(state_assigned_at("icon_state = \"[base]_on\"", 3), None);
+    }
+
+    #[test]
+    fn state_preview() {
+        let icon = Icon::from_bytes(&test_dmi()).unwrap();
+        assert_eq!(icon.states(), vec![("pistol", 1, 1), ("rifle", 1, 1)]);
+        assert!(icon.has_state("pistol"));
+        assert!(!icon.has_state("pistl"));
+        assert_eq!(icon.preview("pistl"), None);
+
+        let uri = icon.preview("rifle").unwrap();
+        let png = base64::engine::general_purpose::STANDARD.decode(uri.strip_prefix("data:image/png;base64,").unwrap()).unwrap();
+        let image = lodepng::decode32(&png).unwrap();
+        assert_eq!((image.width, image.height), (32, 32));
+        assert!(image.buffer.iter().all(|pixel| (pixel.r, pixel.b) == (0, 255)));
+    }
+}
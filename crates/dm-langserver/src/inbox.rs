@@ -0,0 +1,159 @@
+//! Incoming messages, with `$/cancelRequest` noticed while a request is
+//! still being handled.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Instant;
+
+use foldhash::{HashSet, HashSetExt};
+use jsonrpc::Id;
+
+const CANCEL_METHOD: &str = "$/cancelRequest";
+
+pub enum Next {
+    Message(String),
+    Timeout,
+    Closed,
+}
+
+pub struct Inbox {
+    rx: Receiver<String>,
+    /// Messages read while checking for cancellation, not yet handled.
+    queue: RefCell<VecDeque<String>>,
+    /// The ids of queued requests.
+    queued_ids: RefCell<HashSet<Id>>,
+    /// Requests the client has cancelled, which have not yet been answered.
+    cancelled: RefCell<HashSet<Id>>,
+    /// The request currently being handled.
+    current: RefCell<Option<Id>>,
+}
+
+impl Inbox {
+    pub fn new(rx: Receiver<String>) -> Inbox {
+        Inbox {
+            rx,
+            queue: Default::default(),
+            queued_ids: RefCell::new(HashSet::new()),
+            cancelled: RefCell::new(HashSet::new()),
+            current: Default::default(),
+        }
+    }
+
+    /// Wait for the next message, or until the deadline passes.
+    pub fn next(&self, deadline: Option<Instant>) -> Next {
+        if let Some(message) = self.queue.borrow_mut().pop_front() {
+            return Next::Message(message);
+        }
+        match deadline {
+            Some(deadline) => match self.rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(message) => Next::Message(message),
+                Err(RecvTimeoutError::Timeout) => Next::Timeout,
+                Err(RecvTimeoutError::Disconnected) => Next::Closed,
+            },
+            None => match self.rx.recv() {
+                Ok(message) => Next::Message(message),
+                Err(_) => Next::Closed,
+            },
+        }
+    }
+
+    /// Start handling a request. Returns `false` if it was already
+    /// cancelled while it waited in the queue.
+    pub fn begin_request(&self, id: &Id) -> bool {
+        self.queued_ids.borrow_mut().remove(id);
+        if self.cancelled.borrow_mut().remove(id) {
+            return false;
+        }
+        *self.current.borrow_mut() = Some(id.clone());
+        true
+    }
+
+    pub fn end_request(&self) {
+        if let Some(id) = self.current.borrow_mut().take() {
+            self.cancelled.borrow_mut().remove(&id);
+        }
+    }
+
+    /// Check whether the client has cancelled the current request, reading
+    /// any messages which have arrived in the meantime.
+    pub fn is_cancelled(&self) -> bool {
+        while let Ok(message) = self.rx.try_recv() {
+            match parse_cancel(&message) {
+                Some(id) => {
+                    let current = self.current.borrow().as_ref() == Some(&id);
+                    if current || self.queued_ids.borrow().contains(&id) {
+                        self.cancelled.borrow_mut().insert(id);
+                    }
+                },
+                None => {
+                    if let Some(id) = parse_request_id(&message) {
+                        self.queued_ids.borrow_mut().insert(id);
+                    }
+                    self.queue.borrow_mut().push_back(message);
+                },
+            }
+        }
+        match self.current.borrow().as_ref() {
+            Some(id) => self.cancelled.borrow().contains(id),
+            None => false,
+        }
+    }
+}
+
+/// If the message is a `$/cancelRequest`, get the id it cancels.
+fn parse_cancel(message: &str) -> Option<Id> {
+    if !message.contains(CANCEL_METHOD) {
+        return None;
+    }
+    let value: serde_json::Value = serde_json::from_str(message).ok()?;
+    if value["method"] != CANCEL_METHOD {
+        return None;
+    }
+    serde_json::from_value(value["params"]["id"].clone()).ok()
+}
+
+fn parse_request_id(message: &str) -> Option<Id> {
+    let value: serde_json::Value = serde_json::from_str(message).ok()?;
+    // Only requests have both a method and an id.
+    value.get("method")?;
+    serde_json::from_value(value.get("id")?.clone()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn request(id: u64) -> String {
+        format!(r#"{{"jsonrpc":"2.0","id":{},"method":"textDocument/references","params":{{}}}}"#, id)
+    }
+
+    fn cancel(id: u64) -> String {
+        format!(r#"{{"jsonrpc":"2.0","method":"$/cancelRequest","params":{{"id":{}}}}}"#, id)
+    }
+
+    #[test]
+    fn cancel_current_and_queued() {
+        let (tx, rx) = channel();
+        let inbox = Inbox::new(rx);
+        assert!(inbox.begin_request(&Id::Num(1)));
+        assert!(!inbox.is_cancelled());
+
+        tx.send(request(2)).unwrap();
+        tx.send(cancel(1)).unwrap();
+        tx.send(cancel(2)).unwrap();
+        // A cancellation for a request which was already answered.
+        tx.send(cancel(0)).unwrap();
+        assert!(inbox.is_cancelled());
+        inbox.end_request();
+
+        // The queued request is still delivered, but answered as cancelled.
+        match inbox.next(None) {
+            Next::Message(message) => assert_eq!(message, request(2)),
+            _ => panic!("expected the queued request"),
+        }
+        assert!(!inbox.begin_request(&Id::Num(2)));
+        assert!(inbox.cancelled.borrow().is_empty());
+    }
+}
@@ -3,6 +3,7 @@
 //! JSON-RPC over stdin/stdout with Content-Length headers.
 
 use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{channel, Receiver};
 
 pub fn run_until_stdin_eof<F: FnMut(&str)>(mut f: F) {
     let stdin = io::stdin();
@@ -12,6 +13,18 @@ pub fn run_until_stdin_eof<F: FnMut(&str)>(mut f: F) {
     }
 }
 
+/// Read messages from stdin on a background thread, so that the caller can
+/// wake up for timers between them. The channel closes at EOF.
+pub fn spawn_stdin_reader() -> Receiver<String> {
+    let (tx, rx) = channel();
+    std::thread::spawn(move || {
+        run_until_stdin_eof(|message| {
+            let _ = tx.send(message.to_owned());
+        });
+    });
+    rx
+}
+
 pub fn run_with_read<R: BufRead, F: FnMut(&str)>(input: &mut R, mut f: F) {
     while let Some(message) = read(input).expect("JSON-RPC read error") {
         f(&message);
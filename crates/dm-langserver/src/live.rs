@@ -0,0 +1,97 @@
+//! Interim diagnostics for edited files, between full environment rebuilds.
+
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use foldhash::HashMap;
+use url::Url;
+
+use dm::objtree::ObjectTree;
+use dm::preprocessor::DefineHistory;
+use dm::{Context, DMError};
+
+/// How long after the last edit to a file before it is re-analyzed.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+#[derive(Default)]
+pub struct LiveAnalysis {
+    /// Edited files, and when they are due to be re-analyzed.
+    pending: HashMap<Url, Instant>,
+    /// Whether interim diagnostics were published since the environment was
+    /// last parsed.
+    stale: bool,
+}
+
+impl LiveAnalysis {
+    /// Note an edit to a file, pushing back its re-analysis.
+    pub fn schedule(&mut self, url: Url) {
+        self.pending.insert(url, Instant::now() + DEBOUNCE);
+    }
+
+    /// When the next file is due, if any are waiting.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.values().min().copied()
+    }
+
+    /// Remove and return the files which are due now.
+    pub fn take_due(&mut self) -> Vec<Url> {
+        let now = Instant::now();
+        let due: Vec<Url> = self.pending.iter()
+            .filter(|&(_, &deadline)| deadline <= now)
+            .map(|(url, _)| url.clone())
+            .collect();
+        for url in due.iter() {
+            self.pending.remove(url);
+        }
+        if !due.is_empty() {
+            self.stale = true;
+        }
+        due
+    }
+
+    /// Whether a full rebuild is needed to replace interim diagnostics,
+    /// resetting the flag.
+    pub fn take_stale(&mut self) -> bool {
+        std::mem::take(&mut self.stale)
+    }
+}
+
+/// Re-parse one file of the environment against the rest of its object
+/// tree, and run the proc-local checks on it.
+///
+/// Files outside the environment are only parsed on their own, with the
+/// macros defined at the end of the environment, for their syntax errors.
+///
+/// Returns the errors in that file.
+pub fn analyze_file(
+    context: &Context,
+    defines: &DefineHistory,
+    macros: &dreamchecker::MacroNames,
+    objtree: &ObjectTree,
+    path: &Path,
+    contents: Box<dyn io::Read>,
+) -> Result<Vec<DMError>, DMError> {
+    let file_id = context.get_file(path).filter(|&file| context.include_graph().contains(file));
+    let Some(file_id) = file_id else {
+        let mut preprocessor = defines.branch_at_end(context);
+        let file_id = preprocessor.push_file(path.to_owned(), contents)?;
+        let mut errors = dm::outline::parse_preprocessed(context, preprocessor).errors;
+        errors.retain(|error| error.location().file == file_id);
+        return Ok(errors);
+    };
+    // Collect this pass's errors separately from the environment's.
+    let before = context.errors().len();
+    let mut preprocessor = defines.branch_at_file(file_id, context);
+    preprocessor.push_file(path.to_owned(), contents)?;
+    {
+        let indent = dm::indents::IndentProcessor::new(context, &mut preprocessor);
+        let mut parser = dm::parser::Parser::new(context, indent);
+        parser.enable_procs();
+        let overlay = parser.parse_object_tree_overlay(objtree, file_id);
+        dreamchecker::run_file(context, &overlay, macros, file_id);
+    }
+    let mut errors = context.errors_mut().split_off(before);
+    errors.retain(|error| error.location().file == file_id);
+    Ok(errors)
+}
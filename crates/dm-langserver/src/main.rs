@@ -25,20 +25,37 @@ extern crate lazy_static;
 #[macro_use]
 mod macros;
 mod background;
+mod code_actions;
+mod code_lens;
 mod color;
 mod completion;
 mod document;
+mod document_symbols;
 mod extras;
 mod find_references;
+mod folding;
+mod formatting;
+mod icons;
+mod inbox;
 mod jrpc_io;
+mod live;
+mod positions;
+mod progress;
+mod selection;
+mod semantic_tokens;
+mod suggestions;
 mod symbol_search;
+mod type_hierarchy;
+mod watch;
+mod workspaces;
 
 mod debugger;
 
+use std::borrow::Cow;
 use std::collections::hash_map::Entry;
 use std::collections::VecDeque;
 use foldhash::{HashMap, HashMapExt, HashSet, HashSetExt};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
@@ -47,9 +64,12 @@ use lsp_types::MessageType;
 use url::Url;
 
 use dm::annotation::{Annotation, AnnotationTree};
+use dm::dmm;
 use dm::objtree::TypeRef;
 use dm::FileId;
 
+use find_references::ProcKey;
+
 fn main() {
     std::env::set_var("RUST_BACKTRACE", "1");
 
@@ -82,6 +102,8 @@ fn main() {
     if let Some(arg) = args.next() {
         if arg == "--debugger" {
             return debugger::debugger_main(args);
+        } else if arg == "--find-references" {
+            return find_references::find_references_main(args);
         } else if arg == "--version" {
             return;
         } else {
@@ -89,13 +111,28 @@ fn main() {
         }
     }
 
-    let mut engine = Engine::new();
-    jrpc_io::run_until_stdin_eof(|message| engine.handle_input(message));
+    let mut engine = Engine::new(inbox::Inbox::new(jrpc_io::spawn_stdin_reader()));
+    loop {
+        match engine.inbox.next(engine.next_deadline()) {
+            inbox::Next::Message(message) => engine.handle_input(&message),
+            inbox::Next::Timeout => engine.run_timers(),
+            inbox::Next::Closed => break,
+        }
+    }
     engine.exit(0);
 }
 
 const VERSION: Option<jsonrpc::Version> = Some(jsonrpc::Version::V2);
 
+/// How often long request handlers check whether they were cancelled.
+const CANCEL_CHECK_INTERVAL: usize = 256;
+
+/// How many references to gather before sending them as a partial result.
+const REFERENCES_BATCH: usize = 1000;
+
+/// How many children of a type to list in one object tree response.
+const OBJECT_TREE_PAGE: usize = 500;
+
 #[derive(PartialEq)]
 enum InitStatus {
     Starting,
@@ -109,7 +146,83 @@ type Span = interval_tree::RangeInclusive<dm::Location>;
 struct ClientCaps {
     related_info: bool,
     label_offset_support: bool,
+    snippet_support: bool,
     object_tree: bool,
+    work_done_progress: bool,
+    watched_files: bool,
+    configuration: bool,
+}
+
+/// Which kinds of inlay hints the client wants, from `dreammaker.inlayHints`.
+#[derive(Debug)]
+struct InlayHintSettings {
+    types: bool,
+    parameter_names: bool,
+    parent_call_arguments: bool,
+}
+
+impl Default for InlayHintSettings {
+    fn default() -> Self {
+        InlayHintSettings {
+            types: true,
+            parameter_names: true,
+            parent_call_arguments: true,
+        }
+    }
+}
+
+impl InlayHintSettings {
+    fn update(&mut self, settings: &serde_json::Value) {
+        if let Some(value) = settings["types"].as_bool() {
+            self.types = value;
+        }
+        if let Some(value) = settings["parameterNames"].as_bool() {
+            self.parameter_names = value;
+        }
+        if let Some(value) = settings["parentCallArguments"].as_bool() {
+            self.parent_call_arguments = value;
+        }
+    }
+}
+
+/// How to format documents, from `dreammaker.formatting`.
+#[derive(Debug)]
+struct FormattingSettings {
+    /// Indent with spaces rather than tabs, or as the editor says if unset.
+    insert_spaces: Option<bool>,
+    operator_spacing: bool,
+}
+
+impl Default for FormattingSettings {
+    fn default() -> Self {
+        FormattingSettings {
+            insert_spaces: None,
+            operator_spacing: true,
+        }
+    }
+}
+
+impl FormattingSettings {
+    fn update(&mut self, settings: &serde_json::Value) {
+        if let Some(value) = settings["indentStyle"].as_str() {
+            self.insert_spaces = match value {
+                "spaces" => Some(true),
+                "tabs" => Some(false),
+                _ => None,
+            };
+        }
+        if let Some(value) = settings["operatorSpacing"].as_bool() {
+            self.operator_spacing = value;
+        }
+    }
+
+    fn style(&self, options: &lsp_types::FormattingOptions) -> formatting::Style {
+        formatting::Style {
+            insert_spaces: self.insert_spaces.unwrap_or(options.insert_spaces),
+            tab_size: options.tab_size.max(1) as usize,
+            operator_spacing: self.operator_spacing,
+        }
+    }
 }
 
 impl ClientCaps {
@@ -126,12 +239,35 @@ impl ClientCaps {
                 }
             }
 
+            if let Some(ref completion) = text_document.completion {
+                if let Some(ref completion_item) = completion.completion_item {
+                    if let Some(snippet_support) = completion_item.snippet_support {
+                        this.snippet_support = snippet_support;
+                    }
+                }
+            }
+
             if let Some(ref publish_diagnostics) = text_document.publish_diagnostics {
                 if let Some(related_info) = publish_diagnostics.related_information {
                     this.related_info = related_info;
                 }
             }
         }
+        if let Some(ref window) = caps.window {
+            if let Some(work_done_progress) = window.work_done_progress {
+                this.work_done_progress = work_done_progress;
+            }
+        }
+        if let Some(ref workspace) = caps.workspace {
+            if let Some(ref did_change_watched_files) = workspace.did_change_watched_files {
+                if let Some(dynamic_registration) = did_change_watched_files.dynamic_registration {
+                    this.watched_files = dynamic_registration;
+                }
+            }
+            if let Some(configuration) = workspace.configuration {
+                this.configuration = configuration;
+            }
+        }
         if let Some(ref experimental) = caps.experimental {
             if let Some(dreammaker) = experimental.get("dreammaker") {
                 if let Some(object_tree) = dreammaker.get("objectTree") {
@@ -148,6 +284,8 @@ impl ClientCaps {
 #[derive(Default)]
 struct DiagnosticsTracker {
     sent: HashSet<Url>,
+    /// How many of each severity the last full round had.
+    counts: extras::DiagnosticCounts,
 }
 
 impl DiagnosticsTracker {
@@ -160,51 +298,65 @@ impl DiagnosticsTracker {
         }
     }
 
-    fn build(root: Option<&Url>, file_list: &dm::FileList, errors: &[dm::DMError], related_info: bool) -> HashMap<Url, Vec<lsp_types::Diagnostic>> {
+    fn build(
+        root: Option<&Url>,
+        file_list: &dm::FileList,
+        errors: &[dm::DMError],
+        related_info: bool,
+        define_names: Option<&suggestions::DefineNames>,
+        lines: &dyn Fn(&Url) -> Rc<positions::LineIndex>,
+    ) -> HashMap<Url, Vec<lsp_types::Diagnostic>> {
         let mut map: HashMap<_, Vec<_>> = HashMap::new();
         for error in errors.iter() {
             let loc = error.location();
-            let related_information = if !related_info || error.notes().is_empty() {
+            let mut notes: Vec<(dm::Location, String)> = error.notes().iter()
+                .map(|note| (note.location(), note.description().to_owned()))
+                .collect();
+            notes.extend(define_names.and_then(|names| names.suggest(error)));
+
+            let related_information = if !related_info || notes.is_empty() {
                 None
             } else {
-                let mut notes = Vec::with_capacity(error.notes().len());
-                for note in error.notes().iter() {
-                    let Some(uri) = DiagnosticsTracker::file_url(root, file_list, note.location().file) else { continue };
-                    notes.push(lsp_types::DiagnosticRelatedInformation {
-                        location: lsp_types::Location {
-                            uri,
-                            range: location_to_range(note.location()),
-                        },
-                        message: note.description().to_owned(),
+                let mut related = Vec::with_capacity(notes.len());
+                for (location, message) in notes.iter() {
+                    let Some(uri) = DiagnosticsTracker::file_url(root, file_list, location.file) else { continue };
+                    let range = lines(&uri).range(*location);
+                    related.push(lsp_types::DiagnosticRelatedInformation {
+                        location: lsp_types::Location { uri, range },
+                        message: message.clone(),
                     });
                 }
-                Some(notes)
+                Some(related)
             };
+            let Some(uri) = DiagnosticsTracker::file_url(root, file_list, loc.file) else { continue };
             let diag = lsp_types::Diagnostic {
                 message: error.description().to_owned(),
                 severity: Some(convert_severity(error.severity())),
-                range: location_to_range(loc),
+                range: match error.end() {
+                    Some(end) => lines(&uri).span(loc..end),
+                    None => lines(&uri).range(loc),
+                },
                 source: component_to_source(error.component()),
                 code: convert_errorcode(error.errortype()),
+                tags: convert_tags(error.errortype()),
                 related_information,
                 .. Default::default()
             };
-            let Some(uri) = DiagnosticsTracker::file_url(root, file_list, loc.file) else { continue };
             map.entry(uri)
                 .or_insert_with(Default::default)
                 .push(diag);
 
             if !related_info {
                 // Fallback in case the client does not support related info
-                for note in error.notes().iter() {
+                for (location, message) in notes {
+                    let Some(uri) = DiagnosticsTracker::file_url(root, file_list, location.file) else { continue };
                     let diag = lsp_types::Diagnostic {
-                        message: note.description().to_owned(),
+                        message,
                         severity: Some(lsp_types::DiagnosticSeverity::INFORMATION),
-                        range: location_to_range(note.location()),
+                        range: lines(&uri).range(location),
                         source: component_to_source(error.component()),
                         .. Default::default()
                     };
-                    let Some(uri) = DiagnosticsTracker::file_url(root, file_list, note.location().file) else { continue };
                     map.entry(uri)
                         .or_insert_with(Default::default)
                         .push(diag);
@@ -214,7 +366,30 @@ impl DiagnosticsTracker {
         map
     }
 
+    /// Publish interim diagnostics for one file, to be replaced by the next
+    /// full rebuild.
+    fn send_interim(&mut self, url: Url, diagnostics: Vec<lsp_types::Diagnostic>) {
+        self.sent.insert(url.clone());
+        issue_notification::<lsp_types::notification::PublishDiagnostics>(
+            lsp_types::PublishDiagnosticsParams {
+                uri: url,
+                diagnostics,
+                version: None,
+            },
+        );
+    }
+
     fn send(&mut self, map: HashMap<Url, Vec<lsp_types::Diagnostic>>) {
+        self.counts = Default::default();
+        for diagnostic in map.values().flatten() {
+            match diagnostic.severity {
+                Some(lsp_types::DiagnosticSeverity::WARNING) => self.counts.warnings += 1,
+                Some(lsp_types::DiagnosticSeverity::INFORMATION) => self.counts.info += 1,
+                Some(lsp_types::DiagnosticSeverity::HINT) => self.counts.hints += 1,
+                _ => self.counts.errors += 1,
+            }
+        }
+
         let mut new_sent = HashSet::with_capacity(map.len());
         for (url, diagnostics) in map {
             self.sent.remove(&url);  // don't erase below
@@ -243,46 +418,114 @@ impl DiagnosticsTracker {
 
 struct Engine {
     docs: document::DocumentStore,
+    inbox: inbox::Inbox,
 
     status: InitStatus,
     parent_pid: u32,
     threads: Vec<std::thread::JoinHandle<()>>,
-    root: Option<Url>,
+    /// Every workspace folder, with the active one's state held below.
+    workspaces: Vec<workspaces::Workspace>,
+    active: usize,
+    environment_setting: workspaces::EnvironmentSetting,
+    /// Macros to define before reading each environment, from
+    /// `dreammaker.defines`.
+    define_setting: std::collections::BTreeMap<String, String>,
+    /// Whether the environments have been loaded since initialization.
+    environments_loaded: bool,
+    /// An unanswered `workspace/configuration` request, and the roots it
+    /// asked about.
+    configuration_request: Option<(jsonrpc::Id, Vec<Option<Url>>)>,
+    watch_registered: bool,
+    /// Open files which no environment includes.
+    orphans: HashSet<Url>,
 
+    root: Option<Url>,
+    environment: Option<PathBuf>,
     context: dm::Context,
     defines: Option<dm::preprocessor::DefineHistory>,
     objtree: Arc<dm::objtree::ObjectTree>,
     references_table: background::Background<find_references::ReferencesTable>,
+    symbol_index: Option<symbol_search::SymbolIndex>,
 
     annotations: HashMap<Url, (FileId, FileId, Rc<AnnotationTree>)>,
     diagnostics_tracker: Arc<Mutex<DiagnosticsTracker>>,
+    define_names: Arc<suggestions::DefineNames>,
+    /// Every macro the environment defined, for dreamchecker.
+    macro_names: Arc<dreamchecker::MacroNames>,
+    live: live::LiveAnalysis,
+    watcher: watch::FileWatcher,
+    /// When the environment was last parsed, from start to finish.
+    rebuilt: Option<(std::time::SystemTime, std::time::SystemTime)>,
+    icons: icons::IconCache,
+    line_indexes: positions::LineIndexes,
+    /// Whether the client has asked for the object tree by path, and so
+    /// wants to hear when it is rebuilt.
+    object_tree_requested: bool,
 
     client_caps: ClientCaps,
     extools_dll: Option<String>,
     debug_server_dll: Option<String>,
+    inlay_hints: InlayHintSettings,
+    /// Whether to offer code lenses, from `dreammaker.codeLens`.
+    code_lens: bool,
+    /// Whether to link includes and resources which can't be found, from
+    /// `dreammaker.linkMissingFiles`.
+    link_missing_files: bool,
+    /// Whether to re-analyze files as they are edited, from
+    /// `dreammaker.liveDiagnostics`.
+    live_diagnostics: bool,
+    /// Whether to fix indentation as the user types, from
+    /// `dreammaker.formatOnType`.
+    format_on_type: bool,
+    formatting: FormattingSettings,
 }
 
 impl Engine {
-    fn new() -> Self {
+    fn new(inbox: inbox::Inbox) -> Self {
         Engine {
             docs: Default::default(),
+            inbox,
 
             status: InitStatus::Starting,
             parent_pid: 0,
             threads: Default::default(),
-            root: None,
+            workspaces: vec![Default::default()],
+            active: 0,
+            environment_setting: Default::default(),
+            define_setting: Default::default(),
+            environments_loaded: false,
+            configuration_request: None,
+            watch_registered: false,
+            orphans: Default::default(),
 
+            root: None,
+            environment: None,
             context: dm::Context::default(),
             defines: None,
             objtree: Default::default(),
             references_table: Default::default(),
+            symbol_index: None,
 
             annotations: Default::default(),
             diagnostics_tracker: Arc::new(Mutex::new(Default::default())),
+            define_names: Default::default(),
+            macro_names: Default::default(),
+            live: Default::default(),
+            watcher: Default::default(),
+            rebuilt: None,
+            icons: Default::default(),
+            line_indexes: Default::default(),
+            object_tree_requested: false,
 
             client_caps: Default::default(),
             extools_dll: None,
             debug_server_dll: None,
+            inlay_hints: Default::default(),
+            code_lens: true,
+            link_missing_files: false,
+            live_diagnostics: true,
+            format_on_type: true,
+            formatting: Default::default(),
         }
     }
 
@@ -326,6 +569,67 @@ impl Engine {
         }
     }
 
+    /// The non-ASCII lines of a document, for converting its columns to
+    /// positions.
+    fn lines_of(&self, url: &Url) -> Rc<positions::LineIndex> {
+        self.line_indexes.get(url, || self.docs.get_contents(url).ok().map(Cow::into_owned))
+    }
+
+    fn line_index(&self, file: FileId) -> Rc<positions::LineIndex> {
+        match self.file_url(file) {
+            Ok(url) => self.lines_of(&url),
+            Err(_) => Default::default(),
+        }
+    }
+
+    fn location_to_range(&self, loc: dm::Location) -> lsp_types::Range {
+        if loc.is_builtins() {
+            return positions::LineIndex::default().range(loc);
+        }
+        self.line_index(loc.file).range(loc)
+    }
+
+    fn span_to_range(&self, span: std::ops::Range<dm::Location>) -> lsp_types::Range {
+        self.line_index(span.start.file).span(span)
+    }
+
+    /// The location in a document at a position the client sent.
+    fn document_location(&self, url: &Url, file: FileId, position: lsp_types::Position) -> dm::Location {
+        self.lines_of(url).location(file, position)
+    }
+
+    /// Describe the state of an icon under some name, with a preview.
+    fn icon_state_hover(&mut self, icon: &str, icon_file: FileId, state: &str) -> Result<Option<String>, jsonrpc::Error> {
+        let file_path = url_to_path(&self.file_url(icon_file)?)?;
+        let Some(path) = self.resolve_resource(&file_path, icon)? else {
+            return Ok(Some(format!("`'{}'` was not found", icon)));
+        };
+        Ok(Some(match self.icons.get(&path) {
+            Ok(dmi) => match dmi.preview(state) {
+                Some(uri) => format!("![{}]({})\n\n`'{}'`, \"{}\"", state, uri, icon, state),
+                None if dmi.has_state(state) => format!("`'{}'`, \"{}\"", icon, state),
+                None => format!("icon state \"{}\" is not in `'{}'`", state, icon),
+            },
+            Err(e) => format!("could not read `'{}'`: {}", icon, e),
+        }))
+    }
+
+    /// Find a resource named in a file the same way the compiler would.
+    fn resolve_resource(&self, file_path: &std::path::Path, resource: &str) -> Result<Option<PathBuf>, jsonrpc::Error> {
+        let (env_file, file_dirs) = match self.defines {
+            Some(ref defines) => (defines.env_file().to_owned(), defines.file_dirs()),
+            None => (file_path.to_owned(), Vec::new()),
+        };
+        let Some(path) = dm::preprocessor::resolve_resource(file_path, &env_file, &file_dirs, resource) else {
+            return Ok(None);
+        };
+        if path.is_relative() {
+            Ok(Some(std::env::current_dir().map_err(invalid_request)?.join(path)))
+        } else {
+            Ok(Some(path))
+        }
+    }
+
     fn location_link(&self, loc: dm::Location) -> Result<String, jsonrpc::Error> {
         if loc.is_builtins() {
             Ok(String::new())
@@ -352,10 +656,44 @@ impl Engine {
             } else {
                 self.file_url(loc.file)?
             },
-            range: location_to_range(loc),
+            range: self.location_to_range(loc),
         })
     }
 
+    /// Send the references to a symbol as partial results, a batch of files
+    /// at a time.
+    fn stream_references(&mut self, token: lsp_types::ProgressToken, id: dm::objtree::SymbolId) -> Result<(), jsonrpc::Error> {
+        self.references_table.poll();
+        let Some(table) = self.references_table.value() else {
+            return Ok(());
+        };
+        let send = |value| issue_notification::<extras::ReferencesPartialResult>(extras::PartialResultProgress {
+            token: token.clone(),
+            value,
+        });
+
+        // The grouped query leaves out builtins, so send those up front.
+        let mut batch = Vec::new();
+        for &location in table.find_references(id, false).iter().filter(|location| location.is_builtins()) {
+            batch.push(self.convert_location(location, &Default::default(), &[])?);
+        }
+        for (file, locations) in table.find_references_grouped(id, self.context.file_list()) {
+            let uri = self.file_url(file)?;
+            let lines = self.lines_of(&uri);
+            batch.extend(locations.iter().map(|&location| lsp_types::Location::new(uri.clone(), lines.range(location))));
+            if batch.len() >= REFERENCES_BATCH {
+                if self.inbox.is_cancelled() {
+                    return Err(request_cancelled());
+                }
+                send(std::mem::take(&mut batch));
+            }
+        }
+        if !batch.is_empty() {
+            send(batch);
+        }
+        Ok(())
+    }
+
     // ------------------------------------------------------------------------
     // Object tree explorer
 
@@ -392,7 +730,7 @@ impl Engine {
         for (name, var) in ty.vars.iter() {
             let is_declaration = var.declaration.is_some();
             entry.vars.push(extras::ObjectTreeVar {
-                name: name.to_owned(),
+                name: name.to_string(),
                 kind: lsp_types::SymbolKind::FIELD,
                 location: self.convert_location(var.value.location, &var.value.docs, &[&ty.path, "/var/", name]).ok(),
                 is_declaration,
@@ -405,7 +743,7 @@ impl Engine {
             let mut is_verb = proc.declaration.as_ref().map(|d| d.kind.is_verb());
             for value in proc.value.iter() {
                 entry.procs.push(extras::ObjectTreeProc {
-                    name: name.to_owned(),
+                    name: name.to_string(),
                     kind: lsp_types::SymbolKind::METHOD,
                     location: self.convert_location(value.location, &value.docs, &[&ty.path, "/proc/", name]).ok(),
                     is_verb,
@@ -424,6 +762,407 @@ impl Engine {
         entry
     }
 
+    /// One type of the object tree, with a page of its children.
+    fn object_tree_node(&self, path: &str, offset: usize, limit: Option<usize>) -> Option<extras::ObjectTreeNode> {
+        let ty = if path.is_empty() || path == "/" {
+            self.objtree.root()
+        } else {
+            self.objtree.find(path)?
+        };
+
+        let mut vars: Vec<_> = ty.vars.iter()
+            .map(|(name, var)| extras::ObjectTreeNodeVar {
+                name: name.to_string(),
+                location: self.convert_location(var.value.location, &var.value.docs, &[&ty.path, "/var/", name]).ok(),
+                is_declaration: var.declaration.is_some(),
+                value: var.value.constant.as_ref().map(ToString::to_string),
+            })
+            .collect();
+        vars.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut procs: Vec<_> = ty.procs.iter()
+            .map(|(name, proc)| {
+                let value = proc.main_value();
+                extras::ObjectTreeNodeProc {
+                    name: name.to_string(),
+                    location: self.convert_location(value.location, &value.docs, &[&ty.path, "/proc/", name]).ok(),
+                    is_declaration: proc.declaration.is_some(),
+                    is_verb: ty.get_proc_declaration(name).is_some_and(|decl| decl.kind.is_verb()),
+                }
+            })
+            .collect();
+        procs.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut children: Vec<_> = ty.children().collect();
+        children.sort_by(|a, b| a.name().cmp(b.name()));
+        let limit = limit.unwrap_or(OBJECT_TREE_PAGE).max(1);
+        let next_offset = offset.saturating_add(limit);
+
+        Some(extras::ObjectTreeNode {
+            path: if ty.is_root() { "/".to_owned() } else { ty.path.to_string() },
+            name: ty.name().to_owned(),
+            parent: ty.parent_type().map(|parent| parent.path.to_string()),
+            location: if ty.is_root() { None } else { self.convert_location(ty.location, &ty.docs, &[&ty.path]).ok() },
+            vars,
+            procs,
+            child_count: children.len(),
+            children: children.iter()
+                .skip(offset)
+                .take(limit)
+                .map(|child| extras::ObjectTreeChild {
+                    path: child.path.to_string(),
+                    name: child.name().to_owned(),
+                    location: self.convert_location(child.location, &child.docs, &[&child.path]).ok(),
+                    child_count: child.children().count(),
+                })
+                .collect(),
+            next_offset: if next_offset < children.len() { Some(next_offset) } else { None },
+        })
+    }
+
+    // ------------------------------------------------------------------------
+    // Workspace folders
+
+    /// Add a workspace folder, leaving it active.
+    fn add_workspace(&mut self, name: String, mut url: Url) -> usize {
+        if !url.path().ends_with('/') {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+        eprintln!("workspace root: {}", url);
+
+        let index = if self.workspaces.len() == 1 && self.root.is_none() {
+            // Replace the placeholder for single file mode.
+            self.workspaces[0].name = name;
+            self.root = Some(url);
+            0
+        } else {
+            self.workspaces.push(workspaces::Workspace::new(name, Some(url)));
+            self.workspaces.len() - 1
+        };
+        self.activate(index);
+        self.load_config();
+        index
+    }
+
+    fn remove_workspace(&mut self, url: &Url) {
+        let Some(index) = (0..self.workspaces.len()).find(|&index| {
+            self.workspace_view(index).0.is_some_and(|root| root.path().trim_end_matches('/') == url.path().trim_end_matches('/'))
+        }) else {
+            return;
+        };
+        eprintln!("removed workspace root: {}", url);
+        self.activate(index);
+        let mut removed = workspaces::Workspace::default();
+        self.exchange(&mut removed);
+        removed.diagnostics_tracker.lock().unwrap().send(Default::default());
+        if self.workspaces.len() > 1 {
+            self.workspaces.remove(index);
+            let mut workspaces = std::mem::take(&mut self.workspaces);
+            self.exchange(&mut workspaces[0]);
+            self.workspaces = workspaces;
+            self.active = 0;
+        } else {
+            self.workspaces[0] = Default::default();
+        }
+    }
+
+    /// Make the workspace at `index` the one the engine's fields describe.
+    fn activate(&mut self, index: usize) {
+        if index == self.active {
+            return;
+        }
+        let mut workspaces = std::mem::take(&mut self.workspaces);
+        self.exchange(&mut workspaces[self.active]);
+        self.exchange(&mut workspaces[index]);
+        self.workspaces = workspaces;
+        self.active = index;
+    }
+
+    fn exchange(&mut self, workspace: &mut workspaces::Workspace) {
+        use std::mem::swap;
+        swap(&mut self.root, &mut workspace.root);
+        swap(&mut self.environment, &mut workspace.environment);
+        swap(&mut self.context, &mut workspace.context);
+        swap(&mut self.defines, &mut workspace.defines);
+        swap(&mut self.objtree, &mut workspace.objtree);
+        swap(&mut self.references_table, &mut workspace.references_table);
+        swap(&mut self.symbol_index, &mut workspace.symbol_index);
+        swap(&mut self.annotations, &mut workspace.annotations);
+        swap(&mut self.diagnostics_tracker, &mut workspace.diagnostics_tracker);
+        swap(&mut self.define_names, &mut workspace.define_names);
+        swap(&mut self.macro_names, &mut workspace.macro_names);
+        swap(&mut self.live, &mut workspace.live);
+        swap(&mut self.watcher, &mut workspace.watcher);
+        swap(&mut self.rebuilt, &mut workspace.rebuilt);
+    }
+
+    /// The root, context, and loaded environment of any workspace.
+    fn workspace_view(&self, index: usize) -> (Option<&Url>, &dm::Context, Option<&std::path::Path>) {
+        if index == self.active {
+            (self.root.as_ref(), &self.context, self.environment.as_deref())
+        } else {
+            let workspace = &self.workspaces[index];
+            (workspace.root.as_ref(), &workspace.context, workspace.environment.as_deref())
+        }
+    }
+
+    /// Find the workspace a file belongs to: preferably one whose environment
+    /// includes it, and otherwise the innermost folder containing it.
+    fn workspace_for(&self, url: &Url) -> Option<usize> {
+        let path = url_to_path(url).ok()?;
+        let mut best = None;
+        for index in 0..self.workspaces.len() {
+            let (Some(root), context, _) = self.workspace_view(index) else { continue };
+            let Ok(root) = url_to_path(root) else { continue };
+            let Ok(relative) = path.strip_prefix(&root) else { continue };
+            let included = context.get_file(relative).is_some_and(|file| context.include_graph().contains(file));
+            let key = (included, root.components().count());
+            if best.is_none_or(|(best_key, _)| key > best_key) {
+                best = Some((key, index));
+            }
+        }
+        best.map(|(_, index)| index)
+    }
+
+    /// Activate the workspace a request or notification is about, judging by
+    /// the document it names.
+    fn route(&mut self, params: &serde_json::Value) {
+        let uri = params.pointer("/textDocument/uri")
+            .or_else(|| params.pointer("/item/uri"))
+            .or_else(|| params.pointer("/data/uri"));
+        let Some(url) = uri.and_then(|uri| uri.as_str()).and_then(|uri| Url::parse(uri).ok()) else {
+            return;
+        };
+        if let Some(index) = self.workspace_for(&url) {
+            self.activate(index);
+        }
+    }
+
+    fn load_config(&mut self) {
+        if let Some(root_path) = self.root.as_ref().and_then(|root| url_to_path(root).ok()) {
+            let config_path = root_path.join("SpacemanDMM.toml");
+            if config_path.exists() {
+                self.context.force_config(&config_path);
+            }
+        }
+        for (name, value) in self.define_setting.iter() {
+            self.context.add_define(name.clone(), value.clone());
+        }
+    }
+
+    /// Read the baseline of known diagnostics configured for the environment,
+    /// along with the directory its paths are relative to.
+    fn read_baseline(&self) -> Option<(Arc<dm::baseline::Baseline>, PathBuf)> {
+        let env_dir = self.environment.as_ref()?.parent()?.to_owned();
+        let path = env_dir.join(self.context.config().dreamchecker.baseline.as_ref()?);
+        match dm::baseline::Baseline::read(&path) {
+            Ok(baseline) => Some((Arc::new(baseline), env_dir)),
+            Err(err) => {
+                eprintln!("error reading baseline {}: {}", path.display(), err);
+                None
+            }
+        }
+    }
+
+    /// Ask the client for the environment configured for each folder.
+    fn request_configuration(&mut self) {
+        if !self.client_caps.configuration || self.root.is_none() {
+            return;
+        }
+        let roots: Vec<Option<Url>> = (0..self.workspaces.len())
+            .map(|index| self.workspace_view(index).0.cloned())
+            .collect();
+        let items = roots.iter()
+            .map(|root| lsp_types::ConfigurationItem {
+                scope_uri: root.clone(),
+                section: Some("dreammaker.environment".to_owned()),
+            })
+            .collect();
+        let id = issue_request::<lsp_types::request::WorkspaceConfiguration>(lsp_types::ConfigurationParams { items });
+        self.configuration_request = Some((id, roots));
+    }
+
+    /// Handle the client's response to one of our own requests.
+    fn handle_response(&mut self, response: serde_json::Value) {
+        let is_configuration = self.configuration_request.as_ref()
+            .is_some_and(|(id, _)| serde_json::to_value(id).ok().as_ref() == response.get("id"));
+        if !is_configuration {
+            return;
+        }
+        let Some((_, roots)) = self.configuration_request.take() else { return };
+        let Some(values) = response["result"].as_array() else { return };
+        for (root, value) in roots.iter().zip(values) {
+            if let Some(index) = (0..self.workspaces.len()).find(|&index| self.workspace_view(index).0 == root.as_ref()) {
+                self.workspaces[index].configured = value.as_str().map(ToOwned::to_owned);
+            }
+        }
+        self.reload_changed();
+    }
+
+    /// The environment to load for the active workspace, if any.
+    fn find_environment(&self) -> Result<Option<PathBuf>, jsonrpc::Error> {
+        // TODO: support non-files here
+        let Some(root_path) = self.root.as_ref().and_then(|root| url_to_path(root).ok()) else {
+            return Ok(None);
+        };
+        if let Some(dme) = self.environment_setting.for_folder(&self.workspaces[self.active]) {
+            Ok(Some(root_path.join(dme)))
+        } else if let Some(dme) = self.context.config().environment.as_ref() {
+            Ok(Some(root_path.join(dme)))
+        } else {
+            dm::detect_environment(&root_path, dm::DEFAULT_ENV).map_err(invalid_request)
+        }
+    }
+
+    /// Load the active workspace's environment, replacing what was loaded.
+    fn reload(&mut self) -> Result<(), jsonrpc::Error> {
+        let environment = self.find_environment()?;
+        if self.environment.is_some() && self.environment != environment {
+            // Start over, forgetting the files of the old environment.
+            let mut old = workspaces::Workspace {
+                root: self.root.clone(),
+                .. Default::default()
+            };
+            self.exchange(&mut old);
+            old.diagnostics_tracker.lock().unwrap().send(Default::default());
+            self.load_config();
+        }
+        self.context.errors_mut().clear();
+        self.annotations.clear();
+        self.live.take_stale();
+
+        self.start_watching();
+        if let Some(environment) = environment {
+            let started = std::time::SystemTime::now();
+            self.parse_environment(environment)?;
+            self.rebuilt = Some((started, std::time::SystemTime::now()));
+            self.track_files()?;
+        } else if self.root.is_some() {
+            self.show_status("no .dme file");
+        } else {
+            self.show_status("single file mode");
+        }
+        Ok(())
+    }
+
+    fn reload_all(&mut self) {
+        for index in 0..self.workspaces.len() {
+            self.activate(index);
+            if let Err(e) = self.reload() {
+                self.show_message(MessageType::ERROR, e.message);
+            }
+        }
+        self.environments_loaded = true;
+        self.refresh_orphans();
+    }
+
+    /// Reload the workspaces whose environment setting has changed.
+    fn reload_changed(&mut self) {
+        if !self.environments_loaded {
+            return;
+        }
+        let mut reloaded = false;
+        for index in 0..self.workspaces.len() {
+            self.activate(index);
+            let Ok(environment) = self.find_environment() else { continue };
+            if environment != self.environment {
+                eprintln!();
+                eprintln!("reparsing after the environment setting changed...");
+                if let Err(e) = self.reload() {
+                    self.show_message(MessageType::ERROR, e.message);
+                }
+                reloaded = true;
+            }
+        }
+        if reloaded {
+            self.refresh_orphans();
+        }
+    }
+
+    /// Reconfigure every workspace after the define setting has changed,
+    /// reloading those already loaded.
+    fn reload_defines(&mut self) {
+        for index in 0..self.workspaces.len() {
+            self.activate(index);
+            self.context.reset_config();
+            self.load_config();
+            if self.environments_loaded && self.environment.is_some() {
+                eprintln!();
+                eprintln!("reparsing after the define setting changed...");
+                if let Err(e) = self.reload() {
+                    self.show_message(MessageType::ERROR, e.message);
+                }
+            }
+        }
+        if self.environments_loaded {
+            self.refresh_orphans();
+        }
+    }
+
+    fn refresh_orphans(&mut self) {
+        let open: Vec<Url> = self.docs.open_urls().cloned().collect();
+        for url in open {
+            self.check_included(&url);
+        }
+    }
+
+    /// Tell the user when an open file is not part of any loaded environment,
+    /// rather than silently offering less for it.
+    fn check_included(&mut self, url: &Url) {
+        let Ok(path) = url_to_path(url) else { return };
+        let mut environments = Vec::new();
+        let mut included = false;
+        for index in 0..self.workspaces.len() {
+            let (root, context, environment) = self.workspace_view(index);
+            let Some(environment) = environment else { continue };
+            environments.push(environment.file_name().unwrap_or_default().to_string_lossy().into_owned());
+            let root = root.and_then(|root| url_to_path(root).ok());
+            if let Some(relative) = root.as_ref().and_then(|root| path.strip_prefix(root).ok()) {
+                included |= context.get_file(relative).is_some_and(|file| context.include_graph().contains(file));
+            }
+        }
+
+        let is_code = path.extension().is_some_and(|ext| ext == "dm");
+        if is_code && !included && !environments.is_empty() {
+            self.orphans.insert(url.clone());
+            self.issue_notification::<lsp_types::notification::PublishDiagnostics>(lsp_types::PublishDiagnosticsParams {
+                uri: url.clone(),
+                diagnostics: vec![lsp_types::Diagnostic {
+                    severity: Some(lsp_types::DiagnosticSeverity::INFORMATION),
+                    source: Some("dreammaker".to_owned()),
+                    message: format!(
+                        "This file is not included by {}, so definitions, references, and diagnostics are unavailable in it",
+                        environments.join(" or "),
+                    ),
+                    .. Default::default()
+                }],
+                version: None,
+            });
+        } else if self.orphans.remove(url) {
+            self.clear_orphan(url);
+        }
+    }
+
+    fn clear_orphan(&self, url: &Url) {
+        // Leave alone any diagnostics the environment has since published.
+        let published = (0..self.workspaces.len()).any(|index| {
+            let tracker = if index == self.active {
+                &self.diagnostics_tracker
+            } else {
+                &self.workspaces[index].diagnostics_tracker
+            };
+            tracker.lock().unwrap().sent.contains(url)
+        });
+        if !published {
+            self.issue_notification::<lsp_types::notification::PublishDiagnostics>(lsp_types::PublishDiagnosticsParams {
+                uri: url.clone(),
+                diagnostics: Vec::new(),
+                version: None,
+            });
+        }
+    }
+
     // ------------------------------------------------------------------------
     // Environment tracking
 
@@ -432,6 +1171,8 @@ impl Engine {
         let original_start = std::time::Instant::now();
         let mut start = original_start;
         eprintln!("environment: {}", environment.display());
+        // Files may have changed on disk since their lines were indexed.
+        self.line_indexes.clear();
         if let Some(stem) = environment.file_stem() {
             self.issue_notification::<extras::WindowStatus>(extras::WindowStatusParams {
                 environment: Some(stem.to_string_lossy().into_owned()),
@@ -447,6 +1188,7 @@ impl Engine {
         };
 
         // Set up the preprocessor.
+        self.environment = Some(environment.clone());
         self.context.reset_io_time();
         self.context.autodetect_config(&environment);
         let mut pp = match dm::preprocessor::Preprocessor::new(&self.context, environment.clone()) {
@@ -472,14 +1214,47 @@ impl Engine {
             eprint!("setup {}.{:03}s - ", elapsed.as_secs(), elapsed.subsec_millis());
         }
 
-        // Parse the environment.
+        // Parse the environment, estimating progress from how many of the
+        // .dme and its includes have been reached.
+        let total_files = 1 + std::fs::read_to_string(&environment)
+            .map_or(0, |text| text.lines().filter(|line| line.trim_start().starts_with("#include")).count());
+        let progress = std::cell::RefCell::new(progress::WorkDone::begin(
+            self.client_caps.work_done_progress,
+            "Loading environment",
+            Some("preprocessing".to_owned()),
+        ));
         let fatal_errored;
         {
-            let mut parser = dm::parser::Parser::new(&self.context, dm::indents::IndentProcessor::new(&self.context, &mut pp));
+            let mut files_seen = HashSet::new();
+            let tokens = (&mut pp).inspect(|token| {
+                if files_seen.insert(token.location.file) {
+                    if let Some(progress) = progress.borrow_mut().as_mut() {
+                        let message = || format!(
+                            "parsing {}/{} files: {}",
+                            files_seen.len(),
+                            total_files.max(files_seen.len()),
+                            self.context.file_path(token.location.file).display(),
+                        );
+                        progress.count(message, files_seen.len(), total_files, 0..70);
+                    }
+                }
+            });
+            let mut parser = dm::parser::Parser::new(&self.context, dm::indents::IndentProcessor::new(&self.context, tokens));
             parser.enable_procs();
-            let (fatal_errored_2, objtree) = parser.parse_object_tree_2();
+            let (fatal_errored_2, objtree) = parser.parse_object_tree_with_progress(&mut |phase| {
+                if let Some(progress) = progress.borrow_mut().as_mut() {
+                    match phase {
+                        dm::objtree::FinishPhase::ParentTypes => progress.phase("building object tree", 70),
+                        dm::objtree::FinishPhase::Constants => progress.phase("evaluating constants", 80),
+                    }
+                }
+            });
             fatal_errored = fatal_errored_2;
             self.objtree = Arc::new(objtree);
+            self.symbol_index = None;
+        }
+        if let Some(progress) = progress.into_inner() {
+            progress.end(Some(format!("loaded {} types", self.objtree.iter_types().count())));
         }
         let elapsed = start.elapsed(); start += elapsed;
         {
@@ -490,36 +1265,71 @@ impl Engine {
 
         // Background thread: prepare the Find All References database.
         let references_objtree = self.objtree.clone();
+        let mut references_progress = progress::WorkDone::begin(self.client_caps.work_done_progress, "Indexing references", None);
         self.references_table.spawn(move || {
-            let table = find_references::ReferencesTable::new(&references_objtree);
+            let table = find_references::ReferencesTable::with_progress(&references_objtree, &mut |done, total| {
+                if let Some(progress) = references_progress.as_mut() {
+                    progress.count(|| format!("{}/{} types", done, total), done, total, 0..100);
+                }
+            });
+            if let Some(progress) = references_progress {
+                progress.end(None);
+            }
             let elapsed = start.elapsed();
             eprint!("references {}.{:03}s", elapsed.as_secs(), elapsed.subsec_millis());
             print_thread_total();
             table
         });
 
+        let defines = pp.finalize();
+        let usage = dreamchecker::unused_definitions::DefineUsage::new(&defines);
+        let maps = defines.maps().to_vec();
+        let define_names = Arc::new(suggestions::DefineNames::new(&defines));
+        self.define_names = define_names.clone();
+        let macro_names = Arc::new(dreamchecker::MacroNames::new(&defines));
+        self.macro_names = macro_names.clone();
+        self.defines = Some(defines);
+
         // Lock the diagnostics tracker now to avoid dreamchecker winning the race.
         let mut diagnostics_lock = self.diagnostics_tracker.lock().unwrap();
 
+        let baseline = self.read_baseline();
+
         // Background thread: If enabled, and parse was OK, run dreamchecker.
-        if self.context.config().langserver.dreamchecker && !fatal_errored {
+        let checking = self.context.config().langserver.dreamchecker && !fatal_errored;
+        if checking {
             self.show_status("checking");
             let context = self.context.clone();
             let objtree = self.objtree.clone();
             let root = self.root.clone();
             let related_info = self.client_caps.related_info;
             let diagnostics_tracker = self.diagnostics_tracker.clone();
+            let define_names = define_names.clone();
+            let macro_names = macro_names.clone();
+            let baseline = baseline.clone();
             std::thread::spawn(move || {
-                dreamchecker::run(&context, &objtree);
+                dreamchecker::run(&context, &objtree, &macro_names);
+                if let Some(usage) = usage {
+                    dreamchecker::unused_definitions::check_unused_definitions(&context, &objtree, &usage);
+                }
+                let mapped = dreamchecker::verbs::mapped_types(&maps);
+                dreamchecker::verbs::check_unreachable_verbs(&context, &objtree, &mapped);
                 let elapsed = start.elapsed(); start += elapsed;
                 eprint!("dreamchecker {}.{:03}s", elapsed.as_secs(), elapsed.subsec_millis());
                 print_thread_total();
 
+                if let Some((baseline, env_dir)) = baseline {
+                    report_stale_baseline(&baseline.apply(&context, &env_dir));
+                }
+
+                let line_indexes = positions::LineIndexes::default();
                 let map = DiagnosticsTracker::build(
                     root.as_ref(),
                     context.file_list(),
                     &context.errors(),
                     related_info,
+                    Some(&define_names),
+                    &|url| line_indexes.read(url),
                 );
                 diagnostics_tracker.lock().unwrap().send(map);
 
@@ -529,18 +1339,26 @@ impl Engine {
             self.issue_notification::<extras::WindowStatus>(Default::default());
         }
 
+        // Stale entries are only known once dreamchecker has run, if it will.
+        if let Some((baseline, env_dir)) = baseline {
+            let stale = baseline.apply(&self.context, &env_dir);
+            if !checking {
+                report_stale_baseline(&stale);
+            }
+        }
+
         // Send the first round of diagnostics from parsing.
         let map = DiagnosticsTracker::build(
             self.root.as_ref(),
             self.context.file_list(),
             &self.context.errors(),
             self.client_caps.related_info,
+            Some(&define_names),
+            &|url| self.lines_of(url),
         );
         diagnostics_lock.send(map);
         drop(diagnostics_lock);
 
-        self.defines = Some(pp.finalize());
-
         let elapsed = start.elapsed(); start += elapsed;
         eprint!(" - diagnostics {}.{:03}s", elapsed.as_secs(), elapsed.subsec_millis());
 
@@ -550,6 +1368,12 @@ impl Engine {
             let elapsed = start.elapsed(); start += elapsed;
             eprint!(" - object tree {}.{:03}s", elapsed.as_secs(), elapsed.subsec_millis());
         }
+        if self.object_tree_requested {
+            issue_notification::<extras::ObjectTreeChanged>(extras::ObjectTreeChangedParams {
+                root: self.root.clone(),
+                type_count: self.objtree.iter_types().count(),
+            });
+        }
 
         /*if let Some(objtree) = Arc::get_mut(&mut self.objtree) {
             objtree.drop_code();
@@ -561,16 +1385,204 @@ impl Engine {
         Ok(())
     }
 
-    fn get_annotations(&mut self, url: &Url) -> Result<(FileId, FileId, Rc<AnnotationTree>), jsonrpc::Error> {
-        Ok(match self.annotations.entry(url.to_owned()) {
-            Entry::Occupied(o) => o.get().clone(),
-            Entry::Vacant(v) => match self.root {
-                Some(ref root) => {
-                    // normal path, when we have a workspace root & an environment loaded
-                    let path = url_to_path(url)?;
-                    let root = url_to_path(root)?;
+    /// When the main loop should next stop waiting for input.
+    fn next_deadline(&self) -> Option<std::time::Instant> {
+        (0..self.workspaces.len())
+            .flat_map(|index| {
+                let (live, watcher) = if index == self.active {
+                    (&self.live, &self.watcher)
+                } else {
+                    (&self.workspaces[index].live, &self.workspaces[index].watcher)
+                };
+                [live.deadline(), watcher.deadline()]
+            })
+            .flatten()
+            .min()
+    }
 
-                    let defines = match self.defines {
+    fn run_timers(&mut self) {
+        for index in 0..self.workspaces.len() {
+            self.activate(index);
+            self.run_live_analysis();
+            self.watcher.poll();
+            if let Some(changes) = self.watcher.take_rebuild() {
+                if let Err(e) = self.rebuild_for(&changes) {
+                    eprintln!("rebuild failed: {}", e.message);
+                }
+            }
+        }
+    }
+
+    /// Have the client tell us about changes to the environment's files, or
+    /// check for them ourselves if it can't.
+    fn start_watching(&mut self) {
+        if self.watcher.started || self.root.is_none() {
+            return;
+        }
+        self.watcher.started = true;
+        if self.client_caps.watched_files {
+            // One registration covers every workspace folder.
+            if std::mem::replace(&mut self.watch_registered, true) {
+                return;
+            }
+            let options = lsp_types::DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![lsp_types::FileSystemWatcher {
+                    glob_pattern: watch::GLOB.to_owned(),
+                    kind: None,
+                }, lsp_types::FileSystemWatcher {
+                    glob_pattern: watch::CONFIG_GLOB.to_owned(),
+                    kind: None,
+                }],
+            };
+            issue_request::<lsp_types::request::RegisterCapability>(lsp_types::RegistrationParams {
+                registrations: vec![lsp_types::Registration {
+                    id: "dreammaker/watchedFiles".to_owned(),
+                    method: "workspace/didChangeWatchedFiles".to_owned(),
+                    register_options: serde_json::to_value(options).ok(),
+                }],
+            });
+        } else {
+            eprintln!("client cannot watch files, polling instead");
+            self.watcher.enable_polling();
+        }
+    }
+
+    /// Update the files checked when polling to the environment's.
+    fn track_files(&mut self) -> Result<(), jsonrpc::Error> {
+        let Some(ref root) = self.root else {
+            return Ok(());
+        };
+        let root = url_to_path(root)?;
+        let mut files = Vec::new();
+        self.context.file_list().for_each(|path| {
+            if watch::is_watched(path) {
+                files.push(path.to_owned());
+            }
+        });
+        self.watcher.track(root, files);
+        Ok(())
+    }
+
+    /// Rebuild the environment after files changed on disk, then re-apply
+    /// the contents of open documents which differ from disk.
+    fn rebuild_for(&mut self, changes: &[(PathBuf, lsp_types::FileChangeType)]) -> Result<(), jsonrpc::Error> {
+        if changes.is_empty() {
+            return Ok(());
+        }
+        eprintln!();
+        eprintln!("reparsing after files changed on disk: {}", watch::summarize(changes));
+        // Edits to files already in the environment only need those files'
+        // symbols indexed again, unless they changed what is included or
+        // what their macros expand to.
+        let environment = self.environment.clone();
+        let incremental = self.changed_files(changes).map(|files| {
+            let before = self.index_dependencies(&files);
+            (files, before, self.symbol_index.take())
+        });
+        self.reload()?;
+        if let Some((files, before, Some(mut index))) = incremental {
+            if self.environment == environment && self.index_dependencies(&files) == before {
+                index.update_files(&self.objtree, self.defines.as_ref(), &files);
+                self.symbol_index = Some(index);
+            }
+        }
+        self.refresh_orphans();
+
+        if self.live_diagnostics && self.defines.is_some() {
+            let modified: Vec<Url> = self.docs.open_urls()
+                .filter(|url| self.docs.is_modified(url))
+                .cloned()
+                .collect();
+            for url in modified {
+                self.live.schedule(url);
+            }
+        }
+        Ok(())
+    }
+
+    /// The files a batch of on-disk changes edited, if every change was an
+    /// edit to a file the environment includes.
+    fn changed_files(&self, changes: &[(PathBuf, lsp_types::FileChangeType)]) -> Option<Vec<FileId>> {
+        let root = url_to_path(self.root.as_ref()?).ok()?;
+        let graph = self.context.include_graph();
+        changes.iter()
+            .map(|(path, typ)| {
+                if *typ != lsp_types::FileChangeType::CHANGED {
+                    return None;
+                }
+                let relative = path.strip_prefix(&root).ok()?;
+                self.context.get_file(relative).filter(|&file| graph.contains(file))
+            })
+            .collect()
+    }
+
+    /// What indexing some files depends on besides their own declarations:
+    /// the files the environment includes, and the macros the files define.
+    fn index_dependencies(&self, files: &[FileId]) -> (Vec<Option<FileId>>, Vec<(String, dm::preprocessor::Define)>) {
+        let included = self.context.include_graph().edges().iter().map(|edge| edge.file).collect();
+        let defines = self.defines.iter()
+            .flat_map(|defines| defines.iter())
+            .filter(|(range, _)| files.contains(&range.start.file))
+            .map(|(_, define)| define.clone())
+            .collect();
+        (included, defines)
+    }
+
+    /// Re-analyze the edited files which are due, and publish their interim
+    /// diagnostics.
+    fn run_live_analysis(&mut self) {
+        for url in self.live.take_due() {
+            if let Err(e) = self.analyze_live(&url) {
+                eprintln!("live analysis of {}: {}", url, e.message);
+            }
+        }
+    }
+
+    fn analyze_live(&mut self, url: &Url) -> Result<(), jsonrpc::Error> {
+        let (Some(root), Some(defines)) = (self.root.as_ref(), self.defines.as_ref()) else {
+            return Ok(());
+        };
+        let path = url_to_path(url)?;
+        let Ok(stripped) = path.strip_prefix(url_to_path(root)?) else {
+            return Ok(());
+        };
+        let contents = self.docs.read(url).map_err(invalid_request)?;
+        let errors = live::analyze_file(&self.context, defines, &self.macro_names, &self.objtree, stripped, contents).map_err(invalid_request)?;
+
+        let file_id = self.context.get_file(stripped).expect("analyzed file has no id");
+        let Some(file_url) = DiagnosticsTracker::file_url(Some(root), self.context.file_list(), file_id) else {
+            return Ok(());
+        };
+        let mut map = DiagnosticsTracker::build(
+            Some(root),
+            self.context.file_list(),
+            &errors,
+            self.client_caps.related_info,
+            Some(&self.define_names),
+            &|url| self.lines_of(url),
+        );
+        // Mark these as interim, until the next full rebuild replaces them.
+        let mut diagnostics = map.remove(&file_url).unwrap_or_default();
+        for diagnostic in diagnostics.iter_mut() {
+            if let Some(source) = diagnostic.source.as_mut() {
+                source.push_str(" (live)");
+            }
+            diagnostic.data = Some(serde_json::json!({ "interim": true }));
+        }
+        self.diagnostics_tracker.lock().unwrap().send_interim(file_url, diagnostics);
+        Ok(())
+    }
+
+    fn get_annotations(&mut self, url: &Url) -> Result<(FileId, FileId, Rc<AnnotationTree>), jsonrpc::Error> {
+        Ok(match self.annotations.entry(url.to_owned()) {
+            Entry::Occupied(o) => o.get().clone(),
+            Entry::Vacant(v) => match self.root {
+                Some(ref root) => {
+                    // normal path, when we have a workspace root & an environment loaded
+                    let path = url_to_path(url)?;
+                    let root = url_to_path(root)?;
+
+                    let defines = match self.defines {
                         Some(ref d) => d,
                         None => return Err(invalid_request("no preprocessor history")),
                     };
@@ -600,7 +1612,7 @@ impl Engine {
                     let filename = url.to_string();
 
                     let contents = self.docs.get_contents(url).map_err(invalid_request)?.into_owned();
-                    let mut pp = dm::preprocessor::Preprocessor::from_buffer(&self.context, filename.clone().into(), contents);
+                    let mut pp = dm::preprocessor::Preprocessor::from_buffer(&self.context, filename.clone().into(), contents.clone());
                     let file_id = self.context.get_file(filename.as_ref()).expect("file didn't exist?");
                     // Clear old errors for this file. Hacky, but it will work for now.
                     self.context.errors_mut().retain(|error| error.location().file != file_id);
@@ -616,8 +1628,14 @@ impl Engine {
                         // selective definition deletion/reintroduction is implemented.
                         self.objtree = Arc::new(parser.parse_object_tree());
                     }
-                    pp.finalize();
-                    dreamchecker::run(&self.context, &self.objtree);
+                    let defines = pp.finalize();
+                    if let Some(index) = self.symbol_index.as_mut() {
+                        index.update_files(&self.objtree, Some(&defines), &[file_id]);
+                    }
+                    dreamchecker::run(&self.context, &self.objtree, &dreamchecker::MacroNames::new(&defines));
+                    if let Some((baseline, env_dir)) = self.read_baseline() {
+                        baseline.apply_to_file(&self.context, &env_dir, file_id, &contents);
+                    }
 
                     // Perform a diagnostics pump on this file only.
                     // Assume all errors are in this file.
@@ -636,7 +1654,7 @@ impl Engine {
                                 notes.push(lsp_types::DiagnosticRelatedInformation {
                                     location: lsp_types::Location {
                                         uri: url.to_owned(),
-                                        range: location_to_range(note.location()),
+                                        range: self.location_to_range(note.location()),
                                     },
                                     message: note.description().to_owned(),
                                 });
@@ -646,9 +1664,13 @@ impl Engine {
                         let diag = lsp_types::Diagnostic {
                             message: error.description().to_owned(),
                             severity: Some(convert_severity(error.severity())),
-                            range: location_to_range(loc),
+                            range: match error.end() {
+                                Some(end) => self.span_to_range(loc..end),
+                                None => self.location_to_range(loc),
+                            },
                             source: component_to_source(error.component()),
                             code: convert_errorcode(error.errortype()),
+                            tags: convert_tags(error.errortype()),
                             related_information,
                             .. Default::default()
                         };
@@ -660,7 +1682,7 @@ impl Engine {
                                 let diag = lsp_types::Diagnostic {
                                     message: note.description().to_owned(),
                                     severity: Some(lsp_types::DiagnosticSeverity::INFORMATION),
-                                    range: location_to_range(note.location()),
+                                    range: self.location_to_range(note.location()),
                                     source: component_to_source(error.component()),
                                     .. Default::default()
                                 };
@@ -808,13 +1830,209 @@ impl Engine {
         next
     }
 
-    fn symbol_id_at(&mut self, text_document_position: lsp_types::TextDocumentPositionParams) -> Result<Option<dm::objtree::SymbolId>, jsonrpc::Error> {
-        let (_, file_id, annotations) = self.get_annotations(&text_document_position.text_document.uri)?;
-        let location = dm::Location {
-            file: file_id,
-            line: text_document_position.position.line + 1,
-            column: text_document_position.position.character as u16 + 1,
+    /// Name the parameters of a proc after the arguments of the call to it
+    /// at or after the given position.
+    fn call_parameter_names(&self, uri: &Url, position: lsp_types::Position, proc_name: &str) -> Vec<String> {
+        let Ok(contents) = self.docs.get_contents(uri) else {
+            return Vec::new();
+        };
+        let Ok(line_start) = document::total_offset(&contents, position.line, 0) else {
+            return Vec::new();
+        };
+        let line = contents[line_start..].split('\n').next().unwrap_or_default();
+        let column = positions::byte_at(line, position.character, positions::Encoding::current());
+        document::find_identifier_in_line(line, column, proc_name)
+            .and_then(|idx| document::call_arguments(&contents, line_start + idx + proc_name.len()))
+            .map(|args| code_actions::parameter_names(&args))
+            .unwrap_or_default()
+    }
+
+    /// Describe the active workspace's environment and how ready it is.
+    fn status_report(&mut self) -> extras::StatusResult {
+        let mut file_count = 0;
+        self.context.file_list().for_each(|_| file_count += 1);
+        let types = self.objtree.iter_types().filter(|ty| !ty.location.is_builtins());
+        let (mut type_count, mut proc_count) = (0, 0);
+        for ty in types {
+            type_count += 1;
+            proc_count += ty.procs.values()
+                .flat_map(|proc| proc.value.iter())
+                .filter(|value| !value.location.is_builtins())
+                .count();
+        }
+
+        self.references_table.poll();
+        let references = if self.references_table.is_busy() {
+            extras::IndexStatus::Building
+        } else if self.references_table.value().is_none() {
+            extras::IndexStatus::Unavailable
+        } else if self.watcher.is_pending() || self.docs.open_urls().any(|url| {
+            self.docs.is_modified(url) && self.workspace_for(url) == Some(self.active)
+        }) {
+            extras::IndexStatus::Stale
+        } else {
+            extras::IndexStatus::Ready
+        };
+
+        let millis = |time: std::time::SystemTime| {
+            time.duration_since(std::time::UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64)
+        };
+        extras::StatusResult {
+            root: self.root.clone(),
+            environment: self.environment.clone().and_then(|path| path_to_url(path).ok()),
+            file_count,
+            type_count,
+            proc_count,
+            diagnostics: self.diagnostics_tracker.lock().unwrap().counts,
+            rebuild_started: self.rebuilt.map(|(started, _)| millis(started)),
+            rebuild_finished: self.rebuilt.map(|(_, finished)| millis(finished)),
+            references,
+        }
+    }
+
+    /// Normalize the whitespace of a document, or only of the given lines.
+    fn format_document(
+        &mut self,
+        uri: &Url,
+        options: &lsp_types::FormattingOptions,
+        lines: Option<std::ops::RangeInclusive<u32>>,
+    ) -> Result<Option<Vec<lsp_types::TextEdit>>, jsonrpc::Error> {
+        let (_, file_id, annotations) = self.get_annotations(uri)?;
+        let contents = self.docs.get_contents(uri).map_err(invalid_request)?;
+        let headers: HashSet<usize> = annotations.iter()
+            .filter(|(range, annotation)| range.start.file == file_id && matches!(annotation, Annotation::ProcHeader(..)))
+            .map(|(range, _)| range.start.line as usize - 1)
+            .collect();
+        let edits = formatting::format_document(&contents, &self.formatting.style(options), |line| headers.contains(&line));
+
+        let mut line_starts = vec![0];
+        line_starts.extend(contents.match_indices('\n').map(|(idx, _)| idx + 1));
+        let position = |offset: usize| {
+            let line = line_starts.partition_point(|&start| start <= offset) - 1;
+            let character = positions::Encoding::current().len(&contents[line_starts[line]..offset]);
+            lsp_types::Position::new(line as u32, character)
         };
+        let edits: Vec<_> = edits.into_iter()
+            .map(|(range, new_text)| lsp_types::TextEdit {
+                range: lsp_types::Range::new(position(range.start), position(range.end)),
+                new_text,
+            })
+            .filter(|edit| lines.as_ref().is_none_or(|lines| lines.contains(&edit.range.start.line) && lines.contains(&edit.range.end.line)))
+            .collect();
+        Ok(if edits.is_empty() { None } else { Some(edits) })
+    }
+
+    /// Offer to create a proc stub on a type, after its last proc in the file
+    /// which first defines it. Types without a file of their own get the stub
+    /// at the end of the current document instead.
+    fn create_proc_action(
+        &self,
+        uri: &Url,
+        ty: TypeRef,
+        proc_name: &str,
+        params: &[String],
+        diagnostic: &lsp_types::Diagnostic,
+        preferred: bool,
+    ) -> Result<lsp_types::CodeActionOrCommand, jsonrpc::Error> {
+        let (target, after) = if ty.location.is_builtins() || ty.is_root() {
+            (uri.clone(), None)
+        } else {
+            let file = ty.location.file;
+            let last_proc = ty.procs.values()
+                .flat_map(|proc| proc.value.iter())
+                .filter(|value| value.location.file == file)
+                .map(|value| value.location.line as usize - 1)
+                .max();
+            (self.file_url(file)?, last_proc)
+        };
+
+        let contents = self.docs.get_contents(&target).map_err(invalid_request)?;
+        let (offset, new_text) = code_actions::proc_stub(&contents, &ty.path, after, proc_name, params);
+        let position = document::offset_to_position(&contents, offset);
+
+        let mut changes = std::collections::HashMap::new();
+        changes.insert(target, vec![lsp_types::TextEdit {
+            range: lsp_types::Range::new(position, position),
+            new_text,
+        }]);
+        Ok(lsp_types::CodeActionOrCommand::CodeAction(lsp_types::CodeAction {
+            title: format!("Create proc {} on {}", proc_name, ty.pretty_path()),
+            kind: Some(lsp_types::CodeActionKind::QUICKFIX),
+            diagnostics: Some(vec![diagnostic.clone()]),
+            edit: Some(lsp_types::WorkspaceEdit {
+                changes: Some(changes),
+                document_changes: None,
+                change_annotations: None,
+            }),
+            is_preferred: Some(preferred),
+            .. Default::default()
+        }))
+    }
+
+    /// Guess what an identifier in the body of a macro refers to, in the
+    /// global scope, since it is only resolved where the macro is used.
+    fn find_macro_body_symbol<'b, I>(
+        &'b self,
+        iter: &I,
+        annotations: &'b AnnotationTree,
+        location: dm::Location,
+        real_file_id: FileId,
+        line_text: &str,
+        ident: &str,
+    ) -> MacroBodySymbol<'b>
+    where
+        I: Iterator<Item = (Span, &'b Annotation)> + Clone,
+    {
+        let Some((body, define, params)) = iter.clone().find_map(|(span, annotation)| match annotation {
+            Annotation::MacroBody { name, params } => Some((span, name, params)),
+            _ => None,
+        }) else {
+            return MacroBodySymbol::None;
+        };
+
+        // parameters, which are first seen in the parameter list
+        if params.iter().any(|param| param == ident) {
+            let loc = annotations.get_range_raw(body)
+                .filter(|(_, annotation)| matches!(annotation, Annotation::MacroBodyIdent(name) if name == ident))
+                .map(|(span, _)| span.start)
+                .min()
+                .unwrap_or(location);
+            return MacroBodySymbol::Parameter { define, params, loc };
+        }
+
+        // absolute type paths, up to the identifier
+        let column = location.column.saturating_sub(1) as usize;
+        if let Some((_, end)) = document::word_bounds_in_line(line_text, column) {
+            let start = line_text[..end]
+                .rfind(|ch: char| ch != '/' && ch != '_' && !ch.is_ascii_alphanumeric())
+                .map_or(0, |idx| idx + 1);
+            let path = &line_text[start..end];
+            if path.starts_with('/') {
+                if let Some(ty) = self.objtree.find(path) {
+                    return MacroBodySymbol::Type(ty);
+                }
+            }
+        }
+
+        // other macros, then global procs and vars
+        if let Some((loc, _)) = self.find_define_by_name(ident, dm::Location { file: real_file_id, ..location }) {
+            return MacroBodySymbol::Macro(loc);
+        }
+        let root = self.objtree.root();
+        if root.procs.get(ident).is_some() {
+            MacroBodySymbol::Proc(root)
+        } else if root.vars.get(ident).is_some() {
+            MacroBodySymbol::Var(root)
+        } else {
+            MacroBodySymbol::None
+        }
+    }
+
+    fn symbol_id_at(&mut self, text_document_position: lsp_types::TextDocumentPositionParams) -> Result<Option<dm::objtree::SymbolId>, jsonrpc::Error> {
+        let (real_file_id, file_id, annotations) = self.get_annotations(&text_document_position.text_document.uri)?;
+        let location = self.document_location(&text_document_position.text_document.uri, file_id, text_document_position.position);
+        let real_location = dm::Location { file: real_file_id, ..location };
+        self.references_table.poll();
 
         let mut symbol_id = None;
 
@@ -876,7 +2094,7 @@ impl Engine {
                 let (ty, _) = self.find_type_context(&iter);
                 let mut next = ty.or_else(|| Some(self.objtree.root()));
                 while let Some(ty) = next {
-                    if let Some(proc) = ty.procs.get(proc_name) {
+                    if let Some(proc) = ty.procs.get(proc_name.as_str()) {
                         if let Some(ref decl) = proc.declaration {
                             symbol_id = Some(decl.id);
                             break;
@@ -888,24 +2106,23 @@ impl Engine {
             Annotation::UnscopedVar(var_name) => {
                 let (ty, proc_name) = self.find_type_context(&iter);
                 match self.find_unscoped_var(&iter, ty, proc_name, var_name) {
-                    UnscopedVar::Parameter { .. } => {
-                        // TODO
+                    UnscopedVar::Parameter { .. } | UnscopedVar::Local { .. } => {
+                        if let Some(table) = self.references_table.value() {
+                            symbol_id = table.find_local(real_location, var_name);
+                        }
                     },
                     UnscopedVar::Variable { ty, .. } => {
                         if let Some(decl) = ty.get_var_declaration(var_name) {
                             symbol_id = Some(decl.id);
                         }
                     },
-                    UnscopedVar::Local { .. } => {
-                        // TODO
-                    },
                     UnscopedVar::None => {}
                 }
             },
             Annotation::ScopedCall(priors, proc_name) => {
                 let mut next = self.find_scoped_type(&iter, priors);
                 while let Some(ty) = next {
-                    if let Some(proc) = ty.procs.get(proc_name) {
+                    if let Some(proc) = ty.procs.get(proc_name.as_str()) {
                         if let Some(ref decl) = proc.declaration {
                             symbol_id = Some(decl.id);
                             break;
@@ -917,7 +2134,7 @@ impl Engine {
             Annotation::ScopedVar(priors, var_name) => {
                 let mut next = self.find_scoped_type(&iter, priors);
                 while let Some(ty) = next {
-                    if let Some(var) = ty.vars.get(var_name) {
+                    if let Some(var) = ty.vars.get(var_name.as_str()) {
                         if let Some(ref decl) = var.declaration {
                             symbol_id = Some(decl.id);
                             break;
@@ -929,9 +2146,168 @@ impl Engine {
             // TODO: macros
         }
 
+        if symbol_id.is_none() {
+            // Local variable declarations aren't annotated at the name itself.
+            if let Some(table) = self.references_table.value() {
+                let contents = self.docs.get_contents(&text_document_position.text_document.uri).map_err(invalid_request)?;
+                if let Some((_, word)) = document::find_word_range(&contents, text_document_position.position) {
+                    symbol_id = table.find_local(real_location, word);
+                }
+            }
+        }
+
         Ok(symbol_id)
     }
 
+    fn is_type_symbol(&self, symbol_id: dm::objtree::SymbolId) -> bool {
+        self.objtree.iter_types().any(|ty| ty.id == symbol_id)
+    }
+
+    /// Find the proc declared or called at the given position.
+    fn proc_key_at(&mut self, text_document_position: lsp_types::TextDocumentPositionParams) -> Result<Option<ProcKey>, jsonrpc::Error> {
+        let (_, file_id, annotations) = self.get_annotations(&text_document_position.text_document.uri)?;
+        let location = self.document_location(&text_document_position.text_document.uri, file_id, text_document_position.position);
+
+        let mut result = None;
+        let iter = annotations.get_location(location);
+        match_annotation! { iter;
+            Annotation::ProcHeader(parts, idx) => {
+                let mut current = self.objtree.root();
+                let (proc_name, most) = parts.split_last().unwrap();
+                for part in most {
+                    if part == "proc" || part == "verb" { break }
+                    if let Some(child) = current.child(part) {
+                        current = child;
+                    } else {
+                        break;
+                    }
+                }
+
+                if let Some(proc) = current.iter_self_procs().find(|proc| proc.name() == proc_name && proc.index() == *idx) {
+                    result = Some(ProcKey::new(proc));
+                }
+            },
+            Annotation::UnscopedCall(proc_name) => {
+                let (ty, _) = self.find_type_context(&iter);
+                if let Some(proc) = ty.unwrap_or_else(|| self.objtree.root()).get_proc(proc_name) {
+                    result = Some(ProcKey::new(proc));
+                }
+            },
+            Annotation::ScopedCall(priors, proc_name) => {
+                if let Some(proc) = self.find_scoped_type(&iter, priors).and_then(|ty| ty.get_proc(proc_name)) {
+                    result = Some(ProcKey::new(proc));
+                }
+            },
+        }
+        Ok(result)
+    }
+
+    /// Find the single proc targeted by an exact call at the given position:
+    /// `..()` calls the parent proc, `.()` calls the current proc, and
+    /// `new /type()` calls that type's `New()`.
+    fn exact_call_at(&mut self, text_document_position: lsp_types::TextDocumentPositionParams) -> Result<Option<ProcKey>, jsonrpc::Error> {
+        let (_, file_id, annotations) = self.get_annotations(&text_document_position.text_document.uri)?;
+        let location = self.document_location(&text_document_position.text_document.uri, file_id, text_document_position.position);
+        let iter = annotations.get_location(location);
+
+        // Only the innermost call counts, so that calls in the arguments
+        // aren't mistaken for the exact call around them.
+        let innermost = iter.clone()
+            .filter(|(_, annotation)| matches!(annotation,
+                Annotation::ParentCall |
+                Annotation::ProcArguments(..) |
+                Annotation::UnscopedCall(_) |
+                Annotation::ScopedCall(..) |
+                Annotation::UnscopedVar(_) |
+                Annotation::ScopedVar(..)))
+            .max_by_key(|(span, _)| span.start);
+        let Some((span, annotation)) = innermost else {
+            return Ok(None);
+        };
+
+        let current_proc = || match self.find_type_context(&iter) {
+            (Some(ty), Some((proc_name, idx))) => ty.iter_self_procs()
+                .find(|proc| proc.name() == proc_name && proc.index() == idx),
+            _ => None,
+        };
+        let proc = match annotation {
+            Annotation::ParentCall => current_proc().and_then(dm::objtree::ProcRef::parent_proc),
+            Annotation::ProcArguments(_, proc_name, _) if proc_name == ".." => current_proc().and_then(dm::objtree::ProcRef::parent_proc),
+            Annotation::ProcArguments(_, proc_name, _) if proc_name == "." => current_proc(),
+            Annotation::ProcArguments(..) => {
+                let new_type = iter.clone().find_map(|(new_span, annotation)| match annotation {
+                    Annotation::NewArguments(path) if new_span.start == span.start => Some(path),
+                    _ => None,
+                });
+                match new_type.and_then(|path| self.follow_type_path(&iter, path)) {
+                    Some(completion::TypePathResult { ty, decl: None, proc: None }) => ty.get_proc("New"),
+                    _ => None,
+                }
+            },
+            _ => None,
+        };
+        Ok(proc.map(ProcKey::new))
+    }
+
+    /// Find the path of the type defined or named at the given position.
+    fn type_path_at(&mut self, text_document_position: lsp_types::TextDocumentPositionParams) -> Result<Option<String>, jsonrpc::Error> {
+        let (_, file_id, annotations) = self.get_annotations(&text_document_position.text_document.uri)?;
+        let location = self.document_location(&text_document_position.text_document.uri, file_id, text_document_position.position);
+
+        let mut result = None;
+        let iter = annotations.get_location(location);
+        match_annotation! { iter;
+            Annotation::TreePath(absolute, parts) => {
+                if let Some(ty) = self.objtree.type_by_path(completion::combine_tree_path(&iter, *absolute, parts)) {
+                    result = Some(ty.path.to_string());
+                }
+            },
+            Annotation::TypePath(parts) => {
+                if let Some(completion::TypePathResult { ty, decl: None, proc: None }) = self.follow_type_path(&iter, parts) {
+                    result = Some(ty.path.to_string());
+                }
+            },
+        }
+        Ok(result)
+    }
+
+    fn type_hierarchy_item(&self, ty: TypeRef) -> Result<extras::TypeHierarchyItem, jsonrpc::Error> {
+        let location = self.convert_location(ty.location, &ty.docs, &[&ty.path])?;
+        let defined_at = if ty.location.is_builtins() {
+            "(builtin)".to_owned()
+        } else {
+            format!("{}:{}", self.context.file_path(ty.location.file).display(), ty.location.line)
+        };
+        Ok(extras::TypeHierarchyItem {
+            name: ty.path.to_string(),
+            kind: lsp_types::SymbolKind::CLASS,
+            detail: Some(format!("{} - {}", defined_at, type_hierarchy::summary(ty))),
+            uri: location.uri,
+            range: location.range,
+            selection_range: location.range,
+            data: Some(ty.path.to_string()),
+        })
+    }
+
+    fn call_hierarchy_item(&self, key: &ProcKey) -> Result<Option<lsp_types::CallHierarchyItem>, jsonrpc::Error> {
+        let proc = match key.resolve(&self.objtree) {
+            Some(proc) if !proc.is_builtin() => proc,
+            _ => return Ok(None),
+        };
+        let ty = proc.ty();
+        let range = self.location_to_range(proc.location);
+        Ok(Some(lsp_types::CallHierarchyItem {
+            name: key.name.to_string(),
+            kind: if ty.is_root() { lsp_types::SymbolKind::FUNCTION } else { lsp_types::SymbolKind::METHOD },
+            tags: None,
+            detail: Some(if ty.is_root() { "/".to_owned() } else { ty.path.to_string() }),
+            uri: self.file_url(proc.location.file)?,
+            range,
+            selection_range: range,
+            data: serde_json::to_value(key).ok(),
+        }))
+    }
+
     fn construct_proc_hover(&self, proc_name: &str, mut provided_tok: Option<TypeRef>, scoped: bool) -> Result<Vec<String>, jsonrpc::Error> {
         let mut results = Vec::new();
         let mut proclink  = String::new();
@@ -987,8 +2363,12 @@ impl Engine {
         let mut results = Vec::new();
         let mut infos = String::new();
         let mut docstring: Option<String> = None;
+        let mut constant = None;
         while let Some(ty) = provided_tok {
-            if let Some(var) = ty.vars.get(var_name) {
+            if let Some(var) = ty.get().vars.get(var_name) {
+                if constant.is_none() {
+                    constant = Some(var.value.constant.as_ref());
+                }
                 if let Some(ref decl) = var.declaration {
                     // First get the path of the type containing the declaration
                     infos.push_str(format!("[{}]({})\n", ty.pretty_path(), self.location_link(var.value.location)?).as_str());
@@ -1008,6 +2388,11 @@ impl Engine {
             }
         }
         if !infos.is_empty() {
+            // the value on the most-derived type is the one that applies
+            if let Some(Some(constant)) = constant {
+                infos.push('\n');
+                infos.push_str(&constant_hover(constant));
+            }
             results.push(infos);
         }
         if let Some(ds) = docstring {
@@ -1017,13 +2402,195 @@ impl Engine {
         Ok(results)
     }
 
+    /// Hover over a prefab or var override in a map's dictionary, showing
+    /// the map's values next to the type's defaults.
+    fn map_hover(&self, uri: &Url, position: lsp_types::Position) -> Result<Option<lsp_types::Hover>, jsonrpc::Error> {
+        use std::fmt::Write;
+
+        let contents = self.docs.get_contents(uri).map_err(invalid_request)?;
+        let prefabs = dmm::parse_dictionary(&contents);
+        let location = self.document_location(uri, FileId::default(), position);
+        let mut results = Vec::new();
+        let span = match dmm::symbol_at(&prefabs, location) {
+            Some(dmm::Symbol::Prefab(prefab)) => {
+                let Some(ty) = self.objtree.type_by_path(&prefab.path) else { return Ok(None) };
+                results.push(format!("[{}]({})", ty.pretty_path(), self.location_link(ty.location)?));
+                if !ty.docs.is_empty() {
+                    results.push(ty.docs.text());
+                }
+                if !prefab.vars.is_empty() {
+                    let mut message = String::from("```dm\n");
+                    for var in prefab.vars.iter() {
+                        let _ = match ty.get_value(&var.name).and_then(|value| value.constant.as_ref()) {
+                            Some(default) => writeln!(message, "{} = {} // default: {}", var.name, var.value, default),
+                            None => writeln!(message, "{} = {}", var.name, var.value),
+                        };
+                    }
+                    message.push_str("```");
+                    results.push(message);
+                }
+                &prefab.span
+            }
+            Some(dmm::Symbol::Var(prefab, var)) => {
+                let Some(ty) = self.objtree.type_by_path(&prefab.path) else { return Ok(None) };
+                results.append(&mut self.construct_var_hover(&var.name, Some(ty), true)?);
+                &var.span
+            }
+            None => return Ok(None),
+        };
+        if results.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(lsp_types::Hover {
+            range: Some(self.lines_of(uri).span(span.clone())),
+            contents: lsp_types::HoverContents::Array(results.into_iter().map(lsp_types::MarkedString::String).collect()),
+        }))
+    }
+
+    /// The definition of a prefab's type, or the declaration of a var it
+    /// overrides, from a map's dictionary.
+    fn map_definition(&self, uri: &Url, position: lsp_types::Position) -> Result<Option<lsp_types::GotoDefinitionResponse>, jsonrpc::Error> {
+        let contents = self.docs.get_contents(uri).map_err(invalid_request)?;
+        let prefabs = dmm::parse_dictionary(&contents);
+        let location = self.document_location(uri, FileId::default(), position);
+        let result = match dmm::symbol_at(&prefabs, location) {
+            Some(dmm::Symbol::Prefab(prefab)) => match self.objtree.type_by_path(&prefab.path) {
+                Some(ty) => Some(self.convert_location(ty.location, &ty.docs, &[&ty.path])?),
+                None => None,
+            },
+            Some(dmm::Symbol::Var(prefab, var)) => {
+                let mut next = self.objtree.type_by_path(&prefab.path);
+                let mut found = None;
+                while let Some(ty) = next {
+                    if let Some(type_var) = ty.vars.get(var.name.as_str()) {
+                        if type_var.declaration.is_some() {
+                            found = Some(self.convert_location(type_var.value.location, &type_var.value.docs, &[&ty.path, "/var/", &var.name])?);
+                            break;
+                        }
+                    }
+                    next = ty.parent_type();
+                }
+                found
+            }
+            None => None,
+        };
+        Ok(result.map(lsp_types::GotoDefinitionResponse::Scalar))
+    }
+
+    fn construct_macro_hover(&self, name: &str, definition_location: dm::Location, file_id: FileId, real_file_id: FileId) -> Vec<String> {
+        let mut results = Vec::new();
+        let Some((definition_location, define)) = self.find_define(name, definition_location, file_id, real_file_id) else {
+            return results;
+        };
+        results.push(format!("```dm\n{}\n```", define.display_with_name(name)));
+
+        // fold object-like macros as seen from where they were defined
+        if let dm::preprocessor::Define::Constant { .. } = define {
+            if let Some(constant) = self.fold_at(name, definition_location) {
+                results.push(constant_hover(&constant));
+            }
+        }
+        results
+    }
+
+    /// Show what the parameters of a function-like macro are given by the
+    /// call whose name is at `span`, and fold the call if possible.
+    fn construct_macro_call_hover(&self, name: &str, definition_location: dm::Location, span: Span, contents: &str, file_id: FileId, real_file_id: FileId) -> Vec<String> {
+        let mut results = Vec::new();
+        let Some((_, dm::preprocessor::Define::Function { params, variadic, .. })) = self.find_define(name, definition_location, file_id, real_file_id) else {
+            return results;
+        };
+        let Some(args) = document::location_offset(contents, span.start.line, span.start.column)
+            .and_then(|offset| document::call_arguments(contents, offset + name.len()))
+        else {
+            return results;
+        };
+
+        let mut substitution = String::new();
+        for (i, param) in params.iter().enumerate() {
+            use std::fmt::Write;
+            let arg = if *variadic && i + 1 == params.len() {
+                args.get(i..).map(|rest| rest.join(", ")).unwrap_or_default()
+            } else {
+                args.get(i).map(|arg| arg.to_string()).unwrap_or_default()
+            };
+            let _ = writeln!(substitution, "{} = {}", param, arg);
+        }
+        results.push(format!("```dm\n{}```", substitution));
+
+        let call = format!("{}({})", name, args.join(", "));
+        if let Some(constant) = self.fold_at(&call, dm::Location { file: real_file_id, ..span.start }) {
+            results.push(constant_hover(&constant));
+        }
+        results
+    }
+
+    /// Find a macro by where it was defined, as recorded by an annotation.
+    fn find_define(&self, name: &str, mut definition_location: dm::Location, file_id: FileId, real_file_id: FileId) -> Option<(dm::Location, &dm::preprocessor::Define)> {
+        // macros defined in the current file were seen under its scratch ID
+        if definition_location.file == file_id {
+            definition_location.file = real_file_id;
+        }
+        let defines = self.defines.as_ref()?;
+        let (_, (_, define)) = defines.range(interval_tree::range(definition_location, definition_location))
+            .find(|(range, (define_name, _))| range.start == definition_location && define_name == name)?;
+        Some((definition_location, define))
+    }
+
+    /// Find a macro by name, preferring the definition in effect at the given
+    /// location and otherwise the last one seen.
+    fn find_define_by_name(&self, name: &str, location: dm::Location) -> Option<(dm::Location, &dm::preprocessor::Define)> {
+        let defines = self.defines.as_ref()?;
+        defines.active_at(name, location)
+            .or_else(|| defines.definitions(name).pop())
+    }
+
+    /// Fold an expression through the preprocessor and constant folder, as
+    /// seen from the given location.
+    fn fold_at(&self, source: &str, location: dm::Location) -> Option<dm::constants::Constant> {
+        let defines = self.defines.as_ref()?;
+        let context = dm::Context::default();
+        let mut preprocessor = defines.branch_at_location(location, &context);
+        preprocessor.push_file(PathBuf::from("<fold>"), std::io::Cursor::new(source.to_owned())).ok()?;
+        let indent = dm::indents::IndentProcessor::new(&context, &mut preprocessor);
+        dm::parser::parse_expression(&context, location, indent)
+            .and_then(|expr| expr.simple_evaluate(location))
+            .ok()
+    }
+
+    /// Find the object-like macro used at a location and fold it, if it
+    /// names a color. Returns the define's location along with the color.
+    fn macro_color(&self, name: &str, definition_location: dm::Location, file_id: FileId, real_file_id: FileId) -> Option<(dm::Location, [u8; 4])> {
+        let (definition_location, define) = self.find_define(name, definition_location, file_id, real_file_id)?;
+        // Only fold macros which could plausibly be colors.
+        let dm::preprocessor::Define::Constant { subst, .. } = define else {
+            return None;
+        };
+        if !subst.iter().any(|token| matches!(token, dm::lexer::Token::String(_) | dm::lexer::Token::Ident(..))) {
+            return None;
+        }
+        let color = color::constant_color(&self.fold_at(name, definition_location)?)?;
+        Some((definition_location, color))
+    }
+
     // ------------------------------------------------------------------------
     // Driver
 
     fn handle_input(&mut self, message: &str) {
-        let mut outputs: Vec<Output> = match serde_json::from_str(message) {
-            Ok(Request::Single(call)) => self.handle_call(call).into_iter().collect(),
-            Ok(Request::Batch(calls)) => calls.into_iter().flat_map(|call| self.handle_call(call)).collect(),
+        let mut response = None;
+        let request = serde_json::from_str::<serde_json::Value>(message).and_then(|value| {
+            if value.get("method").is_none() && (value.get("result").is_some() || value.get("error").is_some()) {
+                // A response to one of our own requests.
+                response = Some(value);
+                Ok(None)
+            } else {
+                serde_json::from_value(value).map(Some)
+            }
+        });
+        let mut outputs: Vec<Output> = match request {
+            Ok(None) => return self.handle_response(response.unwrap_or_default()),
+            Ok(Some(Request::Single(call))) => self.handle_call(call).into_iter().collect(),
+            Ok(Some(Request::Batch(calls))) => calls.into_iter().flat_map(|call| self.handle_call(call)).collect(),
             Err(decode_error) => vec![Output::Failure(jsonrpc::Failure {
                 jsonrpc: VERSION,
                 error: jsonrpc::Error {
@@ -1049,7 +2616,12 @@ impl Engine {
             Call::Invalid { id } => Some(Output::invalid_request(id, VERSION)),
             Call::MethodCall(method_call) => {
                 let id = method_call.id.clone();
-                Some(Output::from(self.handle_method_call(method_call), id, VERSION))
+                if !self.inbox.begin_request(&id) {
+                    return Some(Output::from(Err(request_cancelled()), id, VERSION));
+                }
+                let result = self.handle_method_call(method_call);
+                self.inbox.end_request();
+                Some(Output::from(result, id, VERSION))
             },
             Call::Notification(notification) => {
                 if let Err(e) = self.handle_notification(notification) {
@@ -1072,8 +2644,15 @@ impl Engine {
         }
 
         let params_value = params_to_value(call.params);
+        self.route(&params_value);
         if let Some(func) = Self::handle_method_call_table(&call.method) {
-            func(self, params_value)
+            let mut result = func(self, params_value);
+            if call.method == <lsp_types::request::Initialize as lsp_types::request::Request>::METHOD {
+                if let Ok(value) = result.as_mut() {
+                    extras::extend_capabilities(value);
+                }
+            }
+            result
         } else {
             eprintln!("Call NYI: {} -> {:?}", call.method, params_value);
             Err(jsonrpc::Error {
@@ -1094,6 +2673,7 @@ impl Engine {
         }
 
         let params_value = params_to_value(notification.params);
+        self.route(&params_value);
         if let Some(func) = Self::handle_notification_table(&notification.method) {
             func(self, params_value)
         } else {
@@ -1122,47 +2702,76 @@ handle_method_call! {
         if let Some(id) = init.process_id {
             self.parent_pid = id;
         }
-        if let Some(mut url) = init.root_uri {
-            if !url.path().ends_with('/') {
-                let path = format!("{}/", url.path());
-                url.set_path(&path);
-            }
-            eprintln!("workspace root: {}", url);
-
-            if let Ok(root_path) = url_to_path(&url) {
-                let config_path = root_path.join("SpacemanDMM.toml");
-                if config_path.exists() {
-                    self.context.force_config(&config_path);
-                }
-            }
-
-            self.root = Some(url);
-        } else {
-            eprintln!("single file mode");
+        if let Some(options) = init.initialization_options.as_ref() {
+            self.environment_setting.update(&options["environment"]);
+            self.define_setting = workspaces::define_setting(&options["defines"]);
         }
-
-        // Extract relevant client capabilities.
-        self.client_caps = ClientCaps::parse(&init.capabilities);
+        match init.workspace_folders.filter(|folders| !folders.is_empty()) {
+            Some(folders) => for folder in folders {
+                self.add_workspace(folder.name, folder.uri);
+            },
+            None => match init.root_uri {
+                Some(url) => {
+                    self.add_workspace(workspaces::folder_name(&url), url);
+                },
+                None => eprintln!("single file mode"),
+            },
+        }
+        self.activate(0);
+
+        // Extract relevant client capabilities.
+        self.client_caps = ClientCaps::parse(&init.capabilities);
         let debug = format!("{:?}", self.client_caps);
         if let (Some(start), Some(end)) = (debug.find('{'), debug.rfind('}')) {
             eprintln!("client capabilities: {}", &debug[start + 2..end - 1]);
         } else {
             eprintln!("client capabilities: {}", debug);
         }
+        let position_encoding = positions::Encoding::negotiate(
+            init.capabilities.general.as_ref().and_then(|general| general.position_encodings.as_deref()),
+        );
+        eprintln!("position encoding: {}", position_encoding.as_str());
         eprintln!();
 
         InitializeResult {
             capabilities: ServerCapabilities {
+                position_encoding: Some(position_encoding),
                 definition_provider: Some(OneOf::Left(true)),
                 workspace_symbol_provider: Some(OneOf::Left(true)),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
                 references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: "\n".to_owned(),
+                    more_trigger_character: Some(vec![":".to_owned()]),
+                }),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Options(CodeActionOptions {
+                    code_action_kinds: Some(vec![CodeActionKind::QUICKFIX, CodeActionKind::REFACTOR_REWRITE]),
+                    work_done_progress_options: Default::default(),
+                    resolve_provider: None,
+                })),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(true),
+                }),
+                semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+                    legend: semantic_tokens::legend(),
+                    range: Some(true),
+                    full: Some(SemanticTokensFullOptions::Bool(true)),
+                    work_done_progress_options: Default::default(),
+                })),
                 implementation_provider: Some(ImplementationProviderCapability::Simple(true)),
                 type_definition_provider: Some(TypeDefinitionProviderCapability::Simple(true)),
                 text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
                     open_close: Some(true),
                     change: Some(TextDocumentSyncKind::INCREMENTAL),
+                    save: Some(TextDocumentSyncSaveOptions::Supported(true)),
                     .. Default::default()
                 })),
                 completion_provider: Some(CompletionOptions {
@@ -1170,6 +2779,7 @@ handle_method_call! {
                     all_commit_characters: None,
                     resolve_provider: None,
                     work_done_progress_options: Default::default(),
+                    completion_item: None,
                 }),
                 signature_help_provider: Some(SignatureHelpOptions {
                     trigger_characters: Some(vec!["(".to_owned(), ",".to_owned()]),
@@ -1177,16 +2787,28 @@ handle_method_call! {
                     work_done_progress_options: Default::default(),
                 }),
                 document_link_provider: Some(DocumentLinkOptions {
-                    resolve_provider: None,
+                    resolve_provider: Some(true),
                     work_done_progress_options: Default::default(),
                 }),
                 color_provider: Some(ColorProviderCapability::Simple(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
                 .. Default::default()
             },
             server_info: Some(ServerInfo {
                 name: "dm-langserver".to_owned(),
                 version: Some(env!("CARGO_PKG_VERSION").to_owned()),
             }),
+            offset_encoding: None,
         }
     }
 
@@ -1205,89 +2827,43 @@ handle_method_call! {
             None => return Ok(None),
         };
 
-        let mut results = Vec::new();
-        if let Some(ref defines) = self.defines {
-            for (range, (name, define)) in defines.iter() {
-                if query.matches_define(name) {
-                    results.push(SymbolInformation {
-                        name: name.to_owned(),
-                        kind: SymbolKind::CONSTANT,
-                        location: self.convert_location(range.start, define.docs(), &["/DM/preprocessor/", name])?,
-                        container_name: None,
-                        tags: None,
-                        deprecated: None,
-                    });
-                }
-            }
+        if self.symbol_index.is_none() {
+            self.symbol_index = Some(symbol_search::SymbolIndex::build(&self.objtree, self.defines.as_ref()));
         }
+        let index = self.symbol_index.as_ref().expect("symbol index was just built");
+        let found = match index.search(&query, &|| self.inbox.is_cancelled()) {
+            Some(found) => found,
+            None => return Err(request_cancelled()),
+        };
 
-        for ty in self.objtree.iter_types() {
-            if query.matches_type(ty.name(), &ty.path) && !ty.is_root() {
-                results.push(SymbolInformation {
-                    name: ty.name().to_owned(),
-                    kind: SymbolKind::CLASS,
-                    location: self.convert_location(ty.location, &ty.docs, &[&ty.path])?,
-                    container_name: Some(ty.parent_path_str().to_owned()),
-                    tags: None,
-                    deprecated: None,
-                });
-            }
-
-            if !query.matches_on_type(&ty.path) {
-                continue;
-            }
-            for (var_name, tv) in ty.vars.iter() {
-                if let Some(decl) = tv.declaration.as_ref() {
-                    if query.matches_var(var_name) {
-                        results.push(SymbolInformation {
-                            name: var_name.clone(),
-                            kind: SymbolKind::FIELD,
-                            location: self.convert_location(decl.location, &tv.value.docs, &[&ty.path, "/var/", var_name])?,
-                            container_name: Some(ty.path.clone()),
-                            tags: None,
-                            deprecated: None,
-                        });
-                    }
-                }
-            }
-
-            for (proc_name, pv) in ty.procs.iter() {
-                if let Some(decl) = pv.declaration.as_ref() {
-                    if query.matches_proc(proc_name, decl.kind) {
-                        results.push(SymbolInformation {
-                            name: proc_name.clone(),
-                            kind: if ty.is_root() {
-                                SymbolKind::FUNCTION
-                            } else if is_constructor_name(proc_name.as_str()) {
-                                SymbolKind::CONSTRUCTOR
-                            } else {
-                                SymbolKind::METHOD
-                            },
-                            location: self.convert_location(decl.location, &pv.main_value().docs, &[&ty.path, "/proc/", proc_name])?,
-                            container_name: Some(ty.path.clone()),
-                            tags: None,
-                            deprecated: None,
-                        });
-                    }
-                }
-            }
+        let mut results = Vec::new();
+        for symbol in found {
+            results.push(SymbolInformation {
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                location: self.convert_location(symbol.location, &Default::default(), &[&symbol.builtin])?,
+                container_name: symbol.container_name.clone(),
+                tags: None,
+                deprecated: None,
+            });
         }
         Some(results)
     }
 
     on HoverRequest(&mut self, params) {
         let tdp = params.text_document_position_params;
-        let (_, file_id, annotations) = self.get_annotations(&tdp.text_document.uri)?;
-        let location = dm::Location {
-            file: file_id,
-            line: tdp.position.line + 1,
-            column: tdp.position.character as u16 + 1,
-        };
-        let symbol_id = self.symbol_id_at(tdp)?;
+        if is_map(&tdp.text_document.uri) {
+            return self.map_hover(&tdp.text_document.uri, tdp.position);
+        }
+        let (real_file_id, file_id, annotations) = self.get_annotations(&tdp.text_document.uri)?;
+        let location = self.document_location(&tdp.text_document.uri, file_id, tdp.position);
+        let symbol_id = self.symbol_id_at(tdp.clone())?;
+        let contents = self.docs.get_contents(&tdp.text_document.uri).map_err(invalid_request)?;
+        let line = contents.lines().nth(tdp.position.line as usize).unwrap_or_default();
         let mut results = Vec::new();
 
         let iter = annotations.get_location(location);
-        for (_range, annotation) in iter.clone() {
+        for (range, annotation) in iter.clone() {
             #[cfg(debug_assertions)] {
                 results.push(format!("{:?}", annotation));
             }
@@ -1309,9 +2885,9 @@ handle_method_call! {
                     let mut next = Some(current);
                     let mut docstring: Option<String> = None;
                     while let Some(current) = next {
-                        if let Some(var) = current.vars.get(last) {
+                        if let Some(var) = current.vars.get(last.as_str()) {
                             let constant = if let Some(ref constant) = var.value.constant {
-                                format!("\n```dm\n= {}\n```", constant)
+                                format!("\n{}", constant_hover(constant))
                             } else {
                                 String::new()
                             };
@@ -1351,7 +2927,7 @@ handle_method_call! {
                     let mut next = Some(current);
                     let mut docstring: Option<String> = None;
                     while let Some(current) = next {
-                        if let Some(proc) = current.procs.get(last) {
+                        if let Some(proc) = current.procs.get(last.as_str()) {
                             let proc_value = proc.main_value();
                             let mut message = format!("[{}]({})  \n```dm\n{}(", current.pretty_path(), self.location_link(proc_value.location)?, last);
                             let mut first = true;
@@ -1404,12 +2980,62 @@ handle_method_call! {
                     let next = self.find_scoped_type(&iter, priors);
                     results.append(&mut self.construct_var_hover(var_name, next, true)?);
                 }
-                Annotation::MacroUse { docs: Some(dc), .. } if !dc.is_empty() => {
-                    results.push(dc.text());
+                Annotation::MacroUse { name, definition_location, docs } => {
+                    results.append(&mut self.construct_macro_hover(name, *definition_location, file_id, real_file_id));
+                    results.append(&mut self.construct_macro_call_hover(name, *definition_location, range, &contents, file_id, real_file_id));
+                    if let Some(dc) = docs {
+                        if !dc.is_empty() {
+                            results.push(dc.text());
+                        }
+                    }
+                }
+                Annotation::MacroBodyIdent(ident) => {
+                    match self.find_macro_body_symbol(&iter, &annotations, location, real_file_id, line, ident) {
+                        MacroBodySymbol::Parameter { define, params, .. } => {
+                            results.push(format!("```dm\n{}\n```\nparameter of `{}({})`", ident, define, params.join(", ")));
+                        }
+                        MacroBodySymbol::Macro(loc) => {
+                            results.append(&mut self.construct_macro_hover(ident, loc, file_id, real_file_id));
+                        }
+                        MacroBodySymbol::Type(ty) => {
+                            results.push(format!("[{}]({})", ty.pretty_path(), self.location_link(ty.location)?));
+                            if !ty.docs.is_empty() {
+                                results.push(ty.docs.text());
+                            }
+                        }
+                        MacroBodySymbol::Proc(root) => {
+                            results.append(&mut self.construct_proc_hover(ident, Some(root), false)?);
+                        }
+                        MacroBodySymbol::Var(root) => {
+                            results.append(&mut self.construct_var_hover(ident, Some(root), false)?);
+                        }
+                        MacroBodySymbol::None => {}
+                    }
                 }
                 _ => {}
             }
         }
+
+        // Preview the icon state being assigned, if the type's icon is known.
+        if let Some(state) = icons::state_assigned_at(line, tdp.position.character as usize).map(ToOwned::to_owned) {
+            let ty = iter.clone()
+                .find_map(|(_, annotation)| match annotation {
+                    Annotation::Variable(path) => {
+                        let (_, most) = path.split_last()?;
+                        self.objtree.type_by_path(most.iter().take_while(|part| *part != "var"))
+                    }
+                    _ => None,
+                })
+                .or_else(|| self.find_type_context(&iter).0);
+            let icon = ty.and_then(|ty| ty.get_value("icon")).and_then(|value| match value.constant {
+                Some(dm::constants::Constant::Resource(ref name)) if !value.location.is_builtins() => Some((name.to_string(), value.location.file)),
+                _ => None,
+            });
+            if let Some((icon, icon_file)) = icon {
+                results.extend(self.icon_state_hover(&icon, icon_file, &state)?);
+            }
+        }
+
         if results.is_empty() {
             None
         } else {
@@ -1422,12 +3048,11 @@ handle_method_call! {
 
     on GotoDefinition(&mut self, params) {
         let tdp = params.text_document_position_params;
+        if is_map(&tdp.text_document.uri) {
+            return self.map_definition(&tdp.text_document.uri, tdp.position);
+        }
         let (real_file_id, file_id, annotations) = self.get_annotations(&tdp.text_document.uri)?;
-        let location = dm::Location {
-            file: file_id,
-            line: tdp.position.line + 1,
-            column: tdp.position.character as u16 + 1,
-        };
+        let location = self.document_location(&tdp.text_document.uri, file_id, tdp.position);
         let mut results = Vec::new();
 
         let iter = annotations.get_location(location);
@@ -1472,7 +3097,7 @@ handle_method_call! {
                 let (ty, _) = self.find_type_context(&iter);
                 let mut next = ty.or_else(|| Some(self.objtree.root()));
                 while let Some(ty) = next {
-                    if let Some(proc) = ty.procs.get(proc_name) {
+                    if let Some(proc) = ty.procs.get(proc_name.as_str()) {
                         results.push(self.convert_location(proc.main_value().location, &proc.main_value().docs, &[&ty.path, "/proc/", proc_name])?);
                         break;
                     }
@@ -1497,7 +3122,7 @@ handle_method_call! {
             Annotation::ScopedCall(priors, proc_name) => {
                 let mut next = self.find_scoped_type(&iter, priors);
                 while let Some(ty) = next {
-                    if let Some(proc) = ty.procs.get(proc_name) {
+                    if let Some(proc) = ty.procs.get(proc_name.as_str()) {
                         results.push(self.convert_location(proc.main_value().location, &proc.main_value().docs, &[&ty.path, "/proc/", proc_name])?);
                         break;
                     }
@@ -1507,7 +3132,7 @@ handle_method_call! {
             Annotation::ScopedVar(priors, var_name) => {
                 let mut next = self.find_scoped_type(&iter, priors);
                 while let Some(ty) = next {
-                    if let Some(var) = ty.vars.get(var_name) {
+                    if let Some(var) = ty.vars.get(var_name.as_str()) {
                         results.push(self.convert_location(var.value.location, &var.value.docs, &[&ty.path, "/var/", var_name])?);
                         break;
                     }
@@ -1541,6 +3166,41 @@ handle_method_call! {
             Annotation::MacroUse { name, definition_location, .. } => {
                 results.push(self.convert_location(*definition_location, &Default::default(), &["/DM/preprocessor/", name])?);
             },
+            Annotation::MacroBodyIdent(ident) => {
+                let contents = self.docs.get_contents(&tdp.text_document.uri).map_err(invalid_request)?;
+                let line = contents.lines().nth(tdp.position.line as usize).unwrap_or_default();
+                match self.find_macro_body_symbol(&iter, &annotations, location, real_file_id, line, ident) {
+                    MacroBodySymbol::Parameter { loc, .. } => {
+                        results.push(self.convert_location(dm::Location { file: real_file_id, ..loc }, &Default::default(), &[])?);
+                    }
+                    MacroBodySymbol::Macro(loc) => {
+                        results.push(self.convert_location(loc, &Default::default(), &["/DM/preprocessor/", ident])?);
+                    }
+                    MacroBodySymbol::Type(ty) => {
+                        results.push(self.convert_location(ty.location, &ty.docs, &[&ty.path])?);
+                    }
+                    MacroBodySymbol::Proc(root) => {
+                        if let Some(proc) = root.procs.get(ident.as_str()) {
+                            results.push(self.convert_location(proc.main_value().location, &proc.main_value().docs, &["/proc/", ident])?);
+                        }
+                    }
+                    MacroBodySymbol::Var(root) => {
+                        if let Some(var) = root.vars.get(ident.as_str()) {
+                            results.push(self.convert_location(var.value.location, &var.value.docs, &["/var/", ident])?);
+                        }
+                    }
+                    MacroBodySymbol::None => {}
+                }
+            },
+            Annotation::Resource(path) => {
+                let file_path = url_to_path(&tdp.text_document.uri)?;
+                if let Some(target) = self.resolve_resource(&file_path, &path.to_string_lossy())? {
+                    results.push(lsp_types::Location {
+                        uri: path_to_url(target)?,
+                        range: Default::default(),
+                    });
+                }
+            },
         }
 
         if results.is_empty() {
@@ -1551,14 +3211,11 @@ handle_method_call! {
     }
 
     on GotoTypeDefinition(&mut self, params) {
-        // Like GotoDefinition, but only supports vars, then finds their types
+        // Like GotoDefinition, but finds the static type of the var or
+        // expression under the cursor instead
         let tdp = params.text_document_position_params;
-        let (_, file_id, annotations) = self.get_annotations(&tdp.text_document.uri)?;
-        let location = dm::Location {
-            file: file_id,
-            line: tdp.position.line + 1,
-            column: tdp.position.character as u16 + 1,
-        };
+        let (real_file_id, file_id, annotations) = self.get_annotations(&tdp.text_document.uri)?;
+        let location = self.document_location(&tdp.text_document.uri, file_id, tdp.position);
 
         let mut type_path: &[String] = &[];
 
@@ -1584,7 +3241,7 @@ handle_method_call! {
             Annotation::ScopedVar(priors, var_name) => {
                 let mut next = self.find_scoped_type(&iter, priors);
                 while let Some(ty) = next {
-                    if let Some(var) = ty.get().vars.get(var_name) {
+                    if let Some(var) = ty.get().vars.get(var_name.as_str()) {
                         if let Some(ref decl) = var.declaration {
                             type_path = &decl.var_type.type_path;
                             break;
@@ -1595,13 +3252,42 @@ handle_method_call! {
             },
         }
 
-        if type_path.is_empty() {
-            None
-        } else if let Some(ty) = self.objtree.type_by_path(type_path) {
-            let ty_loc = self.convert_location(ty.location, &ty.docs, &[&ty.path])?;
-            Some(GotoDefinitionResponse::Scalar(ty_loc))
+        let mut types = Vec::new();
+        if !type_path.is_empty() {
+            // `list/mob/L` is both a /list and a list of /mob
+            if type_path[0] == "list" {
+                types.push(self.objtree.expect("/list").index());
+                type_path = &type_path[1..];
+            }
+            types.extend(self.objtree.type_by_path(type_path).map(|ty| ty.index()));
         } else {
+            // Otherwise, walk the surrounding proc to find the static type
+            // of the expression which begins nearest before the cursor.
+            let cursor = dm::Location { file: real_file_id, ..location };
+            let proc = self.objtree.iter_types()
+                .flat_map(|ty| ty.iter_self_procs())
+                .filter(|proc| proc.location.file == real_file_id && proc.location <= cursor && !proc.is_builtin())
+                .max_by_key(|proc| proc.location);
+            if let Some(proc) = proc {
+                let found = find_references::collect_types(&self.objtree, proc)
+                    .into_iter()
+                    .filter(|(start, _)| start.line == cursor.line && start.column <= cursor.column)
+                    .max_by_key(|(start, _)| start.column);
+                if let Some((_, found)) = found {
+                    types = found;
+                }
+            }
+        }
+
+        let mut results = Vec::new();
+        for idx in types {
+            let ty = &self.objtree[idx];
+            results.push(self.convert_location(ty.location, &ty.docs, &[&ty.path])?);
+        }
+        if results.is_empty() {
             None
+        } else {
+            Some(GotoDefinitionResponse::Array(results))
         }
     }
 
@@ -1609,6 +3295,14 @@ handle_method_call! {
         // Like GotoDefinition, but looks up references instead
         let symbol_id = self.symbol_id_at(params.text_document_position)?;
 
+        if let Some(token) = params.partial_result_params.partial_result_token {
+            if let Some(id) = symbol_id {
+                self.stream_references(token, id)?;
+            }
+            // Everything was sent as partial results.
+            return Ok(Some(Vec::new()));
+        }
+
         let mut result = &[][..];
         if let Some(id) = symbol_id {
             self.references_table.poll();
@@ -1620,7 +3314,10 @@ handle_method_call! {
             None
         } else {
             let mut output = Vec::new();
-            for each in result {
+            for (i, each) in result.iter().enumerate() {
+                if i % CANCEL_CHECK_INTERVAL == 0 && self.inbox.is_cancelled() {
+                    return Err(request_cancelled());
+                }
                 output.push(self.convert_location(*each, &Default::default(), &[])?);
             }
             Some(output)
@@ -1629,13 +3326,26 @@ handle_method_call! {
 
     on GotoImplementation(&mut self, params) {
         let tdp = params.text_document_position_params;
+        // Exact calls can only ever reach one override.
+        if let Some(key) = self.exact_call_at(tdp.clone())? {
+            if let Some(proc) = key.resolve(&self.objtree) {
+                let location = self.convert_location(proc.location, &proc.docs, &[&key.path, "/proc/", &key.name])?;
+                return Ok(Some(GotoDefinitionResponse::Scalar(location)));
+            }
+        }
+
         let symbol_id = self.symbol_id_at(tdp)?;
 
-        let mut result = &[][..];
+        let mut result = Vec::new();
         if let Some(id) = symbol_id {
             self.references_table.poll();
             if let Some(table) = self.references_table.value() {
-                result = table.find_implementations(id);
+                let overrides = table.find_overrides(id);
+                if overrides.is_empty() {
+                    result.extend(table.find_implementations(id).iter().copied());
+                } else {
+                    result.extend(overrides.iter().map(|&(_, location)| location));
+                }
             }
         }
         if result.is_empty() {
@@ -1643,35 +3353,662 @@ handle_method_call! {
         } else {
             let mut output = Vec::new();
             for each in result {
-                output.push(self.convert_location(*each, &Default::default(), &[])?);
+                output.push(self.convert_location(each, &Default::default(), &[])?);
             }
             Some(GotoDefinitionResponse::Array(output))
         }
     }
 
+    on DocumentHighlightRequest(&mut self, params) {
+        let tdp = params.text_document_position_params;
+        let symbol_id = match self.symbol_id_at(tdp.clone())? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let (real_file_id, _, _) = self.get_annotations(&tdp.text_document.uri)?;
+        let table = match self.references_table.value() {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+
+        let contents = self.docs.get_contents(&tdp.text_document.uri).map_err(invalid_request)?;
+        let name = match document::find_word_range(&contents, tdp.position) {
+            Some((_, word)) => word,
+            None => return Ok(None),
+        };
+        let mut output: Vec<DocumentHighlight> = Vec::new();
+        for (loc, write) in table.find_highlights(symbol_id, real_file_id) {
+            let Some(range) = document::find_identifier(&contents, loc.line - 1, loc.column.saturating_sub(1) as usize, name) else {
+                continue;
+            };
+            let kind = if write { DocumentHighlightKind::WRITE } else { DocumentHighlightKind::READ };
+            match output.iter_mut().find(|each| each.range == range) {
+                Some(existing) => if write {
+                    existing.kind = Some(kind);
+                },
+                None => output.push(DocumentHighlight { range, kind: Some(kind) }),
+            }
+        }
+        if output.is_empty() {
+            None
+        } else {
+            Some(output)
+        }
+    }
+
+    on CallHierarchyPrepare(&mut self, params) {
+        match self.proc_key_at(params.text_document_position_params)? {
+            Some(key) => self.call_hierarchy_item(&key)?.map(|item| vec![item]),
+            None => None,
+        }
+    }
+
+    on CallHierarchyIncomingCalls(&mut self, params) {
+        let key: ProcKey = serde_json::from_value(params.item.data.unwrap_or_default()).map_err(invalid_request)?;
+        self.references_table.poll();
+        let table = match self.references_table.value() {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let mut output = Vec::new();
+        for (caller, locations) in table.incoming_calls(&key) {
+            if let Some(from) = self.call_hierarchy_item(caller)? {
+                output.push(CallHierarchyIncomingCall {
+                    from,
+                    from_ranges: locations.into_iter().map(|location| self.location_to_range(location)).collect(),
+                });
+            }
+        }
+        Some(output)
+    }
+
+    on CallHierarchyOutgoingCalls(&mut self, params) {
+        let key: ProcKey = serde_json::from_value(params.item.data.unwrap_or_default()).map_err(invalid_request)?;
+        self.references_table.poll();
+        let table = match self.references_table.value() {
+            Some(table) => table,
+            None => return Ok(None),
+        };
+        let mut output = Vec::new();
+        for (callee, locations) in table.outgoing_calls(&key) {
+            if let Some(to) = self.call_hierarchy_item(callee)? {
+                output.push(CallHierarchyOutgoingCall {
+                    to,
+                    from_ranges: locations.into_iter().map(|location| self.location_to_range(location)).collect(),
+                });
+            }
+        }
+        Some(output)
+    }
+
+    on TypeHierarchyPrepare(&mut self, params) {
+        let path = match self.type_path_at(params.text_document_position_params)? {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        match type_hierarchy::prepare(&self.objtree, &path) {
+            Some(ty) => Some(vec![self.type_hierarchy_item(ty)?]),
+            None => None,
+        }
+    }
+
+    on TypeHierarchySupertypes(&mut self, params) {
+        let path = params.item.data.unwrap_or(params.item.name);
+        match type_hierarchy::supertypes(&self.objtree, &path) {
+            Some(types) => Some(types.into_iter().map(|ty| self.type_hierarchy_item(ty)).collect::<Result<_, _>>()?),
+            None => None,
+        }
+    }
+
+    on TypeHierarchySubtypes(&mut self, params) {
+        let path = params.item.data.unwrap_or(params.item.name);
+        match type_hierarchy::subtypes(&self.objtree, &path) {
+            Some(types) => Some(types.into_iter().map(|ty| self.type_hierarchy_item(ty)).collect::<Result<_, _>>()?),
+            None => None,
+        }
+    }
+
+    on SemanticTokensFullRequest(&mut self, params) {
+        let data = self.semantic_tokens(&params.text_document.uri, None)?;
+        Some(SemanticTokensResult::Tokens(SemanticTokens { result_id: None, data }))
+    }
+
+    on SemanticTokensRangeRequest(&mut self, params) {
+        let data = self.semantic_tokens(&params.text_document.uri, Some(params.range))?;
+        Some(SemanticTokensRangeResult::Tokens(SemanticTokens { result_id: None, data }))
+    }
+
+    on InlayHintRequest(&mut self, params) {
+        let (real_file_id, _, _) = self.get_annotations(&params.text_document.uri)?;
+        let contents = self.docs.get_contents(&params.text_document.uri).map_err(invalid_request)?.into_owned();
+        let lines: Vec<&str> = contents.split('\n').collect();
+        let range = params.range;
+        let first_line = range.start.line + 1;
+        let last_line = range.end.line + 1;
+
+        // Only walk the procs which overlap the requested range. A proc ends
+        // at the latest where the next one in the file begins.
+        let mut procs: Vec<dm::objtree::ProcRef> = self.objtree.iter_types()
+            .flat_map(|ty| ty.iter_self_procs())
+            .filter(|proc| proc.location.file == real_file_id && !proc.is_builtin())
+            .collect();
+        procs.sort_by_key(|proc| proc.location);
+
+        let mut output = Vec::new();
+        for (i, proc) in procs.iter().enumerate() {
+            if proc.location.line > last_line {
+                break;
+            }
+            if procs.get(i + 1).is_some_and(|next| next.location.line < first_line) {
+                continue;
+            }
+            for hint in find_references::collect_hints(&self.objtree, *proc) {
+                if hint.location.line < first_line || hint.location.line > last_line {
+                    continue;
+                }
+                let line = hint.location.line - 1;
+                let column = hint.location.column.saturating_sub(1) as usize;
+                let line_text = lines.get(line as usize).copied().unwrap_or("");
+                let (position, label, kind) = match hint.kind {
+                    find_references::HintKind::Type { var_name, type_path } if self.inlay_hints.types => {
+                        let Some(start) = document::find_identifier_in_line(line_text, column, &var_name) else {
+                            continue;
+                        };
+                        (start + var_name.len(), format!(": {}", type_path), InlayHintKind::TYPE)
+                    },
+                    find_references::HintKind::Parameter(name) if self.inlay_hints.parameter_names => {
+                        (column, format!("{}:", name), InlayHintKind::PARAMETER)
+                    },
+                    find_references::HintKind::ParentArguments(names) if self.inlay_hints.parent_call_arguments => {
+                        let Some(open) = line_text.get(column..).and_then(|rest| rest.find('(')) else {
+                            continue;
+                        };
+                        (column + open + 1, names.join(", "), InlayHintKind::PARAMETER)
+                    },
+                    _ => continue,
+                };
+                let character = line_text.get(..position).map_or(0, |before| positions::Encoding::current().len(before));
+                output.push(InlayHint {
+                    position: Position::new(line, character),
+                    padding_right: Some(label.ends_with(':')),
+                    label: InlayHintLabel::String(label),
+                    kind: Some(kind),
+                    text_edits: None,
+                    tooltip: None,
+                    padding_left: None,
+                    data: None,
+                });
+            }
+        }
+        Some(output)
+    }
+
+    on CodeLensRequest(&mut self, params) {
+        if !self.code_lens {
+            return Ok(None);
+        }
+        let (real_file_id, _, _) = self.get_annotations(&params.text_document.uri)?;
+        let mut output = Vec::new();
+        // The counts are filled in by CodeLensResolve, so opening a file
+        // doesn't wait on every symbol's references.
+        for (location, path, kind) in code_lens::declarations(&self.objtree, real_file_id) {
+            let data = code_lens::LensData { uri: params.text_document.uri.clone(), path, kind };
+            output.push(CodeLens {
+                range: self.location_to_range(location),
+                command: None,
+                data: Some(serde_json::to_value(data).expect("encode problem")),
+            });
+        }
+        Some(output)
+    }
+
+    on CodeLensResolve(&mut self, mut lens) {
+        let data: code_lens::LensData = match lens.data.clone() {
+            Some(data) => serde_json::from_value(data).map_err(invalid_request)?,
+            None => return Err(invalid_request("code lens is missing its data")),
+        };
+        let symbol = match find_references::resolve_symbol_path(&self.objtree, &data.path) {
+            Some(symbol) => symbol,
+            None => return Err(invalid_request(format!("no such symbol: {}", data.path))),
+        };
+
+        let mut locations = Vec::new();
+        if data.kind == code_lens::LensKind::Subtypes {
+            if let Some(ty) = self.objtree.type_by_path(data.path.split('/').filter(|part| !part.is_empty())) {
+                for subtype in self.objtree.iter_types() {
+                    if subtype != ty && subtype.is_subtype_of(&ty) {
+                        locations.push(subtype.location);
+                    }
+                }
+            }
+        } else {
+            self.references_table.poll();
+            let table = match self.references_table.value() {
+                Some(table) => table,
+                None => {
+                    lens.command = Some(Command {
+                        title: "counting...".to_owned(),
+                        command: String::new(),
+                        arguments: None,
+                    });
+                    return Ok(lens);
+                }
+            };
+            if data.kind == code_lens::LensKind::References {
+                locations.extend(table.find_references(symbol, false).iter().copied());
+            } else {
+                // Leave out the declaration itself.
+                let declaration = lens.range.start;
+                locations.extend(table.find_overrides(symbol).iter()
+                    .map(|&(_, location)| location)
+                    .filter(|&location| self.location_to_range(location).start != declaration));
+            }
+        }
+
+        let mut converted = Vec::with_capacity(locations.len());
+        for location in locations {
+            converted.push(self.convert_location(location, &Default::default(), &[])?);
+        }
+        lens.command = Some(Command {
+            title: data.kind.title(converted.len()),
+            command: "editor.action.showReferences".to_owned(),
+            arguments: Some(vec![
+                serde_json::to_value(&data.uri).expect("encode problem"),
+                serde_json::to_value(lens.range.start).expect("encode problem"),
+                serde_json::to_value(converted).expect("encode problem"),
+            ]),
+        });
+        lens
+    }
+
+    on PrepareRenameRequest(&mut self, params) {
+        let symbol_id = match self.symbol_id_at(params.clone())? {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        if self.is_type_symbol(symbol_id) {
+            return Err(invalid_request("renaming types is not supported"));
+        }
+        let contents = self.docs.get_contents(&params.text_document.uri).map_err(invalid_request)?;
+        document::find_word_range(&contents, params.position).map(|(range, word)| {
+            PrepareRenameResponse::RangeWithPlaceholder {
+                range,
+                placeholder: word.to_owned(),
+            }
+        })
+    }
+
+    on Rename(&mut self, params) {
+        let tdp = params.text_document_position;
+        let new_name = params.new_name;
+        if !document::is_valid_ident(&new_name) {
+            return Err(invalid_request(format!("not a valid identifier: {:?}", new_name)));
+        }
+
+        let symbol_id = match self.symbol_id_at(tdp.clone())? {
+            Some(id) => id,
+            None => return Err(invalid_request("nothing to rename here")),
+        };
+        if self.is_type_symbol(symbol_id) {
+            return Err(invalid_request("renaming types is not supported"));
+        }
+        let old_name = {
+            let contents = self.docs.get_contents(&tdp.text_document.uri).map_err(invalid_request)?;
+            match document::find_word_range(&contents, tdp.position) {
+                Some((_, word)) => word.to_owned(),
+                None => return Err(invalid_request("nothing to rename here")),
+            }
+        };
+
+        let table = match self.references_table.value() {
+            Some(table) => table,
+            None => return Err(invalid_request("references are still being computed")),
+        };
+        // Implementations include every override and redefinition.
+        let mut locations: Vec<dm::Location> = table.find_references(symbol_id, true).iter()
+            .chain(table.find_implementations(symbol_id))
+            .filter(|loc| !loc.is_builtins())
+            .copied()
+            .collect();
+        locations.sort_unstable();
+        locations.dedup();
+        let conflict = table.local_name_conflicts(&locations, &new_name, &|| self.inbox.is_cancelled())
+            .ok_or_else(request_cancelled)?;
+
+        let mut changes: std::collections::HashMap<Url, Vec<TextEdit>> = Default::default();
+        for group in locations.chunk_by(|a, b| a.file == b.file) {
+            if self.inbox.is_cancelled() {
+                return Err(request_cancelled());
+            }
+            let uri = self.file_url(group[0].file)?;
+            let contents = self.docs.get_contents(&uri).map_err(invalid_request)?;
+            let edits = changes.entry(uri).or_default();
+            for loc in group {
+                if let Some(range) = document::find_identifier(&contents, loc.line - 1, loc.column.saturating_sub(1) as usize, &old_name) {
+                    if !edits.iter().any(|edit: &TextEdit| edit.range == range) {
+                        edits.push(TextEdit { range, new_text: new_name.clone() });
+                    }
+                }
+            }
+        }
+
+        if conflict {
+            // Still offer the edit, but ask the user to confirm it.
+            let annotation_id: ChangeAnnotationIdentifier = "local-conflict".to_owned();
+            let document_changes = changes.into_iter().map(|(uri, edits)| TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                edits: edits.into_iter().map(|text_edit| OneOf::Right(AnnotatedTextEdit {
+                    text_edit,
+                    annotation_id: annotation_id.clone(),
+                })).collect(),
+            }).collect();
+            let mut change_annotations = std::collections::HashMap::new();
+            change_annotations.insert(annotation_id, ChangeAnnotation {
+                label: format!("`{}` is already a local variable", new_name),
+                needs_confirmation: Some(true),
+                description: Some("The renamed symbol may be shadowed by or shadow an existing local.".to_owned()),
+            });
+            Some(WorkspaceEdit {
+                changes: None,
+                document_changes: Some(DocumentChanges::Edits(document_changes)),
+                change_annotations: Some(change_annotations),
+            })
+        } else {
+            Some(WorkspaceEdit {
+                changes: Some(changes),
+                document_changes: None,
+                change_annotations: None,
+            })
+        }
+    }
+
+    on OnTypeFormatting(&mut self, params) {
+        if !self.format_on_type {
+            return Ok(None);
+        }
+        let tdp = params.text_document_position;
+        let (_, file_id, annotations) = self.get_annotations(&tdp.text_document.uri)?;
+        let contents = self.docs.get_contents(&tdp.text_document.uri).map_err(invalid_request)?;
+        let lines: Vec<&str> = contents.split('\n').collect();
+        let indent_len = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+
+        // the file's own style wins over the editor's
+        let unit = if lines.iter().any(|line| indent_len(line) > 0 && !line.trim().is_empty()) {
+            document::detect_indent(&contents)
+        } else if params.options.insert_spaces {
+            " ".repeat(params.options.tab_size as usize)
+        } else {
+            "\t".to_owned()
+        };
+        // ask the parser whether a line is a statement or a declaration
+        let in_proc = |line: usize| {
+            let column = lines.get(line).map_or(0, |text| indent_len(text)) + 1;
+            let location = dm::Location { file: file_id, line: line as u32 + 1, column: column as u16 };
+            annotations.get_location(location)
+                .any(|(_, annotation)| matches!(annotation, Annotation::ProcBody(..) | Annotation::StatementBlock))
+        };
+
+        let line = tdp.position.line as usize;
+        let edits = match params.ch.as_str() {
+            "\n" => formatting::after_newline(&contents, line, &unit, in_proc),
+            ":" => formatting::after_colon(&contents, line, &unit, in_proc),
+            _ => Vec::new(),
+        };
+        if edits.is_empty() {
+            None
+        } else {
+            Some(edits.into_iter().map(|(line, new_text)| {
+                let end = lines.get(line).map_or(0, |text| indent_len(text));
+                TextEdit {
+                    range: Range::new(Position::new(line as u32, 0), Position::new(line as u32, end as u32)),
+                    new_text,
+                }
+            }).collect())
+        }
+    }
+
+    on Formatting(&mut self, params) {
+        self.format_document(&params.text_document.uri, &params.options, None)?
+    }
+
+    on RangeFormatting(&mut self, params) {
+        let range = params.range;
+        self.format_document(&params.text_document.uri, &params.options, Some(range.start.line..=range.end.line))?
+    }
+
+    on CodeActionRequest(&mut self, params) {
+        let only = params.context.only;
+        let wants = |kind: CodeActionKind| only.as_ref()
+            .is_none_or(|only| only.iter().any(|prefix| kind.as_str().starts_with(prefix.as_str())));
+
+        let uri = params.text_document.uri;
+        let mut actions = Vec::new();
+        let diagnostics = if wants(CodeActionKind::QUICKFIX) { params.context.diagnostics } else { Vec::new() };
+        for diagnostic in diagnostics {
+            if let Some(name) = code_actions::unused_var(&diagnostic.message) {
+                let contents = self.docs.get_contents(&uri).map_err(invalid_request)?;
+                if let Some(range) = code_actions::removable_declaration(&contents, diagnostic.range.start.line as usize, name) {
+                    let range = Range::new(document::offset_to_position(&contents, range.start), document::offset_to_position(&contents, range.end));
+                    let mut changes = std::collections::HashMap::new();
+                    changes.insert(uri.clone(), vec![TextEdit { range, new_text: String::new() }]);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Remove unused var {}", name),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(changes),
+                            document_changes: None,
+                            change_annotations: None,
+                        }),
+                        is_preferred: Some(true),
+                        .. Default::default()
+                    }));
+                }
+                continue;
+            }
+            if let Some((string, path)) = code_actions::string_typepath(&diagnostic.message) {
+                let contents = self.docs.get_contents(&uri).map_err(invalid_request)?;
+                let offset = document::total_offset(&contents, diagnostic.range.start.line, diagnostic.range.start.character)?;
+                if let Some(range) = code_actions::string_literal(&contents, offset, string) {
+                    let range = Range::new(document::offset_to_position(&contents, range.start), document::offset_to_position(&contents, range.end));
+                    let mut changes = std::collections::HashMap::new();
+                    changes.insert(uri.clone(), vec![TextEdit { range, new_text: path.to_owned() }]);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: format!("Use the path {}", path),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(changes),
+                            document_changes: None,
+                            change_annotations: None,
+                        }),
+                        is_preferred: Some(true),
+                        .. Default::default()
+                    }));
+                }
+                continue;
+            }
+            if code_actions::assignment_condition(&diagnostic.message) {
+                let contents = self.docs.get_contents(&uri).map_err(invalid_request)?;
+                let offset = document::total_offset(&contents, diagnostic.range.start.line, diagnostic.range.start.character)?;
+                if let Some(offset) = code_actions::condition_assignment(&contents, offset) {
+                    let position = document::offset_to_position(&contents, offset);
+                    let mut changes = std::collections::HashMap::new();
+                    changes.insert(uri.clone(), vec![TextEdit { range: Range::new(position, position), new_text: "=".to_owned() }]);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: "Change `=` to `==`".to_owned(),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(changes),
+                            document_changes: None,
+                            change_annotations: None,
+                        }),
+                        is_preferred: Some(true),
+                        .. Default::default()
+                    }));
+                }
+                continue;
+            }
+            if let Some((op, side)) = code_actions::ambiguous_in(&diagnostic.message) {
+                let contents = self.docs.get_contents(&uri).map_err(invalid_request)?;
+                let offset = document::total_offset(&contents, diagnostic.range.start.line, diagnostic.range.start.character)?;
+                if let Some(edits) = code_actions::in_grouping(&contents, offset, op, side) {
+                    let edits = edits.into_iter().map(|(range, new_text)| TextEdit {
+                        range: Range::new(document::offset_to_position(&contents, range.start), document::offset_to_position(&contents, range.end)),
+                        new_text,
+                    }).collect();
+                    let mut changes = std::collections::HashMap::new();
+                    changes.insert(uri.clone(), edits);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: "Add parentheses around the `in`".to_owned(),
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(changes),
+                            document_changes: None,
+                            change_annotations: None,
+                        }),
+                        is_preferred: Some(true),
+                        .. Default::default()
+                    }));
+                }
+                continue;
+            }
+            if let Some((name, op, ty)) = code_actions::colon_access(&diagnostic.message) {
+                let contents = self.docs.get_contents(&uri).map_err(invalid_request)?;
+                let offset = document::total_offset(&contents, diagnostic.range.start.line, diagnostic.range.start.character)?;
+                if let Some(edits) = code_actions::colon_rewrite(&contents, offset, name, op, ty) {
+                    let edits = edits.into_iter().map(|(range, new_text)| TextEdit {
+                        range: Range::new(document::offset_to_position(&contents, range.start), document::offset_to_position(&contents, range.end)),
+                        new_text,
+                    }).collect();
+                    let mut changes = std::collections::HashMap::new();
+                    changes.insert(uri.clone(), edits);
+                    actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                        title: match ty {
+                            Some(ty) => format!("Declare as {} and use {:?}", ty, op.replace(':', ".")),
+                            None => format!("Use {:?} instead of {:?}", op.replace(':', "."), op),
+                        },
+                        kind: Some(CodeActionKind::QUICKFIX),
+                        diagnostics: Some(vec![diagnostic.clone()]),
+                        edit: Some(WorkspaceEdit {
+                            changes: Some(changes),
+                            document_changes: None,
+                            change_annotations: None,
+                        }),
+                        is_preferred: Some(true),
+                        .. Default::default()
+                    }));
+                }
+                continue;
+            }
+            let Some((proc_name, type_name)) = code_actions::undefined_proc(&diagnostic.message) else {
+                continue;
+            };
+            let ty = if type_name == "(global)" {
+                Some(self.objtree.root())
+            } else {
+                self.objtree.find(type_name)
+            };
+            let Some(ty) = ty else {
+                continue;
+            };
+
+            let params = self.call_parameter_names(&uri, diagnostic.range.start, proc_name);
+            actions.push(self.create_proc_action(&uri, ty, proc_name, &params, &diagnostic, true)?);
+            // the declared type is often more specific than where it belongs
+            if let Some(parent) = ty.parent_type_without_root() {
+                actions.push(self.create_proc_action(&uri, parent, proc_name, &params, &diagnostic, false)?);
+            }
+        }
+
+        if wants(CodeActionKind::REFACTOR_REWRITE) {
+            let contents = self.docs.get_contents(&uri).map_err(invalid_request)?;
+            let line = params.range.start.line as usize;
+            let mut rewrite = |title: String, range: std::ops::Range<usize>, new_text: String| {
+                let range = Range::new(document::offset_to_position(&contents, range.start), document::offset_to_position(&contents, range.end));
+                let mut changes = std::collections::HashMap::new();
+                changes.insert(uri.clone(), vec![TextEdit { range, new_text }]);
+                actions.push(CodeActionOrCommand::CodeAction(CodeAction {
+                    title,
+                    kind: Some(CodeActionKind::REFACTOR_REWRITE),
+                    edit: Some(WorkspaceEdit {
+                        changes: Some(changes),
+                        document_changes: None,
+                        change_annotations: None,
+                    }),
+                    .. Default::default()
+                }));
+            };
+            if let Some((range, new_text)) = code_actions::absolute_rewrite(&contents, line) {
+                rewrite("Rewrite with absolute paths".to_owned(), range, new_text);
+            }
+            if let Some((type_path, range, new_text)) = code_actions::block_rewrite(&contents, line) {
+                rewrite(format!("Rewrite as a block under {}", type_path), range, new_text);
+            }
+        }
+
+        if actions.is_empty() {
+            None
+        } else {
+            Some(actions)
+        }
+    }
+
     on Completion(&mut self, params) {
         let (_, file_id, annotations) = self.get_annotations(&params.text_document_position.text_document.uri)?;
-        let location = dm::Location {
-            file: file_id,
-            line: params.text_document_position.position.line + 1,
-            column: params.text_document_position.position.character as u16 + 1,
-        };
+        let location = self.document_location(&params.text_document_position.text_document.uri, file_id, params.text_document_position.position);
         let iter = annotations.get_location(location);
         let mut results = Vec::new();
         let mut any_annotation = false;
 
+        // type paths in expressions are narrowed by what surrounds them
+        let (line_before_cursor, indent) = {
+            let contents = self.docs.get_contents(&params.text_document_position.text_document.uri).map_err(invalid_request)?;
+            (
+                document::line_before(&contents, params.text_document_position.position).unwrap_or_default().to_owned(),
+                document::detect_indent(&contents),
+            )
+        };
+        if let Some((before_assignment, typed)) = completion::icon_state_value(&line_before_cursor) {
+            let url = &params.text_document_position.text_document.uri;
+            let position = params.text_document_position.position;
+            let contents = self.docs.get_contents(url).map_err(invalid_request)?;
+            let icon = match completion::preceding_icon_override(&contents, position.line, before_assignment) {
+                Some(icon) => Some((icon.to_owned(), url_to_path(url)?)),
+                None => self.icon_of_context(&iter, &completion::classify_path_context(before_assignment)),
+            };
+            if let Some((icon, relative_to)) = icon {
+                if let Some(path) = self.resolve_resource(&relative_to, &icon)? {
+                    if let Ok(dmi) = self.icons.get(&path) {
+                        let start = Position::new(position.line, position.character.saturating_sub(positions::Encoding::current().len(typed)));
+                        completion::icon_state_items(&mut results, &dmi, typed, Range::new(start, position));
+                    }
+                }
+            }
+            return Ok(if results.is_empty() { None } else { Some(CompletionResponse::Array(results)) });
+        }
+
+        let path_context = completion::classify_path_context(&line_before_cursor);
+        if let completion::PathContext::PrefabVars { ref path, ref assigned } = path_context {
+            let query_start = line_before_cursor.trim_end_matches(|ch: char| ch.is_ascii_alphanumeric() || ch == '_').len();
+            self.prefab_var_completions(&mut results, &iter, path, assigned, &line_before_cursor[query_start..]);
+            return Ok(Some(CompletionResponse::Array(results)));
+        }
+
         match_annotation! { iter;
             // happy path annotations
             Annotation::TreePath(absolute, parts) => {
                 let (query, parts) = parts.split_last().unwrap();
                 let path = completion::combine_tree_path(&iter, *absolute, parts);
                 let (exact, ty) = self.objtree.type_by_path_approx(path);
-                self.tree_completions(&mut results, exact, ty, query);
+                self.tree_completions(&mut results, exact, ty, query, &indent);
                 any_annotation = true;
             },
             Annotation::TypePath(parts) => {
                 let ((last_op, query), parts) = parts.split_last().unwrap();
-                self.path_completions(&mut results, &iter, parts, *last_op, query);
+                self.path_completions(&mut results, &iter, parts, *last_op, query, &path_context);
                 any_annotation = true;
             },
             Annotation::UnscopedCall(query) |
@@ -1693,7 +4030,7 @@ handle_method_call! {
             },
             Annotation::IncompleteTypePath(parts, last_op) => {
                 results.clear();
-                self.path_completions(&mut results, &iter, parts, *last_op, "");
+                self.path_completions(&mut results, &iter, parts, *last_op, "", &path_context);
                 any_annotation = true;
                 break;
             },
@@ -1701,12 +4038,30 @@ handle_method_call! {
                 results.clear();
                 let path = completion::combine_tree_path(&iter, *absolute, parts);
                 let (exact, ty) = self.objtree.type_by_path_approx(path);
-                self.tree_completions(&mut results, exact, ty, "");
+                self.tree_completions(&mut results, exact, ty, "", &indent);
                 any_annotation = true;
                 break;
             },
         }
 
+        // keyword arguments, unless we're already in the value of one
+        if let Some(call) = self.find_call_context(&iter) {
+            let contents = self.docs.get_contents(&params.text_document_position.text_document.uri).map_err(invalid_request)?;
+            let start = call.argument_start.unwrap_or(call.span.start);
+            if let (Some(start), Some(end)) = (
+                document::location_offset(&contents, start.line, start.column),
+                document::location_offset(&contents, location.line, location.column),
+            ) {
+                // an empty trailing argument has no annotation of its own
+                if let Some(typed) = contents.get(start..end).filter(|_| start < end) {
+                    let typed = typed.rsplit(',').next().unwrap_or_default().trim_start_matches('(').trim();
+                    if typed.is_empty() || document::is_valid_ident(typed) {
+                        self.keyword_argument_completions(&mut results, &annotations, &call, &contents, typed);
+                    }
+                }
+            }
+        }
+
         if !any_annotation {
             // Someone hit Ctrl+Space with no usable idents nearby
             let (ty, proc_name) = self.find_type_context(&iter);
@@ -1714,7 +4069,7 @@ handle_method_call! {
                 // TODO: unscoped_completions calls find_type_context again
                 self.unscoped_completions(&mut results, &iter, "");
             } else {
-                self.tree_completions(&mut results, true, ty.unwrap_or_else(|| self.objtree.root()), "");
+                self.tree_completions(&mut results, true, ty.unwrap_or_else(|| self.objtree.root()), "", &indent);
             }
         }
 
@@ -1728,219 +4083,112 @@ handle_method_call! {
     on SignatureHelpRequest(&mut self, params) {
         let tdp = params.text_document_position_params;
         let (_, file_id, annotations) = self.get_annotations(&tdp.text_document.uri)?;
-        let location = dm::Location {
-            file: file_id,
-            line: tdp.position.line + 1,
-            column: tdp.position.character as u16 + 1,
-        };
+        let location = self.document_location(&tdp.text_document.uri, file_id, tdp.position);
         let iter = annotations.get_location(location);
-        let mut result = None;
-
-        if_annotation! { Annotation::ProcArguments(priors, proc_name, mut idx) in iter; {
-            // take the specific argument we're working on
-            if_annotation! { Annotation::ProcArgument(i) in iter; {
-                idx = *i;
-            }}
 
-            let mut next = self.find_scoped_type(&iter, priors);
-            while let Some(ty) = next {
-                if let Some(proc) = ty.procs.get(proc_name) {
-                    use std::fmt::Write;
-
-                    let mut params = Vec::new();
-                    let mut label = format!("{}/{}(", ty.path, proc_name);
-                    let mut sep = "";
-                    for param in proc.main_value().parameters.iter() {
-                        for each in param.var_type.type_path.iter() {
-                            let _ = write!(label, "{}{}", sep, each);
-                            sep = "/";
-                        }
-                        label.push_str(sep);
-                        let start = label.len();
-                        label.push_str(&param.name);
-                        let end = label.len();
-                        sep = ", ";
-
-                        if self.client_caps.label_offset_support {
-                            params.push(ParameterInformation {
-                                label: ParameterLabel::LabelOffsets([start as u32, end as u32]),
-                                documentation: None,
-                            });
-                        } else {
-                            params.push(ParameterInformation {
-                                label: ParameterLabel::Simple(param.name.clone()),
-                                documentation: None,
-                            });
-                        }
-                    }
-                    let _ = write!(label, ")");
-
-                    result = Some(SignatureHelp {
-                        active_signature: Some(0),
-                        active_parameter: Some(idx as u32),
-                        signatures: vec![SignatureInformation {
-                            label,
-                            parameters: Some(params),
-                            documentation: None,
-                            active_parameter: None,
-                        }],
-                    });
-                    break;
-                }
-                next = ty.parent_type();
-                if let Some(ref n) = next {
-                    if n.is_root() && !priors.is_empty() {
-                        break;
+        let Some(call) = self.find_call_context(&iter) else {
+            return Ok(None);
+        };
+        let (ty, proc_name, proc, mut idx) = (call.ty, call.proc_name, call.proc, call.argument);
+
+        // a keyword argument selects its parameter no matter its position
+        if let Some(start) = call.argument_start {
+            let contents = self.docs.get_contents(&tdp.text_document.uri).map_err(invalid_request)?;
+            if let Some(start) = document::location_offset(&contents, start.line, start.column) {
+                if let Some(name) = document::keyword_argument_name(&contents[start..]) {
+                    if let Some(i) = proc.parameters.iter().position(|param| param.name == name) {
+                        idx = i;
                     }
                 }
             }
-        }}
-
-        result
-    }
-
-    #[allow(deprecated)]  // DocumentSymbol::deprecated is... deprecated. But we need to provide a `None` anyways.
-    on DocumentSymbolRequest(&mut self, params) {
-        fn name_and_detail(path: &[String], skip_front: usize) -> (String, Option<String>) {
-            let (name, rest) = path.split_last().unwrap();
-            (
-                name.to_owned(),
-                rest.get(skip_front..).and_then(|i|
-                    i.iter().rev().find(|x|
-                        dm::ast::ProcDeclKind::from_name(x).is_none() &&
-                        dm::ast::ProcFlags::from_name(x).is_none() &&
-                        dm::ast::VarTypeFlags::from_name(x).is_none() &&
-                        *x != "var"
-                    ).map(ToOwned::to_owned)
-                )
-            )
         }
 
-        // recursive traversal
-        fn find_document_symbols<'a, I>(
-            iter: &mut std::iter::Peekable<I>,
-            section_end: dm::Location,
-            skip_front: usize,
-        ) -> Vec<DocumentSymbol>
-            where I: Iterator<Item=(interval_tree::RangeInclusive<dm::Location>, &'a Annotation)>
-        {
-            let mut result = Vec::new();
-
-            loop {
-                if let Some((range, _)) = iter.peek() {
-                    if range.start >= section_end {
-                        break;
-                    }
-                }
-
-                let Some((child_range, annotation)) = iter.next() else {
-                    break;
-                };
-
-                let interval_tree::RangeInclusive { start, end } = child_range;
-                let range = span_to_range(start..end);
-                let selection_range = location_to_range(start);
-                match annotation {
-                    Annotation::TreeBlock(ref path) => {
-                        if path.is_empty() { continue }
-                        let (name, detail) = name_and_detail(path, skip_front);
-                        result.push(DocumentSymbol {
-                            name,
-                            detail,
-                            kind: SymbolKind::CLASS,
-                            tags: None,
-                            deprecated: None,
-                            range,
-                            selection_range,
-                            children: Some(find_document_symbols(iter, end, path.len())),
-                        });
-                    },
-                    Annotation::Variable(ref path) => {
-                        let (name, detail) = name_and_detail(path, skip_front);
-                        result.push(DocumentSymbol {
-                            name,
-                            detail,
-                            kind: SymbolKind::FIELD,
-                            tags: None,
-                            deprecated: None,
-                            range,
-                            selection_range,
-                            children: None,
-                        });
-                    },
-                    Annotation::ProcBody(ref path, _) => {
-                        if path.is_empty() { continue }
-                        let (name, detail) = name_and_detail(path, skip_front);
-                        let kind = if path.len() == 1 || (path.len() == 2 && path[0] == "proc") {
-                            SymbolKind::FUNCTION
-                        } else if is_constructor_name(&name) {
-                            SymbolKind::CONSTRUCTOR
-                        } else {
-                            SymbolKind::METHOD
-                        };
-                        result.push(DocumentSymbol {
-                            name,
-                            detail,
-                            kind,
-                            deprecated: None,
-                            tags: None,
-                            range,
-                            selection_range,
-                            children: Some(find_document_symbols(iter, end, 0)),
-                        });
-                    },
-                    Annotation::LocalVarScope(_, ref name) => {
-                        result.push(DocumentSymbol {
-                            name: name.to_owned(),
-                            detail: None,
-                            kind: SymbolKind::VARIABLE,
-                            tags: None,
-                            deprecated: None,
-                            range,
-                            selection_range,
-                            children: None,
-                        });
-                    },
-                    Annotation::MacroDefinition(ref name) => {
-                        result.push(DocumentSymbol {
-                            name: name.to_owned(),
-                            detail: None,
-                            kind: SymbolKind::CONSTANT,
-                            tags: None,
-                            deprecated: None,
-                            range,
-                            selection_range,
-                            children: None,
-                        })
-                    },
-                    _ => {}
-                }
+        use std::fmt::Write;
+
+        let mut params = Vec::new();
+        let mut label = format!("{}/{}(", ty.path, proc_name);
+        let mut sep = "";
+        for param in proc.parameters.iter() {
+            label.push_str(sep);
+            let start = label.len();
+            label.push_str(&completion::parameter_label(param));
+            let end = label.len();
+            sep = ", ";
+
+            if self.client_caps.label_offset_support {
+                params.push(ParameterInformation {
+                    label: ParameterLabel::LabelOffsets([start as u32, end as u32]),
+                    documentation: None,
+                });
+            } else {
+                params.push(ParameterInformation {
+                    label: ParameterLabel::Simple(label[start..end].to_owned()),
+                    documentation: None,
+                });
             }
-
-            result
         }
+        let _ = write!(label, ")");
+        if let Some(return_type) = ty.get_proc(proc_name).and_then(|proc| proc.return_type()) {
+            let _ = write!(label, " as {}", return_type);
+        }
+
+        Some(SignatureHelp {
+            active_signature: Some(0),
+            active_parameter: Some(idx as u32),
+            signatures: vec![SignatureInformation {
+                label,
+                parameters: Some(params),
+                documentation: None,
+                active_parameter: None,
+            }],
+        })
+    }
 
-        // root
+    on DocumentSymbolRequest(&mut self, params) {
         let (_, file_id, annotations) = self.get_annotations(&params.text_document.uri)?;
         if annotations.is_empty() {
             None
         } else {
-            let start = dm::Location { file: file_id, line: 0, column: 0 };
-            let end = dm::Location { file: file_id, line: !0, column: !0 };
-            let mut vec: Vec<_> = annotations.get_range(start..end).collect();
-            // Our traversal is outside-in. TreeBlock and Variable can have the
-            // same extent if the variable is the only thing in the block, so
-            // sort TreeBlocks first as well.
-            vec.sort_by_key(|x| (x.0.start, std::cmp::Reverse(x.0.end), if matches!(x.1, Annotation::TreeBlock(_)) { 0 } else { 1 }));
-            let mut iter = vec.into_iter().peekable();
-            Some(DocumentSymbolResponse::Nested(find_document_symbols(&mut iter, end, 0)))
+            let contents = self.docs.get_contents(&params.text_document.uri).map_err(invalid_request)?;
+            Some(DocumentSymbolResponse::Nested(document_symbols::document_symbols(&annotations, file_id, &contents)))
         }
     }
 
+    on FoldingRangeRequest(&mut self, params) {
+        let (_, file_id, annotations) = self.get_annotations(&params.text_document.uri)?;
+        let contents = self.docs.get_contents(&params.text_document.uri).map_err(invalid_request)?;
+        Some(folding::folding_ranges(&annotations, file_id, &contents))
+    }
+
+    on SelectionRangeRequest(&mut self, params) {
+        let (_, file_id, annotations) = self.get_annotations(&params.text_document.uri)?;
+        let contents = self.docs.get_contents(&params.text_document.uri).map_err(invalid_request)?;
+        Some(selection::selection_ranges(&annotations, file_id, &contents, &params.positions))
+    }
+
     on DocumentColor(&mut self, params) {
+        let (real_file_id, file_id, annotations) = self.get_annotations(&params.text_document.uri)?;
         let content = self.docs.get_contents(&params.text_document.uri).map_err(invalid_request)?;
+        let mut colors: Vec<(usize, usize, [u8; 4])> = color::extract_colors(&content).collect();
+
+        // rgb() calls with constant arguments the regex couldn't read
+        for (start, end) in color::find_calls(&content) {
+            if colors.iter().any(|&(other_start, other_end, _)| start < other_end && other_start < end) {
+                continue;
+            }
+            let (line, column) = document::offset_line_column(&content, start);
+            let location = dm::Location { file: real_file_id, line, column };
+            // rgba() is accepted as a spelling of rgb()
+            let source = content[start..end].replacen("rgba", "rgb", 1);
+            let folded = dm::constants::evaluate_str(location, source.as_bytes()).ok()
+                .or_else(|| self.fold_at(&source, location));
+            if let Some(rgba) = folded.as_ref().and_then(color::constant_color) {
+                colors.push((start, end, rgba));
+            }
+        }
+
         let mut output = Vec::new();
-        for (start, end, [r, g, b, a]) in color::extract_colors(&content) {
+        for (start, end, [r, g, b, a]) in colors {
             output.push(ColorInformation {
                 range: Range {
                     start: document::offset_to_position(&content, start),
@@ -1954,54 +4202,150 @@ handle_method_call! {
                 },
             });
         }
+
+        // color macros show their swatch where they are used
+        let mut seen = std::collections::BTreeMap::new();
+        for (span, annotation) in annotations.iter() {
+            let Annotation::MacroUse { name, definition_location, .. } = annotation else {
+                continue;
+            };
+            let found = *seen.entry((name, *definition_location))
+                .or_insert_with(|| self.macro_color(name, *definition_location, file_id, real_file_id));
+            if let Some((_, [r, g, b, a])) = found {
+                output.push(ColorInformation {
+                    range: self.span_to_range(span.start..span.end.add_columns(1)),
+                    color: Color {
+                        red: (r as f32) / 255.,
+                        green: (g as f32) / 255.,
+                        blue: (b as f32) / 255.,
+                        alpha: (a as f32) / 255.,
+                    },
+                });
+            }
+        }
         output
     }
 
     on ColorPresentationRequest(&mut self, params) {
+        let (real_file_id, file_id, annotations) = self.get_annotations(&params.text_document.uri)?;
         let content = self.docs.get_contents(&params.text_document.uri).map_err(invalid_request)?;
         let chunk = document::get_range(&content, params.range)?;
+        let rgba = [
+            (params.color.red * 255.).round() as u8,
+            (params.color.green * 255.).round() as u8,
+            (params.color.blue * 255.).round() as u8,
+            (params.color.alpha * 255.).round() as u8,
+        ];
+        let start = self.document_location(&params.text_document.uri, file_id, params.range.start);
+
+        // Macro uses can't be rewritten without corrupting the expansion, so
+        // edit the define instead if it's in this file, or refuse.
+        let macro_use = annotations.get_location(start).find_map(|(span, annotation)| match annotation {
+            Annotation::MacroUse { name, definition_location, .. } if span.start == start => Some((name, *definition_location)),
+            _ => None,
+        });
+        if let Some((name, definition_location)) = macro_use {
+            let Some((define_location, _)) = self.macro_color(name, definition_location, file_id, real_file_id) else {
+                return Ok(Vec::new());
+            };
+            if define_location.file != real_file_id {
+                return Ok(Vec::new());
+            }
+            let line_start = document::total_offset(&content, define_location.line - 1, 0)?;
+            let line = content[line_start..].lines().next().unwrap_or("");
+            let Some((start, end, _)) = color::extract_colors(line).next() else {
+                return Ok(Vec::new());
+            };
+            let format = color::ColorFormat::parse(&line[start..end]).unwrap_or_default();
+            return Ok(vec![ColorPresentation {
+                label: format.format(rgba),
+                text_edit: Some(TextEdit::new(params.range, chunk.to_owned())),
+                additional_text_edits: Some(vec![TextEdit::new(
+                    Range::new(
+                        document::offset_to_position(&content, line_start + start),
+                        document::offset_to_position(&content, line_start + end),
+                    ),
+                    format.format(rgba),
+                )]),
+            }]);
+        }
+
         let color_format = color::ColorFormat::parse(chunk).unwrap_or_default();
-        // TODO: return compatible alternate presentations for converting
-        // between "#..." and rgb().
-        vec![
-            ColorPresentation {
-                label: color_format.format([
-                    (params.color.red * 255.).round() as u8,
-                    (params.color.green * 255.).round() as u8,
-                    (params.color.blue * 255.).round() as u8,
-                    (params.color.alpha * 255.).round() as u8,
-                ]),
+        let mut output = vec![ColorPresentation {
+            label: color_format.format(rgba),
+            .. Default::default()
+        }];
+        // Only offer switching between "#..." and rgb() for code, not for
+        // colors in HTML inside strings.
+        let in_code = annotations.get_location(start).any(|(span, annotation)| {
+            span.start == start && matches!(annotation, Annotation::Expression | Annotation::UnscopedCall(_))
+        });
+        if in_code && !matches!(color_format, color::ColorFormat::Hex { single_quoted: true, .. }) {
+            output.push(ColorPresentation {
+                label: color_format.alternate().format(rgba),
                 .. Default::default()
-            },
-        ]
+            });
+        }
+        output
     }
 
     on DocumentLinkRequest(&mut self, params) {
-        let (_, file_id, annotations) = self.get_annotations(&params.text_document.uri)?;
+        let (real_file_id, file_id, annotations) = self.get_annotations(&params.text_document.uri)?;
         if annotations.is_empty() {
             None
         } else {
-            let mut results = Vec::new();
+            // Resources are resolved the same way the compiler would.
+            let file_path = url_to_path(&params.text_document.uri)?;
+            let (env_file, file_dirs) = match self.defines {
+                Some(ref defines) => (defines.env_file().to_owned(), defines.file_dirs()),
+                None => (file_path.clone(), Vec::new()),
+            };
+            let env_dir = env_file.parent().unwrap_or(Path::new("")).to_owned();
+
+            // Includes are taken from what the preprocessor actually found.
+            let mut targets = Vec::new();
+            for edge in self.context.include_graph().includes_from(real_file_id) {
+                // The path's span includes its quotes.
+                let span = edge.location .. edge.location.add_columns(2 + edge.written.len() as u16);
+                let target = edge.path.as_ref().map(|path| env_dir.join(path)).ok_or_else(|| PathBuf::from(&edge.written));
+                targets.push((span, target));
+            }
             for (span, annotation) in annotations.iter() {
                 if span.start.file != file_id {
                     continue;
                 }
-                match annotation {
-                    Annotation::Include(path) |
-                    Annotation::Resource(path) => {
+                if let Annotation::Resource(path) = annotation {
+                    let target = dm::preprocessor::resolve_resource(&file_path, &env_file, &file_dirs, &path.to_string_lossy())
+                        .ok_or_else(|| path.clone());
+                    targets.push((span.start .. span.end, target));
+                }
+            }
+
+            let mut results = Vec::new();
+            for (span, target) in targets {
+                let range = self.span_to_range(span.start..span.end.add_columns(1));
+                match target {
+                    Ok(path) => {
                         let pathbuf = if path.is_relative() {
                             std::env::current_dir().map_err(invalid_request)?.join(path)
                         } else {
-                            path.to_owned()
+                            path
                         };
                         results.push(DocumentLink {
-                            range: span_to_range(span.start..span.end.add_columns(1)),
+                            range,
                             target: Some(path_to_url(pathbuf)?),
                             tooltip: None,
                             data: None,
                         });
                     }
-                    _ => {}
+                    // Left for DocumentLinkResolve to report.
+                    Err(path) if self.link_missing_files => results.push(DocumentLink {
+                        range,
+                        target: None,
+                        tooltip: Some(format!("{} was not found", path.display())),
+                        data: Some(serde_json::json!({ "missing": path })),
+                    }),
+                    Err(_) => {}
                 }
             }
 
@@ -2009,6 +4353,68 @@ handle_method_call! {
         }
     }
 
+    on DocumentLinkResolve(&mut self, link) {
+        if let Some(missing) = link.data.as_ref().and_then(|data| data["missing"].as_str()) {
+            return Err(invalid_request(format!("file not found: {}", missing)));
+        }
+        link
+    }
+
+    // ------------------------------------------------------------------------
+    // object tree sidebar
+    on Status(&mut self, _params) {
+        self.status_report()
+    }
+
+    on ProcMetrics(&mut self, params) {
+        let sort = params.sort.as_deref().unwrap_or("complexity");
+        if !dreamchecker::metrics::COLUMNS.contains(&sort) {
+            return Err(invalid_request(format!("unknown metric: {}", sort)));
+        }
+        let file = match params.text_document {
+            Some(ref doc) => Some(self.get_annotations(&doc.uri)?.0),
+            None => None,
+        };
+        let mut metrics = dreamchecker::metrics::proc_metrics(&self.objtree);
+        metrics.retain(|each| file.is_none_or(|file| each.location.file == file));
+        dreamchecker::metrics::sort_metrics(&mut metrics, sort);
+        metrics.truncate(params.limit.unwrap_or(usize::MAX));
+        let mut results = Vec::with_capacity(metrics.len());
+        for each in metrics {
+            results.push(extras::ProcMetricsEntry {
+                location: lsp_types::Location {
+                    uri: self.file_url(each.location.file)?,
+                    range: self.location_to_range(each.location),
+                },
+                path: each.path,
+                complexity: each.complexity,
+                statements: each.statements,
+                nesting: each.nesting,
+                parameters: each.parameters,
+            });
+        }
+        results
+    }
+
+    on ReparseEnvironment(&mut self, _params) {
+        eprintln!();
+        eprintln!("reparsing by request...");
+        let active = self.active;
+        self.reload_all();
+        self.activate(active);
+        self.status_report()
+    }
+
+    on ObjectTreeRoot(&mut self, params) {
+        self.object_tree_requested = true;
+        self.object_tree_node("/", 0, params.limit)
+    }
+
+    on ObjectTreeExpand(&mut self, params) {
+        self.object_tree_requested = true;
+        self.object_tree_node(&params.path, params.offset.unwrap_or(0), params.limit)
+    }
+
     // ------------------------------------------------------------------------
     // debugger entry point
     on StartDebugger(&mut self, params) {
@@ -2033,32 +4439,28 @@ handle_notification! {
     // ------------------------------------------------------------------------
     // basic setup
     on Initialized(&mut self, _) {
-        let mut environment = None;
-        if let Some(ref root) = self.root {
-            // TODO: support non-files here
-            if let Ok(root_path) = url_to_path(root) {
-                if let Some(dme) = self.context.config().environment.as_ref() {
-                    environment = Some(root_path.join(dme));
-                } else {
-                    environment = dm::detect_environment(&root_path, dm::DEFAULT_ENV).map_err(invalid_request)?;
-                }
-            }
-        }
-
-        if let Some(environment) = environment {
-            self.parse_environment(environment)?;
-        } else if self.root.is_some() {
-            self.show_status("no .dme file");
-        } else {
-            self.show_status("single file mode");
-        }
+        self.request_configuration();
+        self.reload_all();
     }
 
     on Reparse(&mut self, _p) {
         eprintln!();
         eprintln!("reparsing by request...");
-        self.context.errors_mut().clear();
-        return self.Initialized(_p);
+        self.reload_all();
+    }
+
+    on DidChangeWorkspaceFolders(&mut self, params) {
+        for folder in params.event.removed {
+            self.remove_workspace(&folder.uri);
+        }
+        for folder in params.event.added {
+            self.add_workspace(folder.name, folder.uri);
+            if self.environments_loaded {
+                self.reload()?;
+            }
+        }
+        self.request_configuration();
+        self.refresh_orphans();
     }
 
     on Cancel(&mut self, _) { /* Not implemented, but don't log that. */ }
@@ -2066,17 +4468,74 @@ handle_notification! {
     // ------------------------------------------------------------------------
     // document content management
     on DidOpenTextDocument(&mut self, params) {
+        let url = params.text_document.uri.clone();
         self.docs.open(params.text_document)?;
+        self.line_indexes.forget(&url);
+        if self.environments_loaded {
+            self.check_included(&url);
+        }
     }
 
     on DidCloseTextDocument(&mut self, params) {
         let url = self.docs.close(params.text_document)?;
         self.annotations.remove(&url);
+        self.line_indexes.forget(&url);
+        if self.orphans.remove(&url) {
+            self.clear_orphan(&url);
+        }
     }
 
     on DidChangeTextDocument(&mut self, params) {
         let url = self.docs.change(params.text_document, params.content_changes)?;
         self.annotations.remove(&url);
+        self.line_indexes.forget(&url);
+        if self.live_diagnostics && self.defines.is_some() {
+            self.live.schedule(url);
+        }
+    }
+
+    on DidSaveTextDocument(&mut self, _params) {
+        // Replace any interim diagnostics with those of a full rebuild.
+        if self.live.take_stale() {
+            eprintln!();
+            eprintln!("reparsing after save...");
+            self.reload()?;
+            self.refresh_orphans();
+        }
+    }
+
+    on DidChangeWatchedFiles(&mut self, params) {
+        for event in params.changes {
+            let Some(index) = self.workspace_for(&event.uri) else { continue };
+            self.activate(index);
+            let Some(root) = self.root.as_ref().and_then(|root| url_to_path(root).ok()) else { continue };
+            let Ok(path) = url_to_path(&event.uri) else { continue };
+            let Ok(relative) = path.strip_prefix(&root) else { continue };
+            if watch::is_config(relative) {
+                eprintln!();
+                eprintln!("reparsing after {} changed...", relative.display());
+                self.context.reset_config();
+                self.load_config();
+                self.reload()?;
+                continue;
+            }
+            if !watch::is_watched(relative) {
+                continue;
+            }
+            // New files may be included by the next build, and any .dme may
+            // be the environment. Otherwise, only the environment's files
+            // matter.
+            let relevant = event.typ == FileChangeType::CREATED
+                || relative.extension().is_some_and(|ext| ext == "dme")
+                || self.context.get_file(relative).is_some();
+            // Saves from the editor are handled on save.
+            let saved = event.typ == FileChangeType::CHANGED
+                && self.docs.open_urls().any(|url| *url == event.uri)
+                && !self.docs.is_modified(&event.uri);
+            if relevant && !saved {
+                self.watcher.record(relative.to_owned(), event.typ);
+            }
+        }
     }
 
     on DidChangeConfiguration(&mut self, params) {
@@ -2086,6 +4545,35 @@ handle_notification! {
         if let Some(debug_server_dll) = params.settings["dreammaker"]["debugServerDll"].as_str() {
             self.debug_server_dll = Some(debug_server_dll.to_owned());
         }
+        self.inlay_hints.update(&params.settings["dreammaker"]["inlayHints"]);
+        if let Some(code_lens) = params.settings["dreammaker"]["codeLens"].as_bool() {
+            self.code_lens = code_lens;
+        }
+        if let Some(link_missing_files) = params.settings["dreammaker"]["linkMissingFiles"].as_bool() {
+            self.link_missing_files = link_missing_files;
+        }
+        if let Some(live_diagnostics) = params.settings["dreammaker"]["liveDiagnostics"].as_bool() {
+            self.live_diagnostics = live_diagnostics;
+        }
+        if let Some(format_on_type) = params.settings["dreammaker"]["formatOnType"].as_bool() {
+            self.format_on_type = format_on_type;
+        }
+        self.formatting.update(&params.settings["dreammaker"]["formatting"]);
+        if let Some(environment) = params.settings["dreammaker"].get("environment") {
+            self.environment_setting.update(environment);
+            self.reload_changed();
+        }
+        if let Some(defines) = params.settings["dreammaker"].get("defines") {
+            let defines = workspaces::define_setting(defines);
+            if defines != self.define_setting {
+                self.define_setting = defines;
+                self.reload_defines();
+            }
+        }
+        // Settings scoped to a folder are only available by asking.
+        if self.environments_loaded {
+            self.request_configuration();
+        }
     }
 }
 
@@ -2109,6 +4597,16 @@ fn value_to_params(value: serde_json::Value) -> jsonrpc::Params {
     }
 }
 
+/// The error for a request which the client cancelled while it was being
+/// handled, rather than a partial result.
+fn request_cancelled() -> jsonrpc::Error {
+    jsonrpc::Error {
+        code: jsonrpc::ErrorCode::ServerError(-32800),
+        message: "request cancelled".to_owned(),
+        data: None,
+    }
+}
+
 fn invalid_request<S: ToString>(message: S) -> jsonrpc::Error {
     jsonrpc::Error {
         code: jsonrpc::ErrorCode::InvalidRequest,
@@ -2140,8 +4638,21 @@ fn convert_severity(severity: dm::Severity) -> lsp_types::DiagnosticSeverity {
     }
 }
 
+fn report_stale_baseline(stale: &[dm::baseline::BaselineEntry]) {
+    if !stale.is_empty() {
+        eprintln!("{} baseline entries no longer match; regenerate the baseline to trim them", stale.len());
+    }
+}
+
 fn convert_errorcode(errortype: Option<&'static str>) -> Option<lsp_types::NumberOrString> {
-    errortype.map(|x| lsp_types::NumberOrString::String(x.to_owned()))
+    errortype.map(|x| lsp_types::NumberOrString::String(dm::codes::code_for(x).unwrap_or(x).to_owned()))
+}
+
+fn convert_tags(errortype: Option<&'static str>) -> Option<Vec<lsp_types::DiagnosticTag>> {
+    match errortype {
+        Some("deprecated") => Some(vec![lsp_types::DiagnosticTag::DEPRECATED]),
+        _ => None,
+    }
 }
 
 enum UnscopedVar<'a> {
@@ -2161,24 +4672,34 @@ enum UnscopedVar<'a> {
     None,
 }
 
-fn is_constructor_name(name: &str) -> bool {
-    name == "New" || name == "init" || name == "Initialize"
+/// What an identifier in the body of a macro most likely refers to.
+enum MacroBodySymbol<'a> {
+    Parameter {
+        define: &'a str,
+        params: &'a [String],
+        loc: dm::Location,
+    },
+    Macro(dm::Location),
+    Type(TypeRef<'a>),
+    Proc(TypeRef<'a>),
+    Var(TypeRef<'a>),
+    None,
 }
 
-fn location_to_position(loc: dm::Location) -> lsp_types::Position  {
-    lsp_types::Position {
-        line: loc.line.saturating_sub(1),
-        character: loc.column.saturating_sub(1) as u32,
+/// Format a folded constant for hovers, noting the normalized form of colors.
+fn constant_hover(constant: &dm::constants::Constant) -> String {
+    match constant.as_str().and_then(color::normalize) {
+        Some(color) => format!("```dm\n= {}\n```\n`{}`", constant, color),
+        None => format!("```dm\n= {}\n```", constant),
     }
 }
 
-fn location_to_range(loc: dm::Location) -> lsp_types::Range {
-    let pos = location_to_position(loc);
-    lsp_types::Range::new(pos, pos)
+fn is_map(url: &Url) -> bool {
+    url.path().ends_with(".dmm")
 }
 
-fn span_to_range(range: std::ops::Range<dm::Location>) -> lsp_types::Range {
-    lsp_types::Range::new(location_to_position(range.start), location_to_position(range.end))
+fn is_constructor_name(name: &str) -> bool {
+    name == "New" || name == "init" || name == "Initialize"
 }
 
 fn issue_notification<T>(params: T::Params)
@@ -2195,6 +4716,26 @@ where
     jrpc_io::write(&serde_json::to_string(&request).expect("notification bad to_string"))
 }
 
+/// Send a request to the client. Its response is passed to
+/// `Engine::handle_response`.
+fn issue_request<T>(params: T::Params) -> jsonrpc::Id
+where
+    T: lsp_types::request::Request,
+    T::Params: serde::Serialize,
+{
+    static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let params = serde_json::to_value(params).expect("request bad to_value");
+    let id = jsonrpc::Id::Str(format!("dm-langserver/{}", NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed)));
+    let request = Request::Single(Call::MethodCall(jsonrpc::MethodCall {
+        jsonrpc: VERSION,
+        method: T::METHOD.to_owned(),
+        params: value_to_params(params),
+        id: id.clone(),
+    }));
+    jrpc_io::write(&serde_json::to_string(&request).expect("request bad to_string"));
+    id
+}
+
 fn component_to_source(component: dm::Component) -> Option<String> {
     Some(component.name().unwrap_or("dm-langserver").to_owned())
 }
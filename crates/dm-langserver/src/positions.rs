@@ -0,0 +1,225 @@
+//! Conversion between `dm::Location` columns, which count bytes from one, and
+//! LSP positions, which count UTF-16 code units from zero unless the client
+//! agreed to UTF-8.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use foldhash::HashMap;
+use lsp_types::{Position, PositionEncodingKind, Range};
+use url::Url;
+
+/// Whether UTF-8 was negotiated, which applies to the whole connection.
+static UTF8: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf16,
+    Utf8,
+}
+
+impl Encoding {
+    /// The encoding negotiated with the client.
+    pub fn current() -> Encoding {
+        if UTF8.load(Ordering::Relaxed) {
+            Encoding::Utf8
+        } else {
+            Encoding::Utf16
+        }
+    }
+
+    /// Prefer UTF-8 if the client offers it, as that is what we count in.
+    pub fn negotiate(offered: Option<&[PositionEncodingKind]>) -> PositionEncodingKind {
+        let utf8 = offered.is_some_and(|offered| offered.contains(&PositionEncodingKind::UTF8));
+        UTF8.store(utf8, Ordering::Relaxed);
+        if utf8 {
+            PositionEncodingKind::UTF8
+        } else {
+            PositionEncodingKind::UTF16
+        }
+    }
+
+    pub fn char_len(self, ch: char) -> u32 {
+        match self {
+            Encoding::Utf16 => ch.len_utf16() as u32,
+            Encoding::Utf8 => ch.len_utf8() as u32,
+        }
+    }
+
+    pub fn len(self, text: &str) -> u32 {
+        match self {
+            Encoding::Utf16 => text.chars().map(char::len_utf16).sum::<usize>() as u32,
+            Encoding::Utf8 => text.len() as u32,
+        }
+    }
+}
+
+/// Count the units before a byte offset into a line. Offsets past the end,
+/// like the newline, count one unit per byte.
+pub fn units_before(line: &str, byte: usize, encoding: Encoding) -> u32 {
+    let mut units = 0;
+    for (idx, ch) in line.char_indices() {
+        if idx >= byte {
+            return units;
+        }
+        units += encoding.char_len(ch);
+    }
+    units + byte.saturating_sub(line.len()) as u32
+}
+
+/// Find the byte offset into a line at a number of units. A position inside
+/// a character lands after it.
+pub fn byte_at(line: &str, mut units: u32, encoding: Encoding) -> usize {
+    for (idx, ch) in line.char_indices() {
+        if units == 0 {
+            return idx;
+        }
+        units = units.saturating_sub(encoding.char_len(ch));
+    }
+    line.len() + units as usize
+}
+
+/// The lines of one file which contain non-ASCII text, the only ones where
+/// byte columns and positions differ.
+#[derive(Debug)]
+pub struct LineIndex {
+    encoding: Encoding,
+    /// By one-based line number.
+    wide: HashMap<u32, Box<str>>,
+}
+
+impl Default for LineIndex {
+    fn default() -> LineIndex {
+        LineIndex::with_encoding("", Encoding::current())
+    }
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> LineIndex {
+        LineIndex::with_encoding(text, Encoding::current())
+    }
+
+    pub fn with_encoding(text: &str, encoding: Encoding) -> LineIndex {
+        let wide = text.split('\n')
+            .enumerate()
+            .filter(|(_, line)| !line.is_ascii())
+            .map(|(idx, line)| (idx as u32 + 1, line.into()))
+            .collect();
+        LineIndex { encoding, wide }
+    }
+
+    pub fn position(&self, location: dm::Location) -> Position {
+        let column = location.column.saturating_sub(1) as usize;
+        Position {
+            line: location.line.saturating_sub(1),
+            character: match self.wide.get(&location.line) {
+                Some(line) => units_before(line, column, self.encoding),
+                None => column as u32,
+            },
+        }
+    }
+
+    pub fn range(&self, location: dm::Location) -> Range {
+        let position = self.position(location);
+        Range::new(position, position)
+    }
+
+    pub fn span(&self, span: std::ops::Range<dm::Location>) -> Range {
+        Range::new(self.position(span.start), self.position(span.end))
+    }
+
+    /// The location of a position, in the given file.
+    pub fn location(&self, file: dm::FileId, position: Position) -> dm::Location {
+        let line = position.line + 1;
+        let column = match self.wide.get(&line) {
+            Some(text) => byte_at(text, position.character, self.encoding),
+            None => position.character as usize,
+        };
+        dm::Location {
+            file,
+            line,
+            column: (column + 1).min(u16::MAX as usize) as u16,
+        }
+    }
+}
+
+/// Line indexes of the files results point into, kept until they change.
+#[derive(Default)]
+pub struct LineIndexes {
+    files: RefCell<HashMap<Url, Rc<LineIndex>>>,
+}
+
+impl LineIndexes {
+    pub fn get(&self, url: &Url, load: impl FnOnce() -> Option<String>) -> Rc<LineIndex> {
+        if let Some(index) = self.files.borrow().get(url) {
+            return index.clone();
+        }
+        let index = Rc::new(load().map_or_else(LineIndex::default, |text| LineIndex::new(&text)));
+        self.files.borrow_mut().insert(url.clone(), index.clone());
+        index
+    }
+
+    /// The line index of a file on disk, for when no documents are at hand.
+    pub fn read(&self, url: &Url) -> Rc<LineIndex> {
+        self.get(url, || std::fs::read_to_string(crate::url_to_path(url).ok()?).ok())
+    }
+
+    pub fn forget(&self, url: &Url) {
+        self.files.borrow_mut().remove(url);
+    }
+
+    pub fn clear(&self) {
+        self.files.borrow_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "/obj/item\n\tname = \"меч\" // 剣 🗡\n\tdesc = \"sharp\"\n";
+
+    #[test]
+    fn mixed_width_columns() {
+        let line = "\tname = \"меч\" // 剣 🗡";
+        // The closing quote follows three two-byte letters.
+        let quote = line.rfind('"').unwrap();
+        assert_eq!(quote, 15);
+        assert_eq!(units_before(line, quote, Encoding::Utf16), 12);
+        assert_eq!(units_before(line, quote, Encoding::Utf8), 15);
+
+        // The sword takes two UTF-16 units.
+        let sword = line.find('🗡').unwrap();
+        assert_eq!(units_before(line, sword, Encoding::Utf16), 19);
+        assert_eq!(units_before(line, line.len(), Encoding::Utf16), 21);
+        assert_eq!(units_before(line, line.len() + 1, Encoding::Utf16), 22);
+
+        // A position between the halves of a surrogate pair lands after it.
+        assert_eq!(byte_at(line, 20, Encoding::Utf16), line.len());
+        assert_eq!(byte_at(line, 12, Encoding::Utf16), quote);
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = dm::FileId::default();
+        for encoding in [Encoding::Utf16, Encoding::Utf8] {
+            let index = LineIndex::with_encoding(TEXT, encoding);
+            for (line_number, line) in TEXT.split('\n').enumerate() {
+                for (byte, _) in line.char_indices().chain(Some((line.len(), ' '))) {
+                    let location = dm::Location { file, line: line_number as u32 + 1, column: byte as u16 + 1 };
+                    let position = index.position(location);
+                    assert_eq!(position.line, line_number as u32);
+                    assert_eq!(index.location(file, position), location, "{:?} at {:?}", encoding, position);
+                }
+            }
+        }
+
+        let index = LineIndex::with_encoding(TEXT, Encoding::Utf16);
+        // ASCII lines are left alone.
+        let desc = dm::Location { file, line: 3, column: 9 };
+        assert_eq!(index.position(desc), Position::new(2, 8));
+        let comment = dm::Location { file, line: 2, column: 18 };
+        assert_eq!(index.position(comment), Position::new(1, 14));
+    }
+}
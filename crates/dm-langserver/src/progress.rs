@@ -0,0 +1,73 @@
+//! `window/workDoneProgress` reporting for long-running work.
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use lsp_types::notification::Progress;
+use lsp_types::request::WorkDoneProgressCreate;
+use lsp_types::*;
+
+/// The least time between two reports on the same progress.
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+static NEXT_TOKEN: AtomicU32 = AtomicU32::new(0);
+
+/// An ongoing progress, which may be moved to the thread doing the work.
+pub struct WorkDone {
+    token: ProgressToken,
+    last_report: Option<Instant>,
+}
+
+impl WorkDone {
+    /// Create a progress token on the client and begin reporting on it, if
+    /// the client supports it.
+    pub fn begin(supported: bool, title: &str, message: Option<String>) -> Option<WorkDone> {
+        if !supported {
+            return None;
+        }
+        let token = NumberOrString::String(format!("dreammaker/{}", NEXT_TOKEN.fetch_add(1, Ordering::Relaxed)));
+        crate::issue_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+            token: token.clone(),
+        });
+        let this = WorkDone { token, last_report: None };
+        this.send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: title.to_owned(),
+            cancellable: Some(false),
+            message,
+            percentage: Some(0),
+        }));
+        Some(this)
+    }
+
+    /// Report a new phase of the work, always sent.
+    pub fn phase(&mut self, message: &str, percentage: u32) {
+        self.last_report = Some(Instant::now());
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            cancellable: None,
+            message: Some(message.to_owned()),
+            percentage: Some(percentage.min(100)),
+        }));
+    }
+
+    /// Report the count of items done within a phase, if it has been long
+    /// enough since the last report.
+    pub fn count(&mut self, message: impl FnOnce() -> String, done: usize, total: usize, range: std::ops::Range<u32>) {
+        if self.last_report.is_some_and(|last| last.elapsed() < REPORT_INTERVAL) {
+            return;
+        }
+        let fraction = if total == 0 { 0 } else { done.min(total) as u64 * 1000 / total as u64 };
+        let percentage = range.start + ((range.end - range.start) as u64 * fraction / 1000) as u32;
+        self.phase(&message(), percentage);
+    }
+
+    pub fn end(self, message: Option<String>) {
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd { message }));
+    }
+
+    fn send(&self, value: WorkDoneProgress) {
+        crate::issue_notification::<Progress>(ProgressParams {
+            token: self.token.clone(),
+            value: ProgressParamsValue::WorkDone(value),
+        });
+    }
+}
@@ -0,0 +1,296 @@
+//! Selection ranges for "expand selection", walking outward from the
+//! identifier under the cursor through expressions, statements, blocks, procs,
+//! and types.
+
+use std::collections::HashMap;
+
+use lsp_types::{Position, Range, SelectionRange};
+
+use dm::annotation::{Annotation, AnnotationTree};
+use dm::{FileId, Location};
+
+use crate::positions::{byte_at, units_before, Encoding};
+
+/// A span in zero-based lines and byte columns, with an exclusive end.
+type Span = ((u32, u32), (u32, u32));
+
+/// Compute the selection range hierarchy at each of `positions`.
+pub fn selection_ranges(annotations: &AnnotationTree, file_id: FileId, text: &str, positions: &[Position]) -> Vec<SelectionRange> {
+    let lines: Vec<&str> = text.lines().collect();
+    let outlines = outline_spans(annotations, file_id, &lines);
+
+    let encoding = Encoding::current();
+    let line_text = |line: u32| lines.get(line as usize).copied().unwrap_or("");
+    let to_position = |(line, column): (u32, u32)| Position::new(line, units_before(line_text(line), column as usize, encoding));
+
+    positions.iter().map(|&position| {
+        let cursor = (position.line, byte_at(line_text(position.line), position.character, encoding) as u32);
+        let location = Location {
+            file: file_id,
+            line: position.line + 1,
+            column: cursor.1 as u16 + 1,
+        };
+
+        let mut spans: Vec<Span> = word_at(&lines, cursor).into_iter().collect();
+        for (range, annotation) in annotations.get_location(location) {
+            if is_syntactic(annotation) {
+                spans.push(trim(&lines, range.start, range.end));
+            }
+        }
+        spans.extend(outlines.iter().filter(|span| contains(span, cursor)));
+
+        let mut result = None;
+        for span in nested(&lines, spans).into_iter().rev() {
+            result = Some(SelectionRange {
+                range: Range::new(to_position(span.0), to_position(span.1)),
+                parent: result.map(Box::new),
+            });
+        }
+        result.unwrap_or(SelectionRange {
+            range: Range::new(position, position),
+            parent: None,
+        })
+    }).collect()
+}
+
+fn is_syntactic(annotation: &Annotation) -> bool {
+    matches!(annotation,
+        Annotation::TreePath(..)
+        | Annotation::TypePath(_)
+        | Annotation::Variable(_)
+        | Annotation::Statement
+        | Annotation::StatementBlock
+        | Annotation::Expression
+        | Annotation::UnscopedCall(_)
+        | Annotation::UnscopedVar(_)
+        | Annotation::ScopedCall(..)
+        | Annotation::ScopedVar(..)
+        | Annotation::ParentCall
+        | Annotation::ReturnVal
+        | Annotation::InSequence(_))
+}
+
+/// Spans of whole procs and types, which the parser records in two parts.
+fn outline_spans(annotations: &AnnotationTree, file_id: FileId, lines: &[&str]) -> Vec<Span> {
+    let start = Location { file: file_id, line: 0, column: 0 };
+    let end = Location { file: file_id, line: !0, column: !0 };
+    let mut all: Vec<_> = annotations.get_range(start..end)
+        .map(|(range, annotation)| (range.start, range.end, annotation))
+        .collect();
+    all.sort_by_key(|&(start, _, _)| start);
+
+    let mut bodies = HashMap::new();
+    for &(_, end, annotation) in all.iter() {
+        if let Annotation::ProcBody(path, idx) = annotation {
+            bodies.insert((path, idx), end);
+        }
+    }
+
+    let tree_paths: Vec<Location> = all.iter()
+        .filter(|(_, _, annotation)| matches!(annotation, Annotation::TreePath(..)))
+        .map(|&(start, _, _)| start)
+        .collect();
+
+    let mut output = Vec::new();
+    for &(start, end, annotation) in all.iter() {
+        match annotation {
+            // A type block's span starts at its first entry, so extend it
+            // back to the path that opened it.
+            Annotation::TreeBlock(_) => {
+                let header = tree_paths.partition_point(|&path_start| path_start < start);
+                let header_start = header.checked_sub(1).map_or(start, |i| tree_paths[i]);
+                output.push(trim(lines, header_start, end));
+            },
+            Annotation::ProcHeader(path, idx) => {
+                if let Some(&body_end) = bodies.get(&(path, idx)) {
+                    output.push(trim(lines, start, body_end));
+                }
+            },
+            _ => {}
+        }
+    }
+    output
+}
+
+/// Convert an annotation's inclusive range to a span, dropping the
+/// surrounding whitespace which spans bounded by neighbouring tokens pick up.
+fn trim(lines: &[&str], start: Location, end: Location) -> Span {
+    let line_bytes = |line: u32| lines.get(line as usize).map_or(&[][..], |text| text.as_bytes());
+
+    let mut start = (start.line.saturating_sub(1), start.column.saturating_sub(1) as usize);
+    let mut end = (end.line.saturating_sub(1), end.column as usize);
+    loop {
+        let text = line_bytes(start.0);
+        start.1 = start.1.min(text.len());
+        while start.1 < text.len() && text[start.1].is_ascii_whitespace() {
+            start.1 += 1;
+        }
+        if start.1 < text.len() || start.0 >= end.0 {
+            break;
+        }
+        start = (start.0 + 1, 0);
+    }
+    loop {
+        let text = line_bytes(end.0);
+        let floor = if end.0 == start.0 { start.1 } else { 0 };
+        end.1 = end.1.min(text.len());
+        while end.1 > floor && text[end.1 - 1].is_ascii_whitespace() {
+            end.1 -= 1;
+        }
+        if end.1 > floor || end.0 <= start.0 {
+            break;
+        }
+        end = (end.0 - 1, usize::MAX);
+    }
+    let start = (start.0, start.1 as u32);
+    (start, (end.0, end.1 as u32).max(start))
+}
+
+/// The span of the identifier touching the cursor, if any.
+fn word_at(lines: &[&str], (line, column): (u32, u32)) -> Option<Span> {
+    let text = lines.get(line as usize)?.as_bytes();
+    let is_word = |c: &u8| c.is_ascii_alphanumeric() || *c == b'_';
+    let column = (column as usize).min(text.len());
+    let start = column - text[..column].iter().rev().take_while(|c| is_word(c)).count();
+    let end = column + text[column..].iter().take_while(|c| is_word(c)).count();
+    (start < end).then_some(((line, start as u32), (line, end as u32)))
+}
+
+fn contains(span: &Span, cursor: (u32, u32)) -> bool {
+    span.0 <= cursor && cursor <= span.1
+}
+
+/// Order spans from innermost to outermost, dropping any which do not
+/// strictly contain the one before.
+fn nested(lines: &[&str], mut spans: Vec<Span>) -> Vec<Span> {
+    let mut line_starts = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for text in lines {
+        line_starts.push(offset);
+        offset += text.len() + 1;
+    }
+    let offset_of = |(line, column): (u32, u32)| {
+        line_starts.get(line as usize).copied().unwrap_or(offset) + column as usize
+    };
+
+    // Shortest first, preferring the leftmost when the cursor sits between
+    // two tokens.
+    spans.sort_by_key(|span| (offset_of(span.1) - offset_of(span.0), span.0));
+    let mut output: Vec<Span> = Vec::with_capacity(spans.len());
+    for span in spans {
+        if output.last().is_none_or(|last| span != *last && span.0 <= last.0 && last.1 <= span.1) {
+            output.push(span);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dm::lexer::Lexer;
+
+    /// The text selected at each level when expanding from `marker`, which
+    /// is removed from the code before parsing.
+    fn levels(code: &str, marker: &str) -> Vec<String> {
+        let offset = code.find(marker).expect("missing marker");
+        let code = code.replacen(marker, "", 1);
+        let line = code[..offset].matches('\n').count() as u32;
+        let column = (offset - code[..offset].rfind('\n').map_or(0, |i| i + 1)) as u32;
+
+        let context = Default::default();
+        let lexer = Lexer::new(&context, Default::default(), code.as_bytes());
+        let indent = dm::indents::IndentProcessor::new(&context, lexer);
+        let mut annotations = AnnotationTree::default();
+        dm::parser::Parser::new(&context, indent).parse_annotations_only(&mut annotations);
+
+        let lines: Vec<&str> = code.lines().collect();
+        let ranges = selection_ranges(&annotations, Default::default(), &code, &[Position::new(line, column)]);
+        let mut current = ranges.into_iter().next().map(Box::new);
+        let mut output = Vec::new();
+        while let Some(range) = current {
+            let Range { start, end } = range.range;
+            let mut text = String::new();
+            for line in start.line..=end.line {
+                let content = lines[line as usize];
+                let from = if line == start.line { start.character as usize } else { 0 };
+                let to = if line == end.line { end.character as usize } else { content.len() };
+                if line != start.line {
+                    text.push('\n');
+                }
+                text.push_str(&content[from..to]);
+            }
+            output.push(text);
+            current = range.parent;
+        }
+        output
+    }
+
+    #[test]
+    fn field_access_chain() {
+        let code = r#"
+/obj/item
+    var/x
+    proc/test()
+        world.log << src.l$oc.x
+        return
+"#.trim();
+        assert_eq!(levels(code, "$"), vec![
+            "loc",
+            "src.loc",
+            "src.loc.x",
+            "world.log << src.loc.x",
+            "world.log << src.loc.x\n        return",
+            "proc/test()\n        world.log << src.loc.x\n        return",
+            "/obj/item\n    var/x\n    proc/test()\n        world.log << src.loc.x\n        return",
+        ]);
+    }
+
+    #[test]
+    fn string_interpolation() {
+        let code = r#"
+/proc/test()
+    world.log << "hello [src.na$me]!"
+"#.trim();
+        assert_eq!(levels(code, "$"), vec![
+            "name",
+            "src.name",
+            "\"hello [src.name]!\"",
+            "world.log << \"hello [src.name]!\"",
+            "/proc/test()\n    world.log << \"hello [src.name]!\"",
+        ]);
+    }
+
+    #[test]
+    fn prefab_var_block() {
+        let code = r#"
+/proc/test()
+    var/obj/O = new /obj{name = "a"; de$sc = "b"}()
+"#.trim();
+        assert_eq!(levels(code, "$"), vec![
+            "desc",
+            "desc = \"b\"",
+            "/obj{name = \"a\"; desc = \"b\"}",
+            "new /obj{name = \"a\"; desc = \"b\"}()",
+            "var/obj/O = new /obj{name = \"a\"; desc = \"b\"}()",
+            "/proc/test()\n    var/obj/O = new /obj{name = \"a\"; desc = \"b\"}()",
+        ]);
+    }
+
+    #[test]
+    fn multiple_positions() {
+        let code = "/proc/test()\n    return 1 + 2\n";
+        let context = Default::default();
+        let lexer = Lexer::new(&context, Default::default(), code.as_bytes());
+        let indent = dm::indents::IndentProcessor::new(&context, lexer);
+        let mut annotations = AnnotationTree::default();
+        dm::parser::Parser::new(&context, indent).parse_annotations_only(&mut annotations);
+
+        let positions = [Position::new(1, 11), Position::new(1, 15)];
+        let ranges = selection_ranges(&annotations, Default::default(), code, &positions);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].range, Range::new(Position::new(1, 11), Position::new(1, 12)));
+        assert_eq!(ranges[1].range, Range::new(Position::new(1, 15), Position::new(1, 16)));
+        assert_eq!(ranges[0].parent.as_ref().map(|p| p.range), ranges[1].parent.as_ref().map(|p| p.range));
+    }
+}
@@ -0,0 +1,508 @@
+//! Semantic highlighting based on the parser's annotations.
+
+use lsp_types::*;
+
+use dm::annotation::{Annotation, AnnotationTree};
+use dm::ast::PathOp;
+use dm::objtree::{ProcRef, TypeRef};
+use dm::lexer::{Lexer, Token};
+use dm::Location;
+
+use crate::positions::Encoding;
+use crate::{document, invalid_request, Engine, UnscopedVar};
+
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::TYPE,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::METHOD,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::MACRO,
+    SemanticTokenType::OPERATOR,
+];
+
+const TYPE: u32 = 0;
+const FUNCTION: u32 = 1;
+const METHOD: u32 = 2;
+const PARAMETER: u32 = 3;
+const VARIABLE: u32 = 4;
+const PROPERTY: u32 = 5;
+const MACRO: u32 = 6;
+const OPERATOR: u32 = 7;
+
+const TOKEN_MODIFIERS: &[SemanticTokenModifier] = &[
+    SemanticTokenModifier::DECLARATION,
+    SemanticTokenModifier::READONLY,
+    SemanticTokenModifier::STATIC,
+    SemanticTokenModifier::DEPRECATED,
+];
+
+const DECLARATION: u32 = 1 << 0;
+const READONLY: u32 = 1 << 1;
+const STATIC: u32 = 1 << 2;
+const DEPRECATED: u32 = 1 << 3;
+
+/// Path elements which are keywords rather than names.
+const PATH_KEYWORDS: &[&str] = &["var", "proc", "verb", "static", "global", "const", "tmp", "final"];
+
+pub fn legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: TOKEN_MODIFIERS.to_vec(),
+    }
+}
+
+/// A single token, with zero-indexed line and column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct RawToken {
+    line: u32,
+    start: u32,
+    length: u32,
+    token_type: u32,
+    modifiers: u32,
+}
+
+struct Builder<'a> {
+    lines: Vec<&'a str>,
+    tokens: Vec<RawToken>,
+}
+
+impl<'a> Builder<'a> {
+    fn new(text: &'a str) -> Self {
+        Builder {
+            lines: text.split('\n').collect(),
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Add a token spanning the given byte range of a zero-indexed line.
+    fn push(&mut self, line: u32, start: usize, end: usize, token_type: u32, modifiers: u32) {
+        let Some(line_text) = self.lines.get(line as usize) else {
+            return;
+        };
+        if start >= end || end > line_text.len() {
+            return;
+        }
+        // Columns are measured in the negotiated position encoding.
+        let encoding = Encoding::current();
+        self.tokens.push(RawToken {
+            line,
+            start: encoding.len(&line_text[..start]),
+            length: encoding.len(&line_text[start..end]),
+            token_type,
+            modifiers,
+        });
+    }
+
+    fn line_and_column(&self, location: Location) -> Option<(u32, &'a str, usize)> {
+        let line = location.line.checked_sub(1)?;
+        let line_text = *self.lines.get(line as usize)?;
+        Some((line, line_text, location.column.saturating_sub(1) as usize))
+    }
+
+    /// Add a token for the identifier at the given location.
+    fn ident(&mut self, location: Location, token_type: u32, modifiers: u32) {
+        let Some((line, line_text, column)) = self.line_and_column(location) else {
+            return;
+        };
+        if let Some((start, end)) = document::word_bounds_in_line(line_text, column) {
+            self.push(line, start, end, token_type, modifiers);
+        }
+    }
+
+    /// Add a token for `name` on the same line as `location`, searching at
+    /// or after it.
+    fn ident_named(&mut self, location: Location, name: &str, token_type: u32, modifiers: u32) {
+        let Some((line, line_text, column)) = self.line_and_column(location) else {
+            return;
+        };
+        if let Some(start) = document::find_identifier_in_line(line_text, column, name) {
+            self.push(line, start, start + name.len(), token_type, modifiers);
+        }
+    }
+
+    /// Add a token for `name` on the same line as `location`, searching
+    /// backwards from it.
+    fn ident_named_before(&mut self, location: Location, name: &str, token_type: u32, modifiers: u32) {
+        let Some((line, line_text, column)) = self.line_and_column(location) else {
+            return;
+        };
+        if let Some(start) = document::find_identifier_before_in_line(line_text, column, name) {
+            self.push(line, start, start + name.len(), token_type, modifiers);
+        }
+    }
+
+    /// Add a token for a single punctuation character at the given byte
+    /// offset of a zero-indexed line.
+    fn punct(&mut self, line: u32, start: usize) {
+        self.push(line, start, start + 1, OPERATOR, 0);
+    }
+
+    /// Sort the tokens, drop overlapping ones, and delta-encode them.
+    fn finish(mut self, range: Option<Range>) -> Vec<SemanticToken> {
+        // Sort by position, with earlier-pushed tokens winning ties.
+        self.tokens.sort_by_key(|token| (token.line, token.start));
+        let mut output = Vec::with_capacity(self.tokens.len());
+        let (mut prev_line, mut prev_start, mut prev_end) = (0, 0, 0);
+        for token in self.tokens {
+            if let Some(range) = range {
+                let position = Position::new(token.line, token.start);
+                if position < range.start || position >= range.end {
+                    continue;
+                }
+            }
+            if !output.is_empty() && token.line == prev_line && token.start < prev_end {
+                continue;
+            }
+            let delta_line = token.line - prev_line;
+            output.push(SemanticToken {
+                delta_line,
+                delta_start: if delta_line == 0 { token.start - prev_start } else { token.start },
+                length: token.length,
+                token_type: token.token_type,
+                token_modifiers_bitset: token.modifiers,
+            });
+            prev_line = token.line;
+            prev_start = token.start;
+            prev_end = token.start + token.length;
+        }
+        output
+    }
+}
+
+/// Classify one element of a tree path such as `/obj/item/proc/attack`.
+fn classify_tree_path(full: &[&str], idx: usize, declaration: bool, in_proc: bool) -> Option<(u32, u32)> {
+    let segment = *full.get(idx)?;
+    if PATH_KEYWORDS.contains(&segment) {
+        return None;
+    }
+    let is_last = idx + 1 == full.len();
+    let decl = if declaration && is_last { DECLARATION } else { 0 };
+    if let Some(pos) = full[..idx].iter().position(|&part| part == "proc" || part == "verb") {
+        if !is_last {
+            return None;
+        }
+        return Some((if pos == 0 { FUNCTION } else { METHOD }, decl));
+    }
+    if let Some(pos) = full[..idx].iter().position(|&part| part == "var") {
+        if !is_last {
+            return Some((TYPE, 0));
+        }
+        let flags = &full[pos..idx];
+        let mut modifiers = decl;
+        if flags.contains(&"const") {
+            modifiers |= READONLY;
+        }
+        if in_proc {
+            return Some((VARIABLE, modifiers));
+        }
+        if pos == 0 || flags.contains(&"static") || flags.contains(&"global") {
+            modifiers |= STATIC;
+        }
+        return Some((PROPERTY, modifiers));
+    }
+    Some((TYPE, decl))
+}
+
+/// Whether a proc's original declaration is marked `@deprecated`. Overrides
+/// are deprecated along with it.
+fn proc_deprecated(proc: ProcRef) -> bool {
+    let mut declaration = proc;
+    while let Some(parent) = declaration.parent_proc() {
+        declaration = parent;
+    }
+    declaration.get().docs.deprecated().is_some()
+}
+
+/// Whether the declaration of a var, on this type or a parent, is marked
+/// `@deprecated`.
+fn var_deprecated(ty: TypeRef, name: &str) -> bool {
+    let mut current = Some(ty);
+    while let Some(ty) = current {
+        if let Some(var) = ty.get().vars.get(name) {
+            if var.declaration.is_some() {
+                return var.value.docs.deprecated().is_some();
+            }
+        }
+        current = ty.parent_type();
+    }
+    false
+}
+
+/// Find the byte offset of the unescaped `[` which opens an interpolation,
+/// scanning from the given offset.
+fn find_interp_open(text: &str, from: usize) -> Option<usize> {
+    let mut backslash = false;
+    for (idx, ch) in text[from..].char_indices().skip(1) {
+        match ch {
+            _ if backslash => backslash = false,
+            '\\' => backslash = true,
+            '[' => return Some(from + idx),
+            _ => {}
+        }
+    }
+    None
+}
+
+impl Engine {
+    pub fn semantic_tokens(&mut self, url: &url::Url, range: Option<Range>) -> Result<Vec<SemanticToken>, jsonrpc::Error> {
+        let (_, file_id, annotations) = self.get_annotations(url)?;
+        let contents = self.docs.get_contents(url).map_err(invalid_request)?.into_owned();
+        let mut builder = Builder::new(&contents);
+
+        for (span, annotation) in annotations.iter() {
+            if span.start.file != file_id {
+                continue;
+            }
+            match annotation {
+                Annotation::InSequence(idx) => self.path_segment_token(&annotations, span.start, *idx, &mut builder),
+                Annotation::ProcHeader(parts, idx) => {
+                    let Some((name, mut type_path)) = parts.split_last() else {
+                        continue;
+                    };
+                    if let Some((kwd, rest)) = type_path.split_last() {
+                        if kwd == "proc" || kwd == "verb" {
+                            type_path = rest;
+                        }
+                    }
+                    let proc = self.objtree.type_by_path(type_path)
+                        .and_then(|ty| ty.get().procs.get(name.as_str()))
+                        .and_then(|proc| proc.value.get(*idx));
+                    if let Some(proc) = proc {
+                        // Parameter names follow the proc name in the header.
+                        for param in proc.parameters.iter() {
+                            let location = Location {
+                                line: span.start.line + param.location.line.saturating_sub(proc.location.line),
+                                ..span.start
+                            };
+                            builder.ident_named(location, &param.name, PARAMETER, DECLARATION);
+                        }
+                    }
+                },
+                Annotation::LocalVarScope(var_type, name) => {
+                    // The scope begins just after the variable's name.
+                    let modifiers = if var_type.flags.is_const() { DECLARATION | READONLY } else { DECLARATION };
+                    builder.ident_named_before(span.start, name, VARIABLE, modifiers);
+                },
+                Annotation::UnscopedCall(name) => {
+                    let iter = annotations.get_location(span.start);
+                    let (ty, _) = self.find_type_context(&iter);
+                    let ty = ty.unwrap_or_else(|| self.objtree.root());
+                    let proc = ty.get_proc(name);
+                    let token_type = match proc {
+                        Some(proc) if proc.ty().is_root() => FUNCTION,
+                        _ => METHOD,
+                    };
+                    let modifiers = if proc.is_some_and(proc_deprecated) { DEPRECATED } else { 0 };
+                    builder.ident(span.start, token_type, modifiers);
+                },
+                Annotation::ScopedCall(priors, name) => {
+                    let iter = annotations.get_location(span.start);
+                    let deprecated = self.find_scoped_type(&iter, priors)
+                        .and_then(|ty| ty.get_proc(name))
+                        .is_some_and(proc_deprecated);
+                    builder.ident(span.start, METHOD, if deprecated { DEPRECATED } else { 0 });
+                },
+                Annotation::UnscopedVar(name) => {
+                    let iter = annotations.get_location(span.start);
+                    let (ty, proc_name) = self.find_type_context(&iter);
+                    match self.find_unscoped_var(&iter, ty, proc_name, name) {
+                        UnscopedVar::Parameter { .. } => builder.ident(span.start, PARAMETER, 0),
+                        UnscopedVar::Local { var_type, .. } => {
+                            let modifiers = if var_type.flags.is_const() { READONLY } else { 0 };
+                            builder.ident(span.start, VARIABLE, modifiers);
+                        },
+                        UnscopedVar::Variable { ty, .. } => {
+                            let mut modifiers = ty.get_var_declaration(name).map_or(0, |decl| {
+                                let mut modifiers = 0;
+                                if decl.var_type.flags.is_const() {
+                                    modifiers |= READONLY;
+                                }
+                                if decl.var_type.flags.is_static() || ty.is_root() {
+                                    modifiers |= STATIC;
+                                }
+                                modifiers
+                            });
+                            if var_deprecated(ty, name) {
+                                modifiers |= DEPRECATED;
+                            }
+                            builder.ident(span.start, PROPERTY, modifiers);
+                        },
+                        UnscopedVar::None => {},
+                    }
+                },
+                Annotation::ScopedVar(priors, name) => {
+                    let iter = annotations.get_location(span.start);
+                    let ty = self.find_scoped_type(&iter, priors);
+                    let mut modifiers = ty
+                        .and_then(|ty| ty.get_var_declaration(name))
+                        .map_or(0, |decl| if decl.var_type.flags.is_const() { READONLY } else { 0 });
+                    if ty.is_some_and(|ty| var_deprecated(ty, name)) {
+                        modifiers |= DEPRECATED;
+                    }
+                    builder.ident(span.start, PROPERTY, modifiers);
+                },
+                Annotation::MacroUse { .. } => builder.ident(span.start, MACRO, 0),
+                Annotation::MacroDefinition(name) => builder.ident_named(span.start, name, MACRO, DECLARATION),
+                _ => {},
+            }
+        }
+
+        self.interpolation_tokens(&contents, &mut builder);
+        Ok(builder.finish(range))
+    }
+
+    fn path_segment_token(&self, annotations: &AnnotationTree, location: Location, idx: usize, builder: &mut Builder) {
+        let iter = annotations.get_location(location);
+        let in_proc = iter.clone().any(|(_, annotation)| matches!(annotation, Annotation::ProcBody(..)));
+        for (_, annotation) in iter.clone() {
+            match annotation {
+                Annotation::TreePath(absolute, parts) => {
+                    let mut full: Vec<&str> = Vec::new();
+                    if in_proc {
+                        // Local variable statements omit the leading `var`.
+                        if parts.first().is_none_or(|first| first != "var") {
+                            full.push("var");
+                        }
+                    } else if !absolute {
+                        if_annotation! { Annotation::TreeBlock(prefix) in iter; {
+                            full.extend(prefix.iter().map(String::as_str));
+                        }}
+                    }
+                    let offset = full.len();
+                    full.extend(parts.iter().map(String::as_str));
+                    if let Some((token_type, mut modifiers)) = classify_tree_path(&full, offset + idx, true, in_proc) {
+                        if !in_proc && offset + idx + 1 == full.len() && self.tree_path_deprecated(&full) {
+                            modifiers |= DEPRECATED;
+                        }
+                        builder.ident(location, token_type, modifiers);
+                    }
+                    return;
+                },
+                Annotation::TypePath(parts) => {
+                    let Some((_, segment)) = parts.get(idx) else {
+                        return;
+                    };
+                    if PATH_KEYWORDS.contains(&segment.as_str()) {
+                        return;
+                    }
+                    let after_proc = parts[..idx].iter().any(|(op, part)| *op != PathOp::Dot && (part == "proc" || part == "verb"));
+                    builder.ident(location, if after_proc { METHOD } else { TYPE }, 0);
+                    return;
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Whether the proc or var declared by a tree path like
+    /// `/obj/proc/attack` is deprecated.
+    fn tree_path_deprecated(&self, full: &[&str]) -> bool {
+        let Some((name, rest)) = full.split_last() else {
+            return false;
+        };
+        let Some(pos) = rest.iter().position(|&part| matches!(part, "proc" | "verb" | "var")) else {
+            return false;
+        };
+        let Some(ty) = self.objtree.type_by_path(&rest[..pos]) else {
+            return false;
+        };
+        if rest[pos] == "var" {
+            var_deprecated(ty, name)
+        } else {
+            ty.get_proc(name).is_some_and(proc_deprecated)
+        }
+    }
+
+    fn interpolation_tokens(&self, contents: &str, builder: &mut Builder) {
+        let context = dm::Context::default();
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(contents.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        let mut lexer = Lexer::new(&context, Default::default(), contents.as_bytes());
+        for token in lexer.by_ref() {
+            let (opens, closes) = match token.token {
+                Token::InterpStringBegin(_) => (true, false),
+                Token::InterpStringPart(_) => (true, true),
+                Token::InterpStringEnd(_) => (false, true),
+                _ => continue,
+            };
+            let line = token.location.line - 1;
+            let Some(&line_start) = line_starts.get(line as usize) else {
+                continue;
+            };
+            let column = token.location.column as usize - 1;
+            if closes {
+                builder.punct(line, column);
+            }
+            if opens {
+                if let Some(open) = find_interp_open(contents, line_start + column) {
+                    // The string may have continued onto later lines.
+                    let open_line = line_starts.partition_point(|&start| start <= open) - 1;
+                    builder.punct(open_line as u32, open - line_starts[open_line]);
+                }
+            }
+        }
+        for text_macro in lexer.text_macros() {
+            let line = text_macro.location.line - 1;
+            let start = text_macro.location.column as usize - 1;
+            builder.push(line, start, start + 1 + text_macro.name.len(), MACRO, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_path_segments() {
+        let path = ["obj", "item", "proc", "attack"];
+        assert_eq!(classify_tree_path(&path, 1, true, false), Some((TYPE, 0)));
+        assert_eq!(classify_tree_path(&path, 2, true, false), None);
+        assert_eq!(classify_tree_path(&path, 3, true, false), Some((METHOD, DECLARATION)));
+        assert_eq!(classify_tree_path(&["proc", "helper"], 1, true, false), Some((FUNCTION, DECLARATION)));
+
+        let var = ["obj", "var", "const", "mob", "owner"];
+        assert_eq!(classify_tree_path(&var, 3, true, false), Some((TYPE, 0)));
+        assert_eq!(classify_tree_path(&var, 4, true, false), Some((PROPERTY, DECLARATION | READONLY)));
+        assert_eq!(classify_tree_path(&["var", "total"], 1, true, true), Some((VARIABLE, DECLARATION)));
+    }
+
+    #[test]
+    fn deprecated_symbols() {
+        let context = dm::Context::default();
+        let code = r#"
+/obj
+    /// @deprecated use fire()
+    proc/shoot()
+    /// @deprecated
+    var/ammo = 1
+    var/power = 1
+/obj/item/shoot()
+/obj/item
+    ammo = 2
+"#;
+        let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dm".into(), code.trim());
+        let indents = dm::indents::IndentProcessor::new(&context, pp);
+        let mut parser = dm::parser::Parser::new(&context, indents);
+        parser.enable_procs();
+        let objtree = parser.parse_object_tree();
+
+        let item = objtree.expect("/obj/item");
+        assert!(proc_deprecated(item.get_proc("shoot").unwrap()));
+        assert!(var_deprecated(item, "ammo"));
+        assert!(!var_deprecated(item, "power"));
+        assert!(!var_deprecated(item, "missing"));
+    }
+
+    #[test]
+    fn interpolation_brackets() {
+        let text = r#"x = "a\[b[c]d[e]""#;
+        let first = find_interp_open(text, 4).unwrap();
+        assert_eq!(&text[first..], "[c]d[e]\"");
+        let second = find_interp_open(text, first + 2).unwrap();
+        assert_eq!(&text[second..], "[e]\"");
+    }
+}
@@ -0,0 +1,86 @@
+//! "Did you mean" suggestions for diagnostics about unknown names.
+
+use dm::preprocessor::DefineHistory;
+use dm::{DMError, Location};
+
+/// The name and location of every macro the environment ever defined.
+#[derive(Debug, Default)]
+pub struct DefineNames {
+    names: Vec<(String, Location)>,
+}
+
+impl DefineNames {
+    pub fn new(defines: &DefineHistory) -> DefineNames {
+        let mut names: Vec<(String, Location)> = Vec::new();
+        for (range, (name, _)) in defines.iter() {
+            if !range.start.is_builtins() {
+                names.push((name.clone(), range.start));
+            }
+        }
+        // Keep the first definition of each name.
+        names.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+        names.dedup_by(|a, b| a.0 == b.0);
+        DefineNames { names }
+    }
+
+    /// Find the define closest in spelling to the name an "unknown variable"
+    /// or "undefined var" error is about, as a note pointing at its
+    /// definition.
+    pub fn suggest(&self, error: &DMError) -> Option<(Location, String)> {
+        let description = error.description();
+        let unknown = match description.strip_prefix("unknown variable: ") {
+            Some(name) => name,
+            None => description.strip_prefix("undefined var: \"")?.strip_suffix('"')?,
+        };
+        let limit = std::cmp::max(1, unknown.len() / 3);
+        let (name, location, _) = self.names.iter()
+            .filter(|(name, _)| name != unknown)
+            .map(|(name, location)| (name, location, edit_distance(name, unknown)))
+            .filter(|&(_, _, distance)| distance <= limit)
+            .min_by_key(|&(_, _, distance)| distance)?;
+        Some((*location, format!("did you mean {}?", name)))
+    }
+}
+
+/// The Levenshtein distance between two names, ignoring case.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().flat_map(char::to_lowercase).collect();
+    let b: Vec<char> = b.chars().flat_map(char::to_lowercase).collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, &ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_define() {
+        let context = dm::Context::default();
+        let code = "#define MAX_HEALTH 100\n#define MIN_HEALTH 0\n/obj\n    var/health = MAX_HEALHT\n";
+        let mut pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dm".into(), code);
+        pp.by_ref().for_each(drop);
+        let names = DefineNames::new(&pp.finalize());
+
+        let error = DMError::new(Location::default(), "unknown variable: MAX_HEALHT");
+        let (location, message) = names.suggest(&error).unwrap();
+        assert_eq!(location.line, 1);
+        assert_eq!(message, "did you mean MAX_HEALTH?");
+
+        let error = DMError::new(Location::default(), "undefined var: \"MIN_HAELTH\"");
+        assert_eq!(names.suggest(&error).unwrap().1, "did you mean MIN_HEALTH?");
+
+        let error = DMError::new(Location::default(), "unknown variable: SOMETHING_ELSE");
+        assert_eq!(names.suggest(&error), None);
+        assert_eq!(edit_distance("kitten", "SITTING"), 3);
+    }
+}
@@ -1,5 +1,19 @@
 //! Query parser and evaluator for workspace symbol search.
 
+use std::collections::BTreeMap;
+
+use lsp_types::SymbolKind;
+
+use dm::objtree::ObjectTree;
+use dm::preprocessor::DefineHistory;
+use dm::{FileId, Location};
+
+/// The most results returned for a single query.
+pub const MAX_RESULTS: usize = 250;
+
+/// How often the cancellation check is consulted while searching.
+const CANCEL_CHECK_INTERVAL: usize = 1024;
+
 /// A parsed symbol query.
 #[derive(Debug)]
 pub enum Query {
@@ -35,56 +49,276 @@ impl Query {
         })
     }
 
-    /// Check whether this query matches the define with the given name.
-    pub fn matches_define(&self, name: &str) -> bool {
-        match *self {
-            Query::Anything(ref q) => starts_with(name, q),
-            Query::Define(ref q) => starts_with(name, q),
-            _ => false,
+    fn text(&self) -> &str {
+        match self {
+            Query::Anything(q) | Query::Define(q) | Query::Type(q) | Query::Var(q) | Query::Proc(q) => q,
+        }
+    }
+
+    fn accepts(&self, kind: IndexedKind) -> bool {
+        matches!((self, kind),
+            (Query::Anything(_), _) |
+            (Query::Define(_), IndexedKind::Define) |
+            (Query::Type(_), IndexedKind::Type) |
+            (Query::Var(_), IndexedKind::Var) |
+            (Query::Proc(_), IndexedKind::Proc))
+    }
+
+    /// Score a symbol against this query, or `None` if it doesn't match.
+    ///
+    /// Each whitespace-separated term must match the symbol's full path as a
+    /// subsequence. A query naming the symbol exactly ranks first.
+    fn score(&self, symbol: &IndexedSymbol) -> Option<i32> {
+        if !self.accepts(symbol.indexed_kind) {
+            return None;
+        }
+        let text = self.text();
+        let (haystack, name) = match self {
+            // Type queries look like paths, so match them against the path.
+            Query::Type(_) => (symbol.key.as_bytes(), symbol.key.as_bytes()),
+            _ => (symbol.key.as_bytes(), symbol.key_name().as_bytes()),
+        };
+
+        let mut total = 0;
+        for term in text.split_whitespace() {
+            total += fuzzy_score(haystack, term.as_bytes())?;
+        }
+        let last = text.split_whitespace().last().unwrap_or("").as_bytes();
+        if name == last {
+            total += EXACT_BONUS;
+        } else if name.starts_with(last) {
+            total += PREFIX_BONUS;
+        }
+        Some(total)
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Index
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndexedKind {
+    Define,
+    Type,
+    Var,
+    Proc,
+}
+
+/// A single searchable symbol.
+#[derive(Debug)]
+pub struct IndexedSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub container_name: Option<String>,
+    pub location: Location,
+    /// The DM reference anchor to link to if this symbol is builtin.
+    pub builtin: String,
+    indexed_kind: IndexedKind,
+    /// The lowercase full path of the symbol, which queries match against.
+    key: String,
+}
+
+impl IndexedSymbol {
+    fn key_name(&self) -> &str {
+        self.key.get(self.key.len().saturating_sub(self.name.len())..).unwrap_or(&self.key)
+    }
+}
+
+/// An index of the symbols in the object tree and preprocessor history,
+/// grouped by the file they are defined in.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    files: BTreeMap<FileId, Vec<IndexedSymbol>>,
+}
+
+impl SymbolIndex {
+    /// Index every symbol.
+    pub fn build(objtree: &ObjectTree, defines: Option<&DefineHistory>) -> SymbolIndex {
+        let mut index = SymbolIndex::default();
+        collect(objtree, defines, &|_| true, &mut |symbol| {
+            index.files.entry(symbol.location.file).or_default().push(symbol);
+        });
+        index
+    }
+
+    /// Re-index only the symbols defined in the given files, keeping the
+    /// rest of the index as it was.
+    pub fn update_files(&mut self, objtree: &ObjectTree, defines: Option<&DefineHistory>, files: &[FileId]) {
+        for file in files {
+            self.files.remove(file);
         }
+        let files_ref = &mut self.files;
+        collect(objtree, defines, &|file| files.contains(&file), &mut |symbol| {
+            files_ref.entry(symbol.location.file).or_default().push(symbol);
+        });
     }
 
-    pub fn matches_type(&self, name: &str, path: &str) -> bool {
-        match *self {
-            Query::Anything(ref q) => starts_with(name, q),
-            Query::Type(ref q) => path.to_lowercase().contains(q),
-            _ => false,
+    /// Search the index, returning at most `MAX_RESULTS` symbols, best match
+    /// first. Returns `None` if `is_cancelled` reports that the search is no
+    /// longer wanted.
+    pub fn search(&self, query: &Query, is_cancelled: &dyn Fn() -> bool) -> Option<Vec<&IndexedSymbol>> {
+        let mut matches = Vec::new();
+        for (i, symbol) in self.files.values().flatten().enumerate() {
+            if i % CANCEL_CHECK_INTERVAL == 0 && is_cancelled() {
+                return None;
+            }
+            if let Some(score) = query.score(symbol) {
+                matches.push((score, symbol));
+            }
         }
+        matches.sort_by(|(a_score, a), (b_score, b)| {
+            b_score.cmp(a_score)
+                .then_with(|| a.key.len().cmp(&b.key.len()))
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        matches.truncate(MAX_RESULTS);
+        Some(matches.into_iter().map(|(_, symbol)| symbol).collect())
     }
+}
 
-    pub fn matches_on_type(&self, _path: &str) -> bool {
-        matches!(*self, Query::Anything(_) |
-            Query::Proc(_) |
-            Query::Var(_))
+fn collect(objtree: &ObjectTree, defines: Option<&DefineHistory>, include: &dyn Fn(FileId) -> bool, push: &mut dyn FnMut(IndexedSymbol)) {
+    if let Some(defines) = defines {
+        for (range, (name, define)) in defines.iter() {
+            if !include(range.start.file) {
+                continue;
+            }
+            push(IndexedSymbol {
+                name: name.clone(),
+                kind: SymbolKind::CONSTANT,
+                container_name: None,
+                location: range.start,
+                builtin: builtin_anchor(define.docs(), &["/DM/preprocessor/", name]),
+                indexed_kind: IndexedKind::Define,
+                key: name.to_lowercase(),
+            });
+        }
     }
 
-    pub fn matches_var(&self, name: &str) -> bool {
-        match *self {
-            Query::Anything(ref q) |
-            Query::Var(ref q) => starts_with(name, q),
-            _ => false,
+    for ty in objtree.iter_types() {
+        if !ty.is_root() && include(ty.location.file) {
+            push(IndexedSymbol {
+                name: ty.name().to_owned(),
+                kind: SymbolKind::CLASS,
+                container_name: Some(ty.parent_path_str().to_owned()),
+                location: ty.location,
+                builtin: builtin_anchor(&ty.docs, &[&ty.path]),
+                indexed_kind: IndexedKind::Type,
+                key: ty.path.to_lowercase(),
+            });
+        }
+
+        let container_name = if ty.is_root() { None } else { Some(ty.path.to_string()) };
+        for (var_name, tv) in ty.vars.iter() {
+            if let Some(decl) = tv.declaration.as_ref() {
+                if include(decl.location.file) {
+                    push(IndexedSymbol {
+                        name: var_name.to_string(),
+                        kind: SymbolKind::FIELD,
+                        container_name: container_name.clone(),
+                        location: decl.location,
+                        builtin: builtin_anchor(&tv.value.docs, &[&ty.path, "/var/", var_name]),
+                        indexed_kind: IndexedKind::Var,
+                        key: format!("{}/var/{}", ty.path, var_name).to_lowercase(),
+                    });
+                }
+            }
+        }
+
+        for (proc_name, pv) in ty.procs.iter() {
+            if let Some(decl) = pv.declaration.as_ref() {
+                if include(decl.location.file) {
+                    push(IndexedSymbol {
+                        name: proc_name.to_string(),
+                        kind: if ty.is_root() {
+                            SymbolKind::FUNCTION
+                        } else if crate::is_constructor_name(proc_name.as_str()) {
+                            SymbolKind::CONSTRUCTOR
+                        } else {
+                            SymbolKind::METHOD
+                        },
+                        container_name: container_name.clone(),
+                        location: decl.location,
+                        builtin: builtin_anchor(&pv.main_value().docs, &[&ty.path, "/proc/", proc_name]),
+                        indexed_kind: IndexedKind::Proc,
+                        key: format!("{}/proc/{}", ty.path, proc_name).to_lowercase(),
+                    });
+                }
+            }
         }
     }
+}
+
+fn builtin_anchor(docs: &dm::docs::DocCollection, if_builtin: &[&str]) -> String {
+    match docs.builtin_docs {
+        dm::docs::BuiltinDocs::ReferenceHash(hash) => hash.to_owned(),
+        _ => if_builtin.join(""),
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Fuzzy matching
+
+const MATCH_SCORE: i32 = 1;
+const CONSECUTIVE_BONUS: i32 = 4;
+const BOUNDARY_BONUS: i32 = 6;
+const PREFIX_BONUS: i32 = 10;
+const EXACT_BONUS: i32 = 30;
+
+fn is_boundary(haystack: &[u8], i: usize) -> bool {
+    i == 0 || matches!(haystack[i - 1], b'/' | b'_' | b' ')
+}
+
+/// Score `needle` as a subsequence of `haystack`, both lowercase. Runs of
+/// consecutive characters and characters beginning a path segment or word
+/// score higher.
+fn fuzzy_score(haystack: &[u8], needle: &[u8]) -> Option<i32> {
+    if needle.is_empty() {
+        return Some(0);
+    }
+    // Cheap rejection before doing the full scoring.
+    let mut rest = haystack.iter();
+    if !needle.iter().all(|c| rest.any(|h| h == c)) {
+        return None;
+    }
 
-    pub fn matches_proc(&self, name: &str, _kind: dm::ast::ProcDeclKind) -> bool {
-        match *self {
-            Query::Anything(ref q) |
-            Query::Proc(ref q) => starts_with(name, q),
-            _ => false,
+    // best[j]: the best score with the current needle character matched at
+    // haystack[j].
+    let mut best: Vec<Option<i32>> = haystack.iter().enumerate()
+        .map(|(j, &h)| (h == needle[0]).then(|| char_score(haystack, j)))
+        .collect();
+    for &c in &needle[1..] {
+        let mut next = vec![None; haystack.len()];
+        // The best score of any match ending at least two characters back.
+        let mut gap_best: Option<i32> = None;
+        for j in 1..haystack.len() {
+            if j >= 2 {
+                gap_best = gap_best.max(best[j - 2]);
+            }
+            if haystack[j] != c {
+                continue;
+            }
+            let consecutive = best[j - 1].map(|score| score + CONSECUTIVE_BONUS);
+            if let Some(prior) = consecutive.max(gap_best) {
+                next[j] = Some(prior + char_score(haystack, j));
+            }
         }
+        best = next;
     }
+    best.into_iter().flatten().max()
 }
 
+fn char_score(haystack: &[u8], j: usize) -> i32 {
+    MATCH_SCORE + if is_boundary(haystack, j) { BOUNDARY_BONUS } else { 0 }
+}
+
+// ----------------------------------------------------------------------------
+// Simple matching
+
 fn simplify(s: &str) -> impl Iterator<Item=char> + Clone + '_ {
     s.chars().flat_map(|c| c.to_lowercase()).filter(|c| c.is_alphanumeric())
 }
 
 // ignore case and underscores
-pub fn starts_with<'a>(fulltext: &'a str, query: &'a str) -> bool {
-    let mut query_chars = simplify(query);
-    simplify(fulltext).zip(&mut query_chars).all(|(a, b)| a == b) && query_chars.next().is_none()
-}
-
 pub fn contains<'a>(fulltext: &'a str, query: &'a str) -> bool {
     let (mut fulltext, query) = (simplify(fulltext), simplify(query));
     loop {
@@ -101,3 +335,79 @@ pub fn contains<'a>(fulltext: &'a str, query: &'a str) -> bool {
 fn any_alphanumeric(text: &str) -> bool {
     text.chars().flat_map(|c| c.to_lowercase()).any(|c| c.is_alphanumeric())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(code: &'static str, query: &str) -> Vec<String> {
+        let context = dm::Context::default();
+        let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dm".into(), code.trim());
+        let indents = dm::indents::IndentProcessor::new(&context, pp);
+        let objtree = dm::parser::Parser::new(&context, indents).parse_object_tree();
+        let index = SymbolIndex::build(&objtree, None);
+        let query = Query::parse(query).unwrap();
+        index.search(&query, &|| false).unwrap().into_iter()
+            .filter(|symbol| !symbol.location.is_builtins())
+            .map(|symbol| symbol.key.clone())
+            .collect()
+    }
+
+    const CODE: &str = r#"
+/obj/machinery/door
+/obj/machinery/door/airlock/proc/attack_hand(mob/user)
+/obj/machinery/door/window
+/obj/item/proc/attack(mob/target)
+/obj/machinery/door/airlock/var/locked
+"#;
+
+    #[test]
+    fn fuzzy_ranking() {
+        assert_eq!(fuzzy_score(b"attack_hand", b"ah"), Some(2 * (MATCH_SCORE + BOUNDARY_BONUS)));
+        assert!(fuzzy_score(b"attack", b"ak").unwrap() < fuzzy_score(b"attack", b"at").unwrap());
+        assert_eq!(fuzzy_score(b"door", b"dx"), None);
+    }
+
+    #[test]
+    fn multiple_terms() {
+        assert_eq!(search(CODE, "airlock attack"), vec!["/obj/machinery/door/airlock/proc/attack_hand"]);
+    }
+
+    #[test]
+    fn paths_rank_exact_first() {
+        let results = search(CODE, "/obj/machinery/door");
+        assert_eq!(results[0], "/obj/machinery/door");
+        assert!(results.iter().all(|key| !key.contains("/proc/") && !key.contains("/var/")));
+    }
+
+    fn parse_in(context: &dm::Context, code: &str) -> (ObjectTree, DefineHistory) {
+        let mut pp = dm::preprocessor::Preprocessor::from_buffer(context, "unit_tests.dm".into(), code.trim());
+        let objtree = {
+            let indents = dm::indents::IndentProcessor::new(context, &mut pp);
+            dm::parser::Parser::new(context, indents).parse_object_tree()
+        };
+        (objtree, pp.finalize())
+    }
+
+    fn keys(index: &SymbolIndex, query: &str) -> Vec<String> {
+        index.search(&Query::parse(query).unwrap(), &|| false).unwrap().into_iter()
+            .filter(|symbol| !symbol.location.is_builtins())
+            .map(|symbol| symbol.key.clone())
+            .collect()
+    }
+
+    #[test]
+    fn incremental_update() {
+        let context = dm::Context::default();
+        let (objtree, defines) = parse_in(&context, "#define OLD_MACRO 1\n/obj/item/proc/old_attack()\n");
+        let mut index = SymbolIndex::build(&objtree, Some(&defines));
+        assert_eq!(keys(&index, "#old"), vec!["old_macro"]);
+
+        // The same file, edited.
+        let (objtree, defines) = parse_in(&context, "#define NEW_MACRO 1\n/obj/item/proc/new_attack()\n");
+        let file = objtree.expect("/obj/item").location.file;
+        index.update_files(&objtree, Some(&defines), &[file]);
+        assert_eq!(keys(&index, "#macro"), vec!["new_macro"]);
+        assert_eq!(keys(&index, "proc/attack"), vec!["/obj/item/proc/new_attack"]);
+    }
+}
@@ -0,0 +1,85 @@
+//! The types shown by `textDocument/prepareTypeHierarchy` and its follow-up
+//! supertype and subtype requests.
+
+use dm::objtree::{ObjectTree, TypeRef};
+
+/// The type a hierarchy can be shown for, which is any but the root.
+pub fn prepare<'a>(objtree: &'a ObjectTree, path: &str) -> Option<TypeRef<'a>> {
+    objtree.find(path).filter(|ty| !ty.is_root())
+}
+
+/// The parent of a type, or nothing for the types directly under the root.
+pub fn supertypes<'a>(objtree: &'a ObjectTree, path: &str) -> Option<Vec<TypeRef<'a>>> {
+    let ty = objtree.find(path)?;
+    // `parent_type` follows any `parent_type` var override.
+    Some(ty.parent_type_without_root().into_iter().collect())
+}
+
+/// The types whose parent is this one, sorted by path.
+pub fn subtypes<'a>(objtree: &'a ObjectTree, path: &str) -> Option<Vec<TypeRef<'a>>> {
+    let ty = objtree.find(path)?;
+    let mut output: Vec<_> = objtree.iter_types()
+        .filter(|child| !child.is_root() && child.parent_type().is_some_and(|parent| parent == ty))
+        .collect();
+    output.sort_by(|a, b| a.path.cmp(&b.path));
+    Some(output)
+}
+
+/// Count the vars a type declares and the procs it defines, ignoring vars it
+/// only overrides.
+pub fn summary(ty: TypeRef) -> String {
+    let vars = ty.vars.values().filter(|var| var.declaration.is_some()).count();
+    format!("{} vars, {} procs", vars, ty.procs.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(code: &str) -> ObjectTree {
+        let context = dm::Context::default();
+        let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dm".into(), code.trim());
+        let indents = dm::indents::IndentProcessor::new(&context, pp);
+        let mut parser = dm::parser::Parser::new(&context, indents);
+        parser.enable_procs();
+        parser.parse_object_tree()
+    }
+
+    fn paths(types: Option<Vec<TypeRef>>) -> Option<Vec<String>> {
+        types.map(|types| types.into_iter().map(|ty| ty.path.to_string()).collect())
+    }
+
+    #[test]
+    fn hierarchy() {
+        let objtree = parse(r#"
+/obj/item
+    var/force = 1
+    name = "item"
+    proc/attack()
+/obj/item/sword
+/obj/item/axe
+/obj/item/axe/great
+/obj/thing
+    parent_type = /obj/item
+"#);
+        assert_eq!(prepare(&objtree, "/obj/item").map(|ty| ty.path.to_string()), Some("/obj/item".to_owned()));
+        assert!(prepare(&objtree, "/").is_none());
+        assert!(prepare(&objtree, "/obj/missing").is_none());
+
+        assert_eq!(paths(supertypes(&objtree, "/obj/item/axe/great")), Some(vec!["/obj/item/axe".to_owned()]));
+        assert_eq!(paths(supertypes(&objtree, "/obj/thing")), Some(vec!["/obj/item".to_owned()]));
+        assert_eq!(paths(supertypes(&objtree, "/datum")), Some(vec![]));
+        assert_eq!(paths(supertypes(&objtree, "/obj/missing")), None);
+
+        assert_eq!(paths(subtypes(&objtree, "/obj/item")), Some(vec![
+            "/obj/item/axe".to_owned(),
+            "/obj/item/sword".to_owned(),
+            "/obj/thing".to_owned(),
+        ]));
+        assert_eq!(paths(subtypes(&objtree, "/obj/item/sword")), Some(vec![]));
+
+        // `name` only overrides the builtin var; `parent_type` is not counted either.
+        assert_eq!(summary(objtree.expect("/obj/item")), "1 vars, 1 procs");
+        assert_eq!(summary(objtree.expect("/obj/thing")), "0 vars, 0 procs");
+    }
+}
@@ -0,0 +1,191 @@
+//! Rebuilding the environment when its files change outside the editor.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use foldhash::HashMap;
+use lsp_types::FileChangeType;
+
+/// How long to wait for a burst of changes, like a checkout, to settle.
+const SETTLE: Duration = Duration::from_millis(1000);
+
+/// How often to check modification times when the client can't watch files.
+const POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// The glob to register with clients which can watch files for us.
+pub const GLOB: &str = "**/*.{dm,dme,dmm}";
+
+/// The glob for configuration files, which are watched alongside the code.
+pub const CONFIG_GLOB: &str = "**/SpacemanDMM.toml";
+
+/// Whether a file is a configuration file, changes to which need a reload.
+pub fn is_config(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == "SpacemanDMM.toml")
+}
+
+/// Whether changes to a file can affect the environment.
+pub fn is_watched(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("dm" | "dme" | "dmm"))
+}
+
+#[derive(Default)]
+pub struct FileWatcher {
+    /// Whether the client is watching files for us, or polling was started.
+    pub started: bool,
+    /// Files changed on disk, waiting for the burst to end.
+    changes: HashMap<PathBuf, FileChangeType>,
+    rebuild_at: Option<Instant>,
+    poll: Option<Poll>,
+}
+
+/// The modification times of the environment's files, for when the client
+/// can't watch files for us.
+struct Poll {
+    root: PathBuf,
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    next: Instant,
+}
+
+impl FileWatcher {
+    /// Note a change to a file, pushing back the rebuild.
+    pub fn record(&mut self, path: PathBuf, typ: FileChangeType) {
+        // A file created and then changed is still new.
+        let entry = self.changes.entry(path).or_insert(typ);
+        if typ != FileChangeType::CHANGED {
+            *entry = typ;
+        }
+        self.rebuild_at = Some(Instant::now() + SETTLE);
+    }
+
+    /// Check modification times ourselves from now on.
+    pub fn enable_polling(&mut self) {
+        self.poll = Some(Poll {
+            root: PathBuf::new(),
+            files: Vec::new(),
+            next: Instant::now() + POLL_INTERVAL,
+        });
+    }
+
+    /// Replace the set of files checked when polling, relative to the root.
+    pub fn track(&mut self, root: PathBuf, files: Vec<PathBuf>) {
+        if let Some(poll) = self.poll.as_mut() {
+            poll.files = files.into_iter()
+                .map(|path| {
+                    let modified = modified(&root.join(&path));
+                    (path, modified)
+                })
+                .collect();
+            poll.root = root;
+        }
+    }
+
+    /// When the next rebuild or poll is due, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        match (self.rebuild_at, self.poll.as_ref().map(|poll| poll.next)) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Check modification times, if polling and it's time to.
+    pub fn poll(&mut self) {
+        let Some(poll) = self.poll.as_mut() else { return };
+        if poll.next > Instant::now() {
+            return;
+        }
+        poll.next = Instant::now() + POLL_INTERVAL;
+
+        let mut changed = Vec::new();
+        for (path, last) in poll.files.iter_mut() {
+            let now = modified(&poll.root.join(&*path));
+            if now != *last {
+                let typ = match (*last, now) {
+                    (None, _) => FileChangeType::CREATED,
+                    (_, None) => FileChangeType::DELETED,
+                    _ => FileChangeType::CHANGED,
+                };
+                *last = now;
+                changed.push((path.clone(), typ));
+            }
+        }
+        for (path, typ) in changed {
+            self.record(path, typ);
+        }
+    }
+
+    /// Whether files have changed since the last rebuild.
+    pub fn is_pending(&self) -> bool {
+        self.rebuild_at.is_some()
+    }
+
+    /// If the changes have settled, take them for a rebuild.
+    pub fn take_rebuild(&mut self) -> Option<Vec<(PathBuf, FileChangeType)>> {
+        if self.rebuild_at.is_none_or(|at| at > Instant::now()) {
+            return None;
+        }
+        self.rebuild_at = None;
+        let mut changes: Vec<_> = self.changes.drain().collect();
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        Some(changes)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Describe what triggered a rebuild, for the log.
+pub fn summarize(changes: &[(PathBuf, FileChangeType)]) -> String {
+    let count = |typ| changes.iter().filter(|&&(_, each)| each == typ).count();
+    let mut summary = format!(
+        "{} changed, {} created, {} deleted",
+        count(FileChangeType::CHANGED),
+        count(FileChangeType::CREATED),
+        count(FileChangeType::DELETED),
+    );
+    const SHOWN: usize = 3;
+    for (i, (path, _)) in changes.iter().take(SHOWN).enumerate() {
+        summary.push_str(if i == 0 { ": " } else { ", " });
+        summary.push_str(&path.display().to_string());
+    }
+    if changes.len() > SHOWN {
+        summary.push_str(&format!(", and {} more", changes.len() - SHOWN));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_of_changes() {
+        let mut watcher = FileWatcher::default();
+        assert_eq!(watcher.deadline(), None);
+        watcher.record("code/a.dm".into(), FileChangeType::CREATED);
+        watcher.record("code/a.dm".into(), FileChangeType::CHANGED);
+        watcher.record("code/b.dm".into(), FileChangeType::CHANGED);
+        watcher.record("code/c.dm".into(), FileChangeType::DELETED);
+        watcher.record("code/d.dm".into(), FileChangeType::CHANGED);
+        assert!(watcher.deadline().is_some());
+        // Not settled yet.
+        assert_eq!(watcher.take_rebuild(), None);
+
+        watcher.rebuild_at = Some(Instant::now());
+        let changes = watcher.take_rebuild().unwrap();
+        assert_eq!(
+            summarize(&changes),
+            "2 changed, 1 created, 1 deleted: code/a.dm, code/b.dm, code/c.dm, and 1 more",
+        );
+        assert_eq!(watcher.deadline(), None);
+    }
+
+    #[test]
+    fn watched_extensions() {
+        assert!(is_watched(Path::new("tgstation.dme")));
+        assert!(is_watched(Path::new("code/game/objects.dm")));
+        assert!(is_watched(Path::new("maps/station.dmm")));
+        assert!(!is_watched(Path::new("icons/obj/device.dmi")));
+        assert!(!is_watched(Path::new("README.md")));
+    }
+}
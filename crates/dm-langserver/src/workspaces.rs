@@ -0,0 +1,150 @@
+//! Workspace folders, each with its own environment.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use foldhash::HashMap;
+use url::Url;
+
+use dm::annotation::AnnotationTree;
+use dm::FileId;
+
+use crate::{background, find_references, live, suggestions, symbol_search, watch, DiagnosticsTracker};
+
+/// One workspace folder and the environment loaded for it.
+///
+/// The engine works on one workspace at a time. While a workspace is active,
+/// the fields after `configured` hold defaults and the engine's own fields
+/// hold its state.
+#[derive(Default)]
+pub struct Workspace {
+    /// The folder's name, for matching against `dreammaker.environment`.
+    pub name: String,
+    /// The environment the client configured for this folder specifically,
+    /// from `workspace/configuration`.
+    pub configured: Option<String>,
+
+    pub root: Option<Url>,
+    pub environment: Option<PathBuf>,
+    pub context: dm::Context,
+    pub defines: Option<dm::preprocessor::DefineHistory>,
+    pub objtree: Arc<dm::objtree::ObjectTree>,
+    pub references_table: background::Background<find_references::ReferencesTable>,
+    pub symbol_index: Option<symbol_search::SymbolIndex>,
+    pub annotations: HashMap<Url, (FileId, FileId, Rc<AnnotationTree>)>,
+    pub diagnostics_tracker: Arc<Mutex<DiagnosticsTracker>>,
+    pub define_names: Arc<suggestions::DefineNames>,
+    pub macro_names: Arc<dreamchecker::MacroNames>,
+    pub live: live::LiveAnalysis,
+    pub watcher: watch::FileWatcher,
+    pub rebuilt: Option<(SystemTime, SystemTime)>,
+}
+
+impl Workspace {
+    pub fn new(name: String, root: Option<Url>) -> Workspace {
+        Workspace {
+            name,
+            root,
+            .. Default::default()
+        }
+    }
+}
+
+/// Which environment to load, from `dreammaker.environment`: either one path
+/// for every folder, or an object mapping folder names to paths.
+#[derive(Default, Debug)]
+pub struct EnvironmentSetting {
+    all: Option<String>,
+    folders: HashMap<String, String>,
+}
+
+impl EnvironmentSetting {
+    pub fn update(&mut self, value: &serde_json::Value) {
+        if let Some(path) = value.as_str() {
+            self.all = Some(path.to_owned());
+            self.folders.clear();
+        } else if let Some(object) = value.as_object() {
+            self.all = None;
+            self.folders = object.iter()
+                .filter_map(|(name, path)| Some((name.clone(), path.as_str()?.to_owned())))
+                .collect();
+        } else if value.is_null() {
+            self.all = None;
+            self.folders.clear();
+        }
+    }
+
+    /// The configured environment for a folder, relative to its root.
+    pub fn for_folder<'a>(&'a self, workspace: &'a Workspace) -> Option<&'a str> {
+        workspace.configured.as_deref()
+            .or_else(|| self.folders.get(&workspace.name).map(String::as_str))
+            .or(self.all.as_deref())
+    }
+}
+
+/// The macros to define before reading every environment, from
+/// `dreammaker.defines`: an object mapping names to values, which may be
+/// strings or numbers.
+pub fn define_setting(value: &serde_json::Value) -> BTreeMap<String, String> {
+    value.as_object().into_iter().flatten()
+        .filter_map(|(name, value)| {
+            let value = match value {
+                serde_json::Value::String(string) => string.clone(),
+                serde_json::Value::Number(number) => number.to_string(),
+                _ => return None,
+            };
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+/// The name the client would show for a folder.
+pub fn folder_name(url: &Url) -> String {
+    crate::url_to_path(url).ok()
+        .and_then(|path| Some(path.file_name()?.to_string_lossy().into_owned()))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment_per_folder() {
+        let game = Workspace::new(folder_name(&Url::parse("file:///repos/game/").unwrap()), None);
+        let mut tools = Workspace::new(folder_name(&Url::parse("file:///repos/map%20tools").unwrap()), None);
+        assert_eq!(game.name, "game");
+        assert_eq!(tools.name, "map tools");
+
+        let mut setting = EnvironmentSetting::default();
+        assert_eq!(setting.for_folder(&game), None);
+
+        setting.update(&serde_json::json!("test.dme"));
+        assert_eq!(setting.for_folder(&game), Some("test.dme"));
+        assert_eq!(setting.for_folder(&tools), Some("test.dme"));
+
+        setting.update(&serde_json::json!({ "map tools": "mapping.dme" }));
+        assert_eq!(setting.for_folder(&game), None);
+        assert_eq!(setting.for_folder(&tools), Some("mapping.dme"));
+
+        tools.configured = Some("other.dme".to_owned());
+        assert_eq!(setting.for_folder(&tools), Some("other.dme"));
+    }
+
+    #[test]
+    fn defines_from_setting() {
+        let defines = define_setting(&serde_json::json!({
+            "UNIT_TESTS": "",
+            "MAX_PLAYERS": 80,
+            "IGNORED": null,
+        }));
+        assert_eq!(defines.into_iter().collect::<Vec<_>>(), [
+            ("MAX_PLAYERS".to_owned(), "80".to_owned()),
+            ("UNIT_TESTS".to_owned(), String::new()),
+        ]);
+        assert!(define_setting(&serde_json::Value::Null).is_empty());
+    }
+}
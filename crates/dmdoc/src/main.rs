@@ -6,12 +6,13 @@ extern crate pulldown_cmark;
 extern crate walkdir;
 
 mod markdown;
+mod search;
 mod template;
 
 use dm::ast::{InputType, ProcReturnType};
 use dm::objtree::ObjectTree;
 use maud::{Markup, PreEscaped};
-use pulldown_cmark::{BrokenLink, CowStr};
+use pulldown_cmark::{BrokenLink, CowStr, LinkType};
 use std::collections::{BTreeMap, BTreeSet};
 use foldhash::HashSet;
 use std::fs::{self, File};
@@ -178,8 +179,24 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
     let diagnostic_count: std::cell::Cell<i32> = Default::default();
     let error_entity: std::cell::Cell<Option<String>> = Default::default();
     let error_entity_put = |string: String| error_entity.set(Some(string));
+    let error_entity_at = |location: dm::Location, string: String| {
+        error_entity_put(format!("{}:{}: {}", context.file_path(location.file).display(), location.line, string))
+    };
+
+    // where each definition of each macro stops applying, in include order
+    let mut macro_ranges: BTreeMap<&str, Vec<(dm::Location, dm::Location)>> = BTreeMap::new();
+    for (range, (name, _)) in define_history.iter() {
+        macro_ranges.entry(name.as_str()).or_default().push((range.start, range.end));
+    }
+    for ranges in macro_ranges.values_mut() {
+        ranges.sort();
+    }
+    let file_line = |location: dm::Location| {
+        (context.file_path(location.file).display().to_string().replace('\\', "/"), location.line)
+    };
 
     // if macros have docs, that counts as a module too
+    let mut all_defines = Vec::new();
     for (range, (name, define)) in define_history.iter() {
         let (docs, has_params, params, is_variadic);
         match define {
@@ -205,11 +222,46 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
         if docs.is_empty() {
             continue;
         }
-        error_entity_put(format!("#define {}", name));
+        error_entity_at(range.start, format!("#define {}", name));
         let broken_link_callback = &mut |link: BrokenLink| -> Option<(CowStr, CowStr)> {
-            broken_link_fixer(link, &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
+            broken_link_fixer(link, "", &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
         };
-        let docs = DocBlock::parse(&docs.text(), Some(broken_link_callback));
+        let category = docs.category();
+        let docs = DocBlock::parse(&without_category(&docs.text()), Some(broken_link_callback));
+
+        let mut body = String::new();
+        dm::pretty_print(&mut body, define.substitution().iter().cloned(), false)?;
+        let body = body.trim().to_owned();
+        // only object-like macros can be folded without arguments
+        let value = if has_params || body.is_empty() {
+            None
+        } else {
+            fold_macro(&define_history, name, range.start)
+                .map(|constant| constant.to_string())
+                .filter(|value| *value != body)
+        };
+
+        let ranges = &macro_ranges[name.as_str()];
+        let mut definitions = Vec::new();
+        if ranges.len() > 1 {
+            for (i, &(start, end)) in ranges.iter().enumerate() {
+                // a redefinition takes over until it is undefined, and an
+                // `#undef` ends the definition where it appears
+                let until = match ranges.get(i + 1) {
+                    Some(&(next, _)) => Some(file_line(next)),
+                    None if end.line != !0 => Some(file_line(end)),
+                    None => None,
+                };
+                let (file, line) = file_line(start);
+                definitions.push(Definition {
+                    file,
+                    line,
+                    until,
+                    this: start == range.start,
+                });
+            }
+        }
+
         let module = module_entry(&mut modules1, &context.file_path(range.start.file));
         module.items_wip.push((
             range.start.line,
@@ -218,18 +270,29 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
                 teaser: PreEscaped(docs.teaser().0.to_owned()),
             },
         ));
-        module.defines.insert(
+        all_defines.push(DefineEntry {
             name,
-            Define {
-                docs,
-                has_params,
-                params,
-                is_variadic,
-                line: range.start.line,
-            },
-        );
+            module: module.htmlname.clone(),
+            orig_filename: module.orig_filename.clone(),
+            category,
+            teaser: PreEscaped(docs.teaser().0.to_owned()),
+            line: range.start.line,
+        });
+        module.defines.entry(name).or_default().push(Define {
+            docs,
+            has_params,
+            params,
+            is_variadic,
+            line: range.start.line,
+            body,
+            value,
+            definitions,
+        });
         macro_count += 1;
     }
+    for defines in modules1.values_mut().flat_map(|module| module.defines.values_mut()) {
+        defines.sort_by_key(|define| define.line);
+    }
 
     // search the code tree for Markdown files
     for modules_path in code_directories {
@@ -256,11 +319,22 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
         let buf = read_as_markdown(index_path.as_ref())?.expect("file for --index must be .md or .txt");
         error_entity_put(index_path);
         let broken_link_callback = &mut |link: BrokenLink| -> Option<(CowStr, CowStr)> {
-            broken_link_fixer(link, &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
+            broken_link_fixer(link, "", &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
         };
         index_docs = Some(DocBlock::parse_with_title(&buf, Some(broken_link_callback)));
     }
 
+    // types by the type they inherit from, which is not always their parent path
+    let mut subtypes: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for ty in objtree.iter_types() {
+        if let Some(parent) = ty.parent_type_without_root() {
+            subtypes.entry(parent.get().path.as_str()).or_default().push(ty.get().path.as_str());
+        }
+    }
+    for children in subtypes.values_mut() {
+        children.sort_unstable();
+    }
+
     // collate types which have docs
     let mut count = 0;
     let mut substance_count = 0;
@@ -289,9 +363,9 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
         let mut anything = false;
         let mut substance = false;
         if !ty.docs.is_empty() {
-            error_entity_put(ty.path.to_owned());
+            error_entity_at(ty.location, ty.path.to_string());
             let broken_link_callback = &mut |link: BrokenLink| -> Option<(CowStr, CowStr)> {
-                broken_link_fixer(link, &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
+                broken_link_fixer(link, &ty.path, &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
             };
             let (title, block) = DocBlock::parse_with_title(&ty.docs.text(), Some(broken_link_callback));
             if let Some(title) = title {
@@ -324,9 +398,9 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
                     next = current.parent_type_without_root();
                 }
 
-                error_entity_put(format!("{}/var/{}", ty.path, name));
+                error_entity_at(var.value.location, format!("{}/var/{}", ty.path, name));
                 let broken_link_callback = &mut |link: BrokenLink| -> Option<(CowStr, CowStr)> {
-                    broken_link_fixer(link, &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
+                    broken_link_fixer(link, &ty.path, &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
                 };
                 let block = DocBlock::parse(&var.value.docs.text(), Some(broken_link_callback));
 
@@ -353,9 +427,24 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
                     path: &decl.var_type.type_path,
                     input_type: decl.var_type.input_type,
                 });
+                // an override also shows the value it replaces
+                let inherited = if var.declaration.is_none() {
+                    let mut next = ty.parent_type_without_root();
+                    loop {
+                        let Some(current) = next else { break None };
+                        if let Some(entry) = current.vars.get(name) {
+                            break default_value(&entry.value);
+                        }
+                        next = current.parent_type_without_root();
+                    }
+                } else {
+                    None
+                };
                 parsed_type.vars.insert(name, Var {
                     docs: block,
                     type_,
+                    value: default_value(&var.value),
+                    inherited,
                     // but `decl` is only used if it's on this type
                     decl: if var.declaration.is_some() { "var" } else { "" },
                     file: context.file_path(var.value.location.file).to_owned(),
@@ -384,9 +473,9 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
                     next = current.parent_type_without_root();
                 }
 
-                error_entity_put(format!("{}/proc/{}", ty.path, name));
+                error_entity_at(proc_value.location, format!("{}/proc/{}", ty.path, name));
                 let broken_link_callback = &mut |link: BrokenLink| -> Option<(CowStr, CowStr)> {
-                    broken_link_fixer(link, &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
+                    broken_link_fixer(link, &ty.path, &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
                 };
                 let block = DocBlock::parse(&proc_value.docs.text(), Some(broken_link_callback));
 
@@ -454,6 +543,7 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
                     parsed_type.htmlname = "global";
                 } else {
                     parsed_type.htmlname = &ty.get().path[1..];
+                    parsed_type.inheritance = Some(inheritance(ty, &parsed_type, &subtypes, &types_with_docs));
                 }
             }
             type_docs.insert(ty.get().pretty_path(), parsed_type);
@@ -489,12 +579,18 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
         };
 
         let mut docs = DocCollection::default();
+        let mut docs_line = 0;
         let mut _first = true;
         macro_rules! push_docs { () => {  // oof
             if !docs.is_empty() {
                 let doc = std::mem::take(&mut docs);
+                if docs_line > 0 {
+                    error_entity_put(format!("{}:{}", module.orig_filename, docs_line));
+                } else {
+                    error_entity_put(module.orig_filename.to_owned());
+                }
                 let broken_link_callback = &mut |link: BrokenLink| -> Option<(CowStr, CowStr)> {
-                    broken_link_fixer(link, &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
+                    broken_link_fixer(link, "", &macro_to_module_map, &macro_exists, &diagnostic_count, &error_entity, &modules_which_exist, &objtree, &types_with_docs)
                 };
                 if _first {
                     _first = false;
@@ -508,12 +604,14 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
             }
         }}
 
-        error_entity_put(module.orig_filename.to_owned());
         let mut last_line = 0;
         items_wip.sort_by_key(|&(line, _)| line);
         for (line, item) in items_wip.drain(..) {
             match item {
                 ModuleItem::DocComment(doc) => {
+                    if docs.is_empty() {
+                        docs_line = line;
+                    }
                     if line > last_line + 1 {
                         docs.push(DocComment::new(CommentKind::Line, DocTarget::EnclosingItem));
                     }
@@ -676,6 +774,34 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
         }).0.as_bytes())?;
     }
 
+    // documented macros by category, and otherwise by file, in include order
+    let mut categories: BTreeMap<String, Vec<DefineEntry>> = BTreeMap::new();
+    let mut files: Vec<DefineGroup> = Vec::new();
+    for entry in all_defines {
+        if let Some(category) = entry.category.clone() {
+            categories.entry(category).or_default().push(entry);
+        } else if let Some(group) = files.iter_mut().find(|group| group.title == entry.orig_filename) {
+            group.defines.push(entry);
+        } else {
+            files.push(DefineGroup {
+                title: entry.orig_filename.clone(),
+                is_file: true,
+                defines: vec![entry],
+            });
+        }
+    }
+    let groups: Vec<_> = categories.into_iter()
+        .map(|(title, defines)| DefineGroup { title, is_file: false, defines })
+        .chain(files)
+        .collect();
+    if !groups.is_empty() {
+        let mut f = create(&output_path.join("defines.html"))?;
+        f.write_all(template::dm_defines(&DefinesArgs {
+            env,
+            groups,
+        }).0.as_bytes())?;
+    }
+
     for (_path, details) in modules.iter() {
         let fname = format!("{}.html", details.htmlname);
 
@@ -715,9 +841,162 @@ fn main2() -> Result<(), Box<dyn std::error::Error>> {
         }).0.as_bytes())?;
     }
 
+    // index everything which was given a page or an anchor
+    let mut search_entries = Vec::new();
+    for details in modules.values() {
+        let href = format!("{}.html", details.htmlname);
+        search_entries.push(search::Entry {
+            name: details.name.clone().unwrap_or_else(|| last_element(&details.orig_filename).to_owned()),
+            kind: search::Kind::Module,
+            path: details.orig_filename.clone(),
+            href: href.clone(),
+            summary: search::first_sentence(&details.teaser.0),
+        });
+        for (name, defines) in details.defines.iter() {
+            search_entries.push(search::Entry {
+                name: name.to_string(),
+                kind: search::Kind::Define,
+                path: details.orig_filename.clone(),
+                href: format!("{}#define/{}", href, name),
+                summary: search::first_sentence(defines[0].docs.teaser().0),
+            });
+        }
+    }
+    for (path, details) in type_docs.iter() {
+        if !details.substance {
+            continue;
+        }
+        let href = format!("{}.html", details.htmlname);
+        if details.htmlname != "global" {
+            search_entries.push(search::Entry {
+                name: last_element(path).to_owned(),
+                kind: search::Kind::Type,
+                path: path.to_string(),
+                href: href.clone(),
+                summary: details.docs.as_ref().map_or(String::new(), |docs| search::first_sentence(docs.teaser().0)),
+            });
+        }
+        for (name, proc) in details.procs.iter() {
+            search_entries.push(search::Entry {
+                name: name.to_string(),
+                kind: search::Kind::Proc,
+                path: path.to_string(),
+                href: format!("{}#proc/{}", href, name),
+                summary: search::first_sentence(proc.docs.teaser().0),
+            });
+        }
+        for (name, var) in details.vars.iter() {
+            search_entries.push(search::Entry {
+                name: name.to_string(),
+                kind: search::Kind::Var,
+                path: path.to_string(),
+                href: format!("{}#var/{}", href, name),
+                summary: search::first_sentence(var.docs.teaser().0),
+            });
+        }
+    }
+    search::write(output_path, search_entries)?;
+
     Ok(())
 }
 
+/// Work out what a type declares, overrides, and inherits, with each member
+/// coming from the nearest type which defines it.
+fn inheritance<'a>(
+    ty: dm::objtree::TypeRef<'a>,
+    parsed_type: &ParsedType,
+    subtypes: &BTreeMap<&str, Vec<&'a str>>,
+    types_with_docs: &BTreeMap<&str, TypeHasDocs>,
+) -> Inheritance<'a> {
+    // a link to the entry of a var or proc on an ancestor, or else its page
+    let link = |path: &str, kind: &str, name: &str| {
+        let info = types_with_docs.get(path)?;
+        let documented = match kind {
+            "var" => info.var_docs.contains(name),
+            _ => info.proc_docs.contains(name),
+        };
+        Some(if documented {
+            format!("{}.html#{}/{}", &path[1..], kind, name)
+        } else {
+            format!("{}.html", &path[1..])
+        })
+    };
+
+    let mut ancestors = Vec::new();
+    let mut next = ty.parent_type_without_root();
+    while let Some(current) = next {
+        ancestors.push(current);
+        next = current.parent_type_without_root();
+    }
+
+    let mut members = Vec::new();
+    for (name, var) in ty.get().vars.iter() {
+        let overrides = if var.declaration.is_some() {
+            None
+        } else {
+            ancestors.iter()
+                .find(|ancestor| ancestor.vars.contains_key(name))
+                .map(|ancestor| (ancestor.get().path.as_str(), link(&ancestor.path, "var", name)))
+        };
+        members.push(Member {
+            kind: "var",
+            name: name.as_str(),
+            href: parsed_type.vars.contains_key(name.as_str()).then(|| format!("{}.html#var/{}", parsed_type.htmlname, name)),
+            overrides,
+        });
+    }
+    for (name, proc) in ty.get().procs.iter() {
+        let overrides = if proc.declaration.is_some() {
+            None
+        } else {
+            ancestors.iter()
+                .find(|ancestor| ancestor.procs.contains_key(name))
+                .map(|ancestor| (ancestor.get().path.as_str(), link(&ancestor.path, "proc", name)))
+        };
+        members.push(Member {
+            kind: "proc",
+            name: name.as_str(),
+            href: parsed_type.procs.contains_key(name.as_str()).then(|| format!("{}.html#proc/{}", parsed_type.htmlname, name)),
+            overrides,
+        });
+    }
+    members.sort_by_key(|member| (member.kind == "proc", member.name));
+
+    // anything already seen is shadowed further up
+    let mut seen_vars: BTreeSet<&str> = ty.get().vars.keys().map(|name| name.as_str()).collect();
+    let mut seen_procs: BTreeSet<&str> = ty.get().procs.keys().map(|name| name.as_str()).collect();
+    let mut inherited = Vec::new();
+    for ancestor in ancestors.iter() {
+        let path = ancestor.get().path.as_str();
+        let mut from = Vec::new();
+        for name in ancestor.get().vars.keys() {
+            if seen_vars.insert(name.as_str()) {
+                from.push(Member { kind: "var", name: name.as_str(), href: link(path, "var", name), overrides: None });
+            }
+        }
+        for name in ancestor.get().procs.keys() {
+            if seen_procs.insert(name.as_str()) {
+                from.push(Member { kind: "proc", name: name.as_str(), href: link(path, "proc", name), overrides: None });
+            }
+        }
+        if !from.is_empty() {
+            from.sort_by_key(|member| (member.kind == "proc", member.name));
+            inherited.push(InheritedFrom {
+                path,
+                builtin: ancestor.location.is_builtins(),
+                members: from,
+            });
+        }
+    }
+
+    Inheritance {
+        ancestors: ancestors.iter().rev().map(|ancestor| ancestor.get().path.as_str()).collect(),
+        subtypes: subtypes.get(ty.get().path.as_str()).cloned().unwrap_or_default(),
+        members,
+        inherited,
+    }
+}
+
 fn find_return_type(code: &dm::ast::Block) -> Option<Vec<String>> {
     for stmt in code.iter() {
         if let dm::ast::Statement::Setting { name, mode: dm::ast::SettingMode::Assign, value } = &stmt.elem {
@@ -736,9 +1015,11 @@ fn find_return_type(code: &dm::ast::Block) -> Option<Vec<String>> {
 
 // reference & other captures -> (href, tooltip)
 // this function's purpose is to prevent code copying in above closures
+// `scope` is the path of the type being documented, for relative references
 #[allow(clippy::too_many_arguments)]
 fn broken_link_fixer<'str>(
     link: BrokenLink,
+    scope: &str,
     macro_to_module_map: &BTreeMap<&str, String>,
     macro_exists: &BTreeSet<&str>,
     diagnostic_count: &std::cell::Cell<i32>,
@@ -748,7 +1029,20 @@ fn broken_link_fixer<'str>(
     types_with_docs: &BTreeMap<&str, TypeHasDocs>,
 ) -> Option<(CowStr<'str>, CowStr<'str>)>
     {
-        let referie = link.reference.into_string();
+        let mut referie = link.reference.into_string();
+        // bare paths in the text are only references if they start with a type
+        if link.link_type == LinkType::Autolink {
+            let first = referie[1..].split('/').next().unwrap_or("");
+            if !matches!(first, "proc" | "verb" | "var") && objtree.find(&referie[..first.len() + 1]).is_none() {
+                return None;
+            }
+        }
+        // `[name]`, `[proc/name]`, and `[var/name]` are relative to the scope
+        if !referie.starts_with('/') && !macro_exists.contains(referie.as_str()) {
+            if let Some(resolved) = resolve_relative(&referie, scope, objtree, types_with_docs) {
+                referie = resolved;
+            }
+        }
         let reference = referie.as_str();
         let error_entity_print = || {
             diagnostic_count.set(diagnostic_count.get() + 1);
@@ -904,6 +1198,17 @@ fn broken_link_fixer<'str>(
             }
         }
 
+        // bare paths are left as text rather than linked to a guess
+        if link.link_type == LinkType::Autolink && best > 0 && best < progress.len() {
+            error_entity_print();
+            if entity_exists {
+                eprintln!("    [{}]: not documented", reference);
+            } else {
+                eprintln!("    [{}]: unknown crosslink", reference);
+            }
+            return None;
+        }
+
         if best > 0 {
             use std::fmt::Write;
 
@@ -943,9 +1248,79 @@ fn broken_link_fixer<'str>(
         }
 }
 
+/// Find the nearest type to `scope`, itself then its ancestors then the root,
+/// with a proc or var of the given name, preferring one which documents it.
+fn resolve_relative(reference: &str, scope: &str, objtree: &ObjectTree, types_with_docs: &BTreeMap<&str, TypeHasDocs>) -> Option<String> {
+    let (kinds, name): (&[&str], _) = if let Some(name) = reference.strip_prefix("proc/") {
+        (&["proc"], name)
+    } else if let Some(name) = reference.strip_prefix("verb/") {
+        (&["proc"], name)
+    } else if let Some(name) = reference.strip_prefix("var/") {
+        (&["var"], name)
+    } else {
+        // like `[/ty/name]`, a var wins over a proc of the same name
+        (&["var", "proc"], reference)
+    };
+    if name.is_empty() || name.contains('/') {
+        return None;
+    }
+
+    let mut undocumented = None;
+    let mut next = objtree.find(scope);
+    while let Some(ty) = next {
+        for &kind in kinds {
+            let (exists, documented) = match kind {
+                "var" => (ty.vars.contains_key(name), types_with_docs.get(ty.path.as_str()).is_some_and(|info| info.var_docs.contains(name))),
+                _ => (ty.procs.contains_key(name), types_with_docs.get(ty.path.as_str()).is_some_and(|info| info.proc_docs.contains(name))),
+            };
+            if documented {
+                return Some(format!("{}/{}/{}", ty.path, kind, name));
+            } else if exists && undocumented.is_none() {
+                undocumented = Some(format!("{}/{}/{}", ty.path, kind, name));
+            }
+        }
+        next = ty.parent_type();
+    }
+    undocumented
+}
+
 // ----------------------------------------------------------------------------
 // Helpers
 
+/// The folded value of a var, or its initializer if folding failed.
+fn default_value(value: &dm::objtree::VarValue) -> Option<DefaultValue> {
+    if let Some(constant) = value.constant.as_ref() {
+        Some(DefaultValue {
+            text: constant.to_string(),
+            evaluated: true,
+        })
+    } else {
+        value.expression.as_ref().map(|expr| DefaultValue {
+            text: expr.to_string(),
+            evaluated: false,
+        })
+    }
+}
+
+/// Fold an object-like macro as seen from where it was defined.
+fn fold_macro(defines: &dm::preprocessor::DefineHistory, name: &str, location: dm::Location) -> Option<dm::constants::Constant> {
+    let context = dm::Context::default();
+    let mut preprocessor = defines.branch_at_location(location, &context);
+    preprocessor.push_file(PathBuf::from("<fold>"), io::Cursor::new(name.to_owned())).ok()?;
+    let indent = dm::indents::IndentProcessor::new(&context, &mut preprocessor);
+    dm::parser::parse_expression(&context, location, indent)
+        .and_then(|expr| expr.simple_evaluate(location))
+        .ok()
+}
+
+/// Remove `@category` tags, which are shown by grouping instead.
+fn without_category(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.trim().strip_prefix("@category").is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 fn module_path(path: &Path) -> String {
     let mut path = path.with_extension("");
     if path.file_name().map_or(false, |x| x.to_string_lossy().eq_ignore_ascii_case("README")) {
@@ -1217,7 +1592,7 @@ struct Module1<'a> {
     name: Option<String>,
     teaser: PreEscaped<String>,
     items_wip: Vec<(u32, ModuleItem<'a>)>,
-    defines: BTreeMap<&'a str, Define<'a>>,
+    defines: BTreeMap<&'a str, Vec<Define<'a>>>,
 }
 
 // ----------------------------------------------------------------------------
@@ -1300,12 +1675,43 @@ struct ParsedType<'a> {
     htmlname: &'a str,
     file: PathBuf,
     line: u32,
+    inheritance: Option<Inheritance<'a>>,
+}
+
+/// Where a type sits in the hierarchy, and where its members come from.
+struct Inheritance<'a> {
+    /// Every ancestor but the root, the furthest first.
+    ancestors: Vec<&'a str>,
+    /// The types which inherit directly from this one.
+    subtypes: Vec<&'a str>,
+    /// The vars and procs this type declares or overrides.
+    members: Vec<Member<'a>>,
+    /// The vars and procs this type only inherits, by the ancestor they come
+    /// from, the nearest first.
+    inherited: Vec<InheritedFrom<'a>>,
+}
+
+struct Member<'a> {
+    kind: &'static str,
+    name: &'a str,
+    href: Option<String>,
+    /// For an override, the type it overrides and a link to the entry there.
+    overrides: Option<(&'a str, Option<String>)>,
+}
+
+struct InheritedFrom<'a> {
+    path: &'a str,
+    builtin: bool,
+    members: Vec<Member<'a>>,
 }
 
 struct Var<'a> {
     docs: DocBlock,
     decl: &'static str,
     type_: Option<VarType<'a>>,
+    value: Option<DefaultValue>,
+    /// For an override, the value on the parent type.
+    inherited: Option<DefaultValue>,
     file: PathBuf,
     line: u32,
     parent: Option<String>,
@@ -1322,6 +1728,14 @@ struct VarType<'a> {
     input_type: InputType,
 }
 
+/// The default value of a var, as DM source.
+struct DefaultValue {
+    text: String,
+    /// Whether the value was folded to a constant, rather than being the
+    /// initializer as written.
+    evaluated: bool,
+}
+
 struct Proc {
     docs: DocBlock,
     decl: &'static str,
@@ -1346,7 +1760,7 @@ struct Module<'a> {
     name: Option<String>,
     teaser: PreEscaped<String>,
     items: Vec<ModuleItem<'a>>,
-    defines: BTreeMap<&'a str, Define<'a>>,
+    defines: BTreeMap<&'a str, Vec<Define<'a>>>,
 }
 
 struct Define<'a> {
@@ -1355,6 +1769,45 @@ struct Define<'a> {
     params: &'a [String],
     is_variadic: bool,
     line: u32,
+    /// The substitution as written.
+    body: String,
+    /// The folded value of an object-like macro, if it differs from the body.
+    value: Option<String>,
+    /// Every definition of the macro in include order, if there are several.
+    definitions: Vec<Definition>,
+}
+
+/// One of several definitions of a macro.
+struct Definition {
+    file: String,
+    line: u32,
+    /// Where the next definition or an `#undef` takes over, if anywhere.
+    until: Option<(String, u32)>,
+    /// Whether this is the definition being documented.
+    this: bool,
+}
+
+/// A documented macro, as listed on the defines page.
+struct DefineEntry<'a> {
+    name: &'a str,
+    module: String,
+    orig_filename: String,
+    category: Option<String>,
+    teaser: PreEscaped<String>,
+    line: u32,
+}
+
+/// The documented macros of one category, or of one file if they have none.
+struct DefineGroup<'a> {
+    title: String,
+    /// Whether the group is a file rather than a category.
+    is_file: bool,
+    defines: Vec<DefineEntry<'a>>,
+}
+
+struct DefinesArgs<'a> {
+    env: &'a Environment<'a>,
+    groups: Vec<DefineGroup<'a>>,
 }
 
 enum ModuleItem<'a> {
@@ -4,8 +4,14 @@ use std::collections::VecDeque;
 use std::ops::Range;
 
 use maud::PreEscaped;
-use pulldown_cmark::{self, BrokenLinkCallback, Event, HeadingLevel, Parser, Tag};
+use pulldown_cmark::{self, BrokenLink, BrokenLinkCallback, CowStr, Event, HeadingLevel, LinkType, Parser, Tag};
 
+/// Render a markdown document to HTML.
+///
+/// Bracketed references which are not markdown links, and bare absolute
+/// paths like `/obj/item` outside of links and code blocks, are passed to the
+/// broken link callback. Bare paths are given the `Autolink` link type and
+/// are left as text if the callback does not resolve them.
 pub fn render<'string>(markdown: &'string str, broken_link_callback: BrokenLinkCallback<'string, '_>) -> PreEscaped<String> {
     let mut buf = String::new();
     push_html(&mut buf, parser(markdown, broken_link_callback));
@@ -21,11 +27,11 @@ pub struct DocBlock {
 
 impl DocBlock {
     pub fn parse<'string>(markdown: &'string str, broken_link_callback: BrokenLinkCallback<'string, '_>) -> Self {
-        parse_main(parser(markdown, broken_link_callback).peekable())
+        parse_main(parser(markdown, broken_link_callback).into_iter().peekable())
     }
 
     pub fn parse_with_title<'string>(markdown: &'string str, broken_link_callback: BrokenLinkCallback<'string, '_>) -> (Option<String>, Self) {
-        let mut parser = parser(markdown, broken_link_callback).peekable();
+        let mut parser = parser(markdown, broken_link_callback).into_iter().peekable();
         (
             if let Some(&Event::Start(Tag::Heading(HeadingLevel::H1, _, _))) = parser.peek() {
                 parser.next();
@@ -52,15 +58,111 @@ impl DocBlock {
     }
 }
 
-fn parser<'string, 'func>(markdown: &'string str, broken_link_callback: BrokenLinkCallback<'string, 'func>) -> Parser<'string, 'func> {
-    Parser::new_with_broken_link_callback(
+fn parser<'string>(markdown: &'string str, mut broken_link_callback: BrokenLinkCallback<'string, '_>) -> Vec<Event<'string>> {
+    let events = Parser::new_with_broken_link_callback(
         markdown,
         pulldown_cmark::Options::ENABLE_TABLES | pulldown_cmark::Options::ENABLE_STRIKETHROUGH,
-        broken_link_callback
-    )
+        broken_link_callback.as_mut().map(|callback| &mut **callback as _),
+    ).collect();
+    match broken_link_callback {
+        Some(callback) => link_bare_paths(events, callback),
+        None => events,
+    }
+}
+
+/// Turn bare absolute paths in text and inline code into links, where the
+/// callback can resolve them.
+fn link_bare_paths<'string>(
+    events: Vec<Event<'string>>,
+    callback: &mut dyn FnMut(BrokenLink<'string>) -> Option<(CowStr<'string>, CowStr<'string>)>,
+) -> Vec<Event<'string>> {
+    let mut resolve = |path: &str| callback(BrokenLink {
+        span: 0..0,
+        link_type: LinkType::Autolink,
+        reference: path.to_owned().into(),
+    });
+
+    let mut output = Vec::with_capacity(events.len());
+    // links, images, headings, and code blocks are left alone
+    let mut depth = 0;
+    let mut events = events.into_iter().peekable();
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::Link(..) | Tag::Image(..) | Tag::Heading(..) | Tag::CodeBlock(..)) => depth += 1,
+            Event::End(Tag::Link(..) | Tag::Image(..) | Tag::Heading(..) | Tag::CodeBlock(..)) => depth -= 1,
+            Event::Code(ref code) if depth == 0 => {
+                let path = code.strip_suffix("()").unwrap_or(code);
+                if bare_path_len(path) == Some(path.len()) {
+                    if let Some((href, title)) = resolve(path) {
+                        output.push(Event::Start(Tag::Link(LinkType::Inline, href.clone(), title.clone())));
+                        output.push(event);
+                        output.push(Event::End(Tag::Link(LinkType::Inline, href, title)));
+                        continue;
+                    }
+                }
+            }
+            Event::Text(text) if depth == 0 => {
+                // the parser splits text at characters which might be markup
+                let mut text = text.into_string();
+                while let Some(Event::Text(next)) = events.peek() {
+                    text.push_str(next);
+                    events.next();
+                }
+
+                let mut done = 0;
+                let mut previous = None;
+                for (i, ch) in text.char_indices() {
+                    if i >= done && ch == '/' && previous.is_none_or(|p: char| p.is_whitespace() || p == '(') {
+                        if let Some(len) = bare_path_len(&text[i..]) {
+                            if let Some((href, title)) = resolve(&text[i..i + len]) {
+                                if i > done {
+                                    output.push(Event::Text(text[done..i].to_owned().into()));
+                                }
+                                output.push(Event::Start(Tag::Link(LinkType::Inline, href.clone(), title.clone())));
+                                output.push(Event::Text(text[i..i + len].to_owned().into()));
+                                output.push(Event::End(Tag::Link(LinkType::Inline, href, title)));
+                            }
+                            done = i + len;
+                        }
+                    }
+                    previous = Some(ch);
+                }
+                if done < text.len() {
+                    output.push(Event::Text(text[done..].to_owned().into()));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        output.push(event);
+    }
+    output
+}
+
+/// The length of the absolute path like `/obj/item/proc/attack` at the start
+/// of the text, if there is one. A path followed by a file extension is not
+/// counted.
+fn bare_path_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut len = 0;
+    while bytes.get(len) == Some(&b'/') {
+        let start = len + 1;
+        let mut end = start;
+        while bytes.get(end).is_some_and(|&b| b.is_ascii_alphanumeric() || b == b'_') {
+            end += 1;
+        }
+        if end == start || bytes[start].is_ascii_digit() {
+            break;
+        }
+        len = end;
+    }
+    if len == 0 || (bytes.get(len) == Some(&b'.') && bytes.get(len + 1).is_some_and(u8::is_ascii_alphanumeric)) {
+        return None;
+    }
+    Some(len)
 }
 
-fn parse_main(mut parser: std::iter::Peekable<Parser>) -> DocBlock {
+fn parse_main<'a, I: Iterator<Item=Event<'a>>>(mut parser: std::iter::Peekable<I>) -> DocBlock {
     let mut html = String::new();
     let teaser;
     if let Some(&Event::Start(Tag::Paragraph)) = parser.peek() {
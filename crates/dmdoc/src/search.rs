@@ -0,0 +1,167 @@
+//! The search index, loaded by the search box on every page.
+//!
+//! The index is `search.json`, holding every entry as a compact array:
+//!
+//! ```json
+//! {
+//!     "version": 1,
+//!     "kinds": ["module", "define", "type", "proc", "var"],
+//!     "entries": [["fire", 3, "/obj/item/gun", "obj/item/gun.html#proc/fire", "Shoots once."]]
+//! }
+//! ```
+//!
+//! Each entry is its name, an index into `kinds`, the path it belongs to, the
+//! page and anchor it is documented at, and the first sentence of its docs.
+//!
+//! When the entries would be too large to load all at once, `search.json`
+//! instead has `"shards"`, mapping the first letter of entry names to files
+//! like `search/a.json` which each hold only their `"entries"`. Names which
+//! do not start with a letter or digit are in the `_` shard. The search box
+//! loads only the shard for what was typed. If too few names start with it,
+//! the search box offers to load the rest for names which only contain it.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+/// The size of the serialized entries, in bytes, above which they are split
+/// into shards. A typical search then loads one shard, about a thirtieth of
+/// the entries, so this is about where one file stops loading quickly.
+pub const SHARD_THRESHOLD: usize = 64 * 1024;
+
+/// Sentences longer than this are cut short.
+const MAX_SUMMARY_CHARS: usize = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Module,
+    Define,
+    Type,
+    Proc,
+    Var,
+}
+
+impl Kind {
+    const ALL: &'static [Kind] = &[Kind::Module, Kind::Define, Kind::Type, Kind::Proc, Kind::Var];
+
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Module => "module",
+            Kind::Define => "define",
+            Kind::Type => "type",
+            Kind::Proc => "proc",
+            Kind::Var => "var",
+        }
+    }
+}
+
+/// One searchable item.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub kind: Kind,
+    /// The file of a module or macro, the type of a proc or var, or the full
+    /// path of a type.
+    pub path: String,
+    /// The page, and anchor if any, relative to the output directory.
+    pub href: String,
+    /// The first sentence of the docs, as plain text.
+    pub summary: String,
+}
+
+impl Entry {
+    fn to_json(&self) -> Value {
+        json!([self.name, self.kind as usize, self.path, self.href, self.summary])
+    }
+
+    fn shard(&self) -> char {
+        match self.name.chars().next() {
+            Some(ch) if ch.is_ascii_alphanumeric() => ch.to_ascii_lowercase(),
+            _ => '_',
+        }
+    }
+}
+
+/// Write `search.json`, and its shards if the entries are large.
+pub fn write(output_path: &Path, mut entries: Vec<Entry>) -> io::Result<()> {
+    entries.sort_by(|a, b| {
+        (a.name.to_lowercase(), a.kind, &a.path).cmp(&(b.name.to_lowercase(), b.kind, &b.path))
+    });
+    let kinds: Vec<_> = Kind::ALL.iter().map(|kind| kind.name()).collect();
+    let all: Vec<_> = entries.iter().map(Entry::to_json).collect();
+
+    let entries_json = serde_json::to_string(&all)?;
+    if entries_json.len() <= SHARD_THRESHOLD {
+        return write_json(&output_path.join("search.json"), &json! {{
+            "version": 1,
+            "kinds": kinds,
+            "entries": all,
+        }});
+    }
+
+    let mut shards: BTreeMap<char, Vec<Value>> = BTreeMap::new();
+    for (entry, value) in entries.iter().zip(all) {
+        shards.entry(entry.shard()).or_default().push(value);
+    }
+    let mut files = serde_json::Map::new();
+    for (shard, entries) in shards {
+        let file = format!("search/{}.json", shard);
+        write_json(&output_path.join(&file), &json! {{ "entries": entries }})?;
+        files.insert(shard.to_string(), file.into());
+    }
+    write_json(&output_path.join("search.json"), &json! {{
+        "version": 1,
+        "kinds": kinds,
+        "shards": files,
+    }})
+}
+
+fn write_json(path: &Path, value: &Value) -> io::Result<()> {
+    let mut f = io::BufWriter::new(crate::create(path)?);
+    serde_json::to_writer(&mut f, value)?;
+    f.flush()
+}
+
+/// The first sentence of some rendered docs, as plain text.
+pub fn first_sentence(html: &str) -> String {
+    // strip tags and collapse whitespace
+    let mut text = String::new();
+    let mut in_tag = false;
+    for ch in html.chars() {
+        match ch {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if in_tag => {}
+            _ if ch.is_whitespace() => {
+                if !text.is_empty() && !text.ends_with(' ') {
+                    text.push(' ');
+                }
+            }
+            _ => text.push(ch),
+        }
+    }
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    let text = text.trim_end();
+
+    let mut end = text.len();
+    for (i, ch) in text.char_indices() {
+        if matches!(ch, '.' | '!' | '?') && text[i + 1..].starts_with(' ') {
+            end = i + 1;
+            break;
+        }
+    }
+    let sentence = &text[..end];
+    if sentence.chars().count() > MAX_SUMMARY_CHARS {
+        let cut: String = sentence.chars().take(MAX_SUMMARY_CHARS - 1).collect();
+        format!("{}…", cut.trim_end())
+    } else {
+        sentence.to_owned()
+    }
+}
@@ -5,7 +5,7 @@ use std::path::Path;
 use dm::ast::{InputType, ProcReturnType};
 use maud::{display, html, Markup, PreEscaped, Render, DOCTYPE};
 
-use crate::{markdown::DocBlock, Environment, Index, IndexTree, ModuleArgs, ModuleItem, Type};
+use crate::{markdown::DocBlock, DefaultValue, DefinesArgs, Environment, Index, IndexTree, Member, ModuleArgs, ModuleItem, Type};
 
 pub(crate) fn base(
     env: &Environment,
@@ -24,6 +24,7 @@ pub(crate) fn base(
                     base href=(base_href);
                 }
                 link rel="stylesheet" href="dmdoc.css";
+                script src="dmdoc.js" defer {}
                 title {
                     (title) " - " (env.world_name)
                 }
@@ -34,6 +35,9 @@ pub(crate) fn base(
                     a href="index.html" { (env.world_name) } " - "
                     a href="index.html#modules" { "Modules" } " - "
                     a href="index.html#types" { "Types" }
+                    @if env.coverage.macros_documented > 0 {
+                        " - " a href="defines.html" { "Defines" }
+                    }
                     (header)
                 }
                 main {
@@ -178,7 +182,6 @@ pub(crate) fn dm_index(index: &Index) -> Markup {
             (maud::PreEscaped("\n<!-- produced by: \n"))
             (env.dmdoc.build_info)
             (maud::PreEscaped("\n-->\n"))
-            script src="dmdoc.js" {}
         },
         &display(""),
         &html! {
@@ -312,30 +315,90 @@ pub(crate) fn dm_module(module: &ModuleArgs) -> Markup {
 
             @if !details.defines.is_empty() {
                 h2 id="define" { "Define Details" }
-                @for (name, define) in details.defines.iter() {
-                    h3 id=(format!("define/{}", name)) {
-                        aside class="declaration" {
-                            "#define "
+                @for (name, defines) in details.defines.iter() {
+                    @for (i, define) in defines.iter().enumerate() {
+                        h3 id=[(i == 0).then(|| format!("define/{}", name))] {
+                            aside class="declaration" {
+                                "#define "
+                            }
+                            (name)
+                            @if define.has_params {
+                                aside {
+                                    "("
+                                    @for (i, param) in define.params.iter().enumerate() {
+                                        @if i > 0 {
+                                            ", "
+                                        }
+                                        (param)
+                                    }
+                                    @if define.is_variadic {
+                                        " ..."
+                                    }
+                                    ")"
+                                }
+                            }
+                            @if let Some(ref value) = define.value {
+                                " "
+                                aside class="value" { "= " code { (value) } }
+                            }
+                            (git_link(env, &details.orig_filename, define.line))
                         }
-                        (name)
-                        @if define.has_params {
-                            aside {
-                                "("
-                                @for (i, param) in define.params.iter().enumerate() {
-                                    @if i > 0 {
-                                        ", "
+                        @if !define.body.is_empty() {
+                            pre class="define-body" { code { (define.body) } }
+                        }
+                        (define.docs.html)
+                        @if !define.definitions.is_empty() {
+                            p { "Defined " (define.definitions.len()) " times, in include order:" }
+                            ul class="definitions" {
+                                @for definition in define.definitions.iter() {
+                                    li {
+                                        @if definition.this { strong { (definition.file) ":" (definition.line) } } @else { (definition.file) ":" (definition.line) }
+                                        @match definition.until {
+                                            Some((ref file, line)) => { ", until " (file) ":" (line) },
+                                            None => { ", until the end" },
+                                        }
                                     }
-                                    (param)
                                 }
-                                @if define.is_variadic {
-                                    " ..."
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    )
+}
+
+pub(crate) fn dm_defines(args: &DefinesArgs) -> Markup {
+    let DefinesArgs { env, ref groups } = *args;
+    base(
+        env,
+        "",
+        &display("Defines"),
+        &display(""),
+        &display(""),
+        &html! {
+            h1 { "Defines" }
+            @for group in groups.iter() {
+                h2 {
+                    (group.title)
+                    @if !group.is_file {
+                        " " aside { "(category)" }
+                    }
+                }
+                table class="summary" cellspacing="0" {
+                    @for define in group.defines.iter() {
+                        tr {
+                            th {
+                                a href=(format!("{}.html#define/{}", define.module, define.name)) { (define.name) }
+                            }
+                            td {
+                                (define.teaser)
+                                @if !group.is_file {
+                                    " " aside { (define.orig_filename) ":" (define.line) }
                                 }
-                                ")"
                             }
                         }
-                        (git_link(env, &details.orig_filename, define.line))
                     }
-                    (define.docs.html)
                 }
             }
         }
@@ -383,6 +446,24 @@ pub(crate) fn dm_type(ty: &Type) -> Markup {
             @if let Some(ref docs) = details.docs {
                 (docs.html)
             }
+            @if let Some(ref inheritance) = details.inheritance {
+                p class="ancestry" {
+                    @for ancestor in inheritance.ancestors.iter() {
+                        (type_link(env, ancestor)) " \u{203A} "  // &rsaquo;
+                    }
+                    strong { (path) }
+                }
+                @if !inheritance.subtypes.is_empty() {
+                    details class="subtypes" open[inheritance.subtypes.len() <= COLLAPSE_OVER] {
+                        summary { "Subtypes (" (inheritance.subtypes.len()) ")" }
+                        ul {
+                            @for subtype in inheritance.subtypes.iter() {
+                                li { (type_link(env, subtype)) }
+                            }
+                        }
+                    }
+                }
+            }
 
             @if !details.vars.is_empty() || !details.procs.is_empty() {
                 table class="summary" cellspacing="0" {
@@ -407,6 +488,54 @@ pub(crate) fn dm_type(ty: &Type) -> Markup {
                 }
             }
 
+            @if let Some(ref inheritance) = details.inheritance {
+                @if !inheritance.members.is_empty() || !inheritance.inherited.is_empty() {
+                    h2 id="members" { "Members" }
+                }
+                @if !inheritance.members.is_empty() {
+                    table class="members" cellspacing="0" {
+                        @for member in inheritance.members.iter() {
+                            tr {
+                                td class="kind" { (member.kind) }
+                                th { (member_link(member)) }
+                                td {
+                                    @if let Some((parent, ref href)) = member.overrides {
+                                        "overrides "
+                                        @if let Some(href) = href {
+                                            a href=(href) { (parent) }
+                                        } @else {
+                                            (parent)
+                                        }
+                                    } @else {
+                                        "declared"
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                @if !inheritance.inherited.is_empty() {
+                    details class="inherited" {
+                        summary {
+                            "Inherited ("
+                            (inheritance.inherited.iter().map(|from| from.members.len()).sum::<usize>())
+                            ")"
+                        }
+                        ul {
+                            @for from in inheritance.inherited.iter() {
+                                li class=[from.builtin.then_some("builtin")] {
+                                    "From " (type_link(env, from.path)) ": "
+                                    @for (i, member) in from.members.iter().enumerate() {
+                                        @if i > 0 { ", " }
+                                        (member_link(member))
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+
             @if !details.vars.is_empty() {
                 h2 id="var" { "Var Details" }
                 @for (name, var) in details.vars.iter() {
@@ -438,6 +567,15 @@ pub(crate) fn dm_type(ty: &Type) -> Markup {
                                 }
                             }
                         }
+                        @if let Some(ref value) = var.value {
+                            " "
+                            aside class="value" {
+                                "= " (render_default_value(value))
+                                @if let Some(ref inherited) = var.inherited {
+                                    span class="inherited" { " (inherited: " (render_default_value(inherited)) ")" }
+                                }
+                            }
+                        }
                         (git_link(env, &var.file.to_string_lossy(), var.line))
                     }
                     (var.docs.html)
@@ -516,6 +654,37 @@ pub fn render_input_type(env: &Environment, input_type: InputType) -> Markup {
     }
 }
 
+/// Lists of more items than this start out collapsed.
+const COLLAPSE_OVER: usize = 20;
+
+/// A whole type path, linked if the type has a page.
+fn type_link(env: &Environment, path: &str) -> Markup {
+    if env.all_type_names.contains(path) {
+        html! { a href=(format!("{}.html", &path[1..])) { (path) } }
+    } else {
+        html! { (path) }
+    }
+}
+
+fn member_link(member: &Member) -> Markup {
+    html! {
+        @if let Some(ref href) = member.href {
+            a href=(href) { (member.name) @if member.kind == "proc" { "()" } }
+        } @else {
+            (member.name) @if member.kind == "proc" { "()" }
+        }
+    }
+}
+
+fn render_default_value(value: &DefaultValue) -> Markup {
+    html! {
+        code { (value.text) }
+        @if !value.evaluated {
+            " " span class="unevaluated" title="This value could not be evaluated, so its initializer is shown instead." { "(unevaluated)" }
+        }
+    }
+}
+
 fn linkify_input_type(env: &Environment, show: &str, typepath: &str) -> Markup {
     if env.all_type_names.contains(typepath) {
         html! {
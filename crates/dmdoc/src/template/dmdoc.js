@@ -56,3 +56,268 @@ document.addEventListener("DOMContentLoaded", function() {
         header.appendChild(toggle);
     }
 });
+
+// ----------------------------------------------------------------------------
+// Search
+
+document.addEventListener("DOMContentLoaded", function() {
+    var header = document.getElementsByTagName("header")[0];
+    if (!header || !window.fetch) {
+        return;
+    }
+
+    var MAX_RESULTS = 50;
+
+    var container = document.createElement("span");
+    container.className = "search";
+    var input = document.createElement("input");
+    input.type = "search";
+    input.placeholder = "Search";
+    input.title = "Search (press S or /)";
+    input.setAttribute("autocomplete", "off");
+    var list = document.createElement("ul");
+    list.hidden = true;
+    container.appendChild(input);
+    container.appendChild(list);
+    header.appendChild(container);
+
+    // the index, loaded when first needed
+    var kinds = null;
+    var entries = [];
+    var shards = null;
+    var requested = {};
+    var loaded = {};
+    var pending = 0;
+    var failed = false;
+    // whether the user asked to look through every shard
+    var everywhere = false;
+
+    function fetchJson(url) {
+        return fetch(url).then(function(response) {
+            if (!response.ok) {
+                throw new Error(response.statusText);
+            }
+            return response.json();
+        });
+    }
+
+    function loadShard(key) {
+        if (!shards || !shards[key] || requested[key]) {
+            return;
+        }
+        requested[key] = true;
+        ++pending;
+        fetchJson(shards[key]).then(function(shard) {
+            --pending;
+            loaded[key] = true;
+            entries = entries.concat(shard.entries);
+            update();
+        }, fail);
+    }
+
+    function loadEverywhere() {
+        everywhere = true;
+        for (var key in shards) {
+            loadShard(key);
+        }
+        update();
+    }
+
+    function fail() {
+        failed = true;
+        update();
+    }
+
+    var loading = null;
+    function load() {
+        if (!loading) {
+            loading = fetchJson("search.json").then(function(index) {
+                kinds = index.kinds;
+                if (index.shards) {
+                    // the other shards are only loaded if a search needs them
+                    shards = index.shards;
+                    if (input.value.trim()) {
+                        loadShard(shardKey(input.value));
+                    }
+                } else {
+                    entries = index.entries;
+                }
+                update();
+            }, fail);
+        }
+    }
+
+    function shardKey(query) {
+        var first = query.trim().charAt(0).toLowerCase();
+        return /[a-z0-9]/.test(first) ? first : "_";
+    }
+
+    // exact names first, then prefixes, then substrings of the name, then
+    // substrings of the path
+    function rank(entry, query) {
+        var name = entry[0].toLowerCase();
+        if (name == query) {
+            return 0;
+        } else if (name.startsWith(query)) {
+            return 1;
+        } else if (name.indexOf(query) >= 0) {
+            return 2;
+        } else if ((entry[2] + "/" + name).toLowerCase().indexOf(query) >= 0) {
+            return 3;
+        }
+        return -1;
+    }
+
+    var results = [];
+    var selected = 0;
+    // whether the last item offers to look through every shard
+    var offered = false;
+
+    function choices() {
+        return results.length + (offered ? 1 : 0);
+    }
+
+    function update() {
+        var query = input.value.trim().toLowerCase();
+        list.textContent = "";
+        results = [];
+        offered = false;
+        if (!query) {
+            list.hidden = true;
+            return;
+        }
+
+        var ranked = [];
+        for (var i = 0; i < entries.length; ++i) {
+            var r = rank(entries[i], query);
+            if (r >= 0) {
+                ranked.push([r, i]);
+            }
+        }
+        ranked.sort(function(a, b) { return a[0] - b[0] || a[1] - b[1]; });
+
+        // names starting with the query are all in its shard, and rank above
+        // the rest, so the other shards are only worth loading for names
+        // containing it, which the user has to ask for
+        var key = shardKey(query);
+        if (shards && loaded[key] && !everywhere) {
+            var prefixes = 0;
+            while (prefixes < ranked.length && ranked[prefixes][0] <= 1) {
+                ++prefixes;
+            }
+            offered = prefixes < MAX_RESULTS;
+        }
+        for (var i = 0; i < ranked.length && i < MAX_RESULTS; ++i) {
+            results.push(entries[ranked[i][1]]);
+        }
+        selected = Math.min(selected, Math.max(choices() - 1, 0));
+
+        for (var i = 0; i < results.length; ++i) {
+            var entry = results[i];
+            var item = document.createElement("li");
+            var link = document.createElement("a");
+            link.href = entry[3];
+            var kind = document.createElement("span");
+            kind.className = "kind";
+            kind.textContent = kinds[entry[1]];
+            link.appendChild(kind);
+            var name = document.createElement("b");
+            name.textContent = entry[0];
+            link.appendChild(name);
+            var path = document.createElement("span");
+            path.className = "path";
+            path.textContent = entry[2];
+            link.appendChild(path);
+            if (entry[4]) {
+                var summary = document.createElement("span");
+                summary.className = "summary";
+                summary.textContent = entry[4];
+                link.appendChild(summary);
+            }
+            item.appendChild(link);
+            if (i == selected) {
+                item.className = "selected";
+            }
+            list.appendChild(item);
+        }
+        if (offered) {
+            var item = document.createElement("li");
+            item.className = results.length == selected ? "more selected" : "more";
+            var link = document.createElement("a");
+            link.href = "#";
+            link.textContent = "Search all names containing \u201c" + query + "\u201d";
+            link.addEventListener("click", function(event) {
+                event.preventDefault();
+                input.focus();
+                loadEverywhere();
+            });
+            item.appendChild(link);
+            list.appendChild(item);
+        } else if (!results.length) {
+            var item = document.createElement("li");
+            item.className = "empty";
+            item.textContent = failed ? "The search index could not be loaded." : kinds && !pending ? "No results." : "Loading…";
+            list.appendChild(item);
+        }
+        list.hidden = false;
+    }
+
+    function select(index) {
+        var count = choices();
+        if (!count) {
+            return;
+        }
+        selected = (index + count) % count;
+        for (var i = 0; i < list.children.length; ++i) {
+            list.children[i].classList.toggle("selected", i == selected);
+        }
+        list.children[selected].scrollIntoView({ block: "nearest" });
+    }
+
+    input.addEventListener("focus", load);
+    input.addEventListener("input", function() {
+        selected = 0;
+        load();
+        if (shards) {
+            loadShard(shardKey(input.value));
+        }
+        update();
+    });
+    input.addEventListener("keydown", function(event) {
+        if (event.key == "ArrowDown") {
+            event.preventDefault();
+            select(selected + 1);
+        } else if (event.key == "ArrowUp") {
+            event.preventDefault();
+            select(selected - 1);
+        } else if (event.key == "Enter") {
+            event.preventDefault();
+            if (results[selected]) {
+                list.hidden = true;
+                window.location.href = list.children[selected].firstChild.href;
+            } else if (offered && selected == results.length) {
+                loadEverywhere();
+            }
+        } else if (event.key == "Escape") {
+            input.value = "";
+            update();
+            input.blur();
+        }
+    });
+    input.addEventListener("blur", function() {
+        // let clicks on results land first
+        setTimeout(function() { list.hidden = true; }, 200);
+    });
+
+    document.addEventListener("keydown", function(event) {
+        var target = event.target.tagName.toLowerCase();
+        if (target == "input" || target == "textarea" || event.ctrlKey || event.metaKey || event.altKey) {
+            return;
+        }
+        if (event.key == "s" || event.key == "S" || event.key == "/") {
+            event.preventDefault();
+            input.focus();
+            update();
+        }
+    });
+});
@@ -0,0 +1,219 @@
+//! Snapshots of pages rendered for sample codebases.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json::Value;
+
+/// Document a fixture environment, returning the fixture's directory, the
+/// output directory, and what was printed to stderr.
+fn render(fixture: &str, dme: &str) -> (PathBuf, PathBuf, String) {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(fixture);
+    let (output, stderr) = render_at(&fixture, dme);
+    (fixture, output, stderr)
+}
+
+/// Document an environment in any directory.
+fn render_at(dir: &Path, dme: &str) -> (PathBuf, String) {
+    let output = std::env::temp_dir().join(format!("dmdoc-{}-{}", dme, std::process::id()));
+    let result = Command::new(env!("CARGO_BIN_EXE_dmdoc"))
+        .arg("-e").arg(dme)
+        .arg("--output").arg(&output)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(result.status.success());
+    (output, String::from_utf8(result.stderr).unwrap())
+}
+
+fn read_snapshot(fixture: &Path, name: &str) -> Vec<String> {
+    std::fs::read_to_string(fixture.join(name)).unwrap().lines().map(ToOwned::to_owned).collect()
+}
+
+/// Each documented var's rendered value, as `page#var: html`.
+fn rendered_values(output: &Path, page: &str) -> Vec<String> {
+    let html = std::fs::read_to_string(output.join(page)).unwrap();
+    html.split(r#"<h3 id="var/"#).skip(1).map(|header| {
+        let name = &header[..header.find('"').unwrap()];
+        let header = &header[..header.find("</h3>").unwrap()];
+        let value = header.find(r#"<aside class="value">"#).map_or("", |start| {
+            let value = &header[start..];
+            &value[..value.find("</aside>").unwrap() + "</aside>".len()]
+        });
+        format!("{}#{}: {}", page, name, value)
+    }).collect()
+}
+
+/// A page's main content, without its source links, as `page: html`.
+fn rendered_main(output: &Path, page: &str) -> String {
+    let html = std::fs::read_to_string(output.join(page)).unwrap();
+    let mut main = &html[html.find("<main>").unwrap()..html.find("</main>").unwrap()];
+    let mut without_links = String::new();
+    while let Some(start) = main.find("<img ") {
+        without_links.push_str(&main[..start]);
+        main = &main[start..];
+        main = &main[main.find('>').unwrap() + 1..];
+    }
+    without_links.push_str(main);
+    format!("{}: {}", page, without_links)
+}
+
+/// The links in a page's doc comments, as `text -> href`.
+fn doc_links(output: &Path, page: &str) -> Vec<String> {
+    let html = std::fs::read_to_string(output.join(page)).unwrap();
+    let mut links = Vec::new();
+    for paragraph in html.split("<p>").skip(1) {
+        let paragraph = &paragraph[..paragraph.find("</p>").unwrap()];
+        for link in paragraph.split("<a href=\"").skip(1) {
+            let href = &link[..link.find('"').unwrap()];
+            let text = &link[link.find('>').unwrap() + 1..link.find("</a>").unwrap()];
+            links.push(format!("{} -> {}", text, href));
+        }
+    }
+    links
+}
+
+#[test]
+fn var_values_snapshot() {
+    let (fixture, output, _) = render("values", "values.dme");
+    let mut values = rendered_values(&output, "datum/job.html");
+    values.extend(rendered_values(&output, "datum/job/captain.html"));
+    std::fs::remove_dir_all(&output).unwrap();
+
+    assert_eq!(values, read_snapshot(&fixture, "values.snapshot"));
+}
+
+#[test]
+fn defines_snapshot() {
+    let (fixture, output, _) = render("defines", "defines.dme");
+    let pages: Vec<_> = ["defines.html", "code/flags.html", "code/helpers.html"].iter()
+        .map(|page| rendered_main(&output, page))
+        .collect();
+    std::fs::remove_dir_all(&output).unwrap();
+
+    assert_eq!(pages, read_snapshot(&fixture, "defines.snapshot"));
+}
+
+#[test]
+fn crosslinks_resolve_by_proximity() {
+    let (_, output, warnings) = render("crosslinks", "crosslinks.dme");
+    let global = doc_links(&output, "global.html");
+    let gun = doc_links(&output, "obj/item/gun.html");
+    let energy = doc_links(&output, "obj/item/gun/energy.html");
+    std::fs::remove_dir_all(&output).unwrap();
+
+    // globals see only globals
+    assert_eq!(global, [
+        "/obj/item/gun/proc/reload -> obj/item/gun.html#proc/reload",
+        "describe -> global.html#proc/describe",
+    ]);
+    // a type sees its own procs and vars before the global ones
+    assert_eq!(gun, [
+        "proc/fire -> obj/item/gun.html#proc/fire",
+        "<code>/obj/item/gun/energy</code> -> obj/item/gun/energy.html",
+        "fire -> obj/item/gun.html#proc/fire",
+        "ammo -> obj/item/gun.html#var/ammo",
+        "ammo -> obj/item/gun.html#var/ammo",
+    ]);
+    // then its ancestors' before the global ones
+    assert_eq!(energy, [
+        "fire -> obj/item/gun/energy.html#proc/fire",
+        "reload -> obj/item/gun.html#proc/reload",
+        "describe -> obj/item/gun.html#proc/describe",
+        "/obj/item/gun -> obj/item/gun.html",
+        "/obj/item/gun/proc/reload -> obj/item/gun.html#proc/reload",
+    ]);
+
+    assert!(warnings.contains(concat!(
+        "code/guns.dm:27: /obj/item/gun/energy:\n",
+        "    [missing_proc]: unknown crosslink\n",
+        "    [/obj/item/gun/laser]: unknown crosslink\n",
+    )), "{}", warnings);
+}
+
+/// Check each search index entry's shape, and that it links to an element
+/// which exists.
+fn check_search_entries(output: &Path, entries: &[Value]) {
+    for entry in entries {
+        let fields = entry.as_array().unwrap();
+        assert_eq!(fields.len(), 5, "{}", entry);
+        assert!(!fields[0].as_str().unwrap().is_empty(), "{}", entry);
+        assert!(fields[1].as_u64().unwrap() < 5, "{}", entry);
+        assert!(fields[2].is_string() && fields[4].is_string(), "{}", entry);
+
+        let href = fields[3].as_str().unwrap();
+        let (page, anchor) = href.split_once('#').map_or((href, None), |(page, anchor)| (page, Some(anchor)));
+        let html = std::fs::read_to_string(output.join(page)).unwrap_or_else(|_| panic!("{} does not exist", page));
+        if let Some(anchor) = anchor {
+            assert!(html.contains(&format!(r#"id="{}""#, anchor)), "{} has no #{}", page, anchor);
+        }
+    }
+}
+
+fn read_json(path: &Path) -> Value {
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn search_index_matches_pages() {
+    for (fixture, dme) in [("crosslinks", "crosslinks.dme"), ("defines", "defines.dme")] {
+        let (_, output, _) = render(fixture, dme);
+        let index = read_json(&output.join("search.json"));
+        assert_eq!(index["version"], 1);
+        assert_eq!(index["kinds"], serde_json::json!(["module", "define", "type", "proc", "var"]));
+        assert!(index.get("shards").is_none());
+
+        let entries = index["entries"].as_array().unwrap();
+        check_search_entries(&output, entries);
+        let expected = match fixture {
+            "crosslinks" => serde_json::json!(["fire", 3, "/obj/item/gun/energy", "obj/item/gun/energy.html#proc/fire", "Shoots a beam."]),
+            _ => serde_json::json!(["MAX_PLAYERS", 1, "code/flags.dm", "code/flags.html#define/MAX_PLAYERS", "How many players fit on the station."]),
+        };
+        assert!(entries.contains(&expected), "{:#}", index);
+        std::fs::remove_dir_all(&output).unwrap();
+    }
+}
+
+#[test]
+fn search_index_is_sharded_when_large() {
+    let dir = std::env::temp_dir().join(format!("dmdoc-large-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut code = String::new();
+    for i in 0..3000 {
+        code.push_str(&format!("/// Handles case number {0}, with a sentence long enough to make the index large.\n/proc/{1}_{0}()\n", i, ["alpha", "beta", "_gamma"][i % 3]));
+    }
+    std::fs::write(dir.join("large.dme"), code).unwrap();
+    let (output, _) = render_at(&dir, "large.dme");
+
+    let index = read_json(&output.join("search.json"));
+    assert!(index.get("entries").is_none());
+    let shards = index["shards"].as_object().unwrap();
+    assert_eq!(shards.keys().collect::<Vec<_>>(), ["_", "a", "b", "l"]);
+
+    let mut total = 0;
+    for (key, file) in shards {
+        let entries = read_json(&output.join(file.as_str().unwrap()))["entries"].as_array().unwrap().clone();
+        for entry in &entries {
+            let first = entry[0].as_str().unwrap().chars().next().unwrap().to_ascii_lowercase();
+            assert_eq!(if first.is_ascii_alphanumeric() { first.to_string() } else { "_".to_owned() }, *key);
+        }
+        check_search_entries(&output, &entries[..10.min(entries.len())]);
+        total += entries.len();
+    }
+    // the procs, and the module they are in
+    assert_eq!(total, 3001);
+
+    std::fs::remove_dir_all(&output).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn inheritance_snapshot() {
+    let (fixture, output, _) = render("inheritance", "inheritance.dme");
+    let pages: Vec<_> = ["datum/gear.html", "datum/gear/clothing.html", "datum/gear/clothing/helmet.html"].iter()
+        .map(|page| rendered_main(&output, page))
+        .collect();
+    std::fs::remove_dir_all(&output).unwrap();
+
+    assert_eq!(pages, read_snapshot(&fixture, "inheritance.snapshot"));
+}
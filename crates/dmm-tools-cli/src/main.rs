@@ -55,6 +55,10 @@ struct Context {
 
 impl Context {
     fn objtree(&mut self, opt: &Opt) {
+        self.objtree_with(opt, true);
+    }
+
+    fn objtree_with(&mut self, opt: &Opt, constants: bool) {
         let environment = match opt.environment {
             Some(ref env) => env.into(),
             None => match dm::detect_environment_default() {
@@ -68,17 +72,25 @@ impl Context {
             self.icon_cache.set_icons_root(parent);
         }
 
-        self.dm_context.autodetect_config(&environment);
-        let pp = match dm::preprocessor::Preprocessor::new(&self.dm_context, environment) {
-            Ok(pp) => pp,
-            Err(e) => {
-                eprintln!("i/o error opening environment:\n{}", e);
-                std::process::exit(1);
-            }
-        };
-        let indents = dm::indents::IndentProcessor::new(&self.dm_context, pp);
-        let parser = dm::parser::Parser::new(&self.dm_context, indents);
-        self.objtree = parser.parse_object_tree();
+        self.objtree = parse_environment(&mut self.dm_context, environment, constants);
+    }
+}
+
+fn parse_environment(dm_context: &mut dm::Context, environment: PathBuf, constants: bool) -> ObjectTree {
+    dm_context.autodetect_config(&environment);
+    let pp = match dm::preprocessor::Preprocessor::new(dm_context, environment) {
+        Ok(pp) => pp,
+        Err(e) => {
+            eprintln!("i/o error opening environment:\n{}", e);
+            std::process::exit(1);
+        }
+    };
+    let indents = dm::indents::IndentProcessor::new(dm_context, pp);
+    let parser = dm::parser::Parser::new(dm_context, indents);
+    if constants {
+        parser.parse_object_tree()
+    } else {
+        parser.parse_object_tree_without_constants()
     }
 }
 
@@ -184,6 +196,33 @@ enum Command {
     },
     /// Read a JSON RenderManyCommand from stdin, execute it, and print a RenderManyCommandResult.
     RenderMany,
+    /// List the differing types, vars, and procs between two environments.
+    #[command(name = "diff-objtree")]
+    DiffObjtree {
+        /// Output as JSON.
+        #[arg(short = 'j', long = "json")]
+        json: bool,
+
+        /// Only compare this type and its subtypes.
+        #[arg(short = 'p', long = "prefix")]
+        prefix: Option<String>,
+
+        /// The old `.dme` file.
+        old: PathBuf,
+        /// The new `.dme` file.
+        new: PathBuf,
+    },
+    /// Print the object tree as JSON.
+    #[command(name = "dump-objtree")]
+    DumpObjtree {
+        /// Only dump this type and its subtypes.
+        #[arg(short = 'p', long = "prefix")]
+        prefix: Option<String>,
+
+        /// Skip evaluating and printing the values of vars.
+        #[arg(long = "no-constants")]
+        no_constants: bool,
+    },
 }
 
 fn run(opt: &Opt, command: &Command, context: &mut Context) {
@@ -424,6 +463,39 @@ fn run(opt: &Opt, command: &Command, context: &mut Context) {
             output_json(&report);
         },
         // --------------------------------------------------------------------
+        Command::DumpObjtree {
+            ref prefix, no_constants,
+        } => {
+            context.objtree_with(opt, !no_constants);
+            let options = dm::export::ExportOptions {
+                prefix: prefix.clone(),
+                skip_constants: no_constants,
+            };
+            output_json(&dm::export::export_object_tree(&context.dm_context, &context.objtree, &options));
+        },
+        // --------------------------------------------------------------------
+        Command::DiffObjtree {
+            json, ref prefix, ref old, ref new,
+        } => {
+            let mut trees = Vec::new();
+            for environment in [old, new] {
+                eprintln!("parsing {}", environment.display());
+                let mut dm_context = dm::Context::default();
+                dm_context.set_print_severity(Some(dm::Severity::Error));
+                trees.push(parse_environment(&mut dm_context, environment.clone(), true));
+            }
+            let options = dm::export::ExportOptions {
+                prefix: prefix.clone(),
+                .. Default::default()
+            };
+            let diff = dm::export::diff_object_trees(&trees[0], &trees[1], &options);
+            if json {
+                output_json(&diff);
+            } else {
+                print!("{}", diff);
+            }
+        },
+        // --------------------------------------------------------------------
         Command::RenderMany => {
             let stdin = std::io::stdin();
             let command: RenderManyCommand = serde_json::from_reader(stdin.lock()).unwrap();
@@ -10,6 +10,7 @@ use ndarray::{self, Array3, Axis};
 
 use crate::dmi::Dir;
 use dm::constants::Constant;
+use dm::intern::Interned;
 use dm::DMError;
 
 mod read;
@@ -122,7 +123,7 @@ pub struct ZLevel<'a> {
 pub struct Prefab {
     pub path: String,
     // insertion order, sort of most of the time alphabetical but not quite
-    pub vars: IndexMap<String, Constant, RandomState>,
+    pub vars: IndexMap<Interned, Constant, RandomState>,
 }
 
 impl PartialEq for Prefab {
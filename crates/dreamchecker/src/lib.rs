@@ -5,8 +5,11 @@
 extern crate dreammaker as dm;
 use dm::ast::*;
 use dm::constants::{ConstFn, Constant};
-use dm::objtree::{ObjectTree, ProcRef, TypeRef};
-use dm::{Context, DMError, Location, Severity};
+use dm::objtree::{ObjectTree, ProcRef, TypeRef, VarDeclaration};
+use dm::preprocessor::{DefineHistory, DefineMap};
+use dm::docs::DocCollection;
+use dm::intern::Interned;
+use dm::{Context, DMError, FileId, Location, Severity};
 
 use std::collections::{BTreeMap, VecDeque};
 use foldhash::{HashMap, HashMapExt, HashSet, HashSetExt};
@@ -15,6 +18,12 @@ mod type_expr;
 use type_expr::TypeExpr;
 mod switch_rand_range;
 use switch_rand_range::check_switch_rand_range;
+mod switch_duplicate_cases;
+use switch_duplicate_cases::check_switch_duplicate_cases;
+pub mod metrics;
+pub mod report;
+pub mod unused_definitions;
+pub mod verbs;
 
 #[doc(hidden)]  // Intended for the tests only.
 pub mod test_helpers;
@@ -298,17 +307,66 @@ impl<'o> From<StaticType<'o>> for Analysis<'o> {
 // ----------------------------------------------------------------------------
 // Entry points
 
+/// The name of every macro the environment ever defined, none of which is
+/// reported or suggested as a var.
+///
+/// Unlike the `DefineHistory` it is built from, this may be sent to another
+/// thread.
+#[derive(Debug, Clone, Default)]
+pub struct MacroNames {
+    names: HashSet<String>,
+}
+
+impl MacroNames {
+    pub fn new(defines: &DefineHistory) -> MacroNames {
+        MacroNames {
+            names: defines.iter().map(|(_, (name, _))| name.clone()).collect(),
+        }
+    }
+
+    fn contains(&self, name: &str) -> bool {
+        self.names.contains(name)
+    }
+}
+
 /// Run DreamChecker, registering diagnostics to the context.
-pub fn run(context: &Context, objtree: &ObjectTree) {
-    run_inner(context, objtree, false)
+pub fn run(context: &Context, objtree: &ObjectTree, macros: &MacroNames) {
+    run_inner(context, objtree, macros, false)
 }
 
 /// Run DreamChecker, registering diagnostics and printing progress to stdout.
-pub fn run_cli(context: &Context, objtree: &ObjectTree) {
-    run_inner(context, objtree, true)
+pub fn run_cli(context: &Context, objtree: &ObjectTree, macros: &MacroNames) {
+    run_inner(context, objtree, macros, true)
 }
 
-fn run_inner(context: &Context, objtree: &ObjectTree, cli: bool) {
+/// Run only the proc-local checks, on the procs defined in one file.
+///
+/// Whole-program checks, like override validity and the proc call tree, are
+/// skipped, so this is cheap enough to run as that file is edited.
+pub fn run_file(context: &Context, objtree: &ObjectTree, macros: &MacroNames, file: FileId) {
+    let mut analyzer = AnalyzeObjectTree::new(context, objtree, macros);
+    objtree.root().recurse(&mut |ty| {
+        for proc in ty.iter_self_procs() {
+            if let Some(ref code) = proc.get().code {
+                analyzer.gather_settings(proc, code);
+            }
+        }
+    });
+    objtree.root().recurse(&mut |ty| {
+        for proc in ty.iter_self_procs() {
+            if proc.get().location.file != file {
+                continue;
+            }
+            if let Some(ref code) = proc.get().code {
+                analyzer.check_proc(proc, code);
+                metrics::check_proc_metrics(proc, context);
+            }
+        }
+    });
+    analyzer.finish_check_arguments();
+}
+
+fn run_inner(context: &Context, objtree: &ObjectTree, macros: &MacroNames, cli: bool) {
     macro_rules! cli_println {
         ($($rest:tt)*) => {
             if cli { println!($($rest)*) }
@@ -319,8 +377,10 @@ fn run_inner(context: &Context, objtree: &ObjectTree, cli: bool) {
     cli_println!("Analyzing variables...\n");
 
     check_var_defs(objtree, context);
+    check_proc_defs(objtree, context);
+    verbs::check_verb_settings(objtree, context);
 
-    let mut analyzer = AnalyzeObjectTree::new(context, objtree);
+    let mut analyzer = AnalyzeObjectTree::new(context, objtree, macros);
 
     cli_println!("============================================================");
     cli_println!("Gathering proc settings...\n");
@@ -338,6 +398,7 @@ fn run_inner(context: &Context, objtree: &ObjectTree, cli: bool) {
         for proc in ty.iter_self_procs() {
             if let Some(ref code) = proc.get().code {
                 analyzer.check_proc(proc, code);
+                metrics::check_proc_metrics(proc, context);
             }
         }
     });
@@ -347,11 +408,13 @@ fn run_inner(context: &Context, objtree: &ObjectTree, cli: bool) {
     objtree.root().recurse(&mut |ty| {
         for proc in ty.iter_self_procs() {
             analyzer.check_kwargs(proc);
+            analyzer.check_override_signature(proc);
             analyzer.propagate_violations(proc);
         }
     });
 
     analyzer.finish_check_kwargs();
+    analyzer.finish_check_arguments();
 
     cli_println!("============================================================");
     cli_println!("Analyzing proc call tree...\n");
@@ -380,6 +443,24 @@ struct KwargInfo {
     bad_overrides_at: BTreeMap<String, BadOverride>,
 }
 
+/// A call whose argument count is checked once every proc is analyzed.
+struct ArgumentCount<'o> {
+    location: Location,
+    proc: ProcRef<'o>,
+    is_exact: bool,
+    positional: usize,
+    keywords: Vec<&'o str>,
+}
+
+/// A proc which must not sleep, and why.
+struct SleepRoot<'o> {
+    proc: ProcRef<'o>,
+    why: &'static str,
+    location: Location,
+    note: String,
+    severity: Severity,
+}
+
 /// Struct for SpacemanDMM_* directives
 struct ProcDirective<'o> {
     directive: HashMap<ProcRef<'o>, (bool, Location)>,
@@ -546,11 +627,20 @@ pub struct AnalyzeObjectTree<'o> {
     sleep_exempt: ProcDirective<'o>,
     must_be_pure: ProcDirective<'o>,
     can_be_redefined: ProcDirective<'o>,
+    variadic: ProcDirective<'o>,
+    can_change_signature: ProcDirective<'o>,
     // Debug(ProcRef) -> KwargInfo
     used_kwargs: BTreeMap<String, KwargInfo>,
 
     call_tree: HashMap<ProcRef<'o>, Vec<(ProcRef<'o>, Location, bool)>>,
 
+    /// Whether each analyzed proc reads `args`
+    reads_args: HashMap<ProcRef<'o>, bool>,
+    argument_counts: Vec<ArgumentCount<'o>>,
+
+    /// The only type declaring each var or proc name, if there is exactly one
+    only_declaring_types: HashMap<(String, bool), Option<TypeRef<'o>>>,
+
     sleeping_procs: ViolatingProcs<'o>,
     impure_procs: ViolatingProcs<'o>,
     /// Procs with waitfor=0 or waitfor=FALSE
@@ -558,10 +648,15 @@ pub struct AnalyzeObjectTree<'o> {
 
     sleeping_overrides: ViolatingOverrides<'o>,
     impure_overrides: ViolatingOverrides<'o>,
+
+    /// The macros defined before any code, never suggested as names
+    macros: DefineMap,
+    /// The macros the environment defined anywhere.
+    macro_names: &'o MacroNames,
 }
 
 impl<'o> AnalyzeObjectTree<'o> {
-    pub fn new(context: &'o Context, objtree: &'o ObjectTree) -> Self {
+    pub fn new(context: &'o Context, objtree: &'o ObjectTree, macro_names: &'o MacroNames) -> Self {
         let mut return_type = HashMap::default();
         return_type.insert(objtree.root().get_proc("get_step").unwrap(), StaticType::Type(objtree.expect("/turf")).into());
 
@@ -577,16 +672,29 @@ impl<'o> AnalyzeObjectTree<'o> {
             sleep_exempt: ProcDirective::new("SpacemanDMM_allowed_to_sleep", false, true, true),
             must_be_pure: ProcDirective::new("SpacemanDMM_should_be_pure", false, true, true),
             can_be_redefined: ProcDirective::new("SpacemanDMM_can_be_redefined", false, false, false),
+            variadic: ProcDirective::new("SpacemanDMM_variadic_proc", false, false, true),
+            can_change_signature: ProcDirective::new("SpacemanDMM_can_change_signature", false, false, false),
             used_kwargs: Default::default(),
             call_tree: Default::default(),
+            reads_args: Default::default(),
+            argument_counts: Default::default(),
+            only_declaring_types: Default::default(),
             sleeping_procs: Default::default(),
             impure_procs: Default::default(),
             waitfor_procs: Default::default(),
             sleeping_overrides: Default::default(),
             impure_overrides: Default::default(),
+            macros: DefineMap::with_configured(context),
+            macro_names,
         }
     }
 
+    /// Whether the preprocessor would expand a name somewhere, so it is
+    /// never a var.
+    fn is_macro(&self, name: &str) -> bool {
+        self.macros.contains_key(name) || self.macro_names.contains(name)
+    }
+
     /// Analyze a specific proc
     pub fn check_proc(&mut self, proc: ProcRef<'o>, code: &'o [Spanned<Statement>]) {
         self.must_not_sleep.try_copy_from_parent(proc);
@@ -606,6 +714,8 @@ impl<'o> AnalyzeObjectTree<'o> {
             "SpacemanDMM_allowed_to_sleep" => &mut self.sleep_exempt,
             "SpacemanDMM_should_be_pure" => &mut self.must_be_pure,
             "SpacemanDMM_can_be_redefined" => &mut self.can_be_redefined,
+            "SpacemanDMM_variadic_proc" => &mut self.variadic,
+            "SpacemanDMM_can_change_signature" => &mut self.can_change_signature,
             other => {
                 error(location, format!("unknown linter setting {:?}", directive))
                     .with_errortype("unknown_linter_setting")
@@ -637,66 +747,61 @@ impl<'o> AnalyzeObjectTree<'o> {
     }
 
     pub fn check_proc_call_tree(&mut self) {
+        let mut roots = Vec::new();
         for (procref, &(_, location)) in self.must_not_sleep.directive.iter() {
-            if let Some(sleepvec) = self.sleeping_procs.get_violators(*procref) {
-                error(procref.get().location, format!("{} sets SpacemanDMM_should_not_sleep but calls blocking built-in(s)", procref))
-                    .with_note(location, "SpacemanDMM_should_not_sleep set here")
-                    .with_errortype("must_not_sleep")
-                    .with_blocking_builtins(sleepvec)
-                    .register(self.context)
-            }
-            let mut visited = HashSet::<ProcRef<'o>>::new();
-            let mut to_visit = VecDeque::<(ProcRef<'o>, CallStack, bool)>::new();
-            if let Some(procscalled) = self.call_tree.get(procref) {
-                for (proccalled, location, new_context) in procscalled {
-                    let mut callstack = CallStack::default();
-                    callstack.add_step(*proccalled, *location, *new_context);
-                    to_visit.push_back((*proccalled, callstack, *new_context));
-                }
-            }
-            while let Some((nextproc, callstack, new_context)) = to_visit.pop_front() {
-                if !visited.insert(nextproc) {
-                    continue
-                }
-                if self.waitfor_procs.contains(&nextproc) {
-                    continue
-                }
-                if self.sleep_exempt.get(nextproc).is_some() {
-                    continue
-                }
-                if new_context {
+            roots.push(SleepRoot {
+                proc: *procref,
+                why: "sets SpacemanDMM_should_not_sleep",
+                location,
+                note: "SpacemanDMM_should_not_sleep set here".to_owned(),
+                severity: Severity::Error,
+            });
+        }
+
+        // Operators and procs which run during map load can't sleep either.
+        let map_load_procs: Vec<(TypeRef<'o>, ProcRef<'o>)> = self.context.config().dreamchecker.map_load_procs.iter()
+            .filter_map(|path| {
+                let (ty, name) = path.rsplit_once('/')?;
+                let ty = self.objtree.find(ty)?;
+                Some((ty, ty.get_proc(name)?))
+            })
+            .collect();
+        for ty in self.objtree.iter_types() {
+            for procref in ty.iter_self_procs() {
+                if procref.is_builtin()
+                    || self.must_not_sleep.directive.contains_key(&procref)
+                    || self.waitfor_procs.contains(&procref)
+                    || self.sleep_exempt.get(procref).is_some()
+                {
                     continue
                 }
-                if let Some(sleepvec) = self.sleeping_procs.get_violators(nextproc) {
-                    error(procref.get().location, format!("{} sets SpacemanDMM_should_not_sleep but calls blocking proc {}", procref, nextproc))
-                        .with_note(location, "SpacemanDMM_should_not_sleep set here")
-                        .with_errortype("must_not_sleep")
-                        .with_callstack(&callstack)
-                        .with_blocking_builtins(sleepvec)
-                        .register(self.context)
-                } else if let Some(overridesleep) = self.sleeping_overrides.get_override_violators(nextproc) {
-                    for child_violator in overridesleep {
-                        if procref.ty().is_subtype_of(&nextproc.ty()) && !child_violator.ty().is_subtype_of(&procref.ty()) {
-                            continue
-                        }
-                        error(procref.get().location, format!("{} calls {} which has override child proc that sleeps {}", procref, nextproc, child_violator))
-                            .with_note(location, "SpacemanDMM_should_not_sleep set here")
-                            .with_errortype("must_not_sleep")
-                            .with_callstack(&callstack)
-                            .with_blocking_builtins(self.sleeping_procs.get_violators(*child_violator).unwrap())
-                            .register(self.context)
-                    }
-                }
-                if let Some(calledvec) = self.call_tree.get(&nextproc) {
-                    for (proccalled, location, new_context) in calledvec.iter() {
-                        let mut newstack = callstack.clone();
-                        newstack.add_step(*proccalled, *location, *new_context);
-                        to_visit.push_back((*proccalled, newstack, *new_context));
-                    }
+                if procref.name().starts_with("operator") {
+                    roots.push(SleepRoot {
+                        proc: procref,
+                        why: "is an operator",
+                        location: procref.get().location,
+                        note: "operators must not sleep".to_owned(),
+                        severity: Severity::Warning,
+                    });
+                } else if let Some((_, base)) = map_load_procs.iter()
+                    .find(|(base_ty, base)| base.name() == procref.name() && ty.is_subtype_of(base_ty))
+                {
+                    roots.push(SleepRoot {
+                        proc: procref,
+                        why: "runs during map load",
+                        location: base.get().location,
+                        note: format!("{} runs during map load", base),
+                        severity: Severity::Warning,
+                    });
                 }
             }
         }
 
+        let can_sleep = self.procs_which_can_sleep();
+        for root in roots {
+            self.check_must_not_sleep(&root, &can_sleep);
+        }
+
         for (procref, (_, location)) in self.must_be_pure.directive.iter() {
             if let Some(impurevec) = self.impure_procs.get_violators(*procref) {
                 error(procref.get().location, format!("{} does impure operations", procref))
@@ -749,6 +854,110 @@ impl<'o> AnalyzeObjectTree<'o> {
         }
     }
 
+    /// Find the procs which sleep, directly or through the procs they call,
+    /// so that checking each proc which must not sleep only follows calls
+    /// into them.
+    fn procs_which_can_sleep(&self) -> HashSet<ProcRef<'o>> {
+        let exempt = |procref: ProcRef<'o>| self.waitfor_procs.contains(&procref) || self.sleep_exempt.get(procref).is_some();
+
+        let mut callers = HashMap::<ProcRef<'o>, Vec<ProcRef<'o>>>::new();
+        let mut can_sleep = HashSet::<ProcRef<'o>>::new();
+        let mut to_visit = Vec::new();
+        for (caller, calledvec) in self.call_tree.iter() {
+            for &(proccalled, _, new_context) in calledvec.iter() {
+                if !new_context {
+                    callers.entry(proccalled).or_default().push(*caller);
+                }
+                if !exempt(proccalled)
+                    && (self.sleeping_procs.get_violators(proccalled).is_some() || self.sleeping_overrides.get_override_violators(proccalled).is_some())
+                    && can_sleep.insert(proccalled)
+                {
+                    to_visit.push(proccalled);
+                }
+            }
+        }
+        while let Some(procref) = to_visit.pop() {
+            for &caller in callers.get(&procref).into_iter().flatten() {
+                if !exempt(caller) && can_sleep.insert(caller) {
+                    to_visit.push(caller);
+                }
+            }
+        }
+        can_sleep
+    }
+
+    /// Report the blocking calls made, directly or through other procs, by
+    /// a proc which must not sleep.
+    fn check_must_not_sleep(&self, root: &SleepRoot<'o>, can_sleep: &HashSet<ProcRef<'o>>) {
+        let procref = root.proc;
+        if let Some(sleepvec) = self.sleeping_procs.get_violators(procref) {
+            error(procref.get().location, format!("{} {} but calls blocking built-in(s)", procref, root.why))
+                .with_note(root.location, root.note.as_str())
+                .set_severity(root.severity)
+                .with_errortype("must_not_sleep")
+                .with_blocking_builtins(sleepvec)
+                .register(self.context)
+        }
+        let mut visited = HashSet::<ProcRef<'o>>::new();
+        let mut to_visit = VecDeque::<(ProcRef<'o>, CallStack, bool)>::new();
+        if let Some(procscalled) = self.call_tree.get(&procref) {
+            for (proccalled, location, new_context) in procscalled {
+                if !can_sleep.contains(proccalled) {
+                    continue
+                }
+                let mut callstack = CallStack::default();
+                callstack.add_step(*proccalled, *location, *new_context);
+                to_visit.push_back((*proccalled, callstack, *new_context));
+            }
+        }
+        while let Some((nextproc, callstack, new_context)) = to_visit.pop_front() {
+            if !visited.insert(nextproc) {
+                continue
+            }
+            if self.waitfor_procs.contains(&nextproc) {
+                continue
+            }
+            if self.sleep_exempt.get(nextproc).is_some() {
+                continue
+            }
+            if new_context {
+                continue
+            }
+            if let Some(sleepvec) = self.sleeping_procs.get_violators(nextproc) {
+                error(procref.get().location, format!("{} {} but calls blocking proc {}", procref, root.why, nextproc))
+                    .with_note(root.location, root.note.as_str())
+                    .set_severity(root.severity)
+                    .with_errortype("must_not_sleep")
+                    .with_callstack(&callstack)
+                    .with_blocking_builtins(sleepvec)
+                    .register(self.context)
+            } else if let Some(overridesleep) = self.sleeping_overrides.get_override_violators(nextproc) {
+                for child_violator in overridesleep {
+                    if procref.ty().is_subtype_of(&nextproc.ty()) && !child_violator.ty().is_subtype_of(&procref.ty()) {
+                        continue
+                    }
+                    error(procref.get().location, format!("{} calls {} which has override child proc that sleeps {}", procref, nextproc, child_violator))
+                        .with_note(root.location, root.note.as_str())
+                        .set_severity(root.severity)
+                        .with_errortype("must_not_sleep")
+                        .with_callstack(&callstack)
+                        .with_blocking_builtins(self.sleeping_procs.get_violators(*child_violator).unwrap())
+                        .register(self.context)
+                }
+            }
+            if let Some(calledvec) = self.call_tree.get(&nextproc) {
+                for (proccalled, location, new_context) in calledvec.iter() {
+                    if !can_sleep.contains(proccalled) {
+                        continue
+                    }
+                    let mut newstack = callstack.clone();
+                    newstack.add_step(*proccalled, *location, *new_context);
+                    to_visit.push_back((*proccalled, newstack, *new_context));
+                }
+            }
+        }
+    }
+
     /// Gather and store set directives for the given proc using the provided code body and already existing flags
     pub fn gather_settings(&mut self, proc: ProcRef<'o>, code: &'o [Spanned<Statement>]) {
         let proc_location = proc.get().location;
@@ -890,7 +1099,7 @@ impl<'o> AnalyzeObjectTree<'o> {
 
                 if !missing.is_empty() {
                     kwargs.bad_overrides_at.insert(
-                        proc.ty().path.to_owned(),
+                        proc.ty().path.to_string(),
                         BadOverride { missing, location: proc.location });
                 }
             }
@@ -898,6 +1107,108 @@ impl<'o> AnalyzeObjectTree<'o> {
         }
     }
 
+    /// Compare an override's parameters against those of the proc it
+    /// overrides. Dropped parameters which callers pass by name are reported
+    /// by `finish_check_kwargs`, and added trailing parameters are fine.
+    pub fn check_override_signature(&self, proc: ProcRef<'o>) {
+        let Some(parent) = proc.parent_proc() else { return };
+        if parent.is_builtin() || proc.get_declaration().is_none_or(|decl| decl.location.is_builtins()) {
+            return;
+        }
+        if matches!(self.can_change_signature.get(proc), Some((true, _))) {
+            return;
+        }
+
+        let param_type = |param: &Parameter| {
+            if param.var_type.type_path.is_empty() {
+                return None;
+            }
+            crate::static_type(self.objtree, param.location, &param.var_type.type_path).ok()?.basic_type()
+        };
+        let mismatch = |location: Location, desc: String, severity: Severity| {
+            error(location, desc)
+                .with_note(parent.location, format!("overrides {}", parent))
+                .with_errortype("override_signature")
+                .set_severity(severity)
+                .register(self.context);
+        };
+
+        for (idx, base) in parent.parameters.iter().enumerate() {
+            let Some(param) = proc.parameters.get(idx) else {
+                mismatch(proc.location, format!("override of {} drops parameter {:?}", parent, base.name), Severity::Hint);
+                continue;
+            };
+            if param.name != base.name {
+                mismatch(param.location, format!("override of {} renames parameter {:?} to {:?}", parent, base.name, param.name), Severity::Warning);
+            }
+            if let (Some(base_ty), Some(ty)) = (param_type(base), param_type(param)) {
+                if !ty.is_subtype_of(base_ty.get()) && !base_ty.is_subtype_of(ty.get()) {
+                    mismatch(param.location, format!("override of {} declares parameter {:?} as {} instead of {}", parent, param.name, ty, base_ty), Severity::Warning);
+                }
+            }
+        }
+    }
+
+    /// The only type in the tree which declares the var or proc `name`.
+    fn only_declaring_type(&mut self, name: &str, is_proc: bool) -> Option<TypeRef<'o>> {
+        let objtree = self.objtree;
+        *self.only_declaring_types.entry((name.to_owned(), is_proc)).or_insert_with(|| {
+            let mut declaring = objtree.iter_types().filter(|ty| if is_proc {
+                ty.get().procs.get(name).is_some_and(|proc| proc.declaration.is_some())
+            } else {
+                ty.get().vars.get(name).is_some_and(|var| var.declaration.is_some())
+            });
+            let only = declaring.next()?;
+            match declaring.next() {
+                Some(_) => None,
+                None => Some(only),
+            }
+        })
+    }
+
+    /// Check the argument counts of calls against the procs they call
+    pub fn finish_check_arguments(&self) {
+        // Procs which weren't analyzed might read `args`, so assume they do.
+        let accepts = |proc: ProcRef<'o>, count: usize| {
+            proc.parameters.len() >= count
+                || self.reads_args.get(&proc).copied().unwrap_or(true)
+                || matches!(self.variadic.get_self_or_parent(proc), Some((_, true, _)))
+        };
+
+        for call in self.argument_counts.iter() {
+            let proc = call.proc;
+            let count = call.positional + call.keywords.len();
+            if count > proc.parameters.len() {
+                let accepted = accepts(proc, count)
+                    || (!call.is_exact && proc.ty().overrides_of(proc.name()).any(|child| accepts(child, count)));
+                if !accepted {
+                    error(call.location, format!("too many arguments to {}: expected at most {}, found {}", proc, proc.parameters.len(), count))
+                        .with_note(proc.location, format!("{} is defined here", proc))
+                        .with_errortype("too_many_arguments")
+                        .set_severity(Severity::Warning)
+                        .register(self.context);
+                }
+                continue;
+            }
+
+            if matches!(self.variadic.get_self_or_parent(proc), Some((_, true, _))) {
+                continue;
+            }
+            let missing: Vec<&str> = proc.parameters.iter()
+                .skip(call.positional)
+                .filter(|param| param.default.is_none() && !call.keywords.contains(&param.name.as_str()))
+                .map(|param| param.name.as_str())
+                .collect();
+            if !missing.is_empty() {
+                error(call.location, format!("missing arguments to {}: {}", proc, missing.join(", ")))
+                    .with_note(proc.location, format!("{} is defined here", proc))
+                    .with_errortype("missing_arguments")
+                    .set_severity(Severity::Hint)
+                    .register(self.context);
+            }
+        }
+    }
+
     /// Finish analyzing kwargs for missing overrides
     pub fn finish_check_kwargs(&self) {
         for (base_procname, kwarg_info) in self.used_kwargs.iter() {
@@ -970,10 +1281,327 @@ fn static_type<'o>(objtree: &'o ObjectTree, location: Location, mut of: &[String
     }
 }
 
+/// Whether a condition is always true or false, folding constant expressions.
+fn condition_truthiness(location: Location, condition: &Expression) -> Option<bool> {
+    condition.is_truthy().or_else(|| {
+        let constant = condition.clone().simple_evaluate(location).ok()?;
+        Some(constant.to_bool())
+    })
+}
+
+/// The location of the statement a block ends with, looking into nested blocks.
+fn block_end(block: &[Spanned<Statement>]) -> Option<Location> {
+    let last = block.last()?;
+    let nested: Option<&[Spanned<Statement>]> = match &last.elem {
+        Statement::If { arms, else_arm } => else_arm.as_deref().or_else(|| arms.last().map(|(_, block)| &block[..])),
+        Statement::Switch { cases, default, .. } => default.as_deref().or_else(|| cases.last().map(|(_, block)| &block[..])),
+        Statement::While { block, .. }
+        | Statement::DoWhile { block, .. }
+        | Statement::ForInfinite { block }
+        | Statement::ForLoop { block, .. }
+        | Statement::Spawn { block, .. }
+        | Statement::Label { block, .. } => Some(block),
+        Statement::ForList(for_list) => Some(&for_list.block),
+        Statement::ForRange(for_range) => Some(&for_range.block),
+        Statement::TryCatch { catch_block, .. } => Some(catch_block),
+        _ => None,
+    };
+    Some(nested.and_then(block_end).unwrap_or(last.location))
+}
+
+/// Whether evaluating an expression may do more than produce its value,
+/// which is assumed of any call.
+fn has_side_effects(expr: &Expression) -> bool {
+    match expr {
+        Expression::Base { term, follow } => term_has_side_effects(&term.elem) || follow.iter().any(|each| match &each.elem {
+            Follow::Call(..) => true,
+            Follow::Unary(op) => matches!(op, UnaryOp::PreIncr | UnaryOp::PostIncr | UnaryOp::PreDecr | UnaryOp::PostDecr),
+            Follow::Index(_, index) => has_side_effects(index),
+            _ => false,
+        }),
+        Expression::BinaryOp { lhs, rhs, .. } => has_side_effects(lhs) || has_side_effects(rhs),
+        Expression::AssignOp { .. } => true,
+        Expression::TernaryOp { cond, if_, else_ } => has_side_effects(cond) || has_side_effects(if_) || has_side_effects(else_),
+    }
+}
+
+fn term_has_side_effects(term: &Term) -> bool {
+    match term {
+        Term::Null
+        | Term::Int(_)
+        | Term::Float(_)
+        | Term::Ident(_)
+        | Term::String(_)
+        | Term::Resource(_)
+        | Term::As(_)
+        | Term::Prefab(_)
+        | Term::GlobalIdent(_) => false,
+        Term::Expr(expr) => has_side_effects(expr),
+        Term::InterpString(_, parts) => parts.iter().any(|(expr, _)| expr.elem.as_ref().is_some_and(has_side_effects)),
+        // `list("key" = value)` is an association, not an assignment.
+        Term::List(args) => args.iter().any(|arg| match arg {
+            Expression::AssignOp { op: AssignOp::Assign, lhs, rhs, .. } => has_side_effects(lhs) || has_side_effects(rhs),
+            arg => has_side_effects(arg),
+        }),
+        _ => true,
+    }
+}
+
+/// The var a condition checks to be null, as in `!x`, `x == null`, or
+/// `isnull(x)`.
+fn null_checked(condition: &Expression) -> Option<&str> {
+    fn ident(expr: &Expression) -> Option<&str> {
+        match expr.as_term() {
+            Some(Term::Ident(name)) => Some(name),
+            Some(Term::Expr(expr)) => ident(expr),
+            _ => None,
+        }
+    }
+    match condition {
+        Expression::Base { term, follow } => match (&term.elem, &follow[..]) {
+            (Term::Ident(name), [not]) if matches!(not.elem, Follow::Unary(UnaryOp::Not)) => Some(name),
+            (Term::Call(name, args), []) if name == "isnull" && args.len() == 1 => ident(&args[0]),
+            (Term::Expr(expr), []) => null_checked(expr),
+            _ => None,
+        },
+        Expression::BinaryOp { op: BinaryOp::Eq, lhs, rhs } => match (lhs.as_term(), rhs.as_term()) {
+            (_, Some(Term::Null)) => ident(lhs),
+            (Some(Term::Null), _) => ident(rhs),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Collect the vars an expression mentions by name.
+fn mentioned_names<'a>(expr: &'a Expression, names: &mut Vec<&'a str>) {
+    match expr {
+        Expression::Base { term, follow } => {
+            match &term.elem {
+                Term::Ident(name) => names.push(name),
+                Term::Expr(expr) => mentioned_names(expr, names),
+                Term::Call(_, args) => args.iter().for_each(|arg| mentioned_names(arg, names)),
+                _ => {},
+            }
+            for each in follow.iter() {
+                if let Follow::Call(_, _, args) = &each.elem {
+                    args.iter().for_each(|arg| mentioned_names(arg, names));
+                }
+            }
+        },
+        Expression::BinaryOp { lhs, rhs, .. } | Expression::AssignOp { lhs, rhs, .. } => {
+            mentioned_names(lhs, names);
+            mentioned_names(rhs, names);
+        },
+        Expression::TernaryOp { cond, if_, else_ } => {
+            mentioned_names(cond, names);
+            mentioned_names(if_, names);
+            mentioned_names(else_, names);
+        },
+    }
+}
+
+/// Collect the vars an expression assigns to by name.
+fn assigned_names<'a>(expr: &'a Expression, names: &mut Vec<&'a str>) {
+    match expr {
+        Expression::Base { term, follow } => {
+            match &term.elem {
+                Term::Ident(name) if follow.iter().any(|each| matches!(each.elem,
+                    Follow::Unary(UnaryOp::PreIncr | UnaryOp::PostIncr | UnaryOp::PreDecr | UnaryOp::PostDecr))) => names.push(name),
+                Term::Expr(expr) => assigned_names(expr, names),
+                Term::Call(_, args) => args.iter().for_each(|arg| assigned_names(arg, names)),
+                _ => {},
+            }
+            for each in follow.iter() {
+                if let Follow::Call(_, _, args) = &each.elem {
+                    args.iter().for_each(|arg| assigned_names(arg, names));
+                }
+            }
+        },
+        Expression::AssignOp { lhs, rhs, .. } => {
+            if let Some(Term::Ident(name)) = lhs.as_term() {
+                names.push(name);
+            }
+            assigned_names(rhs, names);
+        },
+        Expression::BinaryOp { lhs, rhs, .. } => {
+            assigned_names(lhs, names);
+            assigned_names(rhs, names);
+        },
+        Expression::TernaryOp { cond, if_, else_ } => {
+            assigned_names(cond, names);
+            assigned_names(if_, names);
+            assigned_names(else_, names);
+        },
+    }
+}
+
+/// Collect the vars a statement or any statement nested in it assigns to or
+/// declares by name.
+fn assigned_in_statement<'a>(statement: &'a Statement, names: &mut Vec<&'a str>) {
+    let block = |block: &'a [Spanned<Statement>], names: &mut Vec<&'a str>| {
+        block.iter().for_each(|stmt| assigned_in_statement(&stmt.elem, names));
+    };
+    match statement {
+        Statement::Expr(expr)
+        | Statement::Return(Some(expr))
+        | Statement::Throw(expr)
+        | Statement::Crash(Some(expr))
+        | Statement::Del(expr) => assigned_names(expr, names),
+        Statement::Var(var) => names.push(&var.name),
+        Statement::Vars(vars) => names.extend(vars.iter().map(|var| var.name.as_str())),
+        Statement::If { arms, else_arm } => {
+            for (condition, body) in arms.iter() {
+                assigned_names(&condition.elem, names);
+                block(body, names);
+            }
+            if let Some(else_arm) = else_arm {
+                block(else_arm, names);
+            }
+        },
+        Statement::While { condition, block: body } => {
+            assigned_names(condition, names);
+            block(body, names);
+        },
+        Statement::DoWhile { block: body, condition } => {
+            block(body, names);
+            assigned_names(&condition.elem, names);
+        },
+        Statement::ForInfinite { block: body }
+        | Statement::Spawn { block: body, .. }
+        | Statement::Label { block: body, .. } => block(body, names),
+        Statement::ForLoop { init, test, inc, block: body } => {
+            for each in [init, inc].into_iter().flatten() {
+                assigned_in_statement(each, names);
+            }
+            if let Some(test) = test {
+                assigned_names(test, names);
+            }
+            block(body, names);
+        },
+        Statement::ForList(for_list) => {
+            names.push(&for_list.name);
+            block(&for_list.block, names);
+        },
+        Statement::ForRange(for_range) => {
+            names.push(&for_range.name);
+            block(&for_range.block, names);
+        },
+        Statement::Switch { cases, default, .. } => {
+            for (_, body) in cases.iter() {
+                block(body, names);
+            }
+            if let Some(default) = default {
+                block(default, names);
+            }
+        },
+        Statement::TryCatch { try_block, catch_params, catch_block } => {
+            block(try_block, names);
+            names.extend(catch_params.iter().filter_map(|param| param.last()).map(String::as_str));
+            block(catch_block, names);
+        },
+        _ => {},
+    }
+}
+
+fn is_typepath(analysis: &Analysis) -> bool {
+    matches!(analysis.value, Some(Constant::Prefab(_)))
+        || analysis.aset.set.iter().any(|assumption| matches!(assumption, Assumption::IsPath(true, _)))
+}
+
+fn is_string(analysis: &Analysis) -> bool {
+    matches!(analysis.value, Some(Constant::String(_)))
+        || analysis.aset.set.contains(&Assumption::IsText(true))
+}
+
+/// Whether an expression is a bare `..()` call.
+fn is_parent_call(expr: &Expression) -> bool {
+    matches!(expr.as_term(), Some(Term::ParentCall(_)))
+}
+
+/// Whether a statement always calls `..()`, either on its own or as the
+/// value of an assignment, declaration, or return.
+fn calls_parent_unconditionally(statement: &Statement) -> bool {
+    match statement {
+        Statement::Expr(Expression::AssignOp { rhs, .. }) => is_parent_call(rhs),
+        Statement::Expr(expr) | Statement::Return(Some(expr)) => is_parent_call(expr),
+        Statement::Var(var) => var.value.as_ref().is_some_and(is_parent_call),
+        _ => false,
+    }
+}
+
+/// Whether a statement may leave the proc or jump elsewhere in it.
+fn may_exit(statement: &Statement) -> bool {
+    let any = |block: &[Spanned<Statement>]| block.iter().any(|stmt| may_exit(&stmt.elem));
+    match statement {
+        Statement::Return(_)
+        | Statement::Throw(_)
+        | Statement::Crash(_)
+        | Statement::Goto(_)
+        | Statement::Label { .. } => true,
+        Statement::If { arms, else_arm } => arms.iter().any(|(_, block)| any(block)) || else_arm.as_deref().is_some_and(any),
+        Statement::Switch { cases, default, .. } => cases.iter().any(|(_, block)| any(block)) || default.as_deref().is_some_and(any),
+        Statement::While { block, .. }
+        | Statement::DoWhile { block, .. }
+        | Statement::ForInfinite { block }
+        | Statement::ForLoop { block, .. }
+        | Statement::Spawn { block, .. } => any(block),
+        Statement::ForList(for_list) => any(&for_list.block),
+        Statement::ForRange(for_range) => any(&for_range.block),
+        Statement::TryCatch { try_block, catch_block, .. } => any(try_block) || any(catch_block),
+        _ => false,
+    }
+}
+
 fn error<S: Into<String>>(location: Location, desc: S) -> DMError {
     DMError::new(location, desc).with_component(dm::Component::DreamChecker)
 }
 
+/// The name within an edit distance of 1-2 from `name`, for suggestions.
+/// Names of three characters or fewer only allow a distance of 1, and names
+/// which the preprocessor would expand, like `TRUE`, are skipped.
+fn nearest_name<'a>(names: impl Iterator<Item = &'a Interned>, name: &str, is_macro: impl Fn(&str) -> bool) -> Option<&'a str> {
+    let max_distance = if name.len() <= 3 { 1 } else { 2 };
+    names
+        .filter(|candidate| !is_macro(candidate))
+        .map(|candidate| (candidate, edit_distance(candidate, name)))
+        .filter(|&(_, distance)| (1..=max_distance).contains(&distance) && distance < name.len())
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(candidate, _)| candidate.as_str())
+}
+
+/// The Levenshtein distance between two names, ignoring case.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().flat_map(char::to_lowercase).collect();
+    let b: Vec<char> = b.chars().flat_map(char::to_lowercase).collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, &ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Find the type which declares the var `name` that `ty` has.
+fn declaring_type<'o>(ty: TypeRef<'o>, name: &str) -> Option<TypeRef<'o>> {
+    ty.iter_parent_types().find(|parent| parent.get().vars.get(name).is_some_and(|var| var.declaration.is_some()))
+}
+
+/// The severity of a final, private, or protected violation, which may be
+/// lowered to a warning while a codebase migrates to the annotations.
+fn access_severity(context: &Context) -> Severity {
+    if context.config().dreamchecker.access_violations_as_warnings {
+        Severity::Warning
+    } else {
+        Severity::Error
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Variable analyzer
 
@@ -1007,21 +1635,23 @@ pub fn check_var_defs(objtree: &ObjectTree, context: &Context) {
                     if typevar.value.location.is_builtins() {
                         continue;
                     }
-                    DMError::new(mydecl.location, format!("{} redeclares var {:?}", path, varname))
+                    error(mydecl.location, format!("{} redeclares var {:?}", path, varname))
                         .with_note(decl.location, format!("declared on {} here", parent.path))
                         .register(context);
                 }
 
                 if decl.var_type.flags.is_final() {
-                    DMError::new(typevar.value.location, format!("{} overrides final var {:?}", path, varname))
+                    error(typevar.value.location, format!("{} overrides final var {:?}", path, varname))
                         .with_errortype("final_var")
+                        .set_severity(access_severity(context))
                         .with_note(decl.location, format!("declared final on {} here", parent.path))
                         .register(context);
                 }
 
                 if decl.var_type.flags.is_private() {
-                    DMError::new(typevar.value.location, format!("{} overrides private var {:?}", path, varname))
+                    error(typevar.value.location, format!("{} overrides private var {:?}", path, varname))
                         .with_errortype("private_var")
+                        .set_severity(access_severity(context))
                         .with_note(decl.location, format!("declared private on {} here", parent.path))
                         .register(context);
                 }
@@ -1030,6 +1660,49 @@ pub fn check_var_defs(objtree: &ObjectTree, context: &Context) {
     }
 }
 
+/// Report proc overrides which no type on the current parent chain declares,
+/// as happens when `parent_type` moves a type out from under the overrides
+/// that were written against its implicit parent.
+pub fn check_proc_defs(objtree: &ObjectTree, context: &Context) {
+    for typeref in objtree.iter_types() {
+        for (procname, typeproc) in typeref.procs.iter() {
+            // Operator overloads need no declaration.
+            if typeproc.declaration.is_some() || procname.starts_with("operator") {
+                continue;
+            }
+            let Some(first) = typeproc.value.first() else { continue };
+            if first.location.is_builtins() || typeref.get_proc_declaration(procname).is_some() {
+                continue;
+            }
+
+            let mut error = error(first.location, format!("override of {:?} but no ancestor of {} declares it", procname, typeref.path))
+                .with_errortype("override_without_declaration");
+            if let Some((path, decl)) = nearest_proc_declaration(objtree, typeref, procname) {
+                error = error.with_note(decl.location, format!("declared on {} here", path));
+            }
+            error.register(context);
+        }
+    }
+}
+
+/// Find a declaration of the named proc elsewhere in the tree, preferring
+/// the type's path ancestors since those are what the override was most
+/// likely written against.
+fn nearest_proc_declaration<'o>(objtree: &'o ObjectTree, typeref: TypeRef<'o>, procname: &str) -> Option<(&'o str, &'o dm::objtree::ProcDeclaration)> {
+    let declared_on = |ty: TypeRef<'o>| {
+        let ty = ty.get();
+        ty.procs.get(procname)?.declaration.as_ref().map(|decl| (ty.path.as_str(), decl))
+    };
+    let mut current = typeref.parent_path();
+    while let Some(ty) = current {
+        if let Some(found) = declared_on(ty) {
+            return Some(found);
+        }
+        current = ty.parent_path();
+    }
+    objtree.iter_types().find_map(declared_on)
+}
+
 // ----------------------------------------------------------------------------
 // Procedure analyzer
 #[derive(Debug)]
@@ -1122,14 +1795,37 @@ impl ControlFlow {
 struct LocalVar<'o> {
     location: Location,
     analysis: Analysis<'o>,
+    /// The index of this var's entry in `AnalyzeProc::declared`, if its use
+    /// is being tracked.
+    declared: Option<usize>,
 }
 
 impl<'o> From<Analysis<'o>> for LocalVar<'o> {
     fn from(analysis: Analysis<'o>) -> Self {
-        LocalVar { location: Location::default(), analysis }
+        LocalVar { location: Location::default(), analysis, declared: None }
     }
 }
 
+/// A local var or parameter which should be reported if it is never read.
+struct Declared<'o> {
+    name: &'o str,
+    location: Location,
+    is_parameter: bool,
+    read: bool,
+}
+
+/// A value assigned to a local var, which should be reported if it is never
+/// read.
+struct Store {
+    var: usize,
+    /// The location of the value which was assigned.
+    location: Location,
+    /// Where the value ends.
+    end: Location,
+    has_side_effects: bool,
+    read: bool,
+}
+
 struct AnalyzeProc<'o, 's> {
     env: &'s mut AnalyzeObjectTree<'o>,
     context: &'o Context,
@@ -1138,12 +1834,43 @@ struct AnalyzeProc<'o, 's> {
     proc_ref: ProcRef<'o>,
     calls_parent: bool,
     inside_newcontext: u32,
+    declared: Vec<Declared<'o>>,
+    reads_args: bool,
+    stores: Vec<Store>,
+    /// The stores which may still be read on the path being visited.
+    live_stores: Vec<usize>,
+    /// The vars read in each loop being visited, innermost last.
+    loop_reads: Vec<HashSet<usize>>,
+    inside_try: u32,
+    /// Vars which have had a reference taken, and so may be read through it.
+    referenced: HashSet<usize>,
+    uses_goto: bool,
+    /// Local vars known to be null in the block being visited, with where
+    /// they became null and how.
+    null_locals: HashMap<String, (Location, &'static str)>,
+    /// A var known to be null at the start of the next block visited.
+    null_in_branch: Option<(String, (Location, &'static str))>,
+    /// The type this proc or the proc it overrides declares it returns.
+    declared_return: Option<(TypeRef<'o>, ProcRef<'o>)>,
+    assigns_dot: bool,
 }
 
 impl<'o, 's> AnalyzeProc<'o, 's> {
     fn new(env: &'s mut AnalyzeObjectTree<'o>, context: &'o Context, objtree: &'o ObjectTree, proc_ref: ProcRef<'o>) -> Self {
         let ty = proc_ref.ty();
 
+        let mut declared_return = None;
+        let mut next = Some(proc_ref);
+        while let Some(current) = next {
+            if let Some(return_type) = env.return_type.get(&current) {
+                if let TypeExpr::Static(static_ty) = return_type {
+                    declared_return = static_ty.basic_type().map(|ty| (ty, current));
+                }
+                break;
+            }
+            next = current.parent_proc();
+        }
+
         AnalyzeProc {
             env,
             context,
@@ -1152,70 +1879,402 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
             proc_ref,
             calls_parent: false,
             inside_newcontext: 0,
+            declared: Vec::new(),
+            reads_args: false,
+            stores: Vec::new(),
+            live_stores: Vec::new(),
+            loop_reads: Vec::new(),
+            inside_try: 0,
+            referenced: HashSet::new(),
+            uses_goto: false,
+            null_locals: HashMap::new(),
+            null_in_branch: None,
+            declared_return,
+            assigns_dot: false,
+        }
+    }
+
+    pub fn run(&mut self, block: &'o [Spanned<Statement>]) {
+        let mut local_vars = HashMap::<String, LocalVar>::new();
+        local_vars.insert(".".to_owned(), Analysis::empty().into());
+        local_vars.insert("args".to_owned(), Analysis::from_static_type_impure(self.objtree.expect("/list")).into());
+        local_vars.insert("usr".to_owned(), Analysis::from_static_type(self.objtree.expect("/mob")).into());
+        if !self.ty.is_root() {
+            local_vars.insert("src".to_owned(), Analysis::from_static_type(self.ty).into());
+        }
+        local_vars.insert("global".to_owned(), Analysis {
+            static_ty: StaticType::Type(self.objtree.root()),
+            aset: assumption_set![Assumption::IsNull(false)],
+            value: None,
+            fix_hint: None,
+            is_impure: Some(true),
+        }.into());
+
+        // Overrides must accept what their parent does, and empty bodies are
+        // there to be overridden, so only check the parameters of the rest.
+        let check_parameters = self.proc_ref.parent_proc().is_none() && !block.is_empty();
+        for param in self.proc_ref.get().parameters.iter() {
+            if self.proc_ref.parent_proc().is_none() {
+                if local_vars.contains_key(param.name.as_str()) {
+                    self.shadows(param.location, &param.name, "builtin var", Location::default(), "shadowed_builtin");
+                } else {
+                    self.check_shadowed_field(param.location, &param.name);
+                }
+            }
+            let mut analysis = self.static_type(param.location, &param.var_type.type_path);
+            analysis.is_impure = Some(true); // all params are impure
+            let declared = if check_parameters {
+                self.declare(&param.name, param.location, true)
+            } else {
+                None
+            };
+            local_vars.insert(param.name.to_owned(), LocalVar {
+                location: self.proc_ref.location,
+                analysis,
+                declared,
+            });
+            //println!("adding parameters {:#?}", self.local_vars);
+        }
+
+        self.visit_block(block, &mut local_vars);
+        self.check_unused();
+        self.check_dead_stores();
+        if self.proc_ref.parent_proc().is_some() {
+            self.check_parent_calls(block);
+        }
+        self.env.reads_args.insert(self.proc_ref, self.reads_args);
+
+        //println!("purity {}", self.is_pure);
+
+        if let Some(parent) = self.proc_ref.parent_proc() {
+            if let Some((proc, true, location)) = self.env.private.get_self_or_parent(self.proc_ref) {
+                if proc != self.proc_ref {
+                    error(self.proc_ref.location, format!("proc overrides private parent, prohibited by {}", proc))
+                    .with_note(location, "prohibited by this private_proc annotation")
+                    .with_errortype("private_proc")
+                    .set_severity(access_severity(self.context))
+                    .register(self.context);
+                }
+            }
+            if let Some((proc, true, location)) = self.env.must_not_override.get_self_or_parent(self.proc_ref) {
+                if proc != self.proc_ref {
+                    error(self.proc_ref.location, format!("proc overrides parent, prohibited by {}", proc))
+                        .with_note(location, "prohibited by this must_not_override annotation")
+                        .with_errortype("must_not_override")
+                        .set_severity(access_severity(self.context))
+                        .register(self.context);
+                }
+            }
+            if !self.calls_parent {
+                match self.env.must_call_parent.get_self_or_parent(self.proc_ref) {
+                    Some((proc, true, location)) => {
+                        error(self.proc_ref.location, format!("proc never calls parent, required by {}", proc))
+                            .with_note(location, "required by this must_call_parent annotation")
+                            .with_errortype("must_call_parent")
+                            .register(self.context);
+                    }
+                    // Lifecycle procs must call their parent unless annotated otherwise.
+                    None if !parent.is_builtin()
+                        && self.context.config().dreamchecker.lifecycle_procs.iter().any(|name| name == self.proc_ref.name()) =>
+                    {
+                        error(self.proc_ref.location, format!("lifecycle proc never calls parent: {}", self.proc_ref))
+                            .with_note(parent.location, format!("overrides {}", parent))
+                            .with_errortype("missing_parent_call")
+                            .set_severity(Severity::Warning)
+                            .register(self.context);
+                    }
+                    _ => {}
+                }
+            }
+            if !parent.is_builtin() && self.proc_ref.ty() == parent.ty()
+                && self.env.can_be_redefined.get_self_or_parent(self.proc_ref).is_none() {
+                error(self.proc_ref.location, format!("redefining proc {}/{}", self.ty, self.proc_ref.name()))
+                    .with_errortype("redefined_proc")
+                    .with_note(parent.location, "previous definition is here")
+                    .set_severity(Severity::Hint)
+                    .register(self.context);
+            }
+        }
+    }
+
+    /// Report a declaration which hides another of the same name.
+    fn shadows(&self, location: Location, name: &str, kind: &str, shadowed: Location, errortype: &'static str) {
+        let mut error = error(location, format!("{:?} shadows a {} of the same name", name, kind))
+            .with_errortype(errortype);
+        error = if errortype == "shadowed_builtin" {
+            // Hiding `src`, `usr`, `args`, or `.` breaks everything after it.
+            error.set_severity(Severity::Error)
+        } else {
+            error.set_severity(Severity::Warning)
+                .with_note(shadowed, format!("{} declared here", kind))
+        };
+        error.register(self.context);
+    }
+
+    /// Report a local var or parameter named after a var the type declares.
+    /// Builtin vars like `loc` are left alone, since shadowing them is idiomatic.
+    fn check_shadowed_field(&self, location: Location, name: &str) {
+        if self.ty.is_root() {
+            return;
+        }
+        if let Some(decl) = self.ty.get_var_declaration(name) {
+            if !decl.location.is_builtins() {
+                self.shadows(location, name, &format!("field of {}", self.ty), decl.location, "shadowed_field");
+            }
+        }
+    }
+
+    /// Report a string used where a typepath is expected, suggesting the path
+    /// it names if there is one.
+    fn string_for_path(&self, location: Location, what: String, string: &Analysis<'o>) {
+        let mut desc = match &string.value {
+            Some(Constant::String(text)) => format!("{} the string {:?}", what, text.as_str()),
+            _ => format!("{} a string", what),
+        };
+        if let Some(Constant::String(text)) = &string.value {
+            if text.starts_with('/') && self.objtree.find(text).is_some() {
+                desc.push_str(&format!("; use the path {} instead", text));
+            }
+        }
+        error(location, desc)
+            .set_severity(Severity::Warning)
+            .with_errortype("string_typepath_mismatch")
+            .register(self.context);
+    }
+
+    /// Check `==` and `!=` between a typepath and a string, which are never
+    /// equal.
+    fn check_path_string_comparison(&self, location: Location, op: BinaryOp, lhs: &Analysis<'o>, rhs: &Analysis<'o>) {
+        if !matches!(op, BinaryOp::Eq | BinaryOp::NotEq | BinaryOp::Equiv | BinaryOp::NotEquiv) {
+            return;
+        }
+        let string = if is_typepath(lhs) && is_string(rhs) {
+            rhs
+        } else if is_string(lhs) && is_typepath(rhs) {
+            lhs
+        } else {
+            return;
+        };
+        self.string_for_path(location, format!("comparing a typepath with \"{}\" to", op), string);
+    }
+
+    /// Report `==` and `!=` against a float which isn't a whole number, as
+    /// such floats are rarely exact.
+    fn check_float_equality(&self, location: Location, op: BinaryOp, lhs: (&Expression, &Analysis<'o>), rhs: (&Expression, &Analysis<'o>), local_vars: &HashMap<String, LocalVar<'o>>) {
+        if !matches!(op, BinaryOp::Eq | BinaryOp::NotEq) {
+            return;
+        }
+        // Comparing against a literal zero is exact, and idiomatic.
+        let is_zero = |expr: &Expression| matches!(expr.as_term(), Some(Term::Int(0)) | Some(Term::Float(0.0)));
+        if is_zero(lhs.0) || is_zero(rhs.0) {
+            return;
+        }
+        let lhs = self.folded_number(location, lhs.0, lhs.1, local_vars);
+        let rhs = self.folded_number(location, rhs.0, rhs.1, local_vars);
+        let Some(float) = [lhs, rhs].into_iter().flatten().find(|number| number.fract() != 0.0) else { return };
+        error(location, format!("comparing with \"{}\" against {}, which may not be represented exactly", op, float))
+            .set_severity(Severity::Warning)
+            .with_errortype("float_equality")
+            .with_note(location, format!("compare a range instead, such as `abs(x - {}) < 0.0001`", float))
+            .register(self.context);
+    }
+
+    /// The number an operand folds to, either as a constant expression or
+    /// through the initializer of the var it names.
+    fn folded_number(&self, location: Location, expr: &Expression, analysis: &Analysis<'o>, local_vars: &HashMap<String, LocalVar<'o>>) -> Option<f32> {
+        let value = match expr.as_term() {
+            Some(Term::Ident(name)) if !local_vars.contains_key(name) => {
+                self.ty.get_value(name).and_then(|value| value.constant.clone())
+            },
+            Some(Term::GlobalIdent(name)) => {
+                self.objtree.root().get_value(name).and_then(|value| value.constant.clone())
+            },
+            _ => analysis.value.clone().or_else(|| expr.clone().simple_evaluate(location).ok()),
+        };
+        match value? {
+            Constant::Float(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    /// Check the arguments of a call against parameters which default to a
+    /// typepath or are declared `as text`.
+    fn check_argument_kinds(&self, location: Location, proc: ProcRef<'o>, parameter: &Parameter, argument: &Analysis<'o>) {
+        let default = parameter.default.as_ref().and_then(Expression::as_term);
+        if matches!(default, Some(Term::Prefab(_))) && is_string(argument) {
+            self.string_for_path(location, format!("parameter {:?} of {} expects a typepath, but is given", parameter.name, proc), argument);
+        } else if (parameter.input_type == Some(InputType::TEXT) || matches!(default, Some(Term::String(_)))) && is_typepath(argument) {
+            error(location, format!("parameter {:?} of {} expects text, but is given a typepath", parameter.name, proc))
+                .set_severity(Severity::Warning)
+                .with_errortype("string_typepath_mismatch")
+                .register(self.context);
+        }
+    }
+
+    /// Check for `istype(x, /path)` calls which can be decided from the
+    /// static type of `x`.
+    fn check_istype(&self, location: Location, value: &Analysis<'o>, path: &Analysis<'o>) {
+        let Some(ty) = value.static_ty.basic_type() else { return };
+        let Some(path) = path.aset.set.iter().find_map(|assumption| match assumption {
+            Assumption::IsPath(true, path) => Some(*path),
+            _ => None,
+        }) else { return };
+        if ty.is_subtype_of(path.get()) {
+            // Still false for null, so this is only a hint.
+            error(location, format!("istype() is always true: {} is already a {}", ty, path))
+                .with_errortype("istype_always_true")
+                .set_severity(Severity::Hint)
+                .register(self.context);
+        } else if !path.is_subtype_of(ty.get()) {
+            error(location, format!("istype() is always false: {} can never be a {}", ty, path))
+                .with_errortype("istype_always_false")
+                .set_severity(Severity::Warning)
+                .register(self.context);
         }
     }
 
-    pub fn run(&mut self, block: &'o [Spanned<Statement>]) {
-        let mut local_vars = HashMap::<String, LocalVar>::new();
-        local_vars.insert(".".to_owned(), Analysis::empty().into());
-        local_vars.insert("args".to_owned(), Analysis::from_static_type_impure(self.objtree.expect("/list")).into());
-        local_vars.insert("usr".to_owned(), Analysis::from_static_type(self.objtree.expect("/mob")).into());
-        if !self.ty.is_root() {
-            local_vars.insert("src".to_owned(), Analysis::from_static_type(self.ty).into());
+    /// Check a returned value against the declared return type, where null
+    /// and subtypes are fine.
+    fn check_return(&self, location: Location, analysis: &Analysis<'o>) {
+        let Some((declared, declared_on)) = self.declared_return else { return };
+        let ty = analysis.static_ty.basic_type().or_else(|| analysis.aset.set.iter().find_map(|assumption| match assumption {
+            Assumption::IsType(true, ty) => Some(*ty),
+            _ => None,
+        }));
+        let found = match (ty, &analysis.value) {
+            (Some(ty), _) if !ty.is_subtype_of(declared.get()) && !declared.is_subtype_of(ty.get()) => ty.to_string(),
+            (None, Some(value @ (Constant::Float(_) | Constant::String(_)))) => value.to_string(),
+            _ => return,
+        };
+        let mut error = error(location, format!("returning {} from {}, which is declared to return {}", found, self.proc_ref, declared))
+            .with_errortype("return_type_mismatch")
+            .set_severity(Severity::Warning);
+        if declared_on != self.proc_ref {
+            error = error.with_note(declared_on.location, format!("return type declared by {}", declared_on));
         }
-        local_vars.insert("global".to_owned(), Analysis {
-            static_ty: StaticType::Type(self.objtree.root()),
-            aset: assumption_set![Assumption::IsNull(false)],
-            value: None,
-            fix_hint: None,
-            is_impure: Some(true),
-        }.into());
+        error.register(self.context);
+    }
 
-        for param in self.proc_ref.get().parameters.iter() {
-            let mut analysis = self.static_type(param.location, &param.var_type.type_path);
-            analysis.is_impure = Some(true); // all params are impure
-            local_vars.insert(param.name.to_owned(), LocalVar {
-                location: self.proc_ref.location,
-                analysis,
+    fn mark_read(&mut self, name: &str, declared: Option<usize>) {
+        if let Some(idx) = declared {
+            self.declared[idx].read = true;
+            let stores = &mut self.stores;
+            self.live_stores.retain(|&store| {
+                let live = stores[store].var != idx;
+                stores[store].read |= !live;
+                live
             });
-            //println!("adding parameters {:#?}", self.local_vars);
+            if let Some(reads) = self.loop_reads.last_mut() {
+                reads.insert(idx);
+            }
+        } else if name == "args" {
+            self.reads_args = true;
         }
+    }
 
-        self.visit_block(block, &mut local_vars);
-
-        //println!("purity {}", self.is_pure);
+    /// Start tracking whether a local var or parameter is read.
+    fn declare(&mut self, name: &'o str, location: Location, is_parameter: bool) -> Option<usize> {
+        if name.starts_with('_') {
+            return None;
+        }
+        self.declared.push(Declared { name, location, is_parameter, read: false });
+        Some(self.declared.len() - 1)
+    }
 
-        if let Some(parent) = self.proc_ref.parent_proc() {
-            if let Some((proc, true, location)) = self.env.private.get_self_or_parent(self.proc_ref) {
-                if proc != self.proc_ref {
-                    error(self.proc_ref.location, format!("proc overrides private parent, prohibited by {}", proc))
-                    .with_note(location, "prohibited by this private_proc annotation")
-                    .with_errortype("private_proc")
+    fn check_unused(&mut self) {
+        for declared in self.declared.iter() {
+            // Parameters may be read through `args` instead.
+            if declared.read || (declared.is_parameter && self.reads_args) {
+                continue;
+            }
+            let end = declared.location.add_columns(declared.name.len() as u16);
+            if declared.is_parameter {
+                error(declared.location, format!("unused parameter: {:?}", declared.name))
+                    .with_end(end)
+                    .set_severity(Severity::Hint)
+                    .with_errortype("unused_param")
+                    .register(self.context);
+            } else {
+                error(declared.location, format!("unused var: {:?}", declared.name))
+                    .with_end(end)
+                    .set_severity(Severity::Warning)
+                    .with_errortype("unused_var")
                     .register(self.context);
-                }
             }
-            if let Some((proc, true, location)) = self.env.must_not_override.get_self_or_parent(self.proc_ref) {
-                if proc != self.proc_ref {
-                    error(self.proc_ref.location, format!("proc overrides parent, prohibited by {}", proc))
-                        .with_note(location, "prohibited by this must_not_override annotation")
-                        .with_errortype("must_not_override")
-                        .register(self.context);
-                }
+        }
+    }
+
+    /// Record a value assigned to a local var, which replaces any previous
+    /// value on this path. Inside a `try` block the previous value may still
+    /// be read by the `catch` block.
+    fn store(&mut self, var: usize, value: &Expression, end: Location) {
+        if self.inside_try == 0 {
+            let stores = &self.stores;
+            self.live_stores.retain(|&store| stores[store].var != var);
+        }
+        self.live_stores.push(self.stores.len());
+        self.stores.push(Store {
+            var,
+            location: value.location(),
+            end,
+            has_side_effects: has_side_effects(value),
+            read: false,
+        });
+    }
+
+    /// Add the stores live on the path just visited to those live after a
+    /// branch.
+    fn merge_live_stores(&self, into: &mut Vec<usize>) {
+        for &store in self.live_stores.iter() {
+            if !into.contains(&store) {
+                into.push(store);
             }
-            if !self.calls_parent {
-                if let Some((proc, true, location)) = self.env.must_call_parent.get_self_or_parent(self.proc_ref) {
-                    error(self.proc_ref.location, format!("proc never calls parent, required by {}", proc))
-                        .with_note(location, "required by this must_call_parent annotation")
-                        .with_errortype("must_call_parent")
-                        .register(self.context);
-                }
+        }
+    }
+
+    fn enter_loop(&mut self) -> Vec<usize> {
+        self.loop_reads.push(HashSet::new());
+        self.live_stores.clone()
+    }
+
+    /// Stores still live at the end of a loop body may be read by the next
+    /// iteration, so any var read in the loop counts as reading them. The
+    /// loop may also not run at all.
+    fn exit_loop(&mut self, mut before: Vec<usize>) {
+        let reads = self.loop_reads.pop().unwrap_or_default();
+        for &store in self.live_stores.iter() {
+            if reads.contains(&self.stores[store].var) {
+                self.stores[store].read = true;
             }
-            if !parent.is_builtin() && self.proc_ref.ty() == parent.ty()
-                && self.env.can_be_redefined.get_self_or_parent(self.proc_ref).is_none() {
-                error(self.proc_ref.location, format!("redefining proc {}/{}", self.ty, self.proc_ref.name()))
-                    .with_errortype("redefined_proc")
-                    .with_note(parent.location, "previous definition is here")
+        }
+        if let Some(outer) = self.loop_reads.last_mut() {
+            outer.extend(reads);
+        }
+        self.merge_live_stores(&mut before);
+        self.live_stores = before;
+    }
+
+    fn check_dead_stores(&self) {
+        if self.uses_goto {
+            return;
+        }
+        for store in self.stores.iter() {
+            let declared = &self.declared[store.var];
+            // Vars which are never read at all are reported as unused.
+            if store.read || !declared.read || self.referenced.contains(&store.var) {
+                continue;
+            }
+            if store.has_side_effects {
+                error(store.location, format!("result assigned to {:?} is never read", declared.name))
+                    .with_end(store.end)
                     .set_severity(Severity::Hint)
+                    .with_errortype("dead_store")
+                    .register(self.context);
+            } else {
+                error(store.location, format!("value assigned to {:?} is never read", declared.name))
+                    .with_end(store.end)
+                    .set_severity(Severity::Warning)
+                    .with_errortype("dead_store")
                     .register(self.context);
             }
         }
@@ -1223,21 +2282,142 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
 
     fn visit_block(&mut self, block: &'o [Spanned<Statement>], local_vars: &mut HashMap<String, LocalVar<'o>>) -> ControlFlow {
         let mut term = ControlFlow::allfalse();
-        for stmt in block.iter() {
-            if term.terminates() {
-                error(stmt.location,"possible unreachable code here")
-                    .with_errortype("unreachable_code")
-                    .register(self.context);
-                return term // stop evaluating
+        // Whether the last statement jumped away unconditionally.
+        let mut jumped = false;
+        // Only derefs in the same block as where a var became null are
+        // checked, so start over.
+        let nulls = self.null_in_branch.take().into_iter().collect();
+        let outer_nulls = std::mem::replace(&mut self.null_locals, nulls);
+        for (idx, stmt) in block.iter().enumerate() {
+            if term.terminates() || jumped {
+                if let Statement::Label { .. } = stmt.elem {
+                    // Labels can be jumped to, so reachability starts over.
+                    term = ControlFlow::allfalse();
+                } else {
+                    let end = block_end(&block[idx..]).unwrap_or(stmt.location);
+                    error(stmt.location,"possible unreachable code here")
+                        .with_errortype("unreachable_code")
+                        .with_end(Location { line: end.line + 1, column: 1, ..end })
+                        .register(self.context);
+                    self.null_locals = outer_nulls;
+                    return term // stop evaluating
+                }
+            }
+            jumped = matches!(stmt.elem, Statement::Break(_) | Statement::Continue(_) | Statement::Goto(_));
+            if !self.null_locals.is_empty() && !matches!(stmt.elem, Statement::Expr(_) | Statement::Var(_) | Statement::Vars(_)) {
+                // Nested statements may reassign a var in a way this block
+                // doesn't see, including before their own conditions run
+                // again.
+                let mut assigned = Vec::new();
+                assigned_in_statement(&stmt.elem, &mut assigned);
+                for name in assigned {
+                    self.null_locals.remove(name);
+                }
             }
             let state = self.visit_statement(stmt.location, &stmt.elem, local_vars);
             term.merge(state);
+            if let Some(next) = block.get(idx + 1) {
+                self.check_misleading_indentation(stmt, next);
+            }
         }
+        self.null_locals = outer_nulls;
         term
     }
 
+    /// Report a statement which looks like it belongs to the body of the
+    /// conditional or loop before it, either because it follows a one-line
+    /// body on the same line, or because it is indented further than the
+    /// conditional itself.
+    fn check_misleading_indentation(&self, previous: &Spanned<Statement>, next: &Spanned<Statement>) {
+        let kind = match previous.elem {
+            Statement::If { .. } => "if",
+            Statement::While { .. } => "while",
+            Statement::ForInfinite { .. }
+            | Statement::ForLoop { .. }
+            | Statement::ForList(_)
+            | Statement::ForRange(_) => "for",
+            Statement::Spawn { .. } => "spawn",
+            _ => return,
+        };
+        let end = block_end(std::slice::from_ref(previous)).unwrap_or(previous.location);
+        let misleading = if next.location.line == end.line {
+            next.location.line == previous.location.line
+        } else {
+            next.location.column > previous.location.column
+        };
+        if misleading {
+            error(next.location, format!("statement is not part of the {} body before it", kind))
+                .set_severity(Severity::Warning)
+                .with_errortype("misleading_indentation")
+                .with_end(Location { line: next.location.line + 1, column: 1, ..next.location })
+                .with_note(previous.location, format!("{} statement is here", kind))
+                .register(self.context);
+        }
+    }
+
+    /// Report `..()` being called twice at the top level of a proc with
+    /// nothing between the calls which could return early.
+    fn check_parent_calls(&self, block: &[Spanned<Statement>]) {
+        let mut previous: Option<(usize, &Spanned<Statement>)> = None;
+        for (idx, stmt) in block.iter().enumerate() {
+            if calls_parent_unconditionally(&stmt.elem) {
+                let Some((previous_idx, previous_stmt)) = previous else {
+                    previous = Some((idx, stmt));
+                    continue;
+                };
+                let assigns_dot = matches!(&previous_stmt.elem, Statement::Expr(Expression::AssignOp { op: AssignOp::Assign, lhs, .. })
+                    if matches!(lhs.as_term(), Some(Term::Ident(name)) if name == "."));
+                if assigns_dot && previous_idx + 1 == idx && matches!(stmt.elem, Statement::Return(_)) {
+                    error(stmt.location, "`return ..()` calls the parent proc again after `. = ..()`")
+                        .set_severity(Severity::Warning)
+                        .with_errortype("parent_call_return")
+                        .with_note(previous_stmt.location, "parent proc already called here; use `return .` instead")
+                        .register(self.context);
+                } else {
+                    error(stmt.location, "parent proc is called twice")
+                        .set_severity(Severity::Warning)
+                        .with_errortype("duplicate_parent_call")
+                        .with_note(previous_stmt.location, "first called here")
+                        .register(self.context);
+                }
+            } else if may_exit(&stmt.elem) {
+                previous = None;
+            }
+        }
+    }
+
+    /// Report a field access or call on a local var which is known to be
+    /// null in this block.
+    fn check_null_deref(&self, location: Location, follow: &Spanned<Follow>, name: &str) {
+        let what = match follow.elem {
+            Follow::Field(PropertyAccessKind::Dot | PropertyAccessKind::Colon, _) => "field access",
+            Follow::Call(PropertyAccessKind::Dot | PropertyAccessKind::Colon, _, _) => "proc call",
+            _ => return,
+        };
+        if let Some(&(null_location, how)) = self.null_locals.get(name) {
+            error(location, format!("{} on {:?}, which is null here", what, name))
+                .set_severity(Severity::Warning)
+                .with_errortype("null_dereference")
+                .with_note(null_location, how)
+                .register(self.context);
+        }
+    }
+
+    /// Stop treating the vars a guard mentions as null while visiting what
+    /// it guards, as in `x && x.foo`, returning them to be restored after.
+    fn guard(&mut self, guard: &Expression) -> Vec<(String, (Location, &'static str))> {
+        if self.null_locals.is_empty() {
+            return Vec::new();
+        }
+        let mut names = Vec::new();
+        mentioned_names(guard, &mut names);
+        names.into_iter()
+            .filter_map(|name| self.null_locals.remove_entry(name))
+            .collect()
+    }
+
     fn loop_condition_check(&mut self, location: Location, expression: &'o Expression) {
-        match expression.is_truthy() {
+        match condition_truthiness(location, expression) {
             Some(true) => {
                 error(location,"loop condition is always true")
                     .with_errortype("loop_condition_determinate")
@@ -1252,6 +2432,19 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
         };
     }
 
+    /// Report `if(x = 5)`, which is usually a typo for `==`. Wrapping the
+    /// assignment in its own parentheses, as in `while((x = next()))`, marks
+    /// it as intentional.
+    fn check_assignment_condition(&self, location: Location, expression: &Expression) {
+        if let Expression::AssignOp { op: AssignOp::Assign, .. } = expression {
+            error(location, "assignment used as a condition; did you mean `==`?")
+                .set_severity(Severity::Warning)
+                .with_errortype("assignment_in_condition")
+                .with_note(location, "wrap the assignment in parentheses if it is intentional")
+                .register(self.context);
+        }
+    }
+
     fn visit_control_condition(&mut self, location: Location, expression: &'o Expression) {
         if expression.is_const_eval() {
             error(location,"control flow condition is a constant evalutation")
@@ -1304,34 +2497,67 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                         .register(self.context);
                 }
                 let return_type = self.visit_expression(location, expr, None, local_vars);
+                self.check_return(location, &return_type);
                 local_vars.get_mut(".").unwrap().analysis = return_type;
+                self.live_stores.clear();
+                return ControlFlow { returns: true, continues: false, breaks: false, fuzzy: false }
+            },
+            Statement::Return(None) => {
+                if let Some((declared, _)) = self.declared_return {
+                    if !self.assigns_dot {
+                        error(location, format!("returning nothing from {}, which is declared to return {}", self.proc_ref, declared))
+                            .with_errortype("missing_return_value")
+                            .set_severity(Severity::Hint)
+                            .register(self.context);
+                    }
+                }
+                self.live_stores.clear();
                 return ControlFlow { returns: true, continues: false, breaks: false, fuzzy: false }
             },
-            Statement::Return(None) => { return ControlFlow { returns: true, continues: false, breaks: false, fuzzy: false } },
             Statement::Crash(expr) => {
                 if let Some(expr) = expr {
                     self.visit_expression(location, expr, None, local_vars);
                 }
+                if self.inside_try == 0 {
+                    self.live_stores.clear();
+                }
                 return ControlFlow { returns: true, continues: false, breaks: false, fuzzy: false }
             },
-            Statement::Throw(expr) => { self.visit_expression(location, expr, None, local_vars); },
+            Statement::Throw(expr) => {
+                self.visit_expression(location, expr, None, local_vars);
+                if self.inside_try == 0 {
+                    self.live_stores.clear();
+                }
+            },
             Statement::While { condition, block } => {
                 let mut scoped_locals = local_vars.clone();
-                // We don't check for static/determine conditions because while(TRUE) is so common.
+                // We don't check for static/true conditions because while(TRUE) is so common.
+                if condition_truthiness(location, condition) == Some(false) {
+                    error(location, "loop condition is always false")
+                        .with_errortype("loop_condition_determinate")
+                        .register(self.context);
+                }
+                self.check_assignment_condition(location, condition);
+                let before = self.enter_loop();
                 self.visit_expression(location, condition, None, &mut scoped_locals);
                 let mut state = self.visit_block(block, &mut scoped_locals);
+                self.exit_loop(before);
                 state.end_loop();
                 return state
             },
             Statement::DoWhile { block, condition } => {
                 let mut scoped_locals = local_vars.clone();
+                let before = self.enter_loop();
                 let mut state = self.visit_block(block, &mut scoped_locals);
                 if state.terminates_loop() {
+                    self.exit_loop(before);
                     error(location,"do while terminates without ever reaching condition")
                         .register(self.context);
                     return state
                 }
+                self.check_assignment_condition(condition.location, &condition.elem);
                 self.visit_expression(condition.location, &condition.elem, None, &mut scoped_locals);
+                self.exit_loop(before);
 
                 state.end_loop();
                 return state
@@ -1339,17 +2565,26 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
             Statement::If { arms, else_arm } => {
                 let mut allterm = ControlFlow::alltrue();
                 let mut alwaystrue = false;
+                let mut after = Vec::new();
                 for (condition, ref block) in arms.iter() {
                     let mut scoped_locals = local_vars.clone();
                     self.visit_control_condition(condition.location, &condition.elem);
+                    self.check_assignment_condition(condition.location, &condition.elem);
                     if alwaystrue {
                         error(condition.location,"unreachable if block, preceeding if/elseif condition(s) are always true")
                             .with_errortype("unreachable_code")
                             .register(self.context);
                     }
                     self.visit_expression(condition.location, &condition.elem, None, &mut scoped_locals);
+                    // The next condition is only reached if this one is false.
+                    let fallthrough = self.live_stores.clone();
+                    self.null_in_branch = null_checked(&condition.elem)
+                        .filter(|name| scoped_locals.contains_key(*name))
+                        .map(|name| (name.to_owned(), (condition.location, "compared to null here")));
                     let state = self.visit_block(block, &mut scoped_locals);
-                    match condition.elem.is_truthy() {
+                    self.merge_live_stores(&mut after);
+                    self.live_stores = fallthrough;
+                    match condition_truthiness(condition.location, &condition.elem) {
                         Some(true) => {
                             error(condition.location,"if condition is always true")
                                 .with_errortype("if_condition_determinate")
@@ -1374,8 +2609,12 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                         }
                     }
                     let state = self.visit_block(else_arm, &mut local_vars.clone());
+                    self.merge_live_stores(&mut after);
+                    self.live_stores = after;
                     allterm.merge_false(state);
                 } else {
+                    self.merge_live_stores(&mut after);
+                    self.live_stores = after;
                     allterm.no_else();
                     return allterm
                 }
@@ -1384,7 +2623,9 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
             },
             Statement::ForInfinite { block } => {
                 let mut scoped_locals = local_vars.clone();
+                let before = self.enter_loop();
                 let mut state = self.visit_block(block, &mut scoped_locals);
+                self.exit_loop(before);
                 state.end_loop();
                 return state
             }
@@ -1393,6 +2634,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                 if let Some(init) = init {
                     self.visit_statement(location, init, &mut scoped_locals);
                 }
+                let before = self.enter_loop();
                 if let Some(test) = test {
                     self.loop_condition_check(location, test);
                     self.visit_control_condition(location, test);
@@ -1402,6 +2644,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                     self.visit_statement(location, inc, &mut scoped_locals);
                 }
                 let mut state = self.visit_block(block, &mut scoped_locals);
+                self.exit_loop(before);
                 state.end_loop();
                 return state
             },
@@ -1438,9 +2681,11 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                     }
                 }
                 if let Some(var_type) = var_type {
-                    self.visit_var(location, var_type, name, None, &mut scoped_locals);
+                    self.visit_var(location, var_type, name, None, None, &mut scoped_locals);
                 }
+                let before = self.enter_loop();
                 let mut state = self.visit_block(block, &mut scoped_locals);
+                self.exit_loop(before);
                 state.end_loop();
                 return state
             },
@@ -1452,9 +2697,11 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                     self.visit_expression(location, step, None, &mut scoped_locals);
                 }
                 if let Some(var_type) = var_type {
-                    self.visit_var(location, var_type, name, Some(start), &mut scoped_locals);
+                    self.visit_var(location, var_type, name, Some(start), None, &mut scoped_locals);
                 }
+                let before = self.enter_loop();
                 let mut state = self.visit_block(block, &mut scoped_locals);
+                self.exit_loop(before);
                 if let Some(startterm) = start.as_term() {
                     if let Some(endterm) = end.as_term() {
                         if let Some(validity) = startterm.valid_for_range(endterm, step.as_ref()) {
@@ -1495,14 +2742,19 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                 if let Some(delay) = delay {
                     self.visit_expression(location, delay, None, &mut scoped_locals);
                 }
+                let mut after = self.live_stores.clone();
                 self.visit_block(block, &mut scoped_locals);
+                self.merge_live_stores(&mut after);
+                self.live_stores = after;
                 self.inside_newcontext = self.inside_newcontext.wrapping_sub(1);
             },
             Statement::Switch { input, cases, default } => {
                 check_switch_rand_range(input, cases, default, location, self.context);
+                check_switch_duplicate_cases(cases, self.context);
                 let mut allterm = ControlFlow::alltrue();
                 self.visit_control_condition(location, input);
                 self.visit_expression(location, input, None, local_vars);
+                let mut after = Vec::new();
                 for (case, ref block) in cases.iter() {
                     let mut scoped_locals = local_vars.clone();
                     if let [dm::ast::Case::Exact(Expression::BinaryOp{op: BinaryOp::Or, ..})] = case.elem[..] {
@@ -1519,13 +2771,20 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                             }
                         }
                     }
+                    let fallthrough = self.live_stores.clone();
                     let state = self.visit_block(block, &mut scoped_locals);
+                    self.merge_live_stores(&mut after);
+                    self.live_stores = fallthrough;
                     allterm.merge_false(state);
                 }
                 if let Some(default) = default {
                     let state = self.visit_block(default, &mut local_vars.clone());
+                    self.merge_live_stores(&mut after);
+                    self.live_stores = after;
                     allterm.merge_false(state);
                 } else {
+                    self.merge_live_stores(&mut after);
+                    self.live_stores = after;
                     allterm.no_else();
                     return allterm
                 }
@@ -1533,7 +2792,13 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                 return allterm
             },
             Statement::TryCatch { try_block, catch_params, catch_block } => {
+                // The catch block may run after any part of the try block.
+                let mut after = self.live_stores.clone();
+                self.inside_try += 1;
                 self.visit_block(try_block, &mut local_vars.clone());
+                self.inside_try -= 1;
+                self.merge_live_stores(&mut after);
+                std::mem::swap(&mut self.live_stores, &mut after);
                 if catch_params.len() > 1 {
                     error(location, format!("Expected 0 or 1 catch parameters, got {}", catch_params.len()))
                         .set_severity(Severity::Warning)
@@ -1550,24 +2815,60 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                         _ => {}
                     }
                     let var_type: VarType = type_path.iter().map(ToOwned::to_owned).collect();
-                    self.visit_var(location, &var_type, var_name, None, &mut catch_locals);
+                    self.visit_var(location, &var_type, var_name, None, None, &mut catch_locals);
                 }
                 self.visit_block(catch_block, &mut catch_locals);
+                self.merge_live_stores(&mut after);
+                self.live_stores = after;
             },
             Statement::Continue(_) => { return ControlFlow { returns: false, continues: true, breaks: false, fuzzy: true } },
             Statement::Break(_) => { return ControlFlow { returns: false, continues: false, breaks: true, fuzzy: true } },
-            Statement::Goto(_) => {},
+            Statement::Goto(_) => self.uses_goto = true,
             Statement::Label { name: _, block } => { self.visit_block(block, &mut local_vars.clone()); },
-            Statement::Del(expr) => { self.visit_expression(location, expr, None, local_vars); },
+            Statement::Del(expr) => {
+                self.visit_expression(location, expr, None, local_vars);
+                // Deleting src ends the proc.
+                if let Some(Term::Ident(name)) = expr.as_term() {
+                    if name == "src" && !self.ty.is_root() {
+                        return ControlFlow { returns: true, continues: false, breaks: false, fuzzy: false }
+                    }
+                }
+            },
         }
         ControlFlow::allfalse()
     }
 
     fn visit_var_stmt(&mut self, location: Location, var: &'o VarStatement, local_vars: &mut HashMap<String, LocalVar<'o>>) {
-        self.visit_var(location, &var.var_type, &var.name, var.value.as_ref(), local_vars)
+        if let Some(outer) = local_vars.get(var.name.as_str()) {
+            if let Some(param) = self.proc_ref.parameters.iter().find(|param| param.name == var.name) {
+                self.shadows(var.location, &var.name, "parameter", param.location, "shadowed_param");
+            } else if outer.location != Location::default() {
+                self.shadows(var.location, &var.name, "local var", outer.location, "shadowed_local");
+            } else {
+                self.shadows(var.location, &var.name, "builtin var", outer.location, "shadowed_builtin");
+            }
+        } else {
+            self.check_shadowed_field(var.location, &var.name);
+        }
+        let declared = self.declare(&var.name, var.location, false);
+        self.visit_var(location, &var.var_type, &var.name, var.value.as_ref(), declared, local_vars);
+        match var.value.as_ref().map(Expression::as_term) {
+            None | Some(Some(Term::Null)) => {
+                self.null_locals.insert(var.name.to_string(), (var.location, "declared null here"));
+            },
+            Some(_) => {
+                self.null_locals.remove(var.name.as_str());
+            },
+        }
+        // Literal initial values are usually just defaults.
+        if let (Some(idx), Some(value)) = (declared, &var.value) {
+            if !value.as_term().is_some_and(Term::is_static) {
+                self.store(idx, value, var.end);
+            }
+        }
     }
 
-    fn visit_var(&mut self, location: Location, var_type: &VarType, name: &str, value: Option<&'o Expression>, local_vars: &mut HashMap<String, LocalVar<'o>>) {
+    fn visit_var(&mut self, location: Location, var_type: &VarType, name: &str, value: Option<&'o Expression>, declared: Option<usize>, local_vars: &mut HashMap<String, LocalVar<'o>>) {
         // Calculate type hint
         let static_type = self.env.static_type(location, &var_type.type_path);
         // Visit the expression if it's there
@@ -1578,7 +2879,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
         analysis.static_ty = static_type;
 
         // Save var to locals
-        local_vars.insert(name.to_owned(), LocalVar { location, analysis });
+        local_vars.insert(name.to_owned(), LocalVar { location, analysis, declared });
     }
 
     fn visit_expression(&mut self, location: Location, expression: &'o Expression, type_hint: Option<TypeRef<'o>>, local_vars: &mut HashMap<String, LocalVar<'o>>) -> Analysis<'o> {
@@ -1589,6 +2890,20 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                 } else {
                     None
                 };
+                if let Term::Ident(name) = &term.elem {
+                    if let Some(first) = follow.first() {
+                        self.check_null_deref(term.location, first, name);
+                    }
+                    if follow.iter().any(|each| matches!(each.elem, Follow::Unary(UnaryOp::Reference))) {
+                        if let Some(idx) = local_vars.get(name).and_then(|var| var.declared) {
+                            self.referenced.insert(idx);
+                        }
+                    }
+                    // `&D` lets whatever it is passed to set the var.
+                    if follow.first().is_some_and(|first| matches!(first.elem, Follow::Unary(UnaryOp::Reference))) {
+                        self.null_locals.remove(name.as_str());
+                    }
+                }
                 let mut ty = self.visit_term(term.location, &term.elem, base_type_hint, local_vars);
                 for each in follow.iter() {
                     ty = self.visit_follow(each.location, ty, &each.elem, local_vars);
@@ -1633,7 +2948,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                             .with_note(location, format!("add parentheses to disambiguate: `(a {} b) in c`", op))
                             .register(self.context);
                     },
-                    Expression::AssignOp { op, lhs, rhs } => {
+                    Expression::AssignOp { op, lhs, rhs, .. } => {
                         error(location, format!("ambiguous `{}` on left side of an `in`", op))
                             .set_severity(Severity::Warning)
                             .with_errortype("ambiguous_in_lhs")
@@ -1650,6 +2965,21 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                             .register(self.context);
                     },
                 };
+                // `a in b && c` is parsed as `a in (b && c)`
+                let mut logical = None;
+                let mut operand = &**rhs;
+                while let Expression::BinaryOp { op: op @ (BinaryOp::And | BinaryOp::Or), lhs, .. } = operand {
+                    logical = Some(*op);
+                    operand = lhs;
+                }
+                if let Some(op) = logical {
+                    error(location, format!("ambiguous `{}` on right side of an `in`", op))
+                        .set_severity(Severity::Warning)
+                        .with_errortype("ambiguous_in_rhs")
+                        .with_note(location, format!("add parentheses to fix: `(a in b) {} c`", op))
+                        .with_note(location, format!("add parentheses to disambiguate: `a in (b {} c)`", op))
+                        .register(self.context);
+                }
                 let lty = self.visit_expression(location, lhs, None, local_vars);
                 let rty = self.visit_expression(location, rhs, None, local_vars);
                 self.visit_binary(lty, rty, BinaryOp::In)
@@ -1659,12 +2989,18 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                 // this is the only case I've seen it used in the wild.
                 // ex: var/datum/cache_entry/E = cache[key] || new
                 let lty = self.visit_expression(location, lhs, type_hint, local_vars);
+                let guarded = self.guard(lhs);
                 let rty = self.visit_expression(location, rhs, type_hint, local_vars);
+                self.null_locals.extend(guarded);
                 self.visit_binary(lty, rty, BinaryOp::Or)
             },
             Expression::BinaryOp { op, lhs, rhs } => {
                 let lty = self.visit_expression(location, lhs, None, local_vars);
+                let guarded = if *op == BinaryOp::And { self.guard(lhs) } else { Vec::new() };
                 let rty = self.visit_expression(location, rhs, None, local_vars);
+                self.null_locals.extend(guarded);
+                self.check_path_string_comparison(location, *op, &lty, &rty);
+                self.check_float_equality(location, *op, (lhs, &lty), (rhs, &rty), local_vars);
                 match op {
                     BinaryOp::BitAnd => self.check_negated_bitwise(lhs, location, BinaryOp::BitAnd, BinaryOp::And),
                     BinaryOp::BitOr => self.check_negated_bitwise(lhs, location, BinaryOp::BitOr, BinaryOp::Or),
@@ -1673,18 +3009,57 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                 }
                 self.visit_binary(lty, rty, *op)
             },
-            Expression::AssignOp { lhs, rhs, .. } => {
-                let lhs = self.visit_expression(location, lhs, None, local_vars);
+            Expression::AssignOp { op, lhs, rhs, end } => {
+                // Assigning to `.` sets the return value.
+                let assigns_dot = *op == AssignOp::Assign && matches!(lhs.as_term(), Some(Term::Ident(name)) if name == ".");
+                let local_name = match lhs.as_term() {
+                    Some(Term::Ident(name)) if local_vars.contains_key(name) => Some(name),
+                    _ => None,
+                };
+                // Assigning to a local var isn't a use of it.
+                let (target, unread) = match lhs.as_term() {
+                    Some(Term::Ident(name)) => match local_vars.get(name) {
+                        Some(var) => (Some(var), var.declared.filter(|&idx| !self.declared[idx].read)),
+                        None => (None, None),
+                    },
+                    _ => (None, None),
+                };
+                // Plain assignment doesn't read the previous value either.
+                let (stored, lhs) = match target {
+                    Some(var) if *op == AssignOp::Assign => (var.declared, var.analysis.clone()),
+                    _ => (None, self.visit_expression(location, lhs, None, local_vars)),
+                };
+                if let Some(idx) = unread {
+                    self.declared[idx].read = false;
+                }
                 if let Some(true) = lhs.is_impure {
                     self.env.impure_procs.insert_violator(self.proc_ref, "Assignment on purity breaking expression", location);
                 }
-                self.visit_expression(location, rhs, lhs.static_ty.basic_type(), local_vars)
+                let rhs_analysis = self.visit_expression(location, rhs, lhs.static_ty.basic_type(), local_vars);
+                if let Some(idx) = stored {
+                    self.store(idx, rhs, *end);
+                }
+                if let Some(name) = local_name {
+                    if *op == AssignOp::Assign && matches!(rhs.as_term(), Some(Term::Null)) {
+                        self.null_locals.insert(name.to_owned(), (location, "set to null here"));
+                    } else {
+                        self.null_locals.remove(name);
+                    }
+                }
+                let rhs = rhs_analysis;
+                if assigns_dot {
+                    self.assigns_dot = true;
+                    self.check_return(location, &rhs);
+                }
+                rhs
             },
             Expression::TernaryOp { cond, if_, else_ } => {
                 // TODO: be sensible
                 self.visit_expression(location, cond, None, local_vars);
+                let guarded = self.guard(cond);
                 let ty = self.visit_expression(location, if_, type_hint, local_vars);
                 self.visit_expression(location, else_, type_hint, local_vars);
+                self.null_locals.extend(guarded);
                 ty
             }
         }
@@ -1693,6 +3068,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
     fn visit_term(&mut self, location: Location, term: &'o Term, type_hint: Option<TypeRef<'o>>, local_vars: &mut HashMap<String, LocalVar<'o>>) -> Analysis<'o> {
         match term {
             Term::Null => Analysis::null(),
+            Term::Error => Analysis::empty(),
             Term::Int(number) => Analysis::from_value(self.objtree, Constant::from(*number), type_hint),
             Term::Float(number) => Analysis::from_value(self.objtree, Constant::from(*number), type_hint),
             Term::String(text) => Analysis::from_value(self.objtree, Constant::String(text.as_str().into()), type_hint),
@@ -1701,6 +3077,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
 
             Term::Ident(unscoped_name) => {
                 if let Some(var) = local_vars.get(unscoped_name) {
+                    self.mark_read(unscoped_name, var.declared);
                     var.analysis.clone()
                         .with_fix_hint(var.location, "add additional type info here")
                 } else if unscoped_name == "type" {
@@ -1715,19 +3092,31 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                         fix_hint: None,
                         is_impure: None,
                     }
+                } else if let Some(parent) = self.ty.parent_type().filter(|_| unscoped_name == "parent_type") {
+                    // Likewise for `parent_type::proc()` and `parent_type::var`.
+                    let pop = dm::constants::Pop::from(parent.path.split('/').skip(1).map(ToOwned::to_owned).collect::<Vec<_>>().into_boxed_slice());
+                    Analysis {
+                        static_ty: StaticType::None,
+                        aset: assumption_set![Assumption::IsPath(true, parent)],
+                        value: Some(Constant::Prefab(Box::new(pop))),
+                        fix_hint: None,
+                        is_impure: None,
+                    }
                 } else if let Some(decl) = self.ty.get_var_declaration(unscoped_name) {
+                    self.check_var_access(location, "var", self.ty, unscoped_name, decl);
+                    self.check_deprecated_var(location, self.ty, unscoped_name);
                     let mut ana = self.static_type(location, &decl.var_type.type_path)
                         .with_fix_hint(decl.location, "add additional type info here");
                     ana.is_impure = Some(true);
                     ana
                 } else {
-                    error(location, format!("undefined var: {:?}", unscoped_name))
-                        .register(self.context);
+                    self.undefined_var(location, unscoped_name.len(), self.ty, unscoped_name, format!("undefined var: {:?}", unscoped_name));
                     Analysis::empty()
                 }
             },
             Term::GlobalIdent(global_name) => {
                 if let Some(decl) = self.objtree.root().get_var_declaration(global_name) {
+                    self.check_deprecated_var(location, self.objtree.root(), global_name);
                     let mut ana = self.static_type(location, &decl.var_type.type_path)
                         .with_fix_hint(decl.location, "add additional type info here");
                     ana.is_impure = Some(true);
@@ -1743,6 +3132,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
             Term::Prefab(prefab) => {
                 if let Some(nav) = self.ty.navigate_path(&prefab.path) {
                     let ty = nav.ty();  // TODO: handle proc/verb paths here
+                    self.check_deprecated_type(location, ty);
                     let pop = dm::constants::Pop::from(ty.path.split('/').skip(1).map(ToOwned::to_owned).collect::<Vec<_>>().into_boxed_slice());
                     Analysis {
                         static_ty: StaticType::None,
@@ -1758,9 +3148,9 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                 }
             },
             Term::InterpString(_, parts) => {
-                for (ref expr, _) in parts.iter() {
-                    if let Some(expr) = expr {
-                        self.visit_expression(location, expr, None, local_vars);
+                for (expr, _) in parts.iter() {
+                    if let Some(ref elem) = expr.elem {
+                        self.visit_expression(expr.location, elem, None, local_vars);
                     }
                 }
                 assumption_set![Assumption::IsText(true)].into()
@@ -1792,8 +3182,8 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                     }
                     Analysis::empty()
                 } else {
-                    error(location, format!("undefined proc: {:?} on {}", unscoped_name, self.ty))
-                        .register(self.context);
+                    self.undefined_proc(location, self.ty, unscoped_name, format!("undefined proc: {:?} on {}", unscoped_name, self.ty));
+                    self.visit_arguments(location, args, local_vars);
                     Analysis::empty()
                 }
             },
@@ -1814,6 +3204,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                     error(location, format!("proc has no parent: {}", self.proc_ref))
                         .with_errortype("proc_has_no_parent")
                         .register(self.context);
+                    self.visit_arguments(location, args, local_vars);
                     Analysis::empty()
                 }
             },
@@ -1821,8 +3212,8 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                 if let Some(proc) = self.objtree.root().get_proc(global_name) {
                     self.visit_call(location, self.objtree.root(), proc, args, true, local_vars)
                 } else {
-                    error(location, format!("undefined global proc: {:?}", global_name))
-                        .register(self.context);
+                    self.undefined_proc(location, self.objtree.root(), global_name, format!("undefined global proc: {:?}", global_name));
+                    self.visit_arguments(location, args, local_vars);
                     Analysis::empty()
                 }
             },
@@ -1834,21 +3225,34 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                     error(location, "no type hint available on implicit new()")
                         .with_errortype("no_typehint_implicit_new")
                         .register(self.context);
+                    if let Some(args) = args {
+                        self.visit_arguments(location, args, local_vars);
+                    }
                     Analysis::empty()
                 }
             },
             Term::NewPrefab { prefab, args } => {
                 if let Some(nav) = self.ty.navigate_path(&prefab.path) {
                     // TODO: handle proc/verb paths here
+                    self.check_deprecated_type(location, nav.ty());
                     self.visit_new(location, nav.ty(), args, local_vars)
                 } else {
                     error(location, format!("failed to resolve path {}", FormatTypePath(&prefab.path)))
                         .register(self.context);
-                        Analysis::empty()
+                    if let Some(args) = args {
+                        self.visit_arguments(location, args, local_vars);
+                    }
+                    Analysis::empty()
                 }
             },
-            Term::NewMiniExpr { .. } => {
+            Term::NewMiniExpr { expr, args } => {
                 // TODO: evaluate
+                if let Some(var) = local_vars.get(expr.ident.as_str()) {
+                    self.mark_read(&expr.ident, var.declared);
+                }
+                if let Some(args) = args {
+                    self.visit_arguments(location, args, local_vars);
+                }
                 Analysis::empty()
             },
 
@@ -1966,13 +3370,155 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                 // `/datum/New()` and never an override.
                 true,
                 local_vars);
-        } else if typepath.path != "/list" {
-            error(location, format!("couldn't find {}/proc/New", typepath.path))
-                .register(self.context);
+        } else {
+            if typepath.path != "/list" {
+                error(location, format!("couldn't find {}/proc/New", typepath.path))
+                    .register(self.context);
+            }
+            if let Some(args) = args {
+                self.visit_arguments(location, args, local_vars);
+            }
         }
         assumption_set![Assumption::IsType(true, typepath)].into()
     }
 
+    /// Report a call to a proc which `ty` does not have, unless the config
+    /// says its procs are dispatched dynamically.
+    fn undefined_proc(&self, location: Location, ty: TypeRef<'o>, name: &str, desc: String) {
+        let dynamic = self.context.config().dreamchecker.dynamic_proc_types.iter()
+            .filter_map(|path| self.objtree.find(path))
+            .any(|dynamic| ty.is_subtype_of(dynamic.get()));
+        if dynamic {
+            return;
+        }
+        let mut error = error(location, desc);
+        let names = ty.iter_parent_types().flat_map(|parent| parent.get().procs.keys());
+        if let Some(proc) = nearest_name(names, name, |candidate| self.env.is_macro(candidate)).and_then(|candidate| ty.get_proc(candidate)) {
+            error = error.with_note(proc.get().location, format!("did you mean {}?", proc.name()));
+        }
+        error.register(self.context);
+    }
+
+    /// Report a use of something whose docs have a `@deprecated` tag.
+    fn deprecated(&self, location: Location, what: String, docs: &DocCollection, definition: Location) {
+        let Some(replacement) = docs.deprecated() else {
+            return;
+        };
+        let desc = if replacement.is_empty() {
+            format!("{} is deprecated", what)
+        } else {
+            format!("{} is deprecated: {}", what, replacement)
+        };
+        error(location, desc)
+            .set_severity(Severity::Warning)
+            .with_errortype("deprecated")
+            .with_note(definition, "deprecated here")
+            .register(self.context);
+    }
+
+    /// Report a use of a deprecated type, or a subtype of one.
+    fn check_deprecated_type(&self, location: Location, ty: TypeRef<'o>) {
+        if let Some(deprecated) = ty.iter_parent_types().find(|parent| parent.get().docs.deprecated().is_some()) {
+            self.deprecated(location, format!("type {}", deprecated), &deprecated.get().docs, deprecated.get().location);
+        }
+    }
+
+    /// Report a use of a deprecated var.
+    fn check_deprecated_var(&self, location: Location, ty: TypeRef<'o>, name: &str) {
+        if let Some(declaring) = declaring_type(ty, name) {
+            let var = &declaring.get().vars[name].value;
+            self.deprecated(location, format!("var {:?} on {}", name, declaring), &var.docs, var.location);
+        }
+    }
+
+    /// Report a call to a deprecated proc, which is marked where it is
+    /// declared.
+    fn check_deprecated_proc(&self, location: Location, proc: ProcRef<'o>) {
+        let mut declaration = proc;
+        while let Some(parent) = declaration.parent_proc() {
+            declaration = parent;
+        }
+        self.deprecated(location, format!("proc {}", declaration), &declaration.get().docs, declaration.get().location);
+    }
+
+    /// Report a use of a private var outside the type which declares it, or
+    /// of a protected var outside that type and its subtypes.
+    fn check_var_access(&self, location: Location, kind: &str, ty: TypeRef<'o>, name: &str, decl: &VarDeclaration) {
+        let flags = decl.var_type.flags;
+        if !flags.is_private() && !flags.is_protected() {
+            return;
+        }
+        let Some(declaring) = declaring_type(ty, name) else {
+            return;
+        };
+        let (errortype, desc) = if flags.is_private() && declaring != self.ty {
+            ("private_var", "private")
+        } else if flags.is_protected() && !self.ty.is_subtype_of(declaring.get()) {
+            ("protected_var", "protected")
+        } else {
+            return;
+        };
+        // unqualified vars are named by where they are declared
+        let shown = if kind == "var" { declaring } else { ty };
+        error(location, format!("{} {:?} on {} is declared as {}", kind, name, shown, desc))
+            .with_errortype(errortype)
+            .set_severity(Severity::Warning)
+            .with_note(decl.location, "definition is here")
+            .register(self.context);
+    }
+
+    /// Report a use of a var which `ty` does not declare, spanning `len`
+    /// characters from `location`.
+    fn undefined_var(&self, location: Location, len: usize, ty: TypeRef<'o>, name: &str, desc: String) {
+        // Not expanded here, but a macro elsewhere, so not meant as a var.
+        if self.env.is_macro(name) {
+            return;
+        }
+        let mut error = error(location, desc)
+            .with_errortype("undefined_var")
+            .with_end(Location { column: location.column + len as u16, ..location });
+        let names = ty.iter_parent_types().flat_map(|parent| parent.get().vars.keys());
+        if let Some(candidate) = nearest_name(names, name, |candidate| self.env.is_macro(candidate)) {
+            if let Some(decl) = ty.get_var_declaration(candidate) {
+                error = error.with_note(decl.location, format!("did you mean {}?", candidate));
+            }
+        }
+        error.register(self.context);
+    }
+
+    /// Surface a `:` or `?:` access, suggesting the typed `.` or `?.` when
+    /// the static type of `lhs`, or failing that the only type in the tree
+    /// which declares the member, is known.
+    fn colon_access(&mut self, location: Location, lhs: &Analysis<'o>, kind: PropertyAccessKind, name: &str, is_call: bool) {
+        let declares = |ty: TypeRef<'o>| if is_call {
+            ty.get_proc(name).is_some()
+        } else {
+            ty.get_var_declaration(name).is_some()
+        };
+        let typed = match kind {
+            PropertyAccessKind::SafeColon => PropertyAccessKind::SafeDot,
+            _ => PropertyAccessKind::Dot,
+        };
+        let mut desc = format!("{:?} accessed with {:?}", name, kind.name());
+        let mut note = None;
+        if lhs.static_ty.basic_type().is_some_and(declares) {
+            desc.push_str(&format!("; use {:?} instead", typed.name()));
+        } else if let Some(ty) = self.env.only_declaring_type(name, is_call) {
+            desc.push_str(&format!("; use {:?} on a {} instead", typed.name(), ty));
+            if let Some((hint_location, _)) = lhs.fix_hint {
+                note = Some((hint_location, format!("declare this as a {}", ty)));
+            }
+        }
+        let mut error = error(location, desc)
+            .set_severity(Severity::Hint)
+            .with_errortype("colon_operator")
+            .with_end(Location { column: location.column + (kind.name().len() + name.len()) as u16, ..location });
+        if let Some((note_location, note_desc)) = note {
+            error = error.with_note(note_location, note_desc);
+        }
+        error.register(self.context);
+    }
+
     fn check_type_sleepers(&mut self, ty: TypeRef<'o>, location: Location, unscoped_name: &str) {
         match ty.get().path.as_str() {
             "/client" => if self.inside_newcontext == 0 && matches!(unscoped_name,
@@ -1993,14 +3539,16 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
         match rhs {
             Follow::Unary(op) => self.visit_unary(lhs, op, location, local_vars),
 
-            Follow::Field(PropertyAccessKind::Colon, _) => Analysis::empty(),
-            Follow::Field(PropertyAccessKind::SafeColon, _) => Analysis::empty(),
-            Follow::Call(PropertyAccessKind::Colon, _, args) |
-            Follow::Call(PropertyAccessKind::SafeColon, _, args) => {
+            Follow::Field(kind @ (PropertyAccessKind::Colon | PropertyAccessKind::SafeColon), name) => {
+                self.colon_access(location, &lhs, *kind, name, false);
+                Analysis::empty()
+            },
+            Follow::Call(kind @ (PropertyAccessKind::Colon | PropertyAccessKind::SafeColon), name, args) => {
+                self.colon_access(location, &lhs, *kind, name, true);
                 // No analysis yet, but be sure to visit the arguments
                 for arg in args.iter() {
                     let mut argument_value = arg;
-                    if let Expression::AssignOp { op: AssignOp::Assign, lhs, rhs } = arg {
+                    if let Expression::AssignOp { op: AssignOp::Assign, lhs, rhs, .. } = arg {
                         match lhs.as_term() {
                             Some(Term::Ident(name)) |
                             Some(Term::String(name)) => {
@@ -2032,24 +3580,17 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
             Follow::Field(kind, name) => {
                 if let Some(ty) = lhs.static_ty.basic_type() {
                     if let Some(decl) = ty.get_var_declaration(name) {
-                        if ty != self.ty && decl.var_type.flags.is_private() {
-                            error(location, format!("field {:?} on {} is declared as private", name, ty))
-                                .with_errortype("private_var")
-                                .set_severity(Severity::Warning)
-                                .with_note(decl.location, "definition is here")
-                                .register(self.context);
-                        } else if !self.ty.is_subtype_of(ty.get()) && decl.var_type.flags.is_protected() {
-                            error(location, format!("field {:?} on {} is declared as protected", name, ty))
-                                .with_errortype("protected_var")
-                                .set_severity(Severity::Warning)
-                                .with_note(decl.location, "definition is here")
-                                .register(self.context);
+                        self.check_var_access(location, "field", ty, name, decl);
+                        self.check_deprecated_var(location, ty, name);
+                        let mut analysis = self.static_type(location, &decl.var_type.type_path)
+                            .with_fix_hint(decl.location, "add additional type info here");
+                        if name == "type" {
+                            analysis.aset.set.insert(Assumption::IsPath(true, ty));
                         }
-                        self.static_type(location, &decl.var_type.type_path)
-                            .with_fix_hint(decl.location, "add additional type info here")
+                        analysis
                     } else {
-                        error(location, format!("undefined field: {:?} on {}", name, ty))
-                            .register(self.context);
+                        let len = kind.name().len() + name.len();
+                        self.undefined_var(location, len, ty, name, format!("undefined field: {:?} on {}", name, ty));
                         Analysis::empty()
                     }
                 } else {
@@ -2090,10 +3631,10 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                     }
                 };
                 let Some(decl) = real_type.get_var_declaration(name) else {
-                    error(location, format!("undefined field: {:?} on {}", name, real_type))
-                        .register(self.context);
+                    self.undefined_var(location, 2 + name.len(), real_type, name, format!("undefined field: {:?} on {}", name, real_type));
                     return Analysis::empty()
                 };
+                self.check_deprecated_var(location, real_type, name);
 
                 self.static_type(location, &decl.var_type.type_path)
                     .with_fix_hint(decl.location, "add additional type info here")
@@ -2107,8 +3648,10 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                             if ty != privateproc.ty() {
                                 error(location, format!("{} attempting to call private proc {}, types do not match", self.proc_ref, privateproc))
                                     .with_errortype("private_proc")
+                                    .set_severity(access_severity(self.context))
                                     .with_note(decllocation, "prohibited by this private_proc annotation")
                                     .register(self.context);
+                                self.visit_arguments(location, arguments, local_vars);
                                 return Analysis::empty() // dont double up with visit_call()
                             }
                         }
@@ -2116,14 +3659,15 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                             if !self.ty.is_subtype_of(protectedproc.ty().get()) {
                                 error(location, format!("{} attempting to call protected proc {}", self.proc_ref, protectedproc))
                                     .with_errortype("protected_proc")
+                                    .set_severity(access_severity(self.context))
                                     .with_note(decllocation, "prohibited by this protected_proc annotation")
                                     .register(self.context);
                             }
                         }
                         self.visit_call(location, ty, proc, arguments, false, local_vars)
                     } else {
-                        error(location, format!("undefined proc: {:?} on {}", name, ty))
-                            .register(self.context);
+                        self.undefined_proc(location, ty, name, format!("undefined proc: {:?} on {}", name, ty));
+                        self.visit_arguments(location, arguments, local_vars);
                         Analysis::empty()
                     }
                 } else {
@@ -2132,6 +3676,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                         .with_errortype("proc_call_static_type")
                         .with_fix_hint(&lhs)
                         .register(self.context);
+                    self.visit_arguments(location, arguments, local_vars);
                     Analysis::empty()
                 }
             },
@@ -2164,8 +3709,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
                     }
                 };
                 let Some(decl) = real_type.get_proc(name) else {
-                    error(location, format!("undefined proc: {:?} on {}", name, real_type))
-                        .register(self.context);
+                    self.undefined_proc(location, real_type, name, format!("undefined proc: {:?} on {}", name, real_type));
                     return Analysis::empty()
                 };
 
@@ -2312,10 +3856,12 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
 
     fn visit_call(&mut self, location: Location, src: TypeRef<'o>, proc: ProcRef<'o>, args: &'o [Expression], is_exact: bool, local_vars: &mut HashMap<String, LocalVar<'o>>) -> Analysis<'o> {
         self.env.call_tree.entry(self.proc_ref).or_default().push((proc, location, self.inside_newcontext != 0));
+        self.check_deprecated_proc(location, proc);
         if let Some((privateproc, true, decllocation)) = self.env.private.get_self_or_parent(proc) {
             if self.ty != privateproc.ty() {
                 error(location, format!("{} attempting to call private proc {}, types do not match", self.proc_ref, privateproc))
                     .with_errortype("private_proc")
+                    .set_severity(access_severity(self.context))
                     .with_note(decllocation, "prohibited by this private_proc annotation")
                     .register(self.context);
             }
@@ -2334,7 +3880,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
             let mut argument_value = arg;
             let mut this_kwarg = None;
             match arg {
-                Expression::AssignOp { op: AssignOp::Assign, lhs, rhs } => {
+                Expression::AssignOp { op: AssignOp::Assign, lhs, rhs, .. } => {
                     match lhs.as_term() {
                         Some(Term::Ident(name)) |
                         Some(Term::String(name)) => {
@@ -2405,6 +3951,45 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
             }
         }
 
+        // `..()` and `.()` without arguments forward this proc's arguments,
+        // and builtin procs are often variadic.
+        let forwards = is_exact && args.is_empty();
+        let builtin = proc.get_declaration().is_none_or(|decl| decl.location.is_builtins());
+        if !arglist_used && !forwards && !builtin {
+            let mut keywords: Vec<&'o str> = param_name_map.keys().copied().collect();
+            keywords.sort_unstable();
+            self.env.argument_counts.push(ArgumentCount {
+                location,
+                proc,
+                is_exact,
+                positional: param_idx,
+                keywords,
+            });
+        }
+
+        if proc.ty().is_root() && proc.name() == "istype" {
+            if let (Some(value), Some(path)) = (param_idx_map.get(&0), param_idx_map.get(&1)) {
+                self.check_istype(location, value, path);
+            }
+        }
+        if proc.ty().is_root() && matches!(proc.name(), "istype" | "ispath") {
+            if let Some(path) = param_idx_map.get(&1).filter(|path| is_string(path)) {
+                self.string_for_path(location, format!("{}() expects a typepath, but is given", proc.name()), path);
+            }
+        } else if !arglist_used {
+            let parameters = &proc.get().parameters;
+            for (idx, argument) in param_idx_map.iter() {
+                if let Some(parameter) = parameters.get(*idx) {
+                    self.check_argument_kinds(location, proc, parameter, argument);
+                }
+            }
+            for (name, argument) in param_name_map.iter() {
+                if let Some(parameter) = parameters.iter().find(|parameter| parameter.name == *name) {
+                    self.check_argument_kinds(location, proc, parameter, argument);
+                }
+            }
+        }
+
         // filter call checking
         // TODO: some filters have limits for their numerical params
         //  eg "rays" type "threshold" param defaults to 0.5, can be 0 to 1
@@ -2468,7 +4053,7 @@ impl<'o, 's> AnalyzeProc<'o, 's> {
     fn visit_arguments(&mut self, location: Location, args: &'o [Expression], local_vars: &mut HashMap<String, LocalVar<'o>>) {
         for arg in args {
             let mut argument_value = arg;
-            if let Expression::AssignOp { op: AssignOp::Assign, lhs, rhs } = arg {
+            if let Expression::AssignOp { op: AssignOp::Assign, lhs, rhs, .. } = arg {
                 match lhs.as_term() {
                     Some(Term::Ident(_name)) |
                     Some(Term::String(_name)) => {
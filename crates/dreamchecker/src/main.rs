@@ -14,7 +14,14 @@ fn main() {
     let mut environment = None;
     let mut config_file = None;
     let mut json = false;
+    let mut format_json = false;
     let mut parse_only = false;
+    let mut baseline_file = None;
+    let mut write_baseline = None;
+    let mut metrics = false;
+    let mut metrics_sort = "complexity".to_owned();
+    let mut includes = None;
+    let mut define_args = Vec::new();
 
     let mut args = std::env::args();
     let _ = args.next();  // skip executable name
@@ -33,10 +40,45 @@ fn main() {
             environment = Some(args.next().expect("must specify a value for -e"));
         } else if arg == "-c" {
             config_file = Some(args.next().expect("must specify a file for -c"));
+        } else if arg == "--define" {
+            let define = args.next().expect("must specify a NAME=VALUE for --define");
+            match define.split_once('=') {
+                Some((name, value)) => define_args.push((name.to_owned(), value.to_owned())),
+                None => define_args.push((define, String::new())),
+            }
         } else if arg == "--json" {
             json = true;
+        } else if arg == "--format" {
+            let format = args.next().expect("must specify a format for --format");
+            if format != "json" && format != "text" {
+                eprintln!("unknown format for --format: {}", format);
+                eprintln!("expected one of: json, text");
+                return;
+            }
+            format_json = format == "json";
         } else if arg == "--parse-only" {
             parse_only = true;
+        } else if arg == "--baseline" {
+            baseline_file = Some(args.next().expect("must specify a file for --baseline"));
+        } else if arg == "--write-baseline" {
+            write_baseline = Some(args.next().expect("must specify a file for --write-baseline"));
+        } else if arg == "--metrics" {
+            metrics = true;
+        } else if arg == "--metrics-sort" {
+            metrics_sort = args.next().expect("must specify a column for --metrics-sort");
+            if !dreamchecker::metrics::COLUMNS.contains(&metrics_sort.as_str()) {
+                eprintln!("unknown column for --metrics-sort: {}", metrics_sort);
+                eprintln!("expected one of: {}", dreamchecker::metrics::COLUMNS.join(", "));
+                return;
+            }
+        } else if arg == "--includes" {
+            let format = args.next().expect("must specify a format for --includes");
+            if format != "json" && format != "dot" {
+                eprintln!("unknown format for --includes: {}", format);
+                eprintln!("expected one of: json, dot");
+                return;
+            }
+            includes = Some(format);
         } else {
             eprintln!("unknown argument: {}", arg);
             return;
@@ -49,30 +91,103 @@ fn main() {
             .expect("error detecting .dme")
             .expect("no .dme found"));
 
+    let print_severity = dm::Severity::Info;
     let mut context = dm::Context::default();
-    context.set_print_severity(Some(dm::Severity::Info));
     if let Some(filepath) = config_file {
         context.force_config(filepath.as_ref());
     } else {
         context.autodetect_config(&dme);
     }
+    for (name, value) in define_args {
+        context.add_define(name, value);
+    }
 
-    println!("============================================================");
-    println!("Parsing {}...\n", dme.display());
-    let pp = dm::preprocessor::Preprocessor::new(&context, dme)
+    let env_dir = dme.parent().unwrap_or(std::path::Path::new("")).to_owned();
+    let baseline_file = baseline_file
+        .map(std::path::PathBuf::from)
+        .or_else(|| context.config().dreamchecker.baseline.as_ref().map(|path| env_dir.join(path)))
+        .filter(|_| write_baseline.is_none());
+    // Diagnostics are printed as they are found, unless the baseline has to
+    // filter them first.
+    let print_later = !format_json && baseline_file.is_some();
+    if !format_json && !print_later {
+        context.set_print_severity(Some(print_severity));
+    }
+
+    if !format_json {
+        println!("============================================================");
+        println!("Parsing {}...\n", dme.display());
+    }
+    let mut pp = dm::preprocessor::Preprocessor::new(&context, dme.clone())
         .expect("i/o error opening .dme");
-    let indents = dm::indents::IndentProcessor::new(&context, pp);
+    let indents = dm::indents::IndentProcessor::new(&context, &mut pp);
     let mut parser = dm::parser::Parser::new(&context, indents);
     parser.enable_procs();
     let (fatal_errored, tree) = parser.parse_object_tree_2();
+    let defines = pp.finalize();
+    let usage = dreamchecker::unused_definitions::DefineUsage::new(&defines);
+    let macros = dreamchecker::MacroNames::new(&defines);
+
+    if metrics {
+        print_metrics(&context, &tree, &metrics_sort, json);
+        return;
+    }
+    if let Some(format) = includes {
+        print_includes(&context, &format);
+        return;
+    }
 
     if !parse_only && !fatal_errored {
-        dreamchecker::run_cli(&context, &tree);
+        if format_json {
+            dreamchecker::run(&context, &tree, &macros);
+        } else {
+            dreamchecker::run_cli(&context, &tree, &macros);
+        }
+        if let Some(usage) = usage {
+            dreamchecker::unused_definitions::check_unused_definitions(&context, &tree, &usage);
+        }
+        dreamchecker::unused_definitions::check_unincluded_files(&context, dme.parent().unwrap_or(std::path::Path::new("")));
+        let mapped = dreamchecker::verbs::mapped_types(defines.maps());
+        dreamchecker::verbs::check_unreachable_verbs(&context, &tree, &mapped);
     }
 
-    println!("============================================================");
-    let errors = context.errors().iter().filter(|each| each.severity() <= dm::Severity::Info).count();
-    println!("Found {} diagnostics", errors);
+    if let Some(path) = write_baseline {
+        let baseline = dm::baseline::Baseline::from_errors(&context, &env_dir);
+        baseline.write(path.as_ref()).expect("error writing baseline");
+        println!("============================================================");
+        println!("Wrote {} diagnostics to {}", baseline.entries().len(), path);
+        return;
+    }
+
+    if let Some(path) = baseline_file {
+        let baseline = dm::baseline::Baseline::read(&path).expect("error reading baseline");
+        let stale = baseline.apply(&context, &env_dir);
+        if !stale.is_empty() && !format_json {
+            println!("============================================================");
+            println!("{} entries in {} no longer match:\n", stale.len(), path.display());
+            for entry in stale.iter() {
+                println!("{}\t{}\t{}", entry.code, entry.file, entry.description);
+            }
+            println!("\nRerun with --write-baseline to trim them.");
+        }
+    }
+
+    if print_later {
+        let stderr = termcolor::StandardStream::stderr(termcolor::ColorChoice::Auto);
+        let mut stderr = stderr.lock();
+        for error in context.errors().iter().filter(|each| each.severity() <= print_severity) {
+            context.pretty_print_error(&mut stderr, error).expect("error writing to stderr");
+        }
+    }
+
+    if format_json {
+        dreamchecker::report::write_json_lines(&context, print_severity, std::io::stdout().lock())
+            .expect("error writing diagnostics");
+    } else {
+        println!("============================================================");
+        let errors = context.errors().iter().filter(|each| each.severity() <= print_severity).count();
+        println!("Found {} diagnostics", errors);
+    }
 
     if json {
         serde_json::to_writer(std::io::stdout().lock(), &json! {{
@@ -83,5 +198,67 @@ fn main() {
         }}).unwrap();
     }
 
-    std::process::exit(if errors > 0 { 1 } else { 0 });
+    // Only errors fail the run; the config can raise anything else to an error.
+    let failed = context.errors().iter().any(|each| each.severity() == dm::Severity::Error);
+    std::process::exit(if failed { 1 } else { 0 });
+}
+
+fn print_metrics(context: &dm::Context, tree: &dm::objtree::ObjectTree, sort: &str, json: bool) {
+    let mut metrics = dreamchecker::metrics::proc_metrics(tree);
+    dreamchecker::metrics::sort_metrics(&mut metrics, sort);
+
+    if json {
+        let list: Vec<_> = metrics.iter().map(|each| json! {{
+            "path": each.path,
+            "file": context.file_path(each.location.file).display().to_string(),
+            "line": each.location.line,
+            "complexity": each.complexity,
+            "statements": each.statements,
+            "nesting": each.nesting,
+            "parameters": each.parameters,
+        }}).collect();
+        serde_json::to_writer(std::io::stdout().lock(), &list).unwrap();
+        println!();
+        return;
+    }
+
+    println!("============================================================");
+    println!("{:>10} {:>10} {:>7} {:>10}  proc", "complexity", "statements", "nesting", "parameters");
+    for each in metrics.iter() {
+        println!(
+            "{:>10} {:>10} {:>7} {:>10}  {}",
+            each.complexity, each.statements, each.nesting, each.parameters, each.path,
+        );
+    }
+}
+
+fn print_includes(context: &dm::Context, format: &str) {
+    let graph = context.include_graph();
+    let file_name = |file: dm::FileId| context.file_path(file).display().to_string();
+
+    if format == "json" {
+        let list: Vec<_> = graph.edges().iter().map(|edge| json! {{
+            "from": file_name(edge.location.file),
+            "line": edge.location.line,
+            "column": edge.location.column,
+            "include": edge.written,
+            "path": edge.path.as_ref().map(|path| path.display().to_string()),
+        }}).collect();
+        serde_json::to_writer(std::io::stdout().lock(), &json! {{
+            "root": graph.root().map(file_name),
+            "includes": list,
+        }}).unwrap();
+        println!();
+        return;
+    }
+
+    println!("digraph includes {{");
+    for (order, edge) in graph.edges().iter().enumerate() {
+        let from = file_name(edge.location.file);
+        match edge.path {
+            Some(ref path) => println!("    {:?} -> {:?} [label=\"{}\"];", from, path.display().to_string(), order + 1),
+            None => println!("    {:?} -> {:?} [label=\"{}\", style=dashed, color=red];", from, edge.written, order + 1),
+        }
+    }
+    println!("}}");
 }
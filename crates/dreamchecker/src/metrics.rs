@@ -0,0 +1,332 @@
+//! Size and complexity metrics for proc bodies.
+
+use dm::ast::*;
+use dm::objtree::{ObjectTree, ProcRef};
+use dm::{Context, DMError, Location, Severity};
+
+/// Metrics of one proc definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcMetrics {
+    /// The proc, as `/type/proc/name`.
+    pub path: String,
+    pub location: Location,
+    /// Number of statements, counting those in nested blocks.
+    pub statements: usize,
+    /// Cyclomatic complexity: one, plus one for each `if` arm, `switch`
+    /// case, loop, `catch`, `&&`, `||`, and `? :`.
+    pub complexity: usize,
+    /// Deepest nesting of blocks within the body.
+    pub nesting: usize,
+    pub parameters: usize,
+}
+
+impl ProcMetrics {
+    /// Measure a proc, if it has a body.
+    pub fn of(proc: ProcRef) -> Option<ProcMetrics> {
+        let value = proc.get();
+        let code = value.code.as_ref()?;
+        let mut walk = MetricsWalk {
+            statements: 0,
+            complexity: 1,
+            depth: 0,
+            nesting: 0,
+        };
+        walk.visit_block(code);
+        Some(ProcMetrics {
+            path: format!("{}/proc/{}", proc.ty().path, proc.name()),
+            location: value.location,
+            statements: walk.statements,
+            complexity: walk.complexity,
+            nesting: walk.nesting,
+            parameters: value.parameters.len(),
+        })
+    }
+
+    /// The value of a metric by its column name.
+    pub fn get(&self, column: &str) -> Option<usize> {
+        match column {
+            "statements" => Some(self.statements),
+            "complexity" => Some(self.complexity),
+            "nesting" => Some(self.nesting),
+            "parameters" => Some(self.parameters),
+            _ => None,
+        }
+    }
+}
+
+/// The names of the metrics, as used by `ProcMetrics::get`.
+pub const COLUMNS: &[&str] = &["complexity", "statements", "nesting", "parameters"];
+
+/// Measure every proc definition in the tree, most complex first.
+pub fn proc_metrics(objtree: &ObjectTree) -> Vec<ProcMetrics> {
+    let mut metrics = Vec::new();
+    objtree.root().recurse(&mut |ty| {
+        for proc in ty.iter_self_procs() {
+            if !proc.is_builtin() {
+                metrics.extend(ProcMetrics::of(proc));
+            }
+        }
+    });
+    sort_metrics(&mut metrics, "complexity");
+    metrics
+}
+
+/// Sort by a metric, largest first, then by path.
+pub fn sort_metrics(metrics: &mut [ProcMetrics], column: &str) {
+    metrics.sort_by(|a, b| b.get(column).cmp(&a.get(column)).then_with(|| a.path.cmp(&b.path)));
+}
+
+/// Report a proc which exceeds the thresholds in the config.
+pub fn check_proc_metrics(proc: ProcRef, context: &Context) {
+    let config = &context.config().dreamchecker;
+    let Some(metrics) = ProcMetrics::of(proc) else { return };
+    let checks = [
+        (config.max_complexity, metrics.complexity, "complexity", "proc_complexity"),
+        (config.max_statements, metrics.statements, "statement count", "proc_length"),
+        (config.max_nesting, metrics.nesting, "nesting depth", "proc_nesting"),
+        (config.max_parameters, metrics.parameters, "parameter count", "proc_parameters"),
+    ];
+    for (limit, value, what, errortype) in checks {
+        match limit {
+            Some(limit) if value > limit => {
+                DMError::new(metrics.location, format!("{} has {} {}, more than the limit of {}", metrics.path, what, value, limit))
+                    .with_component(dm::Component::DreamChecker)
+                    .set_severity(Severity::Warning)
+                    .with_errortype(errortype)
+                    .register(context);
+            }
+            _ => {}
+        }
+    }
+}
+
+struct MetricsWalk {
+    statements: usize,
+    complexity: usize,
+    depth: usize,
+    nesting: usize,
+}
+
+impl MetricsWalk {
+    fn visit_block(&mut self, block: &[Spanned<Statement>]) {
+        self.depth += 1;
+        self.nesting = self.nesting.max(self.depth - 1);
+        for stmt in block.iter() {
+            self.visit_statement(&stmt.elem);
+        }
+        self.depth -= 1;
+    }
+
+    // Every variant is listed so that new statements must be measured.
+    fn visit_statement(&mut self, statement: &Statement) {
+        self.statements += 1;
+        match statement {
+            Statement::Expr(expr)
+            | Statement::Throw(expr)
+            | Statement::Del(expr) => self.visit_expression(expr),
+            Statement::Return(expr) | Statement::Crash(expr) => {
+                if let Some(expr) = expr {
+                    self.visit_expression(expr);
+                }
+            },
+            Statement::While { condition, block } => {
+                self.complexity += 1;
+                self.visit_expression(condition);
+                self.visit_block(block);
+            },
+            Statement::DoWhile { block, condition } => {
+                self.complexity += 1;
+                self.visit_block(block);
+                self.visit_expression(&condition.elem);
+            },
+            Statement::If { arms, else_arm } => {
+                for (condition, block) in arms.iter() {
+                    self.complexity += 1;
+                    self.visit_expression(&condition.elem);
+                    self.visit_block(block);
+                }
+                if let Some(else_arm) = else_arm {
+                    self.visit_block(else_arm);
+                }
+            },
+            Statement::ForInfinite { block } => {
+                self.complexity += 1;
+                self.visit_block(block);
+            },
+            Statement::ForLoop { init, test, inc, block } => {
+                self.complexity += 1;
+                // The clauses are part of the loop, not statements of their own.
+                for each in [init, inc].into_iter().flatten() {
+                    self.visit_statement(each);
+                    self.statements -= 1;
+                }
+                if let Some(test) = test {
+                    self.visit_expression(test);
+                }
+                self.visit_block(block);
+            },
+            Statement::ForList(for_list) => {
+                self.complexity += 1;
+                if let Some(in_list) = &for_list.in_list {
+                    self.visit_expression(in_list);
+                }
+                self.visit_block(&for_list.block);
+            },
+            Statement::ForRange(for_range) => {
+                self.complexity += 1;
+                self.visit_expression(&for_range.start);
+                self.visit_expression(&for_range.end);
+                if let Some(step) = &for_range.step {
+                    self.visit_expression(step);
+                }
+                self.visit_block(&for_range.block);
+            },
+            Statement::Var(var) => {
+                if let Some(value) = &var.value {
+                    self.visit_expression(value);
+                }
+            },
+            Statement::Vars(vars) => {
+                for value in vars.iter().filter_map(|var| var.value.as_ref()) {
+                    self.visit_expression(value);
+                }
+            },
+            Statement::Setting { .. } => {},
+            Statement::Spawn { delay, block } => {
+                if let Some(delay) = delay {
+                    self.visit_expression(delay);
+                }
+                self.visit_block(block);
+            },
+            Statement::Switch { input, cases, default } => {
+                self.visit_expression(input);
+                for (_, block) in cases.iter() {
+                    self.complexity += 1;
+                    self.visit_block(block);
+                }
+                if let Some(default) = default {
+                    self.visit_block(default);
+                }
+            },
+            Statement::TryCatch { try_block, catch_block, .. } => {
+                self.complexity += 1;
+                self.visit_block(try_block);
+                self.visit_block(catch_block);
+            },
+            Statement::Label { block, .. } => self.visit_block(block),
+            Statement::Continue(_)
+            | Statement::Break(_)
+            | Statement::Goto(_) => {},
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Base { term, follow } => {
+                self.visit_term(&term.elem);
+                for each in follow.iter() {
+                    match &each.elem {
+                        Follow::Call(_, _, args) => self.visit_arguments(args),
+                        Follow::Index(_, index) => self.visit_expression(index),
+                        _ => {},
+                    }
+                }
+            },
+            Expression::BinaryOp { op, lhs, rhs } => {
+                if matches!(op, BinaryOp::And | BinaryOp::Or) {
+                    self.complexity += 1;
+                }
+                self.visit_expression(lhs);
+                self.visit_expression(rhs);
+            },
+            Expression::AssignOp { op, lhs, rhs, .. } => {
+                if matches!(op, AssignOp::AndAssign | AssignOp::OrAssign) {
+                    self.complexity += 1;
+                }
+                self.visit_expression(lhs);
+                self.visit_expression(rhs);
+            },
+            Expression::TernaryOp { cond, if_, else_ } => {
+                self.complexity += 1;
+                self.visit_expression(cond);
+                self.visit_expression(if_);
+                self.visit_expression(else_);
+            },
+        }
+    }
+
+    fn visit_term(&mut self, term: &Term) {
+        match term {
+            Term::Expr(expr) => self.visit_expression(expr),
+            Term::Prefab(prefab) => self.visit_prefab(prefab),
+            Term::InterpString(_, parts) => {
+                for (expr, _) in parts.iter() {
+                    if let Some(expr) = &expr.elem {
+                        self.visit_expression(expr);
+                    }
+                }
+            },
+            Term::Call(_, args)
+            | Term::SelfCall(args)
+            | Term::ParentCall(args)
+            | Term::GlobalCall(_, args)
+            | Term::List(args) => self.visit_arguments(args),
+            Term::NewImplicit { args } | Term::NewMiniExpr { args, .. } => {
+                if let Some(args) = args {
+                    self.visit_arguments(args);
+                }
+            },
+            Term::NewPrefab { prefab, args } => {
+                self.visit_prefab(prefab);
+                if let Some(args) = args {
+                    self.visit_arguments(args);
+                }
+            },
+            Term::Input { args, in_list, .. } | Term::Locate { args, in_list } => {
+                self.visit_arguments(args);
+                if let Some(in_list) = in_list {
+                    self.visit_expression(in_list);
+                }
+            },
+            Term::Pick(choices) => {
+                for (weight, value) in choices.iter() {
+                    if let Some(weight) = weight {
+                        self.visit_expression(weight);
+                    }
+                    self.visit_expression(value);
+                }
+            },
+            Term::DynamicCall(lhs_args, rhs_args) => {
+                self.visit_arguments(lhs_args);
+                self.visit_arguments(rhs_args);
+            },
+            Term::ExternalCall { library_name, function_name, args } => {
+                self.visit_expression(library_name);
+                self.visit_expression(function_name);
+                self.visit_arguments(args);
+            },
+            Term::Null
+            | Term::Int(_)
+            | Term::Float(_)
+            | Term::Ident(_)
+            | Term::String(_)
+            | Term::Resource(_)
+            | Term::As(_)
+            | Term::__PROC__
+            | Term::__TYPE__
+            | Term::__IMPLIED_TYPE__
+            | Term::GlobalIdent(_)
+            | Term::Error => {},
+        }
+    }
+
+    fn visit_prefab(&mut self, prefab: &Prefab) {
+        for (_, value) in prefab.vars.iter() {
+            self.visit_expression(value);
+        }
+    }
+
+    fn visit_arguments(&mut self, args: &[Expression]) {
+        args.iter().for_each(|arg| self.visit_expression(arg));
+    }
+}
@@ -0,0 +1,48 @@
+//! Machine-readable output of diagnostics, for CI systems to annotate with.
+
+use std::io::{self, Write};
+
+use dm::{Context, DMError, Location};
+use serde_json::{json, Value};
+
+/// Describe a diagnostic as a JSON object.
+///
+/// Lines and columns start at 1. The range ends at the diagnostic's end if it
+/// has one, or else is empty.
+pub fn diagnostic_json(context: &Context, error: &DMError) -> Value {
+    let location = error.location();
+    let end = error.end().unwrap_or(location);
+    let related: Vec<_> = error.notes().iter().map(|note| json! {{
+        "file": file_name(context, note.location()),
+        "line": note.location().line,
+        "column": note.location().column,
+        "message": note.description(),
+    }}).collect();
+    json! {{
+        "file": file_name(context, location),
+        "range": {
+            "start": { "line": location.line, "column": location.column },
+            "end": { "line": end.line, "column": end.column },
+        },
+        "severity": error.severity().to_string(),
+        "component": error.component().name(),
+        "code": error.errortype().and_then(dm::codes::code_for),
+        "errortype": error.errortype(),
+        "message": error.description(),
+        "related": related,
+    }}
+}
+
+/// Write one JSON object per line for each diagnostic at or above a
+/// severity.
+pub fn write_json_lines<W: Write>(context: &Context, print_severity: dm::Severity, mut out: W) -> io::Result<()> {
+    for error in context.errors().iter().filter(|each| each.severity() <= print_severity) {
+        serde_json::to_writer(&mut out, &diagnostic_json(context, error))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn file_name(context: &Context, location: Location) -> String {
+    context.file_path(location.file).display().to_string()
+}
@@ -0,0 +1,59 @@
+use dm::ast::*;
+use dm::constants::Constant;
+use dm::{Context, DMError, Location, Severity};
+
+enum CaseValue {
+    Exact(Constant),
+    Range(f32, f32),
+}
+
+impl CaseValue {
+    fn overlaps(&self, other: &CaseValue) -> bool {
+        match (self, other) {
+            (CaseValue::Exact(a), CaseValue::Exact(b)) => a == b,
+            (CaseValue::Exact(value), &CaseValue::Range(min, max))
+            | (&CaseValue::Range(min, max), CaseValue::Exact(value)) => {
+                value.to_float().is_some_and(|value| min <= value && value <= max)
+            }
+            (&CaseValue::Range(min_a, max_a), &CaseValue::Range(min_b, max_b)) => min_a <= max_b && min_b <= max_a,
+        }
+    }
+}
+
+/**
+ * Checks for switch cases which can never match because an earlier case
+ * already matches the same value. Cases which aren't constant are skipped.
+ */
+pub fn check_switch_duplicate_cases(cases: &SwitchCases, context: &Context) {
+    let mut earlier: Vec<(CaseValue, Location)> = Vec::new();
+    for case_block in cases.iter() {
+        let location = case_block.0.location;
+        for case in case_block.0.elem.iter() {
+            let Some(value) = get_case_value(case, location) else { continue };
+            if let Some((_, earlier_location)) = earlier.iter().find(|(other, _)| other.overlaps(&value)) {
+                let desc = match value {
+                    CaseValue::Exact(ref constant) => format!("switch case {} duplicates an earlier case", constant),
+                    CaseValue::Range(min, max) => format!("switch case range {} to {} overlaps an earlier case", min, max),
+                };
+                DMError::new(location, desc)
+                    .with_component(dm::Component::DreamChecker)
+                    .with_note(*earlier_location, "earlier case here")
+                    .with_errortype("duplicate_switch_case")
+                    .set_severity(Severity::Warning)
+                    .register(context);
+            }
+            earlier.push((value, location));
+        }
+    }
+}
+
+fn get_case_value(case: &Case, location: Location) -> Option<CaseValue> {
+    match case {
+        Case::Exact(ref value) => Some(CaseValue::Exact(value.to_owned().simple_evaluate(location).ok()?)),
+        Case::Range(ref min, ref max) => {
+            let min = min.to_owned().simple_evaluate(location).ok()?.to_float()?;
+            let max = max.to_owned().simple_evaluate(location).ok()?.to_float()?;
+            Some(CaseValue::Range(min, max))
+        }
+    }
+}
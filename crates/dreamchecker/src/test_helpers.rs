@@ -1,22 +1,43 @@
 use dm::Context;
 use std::borrow::Cow;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::run_inner;
+use crate::{run_inner, MacroNames};
 
 pub const NO_ERRORS: &[(u32, u16, &str)] = &[];
 
+/// A fresh context configured from the given `SpacemanDMM.toml` contents.
+pub fn context_with_config(toml: &str) -> Context {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let config = std::env::temp_dir().join(format!(
+        "dreamchecker_test_{}_{}.toml",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+    ));
+    std::fs::write(&config, toml).unwrap();
+    let mut context = Context::default();
+    context.force_config(&config);
+    std::fs::remove_file(&config).unwrap();
+    context
+}
+
 pub fn parse_a_file_for_test<S: Into<Cow<'static, str>>>(buffer: S) -> Context {
-    let context = Context::default();
+    parse_a_file_in_context_for_test(Context::default(), buffer)
+}
+
+/// Like `parse_a_file_for_test`, but with a context carrying some config.
+pub fn parse_a_file_in_context_for_test<S: Into<Cow<'static, str>>>(context: Context, buffer: S) -> Context {
 
-    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.rs".into(), buffer.into());
+    let mut pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.rs".into(), buffer.into());
 
-    let indents = dm::indents::IndentProcessor::new(&context, pp);
+    let indents = dm::indents::IndentProcessor::new(&context, &mut pp);
 
     let mut parser = dm::parser::Parser::new(&context, indents);
     parser.enable_procs();
     let tree = parser.parse_object_tree();
 
-    run_inner(&context, &tree, false);
+    let macros = MacroNames::new(&pp.finalize());
+    run_inner(&context, &tree, &macros, false);
 
     context
 }
@@ -0,0 +1,504 @@
+//! Detection of macros which are never used, included files whose
+//! definitions are never used elsewhere, and code files which are never
+//! included.
+
+use std::path::Path;
+
+use foldhash::{HashMap, HashSet};
+
+use dm::ast::*;
+use dm::objtree::{ObjectTree, TypeRef};
+use dm::preprocessor::{DefineHistory, MacroUses};
+use dm::{Context, DMError, FileId, Location, Severity};
+
+/// Macros which the compiler reads itself.
+const COMPILER_MACROS: &[&str] = &["DEBUG", "FILE_DIR"];
+
+/// What the preprocessor saw of macro definitions, macro uses, and includes.
+///
+/// Unlike the `DefineHistory` it is built from, this may be sent to another
+/// thread.
+#[derive(Debug, Clone)]
+pub struct DefineUsage {
+    macros: Vec<(String, Location)>,
+    macro_uses: MacroUses,
+    include_locations: HashMap<FileId, Location>,
+}
+
+impl DefineUsage {
+    /// Collect the usage from a finished preprocessor's history, if the
+    /// config enabled tracking it.
+    pub fn new(defines: &DefineHistory) -> Option<DefineUsage> {
+        let macro_uses = defines.macro_uses()?.clone();
+        let mut macros: Vec<_> = defines.iter()
+            .filter(|(range, _)| !range.start.is_builtins())
+            .map(|(range, (name, _))| (name.clone(), range.start))
+            .collect();
+        macros.sort_by_key(|&(_, location)| location);
+        Some(DefineUsage {
+            macros,
+            macro_uses,
+            include_locations: defines.include_locations().clone(),
+        })
+    }
+
+    fn macro_used_outside(&self, name: &str, file: FileId) -> bool {
+        self.macro_uses.get(name).is_some_and(|files| used_outside(files, file))
+    }
+}
+
+/// Report unused macros and includes, as enabled by the config.
+///
+/// Macros and includes are each reported in file order, so the diagnostics
+/// for one file stay together.
+pub fn check_unused_definitions(context: &Context, objtree: &ObjectTree, usage: &DefineUsage) {
+    let config = &context.config().dreamchecker;
+    if config.unused_macros {
+        check_unused_macros(context, usage);
+    }
+    if config.unused_includes {
+        check_unused_includes(context, objtree, usage);
+    }
+}
+
+fn check_unused_macros(context: &Context, usage: &DefineUsage) {
+    for (name, location) in usage.macros.iter() {
+        if COMPILER_MACROS.contains(&name.as_str()) || usage.macro_uses.contains_key(name) {
+            continue;
+        }
+        DMError::new(*location, format!("unused macro: {:?}", name))
+            .with_component(dm::Component::DreamChecker)
+            .set_severity(Severity::Warning)
+            .with_errortype("unused_macro")
+            .register(context);
+    }
+}
+
+fn check_unused_includes(context: &Context, objtree: &ObjectTree, usage: &DefineUsage) {
+    let uses = Uses::collect(objtree);
+    let mut live: HashSet<FileId> = HashSet::default();
+
+    for (name, location) in usage.macros.iter() {
+        if usage.macro_used_outside(name, location.file) {
+            live.insert(location.file);
+        }
+    }
+
+    objtree.root().recurse(&mut |ty| {
+        let declared_in = declaration_file(ty);
+        if let Some(file) = declared_in {
+            if uses.paths.get(ty.path.as_str()).is_some_and(|files| used_outside(files, file)) {
+                live.insert(file);
+            }
+        }
+
+        for (name, type_proc) in ty.procs.iter() {
+            if let Some(declaration) = type_proc.declaration.as_ref() {
+                let file = declaration.location.file;
+                if uses.names.get(name.as_str()).is_some_and(|files| used_outside(files, file)) {
+                    live.insert(file);
+                }
+                // Verbs are available to players without being called.
+                if declaration.kind == ProcDeclKind::Verb && declared_in != Some(file) {
+                    live.insert(file);
+                }
+            }
+            // Overriding a proc declared elsewhere on a type declared
+            // elsewhere changes behavior even if nothing new is called.
+            for value in type_proc.value.iter() {
+                let file = value.location.file;
+                let declaration = ty.get_proc_declaration(name).map(|decl| decl.location.file);
+                if declared_in != Some(file) && declaration != Some(file) {
+                    live.insert(file);
+                }
+            }
+        }
+
+        for (name, type_var) in ty.vars.iter() {
+            match type_var.declaration.as_ref() {
+                Some(declaration) => {
+                    let file = declaration.location.file;
+                    if uses.names.get(name.as_str()).is_some_and(|files| used_outside(files, file)) {
+                        live.insert(file);
+                    }
+                }
+                None => {
+                    let file = type_var.value.location.file;
+                    let declaration = ty.get_var_declaration(name).map(|decl| decl.location.file);
+                    if declared_in != Some(file) && declaration != Some(file) {
+                        live.insert(file);
+                    }
+                }
+            }
+        }
+    });
+
+    // A file which includes a used file is needed for that include.
+    for file in live.clone() {
+        let mut file = file;
+        while let Some(location) = usage.include_locations.get(&file) {
+            if !live.insert(location.file) {
+                break;
+            }
+            file = location.file;
+        }
+    }
+
+    let mut unused: Vec<_> = usage.include_locations.iter()
+        .filter(|(file, _)| !live.contains(file))
+        .map(|(&file, &location)| (location, file))
+        .collect();
+    unused.sort();
+    for (location, file) in unused {
+        DMError::new(location, format!("unused include: {:?}", context.file_path(file).display().to_string()))
+            .with_component(dm::Component::DreamChecker)
+            .set_severity(Severity::Warning)
+            .with_note(Location { file, line: 1, column: 1 }, "nothing defined in this file is used by another file")
+            .with_errortype("unused_include")
+            .register(context);
+    }
+}
+
+/// Report `.dm` files in the environment's directory which it never
+/// includes, if enabled by the config.
+pub fn check_unincluded_files(context: &Context, env_dir: &Path) {
+    if !context.config().dreamchecker.unincluded_files {
+        return;
+    }
+    let unincluded = context.include_graph().unincluded_files(context, env_dir);
+    for path in unincluded {
+        let file = context.register_file(&path);
+        DMError::new(Location { file, line: 1, column: 1 }, "file is not included by the environment")
+            .with_component(dm::Component::DreamChecker)
+            .set_severity(Severity::Warning)
+            .with_errortype("unincluded_file")
+            .register(context);
+    }
+}
+
+fn used_outside(files: &HashSet<FileId>, file: FileId) -> bool {
+    files.iter().any(|&each| each != file)
+}
+
+/// The file in which a type is declared, if it was declared in code.
+fn declaration_file(ty: TypeRef) -> Option<FileId> {
+    if ty.is_root() || ty.location.is_builtins() {
+        None
+    } else {
+        Some(ty.location.file)
+    }
+}
+
+/// The files in which each name and each typepath, including the parents of
+/// typepaths, are mentioned.
+#[derive(Default)]
+pub(crate) struct Uses {
+    names: HashMap<String, HashSet<FileId>>,
+    paths: HashMap<String, HashSet<FileId>>,
+    /// Typepaths, and their parents, which code mentions other than by
+    /// defining them.
+    pub(crate) mentioned: HashSet<String>,
+}
+
+impl Uses {
+    pub(crate) fn collect(objtree: &ObjectTree) -> Uses {
+        let mut uses = Uses::default();
+        objtree.root().recurse(&mut |ty| {
+            if !ty.is_root() {
+                uses.add_path(&ty.path, ty.location.file);
+            }
+            for (name, type_proc) in ty.procs.iter() {
+                for value in type_proc.value.iter() {
+                    let file = value.location.file;
+                    uses.add_name(name, file);
+                    uses.add_path(&ty.path, file);
+                    for parameter in value.parameters.iter() {
+                        uses.add_tree_path(&parameter.var_type.type_path, file);
+                        for expr in parameter.default.iter().chain(parameter.in_list.iter()) {
+                            uses.visit_expression(expr, file);
+                        }
+                    }
+                    if let Some(code) = value.code.as_ref() {
+                        uses.visit_block(code, file);
+                    }
+                }
+            }
+            for (name, type_var) in ty.vars.iter() {
+                let file = type_var.value.location.file;
+                uses.add_name(name, file);
+                uses.add_path(&ty.path, file);
+                if let Some(declaration) = type_var.declaration.as_ref() {
+                    uses.add_tree_path(&declaration.var_type.type_path, declaration.location.file);
+                }
+                if let Some(expr) = type_var.value.expression.as_ref() {
+                    uses.visit_expression(expr, file);
+                }
+            }
+        });
+        uses
+    }
+
+    fn add_name(&mut self, name: &str, file: FileId) {
+        self.names.entry(name.to_owned()).or_default().insert(file);
+    }
+
+    fn add_path(&mut self, path: &str, file: FileId) {
+        let mut path = path;
+        while !path.is_empty() {
+            self.paths.entry(path.to_owned()).or_default().insert(file);
+            path = &path[..path.rfind('/').unwrap_or(0)];
+        }
+    }
+
+    fn add_mention(&mut self, path: &str, file: FileId) {
+        self.add_path(path, file);
+        let mut path = path;
+        while !path.is_empty() && self.mentioned.insert(path.to_owned()) {
+            path = &path[..path.rfind('/').unwrap_or(0)];
+        }
+    }
+
+    fn add_tree_path(&mut self, path: &[Ident], file: FileId) {
+        if !path.is_empty() {
+            self.add_mention(&format!("/{}", path.join("/")), file);
+        }
+    }
+
+    fn visit_prefab(&mut self, prefab: &Prefab, file: FileId) {
+        // Proc references such as `/datum/proc/name` mention the proc by
+        // name, so every segment is counted as a name too.
+        let mut path = String::new();
+        for (_, segment) in prefab.path.iter() {
+            self.add_name(segment, file);
+            path.push('/');
+            path.push_str(segment);
+        }
+        self.add_mention(&path, file);
+        for (name, value) in prefab.vars.iter() {
+            self.add_name(name, file);
+            self.visit_expression(value, file);
+        }
+    }
+
+    fn visit_block(&mut self, block: &[Spanned<Statement>], file: FileId) {
+        for stmt in block.iter() {
+            self.visit_statement(&stmt.elem, file);
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &Statement, file: FileId) {
+        match statement {
+            Statement::Expr(expr)
+            | Statement::Throw(expr)
+            | Statement::Del(expr) => self.visit_expression(expr, file),
+            Statement::Return(expr) | Statement::Crash(expr) => {
+                if let Some(expr) = expr {
+                    self.visit_expression(expr, file);
+                }
+            },
+            Statement::While { condition, block } => {
+                self.visit_expression(condition, file);
+                self.visit_block(block, file);
+            },
+            Statement::DoWhile { block, condition } => {
+                self.visit_block(block, file);
+                self.visit_expression(&condition.elem, file);
+            },
+            Statement::If { arms, else_arm } => {
+                for (condition, block) in arms.iter() {
+                    self.visit_expression(&condition.elem, file);
+                    self.visit_block(block, file);
+                }
+                if let Some(else_arm) = else_arm {
+                    self.visit_block(else_arm, file);
+                }
+            },
+            Statement::ForInfinite { block } => self.visit_block(block, file),
+            Statement::ForLoop { init, test, inc, block } => {
+                for each in [init, inc].into_iter().flatten() {
+                    self.visit_statement(each, file);
+                }
+                if let Some(test) = test {
+                    self.visit_expression(test, file);
+                }
+                self.visit_block(block, file);
+            },
+            Statement::ForList(for_list) => {
+                if let Some(var_type) = &for_list.var_type {
+                    self.add_tree_path(&var_type.type_path, file);
+                }
+                if let Some(in_list) = &for_list.in_list {
+                    self.visit_expression(in_list, file);
+                }
+                self.visit_block(&for_list.block, file);
+            },
+            Statement::ForRange(for_range) => {
+                self.visit_expression(&for_range.start, file);
+                self.visit_expression(&for_range.end, file);
+                if let Some(step) = &for_range.step {
+                    self.visit_expression(step, file);
+                }
+                self.visit_block(&for_range.block, file);
+            },
+            Statement::Var(var) => {
+                self.add_tree_path(&var.var_type.type_path, file);
+                if let Some(value) = &var.value {
+                    self.visit_expression(value, file);
+                }
+            },
+            Statement::Vars(vars) => {
+                for var in vars.iter() {
+                    self.add_tree_path(&var.var_type.type_path, file);
+                    if let Some(value) = &var.value {
+                        self.visit_expression(value, file);
+                    }
+                }
+            },
+            Statement::Setting { .. } => {},
+            Statement::Spawn { delay, block } => {
+                if let Some(delay) = delay {
+                    self.visit_expression(delay, file);
+                }
+                self.visit_block(block, file);
+            },
+            Statement::Switch { input, cases, default } => {
+                self.visit_expression(input, file);
+                for (case_list, block) in cases.iter() {
+                    for case in case_list.elem.iter() {
+                        match case {
+                            Case::Exact(expr) => self.visit_expression(expr, file),
+                            Case::Range(start, end) => {
+                                self.visit_expression(start, file);
+                                self.visit_expression(end, file);
+                            },
+                        }
+                    }
+                    self.visit_block(block, file);
+                }
+                if let Some(default) = default {
+                    self.visit_block(default, file);
+                }
+            },
+            Statement::TryCatch { try_block, catch_block, .. } => {
+                self.visit_block(try_block, file);
+                self.visit_block(catch_block, file);
+            },
+            Statement::Label { block, .. } => self.visit_block(block, file),
+            Statement::Continue(_)
+            | Statement::Break(_)
+            | Statement::Goto(_) => {},
+        }
+    }
+
+    fn visit_expressions(&mut self, exprs: &[Expression], file: FileId) {
+        for expr in exprs.iter() {
+            self.visit_expression(expr, file);
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &Expression, file: FileId) {
+        match expr {
+            Expression::Base { term, follow } => {
+                self.visit_term(&term.elem, file);
+                for each in follow.iter() {
+                    match &each.elem {
+                        Follow::Index(_, index) => self.visit_expression(index, file),
+                        Follow::Field(_, name)
+                        | Follow::StaticField(name)
+                        | Follow::ProcReference(name) => self.add_name(name, file),
+                        Follow::Call(_, name, args) => {
+                            self.add_name(name, file);
+                            self.visit_expressions(args, file);
+                        },
+                        Follow::Unary(_) => {},
+                    }
+                }
+            },
+            Expression::BinaryOp { lhs, rhs, .. }
+            | Expression::AssignOp { lhs, rhs, .. } => {
+                self.visit_expression(lhs, file);
+                self.visit_expression(rhs, file);
+            },
+            Expression::TernaryOp { cond, if_, else_ } => {
+                self.visit_expression(cond, file);
+                self.visit_expression(if_, file);
+                self.visit_expression(else_, file);
+            },
+        }
+    }
+
+    fn visit_term(&mut self, term: &Term, file: FileId) {
+        match term {
+            Term::Ident(name) => self.add_name(name, file),
+            Term::GlobalIdent(name) => self.add_name(name, file),
+            Term::Call(name, args) | Term::GlobalCall(name, args) => {
+                self.add_name(name, file);
+                self.visit_expressions(args, file);
+            },
+            Term::Expr(expr) => self.visit_expression(expr, file),
+            Term::Prefab(prefab) => self.visit_prefab(prefab, file),
+            Term::InterpString(_, parts) => {
+                for expr in parts.iter().filter_map(|(expr, _)| expr.elem.as_ref()) {
+                    self.visit_expression(expr, file);
+                }
+            },
+            Term::SelfCall(args)
+            | Term::ParentCall(args)
+            | Term::List(args) => self.visit_expressions(args, file),
+            Term::NewImplicit { args } => {
+                if let Some(args) = args {
+                    self.visit_expressions(args, file);
+                }
+            },
+            Term::NewPrefab { prefab, args } => {
+                self.visit_prefab(prefab, file);
+                if let Some(args) = args {
+                    self.visit_expressions(args, file);
+                }
+            },
+            Term::NewMiniExpr { expr, args } => {
+                self.add_name(&expr.ident, file);
+                for field in expr.fields.iter() {
+                    self.add_name(&field.ident, file);
+                }
+                if let Some(args) = args {
+                    self.visit_expressions(args, file);
+                }
+            },
+            Term::Input { args, in_list, .. }
+            | Term::Locate { args, in_list } => {
+                self.visit_expressions(args, file);
+                if let Some(in_list) = in_list {
+                    self.visit_expression(in_list, file);
+                }
+            },
+            Term::Pick(args) => {
+                for (weight, value) in args.iter() {
+                    if let Some(weight) = weight {
+                        self.visit_expression(weight, file);
+                    }
+                    self.visit_expression(value, file);
+                }
+            },
+            Term::DynamicCall(lhs, rhs) => {
+                self.visit_expressions(lhs, file);
+                self.visit_expressions(rhs, file);
+            },
+            Term::ExternalCall { library_name, function_name, args } => {
+                self.visit_expression(library_name, file);
+                self.visit_expression(function_name, file);
+                self.visit_expressions(args, file);
+            },
+            Term::Null
+            | Term::Int(_)
+            | Term::Float(_)
+            | Term::String(_)
+            | Term::Resource(_)
+            | Term::As(_)
+            | Term::__PROC__
+            | Term::__TYPE__
+            | Term::__IMPLIED_TYPE__
+            | Term::Error => {},
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Checks on verbs, which players see in menus and panels.
+
+use std::path::PathBuf;
+
+use foldhash::HashSet;
+
+use dm::ast::*;
+use dm::objtree::ObjectTree;
+use dm::{Context, DMError, Severity};
+
+use crate::unused_definitions::Uses;
+
+/// Report verbs which set neither `name` nor `category`, and so appear in
+/// the default menu under their proc name.
+pub fn check_verb_settings(objtree: &ObjectTree, context: &Context) {
+    for ty in objtree.iter_types() {
+        for (name, type_proc) in ty.procs.iter() {
+            match type_proc.declaration.as_ref() {
+                Some(declaration) if declaration.kind == ProcDeclKind::Verb => {},
+                _ => continue,
+            }
+            let Some(value) = type_proc.value.first() else { continue };
+            let Some(code) = value.code.as_ref() else { continue };
+            if value.location.is_builtins() {
+                continue;
+            }
+            let mut named = false;
+            for statement in code.iter() {
+                if let Statement::Setting { name, value, .. } = &statement.elem {
+                    match name.as_str() {
+                        "name" | "category" => named = true,
+                        // Hidden verbs are not in any menu.
+                        "hidden" if value.is_truthy() == Some(true) => named = true,
+                        _ => {},
+                    }
+                }
+            }
+            if !named {
+                DMError::new(value.location, format!("{}/verb/{} sets neither name nor category", ty.path, name))
+                    .with_component(dm::Component::DreamChecker)
+                    .set_severity(Severity::Warning)
+                    .with_errortype("verb_without_name")
+                    .register(context);
+            }
+        }
+    }
+}
+
+/// Report verbs on types which are never created in code nor placed on one
+/// of the given maps, as likely dead UI.
+///
+/// A type counts as created if code mentions it or one of its subtypes as a
+/// typepath, which is a guess in both directions.
+pub fn check_unreachable_verbs(context: &Context, objtree: &ObjectTree, mapped: &HashSet<String>) {
+    let uses = Uses::collect(objtree);
+    for ty in objtree.iter_types() {
+        if ty.is_root() || ty.location.is_builtins() || uses.mentioned.contains(ty.path.as_str()) || mapped.contains(ty.path.as_str()) {
+            continue;
+        }
+        for (name, type_proc) in ty.procs.iter() {
+            match type_proc.declaration.as_ref() {
+                Some(declaration) if declaration.kind == ProcDeclKind::Verb => {
+                    DMError::new(declaration.location, format!("verb {:?} is on {}, which is never created or mapped", name, ty.path))
+                        .with_component(dm::Component::DreamChecker)
+                        .set_severity(Severity::Hint)
+                        .with_errortype("unreachable_verb")
+                        .register(context);
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+/// Read the typepaths placed on the given `.dmm` files, and their parents.
+///
+/// Unreadable maps are skipped.
+pub fn mapped_types(maps: &[PathBuf]) -> HashSet<String> {
+    let mut mapped = HashSet::default();
+    for map in maps {
+        if let Ok(text) = std::fs::read_to_string(map) {
+            scan_map(&text, &mut mapped);
+        }
+    }
+    mapped
+}
+
+/// Collect the typepaths of each prefab in the dictionary.
+fn scan_map(text: &str, mapped: &mut HashSet<String>) {
+    for prefab in dm::dmm::parse_dictionary(text) {
+        let mut path = String::new();
+        for part in prefab.path.iter() {
+            path.push('/');
+            path.push_str(part);
+            mapped.insert(path.clone());
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use std::process::Command;
+
+#[test]
+fn baselined_findings_are_not_printed() {
+    let dir = std::env::temp_dir().join(format!("dreamchecker_baseline_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("test.dme"), "#include \"code.dm\"\n").unwrap();
+    std::fs::write(dir.join("code.dm"), "/proc/first()\n    var/tmp/a = 1\n    return a\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_dreamchecker"))
+            .arg("-e").arg("test.dme")
+            .args(args)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    };
+
+    let (_, stderr) = run(&[]);
+    assert!(stderr.contains("var/tmp has no effect here"), "{}", stderr);
+    run(&["--write-baseline", "baseline.txt"]);
+    let (stdout, stderr) = run(&["--baseline", "baseline.txt"]);
+    assert!(!stderr.contains("var/tmp has no effect here"), "{}", stderr);
+    assert!(stdout.contains("Found 0 diagnostics"), "{}", stdout);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -75,6 +75,7 @@ fn do_while() {
 pub const FOR_LOOP_CONDITION_ERRORS: &[(u32, u16, &str)] = &[
     (4, 5, "loop condition is always true"),
     (4, 5, "control flow condition is a static term"),
+    (6, 5, "loop condition is always true"),
     (6, 5, "control flow condition is a constant evalutation"),
 ];
 
@@ -94,3 +95,140 @@ fn for_loop_condition() {
 "##.trim();
     check_errors_match(code, FOR_LOOP_CONDITION_ERRORS);
 }
+
+pub const UNREACHABLE_ERRORS: &[(u32, u16, &str)] = &[
+    (5, 13, "possible unreachable code here"),
+    (8, 13, "possible unreachable code here"),
+    (11, 9, "possible unreachable code here"),
+    (17, 5, "possible unreachable code here"),
+];
+
+#[test]
+fn unreachable() {
+    let code = r##"
+/datum/proc/test(list/L)
+    for(var/x in L)
+        if(x)
+            break
+            world.log << x
+        else
+            continue
+            world.log << x
+    for(var/y in L)
+        goto done
+        world.log << y
+    done:
+    world.log << "done"
+    spawn()
+        return
+    del src
+    world.log << "after"
+    if(L)
+        world.log << "last"
+"##.trim();
+    check_errors_match(code, UNREACHABLE_ERRORS);
+
+    let context = parse_a_file_for_test(code);
+    let last = context.errors().last().unwrap().end().unwrap();
+    assert_eq!((last.line, last.column), (20, 1));
+}
+
+pub const CONSTANT_CONDITION_ERRORS: &[(u32, u16, &str)] = &[
+    (2, 5, "loop condition is always false"),
+    (4, 7, "if condition is always false"),
+    (8, 5, "loop condition is always false"),
+];
+
+#[test]
+fn constant_condition() {
+    let code = r##"
+/proc/test()
+    while(0)
+        world.log << "never"
+    if(2 - 2 > 1)
+        return
+    while(1)
+        break
+    while(3 < 1 + 1)
+        break
+"##.trim();
+    check_errors_match(code, CONSTANT_CONDITION_ERRORS);
+}
+
+pub const EMPTY_BLOCK_ERRORS: &[(u32, u16, &str)] = &[
+    (2, 5, "if statement has an empty body"),
+    (4, 5, "for statement has an empty body"),
+    (10, 5, "else if statement has an empty body"),
+    (11, 5, "else statement has an empty body"),
+];
+
+#[test]
+fn empty_block() {
+    let code = r##"
+/proc/test(x)
+    if(x)
+    world.log << "always"
+    for(var/i in 1 to 2)
+    while(x) {}
+    if(x)
+        world.log << "x"
+    else if(!x)
+        world.log << "not x"
+    else if(x > 1)
+    else
+    world.log << "done"
+"##.trim();
+    check_errors_match(code, EMPTY_BLOCK_ERRORS);
+}
+
+pub const MISLEADING_INDENTATION_ERRORS: &[(u32, u16, &str)] = &[
+    (5, 8, "inconsistent indentation: 7 % 4 != 0"),
+    (2, 19, "statement is not part of the if body before it"),
+    (5, 8, "statement is not part of the for body before it"),
+];
+
+#[test]
+fn misleading_indentation() {
+    let code = r##"
+/proc/test(x)
+    if(x) return; world.log << "x"
+    for(var/i in 1 to 2)
+        world.log << i
+       world.log << "done"
+    if(x) return
+    world.log << "fine"
+"##.trim();
+    check_errors_match(code, MISLEADING_INDENTATION_ERRORS);
+}
+
+pub const ASSIGNMENT_IN_CONDITION_ERRORS: &[(u32, u16, &str)] = &[
+    (4, 7, "assignment used as a condition; did you mean `==`?"),
+    (6, 12, "assignment used as a condition; did you mean `==`?"),
+    (12, 5, "assignment used as a condition; did you mean `==`?"),
+    (18, 10, "assignment used as a condition; did you mean `==`?"),
+];
+
+#[test]
+fn assignment_in_condition() {
+    let code = r##"
+/proc/next()
+    return 0
+/proc/test(x)
+    if(x = next())
+        return x
+    else if(x = next())
+        return x
+    if((x = next()))
+        return
+    if(x == 5)
+        return
+    while(x = next())
+        continue
+    while((x = next()))
+        continue
+    do
+        x++
+    while(x = next())
+"##.trim();
+    check_errors_match(code, ASSIGNMENT_IN_CONDITION_ERRORS);
+}
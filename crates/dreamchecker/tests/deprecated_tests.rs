@@ -0,0 +1,34 @@
+extern crate dreamchecker as dc;
+
+use dc::test_helpers::*;
+
+pub const DEPRECATED_ERRORS: &[(u32, u16, &str)] = &[
+    (14, 17, "macro OLD_MACRO is deprecated"),
+    (13, 6, "proc /obj/proc/old_proc is deprecated: use new_proc() instead"),
+    (14, 6, "var \"old_var\" on /obj is deprecated: use new_var instead"),
+    (15, 5, "type /obj/old is deprecated: use /obj/new instead"),
+    (16, 12, "type /obj/old is deprecated: use /obj/new instead"),
+];
+
+#[test]
+fn deprecated() {
+    let code = r##"
+/// @deprecated
+#define OLD_MACRO 1
+/// Does a thing.
+/// @deprecated use new_proc() instead
+/obj/proc/old_proc()
+/obj/item/old_proc()
+/// @deprecated use new_var instead
+/obj/var/old_var = 1
+/** @deprecated use /obj/new instead */
+/obj/old
+/obj/old/child
+/proc/test(obj/item/I)
+    I.old_proc()
+    I.old_var = OLD_MACRO
+    new /obj/old()
+    return /obj/old/child
+"##.trim();
+    check_errors_match(code, DEPRECATED_ERRORS);
+}
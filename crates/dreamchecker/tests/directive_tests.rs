@@ -3,7 +3,7 @@ extern crate dreamchecker as dc;
 use dc::test_helpers::*;
 
 pub const TRUE_SUB_ERRORS: &[(u32, u16, &str)] = &[
-    (4, 18, "proc never calls parent, required by /mob/proc/test"),
+    (4, 14, "proc never calls parent, required by /mob/proc/test"),
 ];
 
 #[test]
@@ -46,7 +46,7 @@ fn call_parent_disable() {
 }
 
 pub const NO_OVERRIDE_ERRORS: &[(u32, u16, &str)] = &[
-    (4, 18, "proc overrides parent, prohibited by /mob/proc/test"),
+    (4, 14, "proc overrides parent, prohibited by /mob/proc/test"),
 ];
 
 #[test]
@@ -76,7 +76,7 @@ fn final_proc() {
 
 pub const NO_OVERRIDE_DISABLE_ERRORS: &[(u32, u16, &str)] = &[
     (5, 5, "/mob/subtype/proc/test sets SpacemanDMM_should_not_override false, but it cannot be disabled."),
-    (4, 18, "proc overrides parent, prohibited by /mob/proc/test"),
+    (4, 14, "proc overrides parent, prohibited by /mob/proc/test"),
 ];
 
 
@@ -120,7 +120,7 @@ fn can_be_redefined() {
 }
 
 pub const NO_CAN_BE_REDEFINED_ERRORS: &[(u32, u16, &str)] = &[
-    (4, 10, "redefining proc /mob/test"),
+    (4, 6, "redefining proc /mob/test"),
 ];
 
 #[test]
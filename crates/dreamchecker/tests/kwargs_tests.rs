@@ -4,6 +4,7 @@ use dc::test_helpers::*;
 
 pub const AFTER_KWARG_ERRORS: &[(u32, u16, &str)] = &[
     (3, 5, "proc called with non-kwargs after kwargs: foo()"),
+    (3, 5, "missing arguments to /proc/foo: arg3"),
 ];
 
 #[test]
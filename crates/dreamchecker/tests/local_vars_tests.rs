@@ -1,4 +1,5 @@
 extern crate dreamchecker as dc;
+extern crate dreammaker as dm;
 
 use dc::test_helpers::check_errors_match;
 
@@ -6,6 +7,8 @@ pub const LOCAL_SCOPE_ERRORS: &[(u32, u16, &str)] = &[
     (7, 9, "undefined var: \"bar\""),
     (9, 5, "undefined var: \"bar\""),
     (13, 5, "undefined var: \"bar\""),
+    (4, 13, "unused var: \"bar\""),
+    (12, 13, "unused var: \"bar\""),
 ];
 
 #[test]
@@ -27,3 +30,41 @@ fn local_scope() {
 "##.trim();
     check_errors_match(code, LOCAL_SCOPE_ERRORS);
 }
+
+pub const SHADOWING_ERRORS: &[(u32, u16, &str)] = &[
+    (3, 24, "\"health\" shadows a field of /mob of the same name"),
+    (4, 9, "\"amount\" shadows a parameter of the same name"),
+    (7, 13, "\"x\" shadows a local var of the same name"),
+    (8, 13, "\"usr\" shadows a builtin var of the same name"),
+    (9, 9, "\"health\" shadows a parameter of the same name"),
+];
+
+#[test]
+fn shadowing() {
+    let code = r##"
+/mob
+    var/health
+/mob/proc/heal(amount, health, loc)
+    var/amount = 1
+    var/x = amount + health + loc
+    if(x)
+        var/x = 2
+        var/usr = x
+    var/health = usr
+    return health
+"##.trim();
+    let context = dc::test_helpers::parse_a_file_for_test(code);
+    let errors: Vec<(u32, u16, String)> = context.errors().iter()
+        .filter(|error| !matches!(error.errortype(), Some("unused_var" | "unused_param")))
+        .map(|error| (error.location().line, error.location().column, error.description().to_owned()))
+        .collect();
+    let expected: Vec<(u32, u16, String)> = SHADOWING_ERRORS.iter()
+        .map(|&(line, column, desc)| (line, column, desc.to_owned()))
+        .collect();
+    assert_eq!(errors, expected);
+    let severities: Vec<dm::Severity> = context.errors().iter()
+        .filter(|error| error.errortype() == Some("shadowed_builtin"))
+        .map(|error| error.severity())
+        .collect();
+    assert_eq!(severities, vec![dm::Severity::Error]);
+}
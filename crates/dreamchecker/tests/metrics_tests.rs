@@ -0,0 +1,100 @@
+extern crate dreamchecker as dc;
+extern crate dreammaker as dm;
+
+use dc::metrics::{proc_metrics, sort_metrics, ProcMetrics};
+use dc::test_helpers::{context_with_config, parse_a_file_in_context_for_test};
+
+fn metrics_of(code: &'static str) -> Vec<ProcMetrics> {
+    let context = dm::Context::default();
+    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.rs".into(), code);
+    let indents = dm::indents::IndentProcessor::new(&context, pp);
+    let mut parser = dm::parser::Parser::new(&context, indents);
+    parser.enable_procs();
+    let tree = parser.parse_object_tree();
+    proc_metrics(&tree)
+}
+
+#[test]
+fn proc_metrics_counts() {
+    let code = r##"
+/proc/simple(a)
+    return a
+
+/proc/branchy(a, b, c)
+    if(a && b)
+        for(var/i in 1 to 3)
+            if(c)
+                world << i
+    else if(b || c)
+        return a ? b : c
+    switch(a)
+        if(1)
+            return
+        if(2, 3)
+            return
+        else
+            return
+"##.trim();
+    let metrics = metrics_of(code);
+    let summary: Vec<_> = metrics.iter()
+        .map(|each| (each.path.as_str(), each.complexity, each.statements, each.nesting, each.parameters))
+        .collect();
+    assert_eq!(summary, vec![
+        ("/proc/branchy", 10, 9, 3, 3),
+        ("/proc/simple", 1, 1, 0, 1),
+    ]);
+}
+
+#[test]
+fn proc_metrics_count_branches_in_arguments() {
+    let code = r##"
+/proc/nested(a, b)
+    new /datum(a && b)
+    locate(a || b) in world
+    pick(a ? 1 : 2, b)
+    world << "[a && b]"
+    input("[b || a]") in list(a ? b : a)
+    call(a)(b || a)
+"##.trim();
+    let metrics = metrics_of(code);
+    assert_eq!(metrics[0].complexity, 8);
+}
+
+#[test]
+fn proc_metrics_sort() {
+    let code = r##"
+/proc/many(a, b, c, d)
+    return
+/proc/deep()
+    if(1)
+        if(2)
+            return
+"##.trim();
+    let mut metrics = metrics_of(code);
+    sort_metrics(&mut metrics, "parameters");
+    let paths: Vec<_> = metrics.iter().map(|each| each.path.as_str()).collect();
+    assert_eq!(paths, vec!["/proc/many", "/proc/deep"]);
+}
+
+#[test]
+fn proc_metrics_thresholds() {
+    let context = context_with_config("[dreamchecker]\nmax_complexity = 2\nmax_parameters = 2\n");
+
+    let code = r##"
+/proc/fine(a, b)
+    if(a)
+        return b
+
+/proc/worse(a, b, c)
+    if(a || b)
+        return c
+"##.trim();
+    let context = parse_a_file_in_context_for_test(context, code);
+    let errors: Vec<_> = context.errors().iter()
+        .map(|error| (error.location().line, error.errortype(), error.description().to_owned()))
+        .collect();
+    assert_eq!(errors, vec![
+        (5, Some("proc_complexity"), "/proc/worse has complexity 3, more than the limit of 2".to_owned()),
+        (5, Some("proc_parameters"), "/proc/worse has parameter count 3, more than the limit of 2".to_owned()),
+    ]);
+}
@@ -0,0 +1,59 @@
+extern crate dreamchecker as dc;
+
+use dc::test_helpers::check_errors_match;
+
+pub const NULL_DEREFERENCE_ERRORS: &[(u32, u16, &str)] = &[
+    (8, 9, "proc call on \"D\", which is null here"),
+    (12, 5, "proc call on \"A\", which is null here"),
+    (13, 14, "field access on \"A\", which is null here"),
+    (37, 5, "proc call on \"G\", which is null here"),
+];
+
+#[test]
+fn null_dereference() {
+    let code = r##"
+/datum/var/name
+/datum/proc/foo()
+    return 1
+/proc/get()
+    return new /datum
+/proc/test(datum/D)
+    if(D == null)
+        D.foo()
+    if(!D)
+        return
+    var/datum/A = null
+    A.foo()
+    world << A.name
+    var/datum/B
+    B = get()
+    B.foo()
+    var/datum/C = null
+    if(get())
+        C = get()
+    C.foo()
+    var/datum/E = null
+    if(E && E.foo())
+        return
+    world << (E ? E.name : null)
+    world << E?.name
+    if(isnull(D))
+        D = get()
+        D.foo()
+    var/datum/F = null
+    for(var/i in 1 to 3)
+        F.foo()
+        F = get()
+    if(!isnull(F))
+        F.foo()
+    var/datum/G
+    G = null
+    G.foo()
+    var/datum/H = null
+    init(&H)
+    H.foo()
+/proc/init(ref)
+    *ref = get()
+"##.trim();
+    check_errors_match(code, NULL_DEREFERENCE_ERRORS);
+}
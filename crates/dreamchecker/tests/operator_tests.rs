@@ -6,7 +6,9 @@ pub const IN_AMBIG_ERRORS: &[(u32, u16, &str)] = &[
     (2, 7, "ambiguous `!` on left side of an `in`"),
     (6, 7, "ambiguous `&&` on left side of an `in`"),
     (11, 7, "ambiguous `=` on left side of an `in`"),
+    (13, 7, "assignment used as a condition; did you mean `==`?"),
     (15, 7, "ambiguous ternary on left side of an `in`"),
+    (11, 12, "value assigned to \"i\" is never read"),
 ];
 
 #[test]
@@ -34,6 +36,33 @@ fn in_ambig() {
     check_errors_match(code, IN_AMBIG_ERRORS);
 }
 
+pub const IN_RHS_AMBIG_ERRORS: &[(u32, u16, &str)] = &[
+    (3, 7, "ambiguous `&&` on right side of an `in`"),
+    (7, 7, "ambiguous `||` on right side of an `in`"),
+    (9, 7, "ambiguous `&&` on right side of an `in`"),
+];
+
+#[test]
+fn in_rhs_ambig() {
+    let code = r##"
+/proc/test(x, y, z)
+    var/list/L = list()
+    if(x in L && y)
+        return
+    if((x in L) && y)
+        return
+    if(x in L || y)
+        return
+    if(x in L && y || z)
+        return
+    if(x in (L && y))
+        return
+    if(x in 1 to 5)
+        return
+"##.trim();
+    check_errors_match(code, IN_RHS_AMBIG_ERRORS);
+}
+
 pub const TERNARY_IN_AMBIG_ERRORS: &[(u32, u16, &str)] = &[
     (2, 14, "got \'in\', expected one of: operator, field access, \':\'"),
 ];
@@ -69,8 +98,12 @@ fn operator_overload() {
 
 pub const NOT_AMBIG_BITWISE_ERRORS: &[(u32, u16, &str)] = &[
     (2, 8, "Ambiguous `!` on left side of bitwise `&` operator"),
+    (2, 8, "if condition is always false"),
     (4, 8, "Ambiguous `!` on left side of bitwise `|` operator"),
+    (4, 8, "if condition is always false"),
     (6, 8, "Ambiguous `!` on left side of bitwise `^` operator"),
+    (6, 8, "if condition is always false"),
+    (8, 8, "if condition is always false"),
 ];
 
 #[test]
@@ -90,3 +123,50 @@ fn ambigous_not_bitwise() {
 "##.trim();
     check_errors_match(code, NOT_AMBIG_BITWISE_ERRORS);
 }
+
+pub const FLOAT_EQUALITY_ERRORS: &[(u32, u16, &str)] = &[
+    (6, 7, "comparing with \"==\" against 0.3, which may not be represented exactly"),
+    (8, 7, "comparing with \"!=\" against -0.5, which may not be represented exactly"),
+    (10, 7, "comparing with \"==\" against 0.25, which may not be represented exactly"),
+    (18, 7, "comparing with \"==\" against 0.1, which may not be represented exactly"),
+];
+
+#[test]
+fn float_equality() {
+    let code = r##"
+/datum
+    var/speed = 0.25
+    var/count = 0
+
+/datum/proc/test(progress)
+    if(progress == 0.3)
+        return
+    if(progress != -0.5)
+        return
+    if(speed == progress)
+        return
+    if(progress == 1.0)
+        return
+    if(progress == 0)
+        return
+    if(progress >= 0.3)
+        return
+    if(count == 0.1)
+        return
+"##.trim();
+    check_errors_match(code, FLOAT_EQUALITY_ERRORS);
+}
+
+pub const IN_AMBIG_INTERP_ERRORS: &[(u32, u16, &str)] = &[
+    (3, 9, "ambiguous `!` on left side of an `in`"),
+];
+
+#[test]
+fn in_ambig_interp() {
+    let code = r##"
+/proc/test(x, list/L)
+    world.log << {"first [x]
+second [!x in L]"}
+"##.trim();
+    check_errors_match(code, IN_AMBIG_INTERP_ERRORS);
+}
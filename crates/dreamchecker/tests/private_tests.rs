@@ -1,9 +1,10 @@
 extern crate dreamchecker as dc;
+extern crate dreammaker as dm;
 
 use dc::test_helpers::*;
 
 pub const PRIVATE_PROC_ERRORS: &[(u32, u16, &str)] = &[
-    (5, 21, "proc overrides private parent, prohibited by /mob/proc/private"),
+    (5, 14, "proc overrides private parent, prohibited by /mob/proc/private"),
     (11, 5, "/mob/subtype/proc/test2 attempting to call private proc /mob/proc/private2, types do not match"),
     (12, 8, "/mob/subtype/proc/test2 attempting to call private proc /mob/proc/private2, types do not match"),
     (15, 6, "/obj/proc/test attempting to call private proc /mob/proc/private2, types do not match"),
@@ -35,7 +36,8 @@ fn private_proc() {
 }
 
 pub const PRIVATE_VAR_ERRORS: &[(u32, u16, &str)] = &[
-    (5, 9, "/mob/subtype overrides private var \"foo\""),
+    (5, 5, "/mob/subtype overrides private var \"foo\""),
+    (9, 5, "var \"bar\" on /mob is declared as private"),
     (12, 6, "field \"bar\" on /mob is declared as private"),
     (14, 6, "field \"foo\" on /mob/subtype is declared as private"),
 ];
@@ -115,3 +117,29 @@ fn protected_var() {
 "##.trim();
     check_errors_match(code, PROTECTED_VAR_ERRORS);
 }
+
+#[test]
+fn access_violations_as_warnings() {
+    let context = context_with_config("[dreamchecker]\naccess_violations_as_warnings = true\n");
+
+    let code = r##"
+/mob
+    var/SpacemanDMM_private/foo = TRUE
+/mob/subtype
+    foo = FALSE
+/mob/proc/test()
+    set SpacemanDMM_private_proc = TRUE
+    var/mob/subtype/S = new
+    S.foo = FALSE
+/mob/subtype/test()
+    return
+"##.trim();
+    let context = parse_a_file_in_context_for_test(context, code);
+    let errors: Vec<(u32, String, dm::Severity)> = context.errors().iter()
+        .map(|error| (error.location().line, error.description().to_owned(), error.severity()))
+        .collect();
+    assert_eq!(errors, vec![
+        (4, "/mob/subtype overrides private var \"foo\"".to_owned(), dm::Severity::Warning),
+        (9, "proc overrides private parent, prohibited by /mob/proc/test".to_owned(), dm::Severity::Warning),
+    ]);
+}
@@ -1,7 +1,8 @@
 extern crate dreamchecker as dc;
+extern crate dreammaker as dm;
 
 use dc::test_helpers::check_errors_match;
-use dc::test_helpers::parse_a_file_for_test;
+use dc::test_helpers::{context_with_config, parse_a_file_for_test, parse_a_file_in_context_for_test};
 
 pub const NO_PARENT_ERRORS: &[(u32, u16, &str)] = &[
     (2, 5, "proc has no parent: /mob/proc/test"),
@@ -17,6 +18,40 @@ fn no_parent() {
     check_errors_match(code, NO_PARENT_ERRORS);
 }
 
+pub const DUPLICATE_PARENT_CALL_ERRORS: &[(u32, u16, &str)] = &[
+    (5, 5, "parent proc is called twice"),
+    (8, 5, "`return ..()` calls the parent proc again after `. = ..()`"),
+];
+
+#[test]
+fn duplicate_parent_call() {
+    let code = r##"
+/mob/proc/test()
+    return 1
+/mob/living/test()
+    ..()
+    ..()
+/mob/living/carbon/test()
+    . = ..()
+    return ..()
+/mob/living/carbon/human/test()
+    . = ..()
+    if(!.)
+        return
+    ..()
+/mob/living/silicon/test()
+    var/result = ..()
+    if(prob(50))
+        ..()
+    return result
+"##.trim();
+    check_errors_match(code, DUPLICATE_PARENT_CALL_ERRORS);
+}
+
+pub const RETURN_TYPE_ERRORS: &[(u32, u16, &str)] = &[
+    (2, 5, "returning nothing from /mob/proc/test, which is declared to return /datum"),
+];
+
 #[test]
 fn return_type() {
     let code = r##"
@@ -26,16 +61,14 @@ fn return_type() {
 /mob/proc/test2() as num
     return
 "##.trim();
-    let context = parse_a_file_for_test(code);
-    let error_text: Vec<String> = context.errors().iter().map(|error| format!("{}", error)).collect();
-    if !error_text.is_empty() {
-        panic!("\n{}", error_text.join("\n"))
-    }
+    check_errors_match(code, RETURN_TYPE_ERRORS);
 }
 
 pub const RETURN_TYPE_FAILURE_ERRORS: &[(u32, u16, &str)] = &[
     (4, 13, "cannot specify a return type for a proc override"),
     (7, 22, "bad input type: 'incorrect'"),
+    (2, 5, "returning nothing from /datum/proc/test, which is declared to return /datum"),
+    (5, 5, "returning nothing from /mob/proc/test, which is declared to return /datum"),
 ];
 
 #[test]
@@ -52,3 +85,199 @@ fn return_type_failure() {
 "##.trim();
     check_errors_match(code, RETURN_TYPE_FAILURE_ERRORS);
 }
+
+pub const UNDEFINED_PROC_ERRORS: &[(u32, u16, &str)] = &[
+    (4, 5, "undefined proc: \"atack\" on /mob"),
+    (5, 8, "undefined proc: \"zzz\" on /mob"),
+    (6, 11, "undefined proc: \"helpr\" on (global)"),
+    (9, 6, "\"zzz\" accessed with \":\""),
+];
+
+#[test]
+fn undefined_proc() {
+    let code = r##"
+/proc/helper()
+/mob/proc/attack()
+/mob/proc/test()
+    atack()
+    src.zzz()
+    global.helpr()
+    SpacemanDMM_unlint(src.zzz())
+    var/datum/D = src
+    D:zzz()
+"##.trim();
+    check_errors_match(code, UNDEFINED_PROC_ERRORS);
+
+    let context = parse_a_file_for_test(code);
+    let notes: Vec<Option<String>> = context.errors().iter()
+        .map(|error| error.notes().first().map(|note| format!("{}:{}", note.location().line, note.description())))
+        .collect();
+    assert_eq!(notes, vec![
+        Some("2:did you mean attack?".to_owned()),
+        None,
+        Some("1:did you mean helper?".to_owned()),
+        None,
+    ]);
+}
+
+#[test]
+fn undefined_proc_dynamic_type() {
+    let context = context_with_config("[dreamchecker]\ndynamic_proc_types = [\"/datum/proxy\"]\n");
+
+    let code = r##"
+/datum/proxy/proc/test()
+    forwarded()
+/datum/proxy/child/proc/test2()
+    src.forwarded()
+/datum/proc/test3()
+    forwarded()
+"##.trim();
+    let context = parse_a_file_in_context_for_test(context, code);
+    let errors: Vec<String> = context.errors().iter().map(|error| error.description().to_owned()).collect();
+    assert_eq!(errors, vec!["undefined proc: \"forwarded\" on /datum"]);
+}
+
+pub const ARGUMENT_COUNT_ERRORS: &[(u32, u16, &str)] = &[
+    (26, 5, "parent proc is called twice"),
+    (14, 5, "too many arguments to /datum/proc/two: expected at most 2, found 3"),
+    (15, 5, "too many arguments to /datum/proc/two: expected at most 2, found 3"),
+    (16, 5, "missing arguments to /datum/proc/two: b"),
+    (21, 5, "missing arguments to /datum/proc/two: a, b"),
+];
+
+#[test]
+fn argument_count() {
+    let code = r##"
+/datum/proc/two(a, b)
+    return a + b
+/datum/proc/optional(a, b = 1)
+    return a + b
+/datum/proc/through_args()
+    return args.len
+/datum/proc/variadic()
+    set SpacemanDMM_variadic_proc = TRUE
+/datum/proc/overridden(a)
+    return a
+/datum/child/overridden(a, b)
+    return a + b
+/datum/proc/test()
+    two(1, 2, 3)
+    two(1, 2, b = 3)
+    two(1)
+    optional(1)
+    two(b = 2, a = 1)
+    through_args(1, 2, 3)
+    variadic(1, 2, 3)
+    two()
+    overridden(1, 2)
+    two(arglist(list(1, 2, 3)))
+/datum/child/two(a, b)
+    ..()
+    return ..(a, b)
+"##.trim();
+    check_errors_match(code, ARGUMENT_COUNT_ERRORS);
+}
+
+pub const OVERRIDE_SIGNATURE_ERRORS: &[(u32, u16, &str)] = &[
+    (2, 21, "override of /datum/proc/test renames parameter \"target\" to \"victim\""),
+    (2, 16, "override of /datum/proc/test drops parameter \"amount\""),
+    (4, 25, "override of /datum/proc/test declares parameter \"target\" as /obj instead of /mob"),
+];
+
+#[test]
+fn override_signature() {
+    let code = r##"
+/datum/proc/test(mob/target, amount)
+/datum/renamed/test(victim)
+/datum/added/test(mob/target, amount, extra)
+/datum/retyped/test(obj/target, amount)
+/datum/narrowed/test(mob/living/target, amount)
+/datum/exempt/test()
+    set SpacemanDMM_can_change_signature = TRUE
+/mob/living
+"##.trim();
+    check_errors_match(code, OVERRIDE_SIGNATURE_ERRORS);
+}
+
+pub const LIFECYCLE_ERRORS: &[(u32, u16, &str)] = &[
+    (3, 16, "lifecycle proc never calls parent: /datum/forgets/proc/Destroy"),
+    (10, 5, "possible unreachable code here"),
+    (8, 16, "lifecycle proc never calls parent: /datum/returns/proc/Destroy"),
+];
+
+#[test]
+fn lifecycle_parent_call() {
+    let code = r##"
+/datum/proc/Destroy()
+    return 1
+/datum/forgets/Destroy()
+    return 2
+/datum/calls/Destroy()
+    if(prob(50))
+        return ..()
+/datum/returns/Destroy()
+    return 3
+    ..()
+/datum/exempt/Destroy()
+    set SpacemanDMM_should_call_parent = FALSE
+    return 4
+/datum/proc/Other()
+/datum/forgets/Other()
+"##.trim();
+    check_errors_match(code, LIFECYCLE_ERRORS);
+}
+
+pub const RETURN_TYPE_MISMATCH_ERRORS: &[(u32, u16, &str)] = &[
+    (9, 9, "returning /obj from /mob/proc/test, which is declared to return /mob"),
+    (11, 9, "returning \"text\" from /mob/proc/test, which is declared to return /mob"),
+    (13, 9, "returning nothing from /mob/proc/test, which is declared to return /mob"),
+    (16, 5, "returning /obj from /mob/proc/dot, which is declared to return /mob"),
+    (19, 5, "returning 1 from /mob/living/proc/test, which is declared to return /mob"),
+];
+
+#[test]
+fn return_type_mismatch() {
+    let code = r##"
+/obj
+/mob/living
+/mob/proc/test() as /mob
+    if(prob(1))
+        return src
+    if(prob(1))
+        return new /mob/living
+    if(prob(1))
+        return new /obj
+    if(prob(1))
+        return "text"
+    if(prob(1))
+        return
+    return null
+/mob/proc/dot() as /mob
+    . = new /obj
+    return
+/mob/living/test()
+    return 1
+"##.trim();
+    check_errors_match(code, RETURN_TYPE_MISMATCH_ERRORS);
+}
+
+pub const OVERRIDE_WITHOUT_DECLARATION_ERRORS: &[(u32, u16, &str)] = &[
+    (5, 10, "override of \"bar\" but no ancestor of /obj/foo declares it"),
+];
+
+#[test]
+fn override_without_declaration() {
+    let code = r##"
+/obj/proc/bar()
+    return
+/obj/foo
+    parent_type = /datum
+/obj/foo/bar()
+    return
+/obj/baz/bar()
+    return
+/obj/foo/operator+(other)
+    return other
+"##.trim();
+    check_errors_match(code, OVERRIDE_WITHOUT_DECLARATION_ERRORS);
+}
@@ -0,0 +1,53 @@
+extern crate dreamchecker as dc;
+extern crate dreammaker as dm;
+
+use dc::report::{diagnostic_json, write_json_lines};
+use dc::test_helpers::parse_a_file_for_test;
+
+#[test]
+fn diagnostics_as_json_lines() {
+    let context = parse_a_file_for_test(r#"
+/mob/proc/test()
+    set SpacemanDMM_should_call_parent = TRUE
+
+/mob/subtype/test()
+    return
+
+/obj/proc/text()
+    return "\reed"
+"#.trim());
+
+    let mut out = Vec::new();
+    write_json_lines(&context, dm::Severity::Info, &mut out).unwrap();
+    let lines: Vec<serde_json::Value> = String::from_utf8(out).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 2);
+
+    let text_macro = lines.iter().find(|each| each["component"] == "lexer").unwrap();
+    assert_eq!(text_macro["file"], "unit_tests.rs");
+    assert_eq!(text_macro["severity"], "warning");
+    assert_eq!(text_macro["code"], "SDM0105");
+    assert_eq!(text_macro["errortype"], "unknown_text_macro");
+    assert_eq!(text_macro["range"], serde_json::json!({
+        "start": { "line": 8, "column": 13 },
+        "end": { "line": 8, "column": 18 },
+    }));
+
+    let parent = lines.iter().find(|each| each["component"] == "dreamchecker").unwrap();
+    assert_eq!(parent["severity"], "error");
+    assert_eq!(parent["message"], "proc never calls parent, required by /mob/proc/test");
+    assert_eq!(parent["range"]["start"], serde_json::json!({ "line": 4, "column": 14 }));
+    assert_eq!(parent["related"][0]["line"], 2);
+    assert_eq!(parent["related"][0]["message"], "required by this must_call_parent annotation");
+}
+
+#[test]
+fn parse_errors_name_their_component() {
+    let context = parse_a_file_for_test("/obj/item\n\tvar/amount = (1\n");
+    let components: Vec<_> = context.errors().iter()
+        .map(|error| diagnostic_json(&context, error)["component"].clone())
+        .collect();
+    assert_eq!(components, [serde_json::json!("parser")]);
+}
@@ -1,9 +1,9 @@
 extern crate dreamchecker as dc;
 
-use dc::test_helpers::check_errors_match;
+use dc::test_helpers::{check_errors_match, parse_a_file_for_test};
 
 pub const SLEEP_ERRORS: &[(u32, u16, &str)] = &[
-    (16, 16, "/mob/proc/test3 sets SpacemanDMM_should_not_sleep but calls blocking proc /proc/sleepingproc"),
+    (16, 11, "/mob/proc/test3 sets SpacemanDMM_should_not_sleep but calls blocking proc /proc/sleepingproc"),
 ];
 
 #[test]
@@ -45,7 +45,9 @@ fn sleep() {
 }
 
 pub const SLEEP_ERRORS2: &[(u32, u16, &str)] = &[
-    (8, 21, "/mob/living/proc/bar calls /mob/living/proc/foo which has override child proc that sleeps /mob/living/carbon/proc/foo"),
+    (17, 11, "lifecycle proc never calls parent: /mob/dead/proc/New"),
+    (8, 18, "/mob/living/proc/bar calls /mob/living/proc/foo which has override child proc that sleeps /mob/living/carbon/proc/foo"),
+    (17, 11, "/mob/dead/proc/New runs during map load but calls blocking built-in(s)"),
 ];
 
 #[test]
@@ -107,17 +109,19 @@ fn sleep3() {
     . = ..()
 "##.trim();
     check_errors_match(code, &[
-        (8, 23, "/atom/movable/proc/bar calls /atom/movable/proc/foo which has override child proc that sleeps /mob/proc/foo"),
+        (17, 12, "lifecycle proc never calls parent: /atom/dead/proc/New"),
+        (8, 20, "/atom/movable/proc/bar calls /atom/movable/proc/foo which has override child proc that sleeps /mob/proc/foo"),
+        (17, 12, "/atom/dead/proc/New runs during map load but calls blocking built-in(s)"),
     ]);
 }
 
 pub const SLEEP_ERROR4: &[(u32, u16, &str)] = &[
-    (1, 16, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking built-in(s)"),
-    (1, 16, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /mob/proc/test2"),
-    (1, 16, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /client/proc/checksoundquery"),
-    (1, 16, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /client/proc/checkmeasuretext"),
-    (1, 16, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /world/proc/checkimport"),
-    (1, 16, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /world/proc/checkexport"),
+    (1, 11, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking built-in(s)"),
+    (1, 11, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /mob/proc/test2"),
+    (1, 11, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /client/proc/checksoundquery"),
+    (1, 11, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /client/proc/checkmeasuretext"),
+    (1, 11, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /world/proc/checkimport"),
+    (1, 11, "/mob/proc/test1 sets SpacemanDMM_should_not_sleep but calls blocking proc /world/proc/checkexport"),
 ];
 
 #[test]
@@ -150,7 +154,7 @@ fn sleep4() {
 
 // Test overrides and for regression of issue #267
 pub const SLEEP_ERROR5: &[(u32, u16, &str)] = &[
-        (7, 19, "/datum/sub/proc/checker sets SpacemanDMM_should_not_sleep but calls blocking proc /proc/sleeper"),
+        (7, 12, "/datum/sub/proc/checker sets SpacemanDMM_should_not_sleep but calls blocking proc /proc/sleeper"),
 ];
 
 #[test]
@@ -175,7 +179,7 @@ fn sleep5() {
 }
 
 pub const PURE_ERRORS: &[(u32, u16, &str)] = &[
-    (12, 16, "/mob/proc/test2 sets SpacemanDMM_should_be_pure but calls a /proc/impure that does impure operations"),
+    (12, 11, "/mob/proc/test2 sets SpacemanDMM_should_be_pure but calls a /proc/impure that does impure operations"),
 ];
 
 #[test]
@@ -218,3 +222,71 @@ fn pure2() {
 "##.trim();
     check_errors_match(code, PURE2_ERRORS);
 }
+
+pub const IMPLICIT_SLEEP_ERRORS: &[(u32, u16, &str)] = &[
+    (1, 20, "/datum/vector/proc/operator+ is an operator but calls blocking proc /datum/proc/wait_a_bit"),
+    (14, 11, "/obj/item/proc/New runs during map load but calls blocking built-in(s)"),
+];
+
+#[test]
+fn implicit_sleep_roots() {
+    let code = r##"
+/datum/vector/proc/operator+(other)
+    wait_a_bit()
+    return other
+/datum/proc/wait_a_bit()
+    sleep(1)
+/obj/New()
+    spawn()
+        wait_a_bit()
+    wait_later()
+    return ..()
+/obj/proc/wait_later()
+    set waitfor = FALSE
+    sleep(1)
+/obj/item/New()
+    ..()
+    sleep(1)
+/obj/machine/New()
+    set waitfor = FALSE
+    ..()
+    sleep(1)
+"##.trim();
+    check_errors_match(code, IMPLICIT_SLEEP_ERRORS);
+
+    let context = parse_a_file_for_test(code);
+    let notes: Vec<Vec<String>> = context.errors().iter()
+        .map(|error| error.notes().iter().map(|note| format!("{}:{}", note.location().line, note.description())).collect())
+        .collect();
+    assert_eq!(notes[0], ["1:operators must not sleep", "2:/datum/proc/wait_a_bit() called here", "5:sleep() called here"]);
+}
+
+pub const SHARED_SLEEP_ERRORS: &[(u32, u16, &str)] = &[
+    (1, 13, "/datum/proc/check sets SpacemanDMM_should_not_sleep but calls blocking proc /datum/proc/sleeper"),
+    (13, 20, "/datum/vector/proc/operator- is an operator but calls blocking proc /datum/proc/sleeper"),
+];
+
+#[test]
+fn sleep_through_shared_callees() {
+    let code = r##"
+/datum/proc/check()
+    set SpacemanDMM_should_not_sleep = TRUE
+    quiet()
+    allowed()
+    middle()
+/datum/proc/quiet()
+    return
+/datum/proc/allowed()
+    set SpacemanDMM_allowed_to_sleep = TRUE
+    sleeper()
+/datum/proc/middle()
+    sleeper()
+/datum/vector/proc/operator-(other)
+    quiet()
+    middle()
+    return other
+/datum/proc/sleeper()
+    sleep(1)
+"##.trim();
+    check_errors_match(code, SHARED_SLEEP_ERRORS);
+}
@@ -51,3 +51,180 @@ fn return_type() {
 "##.trim();
     check_errors_match(code, RETURN_TYPE_ERRORS);
 }
+
+pub const UNDEFINED_VAR_ERRORS: &[(u32, u16, &str)] = &[
+    (5, 5, "undefined var: \"helth\""),
+    (6, 8, "undefined field: \"max_helth\" on /mob"),
+    (7, 5, "undefined var: \"zzz\""),
+    (8, 5, "undefined var: \"TRU\""),
+];
+
+#[test]
+fn undefined_var() {
+    let code = r##"
+/mob
+    var/health = 1
+    var/max_health = 1
+/mob/proc/test()
+    helth = 2
+    src.max_helth = 3
+    zzz = 4
+    TRU = 5
+"##.trim();
+    check_errors_match(code, UNDEFINED_VAR_ERRORS);
+
+    let context = parse_a_file_for_test(code);
+    let errors: Vec<_> = context.errors().iter()
+        .map(|error| (
+            error.errortype(),
+            error.end().map(|end| end.column),
+            error.notes().first().map(|note| format!("{}:{}", note.location().line, note.description())),
+        ))
+        .collect();
+    assert_eq!(errors, vec![
+        (Some("undefined_var"), Some(10), Some("2:did you mean health?".to_owned())),
+        (Some("undefined_var"), Some(18), Some("3:did you mean max_health?".to_owned())),
+        (Some("undefined_var"), Some(8), None),
+        // too short to suggest `tag`, and `TRUE` is always expanded as a macro
+        (Some("undefined_var"), Some(8), None),
+    ]);
+}
+
+#[test]
+fn undefined_var_project_macro() {
+    // Both are only macros after their use, and neither is meant as a var.
+    let code = r##"
+/mob
+    var/speed = 1
+/mob/proc/test()
+    LATER = 2
+    sped = 3
+#define LATER 1
+#define speed 4
+"##.trim();
+    let context = parse_a_file_for_test(code);
+    let errors: Vec<_> = context.errors().iter()
+        .map(|error| (error.description().to_owned(), error.notes().len()))
+        .collect();
+    assert_eq!(errors, vec![("undefined var: \"sped\"".to_owned(), 0)]);
+}
+
+pub const ISTYPE_ERRORS: &[(u32, u16, &str)] = &[
+    (3, 8, "istype() is always true: /mob/living is already a /mob"),
+    (5, 8, "istype() is always false: /mob/living can never be a /obj"),
+];
+
+#[test]
+fn istype_determinate() {
+    let code = r##"
+/obj
+/mob/living/proc/test(mob/living/L, mob/M, D)
+    if(istype(L, /mob))
+        return
+    if(istype(L, /obj))
+        return
+    if(istype(M, /mob/living))
+        return
+    if(istype(D, /obj))
+        return
+"##.trim();
+    check_errors_match(code, ISTYPE_ERRORS);
+}
+
+pub const COLON_ERRORS: &[(u32, u16, &str)] = &[
+    (6, 6, "\"charge\" accessed with \":\"; use \".\" instead"),
+    (8, 6, "\"use\" accessed with \":\"; use \".\" on a /obj/item instead"),
+    (9, 10, "\"nowhere\" accessed with \"?:\""),
+    (10, 5, "\"use\" accessed with \":\"; use \".\" on a /obj/item instead"),
+];
+
+#[test]
+fn colon_operator() {
+    let code = r##"
+/obj/item/var/charge
+/obj/item/proc/use()
+#define USE(x) x:use()
+
+/proc/test(obj/item/I, thing)
+    I:charge = 1
+    var/T = thing
+    T:use()
+    thing?:nowhere
+    USE(thing)
+"##.trim();
+    check_errors_match(code, COLON_ERRORS);
+
+    let context = parse_a_file_for_test(code);
+    let notes: Vec<Option<String>> = context.errors().iter()
+        .map(|error| error.notes().first().map(|note| format!("{}:{}", note.location().line, note.description())))
+        .collect();
+    assert_eq!(notes, vec![
+        None,
+        Some("7:declare this as a /obj/item".to_owned()),
+        None,
+        Some("5:declare this as a /obj/item".to_owned()),
+    ]);
+}
+
+pub const STRING_TYPEPATH_ERRORS: &[(u32, u16, &str)] = &[
+    (7, 7, "comparing a typepath with \"==\" to the string \"/obj/item\"; use the path /obj/item instead"),
+    (9, 7, "comparing a typepath with \"!=\" to the string \"/obj/nothing\""),
+    (11, 8, "istype() expects a typepath, but is given the string \"/obj/item\"; use the path /obj/item instead"),
+    (13, 8, "ispath() expects a typepath, but is given the string \"/obj\"; use the path /obj instead"),
+    (15, 5, "parameter \"thing_type\" of /proc/make expects a typepath, but is given the string \"/obj/item\"; use the path /obj/item instead"),
+    (16, 5, "parameter \"name\" of /proc/make expects text, but is given a typepath"),
+    (17, 5, "parameter \"msg\" of /proc/label expects text, but is given a typepath"),
+];
+
+#[test]
+fn string_typepath_mismatch() {
+    let code = r##"
+/obj/item
+/proc/make(thing_type = /obj/item, name = "thing")
+    return new thing_type(name)
+/proc/label(msg as text)
+    return msg
+/proc/test(obj/O)
+    if(O.type == "/obj/item")
+        return
+    if("/obj/nothing" != O.type)
+        return
+    if(istype(O, "/obj/item"))
+        return
+    if(ispath(O.type, "/obj"))
+        return
+    make("/obj/item")
+    make(name = /obj/item)
+    label(/obj)
+    if(O.type == /obj/item)
+        return
+    make(/obj/item, "thing")
+"##.trim();
+    check_errors_match(code, STRING_TYPEPATH_ERRORS);
+}
+
+pub const SCOPE_ACCESS_ERRORS: &[(u32, u16, &str)] = &[
+    (14, 26, "undefined field: \"nope\" on /datum/base"),
+];
+
+#[test]
+fn scope_access() {
+    let code = r##"
+#define TYPE_PROC_REF(T, P) T::P()
+/datum/callback/New(thing, procref)
+/datum/base
+    var/health = 5
+/datum/base/proc/go()
+/datum/base/child
+    health = parent_type::health + 1
+/datum/base/child/go()
+    var/x = new /datum/callback(src, TYPE_PROC_REF(/datum/base, go))
+    var/y = new /datum/callback(src, parent_type::go())
+    var/z = src::health
+    var/w = type::health
+    var/q = /datum/base::go()
+    var/bad = /datum/base::nope
+    return list(x, y, z, w, q, bad)
+"##.trim();
+    check_errors_match(code, SCOPE_ACCESS_ERRORS);
+}
@@ -0,0 +1,57 @@
+extern crate dreamchecker as dc;
+extern crate dreammaker as dm;
+
+use dc::test_helpers::{check_errors_match, context_with_config, parse_a_file_in_context_for_test};
+
+pub const SUPPRESSION_ERRORS: &[(u32, u16, &str)] = &[
+    (7, 9, "unknown diagnostic in suppression comment: bogus"),
+    (5, 9, "unused var: \"c\""),
+    (11, 9, "unused var: \"f\""),
+];
+
+#[test]
+fn suppression_comments() {
+    let code = r##"
+/proc/test(x)
+    // SDMM: ignore(unused-var)
+    var/a
+    var/b // SDMM: ignore(SDM0601)
+    var/c
+    if(x)
+        // SDMM: ignore(unused_var, bogus)
+        for(var/d in x)
+            var/e
+        return x
+    var/f
+"##.trim();
+    check_errors_match(code, SUPPRESSION_ERRORS);
+}
+
+#[test]
+fn severity_by_code_and_path() {
+    let code = r##"
+/proc/test()
+    var/a
+    return
+"##.trim();
+
+    let context = context_with_config("[diagnostics]\nSDM0601 = \"error\"\n");
+    let context = parse_a_file_in_context_for_test(context, code);
+    let severities: Vec<_> = context.errors().iter().map(|error| error.severity()).collect();
+    assert_eq!(severities, vec![dm::Severity::Error]);
+
+    let context = context_with_config(r#"
+[diagnostics]
+unused-var = "error"
+
+[[path_diagnostics]]
+path = "unit_*.rs"
+unused-var = "off"
+
+[[path_diagnostics]]
+path = "code/**"
+unused-var = "info"
+"#);
+    let context = parse_a_file_in_context_for_test(context, code);
+    assert!(context.errors().is_empty());
+}
@@ -0,0 +1,39 @@
+extern crate dreamchecker as dc;
+
+use dc::test_helpers::*;
+
+pub const SWITCH_DUPLICATE_CASES_ERRORS: &[(u32, u16, &str)] = &[
+    (7, 9, "switch case 2 duplicates an earlier case"),
+    (11, 9, "switch case 4 duplicates an earlier case"),
+    (13, 9, "switch case range 5 to 10 overlaps an earlier case"),
+    (15, 9, "switch case \"a\" duplicates an earlier case"),
+];
+
+#[test]
+fn switch_duplicate_cases() {
+    let code = r##"
+/proc/test(x)
+    switch(x)
+        if(1, 2)
+            return
+        if("a")
+            return
+        if(1 + 1)
+            return
+        if(3 to 5)
+            return
+        if(4)
+            return
+        if(5 to 10)
+            return
+        if("a", x)
+            return
+        if(11, "b")
+            return
+"##.trim();
+    check_errors_match(code, SWITCH_DUPLICATE_CASES_ERRORS);
+
+    let context = parse_a_file_for_test(code);
+    let notes: Vec<u32> = context.errors().iter().map(|error| error.notes()[0].location().line).collect();
+    assert_eq!(notes, vec![3, 9, 9, 5]);
+}
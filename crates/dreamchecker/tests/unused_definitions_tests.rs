@@ -0,0 +1,100 @@
+extern crate dreamchecker as dc;
+extern crate dreammaker as dm;
+
+use std::path::PathBuf;
+
+use dc::test_helpers::context_with_config;
+use dc::unused_definitions::{check_unused_definitions, DefineUsage};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("unused_definitions_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn check(context: &dm::Context, mut pp: dm::preprocessor::Preprocessor) -> Vec<(String, u32, String)> {
+    let indents = dm::indents::IndentProcessor::new(context, &mut pp);
+    let mut parser = dm::parser::Parser::new(context, indents);
+    parser.enable_procs();
+    let tree = parser.parse_object_tree();
+    let usage = DefineUsage::new(&pp.finalize()).expect("usage not tracked");
+    check_unused_definitions(context, &tree, &usage);
+    context.errors().iter()
+        .filter(|error| matches!(error.errortype(), Some("unused_macro" | "unused_include")))
+        .map(|error| {
+            let file = context.file_path(error.location().file);
+            let file = file.file_name().unwrap().to_string_lossy().into_owned();
+            (file, error.location().line, error.description().to_owned())
+        })
+        .collect()
+}
+
+#[test]
+fn unused_macros() {
+    let dir = temp_dir("macros");
+    let context = context_with_config("[dreamchecker]\nunused_macros = true\n");
+    let code = r##"
+#define USED 1
+#define UNUSED 2
+#define IN_BODY 3
+#define OUTER (IN_BODY + 1)
+#define CHECKED
+#define IN_DISABLED 4
+#define DEBUG
+#ifdef CHECKED
+#endif
+#if 0
+/proc/disabled()
+    return IN_DISABLED
+#endif
+/proc/test()
+    return USED
+"##.trim();
+    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dme".into(), code);
+    assert_eq!(check(&context, pp), vec![
+        ("unit_tests.dme".to_owned(), 2, "unused macro: \"UNUSED\"".to_owned()),
+        ("unit_tests.dme".to_owned(), 4, "unused macro: \"OUTER\"".to_owned()),
+    ]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn disabled_code_not_counted() {
+    let dir = temp_dir("disabled");
+    let context = context_with_config("[dreamchecker]\nunused_macros = true\ndisabled_code_uses_macros = false\n");
+    let code = r##"
+#define IN_DISABLED 4
+#ifdef NOT_DEFINED
+/proc/disabled()
+    return IN_DISABLED
+#endif
+"##.trim();
+    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dme".into(), code);
+    assert_eq!(check(&context, pp), vec![
+        ("unit_tests.dme".to_owned(), 1, "unused macro: \"IN_DISABLED\"".to_owned()),
+    ]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn unused_includes() {
+    let dir = temp_dir("includes");
+    let files = [
+        ("env.dme", "#include \"types.dm\"\n#include \"dead.dm\"\n#include \"macros.dm\"\n#include \"overrides.dm\"\n#include \"main.dm\"\n"),
+        ("types.dm", "/obj/thing\n    var/size = 1\n"),
+        ("dead.dm", "/obj/dead\n    var/weight = 2\n/proc/dead_helper()\n    return new /obj/dead\n"),
+        ("macros.dm", "#define MACRO_VALUE 3\n"),
+        ("overrides.dm", "/mob/Login()\n    ..()\n"),
+        ("main.dm", "/proc/main()\n    var/obj/thing/T = new\n    return T.size + MACRO_VALUE\n"),
+    ];
+    for (name, contents) in files {
+        std::fs::write(dir.join(name), contents).unwrap();
+    }
+    let context = context_with_config("[dreamchecker]\nunused_includes = true\n");
+    let pp = dm::preprocessor::Preprocessor::new(&context, dir.join("env.dme")).unwrap();
+    assert_eq!(check(&context, pp), vec![
+        ("env.dme".to_owned(), 2, "unused include: \"dead.dm\"".to_owned()),
+        ("env.dme".to_owned(), 5, "unused include: \"main.dm\"".to_owned()),
+    ]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
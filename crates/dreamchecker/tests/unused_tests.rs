@@ -0,0 +1,217 @@
+extern crate dreamchecker as dc;
+
+use dc::test_helpers::{check_errors_match, parse_a_file_for_test, NO_ERRORS};
+
+pub const UNUSED_VAR_ERRORS: &[(u32, u16, &str)] = &[
+    (2, 9, "unused var: \"unread\""),
+    (3, 9, "unused var: \"written\""),
+    (6, 16, "unused var: \"second\""),
+];
+
+#[test]
+fn unused_var() {
+    let code = r##"
+/proc/test()
+    var/unread = 1
+    var/written
+    written = 2
+    written += 3
+    var/first, second
+    var/_ignored
+    return first
+"##.trim();
+    check_errors_match(code, UNUSED_VAR_ERRORS);
+}
+
+#[test]
+fn unused_var_range() {
+    let context = parse_a_file_for_test(r##"
+/proc/test()
+    var/list/things = list()
+"##.trim());
+    let errors = context.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].errortype(), Some("unused_var"));
+    assert_eq!(errors[0].location().column, 14);
+    assert_eq!(errors[0].end().unwrap().column, 20);
+}
+
+#[test]
+fn used_in_interpolation() {
+    let code = r##"
+/proc/test()
+    var/name = "world"
+    var/count = 2
+    world.log << "hello [name], [count > 1 ? "all [count]" : "one"]"
+"##.trim();
+    check_errors_match(code, NO_ERRORS);
+}
+
+#[test]
+fn used_in_spawn() {
+    let code = r##"
+/proc/test()
+    var/delay = 10
+    var/message = "done"
+    spawn(delay)
+        world.log << message
+    spawn()
+        var/inner = 1
+        world.log << "[inner]"
+"##.trim();
+    check_errors_match(code, NO_ERRORS);
+}
+
+#[test]
+fn used_where_unanalyzed() {
+    let code = r##"
+/datum/proc/test(datum/D)
+    var/kind = /datum
+    var/arg = 1
+    var/datum/made = new kind(arg)
+    var/other = 2
+    D:thing(other)
+    var/third = 3
+    var/thing = D
+    thing.foo(third)
+    return made
+"##.trim();
+    check_errors_match(code, &[
+        (6, 6, "\"thing\" accessed with \":\""),
+        (9, 10, "proc call requires static type: \"foo\""),
+    ]);
+}
+
+pub const UNUSED_PARAM_ERRORS: &[(u32, u16, &str)] = &[
+    (1, 24, "unused parameter: \"unused\""),
+];
+
+#[test]
+fn unused_param() {
+    let code = r##"
+/datum/proc/test(used, unused, _skipped)
+    return used
+
+/datum/proc/stub(unused)
+
+/datum/proc/through_args(a, b)
+    return args.len
+
+/datum/proc/overridden(a)
+    return a
+
+/datum/child/overridden(a)
+    return 2
+"##.trim();
+    check_errors_match(code, UNUSED_PARAM_ERRORS);
+}
+
+pub const DEAD_STORE_ERRORS: &[(u32, u16, &str)] = &[
+    (5, 9, "value assigned to \"x\" is never read"),
+    (8, 13, "result assigned to \"y\" is never read"),
+    (15, 13, "value assigned to \"w\" is never read"),
+    (31, 13, "value assigned to \"q\" is never read"),
+];
+
+#[test]
+fn dead_store() {
+    let code = r##"
+/proc/next()
+    return 1
+/proc/test(a)
+    var/x = 1
+    x = 2
+    x = 3
+    world << x
+    var/y = next()
+    y = next()
+    world << y
+    var/z = a + 1
+    if(a)
+        z = 2
+    world << z
+    var/w = a
+    if(a)
+        w = 2
+    else
+        w = 3
+    world << w
+    var/i = a
+    while(i)
+        world << i
+        i = next()
+    var/k = a
+    for(var/n in 1 to 3)
+        k = n
+    world << k
+    var/q = a
+    if(a)
+        q = 1
+        return
+    world << q
+    var/t = a
+    try
+        t = next()
+        t = next()
+    catch
+        world << t
+    var/list/L = list()
+    var/p = &L
+    L = list(1)
+    world << *p
+"##.trim();
+    check_errors_match(code, DEAD_STORE_ERRORS);
+}
+
+#[test]
+fn dead_store_range() {
+    let context = parse_a_file_for_test(r##"
+/proc/test(a)
+    var/x = a
+    x = a + 1
+    return x
+"##.trim());
+    let errors = context.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].errortype(), Some("dead_store"));
+    assert_eq!(errors[0].location().line, 2);
+    assert_eq!(errors[0].location().column, 13);
+    assert_eq!(errors[0].end().unwrap().line, 2);
+    assert_eq!(errors[0].end().unwrap().column, 14);
+}
+
+#[test]
+fn dead_store_range_assignment() {
+    let context = parse_a_file_for_test(r##"
+/proc/test(a)
+    var/x
+    x = a + 0x10 // not read
+    x = 1
+    return x
+"##.trim());
+    let errors = context.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].errortype(), Some("dead_store"));
+    assert_eq!(errors[0].location().line, 3);
+    assert_eq!(errors[0].location().column, 9);
+    assert_eq!(errors[0].end().unwrap().line, 3);
+    assert_eq!(errors[0].end().unwrap().column, 17);
+}
+
+#[test]
+fn dead_store_range_macro() {
+    let context = parse_a_file_for_test(r##"
+#define TWICE(n) (n * 2)
+/proc/test(a)
+    var/x
+    x = TWICE(a) // not read
+    x = 1
+    return x
+"##.trim());
+    let errors = context.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].location().line, 4);
+    assert_eq!(errors[0].location().column, 9);
+    assert_eq!(errors[0].end().unwrap().line, 4);
+    assert_eq!(errors[0].end().unwrap().column, 17);
+}
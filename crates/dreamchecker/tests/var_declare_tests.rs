@@ -3,7 +3,7 @@ extern crate dreamchecker as dc;
 use dc::test_helpers::*;
 
 pub const VAR_DEC_ERRORS: &[(u32, u16, &str)] = &[
-    (5, 12, "/mob/subtype redeclares var \"foo\""),
+    (5, 9, "/mob/subtype redeclares var \"foo\""),
 ];
 
 #[test]
@@ -19,7 +19,7 @@ fn var_redec() {
 }
 
 pub const VAR_FINAL_ERRORS: &[(u32, u16, &str)] = &[
-    (5, 9, "/mob/subtype overrides final var \"foo\""),
+    (5, 5, "/mob/subtype overrides final var \"foo\""),
 ];
 
 #[test]
@@ -0,0 +1,66 @@
+extern crate dreamchecker as dc;
+extern crate dreammaker as dm;
+
+use dc::test_helpers::check_errors_match;
+use dc::verbs::{check_unreachable_verbs, mapped_types};
+
+#[test]
+fn verb_without_name() {
+    let code = r##"
+/mob/verb/unnamed()
+    world << "hi"
+
+/mob/verb/named()
+    set name = "Named"
+
+/mob/verb/categorized()
+    set category = "OOC"
+
+/mob/verb/hidden()
+    set hidden = 1
+"##.trim();
+    check_errors_match(code, &[
+        (1, 11, "/mob/verb/unnamed sets neither name nor category"),
+    ]);
+}
+
+#[test]
+fn unreachable_verbs() {
+    let map = std::env::temp_dir().join(format!("unreachable_verbs_{}.dmm", std::process::id()));
+    std::fs::write(&map, r#""a" = (/obj/mapped/thing{name = "a/b"},/turf/floor,/area/somewhere)
+(1,1,1) = {"
+a
+"}
+"#).unwrap();
+    let mapped = mapped_types(std::slice::from_ref(&map));
+    std::fs::remove_file(&map).unwrap();
+
+    let code = r##"
+/obj/mapped/verb/use()
+    set name = "Use"
+/obj/created/verb/use()
+    set name = "Use"
+/obj/dead/verb/use()
+    set name = "Use"
+/obj/parent/verb/use()
+    set name = "Use"
+/obj/parent/child
+/proc/make()
+    new /obj/created
+    new /obj/parent/child
+"##.trim();
+    let context = dm::Context::default();
+    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dm".into(), code);
+    let indents = dm::indents::IndentProcessor::new(&context, pp);
+    let mut parser = dm::parser::Parser::new(&context, indents);
+    parser.enable_procs();
+    let tree = parser.parse_object_tree();
+    check_unreachable_verbs(&context, &tree, &mapped);
+
+    let errors: Vec<_> = context.errors().iter()
+        .map(|error| (error.location().line, error.description().to_owned()))
+        .collect();
+    assert_eq!(errors, vec![
+        (5, "verb \"use\" is on /obj/dead, which is never created or mapped".to_owned()),
+    ]);
+}
@@ -21,6 +21,9 @@ pub enum Annotation {
     ProcHeader(Vec<Ident>, usize),
     ProcBody(Vec<Ident>, usize),
     LocalVarScope(VarType, Ident),
+    Statement,
+    StatementBlock,  // the body of a proc, loop, or branch
+    Expression,  // an expression, or an operand, field access, or prefab var within one
 
     // local information about a specific token
     UnscopedCall(Ident),
@@ -38,8 +41,16 @@ pub enum Annotation {
         definition_location: Location,
         docs: Option<Rc<DocCollection>>,
     },
+    // the parameters and substitution of a macro, which are only checked
+    // where the macro is used
+    MacroBody {
+        name: Ident,
+        params: Vec<Ident>,
+    },
+    MacroBodyIdent(Ident),  // an identifier in the parameters or substitution
 
     Include(std::path::PathBuf),
+    MissingInclude(std::path::PathBuf),  // as written, with `/` separators
     Resource(std::path::PathBuf),
 
     // error annotations, mostly for autocompletion
@@ -49,6 +60,7 @@ pub enum Annotation {
 
     ProcArguments(Vec<Ident>, String, usize),  // Vec empty for unscoped call
     ProcArgument(usize),  // where in the prog arguments we are
+    NewArguments(TypePath),  // the arguments to `new` of a known type
 }
 
 #[derive(Debug)]
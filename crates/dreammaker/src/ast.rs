@@ -16,6 +16,10 @@ pub type PickArgs = [(Option<Expression>, Expression)];
 /// Cases for [`Statement::Switch`]
 pub type SwitchCases = [(Spanned<Vec<Case>>, Block)];
 
+/// The embedded expressions of a [`Term::InterpString`], each with the text
+/// after it
+pub type InterpStringParts = [(Spanned<Option<Expression>>, Box<str>)];
+
 // ----------------------------------------------------------------------------
 // Simple enums
 
@@ -317,6 +321,20 @@ impl Default for ProcReturnType {
     }
 }
 
+impl fmt::Display for ProcReturnType {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProcReturnType::InputType(input_type) => input_type.fmt(fmt),
+            ProcReturnType::TypePath(path) => {
+                for each in path.iter() {
+                    write!(fmt, "/{}", each)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 /// Information about a proc declaration
 ///
 /// Holds what sort of decl it was (did it use /proc or /verb), alongside a set of flags
@@ -856,6 +874,8 @@ pub enum Expression {
         lhs: Box<Expression>,
         /// The right-hand side of the assignment.
         rhs: Box<Expression>,
+        /// Where the right-hand side ends.
+        end: Location,
     },
     /// A ternary operation.
     TernaryOp {
@@ -869,6 +889,15 @@ pub enum Expression {
 }
 
 impl Expression {
+    /// The location of this expression's leftmost term.
+    pub fn location(&self) -> Location {
+        match self {
+            Expression::Base { term, .. } => term.location,
+            Expression::BinaryOp { lhs, .. } | Expression::AssignOp { lhs, .. } => lhs.location(),
+            Expression::TernaryOp { cond, .. } => cond.location(),
+        }
+    }
+
     /// If this expression consists of a single term, return it.
     pub fn as_term(&self) -> Option<&Term> {
         match self {
@@ -940,7 +969,7 @@ impl Expression {
                     _ => None,
                 }
             },
-            Expression::AssignOp { op, lhs: _, rhs } => {
+            Expression::AssignOp { op, rhs, .. } => {
                 if let AssignOp::Assign = op {
                     return match rhs.as_term() {
                         Some(term) => term.is_truthy(),
@@ -1015,6 +1044,9 @@ pub enum Term {
     /// Second case takes precedence over the first, but we don't properly implement because it would be impossible to
     /// Tell. You can't DO anything to the __IMPLIED_TYPE__ so we don't really need to care about it
     __IMPLIED_TYPE__,
+    /// A placeholder for an expression which failed to parse. Its error has
+    /// already been reported.
+    Error,
 
     // Non-function calls with recursive contents -----------------------------
     /// An expression contained in a term.
@@ -1022,7 +1054,10 @@ pub enum Term {
     /// A prefab literal (path + vars).
     Prefab(Box<Prefab>),
     /// An interpolated string, alternating string/expr/string/expr.
-    InterpString(Ident2, Box<[(Option<Expression>, Box<str>)]>),
+    ///
+    /// Each embedded expression is spanned at its own start, which may be on
+    /// a later line than the string for `{"..."}` strings.
+    InterpString(Ident2, Box<InterpStringParts>),
 
     // Function calls with recursive contents ---------------------------------
     /// An unscoped function call.
@@ -1225,6 +1260,189 @@ impl From<Field> for Follow {
     }
 }
 
+// ----------------------------------------------------------------------------
+// Formatting expressions as DM source
+
+/// Formatting helper for comma-separated arguments.
+struct FormatArgs<'a>(&'a [Expression]);
+
+impl<'a> fmt::Display for FormatArgs<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, arg) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            arg.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formatting helper for an operand, parenthesized unless it is a term.
+struct Operand<'a>(&'a Expression);
+
+impl<'a> fmt::Display for Operand<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Expression::Base { .. } => self.0.fmt(f),
+            other => write!(f, "({})", other),
+        }
+    }
+}
+
+/// Formats as DM source. Operators which are not already in parentheses get
+/// them, so the result may have more than the original source did.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::Base { term, follow } => {
+                let mut text = term.elem.to_string();
+                for each in follow.iter() {
+                    text = match each.elem {
+                        Follow::Unary(op) => op.around(&text).to_string(),
+                        ref other => format!("{}{}", text, other),
+                    };
+                }
+                f.write_str(&text)
+            }
+            // `x in 1 to 5` is not the same as `x in (1 to 5)`
+            Expression::BinaryOp { op: BinaryOp::In, lhs, rhs } if matches!(**rhs, Expression::BinaryOp { op: BinaryOp::To, .. }) => {
+                write!(f, "{} in {}", Operand(lhs), rhs)
+            }
+            Expression::BinaryOp { op, lhs, rhs } => write!(f, "{} {} {}", Operand(lhs), op, Operand(rhs)),
+            Expression::AssignOp { op, lhs, rhs, .. } => write!(f, "{} {} {}", Operand(lhs), op, rhs),
+            Expression::TernaryOp { cond, if_, else_ } => {
+                write!(f, "{} ? {} : {}", Operand(cond), Operand(if_), Operand(else_))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Term::Null => f.write_str("null"),
+            Term::Int(i) => i.fmt(f),
+            Term::Float(n) => crate::lexer::FormatFloat(*n).fmt(f),
+            Term::Ident(ident) => f.write_str(ident),
+            Term::String(string) => crate::lexer::Quote(string).fmt(f),
+            Term::Resource(resource) => write!(f, "'{}'", resource),
+            Term::As(input_type) => write!(f, "as({})", input_type),
+            Term::__PROC__ => f.write_str("__PROC__"),
+            Term::__TYPE__ => f.write_str("__TYPE__"),
+            Term::__IMPLIED_TYPE__ => f.write_str("__IMPLIED_TYPE__"),
+            Term::Error => f.write_str("..."),
+            Term::Expr(expr) => write!(f, "({})", expr),
+            Term::Prefab(prefab) => prefab.fmt(f),
+            Term::InterpString(first, parts) => {
+                let braced = first.contains('"') || first.contains('\n')
+                    || parts.iter().any(|(_, part)| part.contains('"') || part.contains('\n'));
+                f.write_str(if braced { "{\"" } else { "\"" })?;
+                f.write_str(first)?;
+                for (expr, part) in parts.iter() {
+                    match expr.elem {
+                        Some(ref expr) => write!(f, "[{}]", expr)?,
+                        None => f.write_str("[]")?,
+                    }
+                    f.write_str(part)?;
+                }
+                f.write_str(if braced { "\"}" } else { "\"" })
+            }
+            Term::Call(name, args) => write!(f, "{}({})", name, FormatArgs(args)),
+            Term::SelfCall(args) => write!(f, ".({})", FormatArgs(args)),
+            Term::ParentCall(args) => write!(f, "..({})", FormatArgs(args)),
+            Term::NewImplicit { args } => {
+                f.write_str("new")?;
+                if let Some(args) = args {
+                    write!(f, "({})", FormatArgs(args))?;
+                }
+                Ok(())
+            }
+            Term::NewPrefab { prefab, args } => {
+                write!(f, "new {}", prefab)?;
+                if let Some(args) = args {
+                    write!(f, "({})", FormatArgs(args))?;
+                }
+                Ok(())
+            }
+            Term::NewMiniExpr { expr, args } => {
+                write!(f, "new {}", expr.ident)?;
+                for field in expr.fields.iter() {
+                    write!(f, "{}{}", field.kind, field.ident)?;
+                }
+                if let Some(args) = args {
+                    write!(f, "({})", FormatArgs(args))?;
+                }
+                Ok(())
+            }
+            Term::List(args) => write!(f, "list({})", FormatArgs(args)),
+            Term::Input { args, input_type, in_list } => {
+                write!(f, "input({})", FormatArgs(args))?;
+                if let Some(input_type) = input_type {
+                    write!(f, " as {}", input_type)?;
+                }
+                if let Some(in_list) = in_list {
+                    write!(f, " in {}", Operand(in_list))?;
+                }
+                Ok(())
+            }
+            Term::Locate { args, in_list } => {
+                write!(f, "locate({})", FormatArgs(args))?;
+                if let Some(in_list) = in_list {
+                    write!(f, " in {}", Operand(in_list))?;
+                }
+                Ok(())
+            }
+            Term::Pick(args) => {
+                f.write_str("pick(")?;
+                for (i, (weight, value)) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    if let Some(weight) = weight {
+                        write!(f, "{}; ", weight)?;
+                    }
+                    value.fmt(f)?;
+                }
+                f.write_str(")")
+            }
+            Term::DynamicCall(call_args, args) => write!(f, "call({})({})", FormatArgs(call_args), FormatArgs(args)),
+            Term::ExternalCall { library_name, function_name, args } => {
+                write!(f, "call_ext({}, {})({})", library_name, function_name, FormatArgs(args))
+            }
+            Term::GlobalIdent(ident) => write!(f, "::{}", ident),
+            Term::GlobalCall(ident, args) => write!(f, "::{}({})", ident, FormatArgs(args)),
+        }
+    }
+}
+
+impl fmt::Display for Follow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Follow::Index(ListAccessKind::Normal, expr) => write!(f, "[{}]", expr),
+            Follow::Index(ListAccessKind::Safe, expr) => write!(f, "?[{}]", expr),
+            Follow::Field(kind, ident) => write!(f, "{}{}", kind, ident),
+            Follow::Call(kind, ident, args) => write!(f, "{}{}({})", kind, ident, FormatArgs(args)),
+            Follow::Unary(op) => f.write_str(op.name()),
+            Follow::StaticField(ident) => write!(f, "::{}", ident),
+            Follow::ProcReference(ident) => write!(f, "::{}()", ident),
+        }
+    }
+}
+
+impl fmt::Display for Prefab {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", FormatTypePath(&self.path))?;
+        for (i, (name, value)) in self.vars.iter().enumerate() {
+            write!(f, "{}{} = {}", if i == 0 { "{" } else { "; " }, name, value)?;
+        }
+        if !self.vars.is_empty() {
+            f.write_str("}")?;
+        }
+        Ok(())
+    }
+}
+
 /// A parameter declaration in the header of a proc.
 #[derive(Debug, Clone, PartialEq, Default, GetSize)]
 pub struct Parameter {
@@ -1233,6 +1451,7 @@ pub struct Parameter {
     pub default: Option<Expression>,
     pub input_type: Option<InputType>,
     pub in_list: Option<Expression>,
+    /// The location of the parameter's name.
     pub location: Location,
 }
 
@@ -1242,6 +1461,9 @@ impl fmt::Display for Parameter {
         if let Some(input_type) = self.input_type {
             write!(fmt, " as {}", input_type)?;
         }
+        if let Some(in_list) = &self.in_list {
+            write!(fmt, " in {}", in_list)?;
+        }
         Ok(())
     }
 }
@@ -1428,6 +1650,10 @@ pub struct VarStatement {
     pub var_type: VarType,
     pub name: Ident,
     pub value: Option<Expression>,
+    /// The location of the var's name.
+    pub location: Location,
+    /// Where the declaration ends, after its value if it has one.
+    pub end: Location,
 }
 
 #[derive(Debug, Clone, PartialEq, GetSize)]
@@ -0,0 +1,192 @@
+//! Baselines of known diagnostics, so new lints can be adopted gradually.
+//!
+//! A baseline lists the diagnostics a codebase already has, one per line:
+//! code, file, and a fingerprint of the flagged line, separated by tabs and
+//! followed by the description for whoever trims it. Fingerprints hash the
+//! text of the line rather than its position, so entries survive code being
+//! added or removed above them.
+
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+
+use foldhash::HashMap;
+
+use crate::{Context, DMError, FileId};
+
+/// One known diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BaselineEntry {
+    pub code: String,
+    pub file: String,
+    pub fingerprint: String,
+    pub description: String,
+}
+
+impl BaselineEntry {
+    fn key(&self) -> (String, String, String) {
+        (self.code.clone(), self.file.clone(), self.fingerprint.clone())
+    }
+}
+
+/// A set of known diagnostics.
+#[derive(Debug, Clone, Default)]
+pub struct Baseline {
+    entries: Vec<BaselineEntry>,
+}
+
+impl Baseline {
+    /// Record every diagnostic which has a code.
+    pub fn from_errors(context: &Context, env_dir: &Path) -> Baseline {
+        let mut lines = LineCache::new(context, env_dir);
+        let mut entries: Vec<_> = context.errors().iter()
+            .filter_map(|error| lines.entry(error))
+            .collect();
+        entries.sort();
+        Baseline { entries }
+    }
+
+    pub fn entries(&self) -> &[BaselineEntry] {
+        &self.entries
+    }
+
+    pub fn parse(text: &str) -> Baseline {
+        let entries = text.lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut fields = line.split('\t');
+                Some(BaselineEntry {
+                    code: fields.next()?.to_owned(),
+                    file: fields.next()?.to_owned(),
+                    fingerprint: fields.next()?.to_owned(),
+                    description: fields.next().unwrap_or("").to_owned(),
+                })
+            })
+            .collect();
+        Baseline { entries }
+    }
+
+    pub fn read(path: &Path) -> io::Result<Baseline> {
+        Ok(Baseline::parse(&std::fs::read_to_string(path)?))
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::new();
+        for entry in self.entries.iter() {
+            let _ = writeln!(text, "{}\t{}\t{}\t{}", entry.code, entry.file, entry.fingerprint, entry.description);
+        }
+        std::fs::write(path, text)
+    }
+
+    /// Remove the diagnostics this baseline knows about from the context,
+    /// returning the entries which matched nothing.
+    pub fn apply(&self, context: &Context, env_dir: &Path) -> Vec<BaselineEntry> {
+        self.apply_where(context, LineCache::new(context, env_dir), |_| true)
+    }
+
+    /// Remove the diagnostics this baseline knows about from one file which
+    /// was checked again, fingerprinting them against its current contents
+    /// rather than what is saved on disk.
+    pub fn apply_to_file(&self, context: &Context, env_dir: &Path, file: FileId, contents: &str) {
+        let mut lines = LineCache::new(context, env_dir);
+        lines.insert(file, contents);
+        self.apply_where(context, lines, |error| error.location().file == file);
+    }
+
+    fn apply_where(&self, context: &Context, mut lines: LineCache, filter: impl Fn(&DMError) -> bool) -> Vec<BaselineEntry> {
+        let mut remaining: HashMap<(String, String, String), usize> = HashMap::default();
+        for entry in self.entries.iter() {
+            *remaining.entry(entry.key()).or_default() += 1;
+        }
+
+        let known: Vec<bool> = context.errors().iter()
+            .map(|error| {
+                if !filter(error) {
+                    return false;
+                }
+                let Some(entry) = lines.entry(error) else { return false };
+                match remaining.get_mut(&entry.key()) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        true
+                    }
+                    _ => false,
+                }
+            })
+            .collect();
+        let mut known = known.into_iter();
+        context.errors_mut().retain(|_| !known.next().unwrap_or(false));
+
+        let mut stale = Vec::new();
+        for entry in self.entries.iter() {
+            if let Some(count) = remaining.get_mut(&entry.key()) {
+                if *count > 0 {
+                    *count -= 1;
+                    stale.push(entry.clone());
+                }
+            }
+        }
+        stale
+    }
+}
+
+/// Fingerprint a diagnostic by its code, the text of its line, and the name
+/// it is about, which is the first quoted part of its description.
+pub fn fingerprint(code: &str, line: &str, description: &str) -> String {
+    let symbol = description.split('"').nth(1).unwrap_or("");
+    // FNV-1a, which unlike the hashers in std is stable between releases.
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in [code, line.trim(), symbol] {
+        for byte in part.bytes().chain(std::iter::once(0)) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// The lines of each file diagnostics are reported in, read as needed.
+struct LineCache<'a> {
+    context: &'a Context,
+    env_dir: &'a Path,
+    files: HashMap<FileId, Vec<String>>,
+}
+
+impl<'a> LineCache<'a> {
+    fn new(context: &'a Context, env_dir: &'a Path) -> LineCache<'a> {
+        LineCache { context, env_dir, files: HashMap::default() }
+    }
+
+    /// Use the given text for a file instead of reading it.
+    fn insert(&mut self, file: FileId, contents: &str) {
+        self.files.insert(file, split_lines(contents.as_bytes()));
+    }
+
+    fn entry(&mut self, error: &DMError) -> Option<BaselineEntry> {
+        let code = error.code()?;
+        let location = error.location();
+        if location.is_builtins() {
+            return None;
+        }
+        let path = self.context.file_path(location.file).to_owned();
+        let relative = path.strip_prefix(self.env_dir).unwrap_or(&path);
+        let file = relative.to_string_lossy().replace('\\', "/");
+
+        let full = self.env_dir.join(relative);
+        let lines = self.files.entry(location.file).or_insert_with(|| {
+            std::fs::read(full).map(|bytes| split_lines(&bytes)).unwrap_or_default()
+        });
+        let line = lines.get((location.line as usize).wrapping_sub(1)).map_or("", String::as_str);
+
+        Some(BaselineEntry {
+            code: code.to_owned(),
+            file,
+            fingerprint: fingerprint(code, line, error.description()),
+            description: error.description().replace(['\t', '\n'], " "),
+        })
+    }
+}
+
+fn split_lines(bytes: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(bytes).lines().map(ToOwned::to_owned).collect()
+}
@@ -0,0 +1,148 @@
+//! Stable codes for configurable diagnostics, and suppression comments.
+//!
+//! Every errortype has a code which never changes or gets reused, so that
+//! configuration and suppression comments keep working as the analyses grow.
+//! Anywhere a diagnostic is named, its code, its errortype, or its errortype
+//! written with dashes (`unused-var`) may be used.
+
+/// A diagnostic which can be configured or suppressed.
+#[derive(Debug, Clone, Copy)]
+pub struct DiagnosticCode {
+    pub code: &'static str,
+    pub errortype: &'static str,
+}
+
+const fn code(code: &'static str, errortype: &'static str) -> DiagnosticCode {
+    DiagnosticCode { code, errortype }
+}
+
+/// Every diagnostic code. New codes go at the end of their group.
+pub const CODES: &[DiagnosticCode] = &[
+    // Lexer and preprocessor
+    code("SDM0101", "integer_precision_loss"),
+    code("SDM0102", "duplicate_include"),
+    code("SDM0103", "macro_redefined"),
+    code("SDM0104", "macro_undefined_no_definition"),
+    code("SDM0105", "unknown_text_macro"),
+    code("SDM0106", "text_macro_argument"),
+    code("SDM0107", "include_case_mismatch"),
+    code("SDM0108", "invalid_utf8"),
+    // Parser and object tree
+    code("SDM0201", "var_in_proc_parameter"),
+    code("SDM0202", "static_in_proc_parameter"),
+    code("SDM0203", "semicolon_in_proc_parameter"),
+    code("SDM0204", "in_precedes_as"),
+    code("SDM0205", "tmp_no_effect"),
+    code("SDM0206", "final_no_effect"),
+    code("SDM0207", "as_local_var"),
+    code("SDM0208", "empty_block"),
+    code("SDM0209", "override_precedes_definition"),
+    code("SDM0210", "mixed_indentation"),
+    code("SDM0211", "indentation_mismatch"),
+    // Directives, access, and overrides
+    code("SDM0301", "disabled_directive"),
+    code("SDM0302", "sets_directive_twice"),
+    code("SDM0303", "invalid_lint_directive_value"),
+    code("SDM0304", "invalid_set_value"),
+    code("SDM0305", "unknown_linter_setting"),
+    code("SDM0306", "incompatible_directive"),
+    code("SDM0307", "must_not_override"),
+    code("SDM0308", "must_call_parent"),
+    code("SDM0309", "must_be_pure"),
+    code("SDM0310", "must_not_sleep"),
+    code("SDM0311", "redefined_proc"),
+    code("SDM0312", "final_var"),
+    code("SDM0313", "private_proc"),
+    code("SDM0314", "protected_proc"),
+    code("SDM0315", "private_var"),
+    code("SDM0316", "protected_var"),
+    code("SDM0317", "missing_parent_call"),
+    code("SDM0318", "proc_has_no_parent"),
+    code("SDM0319", "override_missing_keyword_arg"),
+    code("SDM0320", "override_signature"),
+    code("SDM0321", "override_without_declaration"),
+    code("SDM0322", "duplicate_parent_call"),
+    code("SDM0323", "parent_call_return"),
+    code("SDM0324", "verb_without_name"),
+    // Types and calls
+    code("SDM0401", "undefined_var"),
+    code("SDM0402", "field_access_static_type"),
+    code("SDM0403", "proc_call_static_type"),
+    code("SDM0404", "missing_arguments"),
+    code("SDM0405", "too_many_arguments"),
+    code("SDM0406", "return_type_mismatch"),
+    code("SDM0407", "missing_return_value"),
+    code("SDM0408", "no_typehint_implicit_new"),
+    code("SDM0409", "istype_always_true"),
+    code("SDM0410", "istype_always_false"),
+    code("SDM0411", "string_typepath_mismatch"),
+    code("SDM0412", "null_dereference"),
+    code("SDM0413", "colon_operator"),
+    code("SDM0414", "deprecated"),
+    // Control flow and expressions
+    code("SDM0501", "unreachable_code"),
+    code("SDM0502", "control_condition_static"),
+    code("SDM0503", "if_condition_determinate"),
+    code("SDM0504", "loop_condition_determinate"),
+    code("SDM0505", "duplicate_switch_case"),
+    code("SDM0506", "assignment_in_condition"),
+    code("SDM0507", "misleading_indentation"),
+    code("SDM0508", "ambiguous_in_lhs"),
+    code("SDM0509", "ambiguous_in_rhs"),
+    code("SDM0510", "ambiguous_not_bitwise"),
+    code("SDM0511", "float_equality"),
+    // Unused and shadowed names
+    code("SDM0601", "unused_var"),
+    code("SDM0602", "unused_param"),
+    code("SDM0603", "dead_store"),
+    code("SDM0604", "shadowed_param"),
+    code("SDM0605", "shadowed_local"),
+    code("SDM0606", "shadowed_field"),
+    code("SDM0607", "shadowed_builtin"),
+    code("SDM0608", "unused_macro"),
+    code("SDM0609", "unused_include"),
+    code("SDM0610", "unreachable_verb"),
+    code("SDM0611", "unincluded_file"),
+    // Size and complexity
+    code("SDM0701", "proc_complexity"),
+    code("SDM0702", "proc_length"),
+    code("SDM0703", "proc_nesting"),
+    code("SDM0704", "proc_parameters"),
+];
+
+/// Look up the code of an errortype.
+pub fn code_for(errortype: &str) -> Option<&'static str> {
+    CODES.iter().find(|each| each.errortype == errortype).map(|each| each.code)
+}
+
+/// Look up the errortype named by a code, errortype, or dashed errortype.
+pub fn errortype_for(name: &str) -> Option<&'static str> {
+    CODES.iter()
+        .find(|each| {
+            each.code.eq_ignore_ascii_case(name)
+                || each.errortype == name
+                || each.errortype.bytes().eq(name.bytes().map(|b| if b == b'-' { b'_' } else { b }))
+        })
+        .map(|each| each.errortype)
+}
+
+/// Parse a `SDMM: ignore(unused-var, SDM0603)` comment into the errortypes
+/// it suppresses.
+///
+/// Returns `None` if the comment is not a suppression comment at all, and
+/// otherwise the errortypes along with any names which are not diagnostics.
+pub fn parse_suppression(comment: &str) -> Option<(Vec<&'static str>, Vec<String>)> {
+    let rest = comment.trim().strip_prefix("SDMM:")?.trim_start();
+    let list = rest.strip_prefix("ignore")?.trim_start().strip_prefix('(')?;
+    let list = &list[..list.find(')')?];
+
+    let mut errortypes = Vec::new();
+    let mut unknown = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        match errortype_for(name) {
+            Some(errortype) => errortypes.push(errortype),
+            None => unknown.push(name.to_owned()),
+        }
+    }
+    Some((errortypes, unknown))
+}
@@ -1,6 +1,7 @@
 //! Configuration file for diagnostics.
 
 use foldhash::HashMap;
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
@@ -15,14 +16,18 @@ use crate::DMError;
 #[serde(default)]
 pub struct Config {
     pub environment: Option<PathBuf>,
+    /// Macros defined before the environment is read, as if by `#define`.
+    pub defines: BTreeMap<String, String>,
 
     // diagnostic configuration
     display: WarningDisplay,
     diagnostics: HashMap<String, WarningLevel>,
+    path_diagnostics: Vec<PathDiagnostics>,
     pub code_standards: CodeStandards,
 
     // tool-specific configuration
     pub langserver: Langserver,
+    pub dreamchecker: DreamChecker,
     pub dmdoc: DMDoc,
     pub debugger: Debugger,
     pub map_renderer: MapRenderer,
@@ -35,12 +40,77 @@ pub struct WarningDisplay {
     error_level: WarningLevel,
 }
 
+/// Diagnostic overrides for files matching a glob, relative to the
+/// environment's directory
+#[derive(Deserialize, Debug, Clone)]
+pub struct PathDiagnostics {
+    path: String,
+    #[serde(flatten)]
+    diagnostics: HashMap<String, WarningLevel>,
+}
+
 /// Langserver config options
 #[derive(Deserialize, Default, Debug, Clone)]
 pub struct Langserver {
     pub dreamchecker: bool,
 }
 
+/// DreamChecker config options
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DreamChecker {
+    /// Typepaths whose procs are dispatched dynamically, so calls to procs
+    /// they do not define are not reported.
+    pub dynamic_proc_types: Vec<String>,
+    /// Names of procs whose overrides must call their parent.
+    pub lifecycle_procs: Vec<String>,
+    /// Procs, as `/type/name`, which run during map load and so must not
+    /// sleep, along with their overrides.
+    pub map_load_procs: Vec<String>,
+    /// Report final, private, and protected violations as warnings.
+    pub access_violations_as_warnings: bool,
+    /// A baseline file, relative to the `.dme`, listing known diagnostics
+    /// which are not reported.
+    pub baseline: Option<String>,
+    /// Warn on procs whose cyclomatic complexity exceeds this.
+    pub max_complexity: Option<usize>,
+    /// Warn on procs with more statements than this.
+    pub max_statements: Option<usize>,
+    /// Warn on procs whose blocks nest deeper than this.
+    pub max_nesting: Option<usize>,
+    /// Warn on procs with more parameters than this.
+    pub max_parameters: Option<usize>,
+    /// Report macros which are never used.
+    pub unused_macros: bool,
+    /// Report included files whose definitions are never used elsewhere.
+    pub unused_includes: bool,
+    /// Report `.dm` files in the environment's directory which it never
+    /// includes.
+    pub unincluded_files: bool,
+    /// Count names in code disabled by `#if` and `#ifdef` as macro uses.
+    pub disabled_code_uses_macros: bool,
+}
+
+impl Default for DreamChecker {
+    fn default() -> Self {
+        DreamChecker {
+            dynamic_proc_types: Vec::new(),
+            lifecycle_procs: vec!["New".to_owned(), "Initialize".to_owned(), "Destroy".to_owned()],
+            map_load_procs: vec!["/atom/New".to_owned()],
+            access_violations_as_warnings: false,
+            baseline: None,
+            max_complexity: None,
+            max_statements: None,
+            max_nesting: None,
+            max_parameters: None,
+            unused_macros: false,
+            unused_includes: false,
+            unincluded_files: false,
+            disabled_code_uses_macros: true,
+        }
+    }
+}
+
 /// Extremely opinionated linter config options
 #[derive(Deserialize, Default, Debug, Clone)]
 #[serde(default)]
@@ -72,7 +142,7 @@ pub struct Debugger {
 pub enum WarningLevel {
     #[serde(alias = "errors")]
     Error = 1,
-    #[serde(alias = "warnings")]
+    #[serde(alias = "warnings", alias = "warn")]
     Warning = 2,
     #[serde(alias = "infos")]
     Info = 3,
@@ -120,11 +190,26 @@ impl Config {
         Ok(toml::from_str(&config_toml)?)
     }
 
-    fn config_warninglevel(&self, error: &DMError) -> Option<&WarningLevel> {
-        if let Some(errortype) = error.errortype() {
-            return self.diagnostics.get(errortype)
+    fn config_warninglevel(&self, error: &DMError, path: Option<&Path>) -> Option<&WarningLevel> {
+        let errortype = error.errortype()?;
+        if let Some(path) = path {
+            let path = path.to_string_lossy().replace('\\', "/");
+            let overridden = self.path_diagnostics.iter().rev()
+                .filter(|each| glob_matches(&each.path, &path))
+                .find_map(|each| configured_level(&each.diagnostics, errortype));
+            if overridden.is_some() {
+                return overridden;
+            }
         }
-        None
+        configured_level(&self.diagnostics, errortype)
+    }
+
+    /// Whether any diagnostics are configured per path, in which case
+    /// errors should be passed to [`set_configured_severity_at`].
+    ///
+    /// [`set_configured_severity_at`]: #method.set_configured_severity_at
+    pub fn has_path_diagnostics(&self) -> bool {
+        !self.path_diagnostics.is_empty()
     }
 
     /// Return a new [`DMError`] with the configured [`Severity`] or [`None`] if disabled
@@ -133,14 +218,17 @@ impl Config {
     /// [`Severity`]: ../enum.Severity.html
     /// [`None`]: ../../std/option/enum.Option.html#variant.None
     pub fn set_configured_severity(&self, error: DMError) -> Option<DMError> {
-        Some(match self.config_warninglevel(&error) {
-            Some(WarningLevel::Error) => error.set_severity(Severity::Error),
-            Some(WarningLevel::Warning) => error.set_severity(Severity::Warning),
-            Some(WarningLevel::Info) => error.set_severity(Severity::Info),
-            Some(WarningLevel::Hint) => error.set_severity(Severity::Hint),
-            Some(WarningLevel::Disabled) => return None,
-            Some(WarningLevel::Unset) | None => error,
-        })
+        let level = self.config_warninglevel(&error, None).copied();
+        apply_level(error, level)
+    }
+
+    /// As [`set_configured_severity`], taking overrides for the path of the
+    /// file the error is in into account.
+    ///
+    /// [`set_configured_severity`]: #method.set_configured_severity
+    pub fn set_configured_severity_at(&self, error: DMError, path: &Path) -> Option<DMError> {
+        let level = self.config_warninglevel(&error, Some(path)).copied();
+        apply_level(error, level)
     }
 
     /// Test the error against the configured error level threshold
@@ -149,6 +237,49 @@ impl Config {
     }
 }
 
+/// Look up the level set for an errortype, by its name or its code.
+fn configured_level<'a>(levels: &'a HashMap<String, WarningLevel>, errortype: &str) -> Option<&'a WarningLevel> {
+    if let Some(level) = levels.get(errortype) {
+        return Some(level);
+    }
+    levels.iter()
+        .find(|(name, _)| crate::codes::errortype_for(name) == Some(errortype))
+        .map(|(_, level)| level)
+}
+
+fn apply_level(error: DMError, level: Option<WarningLevel>) -> Option<DMError> {
+    Some(match level {
+        Some(WarningLevel::Error) => error.set_severity(Severity::Error),
+        Some(WarningLevel::Warning) => error.set_severity(Severity::Warning),
+        Some(WarningLevel::Info) => error.set_severity(Severity::Info),
+        Some(WarningLevel::Hint) => error.set_severity(Severity::Hint),
+        Some(WarningLevel::Disabled) => return None,
+        Some(WarningLevel::Unset) | None => error,
+    })
+}
+
+/// Match a path against a glob, where `*` matches within one path component,
+/// `**` matches across components, and `?` matches one character.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    fn matches(pattern: &[u8], path: &[u8]) -> bool {
+        match pattern {
+            [] => path.is_empty(),
+            [b'*', b'*', b'/', rest @ ..] => {
+                // `**/` may also match no directories at all.
+                matches(rest, path) || (0..path.len()).any(|i| path[i] == b'/' && matches(rest, &path[i + 1..]))
+            }
+            [b'*', b'*', rest @ ..] => (0..=path.len()).any(|i| matches(rest, &path[i..])),
+            [b'*', rest @ ..] => {
+                let component = path.iter().position(|&b| b == b'/').unwrap_or(path.len());
+                (0..=component).any(|i| matches(rest, &path[i..]))
+            }
+            [b'?', rest @ ..] => matches!(path.first(), Some(&b) if b != b'/') && matches(rest, &path[1..]),
+            [first, rest @ ..] => path.first() == Some(first) && matches(rest, &path[1..]),
+        }
+    }
+    matches(pattern.as_bytes(), path.as_bytes())
+}
+
 impl WarningLevel {
     fn applies_to(self, severity: Severity) -> bool {
         match self {
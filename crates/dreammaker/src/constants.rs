@@ -1,4 +1,5 @@
 //! The constant folder/evaluator, used by the preprocessor and object tree.
+use std::collections::BTreeSet;
 use std::fmt;
 use std::ops;
 use std::path::Path;
@@ -14,9 +15,10 @@ use ordered_float::OrderedFloat;
 use crate::heap_size_of_index_map;
 
 use super::ast::*;
+use super::intern::{intern, Interned};
 use super::objtree::*;
 use super::preprocessor::DefineMap;
-use super::{Context, DMError, HasLocation, Location, Severity};
+use super::{Component, Context, DMError, HasLocation, Location, Severity};
 
 pub type Arguments = [(Constant, Option<Constant>)];
 
@@ -27,7 +29,7 @@ pub type Arguments = [(Constant, Option<Constant>)];
 pub struct Pop {
     pub path: TreePath,
     #[get_size(size_fn = heap_size_of_index_map)]
-    pub vars: IndexMap<Ident, Constant, RandomState>,
+    pub vars: IndexMap<Interned, Constant, RandomState>,
 }
 
 impl PartialEq for Pop {
@@ -426,7 +428,8 @@ pub fn evaluate_str(location: Location, input: &[u8]) -> Result<Constant, DMErro
     let expr = crate::parser::parse_expression(&ctx, location, &mut lexer)?;
     let leftover = lexer.remaining();
     if !leftover.is_empty() {
-        return Err(DMError::new(location, format!("leftover: {:?} {}", from_utf8_or_latin1_borrowed(input), leftover.len())));
+        return Err(DMError::new(location, format!("leftover: {:?} {}", from_utf8_or_latin1_borrowed(input), leftover.len()))
+            .with_component(Component::Constants));
     }
     expr.simple_evaluate(location)
 }
@@ -457,8 +460,11 @@ pub fn preprocessor_evaluate(location: Location, expr: Expression, defines: &Def
 
 /// Evaluate all the type-level variables in an object tree into constants.
 pub(crate) fn evaluate_all(context: &Context, tree: &mut ObjectTree) {
+    // A failing initializer is reached again by every var which refers to
+    // it, so only report it the first time.
+    let mut reported = BTreeSet::new();
     for ty in tree.node_indices() {
-        let keys: Vec<String> = tree[ty].vars.keys().cloned().collect();
+        let keys: Vec<Interned> = tree[ty].vars.keys().cloned().collect();
         for key in keys {
             if !tree[ty]
                 .get_var_declaration(&key, tree)
@@ -469,17 +475,19 @@ pub(crate) fn evaluate_all(context: &Context, tree: &mut ObjectTree) {
                 continue;  // skip non-constant-evaluable vars
             }
             match constant_ident_lookup(tree, ty, &key, false, Some(context)) {
-                Err(err) => context.register_error(err),
+                Err(err) => if reported.insert((err.location(), err.description().to_owned())) {
+                    context.register_error(err);
+                },
                 Ok(ConstLookup::Found(_)) => {}
                 Ok(ConstLookup::Continue(_)) => {
                     context.register_error(DMError::new(
-                        tree[ty].vars[&key].value.location,
+                        tree[ty].vars[key.as_str()].value.location,
                         format!(
                             "undefined var '{}' on type '{}'",
                             key,
                             tree[ty].path,
                         ),
-                    ));
+                    ).with_component(Component::Constants));
                 }
             }
         }
@@ -521,17 +529,17 @@ fn constant_ident_lookup(
                             return Err(DMError::new(
                                 var.value.location,
                                 format!("recursive constant reference: {}", ident),
-                            ));
+                            ).with_component(Component::Constants));
                         } else if !decl.var_type.is_const_evaluable() {
                             return Err(DMError::new(
                                 var.value.location,
                                 format!("non-const-evaluable variable: {}", ident),
-                            ));
+                            ).with_component(Component::Constants));
                         } else if !decl.var_type.flags.is_const() && must_be_const {
                             return Err(DMError::new(
                                 var.value.location,
                                 format!("non-const variable: {}", ident),
-                            ));
+                            ).with_component(Component::Constants));
                         }
                         var.value.being_evaluated = true;
                         (var.value.location, decl.var_type.type_path, expr)
@@ -546,17 +554,18 @@ fn constant_ident_lookup(
         }
     };
     // evaluate full_value
-    let value = ConstantFolder {
+    let result = ConstantFolder {
         context,
         tree: Some(tree),
         defines: None,
         location,
         ty,
-    }.expr(expr, if type_hint.is_empty() { None } else { Some(&type_hint) })?;
+    }.expr(expr, if type_hint.is_empty() { None } else { Some(&type_hint) });
     // and store it into 'value', then return it
     let var = tree[ty].vars.get_mut(ident).unwrap();
-    var.value.constant = Some(value.clone());
     var.value.being_evaluated = false;
+    let value = result?;
+    var.value.constant = Some(value.clone());
     Ok(ConstLookup::Found(/*type_hint,*/ value))
 }
 
@@ -569,6 +578,8 @@ struct ConstantFolder<'a> {
 }
 
 impl<'a> HasLocation for ConstantFolder<'a> {
+    const COMPONENT: Component = Component::Constants;
+
     fn location(&self) -> Location {
         self.location
     }
@@ -583,7 +594,15 @@ impl<'a> ConstantFolder<'a> {
                 } else {
                     None
                 };
-                let mut term = self.term(term.elem, base_type_hint)?;
+                let term = match term.elem {
+                    // `src::var` reads the initial value on the type itself.
+                    Term::Ident(ident) if ident == "src" && matches!(
+                        follow.first().map(|each| &each.elem),
+                        Some(Follow::StaticField(_)) | Some(Follow::ProcReference(_))
+                    ) => Term::Ident("type".to_owned()),
+                    other => other,
+                };
+                let mut term = self.term(term, base_type_hint)?;
                 for each in Vec::from(follow).into_iter() {
                     term = self.follow(term, each.elem)?;
                 }
@@ -624,6 +643,7 @@ impl<'a> ConstantFolder<'a> {
                     op: AssignOp::Assign,
                     lhs,
                     rhs,
+                    ..
                 } => {
                     let key = match Term::from(*lhs) {
                         Term::Ident(ident) => Constant::String(ident.into()),
@@ -768,6 +788,8 @@ impl<'a> ConstantFolder<'a> {
     fn term(&mut self, term: Term, type_hint: Option<&TreePath>) -> Result<Constant, DMError> {
         Ok(match term {
             Term::Null => Constant::Null(type_hint.cloned()),
+            // Already reported when it failed to parse.
+            Term::Error => Constant::Null(None),
             Term::NewPrefab { prefab, args } => Constant::New {
                 type_: Some(Box::new(self.prefab(*prefab)?)),
                 args: match args {
@@ -865,6 +887,9 @@ impl<'a> ConstantFolder<'a> {
                         return Err(self.error("no type context".to_owned()))
                     }
                 }
+                // Identifiers left over after macro substitution in a
+                // preprocessor condition are undefined, and count as 0.
+                _ if self.defines.is_some() && self.tree.is_none() => Constant::Float(0.),
                 _ => self.ident(ident, false)?,
             },
             Term::String(v) => Constant::String(v.into()),
@@ -931,12 +956,12 @@ impl<'a> ConstantFolder<'a> {
         Ok(Pop { path, vars })
     }
 
-    fn vars(&mut self, input: Vec<(Ident2, Expression)>) -> Result<IndexMap<Ident, Constant, RandomState>, DMError> {
+    fn vars(&mut self, input: Vec<(Ident2, Expression)>) -> Result<IndexMap<Interned, Constant, RandomState>, DMError> {
         // Visit the vars recursively.
         let mut vars = IndexMap::with_hasher(RandomState::default());
         for (k, v) in input {
             // TODO: find a type annotation by looking up 'k' on the prefab's type
-            vars.insert(String::from(k), self.expr(v, None)?);
+            vars.insert(intern(&k), self.expr(v, None)?);
         }
         Ok(vars)
     }
@@ -954,8 +979,10 @@ impl<'a> ConstantFolder<'a> {
                 return Err(self.error(format!("cannot reference variable {:?} in this context", ident)));
             }
             let tree = self.tree.as_mut().unwrap();
+            // Keep the error where the failing initializer is, and note
+            // each reference that led there.
             match constant_ident_lookup(tree, ty, ident, must_be_const, self.context)
-                .map_err(|e| e.with_location(location))?
+                .map_err(|e| e.with_note(location, format!("{} is referenced here", ident)))?
             {
                 ConstLookup::Found(v) => return Ok(v),
                 ConstLookup::Continue(i) => idx = i,
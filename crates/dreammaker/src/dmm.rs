@@ -0,0 +1,171 @@
+//! A lightweight reader for the dictionary section of `.dmm` map files, for
+//! finding the prefabs placed on a map and the code which defines them.
+//!
+//! Both the standard and TGM formats are accepted, since they differ only in
+//! their whitespace.
+
+use std::ops::Range;
+
+use crate::lexer::{Lexer, Punctuation, Token};
+use crate::{Context, FileId, Location};
+
+/// A type path, with its var overrides, from one dictionary entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prefab {
+    pub path: Vec<String>,
+    pub span: Range<Location>,
+    pub vars: Vec<VarOverride>,
+}
+
+/// One `name = value` inside a prefab's braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VarOverride {
+    pub name: String,
+    pub span: Range<Location>,
+    /// The value as written in the map.
+    pub value: String,
+}
+
+/// The part of a prefab under some location.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Symbol<'a> {
+    Prefab(&'a Prefab),
+    Var(&'a Prefab, &'a VarOverride),
+}
+
+/// A token along with where it starts and the byte range it covers.
+struct Spanned {
+    token: Token,
+    location: Location,
+    bytes: Range<usize>,
+}
+
+/// Read the prefabs of a map's dictionary, stopping where its grid begins.
+pub fn parse_dictionary(text: &str) -> Vec<Prefab> {
+    let context = Context::default();
+    let mut lexer = Lexer::new(&context, FileId::default(), text.as_bytes());
+    let mut tokens = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    while let Some(located) = Iterator::next(&mut lexer) {
+        let end = lexer.offset();
+        let bytes = std::mem::replace(&mut start, end)..end;
+        match located.token {
+            Token::Punct(Punctuation::Tab | Punctuation::Newline | Punctuation::Space) | Token::DocComment(_) => continue,
+            Token::Punct(Punctuation::LParen) => {
+                // `(1,1,1) = {"..."}` starts the grid
+                if depth == 0 && !matches!(tokens.last(), Some(Spanned { token: Token::Punct(Punctuation::Assign), .. })) {
+                    break;
+                }
+                depth += 1;
+            }
+            Token::Punct(Punctuation::RParen) => depth -= 1,
+            _ => {}
+        }
+        tokens.push(Spanned { token: located.token, location: located.location, bytes });
+    }
+
+    let mut prefabs = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let is_entry = matches!(tokens[i].token, Token::String(_))
+            && tokens.get(i + 1).is_some_and(|t| t.token == Token::Punct(Punctuation::Assign))
+            && tokens.get(i + 2).is_some_and(|t| t.token == Token::Punct(Punctuation::LParen));
+        if !is_entry {
+            i += 1;
+            continue;
+        }
+        i += 3;
+        while let Some(prefab) = parse_prefab(text, &tokens, &mut i) {
+            prefabs.push(prefab);
+            if tokens.get(i).is_some_and(|t| t.token == Token::Punct(Punctuation::Comma)) {
+                i += 1;
+            } else {
+                break;
+            }
+        }
+    }
+    prefabs
+}
+
+fn parse_prefab(text: &str, tokens: &[Spanned], i: &mut usize) -> Option<Prefab> {
+    let start = tokens.get(*i)?.location;
+    let mut end = start;
+    let mut path = Vec::new();
+    while let Some(spanned) = tokens.get(*i) {
+        match spanned.token {
+            Token::Punct(Punctuation::Slash) => {}
+            Token::Ident(ref name, _) => {
+                path.push(name.clone());
+                end = end_of(spanned);
+            }
+            _ => break,
+        }
+        *i += 1;
+    }
+    if path.is_empty() {
+        return None;
+    }
+
+    let mut vars = Vec::new();
+    if tokens.get(*i).is_some_and(|t| t.token == Token::Punct(Punctuation::LBrace)) {
+        *i += 1;
+        let mut depth = 0;
+        let mut current: Option<(VarOverride, usize)> = None;
+        while let Some(spanned) = tokens.get(*i) {
+            *i += 1;
+            match spanned.token {
+                Token::Punct(Punctuation::LParen | Punctuation::LBracket | Punctuation::LBrace) => depth += 1,
+                Token::Punct(Punctuation::RParen | Punctuation::RBracket) => depth -= 1,
+                Token::Punct(Punctuation::RBrace) if depth > 0 => depth -= 1,
+                Token::Punct(Punctuation::RBrace | Punctuation::Semicolon) => {
+                    if let Some((mut var, value_start)) = current.take() {
+                        var.value = text[value_start..tokens[*i - 2].bytes.end].trim().to_owned();
+                        vars.push(var);
+                    }
+                    if spanned.token == Token::Punct(Punctuation::RBrace) {
+                        break;
+                    }
+                }
+                Token::Ident(ref name, _)
+                    if current.is_none()
+                        && depth == 0
+                        && tokens.get(*i).is_some_and(|t| t.token == Token::Punct(Punctuation::Assign)) =>
+                {
+                    let var = VarOverride {
+                        name: name.clone(),
+                        span: spanned.location..end_of(spanned),
+                        value: String::new(),
+                    };
+                    current = Some((var, tokens[*i].bytes.end));
+                    *i += 1;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Some(Prefab { path, span: start..end, vars })
+}
+
+fn end_of(spanned: &Spanned) -> Location {
+    let mut end = spanned.location;
+    if let Token::Ident(ref name, _) = spanned.token {
+        end.column += name.len() as u16;
+    }
+    end
+}
+
+/// Find the prefab or var override at a location.
+pub fn symbol_at(prefabs: &[Prefab], location: Location) -> Option<Symbol<'_>> {
+    let contains = |span: &Range<Location>| span.start <= location && location <= span.end;
+    for prefab in prefabs {
+        if contains(&prefab.span) {
+            return Some(Symbol::Prefab(prefab));
+        }
+        if let Some(var) = prefab.vars.iter().find(|var| contains(&var.span)) {
+            return Some(Symbol::Var(prefab, var));
+        }
+    }
+    None
+}
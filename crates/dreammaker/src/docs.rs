@@ -28,6 +28,31 @@ impl DocCollection {
         self.elems.iter().all(|c| c.is_empty())
     }
 
+    /// Find the text of a `@deprecated` tag, which usually names a
+    /// replacement and may be empty.
+    pub fn deprecated(&self) -> Option<String> {
+        self.tag("@deprecated")
+    }
+
+    /// Find the text of a `@category` tag, which groups related items in
+    /// generated documentation.
+    pub fn category(&self) -> Option<String> {
+        self.tag("@category").filter(|category| !category.is_empty())
+    }
+
+    fn tag(&self, tag: &str) -> Option<String> {
+        if !self.elems.iter().any(|comment| comment.text.contains(tag)) {
+            return None;
+        }
+        self.text().lines().find_map(|line| {
+            let rest = line.trim().strip_prefix(tag)?;
+            if rest.starts_with(|ch: char| !ch.is_whitespace()) {
+                return None;
+            }
+            Some(rest.trim().to_owned())
+        })
+    }
+
     /// Render this collection to a single Markdown document.
     pub fn text(&self) -> String {
         let mut output = String::new();
@@ -10,6 +10,7 @@ use get_size_derive::GetSize;
 use termcolor::{Color, ColorSpec};
 
 use crate::config::Config;
+use crate::includes::IncludeGraph;
 
 /// An identifier referring to a loaded file.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -46,10 +47,22 @@ pub struct Context {
     /// Warning config
     config: Config,
     print_severity: Option<Severity>,
+    /// Lines on which `SDMM: ignore(...)` comments suppress diagnostics.
+    suppressions: RefCell<HashMap<FileId, Vec<Suppression>>>,
+    /// The `#include`s of the environment being read.
+    include_graph: RefCell<IncludeGraph>,
 
     io_time: std::cell::Cell<std::time::Duration>,
 }
 
+/// The lines covered by a suppression comment, and what it suppresses.
+#[derive(Debug, Clone)]
+struct Suppression {
+    start: u32,
+    end: u32,
+    errortypes: Vec<&'static str>,
+}
+
 impl FileList {
     /// Add a new file to the context and return its index.
     pub fn register(&self, path: &Path) -> FileId {
@@ -121,6 +134,16 @@ impl Context {
         &self.files
     }
 
+    /// The `#include`s of the environment most recently read with this
+    /// context.
+    pub fn include_graph(&self) -> Ref<'_, IncludeGraph> {
+        self.include_graph.borrow()
+    }
+
+    pub(crate) fn include_graph_mut(&self) -> RefMut<'_, IncludeGraph> {
+        self.include_graph.borrow_mut()
+    }
+
     // ------------------------------------------------------------------------
     // Configuration
 
@@ -137,6 +160,11 @@ impl Context {
         }
     }
 
+    /// Forget any loaded configuration, returning to the defaults.
+    pub fn reset_config(&mut self) {
+        self.config = Config::default();
+    }
+
     pub fn autodetect_config(&mut self, dme: &Path) {
         let toml = dme.parent().unwrap().join("SpacemanDMM.toml");
         if toml.exists() {
@@ -148,6 +176,12 @@ impl Context {
         &self.config
     }
 
+    /// Define a macro before the environment is read, in addition to and
+    /// replacing those in the configuration.
+    pub fn add_define(&mut self, name: String, value: String) {
+        self.config.defines.insert(name, value);
+    }
+
     /// Set a severity at and above which errors will be printed immediately.
     pub fn set_print_severity(&mut self, print_severity: Option<Severity>) {
         self.print_severity = print_severity;
@@ -168,14 +202,64 @@ impl Context {
         self.io_time.get()
     }
 
+    // ------------------------------------------------------------------------
+    // Suppression comments
+
+    /// Forget the suppression comments of a file which is being read again.
+    pub fn clear_suppressions(&self, file: FileId) {
+        self.suppressions.borrow_mut().remove(&file);
+    }
+
+    /// Record a suppression comment. One which follows code on its line
+    /// covers that line, and otherwise it covers the line after it.
+    pub fn add_suppression(&self, location: Location, trailing: bool, errortypes: Vec<&'static str>) {
+        let line = if trailing { location.line } else { location.line + 1 };
+        self.suppressions.borrow_mut().entry(location.file).or_default().push(Suppression {
+            start: line,
+            end: line,
+            errortypes,
+        });
+    }
+
+    /// Extend suppressions covering the first line of a statement or
+    /// declaration to the rest of it.
+    pub fn extend_suppressions(&self, start: Location, end: Location) {
+        if start.file != end.file {
+            return;
+        }
+        if let Some(list) = self.suppressions.borrow_mut().get_mut(&start.file) {
+            for suppression in list.iter_mut().filter(|each| each.start == start.line) {
+                suppression.end = suppression.end.max(end.line);
+            }
+        }
+    }
+
+    fn is_suppressed(&self, error: &DMError) -> bool {
+        let Some(errortype) = error.errortype() else { return false };
+        let suppressions = self.suppressions.borrow();
+        let Some(list) = suppressions.get(&error.location.file) else { return false };
+        list.iter().any(|each| {
+            (each.start..=each.end).contains(&error.location.line) && each.errortypes.contains(&errortype)
+        })
+    }
+
     // ------------------------------------------------------------------------
     // Errors
 
     /// Push an error or other diagnostic to the context.
     pub fn register_error(&self, error: DMError) {
-        let Some(error) = self.config.set_configured_severity(error) else {
+        let error = if self.config.has_path_diagnostics() {
+            let path = self.file_path(error.location.file).to_owned();
+            self.config.set_configured_severity_at(error, &path)
+        } else {
+            self.config.set_configured_severity(error)
+        };
+        let Some(error) = error else {
             return // errortype is disabled
         };
+        if self.is_suppressed(&error) {
+            return
+        }
         // ignore errors with severity above configured level
         if !self.config.registerable_error(&error) {
             return
@@ -214,7 +298,10 @@ impl Context {
         w.set_color(&error.severity.style())?;
         write!(w, "{}", error.severity())?;
         w.reset()?;
-        writeln!(w, ": {}", error.description())?;
+        match error.code() {
+            Some(code) => writeln!(w, ": {} [{}]", error.description(), code)?,
+            None => writeln!(w, ": {}", error.description())?,
+        }
 
         for note in error.notes().iter() {
             if note.location == error.location {
@@ -275,7 +362,7 @@ impl Context {
 // Location handling
 
 /// File, line, and column information for an error.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Default, GetSize)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Default, GetSize)]
 pub struct Location {
     /// The index into the file table.
     pub file: FileId,
@@ -328,13 +415,16 @@ impl Location {
 
 /// A trait for types which may yield location information.
 pub(crate) trait HasLocation {
+    /// The component which errors from this parsing stage belong to.
+    const COMPONENT: Component;
+
     /// Get the current location of this parsing stage.
     fn location(&self) -> Location;
 
     #[inline]
     #[doc(hidden)]
     fn error<S: Into<String>>(&self, message: S) -> DMError {
-        DMError::new(self.location(), message)
+        DMError::new(self.location(), message).with_component(Self::COMPONENT)
     }
 }
 
@@ -382,6 +472,11 @@ impl fmt::Display for Severity {
 pub enum Component {
     #[default]
     Unspecified,
+    Lexer,
+    Preprocessor,
+    Parser,
+    ObjectTree,
+    Constants,
     DreamChecker,
 }
 
@@ -389,6 +484,11 @@ impl Component {
     pub fn name(self) -> Option<&'static str> {
         match self {
             Component::Unspecified => None,
+            Component::Lexer => Some("lexer"),
+            Component::Preprocessor => Some("preprocessor"),
+            Component::Parser => Some("parser"),
+            Component::ObjectTree => Some("objtree"),
+            Component::Constants => Some("constants"),
             Component::DreamChecker => Some("dreamchecker"),
         }
     }
@@ -408,6 +508,7 @@ impl fmt::Display for Component {
 #[must_use]
 pub struct DMError {
     location: Location,
+    end: Option<Location>,
     severity: Severity,
     component: Component,
     description: String,
@@ -428,6 +529,7 @@ impl DMError {
     pub fn new<S: Into<String>>(location: Location, desc: S) -> DMError {
         DMError {
             location,
+            end: None,
             severity: Default::default(),
             component: Default::default(),
             description: desc.into(),
@@ -479,6 +581,12 @@ impl DMError {
         self
     }
 
+    /// Extend this error to cover the code up to the given location.
+    pub fn with_end(mut self, end: Location) -> DMError {
+        self.end = Some(end);
+        self
+    }
+
     #[inline]
     pub fn register(self, context: &Context) {
         context.register_error(self)
@@ -489,6 +597,11 @@ impl DMError {
         self.location
     }
 
+    /// Get the location at which the code this error covers ends, if known.
+    pub fn end(&self) -> Option<Location> {
+        self.end
+    }
+
     /// Get the severity of this diagnostic.
     pub fn severity(&self) -> Severity {
         self.severity
@@ -509,6 +622,11 @@ impl DMError {
         self.errortype
     }
 
+    /// Get the stable code of this error's errortype, if it has one.
+    pub fn code(&self) -> Option<&'static str> {
+        self.errortype.and_then(crate::codes::code_for)
+    }
+
     /// Get the additional notes associated with this error.
     pub fn notes(&self) -> &[DiagnosticNote] {
         &self.notes
@@ -550,6 +668,7 @@ impl Clone for DMError {
     fn clone(&self) -> DMError {
         DMError {
             location: self.location,
+            end: self.end,
             severity: self.severity,
             component: self.component,
             description: self.description.clone(),
@@ -0,0 +1,366 @@
+//! A structured export of the object tree, for tools outside SpacemanDMM.
+//!
+//! The export serializes to a stable JSON document. Types are sorted by path,
+//! and vars, procs, and children are sorted by name, so the same environment
+//! always produces the same output. Two trees can also be compared, which
+//! serializes the same way.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::Serialize;
+
+use super::constants::Constant;
+use super::intern::Interned;
+use super::objtree::{ObjectTree, TypeRef};
+use super::{Context, Location};
+
+/// The version of the export format. Incremented whenever a field is
+/// removed or changes meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Options controlling what is exported.
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Only export this type and its subtypes, such as `/obj/item`.
+    pub prefix: Option<String>,
+    /// Leave out the folded values of vars.
+    pub skip_constants: bool,
+}
+
+/// The exported object tree.
+#[derive(Debug, Serialize)]
+pub struct TreeExport {
+    pub schema_version: u32,
+    /// Every exported type, sorted by path. The root, which holds global vars
+    /// and procs, has the empty path.
+    pub types: Vec<TypeExport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeExport {
+    pub path: String,
+    /// The `parent_type`, or `None` for the root.
+    pub parent: Option<String>,
+    /// Where the type was first seen.
+    pub location: LocationExport,
+    /// The paths of the immediate subtypes, sorted.
+    pub children: Vec<String>,
+    /// Vars which this type declares or overrides.
+    pub vars: BTreeMap<String, VarExport>,
+    /// Procs and verbs which this type declares or overrides.
+    pub procs: BTreeMap<String, ProcExport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LocationExport {
+    /// The file path, or `(builtins)` for things BYOND provides.
+    pub file: String,
+    pub line: u32,
+    pub column: u16,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VarExport {
+    /// Whether this type declares the var, rather than overriding it.
+    pub declared: bool,
+    /// The declared type path, such as `/obj/item`, if there is one.
+    pub type_path: Option<String>,
+    /// Declaration flags, such as `const` and `tmp`.
+    pub flags: Vec<&'static str>,
+    pub location: LocationExport,
+    /// The folded value as DM source, such as `"text"` or `/obj/item`, if
+    /// it could be determined.
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProcExport {
+    /// `proc` or `verb` if this type declares it, or `None` for overrides.
+    pub kind: Option<String>,
+    /// The return type given with `as`, if any.
+    pub return_type: Option<String>,
+    /// Each definition on this type, in order.
+    pub definitions: Vec<ProcDefinitionExport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProcDefinitionExport {
+    pub location: LocationExport,
+    /// Each parameter as DM source, such as `mob/user as mob`.
+    pub parameters: Vec<String>,
+}
+
+/// Export the object tree.
+pub fn export_object_tree(context: &Context, tree: &ObjectTree, options: &ExportOptions) -> TreeExport {
+    let prefix = options.prefix.as_deref().map(|prefix| prefix.trim_end_matches('/'));
+    let mut types: Vec<TypeExport> = tree.iter_types()
+        .filter(|ty| prefix.is_none_or(|prefix| is_within(&ty.path, prefix)))
+        .map(|ty| export_type(context, ty, options))
+        .collect();
+    types.sort_by(|a, b| a.path.cmp(&b.path));
+    TreeExport {
+        schema_version: SCHEMA_VERSION,
+        types,
+    }
+}
+
+fn is_within(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn export_type(context: &Context, ty: TypeRef, options: &ExportOptions) -> TypeExport {
+    let mut children: Vec<String> = ty.children().map(|child| child.path.to_string()).collect();
+    children.sort();
+
+    let vars = ty.vars.iter().map(|(name, var)| {
+        let export = VarExport {
+            declared: var.declaration.is_some(),
+            type_path: var.declaration.as_ref()
+                .filter(|decl| !decl.var_type.type_path.is_empty())
+                .map(|decl| decl.var_type.type_path.iter().map(|each| format!("/{}", each)).collect()),
+            flags: var.declaration.as_ref().map_or_else(Vec::new, |decl| decl.var_type.flags.to_vec()),
+            location: export_location(context, var.value.location),
+            value: var.value.constant.as_ref()
+                .filter(|_| !options.skip_constants)
+                .map(ToString::to_string),
+        };
+        (name.to_string(), export)
+    }).collect();
+
+    let procs = ty.procs.iter().map(|(name, proc)| {
+        let export = ProcExport {
+            kind: proc.declaration.as_ref().map(|decl| decl.kind.to_string()),
+            return_type: proc.declaration.as_ref()
+                .filter(|decl| !decl.return_type.is_empty())
+                .map(|decl| decl.return_type.to_string()),
+            definitions: proc.value.iter().map(|value| ProcDefinitionExport {
+                location: export_location(context, value.location),
+                parameters: value.parameters.iter().map(ToString::to_string).collect(),
+            }).collect(),
+        };
+        (name.to_string(), export)
+    }).collect();
+
+    TypeExport {
+        path: ty.path.to_string(),
+        parent: ty.parent_type().map(|parent| parent.path.to_string()),
+        location: export_location(context, ty.location),
+        children,
+        vars,
+        procs,
+    }
+}
+
+fn export_location(context: &Context, location: Location) -> LocationExport {
+    LocationExport {
+        file: context.file_path(location.file).display().to_string(),
+        line: location.line,
+        column: location.column,
+    }
+}
+
+// ----------------------------------------------------------------------------
+// Differences between two trees
+
+/// The differences between two object trees, such as an environment before
+/// and after a change.
+#[derive(Debug, Serialize)]
+pub struct TreeDiff {
+    pub schema_version: u32,
+    /// Types only in the new tree, sorted.
+    pub added_types: Vec<String>,
+    /// Types only in the old tree, sorted.
+    pub removed_types: Vec<String>,
+    /// Types in both trees which differ, sorted by path.
+    pub changed_types: Vec<TypeDiff>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeDiff {
+    pub path: String,
+    /// The old and new `parent_type`, if it changed.
+    pub parent: Option<Change<String>>,
+    /// Vars newly declared on this type.
+    pub added_vars: Vec<String>,
+    /// Vars no longer declared on this type.
+    pub removed_vars: Vec<String>,
+    /// Vars whose folded value on this type changed, including values
+    /// inherited from parent types. Values are DM source, or `None` if they
+    /// could not be determined.
+    pub changed_vars: BTreeMap<String, Change<Option<String>>>,
+    /// Procs newly declared or overridden on this type.
+    pub added_procs: Vec<String>,
+    /// Procs no longer declared or overridden on this type.
+    pub removed_procs: Vec<String>,
+    /// Procs on this type whose parameters changed.
+    pub changed_procs: BTreeMap<String, Change<Vec<String>>>,
+}
+
+/// A value in the old tree and the new tree.
+#[derive(Debug, Serialize)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+impl TypeDiff {
+    fn is_empty(&self) -> bool {
+        self.parent.is_none()
+            && self.added_vars.is_empty()
+            && self.removed_vars.is_empty()
+            && self.changed_vars.is_empty()
+            && self.added_procs.is_empty()
+            && self.removed_procs.is_empty()
+            && self.changed_procs.is_empty()
+    }
+}
+
+/// Compare two object trees.
+///
+/// Both trees should have had their constants evaluated, as vars are
+/// compared by their folded values rather than by their source.
+pub fn diff_object_trees(old: &ObjectTree, new: &ObjectTree, options: &ExportOptions) -> TreeDiff {
+    let prefix = options.prefix.as_deref().map(|prefix| prefix.trim_end_matches('/'));
+    let included = |ty: &TypeRef| prefix.is_none_or(|prefix| is_within(&ty.path, prefix));
+
+    let mut diff = TreeDiff {
+        schema_version: SCHEMA_VERSION,
+        added_types: Vec::new(),
+        removed_types: Vec::new(),
+        changed_types: Vec::new(),
+    };
+    for old_ty in old.iter_types().filter(included) {
+        match new.find(&old_ty.path) {
+            Some(new_ty) => {
+                let type_diff = diff_type(old_ty, new_ty, options);
+                if !type_diff.is_empty() {
+                    diff.changed_types.push(type_diff);
+                }
+            }
+            None => diff.removed_types.push(old_ty.path.to_string()),
+        }
+    }
+    for new_ty in new.iter_types().filter(included) {
+        if old.find(&new_ty.path).is_none() {
+            diff.added_types.push(new_ty.path.to_string());
+        }
+    }
+    diff.added_types.sort();
+    diff.removed_types.sort();
+    diff.changed_types.sort_by(|a, b| a.path.cmp(&b.path));
+    diff
+}
+
+fn diff_type(old: TypeRef, new: TypeRef, options: &ExportOptions) -> TypeDiff {
+    let old_parent = old.parent_type().map(|parent| parent.path.to_string());
+    let new_parent = new.parent_type().map(|parent| parent.path.to_string());
+
+    let declared = |ty: TypeRef| -> BTreeSet<String> {
+        ty.vars.iter()
+            .filter(|(_, var)| var.declaration.is_some())
+            .map(|(name, _)| name.to_string())
+            .collect()
+    };
+    let old_declared = declared(old);
+    let new_declared = declared(new);
+
+    let mut changed_vars = BTreeMap::new();
+    if !options.skip_constants {
+        let old_visible = visible_vars(old);
+        // Reparenting is reported on its own.
+        for name in visible_vars(new).intersection(&old_visible).filter(|&&name| name != "parent_type") {
+            let old_value = old.get_value(name).and_then(|value| value.constant.as_ref());
+            let new_value = new.get_value(name).and_then(|value| value.constant.as_ref());
+            if old_value != new_value {
+                changed_vars.insert((*name).to_owned(), Change {
+                    old: old_value.map(Constant::to_string),
+                    new: new_value.map(Constant::to_string),
+                });
+            }
+        }
+    }
+
+    let mut changed_procs = BTreeMap::new();
+    for (name, new_proc) in new.procs.iter() {
+        let Some(old_proc) = old.procs.get(name) else { continue };
+        let parameters = |proc: &super::objtree::TypeProc| -> Vec<String> {
+            proc.value.last().map_or_else(Vec::new, |value| value.parameters.iter().map(ToString::to_string).collect())
+        };
+        let (old_parameters, new_parameters) = (parameters(old_proc), parameters(new_proc));
+        if old_parameters != new_parameters {
+            changed_procs.insert(name.to_string(), Change {
+                old: old_parameters,
+                new: new_parameters,
+            });
+        }
+    }
+
+    TypeDiff {
+        path: new.path.to_string(),
+        parent: (old_parent != new_parent).then(|| Change {
+            old: old_parent.unwrap_or_default(),
+            new: new_parent.unwrap_or_default(),
+        }),
+        added_vars: new_declared.difference(&old_declared).cloned().collect(),
+        removed_vars: old_declared.difference(&new_declared).cloned().collect(),
+        changed_vars,
+        added_procs: sorted_difference(new.procs.keys(), |name| !old.procs.contains_key(name)),
+        removed_procs: sorted_difference(old.procs.keys(), |name| !new.procs.contains_key(name)),
+        changed_procs,
+    }
+}
+
+/// The names of every var on a type, including inherited ones.
+fn visible_vars<'a>(ty: TypeRef<'a>) -> BTreeSet<&'a str> {
+    let mut names = BTreeSet::new();
+    let mut current = Some(ty);
+    while let Some(each) = current {
+        names.extend(each.get().vars.keys().map(Interned::as_str));
+        current = each.parent_type();
+    }
+    names
+}
+
+fn sorted_difference<'a>(names: impl Iterator<Item=&'a Interned>, keep: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut names: Vec<String> = names.filter(|name| keep(name)).map(ToString::to_string).collect();
+    names.sort();
+    names
+}
+
+impl fmt::Display for TreeDiff {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for path in self.added_types.iter() {
+            writeln!(fmt, "+ {}", path)?;
+        }
+        for path in self.removed_types.iter() {
+            writeln!(fmt, "- {}", path)?;
+        }
+        for ty in self.changed_types.iter() {
+            writeln!(fmt, "{}", if ty.path.is_empty() { "(global)" } else { &ty.path })?;
+            if let Some(ref parent) = ty.parent {
+                writeln!(fmt, "    parent_type: {} -> {}", parent.old, parent.new)?;
+            }
+            for name in ty.added_vars.iter() {
+                writeln!(fmt, "  + var/{}", name)?;
+            }
+            for name in ty.removed_vars.iter() {
+                writeln!(fmt, "  - var/{}", name)?;
+            }
+            for (name, change) in ty.changed_vars.iter() {
+                let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "(unknown)".to_owned());
+                writeln!(fmt, "    var/{}: {} -> {}", name, show(&change.old), show(&change.new))?;
+            }
+            for name in ty.added_procs.iter() {
+                writeln!(fmt, "  + proc/{}", name)?;
+            }
+            for name in ty.removed_procs.iter() {
+                writeln!(fmt, "  - proc/{}", name)?;
+            }
+            for (name, change) in ty.changed_procs.iter() {
+                writeln!(fmt, "    proc/{}({}) -> ({})", name, change.old.join(", "), change.new.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
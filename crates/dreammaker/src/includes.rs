@@ -0,0 +1,120 @@
+//! The graph of which files include which.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use super::{Context, FileId, Location};
+
+/// One `#include` directive, as seen by the preprocessor.
+#[derive(Debug, Clone)]
+pub struct IncludeEdge {
+    /// The location of the path in the directive. Its file is the including
+    /// file.
+    pub location: Location,
+    /// The path as written in the directive.
+    pub written: String,
+    /// The file which was found, relative to the environment's directory if
+    /// it is inside it, or `None` if no file was found.
+    pub path: Option<PathBuf>,
+    /// The included code file, for `.dm` and `.dme` files which were read.
+    pub file: Option<FileId>,
+}
+
+/// Every `#include` in an environment, in the order the preprocessor saw
+/// them.
+///
+/// Files included more than once have an edge for each `#include`, but only
+/// the first one was read.
+#[derive(Debug, Clone, Default)]
+pub struct IncludeGraph {
+    root: Option<FileId>,
+    edges: Vec<IncludeEdge>,
+}
+
+impl IncludeGraph {
+    pub(crate) fn new(root: FileId) -> IncludeGraph {
+        IncludeGraph {
+            root: Some(root),
+            edges: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, edge: IncludeEdge) {
+        self.edges.push(edge);
+    }
+
+    /// The `.dme` file the environment was read from.
+    pub fn root(&self) -> Option<FileId> {
+        self.root
+    }
+
+    /// Every `#include`, in order.
+    pub fn edges(&self) -> &[IncludeEdge] {
+        &self.edges
+    }
+
+    /// The `#include`s written in a file, in order.
+    pub fn includes_from(&self, file: FileId) -> impl Iterator<Item=&IncludeEdge> {
+        self.edges.iter().filter(move |edge| edge.location.file == file)
+    }
+
+    /// The `#include` which read a file.
+    pub fn included_by(&self, file: FileId) -> Option<&IncludeEdge> {
+        self.edges.iter().find(|edge| edge.file == Some(file))
+    }
+
+    /// Whether a file is the root of the environment or was included by it.
+    pub fn contains(&self, file: FileId) -> bool {
+        self.root == Some(file) || self.included_by(file).is_some()
+    }
+
+    /// The `#include`s leading from the root to a file, innermost first.
+    pub fn include_chain(&self, file: FileId) -> Vec<&IncludeEdge> {
+        let mut chain = Vec::new();
+        let mut file = file;
+        while let Some(edge) = self.included_by(file) {
+            if chain.iter().any(|each: &&IncludeEdge| each.location.file == edge.location.file) {
+                break;
+            }
+            chain.push(edge);
+            file = edge.location.file;
+        }
+        chain
+    }
+
+    /// Find the `.dm` files under the environment's directory which it never
+    /// includes, relative to that directory and sorted.
+    ///
+    /// Hidden directories, such as `.git`, are skipped.
+    pub fn unincluded_files(&self, context: &Context, env_dir: &Path) -> Vec<PathBuf> {
+        let included: BTreeSet<PathBuf> = self.edges.iter()
+            .filter(|edge| edge.file.is_some())
+            .filter_map(|edge| edge.path.clone())
+            .collect();
+
+        let mut found = Vec::new();
+        find_code_files(env_dir, Path::new(""), &mut found);
+        found.retain(|path| !included.contains(path)
+            && context.get_file(path).is_none_or(|file| !self.contains(file)));
+        found.sort();
+        found
+    }
+}
+
+fn find_code_files(root: &Path, relative: &Path, found: &mut Vec<PathBuf>) {
+    let dir = root.join(relative);
+    let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { &dir };
+    let Ok(entries) = dir.read_dir() else { return };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let path = relative.join(&name);
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => find_code_files(root, &path, found),
+            Ok(_) if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dm")) => found.push(path),
+            _ => {}
+        }
+    }
+}
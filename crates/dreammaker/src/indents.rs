@@ -1,8 +1,42 @@
 //! The indentation processor.
 use std::collections::VecDeque;
+use std::rc::Rc;
 
-use crate::lexer::{LocatedToken, Punctuation, Token};
-use crate::{Context, DMError, Location};
+use crate::lexer::{LocatedToken, MacroExpansion, Punctuation, Token};
+use crate::{Component, Context, DMError, Location, Severity};
+
+/// Tab widths which editors commonly display, to compare indentation with.
+const TAB_WIDTHS: [usize; 2] = [4, 8];
+
+/// The leading whitespace of a line.
+#[derive(Debug, Clone, Copy, Default)]
+struct LineIndent {
+    tabs: usize,
+    spaces: usize,
+    /// How wide the whitespace looks with each of `TAB_WIDTHS`.
+    widths: [usize; 2],
+}
+
+impl LineIndent {
+    fn push(&mut self, tab: bool) {
+        for (width, tab_width) in self.widths.iter_mut().zip(TAB_WIDTHS) {
+            *width = if tab { (*width / tab_width + 1) * tab_width } else { *width + 1 };
+        }
+        if tab {
+            self.tabs += 1;
+        } else {
+            self.spaces += 1;
+        }
+    }
+}
+
+fn compare_word(ordering: std::cmp::Ordering) -> &'static str {
+    match ordering {
+        std::cmp::Ordering::Less => "shallower than",
+        std::cmp::Ordering::Equal => "level with",
+        std::cmp::Ordering::Greater => "deeper than",
+    }
+}
 
 /// Eliminates blank lines, parses and validates indentation, braces, and semicolons.
 ///
@@ -12,6 +46,9 @@ pub struct IndentProcessor<'ctx, I> {
     inner: I,
 
     last_input_loc: Location,
+    last_input_body: Option<Location>,
+    last_input_expansion: Option<Rc<MacroExpansion>>,
+    last_input_end: Location,
     eol_location: Option<Location>,
     output: VecDeque<LocatedToken>,
 
@@ -19,6 +56,9 @@ pub struct IndentProcessor<'ctx, I> {
     current: Option<(usize, usize)>,
     // The number of spaces/tabs accumulated on the current line. None when not at line head.
     current_spaces: Option<usize>,
+    // The same whitespace, told apart, and that of the last line measured.
+    current_indent: LineIndent,
+    previous_indent: Option<(LineIndent, Location)>,
     parentheses: usize,
     eof_yielded: bool,
 }
@@ -31,10 +71,15 @@ impl<'ctx, I> IndentProcessor<'ctx, I> where
             context,
             inner: inner.into_iter(),
             last_input_loc: Location::default(),
+            last_input_body: None,
+            last_input_expansion: None,
+            last_input_end: Location::default(),
             eol_location: None,
             output: VecDeque::new(),
             current: None,
             current_spaces: None,
+            current_indent: LineIndent::default(),
+            previous_indent: None,
             parentheses: 0,
             eof_yielded: false,
         }
@@ -66,6 +111,7 @@ impl<'ctx, I> IndentProcessor<'ctx, I> where
             Token::Punct(Punctuation::Newline) => {
                 if self.parentheses == 0 {
                     self.current_spaces = Some(0);
+                    self.current_indent = LineIndent::default();
                 }
                 // semicolons are placed by the first token on the next line
                 if self.eol_location.is_none() {
@@ -77,6 +123,7 @@ impl<'ctx, I> IndentProcessor<'ctx, I> where
             Token::Punct(Punctuation::Space) => {
                 if let Some(spaces) = self.current_spaces.as_mut() {
                     *spaces += 1;
+                    self.current_indent.push(read == Token::Punct(Punctuation::Tab));
                 }
                 return;
             }
@@ -94,6 +141,7 @@ impl<'ctx, I> IndentProcessor<'ctx, I> where
 
         // handle indentation
         if let Some(spaces) = self.current_spaces.take() {
+            self.check_indent_whitespace();
             let (indents, new_indents);
             match self.current {
                 None => {
@@ -119,7 +167,9 @@ impl<'ctx, I> IndentProcessor<'ctx, I> where
                             DMError::new(self.last_input_loc, format!(
                                 "inconsistent indentation: {} % {} != 0",
                                 spaces, spaces_per_indent,
-                            )).register(self.context)
+                            ))
+                                .with_component(Component::Parser)
+                                .register(self.context)
                         }
                         new_indents = spaces / spaces_per_indent;
                         self.current = Some((spaces_per_indent, new_indents));
@@ -135,7 +185,9 @@ impl<'ctx, I> IndentProcessor<'ctx, I> where
                 DMError::new(self.last_input_loc, format!(
                     "inconsistent multiple indentation: {} > 1",
                     new_indents - indents,
-                )).register(self.context);
+                ))
+                    .with_component(Component::Parser)
+                    .register(self.context);
                 for _ in indents..new_indents {
                     self.push_eol(Token::Punct(Punctuation::LBrace));
                 }
@@ -164,7 +216,9 @@ impl<'ctx, I> IndentProcessor<'ctx, I> where
             Token::Punct(Punctuation::RBrace) => {
                 self.current = match self.current {
                     None => {
-                        DMError::new(self.last_input_loc, "unmatched right brace").register(self.context);
+                        DMError::new(self.last_input_loc, "unmatched right brace")
+                            .with_component(Component::Parser)
+                            .register(self.context);
                         None
                     }
                     Some((_, 1)) => None,
@@ -181,7 +235,52 @@ impl<'ctx, I> IndentProcessor<'ctx, I> where
         }
 
         self.eol_location = None;
-        self.push(read);
+        self.output.push_back(LocatedToken {
+            location: self.last_input_loc,
+            token: read,
+            body_location: self.last_input_body,
+            expansion: self.last_input_expansion.clone(),
+            end: self.last_input_end,
+        });
+    }
+}
+
+impl<'ctx, I> IndentProcessor<'ctx, I> {
+    /// Report leading whitespace which mixes tabs and spaces, and lines
+    /// whose nesting relative to the line before looks different than it
+    /// parses, as DM counts a tab the same as a space.
+    fn check_indent_whitespace(&mut self) {
+        let indent = self.current_indent;
+        let location = self.last_input_loc;
+        if indent.tabs > 0 && indent.spaces > 0 {
+            DMError::new(location, "indentation mixes tabs and spaces")
+                .with_component(Component::Parser)
+                .set_severity(Severity::Warning)
+                .with_errortype("mixed_indentation")
+                .register(self.context);
+        }
+
+        if let Some((previous, previous_location)) = self.previous_indent {
+            let parsed = (indent.tabs + indent.spaces).cmp(&(previous.tabs + previous.spaces));
+            let apparent = TAB_WIDTHS.iter().zip(indent.widths.iter().zip(previous.widths))
+                .map(|(&tab_width, (&width, previous_width))| (tab_width, width.cmp(&previous_width)))
+                .find(|&(_, apparent)| apparent != parsed);
+            if let Some((tab_width, apparent)) = apparent {
+                DMError::new(location, format!(
+                    "indentation looks {} the previous line with {}-column tabs, but is parsed as {} it",
+                    compare_word(apparent), tab_width, compare_word(parsed),
+                ))
+                    .with_component(Component::Parser)
+                    .set_severity(Severity::Warning)
+                    .with_errortype("indentation_mismatch")
+                    .with_note(previous_location, format!(
+                        "previous line is indented with {} tabs and {} spaces, this one with {} tabs and {} spaces",
+                        previous.tabs, previous.spaces, indent.tabs, indent.spaces,
+                    ))
+                    .register(self.context);
+            }
+        }
+        self.previous_indent = Some((indent, location));
     }
 }
 
@@ -198,6 +297,9 @@ impl<'ctx, I> Iterator for IndentProcessor<'ctx, I> where
 
             if let Some(tok) = self.inner_next() {
                 self.last_input_loc = tok.location;
+                self.last_input_body = tok.body_location;
+                self.last_input_expansion = tok.expansion;
+                self.last_input_end = tok.end;
                 self.real_next(tok.token);
             } else if self.eof_yielded {
                 return None;
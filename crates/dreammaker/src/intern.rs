@@ -0,0 +1,221 @@
+//! Interned strings, for names which are repeated throughout the object tree.
+//!
+//! Every distinct string is stored once, and an `Interned` is a
+//! reference-counted pointer to it. Two `Interned`s are equal exactly when
+//! they point to the same storage, which makes comparing them as cheap as
+//! comparing integers.
+//!
+//! Strings nothing points to any more are forgotten as more are interned, so
+//! a long-running process such as the language server, which builds many
+//! object trees, only keeps the strings of those still alive.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use get_size::GetSize;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A string stored once in the global interner.
+///
+/// Dereferences to `str`, and hashes the same way, so maps keyed by
+/// `Interned` can be looked up by `&str`.
+#[derive(Clone)]
+pub struct Interned(Arc<str>);
+
+/// The interner is never swept while it holds fewer strings than this.
+const MIN_SWEEP: usize = 1 << 16;
+
+#[derive(Default)]
+struct Interner {
+    strings: HashSet<Arc<str>>,
+    /// How many strings to hold before the next sweep.
+    sweep_at: usize,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+/// Intern a string, returning the shared copy of it.
+pub fn intern(s: &str) -> Interned {
+    let mut interner = interner().lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(existing) = interner.strings.get(s) {
+        return Interned(existing.clone());
+    }
+    if interner.strings.len() >= interner.sweep_at {
+        // Forget the strings only the interner still holds. Sweeping again
+        // only once the survivors have doubled keeps this cheap overall.
+        interner.strings.retain(|each| Arc::strong_count(each) > 1);
+        interner.sweep_at = (interner.strings.len() * 2).max(MIN_SWEEP);
+    }
+    let string: Arc<str> = Arc::from(s);
+    interner.strings.insert(string.clone());
+    Interned(string)
+}
+
+/// The number of distinct strings the interner currently holds.
+pub fn interned_count() -> usize {
+    interner().lock().unwrap_or_else(|poisoned| poisoned.into_inner()).strings.len()
+}
+
+impl Interned {
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for Interned {
+    fn default() -> Self {
+        intern("")
+    }
+}
+
+impl Deref for Interned {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Interned {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Interned {
+    #[inline]
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Interned {
+    fn from(s: &str) -> Self {
+        intern(s)
+    }
+}
+
+impl From<&String> for Interned {
+    fn from(s: &String) -> Self {
+        intern(s)
+    }
+}
+
+impl From<String> for Interned {
+    fn from(s: String) -> Self {
+        intern(&s)
+    }
+}
+
+impl From<Interned> for String {
+    fn from(s: Interned) -> Self {
+        s.0.to_string()
+    }
+}
+
+impl PartialEq for Interned {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Interned {}
+
+impl PartialEq<str> for Interned {
+    fn eq(&self, other: &str) -> bool {
+        *self.0 == *other
+    }
+}
+
+impl PartialEq<&str> for Interned {
+    fn eq(&self, other: &&str) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<String> for Interned {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<Interned> for str {
+    fn eq(&self, other: &Interned) -> bool {
+        *self == *other.0
+    }
+}
+
+impl PartialEq<Interned> for &str {
+    fn eq(&self, other: &Interned) -> bool {
+        **self == *other.0
+    }
+}
+
+impl PartialEq<Interned> for String {
+    fn eq(&self, other: &Interned) -> bool {
+        **self == *other.0
+    }
+}
+
+impl Hash for Interned {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialOrd for Interned {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Interned {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            Ordering::Equal
+        } else {
+            self.0.cmp(&other.0)
+        }
+    }
+}
+
+impl fmt::Display for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Debug for Interned {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+/// The string itself is shared, so only the pointer counts against whatever
+/// holds an `Interned`.
+impl GetSize for Interned {}
+
+impl Serialize for Interned {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Interned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(intern(&String::deserialize(deserializer)?))
+    }
+}
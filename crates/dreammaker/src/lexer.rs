@@ -2,11 +2,13 @@
 use std::borrow::Cow;
 use std::fmt;
 use std::io::Read;
+use std::rc::Rc;
 use std::str::FromStr;
 
 use super::ast::Ident;
 use super::docs::*;
-use super::{Context, DMError, FileId, HasLocation, Location, Severity};
+use super::intern::Interned;
+use super::{Component, Context, DMError, FileId, HasLocation, Location, Severity};
 
 macro_rules! table {
     (
@@ -438,12 +440,39 @@ impl fmt::Display for FormatFloat {
 pub struct LocatedToken {
     pub location: Location,
     pub token: Token,
+    /// Where in a macro's body this token was written, if it was produced by
+    /// expanding one. The token's `location` is where the macro was used.
+    pub body_location: Option<Location>,
+    /// The macro expansion which produced this token, if any.
+    pub expansion: Option<Rc<MacroExpansion>>,
+    /// Where the token ends, exclusive. Tokens produced by expanding a macro
+    /// end where the macro's use does.
+    pub end: Location,
 }
 
 impl LocatedToken {
     #[inline]
     pub fn new(location: Location, token: Token) -> LocatedToken {
-        LocatedToken { location, token }
+        LocatedToken { location, token, body_location: None, expansion: None, end: location }
+    }
+}
+
+/// A use of a macro, which the tokens of its body were produced by.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MacroExpansion {
+    pub name: Interned,
+    /// Where the macro was used.
+    pub invocation: Location,
+    /// Where the macro was defined.
+    pub definition: Location,
+    /// The expansion which the macro's use was itself produced by, if any.
+    pub parent: Option<Rc<MacroExpansion>>,
+}
+
+impl MacroExpansion {
+    /// This expansion and those it is nested in, innermost first.
+    pub fn chain(&self) -> impl Iterator<Item=&MacroExpansion> {
+        std::iter::successors(Some(self), |each| each.parent.as_deref())
     }
 }
 
@@ -480,6 +509,118 @@ pub fn from_utf8_or_latin1_borrowed(bytes: &[u8]) -> Cow<str> {
     }
 }
 
+/// Where a text macro takes the embedded expression it applies to from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMacroArgument {
+    /// Standalone, like `\improper`.
+    None,
+    /// The expression after it, like `\the [src]`.
+    Following,
+    /// The expression before it, like `[count]\th`.
+    Preceding,
+    /// The expression before it if there is one, and otherwise the one after
+    /// it, like `[src] scratches \his head`.
+    Either,
+}
+
+/// The text macros, such as `\the` in `"\the [src]"`. A macro is the
+/// longest of these which the letters after a backslash start with.
+pub const TEXT_MACROS: &[(&str, TextMacroArgument)] = &[
+    ("the", TextMacroArgument::Following),
+    ("The", TextMacroArgument::Following),
+    ("a", TextMacroArgument::Following),
+    ("an", TextMacroArgument::Following),
+    ("A", TextMacroArgument::Following),
+    ("An", TextMacroArgument::Following),
+    ("he", TextMacroArgument::Either),
+    ("He", TextMacroArgument::Either),
+    ("she", TextMacroArgument::Either),
+    ("She", TextMacroArgument::Either),
+    ("his", TextMacroArgument::Either),
+    ("His", TextMacroArgument::Either),
+    ("him", TextMacroArgument::Either),
+    ("Him", TextMacroArgument::Either),
+    ("her", TextMacroArgument::Either),
+    ("Her", TextMacroArgument::Either),
+    ("hers", TextMacroArgument::Either),
+    ("Hers", TextMacroArgument::Either),
+    ("himself", TextMacroArgument::Either),
+    ("Himself", TextMacroArgument::Either),
+    ("herself", TextMacroArgument::Either),
+    ("Herself", TextMacroArgument::Either),
+    ("ref", TextMacroArgument::Following),
+    ("icon", TextMacroArgument::Following),
+    ("roman", TextMacroArgument::Following),
+    ("Roman", TextMacroArgument::Following),
+    ("th", TextMacroArgument::Preceding),
+    ("s", TextMacroArgument::Preceding),
+    ("proper", TextMacroArgument::None),
+    ("improper", TextMacroArgument::None),
+    ("n", TextMacroArgument::None),
+    ("t", TextMacroArgument::None),
+    ("red", TextMacroArgument::None),
+    ("blue", TextMacroArgument::None),
+    ("green", TextMacroArgument::None),
+    ("black", TextMacroArgument::None),
+    ("white", TextMacroArgument::None),
+    ("yellow", TextMacroArgument::None),
+    ("bold", TextMacroArgument::None),
+    ("italic", TextMacroArgument::None),
+];
+
+/// A text macro read within a string literal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextMacro {
+    /// The location of the backslash.
+    pub location: Location,
+    pub name: &'static str,
+}
+
+impl TextMacro {
+    /// The location just past the macro's name.
+    pub fn end(&self) -> Location {
+        self.location.add_columns(1 + self.name.len() as u16)
+    }
+}
+
+fn text_macro_argument(name: &str) -> TextMacroArgument {
+    TEXT_MACROS.iter()
+        .find(|(each, _)| *each == name)
+        .map_or(TextMacroArgument::None, |&(_, argument)| argument)
+}
+
+/// The warning for a text macro with no embedded expression after it.
+fn missing_argument_message(name: &str) -> String {
+    match text_macro_argument(name) {
+        TextMacroArgument::Either => format!(
+            "text macro \"\\{0}\" must be next to an embedded expression, as in \"[src] \\{0}\" or \"\\{0} [src]\"",
+            name,
+        ),
+        _ => format!(
+            "text macro \"\\{0}\" must be followed by an embedded expression, as in \"\\{0} [src]\"",
+            name,
+        ),
+    }
+}
+
+// Used to track the text macro being read in a string, and whether one is
+// still waiting for its argument.
+#[derive(Default)]
+struct TextMacroState {
+    backslash: Option<Location>,
+    word: Option<(Location, String)>,
+    needs_argument: Option<TextMacro>,
+}
+
+// Used to check the first tokens of the expression given to `\ref` or
+// `\icon`.
+#[derive(Debug)]
+struct MacroArgument {
+    text_macro: TextMacro,
+    seen: usize,
+    constant: Option<&'static str>,
+}
+
 // Used to track nested string interpolations and know when they end.
 #[derive(Debug)]
 struct Interpolation {
@@ -501,7 +642,9 @@ fn buffer_read<R: Read>(file: FileId, mut read: R) -> Result<Vec<u8>, DMError> {
 
     if let Err(error) = read.read_to_end(&mut buffer) {
         let location = LocationTracker::count_location(file, &buffer);
-        return Err(DMError::new(location, "i/o error reading file").with_cause(error));
+        return Err(DMError::new(location, "i/o error reading file")
+            .with_component(Component::Lexer)
+            .with_cause(error));
     }
 
     Ok(buffer)
@@ -519,12 +662,16 @@ pub fn buffer_file(file: FileId, path: &std::path::Path) -> Result<Vec<u8>, DMEr
 
     let mut read = match std::fs::File::open(path) {
         Ok(read) => read,
-        Err(error) => return Err(DMError::new(Location { file, line: 1, column: 1 }, "i/o error opening file").with_cause(error)),
+        Err(error) => return Err(DMError::new(Location { file, line: 1, column: 1 }, "i/o error opening file")
+            .with_component(Component::Lexer)
+            .with_cause(error)),
     };
 
     if let Err(error) = read.read_to_end(&mut buffer) {
         let location = LocationTracker::count_location(file, &buffer);
-        return Err(DMError::new(location, "i/o error reading file").with_cause(error));
+        return Err(DMError::new(location, "i/o error reading file")
+            .with_component(Component::Lexer)
+            .with_cause(error));
     }
 
     Ok(buffer)
@@ -533,7 +680,8 @@ pub fn buffer_file(file: FileId, path: &std::path::Path) -> Result<Vec<u8>, DMEr
 /// A wrapper for an input stream which tracks line and column numbers.
 ///
 /// All characters, including tabs, are considered to occupy one column
-/// regardless of position.
+/// regardless of position. The `\r` of a `\r\n` line ending occupies none, so
+/// columns are the same whichever line endings a file uses.
 ///
 /// `io::Error`s are converted to `DMError`s which include the location.
 pub struct LocationTracker<'a> {
@@ -621,6 +769,8 @@ impl<'a> Iterator for LocationTracker<'a> {
 
         if ch == b'\n' {
             self.at_line_end = true;
+        } else if ch == b'\r' && self.inner.get(self.offset) == Some(&b'\n') {
+            return Some(ch);
         }
         match self.location.column.checked_add(1) {
             Some(new) => self.location.column = new,
@@ -637,9 +787,12 @@ pub struct Lexer<'ctx> {
     next: Option<u8>,
     final_newline: bool,
     at_line_head: bool,
+    line_has_token: bool,
     close_allowed: bool,
     directive: Directive,
     interp_stack: Vec<Interpolation>,
+    text_macros: Vec<TextMacro>,
+    macro_argument: Option<MacroArgument>,
 }
 
 impl<'ctx> fmt::Debug for Lexer<'ctx> {
@@ -657,6 +810,8 @@ impl<'ctx> fmt::Debug for Lexer<'ctx> {
 }
 
 impl<'ctx> HasLocation for Lexer<'ctx> {
+    const COMPONENT: Component = Component::Lexer;
+
     #[inline]
     fn location(&self) -> Location {
         self.input.location
@@ -671,14 +826,23 @@ impl<'ctx> Lexer<'ctx> {
             next: None,
             final_newline: false,
             at_line_head: true,
+            line_has_token: false,
             close_allowed: true,
             directive: Directive::None,
             interp_stack: Vec::new(),
+            text_macros: Vec::new(),
+            macro_argument: None,
         }
     }
 
+    /// The text macros in the string literals read so far.
+    pub fn text_macros(&self) -> &[TextMacro] {
+        &self.text_macros
+    }
+
     /// Create a new lexer from a byte stream.
     pub fn new<I: Into<Cow<'ctx, [u8]>>>(context: &'ctx Context, file_number: FileId, input: I) -> Self {
+        context.clear_suppressions(file_number);
         Lexer::from_input(context, LocationTracker::new(file_number, input.into()))
     }
 
@@ -702,6 +866,11 @@ impl<'ctx> Lexer<'ctx> {
         self.input.remaining()
     }
 
+    /// The byte offset just past the last token returned.
+    pub fn offset(&self) -> usize {
+        self.input.offset - self.next.is_some() as usize
+    }
+
     fn next(&mut self) -> Option<u8> {
         if let Some(next) = self.next.take() {
             return Some(next);
@@ -711,6 +880,7 @@ impl<'ctx> Lexer<'ctx> {
         let result = self.input.next();
         if self.location().line > previous_loc.line {
             self.at_line_head = true;
+            self.line_has_token = false;
             self.directive = Directive::None;
         }
         match result {
@@ -732,6 +902,7 @@ impl<'ctx> Lexer<'ctx> {
     }
 
     fn skip_block_comments(&mut self) -> Option<Token> {
+        let start = self.location();
         let mut depth = 1;
         let mut buffer = [0, 0];
         let mut comment_text = Vec::new();
@@ -778,12 +949,12 @@ impl<'ctx> Lexer<'ctx> {
         }
 
         comment.filter(|_| !comment_text.is_empty()).map(|mut c| {
-            c.text = from_utf8_or_latin1(comment_text);
+            c.text = self.decode_text(start, "doc comment", comment_text);
             Token::DocComment(c)
         })
     }
 
-    fn skip_line_comment(&mut self) -> Option<Token> {
+    fn skip_line_comment(&mut self, start: Location, trailing: bool) -> Option<Token> {
         let mut backslash = false;
 
         // read the first character and check for being a comment
@@ -801,7 +972,7 @@ impl<'ctx> Lexer<'ctx> {
         }
 
         while let Some(ch) = self.next() {
-            if ch != b'\r' && ch != b'\n' && comment.is_some() {
+            if ch != b'\r' && ch != b'\n' {
                 comment_text.push(ch);
             }
 
@@ -822,10 +993,29 @@ impl<'ctx> Lexer<'ctx> {
             }
         }
 
-        comment.map(|mut c| {
-            c.text = from_utf8_or_latin1(comment_text);
-            Token::DocComment(c)
-        })
+        match comment {
+            Some(mut c) => {
+                c.text = self.decode_text(start, "doc comment", comment_text);
+                Some(Token::DocComment(c))
+            }
+            None => {
+                self.check_suppression(start, trailing, &comment_text);
+                None
+            }
+        }
+    }
+
+    /// Record a `// SDMM: ignore(...)` comment with the context.
+    fn check_suppression(&self, start: Location, trailing: bool, comment_text: &[u8]) {
+        let Ok(text) = std::str::from_utf8(comment_text) else { return };
+        let Some((errortypes, unknown)) = crate::codes::parse_suppression(text) else { return };
+        for name in unknown {
+            DMError::new(start, format!("unknown diagnostic in suppression comment: {}", name))
+                .with_component(Component::Lexer)
+                .set_severity(Severity::Warning)
+                .register(self.context);
+        }
+        self.context.add_suppression(start, trailing, errortypes);
     }
 
     fn read_number_inner(&mut self, first: u8) -> (bool, u32, Cow<'static, str>) {
@@ -966,7 +1156,8 @@ impl<'ctx> Lexer<'ctx> {
                 Some(b'\'') => break,
                 Some(ch) => buf.push(ch),
                 None => {
-                    self.context.register_error(DMError::new(start_loc, "unterminated resource literal"));
+                    self.context.register_error(DMError::new(start_loc, "unterminated resource literal")
+                        .with_component(Component::Lexer));
                     break;
                 }
             }
@@ -980,15 +1171,21 @@ impl<'ctx> Lexer<'ctx> {
         let mut backslash = false;
         let mut idx = 0;
         let mut interp_opened = false;
+        // The text of `#warn` and `#error` has no text macros.
+        let mut text_macros = (end != b"\n").then(TextMacroState::default);
 
         loop {
             let ch = match self.next() {
                 Some(ch) => ch,
                 None => {
-                    self.context.register_error(DMError::new(start_loc, "unterminated string literal"));
+                    self.context.register_error(DMError::new(start_loc, "unterminated string literal")
+                        .with_component(Component::Lexer));
                     break;
                 }
             };
+            if let Some(state) = text_macros.as_mut() {
+                self.read_text_macro(state, ch, backslash, interp_closed);
+            }
             if ch == end[idx] && !backslash {
                 idx += 1;
                 if idx == end.len() {
@@ -1029,12 +1226,23 @@ impl<'ctx> Lexer<'ctx> {
                     interp_opened = true;
                     break;
                 }
-                b'\\' => backslash = true,
+                b'\\' => {
+                    backslash = true;
+                    if let Some(state) = text_macros.as_mut() {
+                        state.backslash = Some(self.location());
+                    }
+                }
                 ch => buf.push(ch),
             }
         }
 
-        let string = from_utf8_or_latin1(buf);
+        if let Some(text_macro) = text_macros.and_then(|state| state.needs_argument) {
+            if !interp_opened {
+                self.text_macro_error(text_macro, missing_argument_message(text_macro.name));
+            }
+        }
+
+        let string = self.decode_text(start_loc, "string", buf);
         match (interp_opened, interp_closed) {
             (true, true) => Token::InterpStringPart(string),
             (true, false) => Token::InterpStringBegin(string),
@@ -1043,14 +1251,126 @@ impl<'ctx> Lexer<'ctx> {
         }
     }
 
-    fn read_raw_string_inner(&mut self, terminator: &[u8]) -> Token {
-        let start_loc = self.location();
+    /// Follow the text macros in a string as it is read, a byte at a time.
+    fn read_text_macro(&mut self, state: &mut TextMacroState, ch: u8, backslash: bool, interp_closed: bool) {
+        if let Some((_, word)) = state.word.as_mut() {
+            if ch.is_ascii_alphabetic() {
+                word.push(ch as char);
+                return;
+            }
+        }
+        if let Some((location, word)) = state.word.take() {
+            state.needs_argument = self.finish_text_macro(location, &word, interp_closed);
+        }
+        if let Some(text_macro) = state.needs_argument.take() {
+            match ch {
+                b' ' | b'\t' | b'\r' | b'\n' if !backslash => state.needs_argument = Some(text_macro),
+                b'[' if !backslash => {
+                    if matches!(text_macro.name, "ref" | "icon") {
+                        self.macro_argument = Some(MacroArgument { text_macro, seen: 0, constant: None });
+                    }
+                }
+                // Another macro, as in `\the \improper[name]`.
+                b'\\' if !backslash => {}
+                // Pronouns may refer to any expression later in the string.
+                _ if text_macro_argument(text_macro.name) == TextMacroArgument::Either => {
+                    state.needs_argument = Some(text_macro);
+                }
+                _ => self.text_macro_error(text_macro, missing_argument_message(text_macro.name)),
+            }
+        }
+        if backslash && ch.is_ascii_alphabetic() {
+            if let Some(location) = state.backslash {
+                state.word = Some((location, String::from(ch as char)));
+            }
+        }
+    }
+
+    /// Check a text macro once the letters after its backslash are read,
+    /// returning it if it still needs the expression after it.
+    fn finish_text_macro(&mut self, location: Location, word: &str, interp_closed: bool) -> Option<TextMacro> {
+        let Some(&(name, argument)) = TEXT_MACROS.iter()
+            .filter(|(name, _)| word.starts_with(name))
+            .max_by_key(|(name, _)| name.len())
+        else {
+            DMError::new(location, format!("unknown text macro \"\\{}\"", word))
+                .with_component(Component::Lexer)
+                .with_end(location.add_columns(1 + word.len() as u16))
+                .set_severity(Severity::Warning)
+                .with_errortype("unknown_text_macro")
+                .register(self.context);
+            return None;
+        };
+        let text_macro = TextMacro { location, name };
+        self.text_macros.push(text_macro);
+        match argument {
+            TextMacroArgument::Either if interp_closed => None,
+            TextMacroArgument::Following | TextMacroArgument::Either if word.len() > name.len() => {
+                self.text_macro_error(text_macro, missing_argument_message(name));
+                None
+            }
+            TextMacroArgument::Following | TextMacroArgument::Either => Some(text_macro),
+            TextMacroArgument::Preceding if !interp_closed => {
+                self.text_macro_error(text_macro, format!(
+                    "text macro \"\\{0}\" must follow an embedded expression, as in \"[count]\\{0}\"",
+                    name,
+                ));
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn text_macro_error(&self, text_macro: TextMacro, message: String) {
+        DMError::new(text_macro.location, message)
+            .with_component(Component::Lexer)
+            .with_end(text_macro.end())
+            .set_severity(Severity::Warning)
+            .with_errortype("text_macro_argument")
+            .register(self.context);
+    }
+
+    /// Report `\ref` or `\icon` given a number, text, or null, which are
+    /// never atoms.
+    fn check_macro_argument(&mut self, mut argument: MacroArgument, token: &Token) {
+        argument.seen += 1;
+        if argument.seen == 1 {
+            argument.constant = match token {
+                Token::Int(_) | Token::Float(_) => Some("a number"),
+                Token::String(_) | Token::InterpStringBegin(_) => Some("text"),
+                Token::Ident(name, _) if name == "null" => Some("null"),
+                _ => return,
+            };
+            if self.macro_argument.is_none() {
+                self.macro_argument = Some(argument);
+            }
+        } else if let (Token::InterpStringPart(_) | Token::InterpStringEnd(_), Some(constant)) = (token, argument.constant) {
+            self.text_macro_error(argument.text_macro, format!(
+                "text macro \"\\{}\" is given {}, which is not an atom",
+                argument.text_macro.name,
+                constant,
+            ));
+        }
+    }
+
+    fn read_raw_string_inner(&mut self, start: Location, terminator: &[u8], multiline: bool) -> Token {
         let mut buf = Vec::new();
         loop {
             match self.next() {
+                // Only the heredoc forms may span lines. Leave the newline
+                // for the indentation processor rather than eating the rest
+                // of the file.
+                Some(b'\n') if !multiline => {
+                    self.put_back(Some(b'\n'));
+                    DMError::new(start, "unterminated raw string")
+                        .with_component(Component::Lexer)
+                        .register(self.context);
+                    break;
+                }
                 Some(ch) => buf.push(ch),
                 None => {
-                    DMError::new(start_loc, "unterminated raw string")
+                    DMError::new(start, "unterminated raw string")
+                        .with_component(Component::Lexer)
                         .register(self.context);
                     break;
                 }
@@ -1061,49 +1381,76 @@ impl<'ctx> Lexer<'ctx> {
                 break;
             }
         }
-        Token::String(from_utf8_or_latin1(buf))
+        Token::String(self.decode_text(start, "string", buf))
+    }
+
+    /// Decode the text of a string or doc comment, replacing whatever is not
+    /// UTF-8 rather than failing on it.
+    ///
+    /// Files saved in a legacy codepage, such as Windows-1251, are otherwise
+    /// fine, so this is only a warning.
+    fn decode_text(&self, start: Location, what: &str, bytes: Vec<u8>) -> String {
+        match String::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(error) => {
+                DMError::new(start, format!("{} is not valid UTF-8, invalid bytes were replaced", what))
+                    .with_component(Component::Lexer)
+                    .set_severity(Severity::Warning)
+                    .with_errortype("invalid_utf8")
+                    .register(self.context);
+                String::from_utf8_lossy(error.as_bytes()).into_owned()
+            }
+        }
     }
 
     fn read_raw_string(&mut self) -> Token {
         // We just got the '@'. Let's see what the next character is.
+        let start = self.location();
         match self.next() {
             // @<LF> - error
-            Some(b'\n') |
-            None => {
-                self.error("unterminated raw string").register(self.context);
+            ch @ (Some(b'\n') | None) => {
+                self.put_back(ch);
+                DMError::new(start, "unterminated raw string")
+                    .with_component(Component::Lexer)
+                    .register(self.context);
                 Token::String(String::new())
             },
-            // @(<terminator string>)<string><terminator string> - no LF in contents
+            // @(<terminator string>)<string><terminator string> - LF allowed in contents
             Some(b'(') => {
                 // build terminator until ), then read until that terminator
                 let mut terminator = Vec::new();
                 loop {
                     match self.next() {
                         Some(b')') => break,
-                        Some(ch) => terminator.push(ch),
-                        None => {
-                            self.error("unterminated raw string terminator").register(self.context);
+                        ch @ (Some(b'\n') | None) => {
+                            self.put_back(ch);
+                            DMError::new(start, "unterminated raw string terminator")
+                                .with_component(Component::Lexer)
+                                .register(self.context);
                             return Token::String(String::new())
                         }
+                        Some(ch) => terminator.push(ch),
                     }
                 }
                 if terminator.is_empty() {
-                    self.error("empty raw string terminator").register(self.context);
+                    DMError::new(start, "empty raw string terminator")
+                        .with_component(Component::Lexer)
+                        .register(self.context);
                     return Token::String(String::new())
                 }
-                self.read_raw_string_inner(&terminator)
+                self.read_raw_string_inner(start, &terminator, true)
             },
             Some(b'{') => match self.next() {
                 // @{"<string>"} - LF allowed in contents
-                Some(b'"') => self.read_raw_string_inner(b"\"}"),
+                Some(b'"') => self.read_raw_string_inner(start, b"\"}", true),
                 // @{<not ">{ -
                 other => {
                     self.put_back(other);
-                    self.read_raw_string_inner(b"{")
+                    self.read_raw_string_inner(start, b"{", false)
                 }
             },
-            // @<terminator char><string><terminator char>
-            Some(terminator) => self.read_raw_string_inner(&[terminator]),
+            // @<terminator char><string><terminator char> - no LF in contents
+            Some(terminator) => self.read_raw_string_inner(start, &[terminator], false),
         }
     }
 
@@ -1170,6 +1517,24 @@ impl<'ctx> Iterator for Lexer<'ctx> {
     type Item = LocatedToken;
 
     fn next(&mut self) -> Option<LocatedToken> {
+        // Reading the token may start another argument, after this one ends.
+        let argument = self.macro_argument.take();
+        let mut token = self.read_token()?;
+        // The byte after the token may have been read and put back.
+        token.end = if self.next.is_some() {
+            self.input.location
+        } else {
+            self.input.location.add_columns(1)
+        };
+        if let Some(argument) = argument {
+            self.check_macro_argument(argument, &token.token);
+        }
+        Some(token)
+    }
+}
+
+impl<'ctx> Lexer<'ctx> {
+    fn read_token(&mut self) -> Option<LocatedToken> {
         use self::Token::*;
         use self::Punctuation::*;
         let mut skip_newlines = false;
@@ -1183,16 +1548,18 @@ impl<'ctx> Iterator for Lexer<'ctx> {
                         self.final_newline = true;
                         let mut location = self.location();
                         location.column += 1;
-                        return Some(LocatedToken {
-                            location,
-                            token: Token::Punct(Punctuation::Newline),
-                        });
+                        return Some(LocatedToken::new(location, Token::Punct(Punctuation::Newline)));
                     } else {
                         return None;
                     }
                 }
             };
             skip_newlines = false;
+            // Indentation is lexed too, but doesn't count as code.
+            let trailing = self.line_has_token;
+            if first != b' ' && first != b'\t' {
+                self.line_has_token = true;
+            }
 
             let loc = self.location();
             let locate = |token| LocatedToken::new(loc, token);
@@ -1219,7 +1586,7 @@ impl<'ctx> Iterator for Lexer<'ctx> {
                     continue;
                 }
                 Some(LineComment) => {
-                    if let Some(t) = self.skip_line_comment() {
+                    if let Some(t) = self.skip_line_comment(loc, trailing) {
                         return Some(locate(t));
                     }
                     continue;
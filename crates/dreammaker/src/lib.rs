@@ -25,15 +25,23 @@ use indexmap::IndexMap;
 // roughly in order of stage
 pub mod annotation;
 pub mod ast;
+pub mod baseline;
 mod builtins;
+pub mod codes;
 pub mod config;
 pub mod constants;
 pub mod dmi;
+pub mod dmm;
 pub mod docs;
+pub mod export;
+pub mod includes;
 pub mod indents;
+pub mod intern;
 pub mod lexer;
 pub mod objtree;
+pub mod outline;
 pub mod parser;
+mod prefetch;
 pub mod preprocessor;
 
 impl Context {
@@ -1,6 +1,7 @@
 //! The object tree representation, used as a parsing target.
 
-use std::collections::BTreeMap;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 
 use get_size::GetSize;
@@ -17,7 +18,8 @@ use super::ast::{
 };
 use super::constants::Constant;
 use super::docs::DocCollection;
-use super::{Context, DMError, Location, Severity};
+use super::intern::{intern, Interned};
+use super::{Component, Context, DMError, FileId, Location, Severity};
 
 // ----------------------------------------------------------------------------
 // Symbol IDs
@@ -55,18 +57,24 @@ impl SymbolIdSource {
 // ----------------------------------------------------------------------------
 // Variables
 
-pub type Vars = IndexMap<String, Constant, RandomState>;
+pub type Vars = IndexMap<Interned, Constant, RandomState>;
 
 #[derive(Debug, Clone, GetSize)]
 pub struct VarDeclaration {
     pub var_type: VarType,
+    /// The location of the var's name.
     pub location: Location,
+    /// The column just past the var's name.
+    pub end_column: u16,
     pub id: SymbolId,
 }
 
 #[derive(Debug, Clone, GetSize)]
 pub struct VarValue {
+    /// The location of the var's name.
     pub location: Location,
+    /// The column just past the var's name.
+    pub end_column: u16,
     /// Syntactic value, as specified in the source.
     pub expression: Option<Expression>,
     /// Evaluated value for non-static and non-tmp vars.
@@ -83,7 +91,10 @@ pub struct TypeVar {
 
 #[derive(Debug, Clone, GetSize)]
 pub struct ProcDeclaration {
+    /// The location of the proc's name.
     pub location: Location,
+    /// The column just past the proc's name.
+    pub end_column: u16,
     pub kind: ProcDeclKind,
     // todo: tie this into our return type, add support for the funky types
     pub return_type: ProcReturnType,
@@ -93,7 +104,10 @@ pub struct ProcDeclaration {
 
 #[derive(Debug, Clone, GetSize)]
 pub struct ProcValue {
+    /// The location of the proc's name.
     pub location: Location,
+    /// The column just past the proc's name.
+    pub end_column: u16,
     pub parameters: Box<[Parameter]>,
     pub docs: DocCollection,
     pub code: Option<Block>,
@@ -115,23 +129,29 @@ impl TypeProc {
 // ----------------------------------------------------------------------------
 // Types
 
-#[derive(Debug, GetSize)]
+#[derive(Debug, Clone, GetSize)]
 pub struct Type {
-    pub path: String,
+    pub path: Interned,
     path_last_slash: usize,
+    /// The location of the last part of the type's path where it was
+    /// declared with the fewest parts.
     pub location: Location,
+    /// The column just past the last part of the type's path.
+    pub end_column: u16,
     location_specificity: usize,
     /// Variables which this type has declarations or overrides for.
     #[get_size(size_fn = heap_size_of_index_map)]
-    pub vars: IndexMap<String, TypeVar, RandomState>,
+    pub vars: IndexMap<Interned, TypeVar, RandomState>,
     /// Procs and verbs which this type has declarations or overrides for.
     #[get_size(size_fn = heap_size_of_index_map)]
-    pub procs: IndexMap<String, TypeProc, RandomState>,
+    pub procs: IndexMap<Interned, TypeProc, RandomState>,
     parent_path: NodeIndex,
     parent_type: NodeIndex,
     pub docs: DocCollection,
     pub id: SymbolId,
-    children: BTreeMap<String, NodeIndex>,
+    children: BTreeMap<Interned, NodeIndex>,
+    /// The types whose `parent_type` is this one, sorted by path.
+    subtypes: Vec<NodeIndex>,
 }
 
 impl Type {
@@ -305,6 +325,45 @@ impl<'a> TypeRef<'a> {
         ParentTypeIter(Some(*self))
     }
 
+    /// Iterate over the parent **types** of this type, nearest first, not
+    /// including this type.
+    pub fn ancestors(&self) -> impl Iterator<Item=TypeRef<'a>> {
+        self.iter_parent_types().skip(1)
+    }
+
+    /// Iterate over all subtypes of this type in preorder, following
+    /// `parent_type` rather than paths, not including this type.
+    pub fn descendants(&self) -> Descendants<'a> {
+        Descendants {
+            tree: self.tree,
+            start: self.idx,
+            stack: self.subtypes.iter().rev().copied().collect(),
+        }
+    }
+
+    /// Iterate over this type and then all of its subtypes in preorder.
+    pub fn self_and_descendants(&self) -> Descendants<'a> {
+        Descendants {
+            tree: self.tree,
+            start: self.idx,
+            stack: vec![self.idx],
+        }
+    }
+
+    /// Iterate over the final definition of a proc on this type and on each
+    /// of its subtypes which overrides it, in preorder.
+    pub fn overrides_of(&self, name: &'a str) -> impl Iterator<Item=ProcRef<'a>> {
+        self.self_and_descendants().filter_map(move |ty| {
+            let (name, proc) = ty.get().procs.get_key_value(name)?;
+            Some(ProcRef {
+                ty,
+                list: &proc.value,
+                name,
+                idx: proc.value.len() - 1,
+            })
+        })
+    }
+
     /// Recursively visit this and all parent **paths**.
     pub fn visit_parent_paths<F: FnMut(TypeRef<'a>)>(&self, f: &mut F) {
         let mut next = Some(*self);
@@ -513,6 +572,25 @@ impl<'o> NavigatePathResult<'o> {
     }
 }
 
+/// A preorder walk over subtypes. See `TypeRef::descendants`.
+#[derive(Clone)]
+pub struct Descendants<'a> {
+    tree: &'a ObjectTree,
+    start: NodeIndex,
+    stack: Vec<NodeIndex>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = TypeRef<'a>;
+
+    fn next(&mut self) -> Option<TypeRef<'a>> {
+        let idx = self.stack.pop()?;
+        let start = self.start;
+        self.stack.extend(self.tree[idx].subtypes.iter().rev().filter(|&&sub| sub != start));
+        Some(TypeRef::new(self.tree, idx))
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Proc references
 
@@ -533,7 +611,7 @@ impl<'a> ProcRef<'a> {
         self.ty
     }
 
-    pub fn name(&self) -> &str {
+    pub fn name(&self) -> &'a str {
         self.name
     }
 
@@ -580,18 +658,16 @@ impl<'a> ProcRef<'a> {
         self.ty.get_proc_declaration(self.name)
     }
 
+    /// Get the return type declared with `as`, if any.
+    pub fn return_type(self) -> Option<&'a ProcReturnType> {
+        self.get_declaration()
+            .map(|declaration| &declaration.return_type)
+            .filter(|return_type| !return_type.is_empty())
+    }
+
     /// Recursively visit this and all public-facing procs which override it.
     pub fn recurse_children<F: FnMut(ProcRef<'a>)>(self, f: &mut F) {
-        self.ty.recurse(&mut move |ty| {
-            if let Some(proc) = ty.get().procs.get(self.name) {
-                f(ProcRef {
-                    ty,
-                    list: &proc.value,
-                    name: self.name,
-                    idx: proc.value.len() - 1,
-                });
-            }
-        });
+        self.ty.overrides_of(self.name).for_each(f);
     }
 }
 
@@ -637,10 +713,10 @@ impl<'a> std::hash::Hash for ProcRef<'a> {
 // ----------------------------------------------------------------------------
 // The object tree itself
 
-#[derive(Debug, Default, GetSize)]
+#[derive(Debug, Clone, Default, GetSize)]
 pub struct ObjectTree {
     graph: Vec<Type>,
-    types: BTreeMap<String, NodeIndex>,
+    types: BTreeMap<Interned, NodeIndex>,
 }
 
 impl ObjectTree {
@@ -754,9 +830,21 @@ impl std::ops::IndexMut<NodeIndex> for ObjectTree {
     }
 }
 
+/// The steps of finishing an object tree after parsing, for progress
+/// reporting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FinishPhase {
+    /// Resolving the `parent_type` of every type.
+    ParentTypes,
+    /// Evaluating the initial values of vars.
+    Constants,
+}
+
 pub struct ObjectTreeBuilder {
     inner: ObjectTree,
     symbols: SymbolIdSource,
+    /// Where each built-in proc was redeclared, by type and name.
+    builtin_redeclarations: HashMap<(NodeIndex, Interned), Location>,
 }
 
 impl Default for ObjectTreeBuilder {
@@ -767,9 +855,10 @@ impl Default for ObjectTreeBuilder {
             types: Default::default(),
         };
         tree.graph.push(Type {
-            path: String::new(),
+            path: Interned::default(),
             path_last_slash: usize::MAX,
             location: Default::default(),
+            end_column: 0,
             location_specificity: 0,
             vars: Default::default(),
             procs: Default::default(),
@@ -778,11 +867,13 @@ impl Default for ObjectTreeBuilder {
             id: symbols.allocate(),
 
             children: Default::default(),
+            subtypes: Default::default(),
             parent_path: NodeIndex::end(),
         });
         ObjectTreeBuilder {
             inner: tree,
             symbols,
+            builtin_redeclarations: Default::default(),
         }
     }
 }
@@ -814,9 +905,69 @@ impl ObjectTreeBuilder {
         self.inner
     }
 
-    pub(crate) fn finish(mut self, context: &Context, parser_fatal_errored: bool) -> ObjectTree {
+    /// Start from a copy of an existing tree, minus every var and proc which
+    /// the given file declares or overrides, so that a new version of that
+    /// file can be parsed on top of the rest of the environment.
+    ///
+    /// Types the file introduced are kept, but left empty.
+    pub fn overlay(tree: &ObjectTree, file: FileId) -> ObjectTreeBuilder {
+        let mut inner = tree.clone();
+        let mut last_id = 0;
+        for ty in inner.graph.iter_mut() {
+            ty.vars.retain(|_, var| {
+                var.value.location.file != file
+                    && var.declaration.as_ref().is_none_or(|decl| decl.location.file != file)
+            });
+            ty.procs.retain(|_, proc| {
+                proc.value.retain(|value| value.location.file != file);
+                if proc.declaration.as_ref().is_some_and(|decl| decl.location.file == file) {
+                    proc.declaration = None;
+                }
+                !proc.value.is_empty()
+            });
+
+            last_id = last_id.max(ty.id.0);
+            for var in ty.vars.values() {
+                last_id = last_id.max(var.declaration.as_ref().map_or(0, |decl| decl.id.0));
+            }
+            for proc in ty.procs.values() {
+                last_id = last_id.max(proc.declaration.as_ref().map_or(0, |decl| decl.id.0));
+            }
+        }
+        ObjectTreeBuilder {
+            inner,
+            symbols: SymbolIdSource(SymbolId(last_id + 1)),
+            builtin_redeclarations: Default::default(),
+        }
+    }
+
+    /// Finish an overlay. Parent types are assigned again, but constants are
+    /// not evaluated, as the values the rest of the tree already holds may
+    /// not agree with the new file.
+    pub(crate) fn finish_overlay(self, context: &Context) -> ObjectTree {
+        self.finish_without_constants(context)
+    }
+
+    /// Finish without evaluating constants, leaving only builtins' values.
+    pub(crate) fn finish_without_constants(mut self, context: &Context) -> ObjectTree {
+        self.assign_parent_types(context);
+        self.inner
+    }
+
+    pub(crate) fn finish(self, context: &Context, parser_fatal_errored: bool) -> ObjectTree {
+        self.finish_with_progress(context, parser_fatal_errored, &mut |_| {})
+    }
+
+    pub(crate) fn finish_with_progress(
+        mut self,
+        context: &Context,
+        parser_fatal_errored: bool,
+        progress: &mut dyn FnMut(FinishPhase),
+    ) -> ObjectTree {
+        progress(FinishPhase::ParentTypes);
         self.assign_parent_types(context);
         if !parser_fatal_errored {
+            progress(FinishPhase::Constants);
             super::constants::evaluate_all(context, &mut self.inner);
         }
         self.inner
@@ -838,7 +989,7 @@ impl ObjectTreeBuilder {
                         context.register_error(DMError::new(
                             var.value.location,
                             format!("not allowed to change {}/parent_type", path),
-                        ));
+                        ).with_component(Component::ObjectTree));
                     }
                 }
 
@@ -879,7 +1030,8 @@ impl ObjectTreeBuilder {
                             Ok(&empty_string)
                         } else {
                             // A weird situation which should not happen.
-                            Err(DMError::new(location, format!("missing {}/parent_type", path)))
+                            Err(DMError::new(location, format!("missing {}/parent_type", path))
+                                .with_component(Component::ObjectTree))
                         };
 
                         match constant {
@@ -894,8 +1046,11 @@ impl ObjectTreeBuilder {
                                 }
                                 parent_type = &parent_type_buf;
                             }
+                            // Already evaluated, when finishing an overlay.
+                            Ok(Constant::Null(_)) if path == "/client" => {}
                             Ok(other) => {
-                                context.register_error(DMError::new(location, format!("value of {}/parent_type must be a string or typepath, got {}", path, other)));
+                                context.register_error(DMError::new(location, format!("value of {}/parent_type must be a string or typepath, got {}", path, other))
+                                    .with_component(Component::ObjectTree));
                             }
                             Err(e) => {
                                 context.register_error(e);
@@ -914,13 +1069,44 @@ impl ObjectTreeBuilder {
                     context.register_error(DMError::new(
                         location,
                         format!("bad parent type for {}: {}", path, parent_type),
-                    ));
+                    ).with_component(Component::ObjectTree));
                     NodeIndex::new(0)  // on bad parent_type, fall back to the root
                 }
             };
 
             self.inner.graph[type_idx.index()].parent_type = idx;
         }
+
+        // A `parent_type` cycle would make every walk up the tree loop
+        // forever. Break each one by falling back to the root.
+        let len = self.inner.graph.len();
+        for idx in 0..len {
+            let mut current = self.inner.graph[idx].parent_type;
+            for _ in 0..len {
+                if current.index() == idx {
+                    let ty = &mut self.inner.graph[idx];
+                    context.register_error(DMError::new(ty.location, format!("circular parent type for {}", ty.path))
+                        .with_component(Component::ObjectTree));
+                    ty.parent_type = NodeIndex::new(0);
+                    break;
+                }
+                match self.inner.graph.get(current.index()) {
+                    Some(parent) => current = parent.parent_type,
+                    None => break,
+                }
+            }
+        }
+
+        // Index subtypes for `TypeRef::descendants`.
+        for ty in self.inner.graph.iter_mut() {
+            ty.subtypes.clear();
+        }
+        let by_path: Vec<NodeIndex> = self.inner.types.values().copied().collect();
+        for type_idx in by_path {
+            if let Some(parent) = self.inner[type_idx].parent_type_index() {
+                self.inner.graph[parent.index()].subtypes.push(type_idx);
+            }
+        }
     }
 
     // ------------------------------------------------------------------------
@@ -932,12 +1118,13 @@ impl ObjectTreeBuilder {
             if node.location_specificity > len {
                 node.location_specificity = len;
                 node.location = location;
+                node.end_column = end_column(location, child);
             }
             return target;
         }
 
         // time to add a new child
-        let path = format!("{}/{}", self.inner[parent].path, child);
+        let path = intern(&format!("{}/{}", self.inner[parent].path, child));
         let node = NodeIndex::new(self.inner.graph.len());
         self.inner.graph.push(Type {
             path: path.clone(),
@@ -945,14 +1132,16 @@ impl ObjectTreeBuilder {
             vars: Default::default(),
             procs: Default::default(),
             location,
+            end_column: end_column(location, child),
             location_specificity: len,
             parent_type: NodeIndex::end(),
             docs: Default::default(),
             id: self.symbols.allocate(),
             children: Default::default(),
+            subtypes: Default::default(),
             parent_path: parent,
         });
-        self.inner[parent].children.insert(child.to_owned(), node);
+        self.inner[parent].children.insert(intern(child), node);
         self.inner.types.insert(path, node);
         node
     }
@@ -965,13 +1154,20 @@ impl ObjectTreeBuilder {
         declaration: Option<VarDeclaration>,
     ) -> &mut TypeVar {
         // TODO: warn and merge docs for repeats
-        match self.inner[ty].vars.entry(name.to_owned()) {
+        match self.inner[ty].vars.entry(intern(name)) {
             indexmap::map::Entry::Vacant(slot) => {
                 slot.insert(TypeVar { value, declaration })
             },
             indexmap::map::Entry::Occupied(slot) => {
                 let type_var = slot.into_mut();
-                if let Some(declaration) = declaration {
+                if let Some(mut declaration) = declaration {
+                    // Redeclaring a built-in var keeps its built-in type
+                    // unless a new one is given.
+                    if let Some(ref builtin) = type_var.declaration {
+                        if builtin.location.is_builtins() && declaration.var_type.type_path.is_empty() {
+                            declaration.var_type.type_path = builtin.var_type.type_path.clone();
+                        }
+                    }
                     type_var.declaration = Some(declaration);
                 }
                 type_var.value = value;
@@ -990,8 +1186,10 @@ impl ObjectTreeBuilder {
         expression: Option<Expression>,
     ) -> &mut TypeVar {
         let id = self.symbols.allocate();
+        let end_column = end_column(location, name);
         self.insert_var(ty, name, VarValue {
             location,
+            end_column,
             expression,
             docs,
             constant: None,
@@ -999,6 +1197,7 @@ impl ObjectTreeBuilder {
         }, Some(VarDeclaration {
             var_type,
             location,
+            end_column,
             id,
         }))
     }
@@ -1013,6 +1212,7 @@ impl ObjectTreeBuilder {
     ) -> &mut TypeVar {
         self.insert_var(ty, name, VarValue {
             location,
+            end_column: end_column(location, name),
             expression: Some(expression),
             docs,
             constant: None,
@@ -1029,7 +1229,8 @@ impl ObjectTreeBuilder {
         let mut current = NodeIndex::new(0);
         let mut last = match path.next() {
             Some(name) => name,
-            None => return Err(DMError::new(location, "cannot register root path")),
+            None => return Err(DMError::new(location, "cannot register root path")
+                .with_component(Component::ObjectTree)),
         };
         if is_decl(last) {
             return Ok((current, last));
@@ -1076,7 +1277,8 @@ impl ObjectTreeBuilder {
                 }
             }
         } else if is_proc_decl(prev) {
-            return Err(DMError::new(location, "proc looks like a var"));
+            return Err(DMError::new(location, "proc looks like a var")
+                .with_component(Component::ObjectTree));
         }
 
         let mut type_path = Vec::new();
@@ -1094,9 +1296,10 @@ impl ObjectTreeBuilder {
         let symbols = &mut self.symbols;
         let node = &mut self.inner.graph[parent.index()];
         // TODO: warn and merge docs for repeats
-        Ok(Some(node.vars.entry(prev.to_owned()).or_insert_with(|| TypeVar {
+        Ok(Some(node.vars.entry(intern(prev)).or_insert_with(|| TypeVar {
             value: VarValue {
                 location,
+                end_column: end_column(location, prev),
                 expression: suffix.into_initializer(),
                 constant: None,
                 being_evaluated: false,
@@ -1106,6 +1309,7 @@ impl ObjectTreeBuilder {
                 Some(VarDeclaration {
                     var_type: var_type.build(),
                     location,
+                    end_column: end_column(location, prev),
                     id: symbols.allocate(),
                 })
             } else {
@@ -1128,18 +1332,45 @@ impl ObjectTreeBuilder {
         code: Option<Block>,
     ) -> Result<(usize, &mut ProcValue), DMError> {
         let node = &mut self.inner.graph[parent.index()];
-        let proc = node.procs.entry(name.to_owned()).or_insert_with(|| TypeProc {
+        let proc = node.procs.entry(intern(name)).or_insert_with(|| TypeProc {
             value: Vec::with_capacity(1),
             declaration: None,
         });
+        // Redeclaring a built-in proc, like `/datum/proc/New()`, is treated
+        // as an override of it rather than a duplicate definition. It may
+        // still make the proc a verb or give it a return type.
+        let declaration = match (declaration, proc.declaration.as_mut()) {
+            (Some(decl_builder), Some(builtin)) if builtin.location.is_builtins() => {
+                match self.builtin_redeclarations.entry((parent, intern(name))) {
+                    Entry::Occupied(previous) => {
+                        DMError::new(location, format!("duplicate definition of {}/{}", decl_builder.kind, name))
+                            .with_component(Component::ObjectTree)
+                            .with_note(*previous.get(), "previous definition")
+                            .register(context);
+                    }
+                    Entry::Vacant(slot) => {
+                        slot.insert(location);
+                        builtin.kind = decl_builder.kind;
+                        builtin.flags |= decl_builder.flags;
+                        if !return_type.is_empty() {
+                            builtin.return_type = return_type.clone();
+                        }
+                    }
+                }
+                None
+            }
+            (declaration, _) => declaration,
+        };
         if let Some(decl_builder) = declaration {
             if let Some(ref decl) = proc.declaration {
                 DMError::new(location, format!("duplicate definition of {}/{}", decl_builder.kind, name))
+                    .with_component(Component::ObjectTree)
                     .with_note(decl.location, "previous definition")
                     .register(context);
             } else {
                 proc.declaration = Some(ProcDeclaration {
                     location,
+                    end_column: end_column(location, name),
                     kind: decl_builder.kind,
                     flags: decl_builder.flags,
                     return_type,
@@ -1150,6 +1381,7 @@ impl ObjectTreeBuilder {
 
         let value = ProcValue {
             location,
+            end_column: end_column(location, name),
             parameters: parameters.into(),
             docs: Default::default(),
             code
@@ -1168,6 +1400,7 @@ impl ObjectTreeBuilder {
                 // at the beginning of the list (so `..()` finds it).
                 // Configuration can be used to upgrade this above a hint.
                 DMError::new(proc.value[0].location, format!("override of {}/{} precedes definition", node.path, name))
+                    .with_component(Component::ObjectTree)
                     .set_severity(Severity::Hint)
                     .with_errortype("override_precedes_definition")
                     .with_note(location, format!("{}/{}/{} is defined here", node.path, decl, name))
@@ -1265,22 +1498,30 @@ impl ObjectTreeBuilder {
             declaration = Some(ProcDeclBuilder::new(kind, flags));
             proc_name = match next_entry {
                 Some(name) => name,
-                None => return Err(DMError::new(location, "proc must have a name")),
+                None => return Err(DMError::new(location, "proc must have a name")
+                    .with_component(Component::ObjectTree)),
             };
         } else if is_var_decl(proc_name) {
-            return Err(DMError::new(location, "var looks like a proc"));
+            return Err(DMError::new(location, "var looks like a proc")
+                .with_component(Component::ObjectTree));
         }
         if let Some(other) = path.next() {
             return Err(DMError::new(
                 location,
                 format!("proc name must be a single identifier (spurious {:?})", other),
-            ));
+            ).with_component(Component::ObjectTree));
         }
 
         self.register_proc(context, location, parent, proc_name, declaration, parameters, ProcReturnType::default(), code)
     }
 }
 
+/// The column just past a name declared at `location`.
+#[inline]
+fn end_column(location: Location, name: &str) -> u16 {
+    location.column.saturating_add(name.len() as u16)
+}
+
 #[inline]
 fn is_var_decl(s: &str) -> bool {
     s == "var"
@@ -0,0 +1,137 @@
+//! The declarations of a single file, parsed without its environment.
+//!
+//! Parsing a whole `.dme` is slow, but many tools only need to know what one
+//! file declares. The outline of a file is built from that file alone, so it
+//! does not know about parent types, built-ins, or anything declared
+//! elsewhere, and it never touches an existing object tree.
+
+use std::io;
+use std::path::Path;
+
+use super::ast::{Parameter, ProcDeclKind, VarType};
+use super::indents::IndentProcessor;
+use super::intern::Interned;
+use super::parser::Parser;
+use super::preprocessor::{DefineMap, Preprocessor};
+use super::{Context, DMError, Location};
+
+/// What one file contributes to the object tree.
+#[derive(Debug, Clone, Default)]
+pub struct FileOutline {
+    /// The types the file mentions, in the order they were first seen.
+    pub types: Vec<TypeOutline>,
+    /// The vars the file declares or overrides, by location.
+    pub vars: Vec<VarOutline>,
+    /// The procs and verbs the file declares or overrides, by location.
+    pub procs: Vec<ProcOutline>,
+    /// The errors found while parsing.
+    pub errors: Vec<DMError>,
+}
+
+/// A type block.
+#[derive(Debug, Clone)]
+pub struct TypeOutline {
+    pub path: Interned,
+    pub location: Location,
+}
+
+/// A var declaration or override.
+#[derive(Debug, Clone)]
+pub struct VarOutline {
+    /// The path of the type the var is on, empty for globals.
+    pub ty: Interned,
+    pub name: Interned,
+    pub location: Location,
+    /// The declared type of the var, or `None` for an override.
+    pub declaration: Option<VarType>,
+}
+
+/// A proc or verb definition.
+#[derive(Debug, Clone)]
+pub struct ProcOutline {
+    /// The path of the type the proc is on, empty for globals.
+    pub ty: Interned,
+    pub name: Interned,
+    pub location: Location,
+    /// Whether the proc is declared here as a proc or verb, or `None` for an
+    /// override.
+    pub declaration: Option<ProcDeclKind>,
+    pub parameters: Box<[Parameter]>,
+}
+
+impl FileOutline {
+    /// The vars declared or overridden on a type.
+    pub fn vars_of<'a>(&'a self, ty: &'a str) -> impl Iterator<Item=&'a VarOutline> + 'a {
+        self.vars.iter().filter(move |var| var.ty == ty)
+    }
+
+    /// The procs declared or overridden on a type.
+    pub fn procs_of<'a>(&'a self, ty: &'a str) -> impl Iterator<Item=&'a ProcOutline> + 'a {
+        self.procs.iter().filter(move |proc| proc.ty == ty)
+    }
+}
+
+/// Parse the outline of a file from its contents, starting from the given
+/// macros.
+///
+/// Pass `DefineMap::default()` for no macros at all, or
+/// `DefineMap::with_builtins()` for those DM always defines. The file is
+/// registered with the context, but its errors are returned rather than
+/// left there.
+pub fn parse_file(context: &Context, path: &Path, contents: &str, defines: DefineMap) -> FileOutline {
+    let mut preprocessor = Preprocessor::with_defines(context, path.to_owned(), defines);
+    if let Err(e) = preprocessor.push_file(path.to_owned(), io::Cursor::new(contents.to_owned())) {
+        return FileOutline {
+            errors: vec![e],
+            .. Default::default()
+        };
+    }
+    parse_preprocessed(context, preprocessor)
+}
+
+/// Parse the outline of whatever files have been pushed to a preprocessor.
+///
+/// Useful with a preprocessor branched from an environment's define history,
+/// to see the file with the macros it would have there.
+pub fn parse_preprocessed(context: &Context, mut preprocessor: Preprocessor) -> FileOutline {
+    let before = context.errors().len();
+    let tree = {
+        let indent = IndentProcessor::new(context, &mut preprocessor);
+        Parser::new(context, indent).parse_object_tree_without_builtins()
+    };
+
+    let mut outline = FileOutline::default();
+    for ty in tree.iter_types() {
+        if !ty.is_root() {
+            outline.types.push(TypeOutline {
+                path: ty.path.clone(),
+                location: ty.location,
+            });
+        }
+        for (name, var) in ty.vars.iter() {
+            outline.vars.push(VarOutline {
+                ty: ty.path.clone(),
+                name: name.clone(),
+                location: var.value.location,
+                declaration: var.declaration.as_ref().map(|decl| decl.var_type.clone()),
+            });
+        }
+        for (name, proc) in ty.procs.iter() {
+            for value in proc.value.iter() {
+                outline.procs.push(ProcOutline {
+                    ty: ty.path.clone(),
+                    name: name.clone(),
+                    location: value.location,
+                    declaration: proc.declaration.as_ref()
+                        .filter(|decl| decl.location == value.location)
+                        .map(|decl| decl.kind),
+                    parameters: value.parameters.clone(),
+                });
+            }
+        }
+    }
+    outline.vars.sort_by_key(|var| var.location);
+    outline.procs.sort_by_key(|proc| proc.location);
+    outline.errors = context.errors_mut().split_off(before);
+    outline
+}
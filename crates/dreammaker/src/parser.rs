@@ -3,14 +3,15 @@
 use std::borrow::Cow;
 use std::collections::{BTreeMap, VecDeque};
 use std::ops::Range;
+use std::rc::Rc;
 use std::str::FromStr;
 
 use super::annotation::*;
 use super::ast::*;
 use super::docs::*;
-use super::lexer::{LocatedToken, Punctuation, Token};
-use super::objtree::{NodeIndex, ObjectTree, ObjectTreeBuilder};
-use super::{Context, DMError, FileId, HasLocation, Location, Severity};
+use super::lexer::{LocatedToken, MacroExpansion, Punctuation, Token};
+use super::objtree::{FinishPhase, NodeIndex, ObjectTree, ObjectTreeBuilder};
+use super::{Component, Context, DMError, FileId, HasLocation, Location, Severity};
 
 // ----------------------------------------------------------------------------
 // Error handling
@@ -113,6 +114,25 @@ where
     Ok(require!(parser.expression()))
 }
 
+/// Parse a preprocessor condition, which must consist of exactly one
+/// expression.
+pub(crate) fn parse_condition<I>(context: &Context, location: Location, iter: I) -> Result<Expression, DMError>
+where
+    I: IntoIterator<Item=LocatedToken>,
+{
+    let mut parser = Parser::new(context, iter);
+    parser.location = location;
+    let expr = require!(parser.expression());
+    match parser.peek() {
+        Token::Eof => Ok(expr),
+        other => {
+            let desc = format!("unexpected {} after condition", other.single_quoted());
+            Err(DMError::new(parser.location, desc)
+                .with_component(Component::Parser))
+        }
+    }
+}
+
 // ----------------------------------------------------------------------------
 // Operator precedence table
 
@@ -142,10 +162,10 @@ enum Op {
 }
 
 impl Op {
-    fn build(self, lhs: Box<Expression>, rhs: Box<Expression>) -> Expression {
+    fn build(self, lhs: Box<Expression>, rhs: Box<Expression>, end: Location) -> Expression {
         match self {
             Op::BinaryOp(op) => Expression::BinaryOp { op, lhs, rhs },
-            Op::AssignOp(op) => Expression::AssignOp { op, lhs, rhs },
+            Op::AssignOp(op) => Expression::AssignOp { op, lhs, rhs, end },
             Op::TernaryOp(_) => unreachable!(),
         }
     }
@@ -343,6 +363,16 @@ pub struct Parser<'ctx, 'an, 'inp> {
     possible_indentation_error: bool,
     next: Option<Token>,
     location: Location,
+    /// Where in a macro's body the next token was written, if anywhere.
+    body_location: Option<Location>,
+    /// The macro expansion which produced the next token, if any.
+    expansion: Option<Rc<MacroExpansion>>,
+    /// The location of the last token consumed.
+    last_location: Location,
+    /// Where the next token ends.
+    end: Location,
+    /// Where the last token consumed ends.
+    last_end: Location,
     expected: Vec<Cow<'static, str>>,
     skipping_location: Option<Location>,
 
@@ -355,9 +385,25 @@ pub struct Parser<'ctx, 'an, 'inp> {
 }
 
 impl<'ctx, 'an, 'inp> HasLocation for Parser<'ctx, 'an, 'inp> {
+    const COMPONENT: Component = Component::Parser;
+
     fn location(&self) -> Location {
         self.location
     }
+
+    /// Errors at a token produced by expanding macros note each of them.
+    fn error<S: Into<String>>(&self, message: S) -> DMError {
+        let mut error = DMError::new(self.location, message).with_component(Self::COMPONENT);
+        if let (Some(body_location), Some(token)) = (self.body_location, &self.next) {
+            error.add_note(body_location, format!("'{:#}' is expanded from here", token));
+        }
+        for expansion in self.expansion.iter().flat_map(|expansion| expansion.chain()) {
+            if !expansion.definition.is_builtins() {
+                error.add_note(expansion.definition, format!("in expansion of macro {}", expansion.name));
+            }
+        }
+        error
+    }
 }
 
 impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
@@ -374,6 +420,11 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             possible_indentation_error: false,
             next: None,
             location: Default::default(),
+            body_location: None,
+            expansion: None,
+            last_location: Default::default(),
+            end: Default::default(),
+            last_end: Default::default(),
             expected: Vec::new(),
             skipping_location: None,
 
@@ -407,6 +458,33 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         (self.fatal_errored, self.finalize_object_tree())
     }
 
+    /// As `parse_object_tree_2`, but report each step of finishing the tree
+    /// as it begins.
+    pub fn parse_object_tree_with_progress(mut self, progress: &mut dyn FnMut(FinishPhase)) -> (bool, ObjectTree) {
+        self.tree.register_builtins();
+        self.run();
+        self.print_proc_stats();
+        let fatal_errored = self.fatal_errored;
+        (fatal_errored, self.tree.finish_with_progress(self.context, fatal_errored, progress))
+    }
+
+    /// As `parse_object_tree`, but skip evaluating constants, which is much
+    /// of the time spent finishing the tree.
+    pub fn parse_object_tree_without_constants(mut self) -> ObjectTree {
+        self.tree.register_builtins();
+        self.run();
+        self.print_proc_stats();
+        self.tree.finish_without_constants(self.context)
+    }
+
+    /// Parse a single file on top of an existing tree, replacing what that
+    /// file contributed to it. See `ObjectTreeBuilder::overlay`.
+    pub fn parse_object_tree_overlay(mut self, tree: &ObjectTree, file: FileId) -> ObjectTree {
+        self.tree = ObjectTreeBuilder::overlay(tree, file);
+        self.run();
+        self.tree.finish_overlay(self.context)
+    }
+
     #[doc(hidden)]
     pub fn parse_object_tree_without_builtins(mut self) -> ObjectTree {
         self.run();
@@ -438,6 +516,11 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
     }
 
     fn finalize_object_tree(self) -> ObjectTree {
+        self.print_proc_stats();
+        self.tree.finish(self.context, self.fatal_errored)
+    }
+
+    fn print_proc_stats(&self) {
         let procs_total = self.procs_good + self.procs_bad;
         if self.procs_bad > 0 {
             eprintln!(
@@ -447,8 +530,6 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 (self.procs_good * 100 / procs_total)
             );
         }
-
-        self.tree.finish(self.context, self.fatal_errored)
     }
 
     // ------------------------------------------------------------------------
@@ -490,6 +571,43 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         }
     }
 
+    /// Report a statement or tree entry which failed to parse, and skip the
+    /// rest of it so that the ones after it are still parsed.
+    fn recover(&mut self, error: DMError) {
+        self.context.register_error(error);
+        self.skip_to_statement_end();
+    }
+
+    /// Skip up to the next `;` or through the end of the next block,
+    /// whichever comes first, stopping early at a `}` which closes the
+    /// current block.
+    fn skip_to_statement_end(&mut self) {
+        use super::lexer::Token::Punct;
+        use super::lexer::Punctuation::*;
+
+        let mut depth = 0usize;
+        loop {
+            match self.peek() {
+                Token::Eof => return,
+                Punct(RBrace) | Punct(Semicolon) if depth == 0 => return,
+                Punct(LBrace) => depth += 1,
+                Punct(RBrace) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.take();
+                        // The `else` of a broken `if` goes with it.
+                        if !matches!(self.peek(), Token::Ident(ident, _) if ident == "else") {
+                            return;
+                        }
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+            self.take();
+        }
+    }
+
     /// Push an alternative to the "got X, expected one of: ..." list.
     fn expected(&mut self, expected: impl Into<Cow<'static, str>>) {
         let expected = expected.into();
@@ -505,11 +623,14 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 break next;
             }
             match self.input.next() {
-                Some(LocatedToken { location, token: Token::DocComment(comment) }) => {
+                Some(LocatedToken { location, token: Token::DocComment(comment), .. }) => {
                     self.doc_comments_pending.push_back((location, comment));
                 }
-                Some(LocatedToken { location, token }) => {
+                Some(LocatedToken { location, token, body_location, expansion, end }) => {
                     self.location = location;
+                    self.body_location = body_location;
+                    self.expansion = expansion;
+                    self.end = end;
                     self.next = Some(token);
                 }
                 None => {
@@ -530,9 +651,22 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         //assert_ne!(self.peek(), &Token::Eof, "internal parser error: EOF token was consumed");
         self.doc_comments_pending.clear();
         self.expected.clear();
+        self.last_location = self.location;
+        self.last_end = self.end;
         self.next.take().unwrap()
     }
 
+    /// Attach the location of the token just taken to it.
+    fn located(&self, token: Token) -> LocatedToken {
+        LocatedToken {
+            location: self.location,
+            token,
+            body_location: self.body_location,
+            expansion: self.expansion.clone(),
+            end: self.end,
+        }
+    }
+
     fn next_comment_of_target(&mut self, target: DocTarget) -> Status<DocComment> {
         loop {
             if let Some((location, comment)) = self.doc_comments_pending.pop_front() {
@@ -549,7 +683,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 return Ok(None);
             }
             match self.input.next() {
-                Some(LocatedToken { location, token: Token::DocComment(comment) }) => {
+                Some(LocatedToken { location, token: Token::DocComment(comment), .. }) => {
                     if comment.target == target {
                         self.location = location;
                         return success(comment);
@@ -561,6 +695,9 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 }
                 Some(other) => {
                     self.location = other.location;
+                    self.body_location = other.body_location;
+                    self.expansion = other.expansion;
+                    self.end = other.end;
                     self.next = Some(other.token);
                     return Ok(None);
                 }
@@ -576,6 +713,11 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
 
     #[inline]
     fn annotate<F: FnOnce() -> Annotation>(&mut self, start: Location, f: F) {
+        // Peeking moves `self.location`, which error locations depend on, so
+        // only do so when the annotation will actually be kept.
+        if self.annotations.is_none() {
+            return;
+        }
         let end = self.updated_location();
         self.annotate_precise(start..end, f);
     }
@@ -667,7 +809,12 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 tok if tok == &terminator => break,
                 Token::Punct(Punctuation::Semicolon) => continue,
             } else {
-                require!(self.tree_entry(current, proc_builder, var_type.clone()));
+                let start = self.location;
+                let entry = self.tree_entry(current, proc_builder, var_type.clone());
+                if let Err(error) = self.require(entry) {
+                    self.recover(error);
+                }
+                self.context.extend_suppressions(start, self.last_location);
             });
         }
         SUCCESS
@@ -676,7 +823,8 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
     // ------------------------------------------------------------------------
     // Object tree - types
 
-    fn tree_path(&mut self, always_absolute: bool) -> Status<(bool, Vec<Ident>)> {
+    /// Parse a tree path, along with the location of each of its parts.
+    fn tree_path(&mut self, always_absolute: bool) -> Status<(bool, Vec<Ident>, Vec<Location>)> {
         // path :: '/'? ident ('/' ident?)*
 
         // handle leading slash
@@ -686,9 +834,14 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
 
         // 2 is ~66.0%, 4 is ~83.4%, 8 is ~99.9%
         let mut parts = Vec::with_capacity(2);
+        let mut locations = Vec::with_capacity(2);
         // expect at least one ident
+        let first = self.updated_location();
         match self.ident_in_seq(parts.len())? {
-            Some(i) => parts.push(i),
+            Some(i) => {
+                parts.push(i);
+                locations.push(first);
+            }
             None if !(absolute || spurious_lead) => return Ok(None),
             None => {
                 slash_loc.column += 1;
@@ -696,14 +849,16 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     Annotation::IncompleteTreePath(absolute, parts.clone())
                 });
                 self.context.register_error(self.error("path has no effect"));
-                return success((absolute, Vec::new()));
+                return success((absolute, Vec::new(), Vec::new()));
             }
         }
         // followed by ('/' ident)*
         while self.slash()?.is_some() {
             let mut slash_loc = self.location;
+            let location = self.updated_location();
             if let Some(i) = self.ident_in_seq(parts.len())? {
                 parts.push(i);
+                locations.push(location);
             } else {
                 // .../operator/<non-ident> = ... / "operator/"
                 // but .../operator/ident = ... / "operator" / "ident"
@@ -720,7 +875,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         }
 
         self.annotate(start, || Annotation::TreePath(absolute || always_absolute, parts.clone()));
-        success((absolute, parts))
+        success((absolute, parts, locations))
     }
 
     /// Look for nothing, silently accept `/`, and complain but continue if we see a `.` or `:`.
@@ -759,6 +914,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
 
     fn tree_entry(&mut self, mut current: NodeIndex, mut proc_builder: Option<ProcDeclBuilder>, mut var_type: Option<VarTypeBuilder>) -> Status<()> {
         // tree_entry :: path ';'
+        // tree_entry :: var_path ('=' expression)? ',' tree_entry
         // tree_entry :: path '{' tree_entry* '}'
         // tree_entry :: path '=' expression ';'
         // tree_entry :: path '(' argument_list ')' ';'
@@ -774,8 +930,11 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             docs.push(doc_comment);
         }
 
+        // `var/a, b` declares `b` as if it were in the same block as `a`
+        let block_var_type = var_type.clone();
+
         // read and calculate the current path
-        let (absolute, mut path) = if docs.is_empty() {
+        let (absolute, mut path, locations) = if docs.is_empty() {
             leading!(self.tree_path(false))
         } else {
             require!(self.tree_path(false))
@@ -783,6 +942,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
 
         if absolute && current != self.tree.root_index() {
             DMError::new(entry_start, format!("nested absolute path inside {}", self.tree.get_path(current)))
+                .with_component(Component::Parser)
                 .set_severity(Severity::Warning)
                 .register(self.context);
             current = self.tree.root_index();
@@ -800,7 +960,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
 
         let mut relative_type_location = None;
         macro_rules! traverse_tree {
-            ($what:expr) => {
+            ($what:expr, $location:expr) => {
                 let each = $what;
                 if each == "var" {
                     var_type = Some(VarTypeBuilder::default());
@@ -823,10 +983,10 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     }
                 } else {
                     let len = self.tree.get_path(current).chars().filter(|&c| c == '/').count() + path_len;
-                    current = self.tree.subtype_or_add(self.location, current, each, len);
+                    current = self.tree.subtype_or_add($location, current, each, len);
 
                     if !absolute && self.context.config().code_standards.disallow_relative_type_definitions {
-                        relative_type_location = Some(self.location);
+                        relative_type_location = Some($location);
                     }
                 }
             }
@@ -835,14 +995,17 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             () => {
                 if let Some(loc) = relative_type_location {
                     DMError::new(loc, "relatively pathed type defined here")
+                        .with_component(Component::Parser)
                         .set_severity(Severity::Warning)
                         .register(self.context);
                 }
             }
         }
 
-        for each in traverse {
-            traverse_tree!(each);
+        // declarations are located at their name, not the start of the path
+        let name_location = locations[path_len - 1];
+        for (each, &location) in traverse.iter().zip(locations.iter()) {
+            traverse_tree!(each, location);
         }
 
         // parse operator overloading definitions
@@ -870,7 +1033,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             Punct(LBrace) => {
                 self.take();
                 // `thing{` - block
-                traverse_tree!(last_part);
+                traverse_tree!(last_part, name_location);
                 handle_relative_type_error!();
                 let start = self.updated_location();
 
@@ -881,6 +1044,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 if !docs.is_empty() && (proc_builder.is_some() || var_type.is_some()) {
                     // Can't apply docs to `var/` or `proc/` blocks.
                     DMError::new(start, "docs on `var/` or `proc/` block will be applied to their type")
+                        .with_component(Component::Parser)
                         .set_severity(Severity::Warning)
                         .register(self.context);
                 }
@@ -896,9 +1060,25 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 self.take();
                 // `something=` - var
                 handle_relative_type_error!();
-                let location = self.location;
-
-                let expression = require!(self.expression());
+                let location = name_location;
+
+                let expression = self.expression();
+                let expression = match self.require(expression) {
+                    Ok(expression) => expression,
+                    Err(error) => {
+                        // Keep the var, so that the rest of the tree can
+                        // still refer to it.
+                        self.recover(error);
+                        let expression = Expression::from(Term::Error);
+                        if let Some(mut var_type) = var_type {
+                            var_type.suffix(&var_suffix);
+                            self.tree.declare_var(current, last_part, location, docs, var_type.build(), Some(expression));
+                        } else {
+                            self.tree.override_var(current, last_part, location, docs, expression);
+                        }
+                        return SUCCESS;
+                    }
+                };
                 // TODO: save `in` expression?
                 let (input_type, _) = require!(self.input_specifier());
 
@@ -912,21 +1092,23 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     docs.push(comment);
                 }
 
-                require!(self.statement_terminator());
-
                 if let Some(mut var_type) = var_type {
                     var_type.suffix(&var_suffix);
                     var_type.input_type = input_type;
                     self.tree.declare_var(current, last_part, location, docs, var_type.build(), Some(expression));
+                    if let Some(()) = self.exact(Punct(Comma))? {
+                        return self.next_var_entry(current, proc_builder, block_var_type);
+                    }
                 } else {
                     self.tree.override_var(current, last_part, location, docs, expression);
                 }
 
+                require!(self.statement_terminator());
                 SUCCESS
             }
             Punct(LParen) => {
                 // `something(` - proc
-                require!(self.proc_params_and_body(current, proc_builder, last_part, entry_start, absolute, docs));
+                require!(self.proc_params_and_body(current, proc_builder, last_part, name_location, entry_start, absolute, docs));
                 SUCCESS
             }
             _ => {
@@ -958,7 +1140,10 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                         var_type.input_type = input_type;
                         let node = self.tree.get_path(current).to_owned();
                         self.annotate(entry_start, || Annotation::Variable(reconstruct_path(&node, proc_builder, Some(&var_type), last_part)));
-                        self.tree.declare_var(current, last_part, self.location, docs, var_type.build(), var_suffix.into_initializer());
+                        self.tree.declare_var(current, last_part, name_location, docs, var_type.build(), var_suffix.into_initializer());
+                        if let Some(()) = self.exact(Punct(Comma))? {
+                            return self.next_var_entry(current, proc_builder, block_var_type);
+                        }
                     }
                 } else if ProcDeclKind::from_name(last_part).is_some() {
                     self.error("`proc;` item has no effect")
@@ -970,7 +1155,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 } else {
                     handle_relative_type_error!();
                     let len = self.tree.get_path(current).chars().filter(|&c| c == '/').count() + path_len;
-                    current = self.tree.subtype_or_add(self.location, current, last_part, len);
+                    current = self.tree.subtype_or_add(name_location, current, last_part, len);
                     self.tree.extend_docs(current, docs);
                 }
 
@@ -982,6 +1167,13 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
     // ------------------------------------------------------------------------
     // Object tree - Vars
 
+    /// Parse the var after the comma in `var/a = 1, b = 2`.
+    fn next_var_entry(&mut self, current: NodeIndex, proc_builder: Option<ProcDeclBuilder>, var_type: Option<VarTypeBuilder>) -> Status<()> {
+        let var_type = var_type.unwrap_or_default();
+        require!(self.tree_entry(current, proc_builder, Some(var_type)));
+        SUCCESS
+    }
+
     // ------------------------------------------------------------------------
     // Object tree - Procs
 
@@ -1067,13 +1259,12 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         Ok(())
     }
 
-    fn proc_params_and_body(&mut self, current: NodeIndex, proc_builder: Option<ProcDeclBuilder>, name: &str, entry_start: Location, absolute: bool, mut docs: DocCollection) -> Status<()> {
+    #[allow(clippy::too_many_arguments)]
+    fn proc_params_and_body(&mut self, current: NodeIndex, proc_builder: Option<ProcDeclBuilder>, name: &str, location: Location, entry_start: Location, absolute: bool, mut docs: DocCollection) -> Status<()> {
         use super::lexer::Token::*;
         use super::lexer::Punctuation::*;
 
         leading!(self.exact(Punct(LParen)));
-
-        let location = self.location;
         let parameters = require!(self.separated(Comma, RParen, None, Parser::proc_parameter));
         let return_type = self.return_type(proc_builder)?.unwrap_or_default();
 
@@ -1150,6 +1341,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 }
                 if !absolute && self.context.config().code_standards.disallow_relative_proc_definitions {
                     DMError::new(location, "relatively pathed proc defined here")
+                        .with_component(Component::Parser)
                         .set_severity(Severity::Warning)
                         .register(self.context);
                 }
@@ -1174,7 +1366,8 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
 
         // `name` or `obj/name` or `var/obj/name` or ...
         let leading_loc = self.updated_location();
-        let (_absolute, mut path) = leading!(self.tree_path(true));
+        let (_absolute, mut path, mut locations) = leading!(self.tree_path(true));
+        let location = locations.pop().unwrap_or(leading_loc);
         let name = match path.pop() {
             Some(name) => name,
             None => {
@@ -1185,6 +1378,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         if path.first().map_or(false, |i| i == "var") {
             path.remove(0);
             DMError::new(leading_loc, "'var/' is unnecessary here")
+                .with_component(Component::Parser)
                 .set_severity(Severity::Hint)
                 .with_errortype("var_in_proc_parameter")
                 .register(self.context);
@@ -1192,11 +1386,11 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         let mut var_type: VarTypeBuilder = path.into_iter().collect();
         if var_type.flags.is_static() {
             DMError::new(leading_loc, "'static/' has no effect here")
+                .with_component(Component::Parser)
                 .set_severity(Severity::Warning)
                 .with_errortype("static_in_proc_parameter")
                 .register(self.context);
         }
-        let location = self.location;
         // In parameters, the expression within the annotation is ignored.
         var_type.suffix(&require!(self.var_suffix()));
         // = <expr>
@@ -1210,6 +1404,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         // Allow a trailing `;` since BYOND accepts it, but this is dumb
         if let Some(()) = self.exact(Punct(Semicolon))? {
             DMError::new(self.updated_location(), "Extraneous ';' in proc parameter")
+                .with_component(Component::Parser)
                 .set_severity(Severity::Warning)
                 .with_errortype("semicolon_in_proc_parameter")
                 .register(self.context);
@@ -1325,7 +1520,38 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
 
     /// Parse a block
     fn block(&mut self, loop_ctx: &LoopContext) -> Status<Block> {
+        let start = self.updated_location();
         let mut vars = Vec::new();
+        let result = self.block_statements(loop_ctx, &mut vars);
+        if let Ok(Some(_)) = result {
+            self.annotate(start, || Annotation::StatementBlock);
+        }
+        // annotate the locals even if the block is incomplete, so that they
+        // are still known while the user is in the middle of typing
+        for (loc, var_type, name) in vars {
+            self.annotate(loc, || Annotation::LocalVarScope(var_type, name));
+        }
+        Ok(result?.map(Vec::into_boxed_slice))
+    }
+
+    /// Parse the body of a conditional or loop. A body which is empty
+    /// without braces usually means the intended body isn't indented under
+    /// it, so warn about that.
+    fn body(&mut self, loop_ctx: &LoopContext, keyword: &str, location: Location) -> Status<Block> {
+        let braced = matches!(self.peek(), Token::Punct(Punctuation::LBrace));
+        let block = self.block(loop_ctx)?;
+        if block.as_ref().is_some_and(|block| block.is_empty()) && !braced {
+            DMError::new(location, format!("{} statement has an empty body", keyword))
+                .with_component(Component::Parser)
+                .set_severity(Severity::Warning)
+                .with_errortype("empty_block")
+                .with_end(location.add_columns(keyword.len() as u16))
+                .register(self.context);
+        }
+        Ok(block)
+    }
+
+    fn block_statements(&mut self, loop_ctx: &LoopContext, vars: &mut Vec<(Location, VarType, Ident)>) -> Status<Vec<Spanned<Statement>>> {
         let result = if let Some(()) = self.exact(Token::Punct(Punctuation::LBrace))? {
             let mut statements = Vec::new();
             loop {
@@ -1334,7 +1560,12 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 } else if let Some(()) = self.exact(Token::Punct(Punctuation::Semicolon))? {
                     continue;
                 } else {
-                    statements.push(require!(self.statement(loop_ctx, &mut vars)));
+                    let statement = self.statement(loop_ctx, vars);
+                    match self.require(statement) {
+                        Ok(statement) => statements.push(statement),
+                        Err(error) if self.eof => return Err(error),
+                        Err(error) => self.recover(error),
+                    }
                 }
             }
             statements
@@ -1343,16 +1574,22 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             Vec::new()
         } else {
             // and one-line blocks: if(1) neat();
-            let statement = require!(self.statement(loop_ctx, &mut vars));
+            let statement = require!(self.statement(loop_ctx, vars));
             vec![statement]
         };
-        for (loc, var_type, name) in vars {
-            self.annotate(loc, || Annotation::LocalVarScope(var_type, name));
-        }
-        success(result.into_boxed_slice())
+        success(result)
     }
 
     fn statement(&mut self, loop_ctx: &LoopContext, vars: &mut Vec<(Location, VarType, Ident)>) -> Status<Spanned<Statement>> {
+        let result = self.bare_statement(loop_ctx, vars);
+        if let Ok(Some(ref statement)) = result {
+            self.context.extend_suppressions(statement.location, self.last_location);
+            self.annotate(statement.location, || Annotation::Statement);
+        }
+        result
+    }
+
+    fn bare_statement(&mut self, loop_ctx: &LoopContext, vars: &mut Vec<(Location, VarType, Ident)>) -> Status<Spanned<Statement>> {
         let start = self.location();
         let spanned = |v| success(Spanned::new(start, v));
 
@@ -1362,23 +1599,25 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             require!(self.exact(Token::Punct(Punctuation::LParen)));
             let expr = Spanned::new(self.location(), require!(self.expression()));
             require!(self.exact(Token::Punct(Punctuation::RParen)));
-            let block = require!(self.block(loop_ctx));
+            let block = require!(self.body(loop_ctx, "if", start));
             let mut arms = vec![(expr, block)];
 
             let mut else_arm = None;
             self.skip_phantom_semicolons()?;
+            let mut else_location = self.updated_location();
             while let Some(()) = self.exact_ident("else")? {
                 if let Some(()) = self.exact_ident("if")? {
                     require!(self.exact(Token::Punct(Punctuation::LParen)));
                     let expr = Spanned::new(self.location(), require!(self.expression()));
                     require!(self.exact(Token::Punct(Punctuation::RParen)));
-                    let block = require!(self.block(loop_ctx));
+                    let block = require!(self.body(loop_ctx, "else if", else_location));
                     arms.push((expr, block));
                 } else {
-                    else_arm = Some(require!(self.block(loop_ctx)));
+                    else_arm = Some(require!(self.body(loop_ctx, "else", else_location)));
                     break;
                 }
                 self.skip_phantom_semicolons()?;
+                else_location = self.updated_location();
             }
 
             spanned(Statement::If { arms, else_arm })
@@ -1387,7 +1626,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             require!(self.exact(Token::Punct(Punctuation::LParen)));
             let condition = require!(self.expression());
             require!(self.exact(Token::Punct(Punctuation::RParen)));
-            let block = require!(self.block(&LoopContext::While));
+            let block = require!(self.body(&LoopContext::While, "while", start));
             spanned(Statement::While { condition, block })
         } else if let Some(()) = self.exact_ident("do")? {
             // statement :: 'do' block 'while' '(' expression ')' ';'
@@ -1419,7 +1658,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     init: init.map(Box::new),
                     test: test.map(Box::new),
                     inc: inc.map(Box::new),
-                    block: require!(self.block(&LoopContext::ForLoop)),
+                    block: require!(self.body(&LoopContext::ForLoop, "for", start)),
                 })
             } else if let Some(init) = init {
                 // in-list form ("for list")
@@ -1431,13 +1670,14 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                             // for(var/a = 1 to
                             require!(self.exact_ident("to"));
                             let rhs = require!(self.expression());
-                            return spanned(require!(self.for_range(Some(vs.var_type), vs.name, Box::new(value), Box::new(rhs))));
+                            return spanned(require!(self.for_range(Some(vs.var_type), vs.name, Box::new(value), Box::new(rhs), start)));
                         }
                     },
                     Statement::Expr(Expression::AssignOp {
                         op: AssignOp::Assign,
                         lhs,
                         rhs,
+                        ..
                     }) => {
                         // for(a = 1 to
                         let name = match lhs.into_term() {
@@ -1446,7 +1686,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                         };
                         require!(self.exact_ident("to"));
                         let to_rhs = require!(self.expression());
-                        return spanned(require!(self.for_range(None, name, rhs, Box::new(to_rhs))));
+                        return spanned(require!(self.for_range(None, name, rhs, Box::new(to_rhs), start)));
                     }
                     Statement::Expr(Expression::BinaryOp {
                         op: BinaryOp::In,
@@ -1459,7 +1699,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                         };
                         match *rhs {
                             Expression::BinaryOp { op: BinaryOp::To, lhs, rhs } => {
-                                return spanned(require!(self.for_range(None, name, lhs, rhs)));
+                                return spanned(require!(self.for_range(None, name, lhs, rhs, start)));
                             },
                             rhs => {
                                 // I love code duplication, don't you?
@@ -1469,7 +1709,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                                     name: name.into(),
                                     input_type: None,
                                     in_list: Some(rhs),
-                                    block: require!(self.block(&LoopContext::ForList)),
+                                    block: require!(self.body(&LoopContext::ForList, "for", start)),
                                 })));
                             }
                         }
@@ -1492,7 +1732,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     let value = require!(self.expression());
                     if let Some(()) = self.exact_ident("to")? {
                         let rhs = require!(self.expression());
-                        return spanned(require!(self.for_range(var_type, name, Box::new(value), Box::new(rhs))));
+                        return spanned(require!(self.for_range(var_type, name, Box::new(value), Box::new(rhs), start)));
                     }
                     Some(value)
                 } else {
@@ -1505,12 +1745,12 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     name: name.into(),
                     input_type,
                     in_list,
-                    block: require!(self.block(&LoopContext::ForList)),
+                    block: require!(self.body(&LoopContext::ForList, "for", start)),
                 })))
             } else {
                 require!(self.exact(Token::Punct(Punctuation::RParen)));
                 spanned(Statement::ForInfinite {
-                    block: require!(self.block(&LoopContext::ForInfinite)),
+                    block: require!(self.body(&LoopContext::ForInfinite, "for", start)),
                 })
             }
         } else if let Some(()) = self.exact_ident("spawn")? {
@@ -1533,13 +1773,14 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             let mut cases = Vec::new();
             let default = loop {
                 if let Some(()) = self.exact_ident("if")? {
+                    let case_location = self.location();
                     require!(self.exact(Token::Punct(Punctuation::LParen)));
                     let what = require!(self.separated(Punctuation::Comma, Punctuation::RParen, None, Parser::case));
                     if what.is_empty() {
                         self.context.register_error(self.error("switch case cannot be empty"));
                     }
                     let block = require!(self.block(loop_ctx));
-                    cases.push((Spanned::new(self.location(), what), block));
+                    cases.push((Spanned::new(case_location, what), block));
                 } else if let Some(()) = self.exact_ident("else")? {
                     break Some(require!(self.block(loop_ctx)));
                 } else if let Some(()) = self.exact(Token::Punct(Punctuation::Semicolon))? {
@@ -1658,33 +1899,37 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             let mut var_stmts = Vec::new();
             loop {
                 let type_path_start = self.location();
-                let (_, mut tree_path) = require!(self.tree_path(true));
-                let name = match tree_path.pop() {
-                    Some(name) => name,
-                    None => return Err(self.error("'var' must be followed by a name")),
+                let (_, mut tree_path, mut locations) = require!(self.tree_path(true));
+                let (name, location) = match (tree_path.pop(), locations.pop()) {
+                    (Some(name), Some(location)) => (name, location),
+                    _ => return Err(self.error("'var' must be followed by a name")),
                 };
 
                 let mut var_type = tree_path.into_iter().collect::<VarTypeBuilder>();
                 if var_type.flags.is_tmp() {
                     DMError::new(type_path_start, "var/tmp has no effect here")
+                        .with_component(Component::Parser)
                         .set_severity(Severity::Warning)
                         .with_errortype("tmp_no_effect")
                         .register(self.context);
                 }
                 if var_type.flags.is_final() {
                     DMError::new(type_path_start, "var/final has no effect here")
+                        .with_component(Component::Parser)
                         .set_severity(Severity::Warning)
                         .with_errortype("final_no_effect")
                         .register(self.context);
                 }
                 if var_type.flags.is_private() {
                     DMError::new(type_path_start, "var/SpacemanDMM_private has no effect here")
+                        .with_component(Component::Parser)
                         .with_errortype("private_var")
                         .set_severity(Severity::Warning)
                         .register(self.context);
                 }
                 if var_type.flags.is_protected() {
                     DMError::new(type_path_start, "var/SpacemanDMM_protected has no effect here")
+                        .with_component(Component::Parser)
                         .with_errortype("protected_var")
                         .set_severity(Severity::Warning)
                         .register(self.context);
@@ -1697,10 +1942,28 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                 }
 
                 let value = if let Some(()) = self.exact(Token::Punct(Punctuation::Assign))? {
-                    Some(require!(self.expression()))
+                    let expression = self.expression();
+                    match self.require(expression) {
+                        Ok(expression) => Some(expression),
+                        Err(error) if in_for => return Err(error),
+                        Err(error) => {
+                            // Keep the var, so that later statements which
+                            // use it aren't reported too.
+                            self.recover(error);
+                            var_stmts.push(VarStatement {
+                                var_type: var_type.build(),
+                                name,
+                                value: Some(Expression::from(Term::Error)),
+                                location,
+                                end: self.last_end,
+                            });
+                            break;
+                        }
+                    }
                 } else {
                     var_suffix.into_initializer()
                 };
+                let end = self.last_end;
                 let (input_types, in_list) = if !in_for {
                     require!(self.input_specifier())
                 } else {
@@ -1713,7 +1976,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                         .register(self.context);
                 }
 
-                var_stmts.push(VarStatement { var_type: var_type.build(), name, value });
+                var_stmts.push(VarStatement { var_type: var_type.build(), name, value, location, end });
                 if in_for || self.exact(Token::Punct(Punctuation::Comma))?.is_none() {
                     break;
                 }
@@ -1761,6 +2024,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         name: Ident,
         start: Box<Expression>,
         end: Box<Expression>,
+        location: Location,
     ) -> Status<Statement> {
         // step 2
         let step = if let Some(()) = self.exact_ident("step")? {
@@ -1777,7 +2041,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             start: *start,
             end: *end,
             step,
-            block: require!(self.block(&LoopContext::ForRange)),
+            block: require!(self.body(&LoopContext::ForRange, "for", location)),
         })))
     }
 
@@ -1862,12 +2126,15 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         let mut vars = Vec::new();
         if let Some(()) = self.exact(Token::Punct(Punctuation::LBrace))? {
             self.separated(Punctuation::Semicolon, Punctuation::RBrace, Some(()), |this| {
+                let var_start = this.updated_location();
                 let key = require!(this.ident());
                 require!(this.exact(Token::Punct(Punctuation::Assign)));
                 let value = require!(this.expression());
+                this.annotate(var_start, || Annotation::Expression);
                 vars.push((key.into(), value));
                 SUCCESS
             })?;
+            self.annotate(start, || Annotation::Expression);
         }
 
         success(Box::new(Prefab { path: parts, vars: vars.into_boxed_slice() }))
@@ -1878,6 +2145,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
     }
 
     fn expression_ex(&mut self, strength: Option<Strength>, in_ternary: bool) -> Status<Expression> {
+        let start = self.updated_location();
         let mut expr = leading!(self.group(in_ternary));
         loop {
             // try to read the next operator
@@ -1900,6 +2168,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             expr = require!(self.expression_part(expr, info, strength,
                 in_ternary || info.strength == Strength::Conditional));
         }
+        self.annotate(start, || Annotation::Expression);
         success(expr)
     }
 
@@ -1986,7 +2255,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         success(if prev_op.strength.right_binding() {
             let mut result = rhs;
             for (op, bit) in ops.into_iter().zip(bits.into_iter()).rev() {
-                result = op.build(Box::new(bit), Box::new(result));
+                result = op.build(Box::new(bit), Box::new(result), self.last_end);
             }
             result
         } else {
@@ -1994,14 +2263,15 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             let mut ops_iter = ops.into_iter();
             let mut result = iter.next().unwrap();
             for (item, op) in iter.zip(&mut ops_iter) {
-                result = op.build(Box::new(result), Box::new(item));
+                result = op.build(Box::new(result), Box::new(item), self.last_end);
             }
-            ops_iter.next().unwrap().build(Box::new(result), Box::new(rhs))
+            ops_iter.next().unwrap().build(Box::new(result), Box::new(rhs), self.last_end)
         })
     }
 
     // parse an Expression::Base (unary ops, term, follows)
     fn group(&mut self, in_ternary: bool) -> Status<Expression> {
+        let start = self.updated_location();
         // Read prefix unary ops
         let mut unary_ops = Vec::new();
         loop {
@@ -2023,6 +2293,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         } else {
             require!(self.term(&mut belongs_to))
         };
+        self.annotate(term.location, || Annotation::Expression);
 
         // Read postfix unary ops and field-access follows
         let mut follow = Vec::new();
@@ -2037,6 +2308,11 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     None => break,
                 }
             });
+            // each step of a field access chain is its own selectable span
+            self.annotate(term.location, || Annotation::Expression);
+        }
+        if !unary_ops.is_empty() {
+            self.annotate(start, || Annotation::Expression);
         }
 
         // Add prefix unary operators to the follows in reverse order
@@ -2085,9 +2361,10 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     if let Some(ident) = self.ident()? {
                         // prefab
                         // TODO: arrange for this ident to end up in the prefab's annotation
+                        let prefab = require!(self.prefab_ex(vec![(PathOp::Dot, ident)]));
                         Term::NewPrefab {
-                            prefab: require!(self.prefab_ex(vec![(PathOp::Dot, ident)])),
-                            args: self.arguments(&[], "New")?,
+                            args: self.new_arguments(&prefab.path)?,
+                            prefab,
                         }
                     } else {
                         // bare dot
@@ -2114,8 +2391,8 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     }
                 } else if let Some(prefab) = self.prefab()? {
                     Term::NewPrefab {
+                        args: self.new_arguments(&prefab.path)?,
                         prefab,
-                        args: self.arguments(&[], "New")?,
                     }
                 } else {
                     Term::NewImplicit {
@@ -2181,6 +2458,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
                     let in_list = if let Some(()) = self.exact(Token::Punct(Punctuation::In))? {
                         if args.len() > 1 {
                             DMError::new(start, "bad 'locate(x, y, z) in'")
+                                .with_component(Component::Parser)
                                 .set_severity(Severity::Warning)
                                 .register(self.context);
                         }
@@ -2307,7 +2585,8 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
             Token::InterpStringBegin(begin) => {
                 let mut parts = Vec::new();
                 loop {
-                    let expr = self.expression()?;
+                    let expr_start = self.updated_location();
+                    let expr = Spanned::new(expr_start, self.expression()?);
                     self.expected("']'");
                     take_match!(self {
                         Token::InterpStringPart(part) => {
@@ -2482,6 +2761,17 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         }
     }
 
+    /// the argument list of a `new` with a known type, which is annotated
+    /// with that type so `New()` can be looked up on it
+    fn new_arguments(&mut self, path: &TypePath) -> Status<Box<[Expression]>> {
+        let start = self.updated_location();
+        let result = self.arguments(&[], "New");
+        if !matches!(result, Ok(None)) {
+            self.annotate(start, || Annotation::NewArguments(path.clone()));
+        }
+        result
+    }
+
     fn pick_arguments(&mut self) -> Status<Box<PickArgs>> {
         leading!(self.exact(Token::Punct(Punctuation::LParen)));
         success(require!(self.separated(
@@ -2539,7 +2829,7 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         }
         let start = self.take();
         let kind = TTKind::from_token(&start);
-        target.push(LocatedToken::new(self.location(), start));
+        target.push(self.located(start));
         let kind = match kind {
             Some(k) => k,
             None => return SUCCESS,
@@ -2548,7 +2838,8 @@ impl<'ctx, 'an, 'inp> Parser<'ctx, 'an, 'inp> {
         loop {
             self.expected(kind.end());
             if kind.is_end(self.peek()) {
-                target.push(LocatedToken::new(self.location(), self.take()));
+                let end = self.take();
+                target.push(self.located(end));
                 return SUCCESS;
             } else {
                 self.skipping_location = Some(location);
@@ -0,0 +1,206 @@
+//! Reading the files an environment includes ahead of the preprocessor.
+//!
+//! The preprocessor has to take files one at a time and in order, because
+//! each file sees the macros defined by those before it. Reading them from
+//! disk has no such dependency, and nearly every file is included directly
+//! by the `.dme`, so those are read on worker threads while earlier files are
+//! still being parsed. Only the reading happens early: what the preprocessor
+//! does with each file, and every diagnostic, is unchanged.
+//!
+//! Reading stays a fixed number of files ahead of the preprocessor, so only
+//! those files are held in memory rather than the whole environment.
+//!
+//! Lexing and parsing are not moved to the workers. Which macros a file sees,
+//! and so what its tokens are, depends on every file before it, so parsing
+//! files out of order would need a first pass over the whole environment and
+//! a merge which reproduced the serial object tree and error order. Reading
+//! needs neither, and is the part which waits on the disk rather than the
+//! CPU, so overlapping it with parsing helps most where the disk is slow,
+//! such as on a cold cache or a network drive.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Condvar, Mutex};
+
+use foldhash::HashMap;
+
+use super::preprocessor::resolve_include;
+
+/// The most threads to read files on.
+const MAX_THREADS: usize = 8;
+
+/// How many files past the last one taken may be read ahead. Files are held
+/// in memory until they are taken, so this bounds how much is.
+const READ_AHEAD: usize = 64;
+
+/// What a worker found for one `#include`: the file it resolved to and its
+/// contents, or `None` if either failed.
+type Fetched = Option<(PathBuf, Vec<u8>)>;
+
+/// Files being read in the background, by their path as written in the
+/// `.dme`.
+#[derive(Debug, Default)]
+pub struct Prefetch {
+    indices: HashMap<String, usize>,
+    /// Each file's contents as they are read, until taken.
+    pending: Vec<Option<Receiver<Fetched>>>,
+    /// The files before this position have all been taken or dropped.
+    freed: usize,
+    shared: Arc<Shared>,
+}
+
+/// The state the workers share with the preprocessor.
+#[derive(Debug, Default)]
+struct Shared {
+    state: Mutex<State>,
+    /// Signalled when the preprocessor takes a file or stops.
+    progressed: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// The files not yet read, with their position in the `.dme`.
+    queue: VecDeque<(usize, String, SyncSender<Fetched>)>,
+    /// One past the position of the last file taken.
+    taken: usize,
+    cancelled: bool,
+}
+
+impl Prefetch {
+    /// Start reading the `.dm` files which the given `.dme` source includes.
+    pub fn start(env_file: &Path, dme: &[u8]) -> Prefetch {
+        let threads = std::thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(MAX_THREADS);
+        Prefetch::with_threads(env_file, dme, threads)
+    }
+
+    /// Like `start`, but reading on at most the given number of threads.
+    fn with_threads(env_file: &Path, dme: &[u8], max_threads: usize) -> Prefetch {
+        let mut indices = HashMap::default();
+        let mut pending = Vec::new();
+        let mut queue = VecDeque::new();
+        for written in scan_includes(dme) {
+            if indices.contains_key(&written) {
+                continue;
+            }
+            let index = pending.len();
+            let (sender, receiver) = sync_channel(1);
+            indices.insert(written.clone(), index);
+            pending.push(Some(receiver));
+            queue.push_back((index, written, sender));
+        }
+
+        let threads = max_threads.min(queue.len());
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State { queue, taken: 0, cancelled: false }),
+            progressed: Condvar::new(),
+        });
+        let mut started = 0;
+        for _ in 0..threads {
+            let shared = shared.clone();
+            let env_file = env_file.to_owned();
+            let spawned = std::thread::Builder::new()
+                .name("dm-prefetch".to_owned())
+                .spawn(move || fetch_all(&env_file, &shared));
+            if spawned.is_err() {
+                // Whatever is never read here is read when it's included.
+                break;
+            }
+            started += 1;
+        }
+        if started == 0 {
+            // Nothing will ever be sent, so drop the senders for `take` to
+            // find every file unread rather than wait on it forever.
+            shared.state.lock().unwrap().queue.clear();
+        }
+
+        Prefetch { indices, pending, freed: 0, shared }
+    }
+
+    /// Take the contents of a file included by the `.dme`, waiting for them
+    /// if necessary.
+    ///
+    /// Returns `None` if the file was not prefetched, was already taken, or
+    /// could not be read, in which case it should be read as usual.
+    pub fn take(&mut self, written: &str, candidate: &Path) -> Option<Vec<u8>> {
+        let index = *self.indices.get(written)?;
+        let receiver = self.pending[index].take()?;
+
+        // Files before this one which were not taken are in disabled code or
+        // were included from elsewhere first. Stop holding on to them.
+        for each in self.pending.get_mut(self.freed..index).into_iter().flatten() {
+            *each = None;
+        }
+        self.freed = self.freed.max(index + 1);
+        {
+            let mut state = self.shared.state.lock().unwrap();
+            state.taken = state.taken.max(index + 1);
+        }
+        self.shared.progressed.notify_all();
+
+        match receiver.recv() {
+            Ok(Some((path, buffer))) if path == candidate => Some(buffer),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Prefetch {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().cancelled = true;
+        self.shared.progressed.notify_all();
+    }
+}
+
+fn fetch_all(env_file: &Path, shared: &Shared) {
+    loop {
+        let (written, sender) = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.cancelled {
+                    return;
+                }
+                match state.queue.front() {
+                    None => return,
+                    Some(&(index, ..)) if index < state.taken + READ_AHEAD => break,
+                    Some(_) => state = shared.progressed.wait(state).unwrap(),
+                }
+            }
+            let (_, written, sender) = state.queue.pop_front().unwrap();
+            (written, sender)
+        };
+        let fetched = resolve_include(env_file, env_file, &written)
+            .and_then(|path| std::fs::read(&path).ok().map(|buffer| (path, buffer)));
+        // The preprocessor may have finished without including this file.
+        let _ = sender.send(fetched);
+    }
+}
+
+/// Find the `.dm` files a `.dme` includes, in order.
+///
+/// This only looks at lines, so it also finds includes which are disabled
+/// or commented out. Reading those is wasted, but harmless.
+fn scan_includes(dme: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(dme);
+    text.lines()
+        .filter_map(|line| line.trim_start().strip_prefix("#include"))
+        .filter_map(|rest| rest.trim().strip_prefix('"')?.strip_suffix('"'))
+        .filter(|written| written.ends_with(".dm"))
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_threads_started() {
+        let dme = b"#include \"code/a.dm\"\n#include \"code/b.dm\"\n";
+        let mut prefetch = Prefetch::with_threads(Path::new("env.dme"), dme, 0);
+        assert_eq!(prefetch.take("code/b.dm", Path::new("code/b.dm")), None);
+        assert_eq!(prefetch.take("code/a.dm", Path::new("code/a.dm")), None);
+    }
+}
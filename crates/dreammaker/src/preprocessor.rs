@@ -2,19 +2,23 @@
 use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::fs::File;
-use std::path::{Path, PathBuf};
+use std::ffi::{OsStr, OsString};
+use std::path::{self, Path, PathBuf};
 use std::rc::Rc;
 use std::{fmt, io};
 
-use foldhash::HashMap;
+use foldhash::{HashMap, HashSet};
 
 use interval_tree::{range, IntervalTree};
 
 use super::annotation::*;
 use super::ast::Ident;
 use super::docs::{CommentKind, DocCollection, DocComment, DocTarget};
+use super::includes::{IncludeEdge, IncludeGraph};
+use super::intern::intern;
 use super::lexer::*;
-use super::{Context, DMError, FileId, HasLocation, Location, Severity};
+use super::prefetch::Prefetch;
+use super::{Component, Context, DMError, FileId, HasLocation, Location, Severity};
 
 /// The maximum recursion depth of macro expansion.
 const MAX_RECURSION_DEPTH: usize = 32;
@@ -84,19 +88,96 @@ impl<'a> fmt::Display for NameAndDefine<'a> {
 
 type InnerDefineHistory = IntervalTree<Location, (String, Define)>;
 
+/// The files in which each macro was used.
+pub type MacroUses = HashMap<Ident, HashSet<FileId>>;
+
 /// An interval tree representing historic macro definitions.
 #[derive(Debug)]
 pub struct DefineHistory {
     env_file: PathBuf,
     last_input_loc: Location,
     tree: InnerDefineHistory,
+    body_locations: HashMap<Location, Rc<[Location]>>,
+    macro_uses: Option<MacroUses>,
+    include_locations: HashMap<FileId, Location>,
+    maps: Vec<PathBuf>,
 }
 
 impl DefineHistory {
+    /// The `.dme` file this history was read from.
+    pub fn env_file(&self) -> &Path {
+        &self.env_file
+    }
+
+    /// The files in which each macro was used, if the config enabled
+    /// tracking them.
+    ///
+    /// Uses in the bodies of other macros and, if configured, in code
+    /// disabled by `#if` and `#ifdef` count as uses in the file containing
+    /// them.
+    pub fn macro_uses(&self) -> Option<&MacroUses> {
+        self.macro_uses.as_ref()
+    }
+
+    /// The `.dmm` map files included by the environment.
+    pub fn maps(&self) -> &[PathBuf] {
+        &self.maps
+    }
+
+    /// The location of the `#include` which included each file.
+    pub fn include_locations(&self) -> &HashMap<FileId, Location> {
+        &self.include_locations
+    }
+
+    /// Every definition of a macro, in the order they were seen.
+    pub fn definitions(&self, name: &str) -> Vec<(Location, &Define)> {
+        let mut found: Vec<_> = self.tree.iter()
+            .filter(|(_, (define_name, _))| define_name == name)
+            .map(|(range, (_, define))| (range.start, define))
+            .collect();
+        found.sort_by_key(|&(location, _)| location);
+        found
+    }
+
+    /// The definition of a macro which was in effect at the given location.
+    ///
+    /// Redefinitions shadow earlier definitions until they are `#undef`ed,
+    /// so the latest definition still in effect wins.
+    pub fn active_at(&self, name: &str, location: Location) -> Option<(Location, &Define)> {
+        self.tree.range(range(location, location))
+            .filter(|(_, (define_name, _))| define_name == name)
+            .map(|(range, (_, define))| (range.start, define))
+            .max_by_key(|&(start, _)| start)
+    }
+
+    /// Every directory ever named by `#define FILE_DIR`, in the order they
+    /// were defined.
+    pub fn file_dirs(&self) -> Vec<String> {
+        let mut dirs: Vec<String> = Vec::new();
+        for (_, (name, define)) in self.tree.iter() {
+            if name != "FILE_DIR" {
+                continue;
+            }
+            let dir = match define.substitution() {
+                [Token::String(dir)] => dir.clone(),
+                tokens => tokens.iter().map(ToString::to_string).collect(),
+            };
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        dirs
+    }
+
     /// Branch a child preprocessor from this preprocessor's historic state at
     /// the start of the given file.
     pub fn branch_at_file<'ctx2>(&self, file: FileId, context: &'ctx2 Context) -> Preprocessor<'ctx2> {
-        let location = Location { file, line: 0, column: 0 };
+        self.branch_at_location(Location { file, line: 0, column: 0 }, context)
+    }
+
+    /// Branch a child preprocessor from this preprocessor's historic state at
+    /// the given location.
+    pub fn branch_at_location<'ctx2>(&self, location: Location, context: &'ctx2 Context) -> Preprocessor<'ctx2> {
         let defines = DefineMap::from_history(self, location);
 
         Preprocessor {
@@ -104,6 +185,8 @@ impl DefineHistory {
             env_file: self.env_file.clone(),
             include_stack: Default::default(),
             include_locations: Default::default(),
+            record_includes: false,
+            dir_entries: Default::default(),
             multiple_locations: Default::default(),
             history: Default::default(),  // TODO: support branching a second time
             defines,
@@ -113,12 +196,18 @@ impl DefineHistory {
             ifdef_stack: Default::default(),  // should be fine
             ifdef_history: Default::default(),
             last_input_loc: location,
+            last_input_body: None,
+            last_input_expansion: None,
+            last_input_end: location,
+            body_locations: self.body_locations.clone(),
             last_printable_input_loc: location,
             output: Default::default(),
             danger_idents: Default::default(),
             docs_in: Default::default(),
             in_interp_string: 0,
             annotations: None,
+            macro_uses: None,
+            prefetch: None,
         }
     }
 
@@ -129,6 +218,8 @@ impl DefineHistory {
             env_file: self.env_file.clone(),
             include_stack: Default::default(),
             include_locations: Default::default(),
+            record_includes: false,
+            dir_entries: Default::default(),
             multiple_locations: Default::default(),
             history: Default::default(),  // TODO: support branching a second time
             defines: DefineMap::from_history(self, self.last_input_loc),
@@ -138,12 +229,18 @@ impl DefineHistory {
             ifdef_stack: Default::default(),  // should be fine
             ifdef_history: Default::default(),
             last_input_loc: self.last_input_loc,
+            last_input_body: None,
+            last_input_expansion: None,
+            last_input_end: self.last_input_loc,
+            body_locations: self.body_locations.clone(),
             last_printable_input_loc: self.last_input_loc,
             output: Default::default(),
             danger_idents: Default::default(),
             docs_in: Default::default(),
             in_interp_string: 0,
             annotations: None,
+            macro_uses: None,
+            prefetch: None,
         }
     }
 }
@@ -179,6 +276,30 @@ impl DefineMap {
         this
     }
 
+    /// The macros DM always defines, followed by those the configuration
+    /// defines for the environment.
+    pub fn with_configured(context: &Context) -> DefineMap {
+        let mut this = DefineMap::with_builtins();
+        for (name, value) in context.config().defines.iter() {
+            this.define_constant(context, name, value);
+        }
+        this
+    }
+
+    /// Define a constant macro as `#define NAME VALUE` would, located with
+    /// the builtins.
+    pub fn define_constant(&mut self, context: &Context, name: &str, value: &str) {
+        let location = Location::builtins();
+        let subst = Lexer::new(context, location.file, value.as_bytes())
+            .map(|token| token.token)
+            .filter(|token| !matches!(token, Token::Punct(Punctuation::Newline)))
+            .collect();
+        self.insert(name.to_owned(), (location, Define::Constant {
+            subst,
+            docs: Default::default(),
+        }));
+    }
+
     /// Returns the number of elements in the map.
     #[inline]
     pub fn len(&self) -> usize {
@@ -268,9 +389,11 @@ enum Include<'ctx> {
         lexer: Lexer<'ctx>,
     },
     Expansion {
-        //name: Ident,
-        location: Location,
-        tokens: VecDeque<Token>,
+        expansion: Rc<MacroExpansion>,
+        /// Where the macro's use ends, which is where its tokens end too.
+        end: Location,
+        /// Each token, and where in the macro's body it was written.
+        tokens: VecDeque<(Token, Option<Location>)>,
     },
 }
 
@@ -292,6 +415,29 @@ impl<'ctx> Include<'ctx> {
             path,
         }
     }
+
+    /// The input not yet lexed, for files.
+    fn remaining(&self) -> &[u8] {
+        match self {
+            Include::File { lexer, .. } => lexer.remaining(),
+            Include::Expansion { .. } => &[],
+        }
+    }
+}
+
+/// Where the contents of an included file come from.
+enum IncludeSource {
+    Buffer(Vec<u8>),
+    Read(io::BufReader<File>),
+}
+
+impl IncludeSource {
+    fn into_lexer(self, context: &Context, file: FileId) -> Result<Lexer<'_>, DMError> {
+        match self {
+            IncludeSource::Buffer(buffer) => Ok(Lexer::new(context, file, buffer)),
+            IncludeSource::Read(read) => Lexer::from_read(context, file, read),
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -328,11 +474,17 @@ impl<'ctx> Iterator for IncludeStack<'ctx> {
                 },
                 Some(&mut Include::Expansion {
                     ref mut tokens,
-                    location,
-                    ..
+                    ref expansion,
+                    end,
                 }) => {
-                    if let Some(token) = tokens.pop_front() {
-                        return Some(LocatedToken { location, token });
+                    if let Some((token, body_location)) = tokens.pop_front() {
+                        return Some(LocatedToken {
+                            location: expansion.invocation,
+                            token,
+                            body_location,
+                            expansion: Some(expansion.clone()),
+                            end,
+                        });
                     }
                     // else fallthrough to pop()
                 },
@@ -343,6 +495,38 @@ impl<'ctx> Iterator for IncludeStack<'ctx> {
     }
 }
 
+// ----------------------------------------------------------------------------
+// Resolving included and resource file paths
+
+/// The paths an `#include` may refer to, in the order they are tried: first
+/// relative to the including file, then relative to the environment.
+pub fn include_candidates(including_file: &Path, env_file: &Path, path: &str) -> [PathBuf; 2] {
+    let path = PathBuf::from(path.replace('\\', "/"));
+    [
+        including_file.parent().unwrap_or(Path::new("")).join(&path),
+        env_file.parent().unwrap_or(Path::new("")).join(&path),
+    ]
+}
+
+/// Find the file an `#include` refers to, if it exists.
+pub fn resolve_include(including_file: &Path, env_file: &Path, path: &str) -> Option<PathBuf> {
+    include_candidates(including_file, env_file, path).into_iter().find(|candidate| candidate.exists())
+}
+
+/// Find the file a resource literal refers to, if it exists.
+///
+/// Resources are looked for relative to the file they appear in, then the
+/// environment, then each `FILE_DIR`, ignoring case like BYOND does.
+pub fn resolve_resource(file: &Path, env_file: &Path, file_dirs: &[String], path: &str) -> Option<PathBuf> {
+    let path = PathBuf::from(path.replace('\\', "/"));
+    let env_dir = env_file.parent().unwrap_or(Path::new(""));
+    let dirs = [file.parent().unwrap_or(Path::new("")).to_owned(), env_dir.to_owned()];
+    dirs.into_iter()
+        .chain(file_dirs.iter().map(|dir| env_dir.join(dir.replace('\\', "/"))))
+        .map(|dir| crate::fix_case(&dir.join(&path)).into_owned())
+        .find(|candidate| candidate.exists())
+}
+
 // ----------------------------------------------------------------------------
 // The main preprocessor
 
@@ -385,14 +569,28 @@ pub struct Preprocessor<'ctx> {
 
     include_stack: IncludeStack<'ctx>,
     include_locations: HashMap<FileId, Location>,
+    /// Whether to record `#include`s into the context's include graph.
+    record_includes: bool,
+    /// The names in each directory, for checking the case of `#include`s.
+    dir_entries: HashMap<PathBuf, HashSet<OsString>>,
     // list of files with #pragma multiple to allow for more then one include
     // should this be done as an enum in include_locations instead?
     multiple_locations: HashMap<FileId, Location>,
     last_input_loc: Location,
+    /// Where in a macro's body the last input token was written, if anywhere.
+    last_input_body: Option<Location>,
+    /// The macro expansion which produced the last input token, if any.
+    last_input_expansion: Option<Rc<MacroExpansion>>,
+    /// Where the last input token ends.
+    last_input_end: Location,
+    /// Where each token in the body of each macro was written, by the
+    /// location of the macro's definition.
+    body_locations: HashMap<Location, Rc<[Location]>>,
     output: VecDeque<LocatedToken>,
     ifdef_stack: Vec<Ifdef>,
     ifdef_history: IntervalTree<Location, bool>,
     annotations: Option<AnnotationTree>,
+    macro_uses: Option<MacroUses>,
 
     history: InnerDefineHistory,
     defines: DefineMap,
@@ -405,13 +603,26 @@ pub struct Preprocessor<'ctx> {
     in_interp_string: u32,
 
     docs_in: VecDeque<(Location, DocComment)>,
+    /// The files the `.dme` includes, being read in the background.
+    prefetch: Option<Prefetch>,
+}
+
+fn macro_use_tracking(context: &Context) -> Option<MacroUses> {
+    let config = &context.config().dreamchecker;
+    if config.unused_macros || config.unused_includes {
+        Some(Default::default())
+    } else {
+        None
+    }
 }
 
 impl<'ctx> HasLocation for Preprocessor<'ctx> {
+    const COMPONENT: Component = Component::Preprocessor;
+
     fn location(&self) -> Location {
         match self.include_stack.stack.last() {
             Some(Include::File { lexer, .. }) => lexer.location(),
-            Some(&Include::Expansion { location, .. }) => location,
+            Some(Include::Expansion { expansion, .. }) => expansion.invocation,
             None => Location::default()
         }
     }
@@ -423,27 +634,37 @@ impl<'ctx> Preprocessor<'ctx> {
         // Buffer the entire environment file. Large environments take a while
         // to load and locking it for the whole time is somewhat inconvenient.
         let include = Include::from_path(context, env_file.clone())?;
+        let prefetch = Prefetch::start(&env_file, include.remaining());
+        *context.include_graph_mut() = IncludeGraph::new(context.register_file(&env_file));
 
         Ok(Preprocessor {
             context,
             env_file,
             include_stack: IncludeStack { stack: vec![include] },
             include_locations: Default::default(),
+            record_includes: true,
+            dir_entries: Default::default(),
             multiple_locations: Default::default(),
             history: Default::default(),
-            defines: DefineMap::with_builtins(),
+            defines: DefineMap::with_configured(context),
             maps: Default::default(),
             skins: Default::default(),
             scripts: Default::default(),
             ifdef_stack: Default::default(),
             ifdef_history: Default::default(),
             last_input_loc: Default::default(),
+            last_input_body: None,
+            last_input_expansion: None,
+            last_input_end: Default::default(),
+            body_locations: Default::default(),
             last_printable_input_loc: Default::default(),
             output: Default::default(),
             danger_idents: Default::default(),
             docs_in: Default::default(),
             in_interp_string: 0,
             annotations: None,
+            macro_uses: macro_use_tracking(context),
+            prefetch: Some(prefetch),
         })
     }
 
@@ -452,27 +673,74 @@ impl<'ctx> Preprocessor<'ctx> {
             Cow::Borrowed(s) => Cow::Borrowed(s.as_bytes()),
             Cow::Owned(s) => Cow::Owned(s.into_bytes()),
         };
+        // Buffers are not read ahead, as they're usually single files.
         let include = Include::from_buffer(context, env_file.clone(), cow_u8);
+        *context.include_graph_mut() = IncludeGraph::new(context.register_file(&env_file));
         Preprocessor {
             context,
             env_file,
             include_stack: IncludeStack { stack: vec![include] },
             include_locations: Default::default(),
+            record_includes: true,
+            dir_entries: Default::default(),
+            multiple_locations: Default::default(),
+            history: Default::default(),
+            defines: DefineMap::with_configured(context),
+            maps: Default::default(),
+            skins: Default::default(),
+            scripts: Default::default(),
+            ifdef_stack: Default::default(),
+            ifdef_history: Default::default(),
+            last_input_loc: Default::default(),
+            last_input_body: None,
+            last_input_expansion: None,
+            last_input_end: Default::default(),
+            body_locations: Default::default(),
+            last_printable_input_loc: Default::default(),
+            output: Default::default(),
+            danger_idents: Default::default(),
+            docs_in: Default::default(),
+            in_interp_string: 0,
+            annotations: None,
+            macro_uses: macro_use_tracking(context),
+            prefetch: None,
+        }
+    }
+
+    /// Create a preprocessor with no files pushed yet, starting from the given
+    /// macros, for parsing files outside a whole environment.
+    ///
+    /// Unlike `new` and `from_buffer`, this leaves the context's include graph
+    /// alone.
+    pub fn with_defines(context: &'ctx Context, env_file: PathBuf, defines: DefineMap) -> Self {
+        Preprocessor {
+            context,
+            env_file,
+            include_stack: Default::default(),
+            include_locations: Default::default(),
+            record_includes: false,
+            dir_entries: Default::default(),
             multiple_locations: Default::default(),
             history: Default::default(),
-            defines: DefineMap::with_builtins(),
+            defines,
             maps: Default::default(),
             skins: Default::default(),
             scripts: Default::default(),
             ifdef_stack: Default::default(),
             ifdef_history: Default::default(),
             last_input_loc: Default::default(),
+            last_input_body: None,
+            last_input_expansion: None,
+            last_input_end: Default::default(),
+            body_locations: Default::default(),
             last_printable_input_loc: Default::default(),
             output: Default::default(),
             danger_idents: Default::default(),
             docs_in: Default::default(),
             in_interp_string: 0,
             annotations: None,
+            macro_uses: None,
+            prefetch: None,
         }
     }
 
@@ -496,6 +764,10 @@ impl<'ctx> Preprocessor<'ctx> {
             env_file: self.env_file,
             last_input_loc: self.last_input_loc,
             tree: self.history,
+            body_locations: self.body_locations,
+            macro_uses: self.macro_uses,
+            include_locations: self.include_locations,
+            maps: self.maps,
         }
     }
 
@@ -543,6 +815,20 @@ impl<'ctx> Preprocessor<'ctx> {
             return;
         }
 
+        if let Some(replacement) = docs.as_ref().and_then(|docs| docs.deprecated()) {
+            let desc = if replacement.is_empty() {
+                format!("macro {} is deprecated", ident)
+            } else {
+                format!("macro {} is deprecated: {}", ident, replacement)
+            };
+            DMError::new(self.last_input_loc, desc)
+                .with_component(Component::Preprocessor)
+                .set_severity(Severity::Warning)
+                .with_note(definition_location, "deprecated here")
+                .with_errortype("deprecated")
+                .register(self.context);
+        }
+
         if let Some(annotations) = self.annotations.as_mut() {
             annotations.insert(
                 self.last_input_loc .. self.last_input_loc.add_columns(ident.len() as u16),
@@ -555,6 +841,16 @@ impl<'ctx> Preprocessor<'ctx> {
         }
     }
 
+    fn use_macro(&mut self, ident: &str) {
+        if let Some(uses) = self.macro_uses.as_mut() {
+            uses.entry(ident.to_owned()).or_default().insert(self.last_input_loc.file);
+        }
+    }
+
+    fn disabled_code_uses_macros(&self) -> bool {
+        self.macro_uses.is_some() && self.context.config().dreamchecker.disabled_code_uses_macros
+    }
+
     fn in_environment(&self) -> bool {
         for include in self.include_stack.stack.iter().rev() {
             if let Include::File { ref path, .. } = *include {
@@ -593,6 +889,9 @@ impl<'ctx> Preprocessor<'ctx> {
         let start = self.last_input_loc;
         while let Some(tok) = self.inner_next() {
             self.last_input_loc = tok.location;
+            self.last_input_body = tok.body_location;
+            self.last_input_expansion = tok.expansion;
+            self.last_input_end = tok.end;
 
             if let Token::Punct(Punctuation::Newline) = tok.token {
                 break;
@@ -610,7 +909,7 @@ impl<'ctx> Preprocessor<'ctx> {
             return Ok(false);
         }
 
-        let expr = crate::parser::parse_expression(
+        let expr = crate::parser::parse_condition(
             self.context,
             start,
             self.output.drain(..),
@@ -618,12 +917,20 @@ impl<'ctx> Preprocessor<'ctx> {
         Ok(crate::constants::preprocessor_evaluate(start, expr, &self.defines, Some(self.context))?.to_bool())
     }
 
-    fn evaluate(&mut self) -> bool {
+    fn evaluate(&mut self, directive: &str) -> bool {
+        let start = self.last_input_loc;
         // always succeed in order to avoid phantom "unmatched #endif" messages
         match self.evaluate_inner() {
             Ok(value) => value,
             Err(err) => {
-                self.context.register_error(err);
+                // Report at the directive, so that errors from inside macro
+                // expansions still point somewhere useful.
+                let mut error = DMError::new(start, format!("malformed #{} condition: {}", directive, err.description()))
+                    .with_component(Component::Preprocessor);
+                if err.location() != start {
+                    error.add_note(err.location(), "condition fails here");
+                }
+                self.context.register_error(error);
                 false
             }
         }
@@ -646,11 +953,16 @@ impl<'ctx> Preprocessor<'ctx> {
     // ------------------------------------------------------------------------
     // Internal utilities
 
-    fn prepare_include_file(&mut self, path: PathBuf) -> Result<Include<'ctx>, DMError> {
-        // Attempt to open the file.
-        let read = io::BufReader::new(File::open(&path).map_err(|e|
-            DMError::new(self.last_input_loc, format!("failed to open file: #include {:?}", path))
-                .with_cause(e))?);
+    fn prepare_include_file(&mut self, path: PathBuf, written: &str) -> Result<Include<'ctx>, DMError> {
+        // Use the file's contents if they were read ahead, or else attempt to
+        // open the file.
+        let source = match self.take_prefetched(&path, written) {
+            Some(buffer) => IncludeSource::Buffer(buffer),
+            None => IncludeSource::Read(io::BufReader::new(File::open(&path).map_err(|e|
+                DMError::new(self.last_input_loc, format!("failed to open file: #include {:?}", path))
+                    .with_component(Component::Preprocessor)
+                    .with_cause(e))?)),
+        };
 
         // Get the path relative to the environment root, for easy lookup later.
         let register = path.strip_prefix(self.env_file.parent().unwrap()).unwrap_or(&path);
@@ -661,6 +973,7 @@ impl<'ctx> Preprocessor<'ctx> {
         if let Some(&loc) = self.include_locations.get(&file_id) {
             if !self.multiple_locations.contains_key(&file_id) {
                 Err(DMError::new(self.last_input_loc, format!("duplicate #include {:?}", path))
+                    .with_component(Component::Preprocessor)
                     .set_severity(Severity::Warning)
                     .with_note(loc, "previously included here")
                     .with_errortype("duplicate_include"))
@@ -668,7 +981,7 @@ impl<'ctx> Preprocessor<'ctx> {
                 Ok(Include::File {
                     path,
                     //file: file_id,
-                    lexer: Lexer::from_read(self.context, file_id, read)?,
+                    lexer: source.into_lexer(self.context, file_id)?,
                 })
             }
         } else {
@@ -676,22 +989,141 @@ impl<'ctx> Preprocessor<'ctx> {
             Ok(Include::File {
                 path,
                 //file: file_id,
-                lexer: Lexer::from_read(self.context, file_id, read)?,
+                lexer: source.into_lexer(self.context, file_id)?,
             })
         }
     }
 
+    /// Take a file's contents from the prefetch, if it was included directly
+    /// by the `.dme` and read successfully.
+    fn take_prefetched(&mut self, path: &Path, written: &str) -> Option<Vec<u8>> {
+        if self.include_stack.top_file_path() != self.env_file {
+            return None;
+        }
+        let start_time = std::time::Instant::now();
+        let buffer = self.prefetch.as_mut()?.take(written, path);
+        self.context.add_io_time(start_time.elapsed());
+        buffer
+    }
+
+    fn record_include(&mut self, location: Location, written: &str, found: Option<&Path>) {
+        if !self.record_includes {
+            return;
+        }
+        // Code files are registered by the same path, if they were read.
+        let env_dir = self.env_file.parent().unwrap_or(Path::new(""));
+        let path = found.map(|path| path.strip_prefix(env_dir).unwrap_or(path).to_owned());
+        let file = path.as_ref()
+            .filter(|path| path.extension().is_some_and(|ext| ext == "dm" || ext == "dme"))
+            .and_then(|path| self.context.get_file(path));
+        self.context.include_graph_mut().push(IncludeEdge {
+            location,
+            written: written.to_owned(),
+            path,
+            file,
+        });
+    }
+
+    /// Warn on an `#include` which only finds its file because the
+    /// filesystem ignores case, as it would fail on other systems.
+    fn check_include_case(&mut self, location: Location, found: &Path, written: &Path) {
+        let names: Vec<&OsStr> = written.components().rev()
+            .map_while(|component| match component {
+                path::Component::Normal(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+        let mut path = found;
+        for name in names {
+            let Some(dir) = path.parent() else { break };
+            let entries = self.dir_entries.entry(dir.to_owned()).or_insert_with(|| match dir.read_dir() {
+                Ok(entries) => entries.flatten().map(|entry| entry.file_name()).collect(),
+                Err(_) => Default::default(),
+            });
+            if !entries.is_empty() && !entries.contains(name) {
+                if let Some(actual) = entries.iter().find(|each| each.eq_ignore_ascii_case(name)) {
+                    DMError::new(location, format!(
+                        "#include {:?} differs in case from {:?}, and will fail on case-sensitive filesystems",
+                        name, actual,
+                    ))
+                        .with_component(Component::Preprocessor)
+                        .set_severity(Severity::Warning)
+                        .with_errortype("include_case_mismatch")
+                        .register(self.context);
+                }
+                return;
+            }
+            path = dir;
+        }
+    }
+
     fn check_danger_ident(&mut self, name: &str, kind: &str) {
         if let Some(loc) = self.danger_idents.get(name) {
             self.context.register_error(DMError::new(*loc, format!(
                 "macro {:?} used immediately before being {}:\n\
                 https://www.byond.com/forum/?post=2072419", name, kind
-            )).set_severity(Severity::Warning));
+            ))
+                .with_component(Component::Preprocessor)
+                .set_severity(Severity::Warning));
         }
     }
 
+    /// Substitute constant macros into the text of a `#warn` or `#error`.
+    fn substitute_message(&mut self, text: &str, expanding: &mut Vec<String>) -> String {
+        use std::fmt::Write;
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            let (name, after) = rest.split_at(end);
+            rest = after;
+
+            let subst = match self.defines.get(name) {
+                Some((_, Define::Constant { subst, .. })) if !expanding.iter().any(|x| x == name) => subst.clone(),
+                _ => {
+                    output.push_str(name);
+                    continue;
+                }
+            };
+            self.use_macro(name);
+            let mut rendered = String::new();
+            let mut prev = None;
+            for token in subst.iter() {
+                if prev.is_some_and(|prev| token.separate_from(prev)) {
+                    rendered.push(' ');
+                }
+                let _ = write!(rendered, "{}", token);
+                prev = Some(token);
+            }
+            expanding.push(name.to_owned());
+            output.push_str(&self.substitute_message(&rendered, expanding));
+            expanding.pop();
+        }
+        output.push_str(rest);
+        output
+    }
+
+    /// Describe a use of a macro, nested in the expansion which produced
+    /// the macro's name, if any.
+    fn expansion_of(&self, name: &str, invocation: Location, definition: Location) -> Rc<MacroExpansion> {
+        Rc::new(MacroExpansion {
+            name: intern(name),
+            invocation,
+            definition,
+            parent: self.last_input_expansion.clone(),
+        })
+    }
+
     fn push_output(&mut self, token: Token) {
-        self.output.push_back(LocatedToken::new(self.last_input_loc, token));
+        self.output.push_back(LocatedToken {
+            location: self.last_input_loc,
+            token,
+            body_location: self.last_input_body,
+            expansion: self.last_input_expansion.clone(),
+            end: self.last_input_end,
+        });
     }
 
     fn inner_next(&mut self) -> Option<LocatedToken> {
@@ -735,33 +1167,38 @@ impl<'ctx> Preprocessor<'ctx> {
                     // ifdefs
                     "endif" => {
                         self.pop_ifdef().ok_or_else(||
-                            DMError::new(self.last_input_loc, "unmatched #endif"))?;
+                            DMError::new(self.last_input_loc, "unmatched #endif")
+                                .with_component(Component::Preprocessor))?;
                     }
                     "else" => {
                         let last = self.pop_ifdef().ok_or_else(||
-                            DMError::new(self.last_input_loc, "unmatched #else"))?;
+                            DMError::new(self.last_input_loc, "unmatched #else")
+                                .with_component(Component::Preprocessor))?;
                         self.ifdef_stack.push(last.else_(self.last_input_loc));
                     }
                     "ifdef" => {
                         expect_token!((define_name) = Token::Ident(define_name, _));
                         expect_token!(() = Token::Punct(Punctuation::Newline));
+                        self.use_macro(&define_name);
                         let enabled = self.is_defined(&define_name);
                         self.ifdef_stack.push(Ifdef::new(self.last_input_loc, enabled));
                     }
                     "ifndef" => {
                         expect_token!((define_name) = Token::Ident(define_name, _));
                         expect_token!(() = Token::Punct(Punctuation::Newline));
+                        self.use_macro(&define_name);
                         let enabled = !self.is_defined(&define_name);
                         self.ifdef_stack.push(Ifdef::new(self.last_input_loc, enabled));
                     }
                     "if" => {
-                        let enabled = self.evaluate();
+                        let enabled = self.evaluate("if");
                         self.ifdef_stack.push(Ifdef::new(self.last_input_loc, enabled));
                     }
                     "elif" => {
                         let last = self.pop_ifdef().ok_or_else(||
-                            DMError::new(self.last_input_loc, "unmatched #elif"))?;
-                        let enabled = self.evaluate();
+                            DMError::new(self.last_input_loc, "unmatched #elif")
+                                .with_component(Component::Preprocessor))?;
+                        let enabled = self.evaluate("elif");
                         self.ifdef_stack.push(last.else_if(self.last_input_loc, enabled));
                     }
                     // --------------------------------------------------------
@@ -775,15 +1212,8 @@ impl<'ctx> Preprocessor<'ctx> {
                         expect_token!(() = Token::Punct(Punctuation::Newline));
                         let path = PathBuf::from(path_str.replace('\\', "/"));
 
-                        for candidate in [
-                            // 1. relative to file in which `#include` appears.
-                            self.include_stack.top_file_path().parent().unwrap().join(&path),
-                            // 2. relative to root `.dme` file.
-                            self.env_file.parent().unwrap().join(&path)
-                        ] {
-                            if !candidate.exists() {
-                                continue;
-                            }
+                        if let Some(candidate) = resolve_include(self.include_stack.top_file_path(), &self.env_file, &path_str) {
+                            self.check_include_case(include_loc, &candidate, &path);
                             // Double-match is used to let go of the borrow of
                             // `candidate` so it can be used in the second half.
                             // This is how BYOND refers to it's file formats, this is how we should refer to them.
@@ -804,11 +1234,12 @@ impl<'ctx> Preprocessor<'ctx> {
                                     self.context.register_error(DMError::new(
                                         self.last_input_loc,
                                         format!("unknown extension {:?}", ext),
-                                    ));
+                                    ).with_component(Component::Preprocessor));
                                     return Ok(());
                                 }
                                 None => {
-                                    self.context.register_error(DMError::new(self.last_input_loc, "filename has no extension"));
+                                    self.context.register_error(DMError::new(self.last_input_loc, "filename has no extension")
+                                        .with_component(Component::Preprocessor));
                                     return Ok(());
                                 }
                             };
@@ -820,10 +1251,10 @@ impl<'ctx> Preprocessor<'ctx> {
                             }
 
                             match file_type {
-                                FileType::DMM => self.maps.push(candidate),
-                                FileType::DMF => self.skins.push(candidate),
-                                FileType::DMS => self.scripts.push(candidate),
-                                FileType::DM => match self.prepare_include_file(candidate) {
+                                FileType::DMM => self.maps.push(candidate.clone()),
+                                FileType::DMF => self.skins.push(candidate.clone()),
+                                FileType::DMS => self.scripts.push(candidate.clone()),
+                                FileType::DM => match self.prepare_include_file(candidate.clone(), &path_str) {
                                     Ok(include) => {
                                         // A phantom newline keeps the include
                                         // directive being indented from making
@@ -834,20 +1265,46 @@ impl<'ctx> Preprocessor<'ctx> {
                                     Err(e) => self.context.register_error(e),
                                 },
                             }
+                            self.record_include(include_loc, &path_str, Some(&candidate));
 
                             return Ok(());
                         }
 
-                        self.context.register_error(DMError::new(self.last_input_loc, format!("failed to find #include {:?}", path)));
+                        self.record_include(include_loc, &path_str, None);
+
+                        if let Some(annotations) = self.annotations.as_mut() {
+                            annotations.insert(
+                                include_loc .. include_loc.add_columns(2 + path_str.len() as u16),
+                                Annotation::MissingInclude(path.clone()));
+                        }
+                        let env_dir = self.env_file.parent().unwrap_or(Path::new(""));
+                        let mut error = DMError::new(self.last_input_loc, format!("failed to find #include {:?}", path))
+                            .with_component(Component::Preprocessor);
+                        let mut candidates = include_candidates(self.include_stack.top_file_path(), &self.env_file, &path_str).to_vec();
+                        candidates.dedup();
+                        for candidate in candidates {
+                            let tried = candidate.strip_prefix(env_dir).unwrap_or(&candidate);
+                            let found = crate::fix_case(&candidate);
+                            if found != candidate {
+                                let found = found.strip_prefix(env_dir).unwrap_or(&found);
+                                error.add_note(include_loc, format!("{} differs only in case from {}", tried.display(), found.display()));
+                            } else {
+                                error.add_note(include_loc, format!("tried {}", tried.display()));
+                            }
+                        }
+                        self.context.register_error(error);
                         return Ok(());
                     }
                     // both constant and function defines
                     "define" if disabled => {
                         // Skip to the end of the line, or else we'll catch
                         // stringify operators `#X` as unknown directives.
+                        let uses_macros = self.disabled_code_uses_macros();
                         loop {
-                            if let Token::Punct(Punctuation::Newline) = next!() {
-                                break
+                            match next!() {
+                                Token::Punct(Punctuation::Newline) => break,
+                                Token::Ident(name, _) if uses_macros => self.use_macro(&name),
+                                _ => {}
                             }
                         }
                     }
@@ -866,16 +1323,29 @@ impl<'ctx> Preprocessor<'ctx> {
                         self.check_danger_ident(&define_name, "defined");
                         let mut params = Vec::new();
                         let mut subst = Vec::new();
+                        let mut body_locations = Vec::new();
                         let mut variadic = false;
+                        let mut function = false;
+                        // where each identifier in the body was written
+                        let annotate_body = self.annotations.is_some();
+                        let mut body_idents = Vec::new();
                         'outer: {
                             match next!() {
                                 Token::Punct(Punctuation::LParen) if !ws => {
+                                    function = true;
                                     loop {
                                         if variadic {
                                             return Err(self.error("only the last parameter of a macro may be variadic"));
                                         }
                                         match next!() {
-                                            Token::Ident(name, _) => params.push(name),
+                                            // `#define F() ...` takes no arguments
+                                            Token::Punct(Punctuation::RParen) if params.is_empty() => break,
+                                            Token::Ident(name, _) => {
+                                                if annotate_body {
+                                                    body_idents.push((_last_expected_loc, name.clone()));
+                                                }
+                                                params.push(name);
+                                            }
                                             Token::Punct(Punctuation::Ellipsis) => {
                                                 params.push("__VA_ARGS__".to_owned());  // default
                                                 variadic = true;
@@ -909,7 +1379,13 @@ impl<'ctx> Preprocessor<'ctx> {
                                     }
                                     docs.push(doc);
                                 }
+                                Token::Ident(name, ws) if annotate_body => {
+                                    body_idents.push((_last_expected_loc, name.clone()));
+                                    body_locations.push(_last_expected_loc);
+                                    subst.push(Token::Ident(name, ws));
+                                }
                                 other => {
+                                    body_locations.push(_last_expected_loc);
                                     subst.push(other);
                                 }
                             }
@@ -928,22 +1404,50 @@ impl<'ctx> Preprocessor<'ctx> {
                                         }
                                         docs.push(doc);
                                     }
-                                    other => subst.push(other),
+                                    Token::Ident(name, ws) if annotate_body => {
+                                        body_idents.push((_last_expected_loc, name.clone()));
+                                        body_locations.push(_last_expected_loc);
+                                        subst.push(Token::Ident(name, ws));
+                                    }
+                                    other => {
+                                        body_locations.push(_last_expected_loc);
+                                        subst.push(other);
+                                    }
+                                }
+                            }
+                        }
+                        if let Some(annotations) = self.annotations.as_mut() {
+                            annotations.insert(
+                                define_name_loc.add_columns(define_name.len() as u16) .. _last_expected_loc,
+                                Annotation::MacroBody { name: define_name.clone(), params: params.clone() });
+                            for (loc, name) in body_idents {
+                                annotations.insert(
+                                    loc .. loc.add_columns(name.len() as u16),
+                                    Annotation::MacroBodyIdent(name));
+                            }
+                        }
+                        if self.macro_uses.is_some() {
+                            for token in subst.iter() {
+                                match token {
+                                    Token::Ident(name, _) if *name != define_name && !params.contains(name) => self.use_macro(name),
+                                    _ => {}
                                 }
                             }
                         }
-                        let define = if params.is_empty() {
+                        let define = if !function {
                             Define::Constant { subst, docs: Rc::new(docs) }
                         } else {
                             Define::Function { params, subst, variadic, docs: Rc::new(docs) }
                         };
                         // DEBUG can only be defined in the root .dme file
                         if define_name != "DEBUG" || self.in_environment() {
+                            self.body_locations.insert(define_name_loc, body_locations.into());
                             if let Some(previous_loc) = self.defines.insert(define_name.clone(), (define_name_loc, define)) {
-                                // DM doesn't issue a warning for this, but it's usually a mistake, so let's.
+                                // DM warns about this too, as it's usually a mistake.
                                 // FILE_DIR is handled specially and sometimes makes sense to define multiple times.
                                 if define_name != "FILE_DIR" {
                                     DMError::new(define_name_loc, format!("macro redefined: {}", define_name))
+                                        .with_component(Component::Preprocessor)
                                         .set_severity(Severity::Warning)
                                         .with_note(previous_loc, format!("previous definition of {}", define_name))
                                         .with_errortype("macro_redefined")
@@ -962,22 +1466,22 @@ impl<'ctx> Preprocessor<'ctx> {
                             self.move_to_history(define_name, previous);
                         } else {
                             DMError::new(define_name_loc, format!("macro undefined while not defined: {}", define_name))
+                                .with_component(Component::Preprocessor)
                                 .with_errortype("macro_undefined_no_definition")
-                                .set_severity(Severity::Warning)
+                                .set_severity(Severity::Info)
                                 .register(self.context);
                         }
                     }
-                    "warn" if disabled => {}
-                    "warn" => {
-                        expect_token!((text) = Token::String(text));
-                        DMError::new(self.last_input_loc, format!("#{} {}", ident, text.trim_end_matches(['\r', '\n'])))
-                            .set_severity(Severity::Warning)
-                            .register(self.context);
-                    }
-                    "error" if disabled => {}
-                    "error" => {
+                    "warn" | "error" if disabled => {}
+                    "warn" | "error" => {
                         expect_token!((text) = Token::String(text));
-                        self.context.register_error(DMError::new(self.last_input_loc, format!("#{} {}", ident, text.trim_end_matches(['\r', '\n']))));
+                        let message = self.substitute_message(text.trim_end_matches(['\r', '\n']), &mut Vec::new());
+                        let mut error = DMError::new(self.last_input_loc, format!("#{} {}", ident, message))
+                            .with_component(Component::Preprocessor);
+                        if ident == "warn" {
+                            error = error.set_severity(Severity::Warning);
+                        }
+                        self.context.register_error(error);
                     }
                     "pragma" if disabled => {}
                     "pragma" => {
@@ -996,7 +1500,8 @@ impl<'ctx> Preprocessor<'ctx> {
                             }
                         }
                         return Err(DMError::new(self.last_input_loc, format!("unknown directive: #{}{}{}", ident,
-                            if !meant.is_empty() { ", did you mean #" } else { "" }, meant)));
+                            if !meant.is_empty() { ", did you mean #" } else { "" }, meant))
+                            .with_component(Component::Preprocessor));
                     }
                 }
                 // yield a newline
@@ -1004,7 +1509,14 @@ impl<'ctx> Preprocessor<'ctx> {
                 return Ok(());
             }
             // anything other than directives may be ifdef'd out
-            _ if disabled => return Ok(()),
+            _ if disabled => {
+                if let Token::Ident(ref ident, _) = read {
+                    if self.disabled_code_uses_macros() {
+                        self.use_macro(ident);
+                    }
+                }
+                return Ok(());
+            }
             // identifiers may be macros
             Token::Ident(ref ident, whitespace) => {
                 self.flush_docs();
@@ -1036,7 +1548,18 @@ impl<'ctx> Preprocessor<'ctx> {
                     if let Some(idx) = self.output.len().checked_sub(2) {
                         if let Some(LocatedToken { token: Token::Ident(identname, _), .. }) = self.output.get(idx) {
                             if identname.as_str() == "defined" {
-                                self.push_output(Token::Ident(ident.to_owned(), whitespace));
+                                self.use_macro(ident);
+                                if inside_condition {
+                                    // Substitute the whole call before the
+                                    // argument can be expanded, which also
+                                    // lets `defined(__MAIN__)` work.
+                                    expect_token!(() = Token::Punct(Punctuation::RParen));
+                                    let defined = self.is_defined(ident);
+                                    self.output.truncate(idx);
+                                    self.push_output(Token::Int(defined as i32));
+                                } else {
+                                    self.push_output(Token::Ident(ident.to_owned(), whitespace));
+                                }
                                 return Ok(());
                             }
                         }
@@ -1054,14 +1577,18 @@ impl<'ctx> Preprocessor<'ctx> {
                 match expansion {
                     Some((location, Define::Constant { subst, docs })) => {
                         self.annotate_macro(ident, location, Some(docs));
+                        self.use_macro(ident);
+                        let body_locations = self.body_locations.get(&location).cloned().unwrap_or_default();
+                        let body_locations = body_locations.iter().map(|&each| Some(each)).chain(std::iter::repeat(None));
                         self.include_stack.stack.push(Include::Expansion {
-                            //name: ident.to_owned(),
-                            tokens: subst.into_iter().collect(),
-                            location: self.last_input_loc,
+                            expansion: self.expansion_of(ident, self.last_input_loc, location),
+                            tokens: subst.into_iter().zip(body_locations).collect(),
+                            end: self.last_input_end,
                         });
                         return Ok(());
                     }
                     Some((location, Define::Function { ref params, ref subst, variadic, docs })) => {
+                        let name_loc = self.last_input_loc;
                         // if it's not followed by an LParen, it isn't really a function call
                         match next!() {
                             Token::Punct(Punctuation::LParen) => {}
@@ -1077,14 +1604,18 @@ impl<'ctx> Preprocessor<'ctx> {
                             }
                         }
 
+                        let call_loc = self.last_input_loc;
                         self.annotate_macro(ident, location, Some(docs));
+                        self.use_macro(ident);
 
                         // read arguments
                         let mut args = Vec::new();
                         let mut this_arg = Vec::new();
                         let mut parens = 0;
-                        loop {
-                            let token = next!();
+                        let end = loop {
+                            let Some(LocatedToken { token, end, .. }) = self.inner_next() else {
+                                return Err(self.error("unexpected EOF"));
+                            };
                             match token {
                                 Token::Punct(Punctuation::LParen) => {
                                     parens += 1;
@@ -1093,7 +1624,7 @@ impl<'ctx> Preprocessor<'ctx> {
                                 Token::Punct(Punctuation::RParen) => {
                                     if parens == 0 {
                                         args.push(this_arg);
-                                        break;
+                                        break end;
                                     }
                                     parens -= 1;
                                     this_arg.push(token);
@@ -1104,7 +1635,7 @@ impl<'ctx> Preprocessor<'ctx> {
                                 }
                                 _ => this_arg.push(token),
                             }
-                        }
+                        };
 
                         // check for correct number of arguments
                         if variadic {
@@ -1115,61 +1646,77 @@ impl<'ctx> Preprocessor<'ctx> {
                                 args.push(Vec::new());
                             }
                         }
+                        if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+                            args.clear();
+                        }
                         if args.len() != params.len() {
-                            return Err(self.error("wrong number of arguments to macro call"));
+                            return Err(DMError::new(call_loc, format!(
+                                "wrong number of arguments to macro {}: expected {}{}, got {}",
+                                ident,
+                                if variadic { "at least " } else { "" },
+                                if variadic { params.len() - 1 } else { params.len() },
+                                args.len(),
+                            ))
+                                .with_component(Component::Preprocessor)
+                                .with_note(location, format!("{} is defined here", ident)));
                         }
 
-                        // paste them into the expansion
+                        // paste them into the expansion, along with where
+                        // in the body each token was written
+                        let body_locations = self.body_locations.get(&location).cloned();
+                        let body_location = |i: usize| body_locations.as_ref().and_then(|each| each.get(i).copied());
+                        let arg = |i: usize| args[i].iter().cloned().map(|token| (token, None));
                         let mut expansion = VecDeque::new();
-                        let mut input = subst.iter().cloned();
-                        while let Some(token) = input.next() {
+                        let mut input = subst.iter().cloned().enumerate();
+                        while let Some((i, token)) = input.next() {
+                            let here = body_location(i);
                             match token {
                                 // just an ident = expand it
                                 Token::Ident(ident, ws) => match params.iter().position(|x| *x == ident) {
-                                    Some(i) => expansion.extend(args[i].iter().cloned()),
-                                    None => expansion.push_back(Token::Ident(ident, ws)),
+                                    Some(i) => expansion.extend(arg(i)),
+                                    None => expansion.push_back((Token::Ident(ident, ws), here)),
                                 },
                                 // token paste = concat two idents together, if at all possible
                                 Token::Punct(Punctuation::TokenPaste) => {
                                     match (expansion.pop_back(), input.next()) {
-                                        (Some(Token::Ident(first, ws1)), Some(Token::Ident(param_name, ws))) => {
+                                        (Some((Token::Ident(first, ws1), first_loc)), Some((_, Token::Ident(param_name, ws)))) => {
                                             match params.iter().position(|x| *x == param_name) {
                                                 Some(i) => {
-                                                    let mut arg = args[i].iter().cloned();
+                                                    let mut arg = arg(i);
                                                     match arg.next() {
-                                                        Some(Token::Ident(param_ident, ws)) => {
-                                                            expansion.push_back(Token::Ident(
+                                                        Some((Token::Ident(param_ident, ws), _)) => {
+                                                            expansion.push_back((Token::Ident(
                                                                 format!("{}{}", first, param_ident),
                                                                 ws,
-                                                            ));
+                                                            ), first_loc));
                                                         }
-                                                        Some(Token::Int(param_int)) => {
-                                                            expansion.push_back(Token::Ident(
+                                                        Some((Token::Int(param_int), _)) => {
+                                                            expansion.push_back((Token::Ident(
                                                                 format!("{}{}", first, param_int),
                                                                 ws,
-                                                            ))
+                                                            ), first_loc))
                                                         }
                                                         Some(other) => {
-                                                            expansion.push_back(Token::Ident(first, ws1));
+                                                            expansion.push_back((Token::Ident(first, ws1), first_loc));
                                                             expansion.push_back(other);
                                                         }
                                                         None => {}
                                                     }
                                                     expansion.extend(arg);
                                                 }
-                                                None => expansion.push_back(Token::Ident(format!("{}{}", first, param_name), ws)),
+                                                None => expansion.push_back((Token::Ident(format!("{}{}", first, param_name), ws), first_loc)),
                                             }
                                         }
-                                        (non_ident_first, Some(Token::Ident(second, ws))) => {
+                                        (non_ident_first, Some((j, Token::Ident(second, ws)))) => {
                                             expansion.extend(non_ident_first);
                                             match params.iter().position(|x| *x == second) {
-                                                Some(i) => expansion.extend(args[i].iter().cloned()),
-                                                None => expansion.push_back(Token::Ident(second, ws)),
+                                                Some(i) => expansion.extend(arg(i)),
+                                                None => expansion.push_back((Token::Ident(second, ws), body_location(j))),
                                             }
                                         }
                                         (non_ident_first, non_ident_second) => {
                                             expansion.extend(non_ident_first);
-                                            expansion.extend(non_ident_second);
+                                            expansion.extend(non_ident_second.map(|(j, token)| (token, body_location(j))));
                                         }
                                     }
                                     // read the next ident and concat it into the previous ident
@@ -1177,34 +1724,41 @@ impl<'ctx> Preprocessor<'ctx> {
                                 // hash = must be followed by a param name, stringify the whole argument
                                 Token::Punct(Punctuation::Hash) => {
                                     match input.next() {
-                                        Some(Token::Ident(argname, _)) => match params.iter().position(|x| *x == argname) {
+                                        Some((_, Token::Ident(argname, _))) => match params.iter().position(|x| *x == argname) {
                                             Some(i) => {
                                                 let mut string = String::new();
+                                                let mut prev = None;
                                                 for each in &args[i] {
                                                     use std::fmt::Write;
-                                                    if !string.is_empty() {
+                                                    if prev.is_some_and(|prev| each.separate_from(prev)) {
                                                         string.push(' ');
                                                     }
+                                                    prev = Some(each);
                                                     let _e = write!(string, "{}", each);
                                                     #[cfg(debug_assertions)] {
                                                         _e.unwrap();
                                                     }
                                                 }
-                                                expansion.push_back(Token::String(string));
+                                                expansion.push_back((Token::String(string), here));
                                             }
-                                            None => return Err(DMError::new(self.last_input_loc, format!("can't stringify non-argument ident {:?}", argname))),
+                                            None => return Err(DMError::new(self.last_input_loc, format!("can't stringify non-argument ident {:?}", argname))
+                                                .with_component(Component::Preprocessor)),
                                         }
-                                        Some(tok) => return Err(DMError::new(self.last_input_loc, format!("can't stringify non-ident '{}'", tok))),
-                                        None => return Err(DMError::new(self.last_input_loc, "can't stringify EOF")),
+                                        Some((_, tok)) => return Err(DMError::new(self.last_input_loc, format!("can't stringify non-ident '{}'", tok))
+                                            .with_component(Component::Preprocessor)),
+                                        None => return Err(DMError::new(self.last_input_loc, "can't stringify EOF")
+                                            .with_component(Component::Preprocessor)),
                                     }
                                 }
-                                _ => expansion.push_back(token),
+                                _ => expansion.push_back((token, here)),
                             }
                         }
+                        // The expansion is located at the macro's name, rather
+                        // than wherever its arguments happened to end.
                         self.include_stack.stack.push(Include::Expansion {
-                            //name: ident.to_owned(),
+                            expansion: self.expansion_of(ident, name_loc, location),
                             tokens: expansion,
-                            location: self.last_input_loc,
+                            end,
                         });
                         return Ok(());
                     }
@@ -1251,12 +1805,16 @@ impl<'ctx> Iterator for Preprocessor<'ctx> {
 
                 // update last_input_loc and attempt to process the input token
                 self.last_input_loc = tok.location;
+                self.last_input_body = tok.body_location;
+                self.last_input_expansion = tok.expansion;
+                self.last_input_end = tok.end;
                 if let Err(e) = self.real_next(tok.token, false) {
                     self.context.register_error(e);
                 }
             } else {
                 while let Some(ifdef) = self.pop_ifdef() {
-                    self.context.register_error(DMError::new(ifdef.location, "unterminated #if/#ifdef"));
+                    self.context.register_error(DMError::new(ifdef.location, "unterminated #if/#ifdef")
+                        .with_component(Component::Preprocessor));
                 }
                 return None;
             }
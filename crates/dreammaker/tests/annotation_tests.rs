@@ -39,3 +39,72 @@ fn annotation_basic() {
         }
     }
 }
+
+#[test]
+fn annotation_incomplete_call() {
+    let code = r#"
+/proc/test()
+    var/obj/item/I = new /obj/item(src, )
+    I.attack(src, flags = )
+"#.trim();
+
+    let context = Default::default();
+    let lexer = Lexer::new(&context, Default::default(), code.as_bytes());
+    let indent = IndentProcessor::new(&context, lexer);
+    let mut annotations = AnnotationTree::default();
+    Parser::new(&context, indent).parse_annotations_only(&mut annotations);
+
+    let at = |line, column| annotations.get_location(Location {
+        file: Default::default(),
+        line,
+        column,
+    }).map(|(_, annotation)| annotation).collect::<Vec<_>>();
+
+    // the type being created is known inside the `new` arguments
+    assert!(at(2, 41).iter().any(|a| matches!(a, Annotation::NewArguments(path) if path.len() == 2)));
+    // the local is still in scope despite the statement being unfinished
+    assert!(at(3, 27).iter().any(|a| matches!(a, Annotation::LocalVarScope(_, name) if name == "I")));
+    assert!(at(3, 27).iter().any(|a| matches!(a, Annotation::ProcArguments(_, name, _) if name == "attack")));
+}
+
+#[test]
+fn annotation_interpolation() {
+    let code = r#"
+/obj/var/weapon_name
+/obj/var/obj/other
+/obj/proc/hit(mob/target)
+    world << "You hit [target] with [src.weapon_name] and [other.other.weapon_name]!"
+    world << {"Block
+[target] [other.weapon_name]"}
+"#.trim();
+
+    let context = Default::default();
+    let lexer = Lexer::new(&context, Default::default(), code.as_bytes());
+    let indent = IndentProcessor::new(&context, lexer);
+    let mut annotations = AnnotationTree::default();
+    Parser::new(&context, indent).parse_annotations_only(&mut annotations);
+    context.assert_success();
+
+    let at = |line, column| annotations.get_location(Location {
+        file: Default::default(),
+        line,
+        column,
+    }).map(|(_, annotation)| annotation).collect::<Vec<_>>();
+    let unscoped = |line, column, name: &str| at(line, column).iter()
+        .any(|a| matches!(a, Annotation::UnscopedVar(var) if var == name));
+    let scoped = |line, column, prior: &[&str], name: &str| at(line, column).iter()
+        .any(|a| matches!(a, Annotation::ScopedVar(path, var) if path == prior && var == name));
+
+    // several interpolations on one line, each at its own columns
+    assert!(unscoped(4, 24, "target"));
+    assert!(unscoped(4, 29, "target"));
+    assert!(!unscoped(4, 33, "target"));
+    assert!(scoped(4, 42, &["src"], "weapon_name"));
+    assert!(scoped(4, 66, &["other"], "other"));
+    assert!(scoped(4, 75, &["other", "other"], "weapon_name"));
+    assert!(!at(4, 57).iter().any(|a| matches!(a, Annotation::UnscopedVar(_) | Annotation::ScopedVar(..))));
+
+    // and on the later lines of a block string
+    assert!(unscoped(6, 2, "target"));
+    assert!(scoped(6, 20, &["other"], "weapon_name"));
+}
@@ -4,22 +4,9 @@ use core::panic;
 
 use dm::ast::*;
 use dm::constants::*;
-use dm::objtree::ObjectTree;
-use dm::preprocessor::Preprocessor;
-use dm::*;
-
-fn with_code<F: FnOnce(Context, ObjectTree)>(code: &'static str, f: F) {
-    let context = Context::default();
-    let path = std::path::PathBuf::from(r"test.dm");
-    let pp = Preprocessor::from_buffer(&context, path, code.trim());
-    let indents = indents::IndentProcessor::new(&context, pp);
-    let mut parser = parser::Parser::new(&context, indents);
-    parser.enable_procs();
-    let _tree = parser.parse_object_tree();
-
-    f(context, _tree)
-}
 
+mod common;
+use common::with_code;
 
 #[test]
 fn check_semicolon_in_proc_parameters() {
@@ -147,7 +134,7 @@ var/global/bill = 1
             panic!("Third statement was not an expression")
         };
 
-        let Expression::AssignOp { op: _, lhs: _, rhs } = larrys_read else {
+        let Expression::AssignOp { op: _, rhs, .. } = larrys_read else {
             panic!("x = larry::stat was NOT evaluated as an assignment expression")
         };
         let Expression::Base { term: _, ref follow } = **rhs else {
@@ -167,7 +154,7 @@ var/global/bill = 1
             panic!("Fourth statement was not an expression")
         };
 
-        let Expression::AssignOp { op: _, lhs: _, rhs } = proc_ref else {
+        let Expression::AssignOp { op: _, rhs, .. } = proc_ref else {
             panic!("x = /datum/test::reference() was NOT evaluated as an assignment expression")
         };
         let Expression::Base { term: _, ref follow } = **rhs else {
@@ -186,7 +173,7 @@ var/global/bill = 1
             panic!("Fifth statement was not an expression")
         };
 
-        let Expression::AssignOp { op: _, lhs, rhs: _ } = static_set else {
+        let Expression::AssignOp { op: _, lhs, .. } = static_set else {
             panic!("/datum/test::stat = 2 was NOT evaluated as an assignment expression")
         };
         let Expression::Base { term: _, ref follow } = **lhs else {
@@ -221,7 +208,7 @@ var/global/bill = 1
             panic!("Seventh statement was not an expression")
         };
 
-        let Expression::AssignOp { op: _, lhs: _, rhs } = proc_ref else {
+        let Expression::AssignOp { op: _, rhs, .. } = proc_ref else {
             panic!("x = ::bill was NOT evaluated as an assignment expression")
         };
         let Expression::Base { ref term, follow: _ } = **rhs else {
@@ -288,3 +275,102 @@ fn proc_operator_slash() {
         tree.expect("/datum/operator").get_proc("bar").unwrap();
     });
 }
+
+#[test]
+fn proc_signature_types() {
+    with_code("
+/proc/foo() as num
+/proc/bar(mob/user as mob|null in view(1))
+/proc/baz() as /obj/item
+/datum/proc/qux(a, b as text) as num|null
+/datum/child/qux(a, b)
+/obj/item
+", |context, tree| {
+        context.assert_success();
+
+        let foo = tree.root().get_proc("foo").unwrap();
+        assert_eq!(foo.return_type().unwrap().to_string(), "num");
+
+        let bar = tree.root().get_proc("bar").unwrap();
+        assert!(bar.return_type().is_none());
+        let user = &bar.get().parameters[0];
+        assert!(user.in_list.is_some());
+        assert_eq!(user.to_string(), "mob/user as mob|null in view(1)");
+
+        let baz = tree.root().get_proc("baz").unwrap();
+        assert_eq!(baz.return_type().unwrap().to_string(), "/obj/item");
+
+        // Overrides report the return type of the original declaration.
+        let qux = tree.expect("/datum/child").get_proc("qux").unwrap();
+        assert_eq!(qux.return_type().unwrap().to_string(), "num|null");
+    });
+}
+
+#[test]
+fn scope_in_defines() {
+    with_code("
+#define TYPE_VAR(T, V) T::V
+#define TYPE_PROC_REF(T, P) T::P()
+/datum/callback/New(thing, procref)
+/datum/base
+    var/health = 5
+    var/alias = TYPE_VAR(/datum/base, health)
+/datum/base/proc/go()
+/datum/base/child
+    health = parent_type::health + 1
+    var/copy = src::health
+/datum/base/child/go()
+    var/x = new /datum/callback(src, TYPE_PROC_REF(/datum/base, go))
+    return x
+", |context, tree| {
+        context.assert_success();
+
+        let base = tree.expect("/datum/base");
+        assert_eq!(base.get_value("alias").unwrap().constant, Some(Constant::Float(5.)));
+        let child = tree.expect("/datum/base/child");
+        assert_eq!(child.get_value("health").unwrap().constant, Some(Constant::Float(6.)));
+        assert_eq!(child.get_value("copy").unwrap().constant, Some(Constant::Float(6.)));
+
+        let go = child.get_proc("go").unwrap();
+        let code = go.code.as_ref().unwrap();
+        let Statement::Var(callback) = &code[0].elem else {
+            panic!("first statement was not a var")
+        };
+        let Some(Expression::Base { term, .. }) = &callback.value else {
+            panic!("callback was not a base expression")
+        };
+        let Term::NewPrefab { args: Some(args), .. } = &term.elem else {
+            panic!("callback was not a new call")
+        };
+        let Expression::Base { follow, .. } = &args[1] else {
+            panic!("TYPE_PROC_REF was not a base expression")
+        };
+        match &follow[0].elem {
+            Follow::ProcReference(name) => assert_eq!(name, "go"),
+            other => panic!("TYPE_PROC_REF was not a proc reference: {:?}", other),
+        }
+    });
+}
+
+#[test]
+fn interp_string_part_locations() {
+    with_code("
+/proc/f(a, b)
+    return {\"first [a]
+second
+    third [b] end\"}
+", |context, tree| {
+        context.assert_success();
+
+        let f = tree.root().get_proc("f").unwrap();
+        let code = f.code.as_ref().unwrap();
+        let Statement::Return(Some(Expression::Base { term, .. })) = &code[0].elem else {
+            panic!("expected a return of a term")
+        };
+        let Term::InterpString(_, parts) = &term.elem else {
+            panic!("expected an interpolated string")
+        };
+        let spans: Vec<_> = parts.iter().map(|(expr, _)| (expr.location.line, expr.location.column)).collect();
+        assert_eq!(spans, vec![(2, 21), (4, 12)]);
+    });
+}
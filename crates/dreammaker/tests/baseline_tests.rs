@@ -0,0 +1,92 @@
+extern crate dreammaker as dm;
+
+use std::path::Path;
+
+use dm::baseline::{fingerprint, Baseline};
+use dm::Context;
+
+fn parse(dme: &Path) -> Context {
+    let context = Context::default();
+    let pp = dm::preprocessor::Preprocessor::new(&context, dme.to_owned()).unwrap();
+    let mut parser = dm::parser::Parser::new(&context, dm::indents::IndentProcessor::new(&context, pp));
+    parser.enable_procs();
+    parser.parse_object_tree();
+    context
+}
+
+fn descriptions(context: &Context) -> Vec<String> {
+    context.errors().iter().map(|error| format!("{}:{}", error.location().line, error.description())).collect()
+}
+
+#[test]
+fn fingerprints() {
+    let unused = fingerprint("SDM0601", "    var/a", "unused var: \"a\"");
+    assert_eq!(unused, fingerprint("SDM0601", "var/a", "unused var: \"a\" (reworded)"));
+    assert_ne!(unused, fingerprint("SDM0601", "var/a", "unused var: \"b\""));
+    assert_ne!(unused, fingerprint("SDM0603", "var/a", "unused var: \"a\""));
+    // Baselines are committed, so this must never change.
+    assert_eq!(unused, "761a92d42294762a");
+}
+
+#[test]
+fn baseline_tolerates_drift() {
+    let dir = std::env::temp_dir().join(format!("baseline_tests_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dme = dir.join("test.dme");
+    let code = dir.join("code.dm");
+    let baseline_file = dir.join("baseline.txt");
+    std::fs::write(&dme, "#include \"code.dm\"\n").unwrap();
+
+    std::fs::write(&code, "/proc/first()\n    var/tmp/a = 1\n    return a\n").unwrap();
+    let context = parse(&dme);
+    assert_eq!(descriptions(&context), vec!["2:var/tmp has no effect here"]);
+    Baseline::from_errors(&context, &dir).write(&baseline_file).unwrap();
+    let baseline = Baseline::read(&baseline_file).unwrap();
+    assert_eq!(baseline.entries().len(), 1);
+    assert_eq!(baseline.entries()[0].file, "code.dm");
+
+    // Moving the line doesn't matter, but a new finding is still reported.
+    std::fs::write(&code, "/proc/second()\n    var/tmp/b = 1\n    return b\n\n/proc/first()\n    var/tmp/a = 1\n    return a\n").unwrap();
+    let context = parse(&dme);
+    assert!(baseline.apply(&context, &dir).is_empty());
+    assert_eq!(descriptions(&context), vec!["2:var/tmp has no effect here"]);
+
+    // Fixing the finding leaves the entry stale.
+    std::fs::write(&code, "/proc/first()\n    var/a = 1\n    return a\n").unwrap();
+    let context = parse(&dme);
+    let stale = baseline.apply(&context, &dir);
+    assert_eq!(stale.len(), 1);
+    assert_eq!(stale[0].description, "var/tmp has no effect here");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn baseline_uses_unsaved_contents() {
+    let dir = std::env::temp_dir().join(format!("baseline_tests_unsaved_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let baseline_file = dir.join("baseline.txt");
+    let saved = "/proc/first()\n    var/tmp/a = 1\n    return a\n";
+    std::fs::write(dir.join("code.dm"), saved).unwrap();
+
+    let parse_buffer = |contents: &str| {
+        let context = Context::default();
+        let pp = dm::preprocessor::Preprocessor::from_buffer(&context, dir.join("code.dm"), contents);
+        let mut parser = dm::parser::Parser::new(&context, dm::indents::IndentProcessor::new(&context, pp));
+        parser.enable_procs();
+        parser.parse_object_tree();
+        context
+    };
+    let context = parse_buffer(saved);
+    Baseline::from_errors(&context, &dir).write(&baseline_file).unwrap();
+    let baseline = Baseline::read(&baseline_file).unwrap();
+
+    // The finding moved in the editor, but not on disk.
+    let edited = "/proc/second()\n    return\n\n/proc/first()\n    var/tmp/a = 1\n    return a\n";
+    let context = parse_buffer(edited);
+    let file = context.get_file(&dir.join("code.dm")).unwrap();
+    baseline.apply_to_file(&context, &dir, file, edited);
+    assert_eq!(descriptions(&context), Vec::<String>::new());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -2,7 +2,71 @@ extern crate dreammaker as dm;
 
 use dm::objtree::ObjectTree;
 
+mod common;
+use common::with_code;
+
 #[test]
 fn check_builtins() {
     println!("{:?}", ObjectTree::with_builtins());
 }
+
+#[test]
+fn redeclare_builtin_proc() {
+    with_code("
+/datum/proc/Topic(href, href_list)
+    return ..()
+", |context, tree| {
+        context.assert_success();
+        let topic = tree.expect("/datum").get_proc("Topic").unwrap();
+        assert!(topic.get_declaration().unwrap().location.is_builtins());
+        assert!(!topic.get().location.is_builtins());
+    });
+}
+
+#[test]
+fn redeclare_builtin_proc_as_verb() {
+    with_code("
+/mob/verb/Login()
+    return ..()
+/client/proc/Topic(href, href_list) as text
+    return ..()
+", |context, tree| {
+        context.assert_success();
+        let login = tree.expect("/mob").get_proc_declaration("Login").unwrap();
+        assert!(login.location.is_builtins());
+        assert_eq!(login.kind, dm::ast::ProcDeclKind::Verb);
+        let topic = tree.expect("/client").get_proc_declaration("Topic").unwrap();
+        assert!(!topic.return_type.is_empty());
+    });
+}
+
+#[test]
+fn redeclare_builtin_proc_twice() {
+    with_code("
+/datum/proc/Topic(href, href_list)
+    return ..()
+/datum/proc/Topic(href, href_list)
+    return ..()
+", |context, _| {
+        let errors = context.errors();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].description(), "duplicate definition of proc/Topic");
+        assert_eq!(errors[0].location().line, 3);
+        assert_eq!(errors[0].notes()[0].location().line, 1);
+    });
+}
+
+#[test]
+fn redeclare_builtin_var() {
+    with_code("
+/atom/var/loc
+/atom/var/name = \"thing\"
+", |context, tree| {
+        context.assert_success();
+        let atom = tree.expect("/atom");
+        let loc = atom.get_var_declaration("loc").unwrap();
+        assert!(!loc.location.is_builtins());
+        assert_eq!(&*loc.var_type.type_path, &["atom".to_owned()]);
+        assert!(atom.get_var_declaration("name").unwrap().var_type.type_path.is_empty());
+    });
+}
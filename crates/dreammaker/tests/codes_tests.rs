@@ -0,0 +1,65 @@
+extern crate dreammaker as dm;
+
+use dm::codes::*;
+use dm::config::glob_matches;
+
+#[test]
+fn codes_are_unique() {
+    for (i, each) in CODES.iter().enumerate() {
+        for other in &CODES[i + 1..] {
+            assert_ne!(each.code, other.code);
+            assert_ne!(each.errortype, other.errortype);
+        }
+    }
+}
+
+#[test]
+fn every_errortype_has_code() {
+    let crates = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let mut found = 0;
+    for entry in walkdir::WalkDir::new(crates) {
+        let entry = entry.unwrap();
+        if entry.path().extension().is_none_or(|ext| ext != "rs") || entry.path().components().any(|c| c.as_os_str() == "target") {
+            continue;
+        }
+        let source = std::fs::read_to_string(entry.path()).unwrap();
+        for (_, rest) in source.match_indices("with_errortype(\"").map(|(i, _)| source.split_at(i + 16)) {
+            let errortype = &rest[..rest.find('"').unwrap()];
+            assert!(code_for(errortype).is_some(), "{} in {} has no code", errortype, entry.path().display());
+            found += 1;
+        }
+    }
+    assert!(found > 0);
+}
+
+#[test]
+fn diagnostic_names() {
+    assert_eq!(errortype_for("unused_var"), Some("unused_var"));
+    assert_eq!(errortype_for("unused-var"), Some("unused_var"));
+    assert_eq!(errortype_for("SDM0601"), Some("unused_var"));
+    assert_eq!(errortype_for("sdm0601"), Some("unused_var"));
+    assert_eq!(errortype_for("unused"), None);
+    assert_eq!(code_for("dead_store"), Some("SDM0603"));
+}
+
+#[test]
+fn suppression_comments() {
+    assert_eq!(parse_suppression(" SDMM: ignore(unused-var)"), Some((vec!["unused_var"], vec![])));
+    assert_eq!(
+        parse_suppression(" SDMM: ignore(SDM0603, bogus) because"),
+        Some((vec!["dead_store"], vec!["bogus".to_owned()])),
+    );
+    assert_eq!(parse_suppression(" SDMM: ignore"), None);
+    assert_eq!(parse_suppression(" ignore(unused_var)"), None);
+}
+
+#[test]
+fn globs() {
+    assert!(glob_matches("code/*.dm", "code/foo.dm"));
+    assert!(!glob_matches("code/*.dm", "code/modules/foo.dm"));
+    assert!(glob_matches("code/**/*.dm", "code/foo.dm"));
+    assert!(glob_matches("code/**/*.dm", "code/modules/mob/foo.dm"));
+    assert!(glob_matches("code/**", "code/modules/foo.dmm"));
+    assert!(glob_matches("**/legacy_?.dm", "code/legacy_1.dm"));
+    assert!(!glob_matches("code/**", "maps/foo.dmm"));
+}
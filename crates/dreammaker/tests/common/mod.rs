@@ -0,0 +1,17 @@
+//! Helpers shared by the integration tests.
+
+use dreammaker as dm;
+
+use dm::objtree::ObjectTree;
+
+/// Parse some code, procs included, and hand its context and object tree to
+/// the given closure.
+pub fn with_code<F: FnOnce(dm::Context, ObjectTree)>(code: &'static str, f: F) {
+    let context = dm::Context::default();
+    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "test.dm".into(), code.trim());
+    let indents = dm::indents::IndentProcessor::new(&context, pp);
+    let mut parser = dm::parser::Parser::new(&context, indents);
+    parser.enable_procs();
+    let tree = parser.parse_object_tree();
+    f(context, tree)
+}
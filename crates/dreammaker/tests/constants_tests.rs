@@ -118,3 +118,52 @@ fn no_fexists_outside_preproc() {
         "non-constant function call: fexists",
     );
 }
+
+type Notes = Vec<(u32, String)>;
+
+fn tree_errors(code: &str) -> Vec<(u32, String, Notes)> {
+    let context = dm::Context::default();
+    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "unit_tests.dm".into(), code.trim());
+    let indents = dm::indents::IndentProcessor::new(&context, pp);
+    dm::parser::Parser::new(&context, indents).parse_object_tree();
+    let errors = context.errors();
+    errors.iter()
+        .map(|error| (
+            error.location().line,
+            error.description().to_owned(),
+            error.notes().iter().map(|note| (note.location().line, note.description().to_owned())).collect(),
+        ))
+        .collect()
+}
+
+#[test]
+fn evaluation_chain() {
+    let errors = tree_errors(r#"
+/obj
+    var/a = b + 1
+    var/b = c * 2
+    var/c = MISSPELT
+    var/d = a
+"#);
+    // Reported once, at the failing initializer, and not as a recursive
+    // reference the next time a var reaches it.
+    assert_eq!(errors, vec![
+        (4, "unknown variable: MISSPELT".to_owned(), vec![
+            (3, "c is referenced here".to_owned()),
+            (2, "b is referenced here".to_owned()),
+        ]),
+    ]);
+}
+
+#[test]
+fn recursive_reference() {
+    let errors = tree_errors(r#"
+/obj
+    var/a = b
+    var/b = a
+"#);
+    assert_eq!(errors[0], (2, "recursive constant reference: a".to_owned(), vec![
+        (3, "a is referenced here".to_owned()),
+        (2, "b is referenced here".to_owned()),
+    ]));
+}
@@ -0,0 +1,75 @@
+extern crate dreammaker as dm;
+
+use std::path::Path;
+
+use dm::preprocessor::Preprocessor;
+
+const SOURCE: &str = "
+#ifdef UNIT_TESTS
+/datum/unit_test
+#endif
+#if MAX_PLAYERS > 50
+/datum/crowded
+#endif
+";
+
+fn parse(context: &dm::Context) -> dm::objtree::ObjectTree {
+    let pp = Preprocessor::from_buffer(context, "define_tests.dm".into(), SOURCE);
+    let indents = dm::indents::IndentProcessor::new(context, pp);
+    let tree = dm::parser::Parser::new(context, indents).parse_object_tree();
+    context.assert_success();
+    tree
+}
+
+#[test]
+fn defines_select_branches() {
+    let context = dm::Context::default();
+    let tree = parse(&context);
+    assert!(tree.find("/datum/unit_test").is_none());
+    assert!(tree.find("/datum/crowded").is_none());
+
+    let mut context = dm::Context::default();
+    context.add_define("UNIT_TESTS".to_owned(), String::new());
+    context.add_define("MAX_PLAYERS".to_owned(), "80".to_owned());
+    let tree = parse(&context);
+    assert!(tree.find("/datum/unit_test").is_some());
+    assert!(tree.find("/datum/crowded").is_some());
+}
+
+#[test]
+fn defines_from_config() {
+    let dme = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/defines/defines.dme");
+    let parse = |context: &dm::Context| {
+        let mut pp = Preprocessor::new(context, dme.clone()).unwrap();
+        let tree = {
+            let indents = dm::indents::IndentProcessor::new(context, &mut pp);
+            dm::parser::Parser::new(context, indents).parse_object_tree()
+        };
+        context.assert_success();
+        (tree, pp.finalize())
+    };
+
+    let mut context = dm::Context::default();
+    context.autodetect_config(&dme);
+    let (tree, history) = parse(&context);
+    assert!(tree.find("/datum/unit_test").is_some());
+    assert!(tree.find("/datum/quiet").is_some());
+    assert!(tree.find("/datum/crowded").is_none());
+
+    // They are defined with the builtins, and their uses are tracked.
+    let definitions = history.definitions("MAX_PLAYERS");
+    assert_eq!(definitions.len(), 1);
+    assert!(definitions[0].0.is_builtins());
+    let uses = history.macro_uses().unwrap();
+    let file = context.get_file(&dme).unwrap();
+    assert!(uses["UNIT_TESTS"].contains(&file));
+    assert!(uses["MAX_PLAYERS"].contains(&file));
+
+    // Defines added afterward replace those in the config.
+    let mut context = dm::Context::default();
+    context.autodetect_config(&dme);
+    context.add_define("MAX_PLAYERS".to_owned(), "80".to_owned());
+    let (tree, _) = parse(&context);
+    assert!(tree.find("/datum/crowded").is_some());
+    assert!(tree.find("/datum/quiet").is_none());
+}
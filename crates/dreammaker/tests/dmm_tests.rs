@@ -0,0 +1,95 @@
+extern crate dreammaker as dm;
+
+use dm::dmm::*;
+use dm::{FileId, Location};
+
+fn loc(line: u32, column: u16) -> Location {
+    Location { file: FileId::default(), line, column }
+}
+
+fn path_str(prefab: &Prefab) -> String {
+    prefab.path.iter().map(|part| format!("/{}", part)).collect()
+}
+
+fn summarize(text: &str) -> Vec<(String, Vec<(String, String)>)> {
+    parse_dictionary(text)
+        .into_iter()
+        .map(|prefab| {
+            let vars = prefab.vars.iter().map(|var| (var.name.clone(), var.value.clone())).collect();
+            (path_str(&prefab), vars)
+        })
+        .collect()
+}
+
+#[test]
+fn standard_and_tgm() {
+    let standard = concat!(
+        "\"aa\" = (/obj/structure/table{dir = 4; name = \"a; b}\"},/turf/floor,/area/station)\n",
+        "\"ab\" = (/obj/item{list_var = list(\"x\" = 1, \"y\" = 2)},/turf/floor,/area/station)\n",
+        "\n",
+        "(1,1,1) = {\"\n",
+        "aaab\n",
+        "\"}\n",
+    );
+    let tgm = concat!(
+        "//MAP CONVERTED BY dmm2tgm.py THIS HEADER COMMENT PREVENTS RECONVERSION, DO NOT REMOVE\n",
+        "\"aa\" = (\n",
+        "/obj/structure/table{\n",
+        "\tdir = 4;\n",
+        "\tname = \"a; b}\"\n",
+        "\t},\n",
+        "/turf/floor,\n",
+        "/area/station)\n",
+        "\"ab\" = (\n",
+        "/obj/item{\n",
+        "\tlist_var = list(\"x\" = 1, \"y\" = 2)\n",
+        "\t},\n",
+        "/turf/floor,\n",
+        "/area/station)\n",
+        "\n",
+        "(1,1,1) = {\"\n",
+        "aa\n",
+        "ab\n",
+        "\"}\n",
+    );
+    let expected = vec![
+        ("/obj/structure/table".to_owned(), vec![
+            ("dir".to_owned(), "4".to_owned()),
+            ("name".to_owned(), "\"a; b}\"".to_owned()),
+        ]),
+        ("/turf/floor".to_owned(), vec![]),
+        ("/area/station".to_owned(), vec![]),
+        ("/obj/item".to_owned(), vec![
+            ("list_var".to_owned(), "list(\"x\" = 1, \"y\" = 2)".to_owned()),
+        ]),
+        ("/turf/floor".to_owned(), vec![]),
+        ("/area/station".to_owned(), vec![]),
+    ];
+    assert_eq!(summarize(standard), expected);
+    assert_eq!(summarize(tgm), expected);
+}
+
+#[test]
+fn symbols() {
+    let text = "\"aa\" = (\n/obj/structure/table{\n\tdir = 4\n\t},\n/area)\n";
+    let prefabs = parse_dictionary(text);
+    assert_eq!(prefabs[0].span, loc(2, 1)..loc(2, 21));
+    assert_eq!(prefabs[0].vars[0].span, loc(3, 2)..loc(3, 5));
+
+    match symbol_at(&prefabs, loc(2, 10)) {
+        Some(Symbol::Prefab(prefab)) => assert_eq!(path_str(prefab), "/obj/structure/table"),
+        other => panic!("{:?}", other),
+    }
+    match symbol_at(&prefabs, loc(3, 4)) {
+        Some(Symbol::Var(prefab, var)) => {
+            assert_eq!(path_str(prefab), "/obj/structure/table");
+            assert_eq!(var.name, "dir");
+        }
+        other => panic!("{:?}", other),
+    }
+    match symbol_at(&prefabs, loc(5, 3)) {
+        Some(Symbol::Prefab(prefab)) => assert_eq!(path_str(prefab), "/area"),
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(symbol_at(&prefabs, loc(3, 8)), None);
+}
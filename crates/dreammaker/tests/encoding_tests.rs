@@ -0,0 +1,77 @@
+extern crate dreammaker as dm;
+
+use std::path::Path;
+
+use dm::Severity;
+
+fn fixture(name: &str) -> (dm::Context, dm::objtree::ObjectTree) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/encoding").join(name);
+    let context = dm::Context::default();
+    let tree = context.parse_environment(&path).expect("fixture should be readable");
+    (context, tree)
+}
+
+fn position(location: dm::Location) -> (u32, u16) {
+    (location.line, location.column)
+}
+
+fn string_value<'a>(tree: &'a dm::objtree::ObjectTree, path: &str, var: &str) -> &'a str {
+    tree.expect(path).get_value(var).unwrap().constant.as_ref().unwrap().as_str().unwrap()
+}
+
+#[test]
+fn byte_order_mark_is_skipped() {
+    let (context, tree) = fixture("bom.dm");
+    context.assert_success();
+    assert!(context.errors().is_empty());
+
+    let sword = tree.expect("/obj/item/sword");
+    assert_eq!(position(sword.location), (1, 11));
+    assert_eq!(position(sword.get_value("name").unwrap().location), (2, 2));
+    assert_eq!(string_value(&tree, "/obj/item/sword", "name"), "sword");
+}
+
+#[test]
+fn crlf_counts_as_one_line_ending() {
+    let (context, tree) = fixture("crlf.dm");
+    context.assert_success();
+    assert!(context.errors().is_empty());
+
+    let axe = tree.expect("/obj/item/axe");
+    assert_eq!(position(axe.location), (1, 11));
+    assert_eq!(position(axe.get_value("name").unwrap().location), (2, 2));
+    assert_eq!(position(axe.get_value("sharp").unwrap().location), (3, 6));
+    assert_eq!(position(axe.get_proc("chop").unwrap().location), (5, 20));
+    assert_eq!(string_value(&tree, "/obj/item/axe", "name"), "axe");
+
+    // Every token is where it would be with `\n` line endings.
+    let crlf = std::fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/encoding/crlf.dm")).unwrap();
+    let lf = String::from_utf8(crlf.clone()).unwrap().replace("\r\n", "\n");
+    let lex = |bytes: &[u8]| {
+        let context = dm::Context::default();
+        dm::lexer::Lexer::new(&context, Default::default(), bytes)
+            .map(|token| (position(token.location), token.token))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(lex(&crlf), lex(lf.as_bytes()));
+}
+
+#[test]
+fn invalid_utf8_is_replaced_with_warnings() {
+    let (context, tree) = fixture("cp1251.dm");
+    let warnings: Vec<_> = context.errors().iter()
+        .map(|error| (position(error.location()), error.severity(), error.errortype(), error.description().to_owned()))
+        .collect();
+    assert_eq!(warnings, [
+        ((2, 1), Severity::Warning, Some("invalid_utf8"), "doc comment is not valid UTF-8, invalid bytes were replaced".to_owned()),
+        ((4, 9), Severity::Warning, Some("invalid_utf8"), "string is not valid UTF-8, invalid bytes were replaced".to_owned()),
+    ]);
+
+    // The rest of the file is unaffected.
+    let blade = tree.expect("/obj/item/blade");
+    assert_eq!(position(blade.location), (3, 11));
+    assert_eq!(blade.docs.text(), "\u{FFFD}\u{FFFD}\u{FFFD}");
+    assert_eq!(string_value(&tree, "/obj/item/blade", "name"), "\u{FFFD}".repeat(6));
+    assert_eq!(position(blade.get_value("desc").unwrap().location), (5, 2));
+    assert_eq!(string_value(&tree, "/obj/item/blade", "desc"), "sharp");
+}
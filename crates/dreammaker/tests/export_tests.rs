@@ -0,0 +1,163 @@
+extern crate dreammaker as dm;
+
+use dm::export::*;
+
+fn export(code: &'static str, options: &ExportOptions) -> TreeExport {
+    let context = dm::Context::default();
+    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "export.dm".into(), code.trim());
+    let indents = dm::indents::IndentProcessor::new(&context, pp);
+    let mut parser = dm::parser::Parser::new(&context, indents);
+    parser.enable_procs();
+    let tree = parser.parse_object_tree();
+    context.assert_success();
+    export_object_tree(&context, &tree, options)
+}
+
+const CODE: &str = "
+/obj/item
+    var/const/max_health = 10
+    var/obj/item/other = /obj/item
+/obj/item/proc/attack(mob/user as mob) as num
+/obj/item/sword/attack(mob/user)
+    return ..()
+/obj/item/axe
+/obj/other
+";
+
+#[test]
+fn export_prefix() {
+    let export = export(CODE, &ExportOptions {
+        prefix: Some("/obj/item/".to_owned()),
+        .. Default::default()
+    });
+    assert_eq!(export.schema_version, SCHEMA_VERSION);
+    let paths: Vec<_> = export.types.iter().map(|ty| ty.path.as_str()).collect();
+    assert_eq!(paths, ["/obj/item", "/obj/item/axe", "/obj/item/sword"]);
+
+    let item = &export.types[0];
+    assert_eq!(item.parent.as_deref(), Some("/obj"));
+    assert_eq!(item.children, ["/obj/item/axe", "/obj/item/sword"]);
+    assert_eq!(item.location.file, "export.dm");
+
+    let max_health = &item.vars["max_health"];
+    assert!(max_health.declared);
+    assert_eq!(max_health.flags, ["const"]);
+    assert_eq!(max_health.value.as_deref(), Some("10"));
+    assert_eq!(item.vars["other"].type_path.as_deref(), Some("/obj/item"));
+
+    let attack = &item.procs["attack"];
+    assert_eq!(attack.kind.as_deref(), Some("proc"));
+    assert_eq!(attack.return_type.as_deref(), Some("num"));
+    assert_eq!(attack.definitions[0].parameters, ["mob/user as mob"]);
+
+    let sword_attack = &export.types[2].procs["attack"];
+    assert_eq!(sword_attack.kind, None);
+    assert_eq!(sword_attack.definitions[0].location.line, 5);
+}
+
+#[test]
+fn export_skip_constants() {
+    let export = export(CODE, &ExportOptions {
+        prefix: Some("/obj/item".to_owned()),
+        skip_constants: true,
+    });
+    assert!(export.types.iter().all(|ty| ty.vars.values().all(|var| var.value.is_none())));
+}
+
+#[test]
+fn export_builtins() {
+    let export = export("", &ExportOptions {
+        prefix: Some("/datum".to_owned()),
+        .. Default::default()
+    });
+    let datum = &export.types[0];
+    assert_eq!(datum.path, "/datum");
+    assert_eq!(datum.parent.as_deref(), Some(""));
+    assert_eq!(datum.procs["New"].definitions[0].location.file, "(builtins)");
+}
+
+fn parse(code: &'static str) -> dm::objtree::ObjectTree {
+    let context = dm::Context::default();
+    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "diff.dm".into(), code.trim());
+    let indents = dm::indents::IndentProcessor::new(&context, pp);
+    let tree = dm::parser::Parser::new(&context, indents).parse_object_tree();
+    context.assert_success();
+    tree
+}
+
+#[test]
+fn diff_trees() {
+    let old = parse("
+#define BASE_HEALTH 10
+/obj/item
+    var/max_health = BASE_HEALTH
+    var/other
+/obj/item/proc/attack(mob/user)
+/obj/item/sword
+/obj/item/sword/attack(mob/user)
+/obj/item/axe
+");
+    let new = parse("
+#define BASE_HEALTH 5 * 3
+/obj/item
+    var/max_health = BASE_HEALTH
+    var/sharp = 1
+/obj/item/proc/attack(mob/user, params)
+/obj/item/proc/throw_at()
+/obj/item/sword
+    parent_type = /obj
+/obj/item/sword/attack(mob/user)
+/obj/item/shield
+");
+    let diff = diff_object_trees(&old, &new, &Default::default());
+    assert_eq!(diff.added_types, ["/obj/item/shield"]);
+    assert_eq!(diff.removed_types, ["/obj/item/axe"]);
+    let paths: Vec<_> = diff.changed_types.iter().map(|ty| ty.path.as_str()).collect();
+    assert_eq!(paths, ["/obj/item", "/obj/item/sword"]);
+
+    let item = &diff.changed_types[0];
+    assert!(item.parent.is_none());
+    assert_eq!(item.added_vars, ["sharp"]);
+    assert_eq!(item.removed_vars, ["other"]);
+    let max_health = &item.changed_vars["max_health"];
+    assert_eq!((max_health.old.as_deref(), max_health.new.as_deref()), (Some("10"), Some("15")));
+    assert_eq!(item.added_procs, ["throw_at"]);
+    let attack = &item.changed_procs["attack"];
+    assert_eq!((&*attack.old, &*attack.new), (&["mob/user".to_owned()][..], &["mob/user".to_owned(), "params".to_owned()][..]));
+
+    let sword = &diff.changed_types[1];
+    let parent = sword.parent.as_ref().unwrap();
+    assert_eq!((parent.old.as_str(), parent.new.as_str()), ("/obj/item", "/obj"));
+    assert!(sword.changed_vars.is_empty());
+
+    assert_eq!(diff.to_string(), "\
++ /obj/item/shield
+- /obj/item/axe
+/obj/item
+  + var/sharp
+  - var/other
+    var/max_health: 10 -> 15
+  + proc/throw_at
+    proc/attack(mob/user) -> (mob/user, params)
+/obj/item/sword
+    parent_type: /obj/item -> /obj
+");
+}
+
+#[test]
+fn diff_inherited_values() {
+    let old = parse("
+/obj/item
+    var/force = 1
+/obj/item/sword
+");
+    let new = parse("
+/obj/item
+    var/force = 2
+/obj/item/sword
+");
+    let diff = diff_object_trees(&old, &new, &Default::default());
+    let paths: Vec<_> = diff.changed_types.iter().map(|ty| ty.path.as_str()).collect();
+    assert_eq!(paths, ["/obj/item", "/obj/item/sword"]);
+    assert!(diff.changed_types[1].changed_vars.contains_key("force"));
+}
@@ -1,6 +1,7 @@
 extern crate dreammaker as dm;
 
 use dm::ast::*;
+use dm::Location;
 use dm::lexer::Lexer;
 use dm::parser::*;
 
@@ -37,6 +38,7 @@ fn ternary_precedence() {
                     rhs: Box::new(Expression::from(Term::Int(6))),
                 }),
             }),
+            end: Location { line: 1, column: 28, ..Default::default() },
         }
     )
 }
@@ -56,6 +58,7 @@ fn in_after_ternary() {
                     if_: Box::new(Expression::from(Term::Int(2))),
                     else_: Box::new(Expression::from(Term::Int(3))),
                 }),
+                end: Location { line: 1, column: 16, ..Default::default() },
             }),
             rhs: Box::new(Expression::from(Term::Int(4))),
         }
@@ -128,3 +131,20 @@ fn pointer_ops() {
         }
     )
 }
+
+#[test]
+fn display_as_source() {
+    let display = |source| parse_expr(source).to_string();
+    assert_eq!(display("PAYCHECK_MINIMAL * 3"), "PAYCHECK_MINIMAL * 3");
+    assert_eq!(display("1 + 2 * 3"), "1 + (2 * 3)");
+    assert_eq!(display("(1 + 2) * 3"), "(1 + 2) * 3");
+    assert_eq!(display("-foo.bar[2]"), "-foo.bar[2]");
+    assert_eq!(display("a ? b : c + 1"), "a ? b : (c + 1)");
+    assert_eq!(display("x in 1 to 5"), "x in 1 to 5");
+    assert_eq!(display("list(\"a\" = 1, 'icon.dmi')"), "list(\"a\" = 1, 'icon.dmi')");
+    assert_eq!(display("new /obj/item{name = \"x\"}(src)"), "new /obj/item{name = \"x\"}(src)");
+    assert_eq!(display("\"[src] has [count] items\""), "\"[src] has [count] items\"");
+    assert_eq!(display("pick(prob(20); 1, 2)"), "pick(prob(20); 1, 2)");
+    assert_eq!(display("/datum::foo()"), "/datum::foo()");
+    assert_eq!(display("global.proc_call(1.5)"), "global.proc_call(1.5)");
+}
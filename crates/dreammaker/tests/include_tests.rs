@@ -0,0 +1,192 @@
+extern crate dreammaker as dm;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use dm::preprocessor::*;
+
+/// Lay out a small project in a fresh temporary directory.
+fn project(name: &str, files: &[&str]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("dm-include-tests-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for file in files {
+        let path = root.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "").unwrap();
+    }
+    root
+}
+
+#[test]
+fn include_resolution() {
+    let root = project("include", &[
+        "env.dme",
+        "code/game/machinery.dm",
+        "code/game/helpers.dm",
+        "code/helpers.dm",
+    ]);
+    let env = root.join("env.dme");
+    let including = root.join("code/game/machinery.dm");
+
+    // backslashes are path separators, and the environment is a fallback
+    assert_eq!(
+        resolve_include(&env, &env, r"code\game\machinery.dm"),
+        Some(root.join("code/game/machinery.dm")),
+    );
+    // the including file's directory wins over the environment's
+    assert_eq!(
+        resolve_include(&including, &env, "helpers.dm"),
+        Some(root.join("code/game/helpers.dm")),
+    );
+    assert_eq!(
+        resolve_include(&including, &env, r"code\helpers.dm"),
+        Some(root.join("code/helpers.dm")),
+    );
+    assert_eq!(resolve_include(&including, &env, "missing.dm"), None);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn resource_resolution() {
+    let root = project("resource", &[
+        "env.dme",
+        "code/game/machinery.dm",
+        "code/game/local.ogg",
+        "icons/obj/device.dmi",
+        "sound/effects/bang.ogg",
+    ]);
+    let env = root.join("env.dme");
+    let file = root.join("code/game/machinery.dm");
+    let file_dirs = ["icons".to_owned()];
+
+    assert_eq!(
+        resolve_resource(&file, &env, &file_dirs, "sound/effects/bang.ogg"),
+        Some(root.join("sound/effects/bang.ogg")),
+    );
+    assert_eq!(
+        resolve_resource(&file, &env, &file_dirs, "local.ogg"),
+        Some(root.join("code/game/local.ogg")),
+    );
+    assert_eq!(
+        resolve_resource(&file, &env, &file_dirs, r"obj\device.dmi"),
+        Some(root.join("icons/obj/device.dmi")),
+    );
+    assert_eq!(resolve_resource(&file, &env, &[], "obj/device.dmi"), None);
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn file_dirs_from_defines() {
+    let context = Default::default();
+    let code = "#define FILE_DIR .\n#define FILE_DIR \"icons\"\n#define FILE_DIR sound/effects\n";
+    let mut preprocessor = Preprocessor::from_buffer(&context, "env.dme".into(), code);
+    preprocessor.by_ref().for_each(drop);
+    let history = preprocessor.finalize();
+    assert_eq!(history.env_file(), Path::new("env.dme"));
+    assert_eq!(history.file_dirs(), vec![".", "icons", "sound/effects"]);
+}
+
+#[test]
+fn include_graph() {
+    let root = project("graph", &[
+        "env.dme",
+        "code/a.dm",
+        "code/b.dm",
+        "code/unused.dm",
+        "maps/station.dmm",
+        ".git/hooks/ignored.dm",
+    ]);
+    fs::write(root.join("env.dme"), "#include \"code/a.dm\"\n#include \"maps/station.dmm\"\n#include \"code/missing.dm\"\n#include \"code/B.dm\"\n").unwrap();
+    fs::write(root.join("code/a.dm"), "#include \"b.dm\"\n").unwrap();
+    fs::write(root.join("code/b.dm"), "#include \"a.dm\"\n").unwrap();
+
+    let context = dm::Context::default();
+    let preprocessor = Preprocessor::new(&context, root.join("env.dme")).unwrap();
+    preprocessor.for_each(drop);
+
+    let graph = context.include_graph();
+    let written: Vec<_> = graph.edges().iter().map(|edge| edge.written.as_str()).collect();
+    assert_eq!(written, ["code/a.dm", "b.dm", "a.dm", "maps/station.dmm", "code/missing.dm", "code/B.dm"]);
+
+    let env = graph.root().unwrap();
+    let a = context.get_file(Path::new("code/a.dm")).unwrap();
+    let b = context.get_file(Path::new("code/b.dm")).unwrap();
+    assert_eq!(graph.edges()[0].location.file, env);
+    assert_eq!(graph.edges()[3].path.as_deref(), Some(Path::new("maps/station.dmm")));
+    assert_eq!(graph.edges()[3].file, None);
+    assert_eq!(graph.edges()[4].path, None);
+    assert_eq!(graph.includes_from(a).count(), 1);
+    assert!(graph.contains(env) && graph.contains(a) && graph.contains(b));
+    let chain: Vec<_> = graph.include_chain(b).iter().map(|edge| edge.location.file).collect();
+    assert_eq!(chain, [a, env]);
+
+    assert_eq!(graph.unincluded_files(&context, &root), [PathBuf::from("code/unused.dm")]);
+
+    // the second inclusion of a.dm, the missing file, and the case mismatch
+    let errors = context.errors();
+    let descriptions: Vec<_> = errors.iter().map(|error| error.description()).collect();
+    assert_eq!(descriptions.len(), 3, "{:?}", descriptions);
+    assert!(descriptions[0].starts_with("duplicate #include"));
+    assert_eq!(descriptions[1], "failed to find #include \"code/missing.dm\"");
+    assert_eq!(errors[1].notes().len(), 1);
+    assert_eq!(descriptions[2], "failed to find #include \"code/B.dm\"");
+    assert!(errors[2].notes().iter().any(|note| note.description() == "code/B.dm differs only in case from code/b.dm"));
+
+    drop(errors);
+    drop(graph);
+    fs::remove_dir_all(root).unwrap();
+}
+
+/// Parse an environment and describe the tree and errors it produced.
+fn parse_described(context: &dm::Context, preprocessor: Preprocessor) -> (Vec<String>, Vec<String>) {
+    let indents = dm::indents::IndentProcessor::new(context, preprocessor);
+    let tree = dm::parser::Parser::new(context, indents).parse_object_tree_without_builtins();
+    let types = tree.iter_types()
+        .map(|ty| format!("{} {:?} {:?}", ty.path, ty.location, ty.vars.keys().collect::<Vec<_>>()))
+        .collect();
+    let errors = context.errors().iter()
+        .map(|error| format!("{:?} {}", error.location(), error.description()))
+        .collect();
+    (types, errors)
+}
+
+#[test]
+fn prefetched_includes_match_serial() {
+    let mut files = vec!["env.dme", "code/nested.dm", "code/inner.dm", "code/unused.dm"];
+    // More files than are read ahead at once.
+    let numbered: Vec<String> = (0..200).map(|i| format!("code/file{}.dm", i)).collect();
+    files.extend(numbered.iter().map(String::as_str));
+    let root = project("prefetch", &files);
+
+    let mut dme = String::new();
+    for (i, file) in numbered.iter().enumerate() {
+        dme.push_str(&format!("#include \"{}\"\n", file));
+        if i == 100 {
+            dme.push_str("#if 0\n#include \"code/unused.dm\"\n#endif\n");
+        }
+        fs::write(root.join(file), format!("#ifdef FEATURE_{}\n/obj/enabled{}\n#endif\n/obj/file{}\n\tvar/x = {}\n#define FEATURE_{}\n", i, i, i, i, i + 1)).unwrap();
+    }
+    dme.push_str("#include \"code/nested.dm\"\n#include \"code/file3.dm\"\n#include \"code/missing.dm\"\n");
+    fs::write(root.join("env.dme"), &dme).unwrap();
+    fs::write(root.join("code/nested.dm"), "#include \"inner.dm\"\n/obj/nested\n").unwrap();
+    fs::write(root.join("code/inner.dm"), "/obj/inner\n\tvar/y = (\n").unwrap();
+    fs::write(root.join("code/unused.dm"), "/obj/unused\n").unwrap();
+
+    let prefetched_context = dm::Context::default();
+    let prefetched = parse_described(&prefetched_context, Preprocessor::new(&prefetched_context, root.join("env.dme")).unwrap());
+
+    // Pushing the environment as an ordinary file reads everything serially.
+    let serial_context = dm::Context::default();
+    let mut serial_preprocessor = Preprocessor::with_defines(&serial_context, root.join("env.dme"), DefineMap::with_builtins());
+    serial_preprocessor.push_file(root.join("env.dme"), fs::File::open(root.join("env.dme")).unwrap()).unwrap();
+    let serial = parse_described(&serial_context, serial_preprocessor);
+
+    assert_eq!(prefetched, serial);
+    assert!(prefetched.0.iter().any(|ty| ty.starts_with("/obj/enabled199 ")));
+    assert!(!prefetched.0.iter().any(|ty| ty.starts_with("/obj/unused ")));
+    assert_eq!(prefetched.1.len(), 3, "{:?}", prefetched.1);
+
+    fs::remove_dir_all(root).unwrap();
+}
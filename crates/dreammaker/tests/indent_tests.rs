@@ -0,0 +1,39 @@
+extern crate dreammaker as dm;
+
+use dm::indents::IndentProcessor;
+use dm::lexer::Lexer;
+
+fn indent_errors(code: &str) -> Vec<(u32, std::string::String)> {
+    let context = dm::Context::default();
+    let lexer = Lexer::new(&context, Default::default(), code.as_bytes());
+    IndentProcessor::new(&context, lexer).for_each(drop);
+    let errors = context.errors();
+    errors.iter()
+        .map(|error| (error.location().line, error.description().to_owned()))
+        .collect()
+}
+
+#[test]
+fn consistent_indentation() {
+    assert_eq!(indent_errors("/proc/a()\n\tif(1)\n\t\treturn\n\treturn\n"), vec![]);
+    assert_eq!(indent_errors("/proc/a()\n    if(1)\n        return\n    return\n"), vec![]);
+}
+
+#[test]
+fn mixed_indentation() {
+    assert_eq!(indent_errors("/proc/a()\n\tif(1)\n\t    return\n"), vec![
+        (3, "indentation mixes tabs and spaces".to_owned()),
+        (3, "inconsistent multiple indentation: 4 > 1".to_owned()),
+    ]);
+}
+
+#[test]
+fn indentation_mismatch() {
+    assert_eq!(indent_errors("/proc/a()\n\tif(1)\n\t\treturn\n    world.log << 1\n"), vec![
+        (4, "indentation looks shallower than the previous line with 4-column tabs, but is parsed as deeper than it".to_owned()),
+        (4, "inconsistent multiple indentation: 2 > 1".to_owned()),
+    ]);
+    assert_eq!(indent_errors("/proc/a()\n\tif(1)\n\t\treturn\n  world.log << 1\n"), vec![
+        (4, "indentation looks shallower than the previous line with 4-column tabs, but is parsed as level with it".to_owned()),
+    ]);
+}
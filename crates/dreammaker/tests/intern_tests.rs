@@ -0,0 +1,41 @@
+extern crate dreammaker as dm;
+
+use dm::intern::*;
+
+#[test]
+fn interning_shares_storage() {
+    let a = intern("attack_self");
+    let b = intern(&String::from("attack_self"));
+    assert_eq!(a, b);
+    assert!(std::ptr::eq(a.as_str(), b.as_str()));
+    assert_ne!(a, intern("attack_hand"));
+    assert_eq!(a, "attack_self");
+    assert!(intern("a") < intern("b"));
+}
+
+#[test]
+fn unreferenced_strings_are_forgotten() {
+    let kept = intern("interned and kept");
+    for i in 0..200_000 {
+        intern(&format!("interned and dropped {}", i));
+    }
+    assert!(interned_count() < 200_000, "{}", interned_count());
+    let again = intern("interned and kept");
+    assert!(std::ptr::eq(kept.as_str(), again.as_str()));
+}
+
+#[test]
+fn object_tree_lookups_by_str() {
+    let context = dm::Context::default();
+    let pp = dm::preprocessor::Preprocessor::from_buffer(&context, "intern.dm".into(), "/obj/item\n\tvar/force = 5\n\tproc/attack()\n");
+    let indents = dm::indents::IndentProcessor::new(&context, pp);
+    let tree = dm::parser::Parser::new(&context, indents).parse_object_tree();
+    context.assert_success();
+
+    let item = tree.expect("/obj/item");
+    assert_eq!(item.path, "/obj/item");
+    assert!(item.vars.contains_key("force"));
+    assert!(item.procs.contains_key("attack"));
+    let name = item.vars.keys().next().unwrap().clone();
+    assert_eq!(name, intern("force"));
+}
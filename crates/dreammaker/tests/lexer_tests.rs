@@ -139,3 +139,116 @@ fn heredoc_with_quotes() {
         ]
     );
 }
+
+#[test]
+fn token_end_offsets() {
+    let code = "x+=y // c\nz = \"a[b]c\" ..()";
+    let context = Default::default();
+    let mut lexer = Lexer::new(&context, Default::default(), code.as_bytes());
+    let mut ends = Vec::new();
+    while let Some(token) = Iterator::next(&mut lexer) {
+        if token.token != Punct(Newline) {
+            ends.push(&code[..lexer.offset()]);
+        }
+    }
+    assert_eq!(ends, [
+        "x",
+        "x+=",
+        "x+=y",
+        "x+=y // c\nz",
+        "x+=y // c\nz =",
+        "x+=y // c\nz = \"a[",
+        "x+=y // c\nz = \"a[b",
+        "x+=y // c\nz = \"a[b]c\"",
+        "x+=y // c\nz = \"a[b]c\" ..",
+        "x+=y // c\nz = \"a[b]c\" ..(",
+        "x+=y // c\nz = \"a[b]c\" ..()",
+    ]);
+}
+
+fn text_macro_errors(code: &str) -> Vec<(u16, u16, std::string::String)> {
+    let context = dm::Context::default();
+    Lexer::new(&context, Default::default(), code.as_bytes()).for_each(drop);
+    let errors = context.errors();
+    errors.iter()
+        .map(|error| (error.location().column, error.end().map_or(0, |end| end.column), error.description().to_owned()))
+        .collect()
+}
+
+#[test]
+fn text_macros() {
+    assert_eq!(text_macro_errors(r#""\the [src] \improper[name]\n\red warning""#), vec![]);
+    assert_eq!(text_macro_errors(r#""[count]\th \s and \an[x]""#), vec![]);
+
+    assert_eq!(text_macro_errors(r#""\reed alert""#), vec![
+        (2, 7, r#"unknown text macro "\reed""#.to_owned()),
+    ]);
+    assert_eq!(text_macro_errors(r#""\the cat""#), vec![
+        (2, 6, r#"text macro "\the" must be followed by an embedded expression, as in "\the [src]""#.to_owned()),
+    ]);
+    // pronouns take the expression before them if there is one
+    assert_eq!(text_macro_errors(r#""[src] scratches \his head""#), vec![]);
+    assert_eq!(text_macro_errors(r#""\He waves at [user]""#), vec![]);
+    assert_eq!(text_macro_errors(r#""\his head""#), vec![
+        (2, 6, r#"text macro "\his" must be next to an embedded expression, as in "[src] \his" or "\his [src]""#.to_owned()),
+    ]);
+    // a line break is whitespace in a multi-line string
+    assert_eq!(text_macro_errors("{\"\\the\n[src]\"}"), vec![]);
+    assert_eq!(text_macro_errors(r#""first\th""#), vec![
+        (7, 10, r#"text macro "\th" must follow an embedded expression, as in "[count]\th""#.to_owned()),
+    ]);
+    assert_eq!(text_macro_errors(r#""\ref[1] \icon["x"] \ref[src] \icon['a.dmi']""#), vec![
+        (2, 6, r#"text macro "\ref" is given a number, which is not an atom"#.to_owned()),
+        (10, 15, r#"text macro "\icon" is given text, which is not an atom"#.to_owned()),
+    ]);
+}
+
+#[test]
+fn text_macro_positions() {
+    let context = dm::Context::default();
+    let mut lexer = Lexer::new(&context, Default::default(), r#"x = "\the [y]\s""#.as_bytes());
+    lexer.by_ref().for_each(drop);
+    let macros: Vec<_> = lexer.text_macros().iter().map(|each| (each.location.column, each.name)).collect();
+    assert_eq!(macros, vec![(6, "the"), (14, "s")]);
+}
+
+#[test]
+fn raw_strings_skip_escapes() {
+    assert_eq!(one_token(r#"@"C:\Program Files\BYOND""#), Token::String(r"C:\Program Files\BYOND".to_owned()));
+    assert_eq!(one_token(r#"@"[not_embedded]\n""#), Token::String(r"[not_embedded]\n".to_owned()));
+    assert_eq!(one_token(r#"@/\d+\s*"quoted"/"#), Token::String(r#"\d+\s*"quoted""#.to_owned()));
+    assert_eq!(one_token("@{\"line one\nline \\two\"}"), Token::String("line one\nline \\two".to_owned()));
+    assert_eq!(one_token("@(EOF)first\n\tsecond EOF"), Token::String("first\n\tsecond ".to_owned()));
+}
+
+#[test]
+fn unterminated_raw_string() {
+    let context = dm::Context::default();
+    let tokens: Vec<_> = Lexer::new(&context, Default::default(), b"x = @\"oops\ny = 1\n".as_slice())
+        .map(|t| (t.location.line, t.location.column, t.token))
+        .collect();
+    // The string stops at the end of the line instead of eating the file.
+    assert!(tokens.contains(&(1, 5, Token::String("oops".to_owned()))));
+    assert!(tokens.contains(&(2, 1, Ident("y".to_owned(), true))));
+    let errors = context.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!((errors[0].location().line, errors[0].location().column), (1, 5));
+    assert_eq!(errors[0].description(), "unterminated raw string");
+}
+
+#[test]
+fn multiline_raw_string_indentation() {
+    let context = dm::Context::default();
+    let code = "/proc/f()\n\tvar/x = @{\"one\ntwo\n\"}\n\tvar/y = @(END)\n  three\nEND\n\treturn x + y\n";
+    let lexer = Lexer::new(&context, Default::default(), code.as_bytes());
+    let tokens: Vec<_> = dm::indents::IndentProcessor::new(&context, lexer)
+        .map(|t| (t.location.line, t.token))
+        .collect();
+    context.assert_success();
+    assert!(tokens.contains(&(2, Token::String("one\ntwo\n".to_owned()))));
+    assert!(tokens.contains(&(5, Token::String("\n  three\n".to_owned()))));
+    // The lines inside the strings do not affect indentation.
+    assert!(tokens.contains(&(8, Ident("return".to_owned(), true))));
+    assert_eq!(tokens.iter().filter(|(_, t)| *t == Punct(LBrace)).count(), 1);
+    assert_eq!(tokens.iter().filter(|(_, t)| *t == Punct(RBrace)).count(), 1);
+}
@@ -86,3 +86,315 @@ exists
 #endif
 "#), &[]);
 }
+
+#[test]
+fn macro_body_annotations() {
+    use dm::annotation::Annotation;
+
+    let ctx = dm::Context::default();
+    let mut pp = Preprocessor::from_buffer(&ctx, "macro_tests.rs".into(), r#"
+#define SQUARE(X) ((X) * (X))
+#define AREA(W, H) (W * H + SQUARE(BORDER))
+"#);
+    pp.enable_annotations();
+    for _ in &mut pp {}
+    ctx.assert_success();
+    let annotations = pp.take_annotations().unwrap();
+
+    let at = |line, column| annotations.iter()
+        .filter(|(range, _)| range.start.line == line && range.start.column <= column && range.end.column >= column)
+        .map(|(_, annotation)| annotation)
+        .collect::<Vec<_>>();
+
+    // the body is known from the parameters to the end of the line
+    assert!(at(2, 20).iter().any(|a| matches!(a, Annotation::MacroBody { name, params } if name == "SQUARE" && params == &["X"])));
+    // parameters and identifiers in the substitution each have a location
+    assert!(at(2, 16).iter().any(|a| matches!(a, Annotation::MacroBodyIdent(name) if name == "X")));
+    assert!(at(3, 30).iter().any(|a| matches!(a, Annotation::MacroBodyIdent(name) if name == "SQUARE")));
+    assert!(at(3, 37).iter().any(|a| matches!(a, Annotation::MacroBodyIdent(name) if name == "BORDER")));
+    // but the name being defined is not part of the body
+    assert!(!at(3, 10).iter().any(|a| matches!(a, Annotation::MacroBody { .. })));
+}
+
+#[test]
+fn if_conditions() {
+    assert_eq!(process(r#"
+#if DM_VERSION >= 515
+ok1
+#endif
+"#), &[
+        Ident("ok1".into(), false),
+    ]);
+
+    assert_eq!(process(r#"
+#define LEVEL 3
+#if defined(LEVEL) && LEVEL > 2 && (LEVEL + 1) * 2 % 5 == 3
+ok2
+#endif
+"#), &[
+        Ident("ok2".into(), false),
+    ]);
+
+    // undefined identifiers are 0
+    assert_eq!(process(r#"
+#if NOT_DEFINED
+no
+#elif !NOT_DEFINED && defined(__MAIN__)
+ok3
+#endif
+"#), &[
+        Ident("ok3".into(), false),
+    ]);
+}
+
+#[test]
+fn malformed_if_condition() {
+    let ctx = dm::Context::default();
+    let pp = Preprocessor::from_buffer(&ctx, "macro_tests.rs".into(), "\n#if 1 2\nno\n#else\nyes\n#endif\n");
+    let tokens: Vec<_> = pp.map(|loctok| loctok.token).filter(|tok| *tok != Punct(Newline)).collect();
+    assert_eq!(tokens, &[Ident("yes".into(), false)]);
+
+    let errors = ctx.errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].location().line, 2);
+    assert!(errors[0].description().starts_with("malformed #if condition"));
+}
+
+#[test]
+fn warn_and_error_directives() {
+    use dm::Severity;
+
+    let ctx = dm::Context::default();
+    let pp = Preprocessor::from_buffer(&ctx, "macro_tests.rs".into(), r#"
+#define MAP_NAME "Box Station"
+#define WHERE on MAP_NAME
+#warn deprecated include WHERE
+#ifdef NOT_DEFINED
+#error untaken branch
+#endif
+#error You must define MAP_FILE before including this file
+"#);
+    for _ in pp {}
+
+    let errors = ctx.errors();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].location().line, 4);
+    assert_eq!(errors[0].severity(), Severity::Warning);
+    assert_eq!(errors[0].description(), "#warn deprecated include on \"Box Station\"");
+    assert_eq!(errors[1].location().line, 8);
+    assert_eq!(errors[1].severity(), Severity::Error);
+    assert_eq!(errors[1].description(), "#error You must define MAP_FILE before including this file");
+}
+
+// The expansions below match what DreamMaker produces for the same input.
+
+#[test]
+fn variadic_macros() {
+    assert_eq!(process(r#"
+#define CHECK(x...) check(x)
+CHECK(1, 2)
+CHECK()
+"#), &[
+        Ident("check".into(), false), Punct(LParen), Int(1), Punct(Comma), Int(2), Punct(RParen), Punct(Newline),
+        Ident("check".into(), false), Punct(LParen), Punct(RParen),
+    ]);
+
+    // the remaining arguments are substituted along with their commas
+    assert_eq!(process(r#"
+#define LOG(fmt, args...) log(fmt, args)
+LOG("a")
+LOG("b", 1, 2)
+"#), &[
+        Ident("log".into(), false), Punct(LParen), String("a".into()), Punct(Comma), Punct(RParen), Punct(Newline),
+        Ident("log".into(), false), Punct(LParen), String("b".into()), Punct(Comma), Int(1), Punct(Comma), Int(2), Punct(RParen),
+    ]);
+
+    assert_eq!(process(r#"
+#define ANON(...) anon(__VA_ARGS__)
+ANON(1, 2)
+"#), &[
+        Ident("anon".into(), false), Punct(LParen), Int(1), Punct(Comma), Int(2), Punct(RParen),
+    ]);
+}
+
+#[test]
+fn nested_variadic_macros() {
+    assert_eq!(process(r#"
+#define CHECK(x...) check(x)
+CHECK(CHECK(1), (2, 3))
+"#), &[
+        Ident("check".into(), false), Punct(LParen),
+            Ident("check".into(), false), Punct(LParen), Int(1), Punct(RParen),
+            Punct(Comma),
+            Punct(LParen), Int(2), Punct(Comma), Int(3), Punct(RParen),
+        Punct(RParen),
+    ]);
+}
+
+#[test]
+fn stringify_and_paste() {
+    assert_eq!(process(r#"
+#define STR(x...) #x
+STR(a, b.c + 1)
+"#), &[
+        String("a, b.c + 1".into()),
+    ]);
+
+    assert_eq!(process(r#"
+#define PASTE(a, b) a##b
+PASTE(foo, bar) PASTE(x, 1)
+"#), &[
+        Ident("foobar".into(), false), Ident("x1".into(), false),
+    ]);
+}
+
+#[test]
+fn empty_parameter_list() {
+    assert_eq!(process(r#"
+#define NOTHING() nothing
+NOTHING()
+"#), &[
+        Ident("nothing".into(), false),
+    ]);
+}
+
+#[test]
+fn wrong_argument_count() {
+    let ctx = dm::Context::default();
+    let pp = Preprocessor::from_buffer(&ctx, "macro_tests.rs".into(), r#"
+#define TWO(a, b) a + b
+#define AT_LEAST(a, b, rest...) a
+TWO(1)
+AT_LEAST(1)
+"#);
+    for _ in pp {}
+
+    let errors = ctx.errors();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].location().line, 4);
+    assert_eq!(errors[0].description(), "wrong number of arguments to macro TWO: expected 2, got 1");
+    assert_eq!(errors[0].notes()[0].location().line, 2);
+    assert_eq!(errors[1].location().line, 5);
+    assert_eq!(errors[1].description(), "wrong number of arguments to macro AT_LEAST: expected at least 2, got 1");
+    assert_eq!(errors[1].notes()[0].location().line, 3);
+}
+
+#[test]
+fn redefinition_history() {
+    use dm::{Location, Severity};
+
+    let ctx = dm::Context::default();
+    let mut pp = Preprocessor::from_buffer(&ctx, "macro_tests.rs".into(), r#"
+#define SPEED 1
+SPEED
+#define SPEED 2
+SPEED
+#undef SPEED
+SPEED
+#undef NEVER_DEFINED
+"#);
+    pp.by_ref().for_each(drop);
+
+    let errors = ctx.errors();
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].description(), "macro redefined: SPEED");
+    assert_eq!(errors[0].location().line, 4);
+    assert_eq!(errors[0].notes()[0].location().line, 2);
+    assert_eq!(errors[1].severity(), Severity::Info);
+    drop(errors);
+
+    let history = pp.finalize();
+    let at = |line| {
+        let location = Location { file: ctx.get_file("macro_tests.rs".as_ref()).unwrap(), line, column: 1 };
+        history.active_at("SPEED", location).map(|(location, _)| location.line)
+    };
+    assert_eq!(at(3), Some(2));
+    assert_eq!(at(5), Some(4));
+    // undefining the redefinition restores the original
+    assert_eq!(at(7), Some(2));
+    assert_eq!(history.definitions("SPEED").len(), 2);
+}
+
+/// Parse code with procs, returning the context for its errors.
+fn parse_with_errors(source: &'static str) -> dm::Context {
+    let ctx = dm::Context::default();
+    let pp = Preprocessor::from_buffer(&ctx, "macro_tests.dm".into(), source.trim());
+    let indents = dm::indents::IndentProcessor::new(&ctx, pp);
+    let mut parser = dm::parser::Parser::new(&ctx, indents);
+    parser.enable_procs();
+    parser.parse_object_tree();
+    ctx
+}
+
+#[test]
+fn nested_expansion_notes() {
+    let ctx = parse_with_errors("
+#define INNER(x) (x +)
+#define OUTER(x) INNER(x)
+/proc/test()
+    return OUTER(1)
+");
+    let use_loc = dm::Location { file: ctx.get_file("macro_tests.dm".as_ref()).unwrap(), line: 4, column: 12 };
+    let errors = ctx.errors();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    assert_eq!(errors[0].location(), use_loc);
+    let notes: Vec<_> = errors[0].notes().iter()
+        .map(|note| (note.description(), note.location().line))
+        .collect();
+    assert_eq!(notes, [
+        ("')' is expanded from here", 1),
+        ("in expansion of macro INNER", 1),
+        ("in expansion of macro OUTER", 2),
+    ]);
+    // the token the error is at is located within the body of INNER
+    assert_eq!(errors[0].notes()[0].location().column, 22);
+}
+
+#[test]
+fn expansion_chain_on_tokens() {
+    let ctx = dm::Context::default();
+    let pp = Preprocessor::from_buffer(&ctx, "macro_tests.rs".into(), "
+#define INNER 1
+#define OUTER INNER
+OUTER
+".trim());
+    let one = pp.into_iter().find(|token| token.token == Int(1)).unwrap();
+    let chain: Vec<_> = one.expansion.as_ref().unwrap().chain()
+        .map(|expansion| (expansion.name.to_string(), expansion.invocation.line, expansion.definition.line))
+        .collect();
+    assert_eq!(chain, [("INNER".to_owned(), 3, 1), ("OUTER".to_owned(), 3, 2)]);
+}
+
+#[test]
+fn sequential_expansion_notes() {
+    let ctx = parse_with_errors("
+#define ONE 1
+#define BROKEN (2 +)
+#define BOTH ONE + BROKEN
+/proc/test()
+    return BOTH
+");
+    let errors = ctx.errors();
+    assert_eq!(errors.len(), 1, "{:?}", errors);
+    let notes: Vec<_> = errors[0].notes().iter()
+        .map(|note| (note.description(), note.location().line))
+        .collect();
+    // ONE was expanded before BROKEN on the same line, not around it
+    assert_eq!(notes, [
+        ("')' is expanded from here", 2),
+        ("in expansion of macro BROKEN", 2),
+        ("in expansion of macro BOTH", 3),
+    ]);
+}
+
+#[test]
+fn expansion_notes_only_at_expansions() {
+    let ctx = parse_with_errors("
+#define ONE 1
+/proc/test()
+    return ONE + (
+");
+    let errors = ctx.errors();
+    assert!(!errors.is_empty());
+    assert!(errors.iter().all(|error| error.notes().iter().all(|note| !note.description().starts_with("in expansion"))));
+}
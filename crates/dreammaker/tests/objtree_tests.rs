@@ -0,0 +1,180 @@
+extern crate dreammaker as dm;
+
+mod common;
+use common::with_code;
+
+const TREE: &str = "
+/obj/item/proc/attack()
+/obj/item/sword
+/obj/item/sword/attack()
+/obj/item/sword/long
+/obj/item/axe
+/obj/weapon
+    parent_type = /obj/item/sword
+/obj/weapon/attack()
+/obj/weapon/dagger
+";
+
+fn paths<'a>(types: impl Iterator<Item=dm::objtree::TypeRef<'a>>) -> Vec<&'a str> {
+    types.map(|ty| ty.get().path.as_str()).collect()
+}
+
+#[test]
+fn descendants_follow_parent_type() {
+    with_code(TREE, |context, tree| {
+        context.assert_success();
+        let item = tree.expect("/obj/item");
+        assert_eq!(paths(item.descendants()), [
+            "/obj/item/axe",
+            "/obj/item/sword",
+            "/obj/item/sword/long",
+            "/obj/weapon",
+            "/obj/weapon/dagger",
+        ]);
+        assert_eq!(paths(tree.expect("/obj/item/sword").self_and_descendants()), [
+            "/obj/item/sword",
+            "/obj/item/sword/long",
+            "/obj/weapon",
+            "/obj/weapon/dagger",
+        ]);
+        assert!(tree.expect("/obj/weapon/dagger").descendants().next().is_none());
+    });
+}
+
+#[test]
+fn ancestors_follow_parent_type() {
+    with_code(TREE, |context, tree| {
+        context.assert_success();
+        assert_eq!(paths(tree.expect("/obj/weapon/dagger").ancestors()), [
+            "/obj/weapon",
+            "/obj/item/sword",
+            "/obj/item",
+            "/obj",
+            "/atom/movable",
+            "/atom",
+            "/datum",
+            "",
+        ]);
+        assert!(tree.root().ancestors().next().is_none());
+    });
+}
+
+#[test]
+fn overrides_of() {
+    with_code(TREE, |context, tree| {
+        context.assert_success();
+        let overrides: Vec<_> = tree.expect("/obj/item").overrides_of("attack")
+            .map(|proc| proc.ty().get().path.as_str())
+            .collect();
+        assert_eq!(overrides, ["/obj/item", "/obj/item/sword", "/obj/weapon"]);
+        assert_eq!(tree.expect("/obj/item/axe").overrides_of("attack").count(), 0);
+    });
+}
+
+#[test]
+fn circular_parent_type() {
+    with_code("
+/obj/a
+    parent_type = /obj/b
+/obj/b
+    parent_type = /obj/a
+", |context, tree| {
+        let errors = context.errors();
+        let circular: Vec<_> = errors.iter()
+            .map(|error| error.description())
+            .filter(|description| description.starts_with("circular"))
+            .collect();
+        assert_eq!(circular, ["circular parent type for /obj/a"]);
+        assert_eq!(paths(tree.expect("/obj/a").ancestors()), [""]);
+        assert_eq!(paths(tree.expect("/obj/a").descendants()), ["/obj/b"]);
+    });
+}
+
+fn span(location: dm::Location, end_column: u16) -> (u32, u16, u16) {
+    (location.line, location.column, end_column)
+}
+
+#[test]
+fn absolute_declarations_located_at_name() {
+    with_code("
+/obj/item/sword
+/obj/item/axe/var/sharpness = 1
+/obj/item/axe/proc/chop()
+/obj/item/axe/chop()
+", |context, tree| {
+        context.assert_success();
+        let item = tree.expect("/obj/item");
+        assert_eq!(span(item.location, item.end_column), (1, 6, 10));
+        let sword = tree.expect("/obj/item/sword");
+        assert_eq!(span(sword.location, sword.end_column), (1, 11, 16));
+        let axe = tree.expect("/obj/item/axe");
+        assert_eq!(span(axe.location, axe.end_column), (4, 11, 14));
+
+        let var = axe.vars.get("sharpness").unwrap();
+        assert_eq!(span(var.value.location, var.value.end_column), (2, 19, 28));
+        let decl = var.declaration.as_ref().unwrap();
+        assert_eq!(span(decl.location, decl.end_column), (2, 19, 28));
+
+        let proc = axe.procs.get("chop").unwrap();
+        let decl = proc.declaration.as_ref().unwrap();
+        assert_eq!(span(decl.location, decl.end_column), (3, 20, 24));
+        let spans: Vec<_> = proc.value.iter().map(|value| span(value.location, value.end_column)).collect();
+        assert_eq!(spans, [(3, 20, 24), (4, 15, 19)]);
+    });
+}
+
+#[test]
+fn block_declarations_located_at_name() {
+    with_code(r#"
+/mob
+    living
+        name = "living"
+        verb/say()
+        proc
+            die()
+"#, |context, tree| {
+        context.assert_success();
+        let living = tree.expect("/mob/living");
+        assert_eq!(span(living.location, living.end_column), (2, 5, 11));
+
+        let name = living.vars.get("name").unwrap();
+        assert!(name.declaration.is_none());
+        assert_eq!(span(name.value.location, name.value.end_column), (3, 9, 13));
+
+        let say = living.procs.get("say").unwrap();
+        assert_eq!(span(say.main_value().location, say.main_value().end_column), (4, 14, 17));
+        let die = living.procs.get("die").unwrap();
+        assert_eq!(span(die.main_value().location, die.main_value().end_column), (6, 13, 16));
+    });
+}
+
+#[test]
+fn comma_separated_vars_located_at_each_name() {
+    with_code("
+/obj/item
+    var
+        a = 1, bb
+        list/c, d = 2
+    var/tmp/e, f
+", |context, tree| {
+        context.assert_success();
+        let obj = tree.expect("/obj/item");
+        let spans: Vec<_> = obj.vars.iter()
+            .map(|(name, var)| (name.as_str(), span(var.value.location, var.value.end_column)))
+            .collect();
+        assert_eq!(spans, [
+            ("a", (3, 9, 10)),
+            ("bb", (3, 16, 18)),
+            ("c", (4, 14, 15)),
+            ("d", (4, 17, 18)),
+            ("e", (5, 13, 14)),
+            ("f", (5, 16, 17)),
+        ]);
+
+        let declared = |name: &str| obj.vars.get(name).unwrap().declaration.as_ref().unwrap().var_type.clone();
+        assert_eq!(declared("c").type_path, ["list".to_owned()].into());
+        assert!(declared("d").type_path.is_empty());
+        assert!(declared("e").flags.is_tmp());
+        assert!(!declared("f").flags.is_tmp());
+    });
+}
@@ -0,0 +1,84 @@
+extern crate dreammaker as dm;
+
+use dm::outline::*;
+use dm::preprocessor::DefineMap;
+
+fn outline(code: &str, defines: DefineMap) -> FileOutline {
+    let context = dm::Context::default();
+    parse_file(&context, "outline.dm".as_ref(), code.trim(), defines)
+}
+
+#[test]
+fn outline_declarations() {
+    let outline = outline(r#"
+/obj/item
+    var/obj/item/other
+    name = "item"
+    proc/attack(mob/user)
+/obj/item/sword/attack(mob/user)
+/proc/helper()
+/mob/verb/look()
+"#, DefineMap::default());
+    assert!(outline.errors.is_empty(), "{:?}", outline.errors);
+
+    let types: Vec<_> = outline.types.iter().map(|ty| ty.path.as_str()).collect();
+    assert_eq!(types, ["/obj", "/obj/item", "/obj/item/sword", "/mob"]);
+    assert_eq!(outline.types[1].location.line, 1);
+
+    let vars: Vec<_> = outline.vars_of("/obj/item")
+        .map(|var| (var.name.as_str(), var.location.line, var.declaration.is_some()))
+        .collect();
+    assert_eq!(vars, [("other", 2, true), ("name", 3, false)]);
+    let decl = outline.vars[0].declaration.as_ref().unwrap();
+    assert_eq!(&decl.type_path[..], ["obj", "item"]);
+
+    let procs: Vec<_> = outline.procs.iter()
+        .map(|proc| (proc.ty.as_str(), proc.name.as_str(), proc.location.line, proc.declaration))
+        .collect();
+    assert_eq!(procs, [
+        ("/obj/item", "attack", 4, Some(dm::ast::ProcDeclKind::Proc)),
+        ("/obj/item/sword", "attack", 5, None),
+        ("", "helper", 6, Some(dm::ast::ProcDeclKind::Proc)),
+        ("/mob", "look", 7, Some(dm::ast::ProcDeclKind::Verb)),
+    ]);
+    assert_eq!(outline.procs[1].parameters[0].name, "user");
+}
+
+#[test]
+fn outline_uses_supplied_macros() {
+    let code = "
+#ifdef FEATURE
+/obj/feature
+#endif
+/obj/always
+";
+    let without = outline(code, DefineMap::default());
+    assert!(without.types.iter().all(|ty| ty.path != "/obj/feature"));
+
+    let mut defines = DefineMap::default();
+    defines.insert("FEATURE".to_owned(), (Default::default(), dm::preprocessor::Define::Constant {
+        subst: Vec::new(),
+        docs: Default::default(),
+    }));
+    let with = outline(code, defines);
+    assert!(with.types.iter().any(|ty| ty.path == "/obj/feature"));
+}
+
+#[test]
+fn outline_syntax_errors() {
+    let outline = outline("
+/obj/item
+    var/x = (
+/obj/other
+", DefineMap::default());
+    assert!(!outline.errors.is_empty());
+    assert!(outline.types.iter().any(|ty| ty.path == "/obj/item"));
+}
+
+#[test]
+fn outline_leaves_context_clean() {
+    let context = dm::Context::default();
+    let outline = parse_file(&context, "broken.dm".as_ref(), "/obj/item\n    var/x = (\n", DefineMap::default());
+    assert!(!outline.errors.is_empty());
+    assert!(context.errors().is_empty());
+}
@@ -0,0 +1,42 @@
+extern crate dreammaker as dm;
+
+use std::io::Cursor;
+
+use dm::preprocessor::Preprocessor;
+use dm::*;
+
+#[test]
+fn overlay_replaces_one_file() {
+    let context = Context::default();
+    let mut preprocessor = Preprocessor::from_buffer(&context, "b.dm".into(), "/obj/thing\n\tvar/health = 10\n\tproc/heal()\n");
+    let a = preprocessor.push_file("a.dm".into(), Cursor::new("/obj/thing\n\tvar/armor = 1\n\tproc/defend()\n")).unwrap();
+    let tree = parser::Parser::new(&context, indents::IndentProcessor::new(&context, &mut preprocessor)).parse_object_tree();
+
+    let code = "/obj/thing\n\tvar/shield = 2\n\thealth = 20\n/obj/other\n";
+    let lexer = lexer::Lexer::new(&context, a, code.as_bytes());
+    let overlay = parser::Parser::new(&context, indents::IndentProcessor::new(&context, lexer))
+        .parse_object_tree_overlay(&tree, a);
+    context.assert_success();
+
+    // The original tree is left alone.
+    let thing = tree.expect("/obj/thing");
+    assert!(thing.get().vars.contains_key("armor"));
+    assert!(tree.find("/obj/other").is_none());
+
+    let thing = overlay.expect("/obj/thing");
+    assert!(!thing.get().vars.contains_key("armor"));
+    assert!(!thing.get().procs.contains_key("defend"));
+    assert!(thing.get().procs.contains_key("heal"));
+    assert_eq!(thing.get().vars["health"].value.location.file, a);
+    assert!(overlay.find("/obj/other").is_some());
+    assert_eq!(overlay.expect("/obj/other").parent_type().unwrap().path, "/obj");
+
+    // New declarations don't reuse the ids of the rest of the tree.
+    let shield = thing.get().vars["shield"].declaration.as_ref().unwrap().id;
+    for ty in tree.iter_types() {
+        assert_ne!(ty.id, shield);
+        for var in ty.vars.values() {
+            assert_ne!(var.declaration.as_ref().map(|decl| decl.id), Some(shield));
+        }
+    }
+}
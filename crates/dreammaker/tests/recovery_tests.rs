@@ -0,0 +1,81 @@
+extern crate dreammaker as dm;
+
+use dm::ast::*;
+use dm::objtree::ObjectTree;
+use dm::preprocessor::Preprocessor;
+use dm::*;
+
+fn parse(context: &Context, code: &'static str) -> ObjectTree {
+    let preprocessor = Preprocessor::from_buffer(context, "recovery.dm".into(), code.trim());
+    let mut parser = parser::Parser::new(context, indents::IndentProcessor::new(context, preprocessor));
+    parser.enable_procs();
+    parser.parse_object_tree()
+}
+
+fn error_lines(context: &Context) -> Vec<u32> {
+    context.errors().iter()
+        .filter(|error| error.severity() == Severity::Error)
+        .map(|error| error.location().line)
+        .collect()
+}
+
+#[test]
+fn tree_entries_after_errors() {
+    let context = Context::default();
+    let tree = parse(&context, r#"
+/obj/first
+    var/health = 10
+/obj/broken)
+/obj/second
+    var/armor = 2
+    ) junk
+/obj/third
+"#);
+    assert_eq!(error_lines(&context), vec![3, 6]);
+    assert!(tree.find("/obj/first").is_some());
+    assert!(tree.expect("/obj/second").get().vars.contains_key("armor"));
+    assert!(tree.find("/obj/third").is_some());
+}
+
+#[test]
+fn var_with_broken_value() {
+    let context = Context::default();
+    let tree = parse(&context, r#"
+/obj/thing
+    var/health = 10 +
+    var/armor = ]
+    var/shield = 3
+    name = *
+"#);
+    assert_eq!(error_lines(&context), vec![2, 3, 5]);
+    let thing = tree.expect("/obj/thing");
+    let vars = &thing.get().vars;
+    assert_eq!(vars["health"].value.expression, Some(Expression::from(Term::Error)));
+    assert_eq!(vars["armor"].value.expression, Some(Expression::from(Term::Error)));
+    assert_eq!(vars["shield"].value.constant, Some(dm::constants::Constant::Float(3.0)));
+    assert_eq!(vars["name"].value.expression, Some(Expression::from(Term::Error)));
+}
+
+#[test]
+fn statements_after_errors() {
+    let context = Context::default();
+    let tree = parse(&context, r#"
+/proc/test()
+    var/a = 1 +
+    if(a ==)
+        a = 2
+    else
+        a = 3
+    a = )
+    return a
+
+/proc/after()
+    return
+"#);
+    assert_eq!(error_lines(&context), vec![2, 3, 7]);
+    let code = tree.root().get_proc("test").unwrap().get().code.as_ref().unwrap();
+    assert_eq!(code.len(), 2);
+    assert!(matches!(&code[0].elem, Statement::Var(var) if var.value == Some(Expression::from(Term::Error))));
+    assert!(matches!(code[1].elem, Statement::Return(Some(_))));
+    assert!(tree.root().get_proc("after").is_some());
+}